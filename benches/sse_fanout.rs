@@ -0,0 +1,61 @@
+//! Cost of fanning one broadcast event out to many SSE subscribers.
+//!
+//! `per_subscriber` is how streams used to work: the channel carried envelopes and every
+//! stream serialized and framed its own copy. `pre_rendered` renders the frame once before
+//! sending ([`BroadcastEvent`]) and each stream forwards a shared `Bytes`.
+//!
+//! Run with `cargo bench --bench sse_fanout`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use medhealth_backend::models::{AlertLevel, EventEnvelope, MlAlert, SseEvent};
+use medhealth_backend::sse::{sse_frame, BroadcastEvent};
+use serde_json::json;
+use std::hint::black_box;
+use tokio::sync::broadcast;
+
+fn alert() -> EventEnvelope {
+    EventEnvelope::new(SseEvent::Alert(MlAlert {
+        level: AlertLevel::High,
+        message: "Abnormal vital signs detected. Medical review recommended.".to_string(),
+        details: json!({
+            "anomalies": ["Tachycardia", "Low SpO2"],
+            "anomaly_score": 0.91,
+            "vitals": {"heart_rate": 128, "spo2": 89, "temperature": 38.1},
+        }),
+    }))
+}
+
+fn fanout(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sse_fanout");
+    for subscribers in [10, 100, 1000] {
+        group.throughput(Throughput::Elements(subscribers as u64));
+
+        group.bench_with_input(BenchmarkId::new("per_subscriber", subscribers), &subscribers, |b, &n| {
+            let (tx, _) = broadcast::channel::<EventEnvelope>(16);
+            let mut receivers: Vec<_> = (0..n).map(|_| tx.subscribe()).collect();
+            b.iter(|| {
+                tx.send(alert()).unwrap();
+                for rx in &mut receivers {
+                    let envelope = rx.try_recv().unwrap();
+                    black_box(sse_frame(&envelope).unwrap());
+                }
+            });
+        });
+
+        group.bench_with_input(BenchmarkId::new("pre_rendered", subscribers), &subscribers, |b, &n| {
+            let (tx, _) = broadcast::channel::<BroadcastEvent>(16);
+            let mut receivers: Vec<_> = (0..n).map(|_| tx.subscribe()).collect();
+            b.iter(|| {
+                tx.send(BroadcastEvent::new(alert()).unwrap()).unwrap();
+                for rx in &mut receivers {
+                    let event = rx.try_recv().unwrap();
+                    black_box(event.frame());
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, fanout);
+criterion_main!(benches);
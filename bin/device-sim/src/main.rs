@@ -0,0 +1,215 @@
+//! Emulates a fleet of walkers sending correctly signed readings to a MedHealth server.
+//!
+//! Each walker runs a clinical profile (healthy, COPD, arrhythmia) and uploads through
+//! `medhealth-client` at the firmware's rate. A fixed `--seed` replays the same readings,
+//! which makes an alert scenario reproducible. The walkers must exist in `devices`;
+//! `--print-sql` prints the statements that register them.
+
+mod profile;
+
+use medhealth_client::{ClientError, DeviceClient, RetryPolicy};
+use profile::{Profile, Walker};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const USAGE: &str = "usage: device-sim --target <base URL> [--devices <n>] [--profile healthy,copd,arrhythmia] \
+[--interval-ms <ms>] [--duration-secs <s>] [--prefix <device id prefix>] [--seed <n>] [--print-sql]
+The device secret is read from DEVICE_SECRET.";
+
+/// Firmware uploads a reading every 2 seconds
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq)]
+struct SimArgs {
+    target: String,
+    devices: usize,
+    /// Assigned to walkers round-robin
+    profiles: Vec<Profile>,
+    interval: Duration,
+    /// Run until interrupted when unset
+    duration: Option<Duration>,
+    prefix: String,
+    seed: Option<u64>,
+    print_sql: bool,
+}
+
+impl SimArgs {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut parsed = SimArgs {
+            target: String::new(),
+            devices: 1,
+            profiles: vec![Profile::Healthy],
+            interval: DEFAULT_INTERVAL,
+            duration: None,
+            prefix: "SIM-WALKER".to_string(),
+            seed: None,
+            print_sql: false,
+        };
+
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().cloned().ok_or_else(|| format!("{} needs a value", flag));
+            let number = |flag: &str, value: String| value.parse::<u64>().map_err(|_| format!("{}: '{}' is not a number", flag, value));
+            match flag.as_str() {
+                "--target" => parsed.target = value()?.trim_end_matches('/').to_string(),
+                "--devices" => parsed.devices = number(flag, value()?)? as usize,
+                "--profile" => parsed.profiles = value()?.split(',').map(Profile::parse).collect::<Result<_, _>>()?,
+                "--interval-ms" => parsed.interval = Duration::from_millis(number(flag, value()?)?),
+                "--duration-secs" => parsed.duration = Some(Duration::from_secs(number(flag, value()?)?)),
+                "--prefix" => parsed.prefix = value()?,
+                "--seed" => parsed.seed = Some(number(flag, value()?)?),
+                "--print-sql" => parsed.print_sql = true,
+                other => return Err(format!("unknown argument '{}'", other)),
+            }
+        }
+
+        if parsed.devices == 0 {
+            return Err("--devices must be at least 1".to_string());
+        }
+        if parsed.interval.is_zero() {
+            return Err("--interval-ms must be at least 1".to_string());
+        }
+        if !parsed.print_sql && !parsed.target.starts_with("http://") && !parsed.target.starts_with("https://") {
+            return Err("--target must be an http(s) URL".to_string());
+        }
+        Ok(parsed)
+    }
+
+    fn device_id(&self, index: usize) -> String {
+        format!("{}-{:03}", self.prefix, index + 1)
+    }
+
+    fn profile(&self, index: usize) -> Profile {
+        self.profiles[index % self.profiles.len()]
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    sent: AtomicU64,
+    rejected: AtomicU64,
+    failed: AtomicU64,
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args = match SimArgs::parse(&args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if args.print_sql {
+        for index in 0..args.devices {
+            println!(
+                "INSERT INTO devices (device_id, device_name, secret_hash) VALUES ('{}', 'Simulated walker ({})', '') ON CONFLICT (device_id) DO NOTHING;",
+                args.device_id(index),
+                args.profile(index).name()
+            );
+        }
+        return;
+    }
+
+    let Ok(secret) = std::env::var("DEVICE_SECRET") else {
+        eprintln!("DEVICE_SECRET must be set to the server's device.secret");
+        std::process::exit(2);
+    };
+
+    let seed = args.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    println!(
+        "Simulating {} walker(s) against {} every {:?} (seed {})",
+        args.devices, args.target, args.interval, seed
+    );
+
+    let counters = Arc::new(Counters::default());
+    let mut tasks = Vec::with_capacity(args.devices);
+    for index in 0..args.devices {
+        let client = DeviceClient::new(&args.target, args.device_id(index), &secret).with_retry(RetryPolicy::default());
+        let walker = Walker::new(args.profile(index), StdRng::seed_from_u64(seed.wrapping_add(index as u64)));
+        // Spread the first uploads over one interval so walkers don't send in lockstep
+        let offset = args.interval.mul_f64(index as f64 / args.devices as f64);
+        tasks.push(tokio::spawn(run_walker(client, walker, args.interval, offset, counters.clone())));
+    }
+
+    match args.duration {
+        Some(duration) => tokio::time::sleep(duration).await,
+        None => {
+            let _ = tokio::signal::ctrl_c().await;
+        }
+    }
+    for task in &tasks {
+        task.abort();
+    }
+
+    println!(
+        "sent {}, rejected {}, failed {}",
+        counters.sent.load(Ordering::Relaxed),
+        counters.rejected.load(Ordering::Relaxed),
+        counters.failed.load(Ordering::Relaxed)
+    );
+}
+
+async fn run_walker(client: DeviceClient, mut walker: Walker, interval: Duration, offset: Duration, counters: Arc<Counters>) {
+    tokio::time::sleep(offset).await;
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+        let reading = walker.next_reading(chrono::Utc::now().timestamp());
+        match client.send_vitals(&reading).await {
+            Ok(_) => {
+                counters.sent.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e @ ClientError::Status { .. }) => {
+                counters.rejected.fetch_add(1, Ordering::Relaxed);
+                eprintln!("{} ({}): {}", client.device_id(), walker.profile().name(), e);
+            }
+            Err(e) => {
+                counters.failed.fetch_add(1, Ordering::Relaxed);
+                eprintln!("{} ({}): {}", client.device_id(), walker.profile().name(), e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_sim_args() {
+        let parsed = SimArgs::parse(&args(&[
+            "--target", "http://localhost:8080/",
+            "--devices", "5",
+            "--profile", "copd,arrhythmia",
+            "--interval-ms", "500",
+            "--seed", "9",
+        ]))
+        .unwrap();
+
+        assert_eq!(parsed.target, "http://localhost:8080");
+        assert_eq!(parsed.interval, Duration::from_millis(500));
+        assert_eq!(parsed.device_id(4), "SIM-WALKER-005");
+        assert_eq!(parsed.profile(0), Profile::Copd);
+        assert_eq!(parsed.profile(3), Profile::Arrhythmia);
+    }
+
+    #[test]
+    fn test_rejects_bad_sim_args() {
+        assert!(SimArgs::parse(&args(&["--devices", "2"])).is_err());
+        assert!(SimArgs::parse(&args(&["--target", "http://x", "--devices", "0"])).is_err());
+        assert!(SimArgs::parse(&args(&["--target", "http://x", "--profile", "asthma"])).is_err());
+        assert!(SimArgs::parse(&args(&["--print-sql", "--devices", "3"])).unwrap().print_sql);
+    }
+}
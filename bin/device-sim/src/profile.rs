@@ -0,0 +1,205 @@
+//! Clinical profiles: how a simulated patient's vitals drift from reading to reading.
+//!
+//! Each walker keeps its own baseline and mean-reverting noise, and some profiles have
+//! episodes (desaturations, arrhythmic runs) that should trip the server's alerts.
+
+use medhealth_client::VitalsReading;
+use rand::rngs::StdRng;
+use rand::Rng;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    /// Stable vitals within normal ranges
+    Healthy,
+    /// Low baseline SpO2 with desaturation episodes, especially while walking
+    Copd,
+    /// Normal baseline with runs of irregular tachycardia and occasional bradycardia
+    Arrhythmia,
+}
+
+impl Profile {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "healthy" => Ok(Profile::Healthy),
+            "copd" => Ok(Profile::Copd),
+            "arrhythmia" => Ok(Profile::Arrhythmia),
+            other => Err(format!("unknown profile '{}' (healthy, copd, arrhythmia)", other)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Profile::Healthy => "healthy",
+            Profile::Copd => "copd",
+            Profile::Arrhythmia => "arrhythmia",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Episode {
+    Desaturation { target_spo2: f32 },
+    Tachycardia,
+    Bradycardia,
+}
+
+/// One simulated patient's physiology
+pub struct Walker {
+    profile: Profile,
+    rng: StdRng,
+    base_hr: f32,
+    base_spo2: f32,
+    base_temp: f32,
+    hr: f32,
+    spo2: f32,
+    temp: f32,
+    /// Readings left in the current walking bout
+    walking: u32,
+    episode: Option<(Episode, u32)>,
+}
+
+impl Walker {
+    pub fn new(profile: Profile, mut rng: StdRng) -> Self {
+        let (base_hr, base_spo2) = match profile {
+            Profile::Healthy => (rng.gen_range(62.0..80.0), rng.gen_range(96.5..98.5)),
+            Profile::Copd => (rng.gen_range(82.0..94.0), rng.gen_range(90.0..93.0)),
+            Profile::Arrhythmia => (rng.gen_range(68.0..82.0), rng.gen_range(95.5..98.0)),
+        };
+        let base_temp = rng.gen_range(36.4..36.9);
+        Self {
+            profile,
+            rng,
+            base_hr,
+            base_spo2,
+            base_temp,
+            hr: base_hr,
+            spo2: base_spo2,
+            temp: base_temp,
+            walking: 0,
+            episode: None,
+        }
+    }
+
+    pub fn profile(&self) -> Profile {
+        self.profile
+    }
+
+    /// The next reading, taken at `timestamp` (Unix seconds)
+    pub fn next_reading(&mut self, timestamp: i64) -> VitalsReading {
+        self.step_activity();
+        self.step_episode();
+
+        let walking = self.walking > 0;
+        let (mut hr_target, mut spo2_target) = (self.base_hr, self.base_spo2);
+        if walking {
+            hr_target += 18.0;
+            // Exertion costs COPD patients far more oxygen
+            spo2_target -= if self.profile == Profile::Copd { 3.0 } else { 0.5 };
+        }
+
+        match self.episode {
+            Some((Episode::Desaturation { target_spo2 }, _)) => {
+                spo2_target = target_spo2;
+                hr_target += 15.0;
+            }
+            // Irregular: each beat-to-beat average is drawn afresh rather than drifting
+            Some((Episode::Tachycardia, _)) => self.hr = self.rng.gen_range(125.0..175.0),
+            Some((Episode::Bradycardia, _)) => self.hr = self.rng.gen_range(36.0..46.0),
+            None => {}
+        }
+        if !matches!(self.episode, Some((Episode::Tachycardia | Episode::Bradycardia, _))) {
+            self.hr += (hr_target - self.hr) * 0.25 + self.noise(2.5);
+        }
+        self.spo2 += (spo2_target - self.spo2) * 0.3 + self.noise(0.6);
+        self.temp += (self.base_temp - self.temp) * 0.1 + self.noise(0.03);
+
+        let mut reading = VitalsReading::new(
+            self.hr.round().clamp(30.0, 220.0) as i32,
+            self.spo2.round().clamp(70.0, 100.0) as i32,
+            (self.temp * 10.0).round() / 10.0,
+            timestamp,
+        );
+        if walking {
+            reading.steps = Some(self.rng.gen_range(2..5));
+            reading.motion = Some(self.rng.gen_range(0.15..0.4));
+        } else {
+            reading.steps = Some(0);
+            reading.motion = Some(self.rng.gen_range(0.0..0.03));
+        }
+        reading
+    }
+
+    fn noise(&mut self, amplitude: f32) -> f32 {
+        self.rng.gen_range(-amplitude..amplitude)
+    }
+
+    fn step_activity(&mut self) {
+        if self.walking > 0 {
+            self.walking -= 1;
+        } else if self.rng.gen_bool(0.005) {
+            self.walking = self.rng.gen_range(30..150);
+        }
+    }
+
+    fn step_episode(&mut self) {
+        if let Some((episode, left)) = self.episode {
+            self.episode = (left > 1).then_some((episode, left - 1));
+            return;
+        }
+        let (chance, episode, length) = match self.profile {
+            Profile::Healthy => return,
+            Profile::Copd => {
+                let chance = if self.walking > 0 { 0.01 } else { 0.002 };
+                (chance, Episode::Desaturation { target_spo2: self.rng.gen_range(82.0..88.0) }, self.rng.gen_range(15..45))
+            }
+            Profile::Arrhythmia if self.rng.gen_bool(0.2) => (0.01, Episode::Bradycardia, self.rng.gen_range(5..15)),
+            Profile::Arrhythmia => (0.01, Episode::Tachycardia, self.rng.gen_range(10..30)),
+        };
+        if self.rng.gen_bool(chance) {
+            self.episode = Some((episode, length));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn run(profile: Profile, seed: u64, readings: usize) -> Vec<VitalsReading> {
+        let mut walker = Walker::new(profile, StdRng::seed_from_u64(seed));
+        (0..readings).map(|i| walker.next_reading(1_700_000_000 + 2 * i as i64)).collect()
+    }
+
+    #[test]
+    fn test_healthy_stays_in_normal_ranges() {
+        for reading in run(Profile::Healthy, 7, 5000) {
+            assert!((50..=120).contains(&reading.heart_rate), "{:?}", reading);
+            assert!(reading.spo2 >= 94, "{:?}", reading);
+            assert!((36.0..=37.5).contains(&reading.temperature), "{:?}", reading);
+        }
+    }
+
+    #[test]
+    fn test_episodic_profiles_reach_alert_ranges() {
+        let copd = run(Profile::Copd, 7, 5000);
+        assert!(copd.iter().any(|r| r.spo2 < 88));
+        assert!(copd.iter().filter(|r| r.spo2 >= 88).count() > copd.len() / 2);
+
+        let arrhythmia = run(Profile::Arrhythmia, 7, 5000);
+        assert!(arrhythmia.iter().any(|r| r.heart_rate > 130));
+        assert!(arrhythmia.iter().all(|r| r.spo2 >= 90));
+    }
+
+    #[test]
+    fn test_same_seed_replays_the_same_scenario() {
+        assert_eq!(run(Profile::Arrhythmia, 42, 500), run(Profile::Arrhythmia, 42, 500));
+        assert_ne!(run(Profile::Arrhythmia, 42, 500), run(Profile::Arrhythmia, 43, 500));
+    }
+
+    #[test]
+    fn test_parse_profiles() {
+        assert_eq!(Profile::parse(" COPD").unwrap(), Profile::Copd);
+        assert!(Profile::parse("asthma").is_err());
+    }
+}
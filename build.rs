@@ -0,0 +1,47 @@
+//! Build metadata embedded in the binary, served by `GET /version` and used as the Sentry
+//! release name:
+//!
+//! - `BUILD_GIT_SHA`: the commit being built, from `GIT_SHA` (Docker builds without `.git`)
+//!   or git
+//! - `BUILD_TIMESTAMP`: Unix seconds, from `SOURCE_DATE_EPOCH` for reproducible builds
+//! - `BUILD_FEATURES`: enabled cargo features, comma separated
+//! - `BUILD_PROFILE` and `BUILD_RUSTC`: cargo profile and compiler version
+
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=GIT_SHA");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+
+    let sha = std::env::var("GIT_SHA")
+        .ok()
+        .filter(|sha| !sha.is_empty())
+        .or_else(|| command_output("git", &["rev-parse", "--short=12", "HEAD"]));
+    if let Some(sha) = sha {
+        println!("cargo:rustc-env=BUILD_GIT_SHA={}", sha);
+    }
+
+    let timestamp = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<u64>().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0));
+    println!("cargo:rustc-env=BUILD_TIMESTAMP={}", timestamp);
+
+    let mut features: Vec<String> = std::env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(|name| name.to_lowercase().replace('_', "-")))
+        .collect();
+    features.sort();
+    println!("cargo:rustc-env=BUILD_FEATURES={}", features.join(","));
+
+    println!("cargo:rustc-env=BUILD_PROFILE={}", std::env::var("PROFILE").unwrap_or_default());
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    println!("cargo:rustc-env=BUILD_RUSTC={}", command_output(&rustc, &["--version"]).unwrap_or_default());
+}
@@ -0,0 +1,227 @@
+//! Typed client for the MedHealth backend.
+//!
+//! [`Client`] calls the JWT-authenticated REST API and opens the SSE event stream;
+//! [`DeviceClient`] uploads signed readings the way walker firmware does. Both retry
+//! requests the server did not act on, per their [`RetryPolicy`].
+
+pub mod error;
+pub mod models;
+pub mod retry;
+pub mod signing;
+pub mod sse;
+pub mod webhooks;
+
+pub use error::ClientError;
+pub use models::*;
+pub use retry::RetryPolicy;
+pub use sse::EventStream;
+
+use reqwest::{header, Response};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use uuid::Uuid;
+
+/// A user's session with the REST API
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+    retry: RetryPolicy,
+}
+
+impl Client {
+    /// `base_url` is the server root, e.g. `http://localhost:8080`
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            token: None,
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Create an account and keep its token for later calls
+    pub async fn signup(&mut self, email: &str, password: &str) -> Result<AuthResponse, ClientError> {
+        self.authenticate("/auth/signup", email, password).await
+    }
+
+    /// Log in and keep the token for later calls
+    pub async fn login(&mut self, email: &str, password: &str) -> Result<AuthResponse, ClientError> {
+        self.authenticate("/auth/login", email, password).await
+    }
+
+    async fn authenticate(&mut self, path: &str, email: &str, password: &str) -> Result<AuthResponse, ClientError> {
+        let auth: AuthResponse = self
+            .post(path, &serde_json::json!({"email": email, "password": password}))
+            .await?;
+        self.token = Some(auth.token.clone());
+        Ok(auth)
+    }
+
+    pub async fn latest_vitals(&self) -> Result<LatestVitals, ClientError> {
+        self.get("/api/vitals/latest").await
+    }
+
+    /// JSON Schema for a payload published under `/api/schemas`
+    pub async fn schema(&self, name: &str) -> Result<serde_json::Value, ClientError> {
+        self.get(&format!("/api/schemas/{}", name)).await
+    }
+
+    /// Deliveries to webhook `webhook` (e.g. `contact`) numbered after `after_sequence`, oldest first
+    pub async fn webhook_deliveries(&self, webhook: &str, after_sequence: u64) -> Result<Vec<WebhookDelivery>, ClientError> {
+        self.get(&format!("/api/webhooks/{}/deliveries?after_sequence={}", webhook, after_sequence)).await
+    }
+
+    /// Post a recorded webhook event again under its original id and sequence
+    pub async fn redeliver_webhook(&self, webhook: &str, event_id: Uuid) -> Result<WebhookDelivery, ClientError> {
+        self.post(&format!("/api/webhooks/{}/redeliver/{}", webhook, event_id), &serde_json::json!({})).await
+    }
+
+    /// GET any JSON endpoint, e.g. `/api/patients`
+    pub async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, ClientError> {
+        let url = self.url(path);
+        let resp = self.retry.send(|| self.authorize(self.http.get(&url))).await?;
+        json_response(resp).await
+    }
+
+    /// POST a JSON body to any endpoint
+    pub async fn post<B: Serialize, T: DeserializeOwned>(&self, path: &str, body: &B) -> Result<T, ClientError> {
+        let url = self.url(path);
+        let resp = self.retry.send(|| self.authorize(self.http.post(&url)).json(body)).await?;
+        json_response(resp).await
+    }
+
+    /// PUT a JSON body to any endpoint
+    pub async fn put<B: Serialize, T: DeserializeOwned>(&self, path: &str, body: &B) -> Result<T, ClientError> {
+        let url = self.url(path);
+        let resp = self.retry.send(|| self.authorize(self.http.put(&url)).json(body)).await?;
+        json_response(resp).await
+    }
+
+    /// Open the live event stream; the server has subscribed once this returns
+    pub async fn events(&self) -> Result<EventStream, ClientError> {
+        let mut request = self.http.get(self.url("/api/stream/vitals"));
+        if let Some(token) = &self.token {
+            request = request.query(&[("token", token)]);
+        }
+        let resp = request.header(header::ACCEPT, "text/event-stream").send().await?;
+        if !resp.status().is_success() {
+            return Err(ClientError::from_response(resp).await);
+        }
+        let content_type = resp.headers().get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or_default();
+        if !content_type.starts_with("text/event-stream") {
+            return Err(ClientError::Status {
+                status: resp.status(),
+                message: format!("expected an event stream, got '{}'", content_type),
+            });
+        }
+        Ok(EventStream::new(resp))
+    }
+
+    /// A device client for the same server
+    pub fn device(&self, device_id: impl Into<String>, secret: impl Into<String>) -> DeviceClient {
+        DeviceClient {
+            http: self.http.clone(),
+            base_url: self.base_url.clone(),
+            device_id: device_id.into(),
+            secret: secret.into(),
+            retry: self.retry,
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+}
+
+/// Uploads from one walker, HMAC-signed with its secret
+#[derive(Debug, Clone)]
+pub struct DeviceClient {
+    http: reqwest::Client,
+    base_url: String,
+    device_id: String,
+    secret: String,
+    retry: RetryPolicy,
+}
+
+impl DeviceClient {
+    pub fn new(base_url: impl Into<String>, device_id: impl Into<String>, secret: impl Into<String>) -> Self {
+        Client::new(base_url).device(device_id, secret)
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn device_id(&self) -> &str {
+        &self.device_id
+    }
+
+    pub async fn send_vitals(&self, reading: &VitalsReading) -> Result<IngestAck, ClientError> {
+        json_response(self.post_signed("/api/device/vitals", reading).await?).await
+    }
+
+    /// Check a reading against the server's signing, validation and alert rules without storing it
+    pub async fn dry_run_vitals(&self, reading: &VitalsReading) -> Result<IngestDryRun, ClientError> {
+        json_response(self.post_signed("/api/device/vitals?dry_run=true", reading).await?).await
+    }
+
+    pub async fn send_event(&self, event: &DeviceEvent) -> Result<EventAck, ClientError> {
+        json_response(self.post_signed("/api/device/events", event).await?).await
+    }
+
+    /// POST any body signed as this device, returning the response whatever its status.
+    ///
+    /// Each attempt is signed at send time, so retries stay inside the server's replay window.
+    pub async fn post_signed<B: Serialize + ?Sized>(&self, path: &str, body: &B) -> Result<Response, ClientError> {
+        let url = format!("{}{}", self.base_url, path);
+        let payload = serde_json::to_string(body)?;
+        let resp = self
+            .retry
+            .send(|| {
+                let timestamp = chrono::Utc::now().timestamp();
+                let mut request = self.http.post(&url).header(header::CONTENT_TYPE, "application/json");
+                for (name, value) in signing::signed_headers(&self.device_id, &self.secret, timestamp, &payload) {
+                    request = request.header(name, value);
+                }
+                request.body(payload.clone())
+            })
+            .await?;
+        Ok(resp)
+    }
+}
+
+async fn json_response<T: DeserializeOwned>(resp: Response) -> Result<T, ClientError> {
+    if !resp.status().is_success() {
+        return Err(ClientError::from_response(resp).await);
+    }
+    let bytes = resp.bytes().await?;
+    Ok(serde_json::from_slice(&bytes)?)
+}
@@ -0,0 +1,240 @@
+//! Wire types for the endpoints the client wraps. Field names follow the server's JSON,
+//! including the walker firmware's camelCase vitals.
+
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// ============ Auth Models ============
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthResponse {
+    pub token: String,
+    pub refresh_token: String,
+    pub user: User,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct User {
+    pub id: Uuid,
+    pub email: String,
+    pub role: String,
+}
+
+// ============ Device Models ============
+
+/// One reading as the walker uploads it to `/api/device/vitals`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VitalsReading {
+    pub heart_rate: i32,
+    pub spo2: i32,
+    pub temperature: f32,
+    /// Unix seconds when the reading was taken
+    pub timestamp: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub steps: Option<i32>,
+    /// Mean acceleration above gravity since the previous reading, in g
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motion: Option<f32>,
+    /// Barometric height change since the previous reading, in metres
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elevation_change: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ambient_temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub humidity: Option<f32>,
+}
+
+impl VitalsReading {
+    pub fn new(heart_rate: i32, spo2: i32, temperature: f32, timestamp: i64) -> Self {
+        Self {
+            heart_rate,
+            spo2,
+            temperature,
+            timestamp,
+            steps: None,
+            motion: None,
+            elevation_change: None,
+            ambient_temperature: None,
+            humidity: None,
+        }
+    }
+}
+
+/// A discrete walker event for `/api/device/events`: `sos`, `near_fall` or `stumble`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceEvent {
+    pub event_type: String,
+    pub timestamp: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IngestAck {
+    pub status: String,
+    pub reading_id: i64,
+}
+
+/// What the server would do with a reading sent with `?dry_run=true`; nothing was stored
+#[derive(Debug, Clone, Deserialize)]
+pub struct IngestDryRun {
+    pub status: String,
+    /// False when the organization's storage quota would refuse the reading
+    pub would_accept: bool,
+    /// `within`, `near`, `exceeded` or `rejecting`
+    pub quota: String,
+    pub device_id: String,
+    pub patient_id: Option<Uuid>,
+    /// The metadata that would be stored with the reading
+    pub metadata: serde_json::Value,
+    pub analysis: DryRunAnalysis,
+    /// The alert that would be broadcast, if any
+    pub alert: Option<MlAlert>,
+    pub vitals: LatestVitals,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DryRunAnalysis {
+    pub anomaly_detected: bool,
+    pub anomaly_score: f32,
+    pub classification: String,
+    pub alert_level: String,
+    pub quality_score: f32,
+    pub details: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EventAck {
+    pub status: String,
+    #[serde(default)]
+    pub event_id: Option<Uuid>,
+    #[serde(default)]
+    pub alert_id: Option<Uuid>,
+}
+
+// ============ Vitals & Alert Models ============
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatestVitals {
+    #[serde(rename = "heartRate")]
+    pub heart_rate: i32,
+    pub spo2: i32,
+    pub temperature: f32,
+    pub timestamp: i64,
+    pub quality_score: Option<f32>,
+    pub ml_alert: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MlAlert {
+    pub level: String,
+    pub message: String,
+    pub details: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MedicationReminder {
+    pub dose_id: Uuid,
+    pub medication_id: Uuid,
+    pub patient_id: Uuid,
+    pub name: String,
+    pub dosage: String,
+    pub scheduled_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertMessage {
+    pub id: Uuid,
+    pub alert_id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub author_id: Option<Uuid>,
+    pub author_email: Option<String>,
+    pub author_role: Option<String>,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An alert acknowledged or resolved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertStatusChange {
+    pub alert_id: Uuid,
+    pub patient_id: Option<Uuid>,
+    pub kind: String,
+    pub level: String,
+    /// `open`, `acknowledged` or `resolved`
+    pub status: String,
+    pub changed_by: Option<Uuid>,
+    pub changed_at: DateTime<Utc>,
+}
+
+// ============ Event Models ============
+
+/// A versioned event envelope, as carried by SSE frames and webhooks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub version: u32,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub id: Uuid,
+    pub occurred_at: DateTime<Utc>,
+    #[serde(default)]
+    pub data: serde_json::Value,
+}
+
+/// An event's payload decoded by type; types this client predates are `Other`
+#[derive(Debug, Clone)]
+pub enum EventKind {
+    Vitals(LatestVitals),
+    Alert(MlAlert),
+    Heartbeat { timestamp: i64 },
+    Reminder(MedicationReminder),
+    AlertMessage(AlertMessage),
+    AlertStatus(AlertStatusChange),
+    Other,
+}
+
+#[derive(Deserialize)]
+struct Heartbeat {
+    timestamp: i64,
+}
+
+impl Event {
+    /// Decode `data` into a caller-chosen type
+    pub fn data_as<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        T::deserialize(&self.data)
+    }
+
+    pub fn kind(&self) -> Result<EventKind, serde_json::Error> {
+        Ok(match self.event_type.as_str() {
+            "vitals" => EventKind::Vitals(self.data_as()?),
+            "alert" => EventKind::Alert(self.data_as()?),
+            "heartbeat" => EventKind::Heartbeat { timestamp: self.data_as::<Heartbeat>()?.timestamp },
+            "reminder" => EventKind::Reminder(self.data_as()?),
+            "alert_message" => EventKind::AlertMessage(self.data_as()?),
+            "alert_status" => EventKind::AlertStatus(self.data_as()?),
+            _ => EventKind::Other,
+        })
+    }
+}
+
+// ============ Webhook Models ============
+
+/// One event posted to an outgoing webhook, as listed by `/api/webhooks/{id}/deliveries`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub event_id: Uuid,
+    pub webhook: String,
+    pub sequence: u64,
+    pub event_type: String,
+    /// `pending`, `delivered` or `failed`, as of the latest attempt
+    pub status: String,
+    pub attempts: u32,
+    pub response_status: Option<u16>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
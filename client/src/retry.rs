@@ -0,0 +1,125 @@
+//! Retrying requests that the server did not act on.
+//!
+//! Only failures that guarantee nothing was processed are retried: connection errors,
+//! `429 Too Many Requests` and `503 Service Unavailable`. A 500 or a timeout may have
+//! stored a reading already, so those are returned to the caller.
+
+use reqwest::{header, RequestBuilder, Response, StatusCode};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first; 1 disables retrying
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each one after
+    pub base_delay: Duration,
+    /// Upper bound on any single delay, including a server's `Retry-After`
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self { max_attempts: 1, ..Self::default() }
+    }
+
+    /// Delay before retry number `retry` (1-based)
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+
+    /// Send the request built by `build`, rebuilding it for each attempt so signed
+    /// requests get a fresh timestamp
+    pub async fn send<F>(&self, mut build: F) -> Result<Response, reqwest::Error>
+    where
+        F: FnMut() -> RequestBuilder,
+    {
+        let mut attempt = 1;
+        loop {
+            let result = build().send().await;
+            let delay = match &result {
+                _ if attempt >= self.max_attempts => return result,
+                Ok(resp) if is_retryable(resp.status()) => retry_after(resp).unwrap_or_else(|| self.backoff(attempt)),
+                Err(e) if e.is_connect() => self.backoff(attempt),
+                _ => return result,
+            };
+            tokio::time::sleep(delay.min(self.max_delay)).await;
+            attempt += 1;
+        }
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE
+}
+
+/// `Retry-After` in seconds; HTTP-date values fall back to the backoff
+fn retry_after(resp: &Response) -> Option<Duration> {
+    let seconds = resp.headers().get(header::RETRY_AFTER)?.to_str().ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_cap() {
+        let policy = RetryPolicy { max_attempts: 10, base_delay: Duration::from_millis(100), max_delay: Duration::from_secs(1) };
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(3), Duration::from_millis(400));
+        assert_eq!(policy.backoff(8), Duration::from_secs(1));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(1));
+    }
+
+    /// Serve the given raw responses in order, one per connection
+    async fn serve(responses: Vec<&'static str>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for response in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = [0u8; 4096];
+                let _ = socket.read(&mut buf).await;
+                socket.write_all(response.as_bytes()).await.unwrap();
+                let _ = socket.shutdown().await;
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    const BUSY: &str = "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+    const FAILED: &str = "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+    const OK: &str = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}";
+
+    #[tokio::test]
+    async fn test_retries_busy_responses_but_not_failures() {
+        let http = reqwest::Client::new();
+        let policy = RetryPolicy { base_delay: Duration::from_millis(1), ..RetryPolicy::default() };
+
+        let url = serve(vec![BUSY, BUSY, OK]).await;
+        let mut attempts = 0;
+        let resp = policy.send(|| { attempts += 1; http.get(&url) }).await.unwrap();
+        assert_eq!((resp.status(), attempts), (StatusCode::OK, 3));
+
+        let url = serve(vec![FAILED, OK]).await;
+        let resp = policy.send(|| http.get(&url)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let url = serve(vec![BUSY, OK]).await;
+        let resp = RetryPolicy::none().send(|| http.get(&url)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
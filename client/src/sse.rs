@@ -0,0 +1,79 @@
+//! Reading `/api/stream/vitals`.
+
+use crate::error::ClientError;
+use crate::models::Event;
+use reqwest::Response;
+
+/// A live connection to the event stream
+pub struct EventStream {
+    response: Response,
+    buffer: String,
+}
+
+impl EventStream {
+    pub(crate) fn new(response: Response) -> Self {
+        Self { response, buffer: String::new() }
+    }
+
+    /// Next event, or `None` once the server closes the stream.
+    ///
+    /// Cancel-safe: a partly received frame stays buffered, so this can be raced against
+    /// a timeout and called again.
+    pub async fn next(&mut self) -> Result<Option<Event>, ClientError> {
+        loop {
+            if let Some(end) = self.buffer.find("\n\n") {
+                let frame: String = self.buffer.drain(..end + 2).collect();
+                match parse_frame(&frame)? {
+                    Some(event) => return Ok(Some(event)),
+                    None => continue,
+                }
+            }
+            match self.response.chunk().await? {
+                Some(bytes) => self.buffer.push_str(&String::from_utf8_lossy(&bytes)),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// The envelope in a frame's `data:` lines; comment-only frames yield `None`
+pub fn parse_frame(frame: &str) -> Result<Option<Event>, ClientError> {
+    let data: Vec<&str> = frame
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(str::trim_start)
+        .collect();
+    if data.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(&data.join("\n"))?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::EventKind;
+
+    #[test]
+    fn test_parse_frame_decodes_the_envelope() {
+        let frame = "event: vitals\nid: 5f0c6a8e-3a52-4a4e-9a3e-2f7a1f0b9c11\ndata: {\"version\":1,\"type\":\"vitals\",\
+            \"id\":\"5f0c6a8e-3a52-4a4e-9a3e-2f7a1f0b9c11\",\"occurred_at\":\"2026-03-01T02:15:00Z\",\
+            \"data\":{\"heartRate\":72,\"spo2\":97,\"temperature\":36.8,\"timestamp\":1772331300,\
+            \"quality_score\":null,\"ml_alert\":null}}\n\n";
+        let event = parse_frame(frame).unwrap().unwrap();
+        assert_eq!(event.version, 1);
+        assert!(matches!(event.kind().unwrap(), EventKind::Vitals(v) if v.heart_rate == 72));
+
+        assert!(parse_frame(": keepalive\n\n").unwrap().is_none());
+        assert!(parse_frame("data: not json\n\n").is_err());
+    }
+
+    #[test]
+    fn test_unknown_event_types_are_not_errors() {
+        let frame = "data: {\"version\":1,\"type\":\"teleport\",\"id\":\"5f0c6a8e-3a52-4a4e-9a3e-2f7a1f0b9c11\",\
+            \"occurred_at\":\"2026-03-01T02:15:00Z\",\"data\":{\"to\":\"mars\"}}\n\n";
+        let event = parse_frame(frame).unwrap().unwrap();
+        assert!(matches!(event.kind().unwrap(), EventKind::Other));
+        assert_eq!(event.data["to"], "mars");
+    }
+}
@@ -0,0 +1,236 @@
+//! Receiving the server's outgoing webhooks.
+//!
+//! Every delivery is signed over its timestamp, sequence number and body, so a receiver
+//! should, in order:
+//!
+//! 1. [`verify`] the headers and body, which rejects forged, altered and stale deliveries;
+//! 2. pass the result to a [`ReplayGuard`], which spots events already processed, e.g. a
+//!    retry or redelivery after a lost response, and numbers the receiver missed;
+//! 3. ask an admin to redeliver missed events ([`Client::redeliver_webhook`](crate::Client::redeliver_webhook)),
+//!    found with [`Client::webhook_deliveries`](crate::Client::webhook_deliveries).
+//!
+//! Respond 2xx to duplicates too, so the server records the event as delivered.
+
+use crate::models::Event;
+use base64::{engine::general_purpose, Engine as _};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::{HashSet, VecDeque};
+use std::ops::Range;
+use thiserror::Error;
+use uuid::Uuid;
+
+pub const ID_HEADER: &str = "X-Webhook-Id";
+pub const SEQUENCE_HEADER: &str = "X-Webhook-Sequence";
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+/// How far a delivery's timestamp may be from the receiver's clock, as the server assumes
+pub const DEFAULT_TOLERANCE_SECONDS: i64 = 300;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum WebhookError {
+    #[error("missing {0} header")]
+    MissingHeader(&'static str),
+
+    #[error("invalid {0} header")]
+    InvalidHeader(&'static str),
+
+    #[error("signature does not match")]
+    BadSignature,
+
+    /// Outside the tolerance, so possibly a captured delivery played back later
+    #[error("timestamp {timestamp} is {skew}s away from now")]
+    Stale { timestamp: i64, skew: i64 },
+
+    #[error("invalid body: {0}")]
+    Body(String),
+}
+
+/// A delivery whose signature checked out
+#[derive(Debug, Clone)]
+pub struct VerifiedDelivery {
+    pub event: Event,
+    pub sequence: u64,
+    pub timestamp: i64,
+}
+
+/// Base64 HMAC-SHA256 over `"{timestamp}.{sequence}.{body}"`, sent in `X-Webhook-Signature`
+pub fn webhook_signature(secret: &str, timestamp: i64, sequence: u64, body: &str) -> String {
+    general_purpose::STANDARD.encode(mac(secret, timestamp, sequence, body).finalize().into_bytes())
+}
+
+fn mac(secret: &str, timestamp: i64, sequence: u64, body: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("{}.{}.{}", timestamp, sequence, body).as_bytes());
+    mac
+}
+
+/// Check a delivery's signature and timestamp against `now` (Unix seconds) and decode it.
+/// `header` looks a header up by name, so any HTTP framework's request can be passed in.
+pub fn verify<'a>(
+    secret: &str,
+    header: impl Fn(&str) -> Option<&'a str>,
+    body: &str,
+    now: i64,
+    tolerance_seconds: i64,
+) -> Result<VerifiedDelivery, WebhookError> {
+    let required = |name: &'static str| header(name).map(str::trim).ok_or(WebhookError::MissingHeader(name));
+    let timestamp: i64 = required(TIMESTAMP_HEADER)?
+        .parse()
+        .map_err(|_| WebhookError::InvalidHeader(TIMESTAMP_HEADER))?;
+    let sequence: u64 = required(SEQUENCE_HEADER)?
+        .parse()
+        .map_err(|_| WebhookError::InvalidHeader(SEQUENCE_HEADER))?;
+    let id: Uuid = required(ID_HEADER)?.parse().map_err(|_| WebhookError::InvalidHeader(ID_HEADER))?;
+    let signature = general_purpose::STANDARD
+        .decode(required(SIGNATURE_HEADER)?)
+        .map_err(|_| WebhookError::InvalidHeader(SIGNATURE_HEADER))?;
+
+    mac(secret, timestamp, sequence, body)
+        .verify_slice(&signature)
+        .map_err(|_| WebhookError::BadSignature)?;
+    let skew = (now - timestamp).abs();
+    if skew > tolerance_seconds {
+        return Err(WebhookError::Stale { timestamp, skew });
+    }
+
+    let event: Event = serde_json::from_str(body).map_err(|e| WebhookError::Body(e.to_string()))?;
+    if event.id != id {
+        return Err(WebhookError::InvalidHeader(ID_HEADER));
+    }
+    Ok(VerifiedDelivery { event, sequence, timestamp })
+}
+
+/// What a receiver should do with a verified delivery
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Delivery {
+    /// Process it
+    New,
+    /// Process it, and have the sequence numbers in `missing` redelivered
+    NewAfterGap { missing: Range<u64> },
+    /// Already processed; acknowledge without acting again
+    Duplicate,
+}
+
+/// Remembers the most recent event ids and the highest sequence number seen.
+///
+/// It lives in memory, so a receiver running several instances, or one that must survive
+/// restarts, should keep the same two facts in its own store instead.
+#[derive(Debug, Clone)]
+pub struct ReplayGuard {
+    capacity: usize,
+    seen: HashSet<Uuid>,
+    order: VecDeque<Uuid>,
+    highest: u64,
+}
+
+impl ReplayGuard {
+    /// Remember up to `capacity` event ids; older ones are forgotten first
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            seen: HashSet::new(),
+            order: VecDeque::new(),
+            highest: 0,
+        }
+    }
+
+    /// Classify `delivery` and remember it. Redelivered events fill gaps: an unseen id below
+    /// the highest sequence is `New`.
+    pub fn check(&mut self, delivery: &VerifiedDelivery) -> Delivery {
+        let id = delivery.event.id;
+        if !self.seen.insert(id) {
+            return Delivery::Duplicate;
+        }
+        self.order.push_back(id);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+
+        let expected = self.highest + 1;
+        self.highest = self.highest.max(delivery.sequence);
+        // The first delivery a guard sees starts its count, whatever its number
+        if expected > 1 && delivery.sequence > expected {
+            Delivery::NewAfterGap { missing: expected..delivery.sequence }
+        } else {
+            Delivery::New
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "webhook-secret-of-at-least-32-bytes";
+    const NOW: i64 = 1700000000;
+
+    fn body(id: Uuid) -> String {
+        format!(
+            r#"{{"version":1,"id":"{}","occurred_at":"2023-11-14T22:13:20Z","type":"contact_requested","data":{{"channel":"sms"}}}}"#,
+            id
+        )
+    }
+
+    fn headers(id: Uuid, sequence: u64, timestamp: i64, signature: String) -> Vec<(&'static str, String)> {
+        vec![
+            (ID_HEADER, id.to_string()),
+            (SEQUENCE_HEADER, sequence.to_string()),
+            (TIMESTAMP_HEADER, timestamp.to_string()),
+            (SIGNATURE_HEADER, signature),
+        ]
+    }
+
+    fn check(headers: &[(&'static str, String)], body: &str) -> Result<VerifiedDelivery, WebhookError> {
+        let lookup = |name: &str| headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str());
+        verify(SECRET, lookup, body, NOW, DEFAULT_TOLERANCE_SECONDS)
+    }
+
+    fn delivered(sequence: u64) -> VerifiedDelivery {
+        let id = Uuid::from_u128(sequence.into());
+        let body = body(id);
+        check(&headers(id, sequence, NOW, webhook_signature(SECRET, NOW, sequence, &body)), &body).unwrap()
+    }
+
+    #[test]
+    fn test_verify_rejects_forged_altered_and_stale_deliveries() {
+        let id = Uuid::from_u128(7);
+        let body = body(id);
+        let signed = |sequence, timestamp| headers(id, sequence, timestamp, webhook_signature(SECRET, timestamp, sequence, &body));
+
+        let delivery = check(&signed(3, NOW - 10), &body).unwrap();
+        assert_eq!((delivery.event.id, delivery.sequence), (id, 3));
+        assert_eq!(delivery.event.event_type, "contact_requested");
+
+        assert_eq!(check(&signed(3, NOW), &body.replace("sms", "call")).unwrap_err(), WebhookError::BadSignature);
+        let mut resequenced = signed(3, NOW);
+        resequenced[1].1 = "4".into();
+        assert_eq!(check(&resequenced, &body).unwrap_err(), WebhookError::BadSignature);
+        let forged = headers(id, 3, NOW, webhook_signature("another-secret", NOW, 3, &body));
+        assert_eq!(check(&forged, &body).unwrap_err(), WebhookError::BadSignature);
+        assert!(matches!(check(&signed(3, NOW - 600), &body), Err(WebhookError::Stale { skew: 600, .. })));
+        assert_eq!(check(&signed(3, NOW)[1..], &body).unwrap_err(), WebhookError::MissingHeader(ID_HEADER));
+
+        let other = headers(Uuid::from_u128(8), 3, NOW, webhook_signature(SECRET, NOW, 3, &body));
+        assert_eq!(check(&other, &body).unwrap_err(), WebhookError::InvalidHeader(ID_HEADER));
+    }
+
+    #[test]
+    fn test_replay_guard_spots_duplicates_and_gaps() {
+        let mut guard = ReplayGuard::new(2);
+        let (first, second, fifth) = (delivered(1), delivered(2), delivered(5));
+
+        assert_eq!(guard.check(&first), Delivery::New);
+        assert_eq!(guard.check(&first), Delivery::Duplicate);
+        assert_eq!(guard.check(&second), Delivery::New);
+        assert_eq!(guard.check(&fifth), Delivery::NewAfterGap { missing: 3..5 });
+
+        // A redelivered event fills the gap without reporting a new one
+        assert_eq!(guard.check(&delivered(3)), Delivery::New);
+        // Only the most recent ids are remembered
+        assert_eq!(guard.check(&first), Delivery::New);
+    }
+}
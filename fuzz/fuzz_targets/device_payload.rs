@@ -0,0 +1,48 @@
+//! Device request bodies: deserialize, validate and, when valid, analyze exactly as
+//! `/api/device/vitals` and `/api/device/events` do.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use medhealth_backend::config::MlConfig;
+use medhealth_backend::ml_service::MlService;
+use medhealth_backend::models::{DeviceEventIngest, DeviceVitalsIngest, SensorReading};
+use validator::Validate;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(body) = serde_json::from_slice::<DeviceVitalsIngest>(data) {
+        if body.validate().is_ok() {
+            // The signature is computed over the re-serialized body
+            let _ = serde_json::to_string(&body).unwrap();
+
+            let ml = MlService::new(MlConfig {
+                anomaly_threshold: 0.85,
+                enable_alerts: true,
+                critical_hr_low: 40,
+                critical_hr_high: 180,
+                critical_spo2_low: 88,
+                alert_cooldown_seconds: 300,
+                model: None,
+            });
+            let reading = SensorReading {
+                id: 0,
+                device_id: Default::default(),
+                heart_rate: Some(body.heart_rate),
+                spo2: Some(body.spo2),
+                temperature: Some(body.temperature),
+                reading_timestamp: Default::default(),
+                received_at: Default::default(),
+                quality_score: None,
+                metadata: serde_json::json!({ "steps": body.steps }),
+            };
+            let analysis = ml.analyze_reading(&reading);
+            assert!((0.0..=1.0).contains(&analysis.anomaly_score));
+            let _ = ml.generate_alert(&analysis);
+        }
+    }
+
+    if let Ok(event) = serde_json::from_slice::<DeviceEventIngest>(data) {
+        if event.validate().is_ok() {
+            let _ = serde_json::to_string(&event).unwrap();
+        }
+    }
+});
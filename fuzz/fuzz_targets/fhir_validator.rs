@@ -0,0 +1,20 @@
+//! Arbitrary JSON through the FHIR observation validator
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use medhealth_backend::config::FhirConfig;
+use medhealth_backend::fhir_service::FhirService;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(resource) = serde_json::from_slice::<serde_json::Value>(data) else {
+        return;
+    };
+
+    let fhir = FhirService::new(FhirConfig {
+        base_url: "http://localhost:8080/fhir".to_string(),
+        organization_id: "org-fuzz".to_string(),
+    });
+    if fhir.validate_observation(&resource) {
+        assert_eq!(resource["resourceType"], "Observation");
+    }
+});
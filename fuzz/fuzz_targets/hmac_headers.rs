@@ -0,0 +1,32 @@
+//! Device authentication headers and signature checks.
+//!
+//! Input layout: `X-Device-Id`, `X-Timestamp`, `X-Signature` and the body, separated
+//! by NUL bytes; missing parts are treated as absent headers.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use medhealth_backend::auth::{device_signature, timestamp_within_window, verify_device_signature, DeviceAuthHeaders};
+
+const SECRET: &str = "fuzz_device_secret";
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+    let mut parts = input.splitn(4, '\0');
+    let (device_id, timestamp, signature) = (parts.next(), parts.next(), parts.next());
+    let payload = parts.next().unwrap_or("");
+
+    let Ok(headers) = DeviceAuthHeaders::parse(device_id, timestamp, signature) else {
+        return;
+    };
+    let _ = timestamp_within_window(1_700_000_000, headers.timestamp, 60);
+
+    // Only the genuine signature may verify
+    let genuine = device_signature(SECRET, headers.timestamp, payload);
+    assert_eq!(
+        verify_device_signature(SECRET, headers.timestamp, payload, headers.signature),
+        headers.signature == genuine
+    );
+    assert!(verify_device_signature(SECRET, headers.timestamp, payload, &genuine));
+});
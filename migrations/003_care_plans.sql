@@ -0,0 +1,43 @@
+-- Care plans: per-patient activity and vitals targets
+CREATE TABLE IF NOT EXISTS care_plans (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    patient_id UUID NOT NULL REFERENCES patients(id) ON DELETE CASCADE,
+    title TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('draft', 'active', 'completed', 'revoked')),
+    target_steps INTEGER CHECK (target_steps > 0),
+    target_activity_minutes INTEGER CHECK (target_activity_minutes > 0),
+    hr_min INTEGER CHECK (hr_min >= 0 AND hr_min <= 300),
+    hr_max INTEGER CHECK (hr_max >= 0 AND hr_max <= 300),
+    spo2_min INTEGER CHECK (spo2_min >= 0 AND spo2_min <= 100),
+    start_date DATE NOT NULL DEFAULT CURRENT_DATE,
+    end_date DATE,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CHECK (hr_min IS NULL OR hr_max IS NULL OR hr_min < hr_max),
+    CHECK (end_date IS NULL OR end_date >= start_date)
+);
+
+CREATE INDEX idx_care_plans_patient ON care_plans(patient_id);
+CREATE INDEX idx_care_plans_active ON care_plans(status) WHERE status = 'active';
+
+-- One evaluation per plan per day, written by the care plan worker
+CREATE TABLE IF NOT EXISTS care_plan_evaluations (
+    id BIGSERIAL PRIMARY KEY,
+    care_plan_id UUID NOT NULL REFERENCES care_plans(id) ON DELETE CASCADE,
+    eval_date DATE NOT NULL,
+    readings INTEGER NOT NULL DEFAULT 0,
+    steps INTEGER,
+    activity_minutes INTEGER,
+    avg_heart_rate REAL,
+    min_spo2 INTEGER,
+    hr_in_range_ratio REAL,
+    steps_met BOOLEAN,
+    activity_met BOOLEAN,
+    vitals_met BOOLEAN,
+    evaluated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (care_plan_id, eval_date)
+);
+
+-- Step counts reported by the walker live in sensor_readings.metadata->'steps'
+CREATE INDEX idx_sensor_readings_device_time ON sensor_readings(device_id, reading_timestamp);
@@ -0,0 +1,46 @@
+-- In-app notification inbox (one row per recipient)
+CREATE TABLE IF NOT EXISTS notifications (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    patient_id UUID REFERENCES patients(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    title TEXT NOT NULL,
+    body TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    read_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_notifications_user ON notifications(user_id, created_at DESC);
+
+-- Medication schedules; times are daily administration times in UTC
+CREATE TABLE IF NOT EXISTS medications (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    patient_id UUID NOT NULL REFERENCES patients(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    dosage TEXT NOT NULL,
+    instructions TEXT,
+    times TIME[] NOT NULL CHECK (cardinality(times) > 0),
+    start_date DATE NOT NULL DEFAULT CURRENT_DATE,
+    end_date DATE,
+    active BOOLEAN NOT NULL DEFAULT true,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CHECK (end_date IS NULL OR end_date >= start_date)
+);
+
+CREATE INDEX idx_medications_patient ON medications(patient_id) WHERE active = true;
+
+-- Individual scheduled doses, materialized ahead of time by the reminder worker
+CREATE TABLE IF NOT EXISTS medication_doses (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    medication_id UUID NOT NULL REFERENCES medications(id) ON DELETE CASCADE,
+    scheduled_at TIMESTAMPTZ NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'taken', 'skipped', 'missed')),
+    reminded_at TIMESTAMPTZ,
+    confirmed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    confirmed_at TIMESTAMPTZ,
+    note TEXT,
+    UNIQUE (medication_id, scheduled_at)
+);
+
+CREATE INDEX idx_medication_doses_due ON medication_doses(scheduled_at) WHERE status = 'pending';
@@ -0,0 +1,15 @@
+-- Subjective self-reports submitted from the companion app
+CREATE TABLE IF NOT EXISTS checkins (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    patient_id UUID NOT NULL REFERENCES patients(id) ON DELETE CASCADE,
+    submitted_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    pain_score INTEGER CHECK (pain_score >= 0 AND pain_score <= 10),
+    dizziness INTEGER CHECK (dizziness >= 0 AND dizziness <= 10),
+    fatigue INTEGER CHECK (fatigue >= 0 AND fatigue <= 10),
+    shortness_of_breath BOOLEAN,
+    fell_since_last BOOLEAN,
+    notes TEXT,
+    submitted_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_checkins_patient_time ON checkins(patient_id, submitted_at DESC);
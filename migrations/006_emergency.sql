@@ -0,0 +1,45 @@
+-- People to reach when a patient raises an SOS; lower priority is contacted first
+CREATE TABLE IF NOT EXISTS emergency_contacts (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    patient_id UUID NOT NULL REFERENCES patients(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    relationship TEXT,
+    phone TEXT NOT NULL,
+    priority INTEGER NOT NULL DEFAULT 1 CHECK (priority >= 1),
+    notify_sms BOOLEAN NOT NULL DEFAULT true,
+    notify_call BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_emergency_contacts_patient ON emergency_contacts(patient_id, priority);
+
+-- Alerts raised by device events (e.g. the SOS button) rather than by vitals analysis
+CREATE TABLE IF NOT EXISTS alerts (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    patient_id UUID REFERENCES patients(id) ON DELETE CASCADE,
+    device_id UUID REFERENCES devices(id) ON DELETE SET NULL,
+    kind TEXT NOT NULL,
+    level TEXT NOT NULL CHECK (level IN ('low', 'medium', 'high', 'critical')),
+    message TEXT NOT NULL,
+    details JSONB NOT NULL DEFAULT '{}'::jsonb,
+    raised_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    acknowledged_at TIMESTAMPTZ,
+    acknowledged_by UUID REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_alerts_patient ON alerts(patient_id, raised_at DESC);
+
+-- Every step taken in response to an alert, in order (notifications sent, acknowledgement)
+CREATE TABLE IF NOT EXISTS alert_responses (
+    id BIGSERIAL PRIMARY KEY,
+    alert_id UUID NOT NULL REFERENCES alerts(id) ON DELETE CASCADE,
+    channel TEXT NOT NULL CHECK (channel IN ('inbox', 'sms', 'call', 'acknowledgement')),
+    contact_id UUID REFERENCES emergency_contacts(id) ON DELETE SET NULL,
+    user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    target TEXT,
+    status TEXT NOT NULL CHECK (status IN ('sent', 'failed', 'acknowledged')),
+    detail TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_alert_responses_alert ON alert_responses(alert_id, id);
@@ -0,0 +1,20 @@
+-- Voice escalation: one call per interval, walking the emergency contacts in priority order
+ALTER TABLE alerts
+    ADD COLUMN escalation_level INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN escalated_at TIMESTAMPTZ;
+
+CREATE INDEX idx_alerts_unacknowledged ON alerts(raised_at) WHERE acknowledged_at IS NULL;
+
+-- Voice calls and their provider status callbacks join the response chain
+ALTER TABLE alert_responses ADD COLUMN provider_ref TEXT;
+
+ALTER TABLE alert_responses DROP CONSTRAINT alert_responses_channel_check;
+ALTER TABLE alert_responses ADD CONSTRAINT alert_responses_channel_check
+    CHECK (channel IN ('inbox', 'sms', 'call', 'voice', 'acknowledgement'));
+
+ALTER TABLE alert_responses DROP CONSTRAINT alert_responses_status_check;
+ALTER TABLE alert_responses ADD CONSTRAINT alert_responses_status_check
+    CHECK (status IN ('sent', 'failed', 'acknowledged',
+                      'queued', 'initiated', 'ringing', 'in-progress', 'completed', 'busy', 'no-answer', 'canceled'));
+
+CREATE INDEX idx_alert_responses_provider_ref ON alert_responses(provider_ref) WHERE provider_ref IS NOT NULL;
@@ -0,0 +1,11 @@
+-- Discussion between caregivers and clinicians, attached to the alert it is about
+CREATE TABLE IF NOT EXISTS alert_messages (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    alert_id UUID NOT NULL REFERENCES alerts(id) ON DELETE CASCADE,
+    parent_id UUID REFERENCES alert_messages(id) ON DELETE CASCADE,
+    author_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    body TEXT NOT NULL CHECK (length(body) > 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_alert_messages_alert ON alert_messages(alert_id, created_at);
@@ -0,0 +1,10 @@
+-- Wards group patients for clinical teams (shift handoffs, ward dashboards)
+CREATE TABLE IF NOT EXISTS wards (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    name TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+ALTER TABLE patients ADD COLUMN ward_id UUID REFERENCES wards(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_patients_ward ON patients(ward_id);
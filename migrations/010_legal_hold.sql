@@ -0,0 +1,60 @@
+-- Legal holds freeze a patient's record: retention purges skip it and it cannot be deleted
+CREATE TABLE IF NOT EXISTS legal_holds (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    patient_id UUID NOT NULL REFERENCES patients(id) ON DELETE RESTRICT,
+    reason TEXT NOT NULL CHECK (length(reason) > 0),
+    matter_reference TEXT,
+    placed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    placed_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    released_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    released_at TIMESTAMPTZ
+);
+
+CREATE UNIQUE INDEX idx_legal_holds_active ON legal_holds(patient_id) WHERE released_at IS NULL;
+
+-- Signed export archives, stored verbatim so every later copy can be checked against them.
+-- Actor columns are snapshots rather than foreign keys so that deleting a user never
+-- has to rewrite these append-only rows.
+CREATE TABLE IF NOT EXISTS legal_hold_exports (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    hold_id UUID NOT NULL REFERENCES legal_holds(id) ON DELETE RESTRICT,
+    patient_id UUID NOT NULL REFERENCES patients(id) ON DELETE RESTRICT,
+    created_by UUID,
+    created_at TIMESTAMPTZ NOT NULL,
+    archive BYTEA NOT NULL,
+    sha256 TEXT NOT NULL,
+    signature TEXT NOT NULL
+);
+
+CREATE INDEX idx_legal_hold_exports_hold ON legal_hold_exports(hold_id);
+
+-- Chain of custody: every event hashes the previous one for the same export
+CREATE TABLE IF NOT EXISTS custody_events (
+    id BIGSERIAL PRIMARY KEY,
+    export_id UUID NOT NULL REFERENCES legal_hold_exports(id) ON DELETE RESTRICT,
+    action TEXT NOT NULL CHECK (action IN ('created', 'downloaded', 'verified')),
+    actor_id UUID,
+    actor_email TEXT,
+    actor_ip TEXT,
+    note TEXT,
+    recorded_at TIMESTAMPTZ NOT NULL,
+    prev_hash TEXT,
+    hash TEXT NOT NULL
+);
+
+CREATE INDEX idx_custody_events_export ON custody_events(export_id, id);
+
+-- Exports and custody events are append-only
+CREATE OR REPLACE FUNCTION reject_legal_hold_mutation() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION '% is append-only', TG_TABLE_NAME;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER legal_hold_exports_immutable
+    BEFORE UPDATE OR DELETE ON legal_hold_exports
+    FOR EACH ROW EXECUTE FUNCTION reject_legal_hold_mutation();
+
+CREATE TRIGGER custody_events_immutable
+    BEFORE UPDATE OR DELETE ON custody_events
+    FOR EACH ROW EXECUTE FUNCTION reject_legal_hold_mutation();
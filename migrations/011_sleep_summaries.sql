@@ -0,0 +1,15 @@
+-- Nightly rest/sleep summaries inferred from heart rate and walker motion,
+-- one row per patient per night (noon to noon UTC, keyed by the morning's date)
+CREATE TABLE IF NOT EXISTS sleep_summaries (
+    patient_id UUID NOT NULL REFERENCES patients(id) ON DELETE CASCADE,
+    sleep_date DATE NOT NULL,
+    period_count INTEGER NOT NULL DEFAULT 0,
+    rest_minutes INTEGER NOT NULL DEFAULT 0,
+    sleep_minutes INTEGER NOT NULL DEFAULT 0,
+    longest_minutes INTEGER NOT NULL DEFAULT 0,
+    avg_heart_rate REAL,
+    rest_hr_threshold REAL,
+    periods JSONB NOT NULL DEFAULT '[]'::jsonb,
+    computed_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (patient_id, sleep_date)
+);
@@ -0,0 +1,19 @@
+-- Activity segments: consecutive walker motion windows sharing one activity label,
+-- rebuilt per device and UTC day by the activity classifier
+CREATE TABLE IF NOT EXISTS activity_segments (
+    id BIGSERIAL PRIMARY KEY,
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    patient_id UUID REFERENCES patients(id) ON DELETE CASCADE,
+    activity TEXT NOT NULL CHECK (activity IN ('walking', 'stairs', 'standing', 'sitting')),
+    started_at TIMESTAMPTZ NOT NULL,
+    ended_at TIMESTAMPTZ NOT NULL,
+    steps INTEGER NOT NULL DEFAULT 0,
+    elevation_change REAL NOT NULL DEFAULT 0,
+    readings INTEGER NOT NULL DEFAULT 0,
+    classifier TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CHECK (ended_at > started_at)
+);
+
+CREATE INDEX idx_activity_segments_device_time ON activity_segments(device_id, started_at);
+CREATE INDEX idx_activity_segments_patient_time ON activity_segments(patient_id, started_at);
@@ -0,0 +1,8 @@
+-- Prescribed daily walker use and the per-day usage measured against it
+ALTER TABLE care_plans ADD COLUMN prescribed_usage_minutes INTEGER CHECK (prescribed_usage_minutes > 0);
+
+ALTER TABLE care_plan_evaluations ADD COLUMN usage_minutes INTEGER;
+ALTER TABLE care_plan_evaluations ADD COLUMN usage_met BOOLEAN;
+
+-- Non-use alerts are raised once per streak, identified by its first day
+CREATE INDEX idx_alerts_walker_non_use ON alerts(patient_id, (details->>'streak_start')) WHERE kind = 'walker_non_use';
@@ -0,0 +1,15 @@
+-- Near-fall and stumble events detected on the walker, with the context the firmware reports
+CREATE TABLE IF NOT EXISTS near_fall_events (
+    id BIGSERIAL PRIMARY KEY,
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    patient_id UUID REFERENCES patients(id) ON DELETE CASCADE,
+    event_type TEXT NOT NULL CHECK (event_type IN ('near_fall', 'stumble')),
+    occurred_at TIMESTAMPTZ NOT NULL,
+    -- Walking speed just before the event, m/s
+    speed REAL CHECK (speed >= 0),
+    surface TEXT,
+    details JSONB NOT NULL DEFAULT '{}'::jsonb,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_near_fall_events_patient_time ON near_fall_events(patient_id, occurred_at DESC);
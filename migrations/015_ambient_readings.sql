@@ -0,0 +1,12 @@
+-- Ambient conditions reported alongside vitals, kept apart from clinical readings
+CREATE TABLE IF NOT EXISTS ambient_readings (
+    sensor_reading_id BIGINT PRIMARY KEY REFERENCES sensor_readings(id) ON DELETE CASCADE,
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    ambient_temperature REAL CHECK (ambient_temperature >= -40.0 AND ambient_temperature <= 60.0),
+    humidity REAL CHECK (humidity >= 0.0 AND humidity <= 100.0),
+    -- Hot surroundings coinciding with an elevated heart rate
+    heat_stress BOOLEAN NOT NULL DEFAULT false,
+    recorded_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX idx_ambient_readings_device_time ON ambient_readings(device_id, recorded_at);
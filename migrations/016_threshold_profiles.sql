@@ -0,0 +1,37 @@
+-- Named alert threshold profiles per patient condition. Rules are versioned and never
+-- edited in place, so every analysis can be traced to the rules in effect at the time.
+CREATE TABLE IF NOT EXISTS threshold_profiles (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    name TEXT NOT NULL UNIQUE,
+    description TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS threshold_profile_versions (
+    id BIGSERIAL PRIMARY KEY,
+    profile_id UUID NOT NULL REFERENCES threshold_profiles(id) ON DELETE RESTRICT,
+    version INTEGER NOT NULL CHECK (version >= 1),
+    rules JSONB NOT NULL,
+    created_by UUID,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (profile_id, version)
+);
+
+ALTER TABLE patients ADD COLUMN threshold_profile_id UUID REFERENCES threshold_profiles(id) ON DELETE SET NULL;
+ALTER TABLE ml_analysis ADD COLUMN threshold_profile_version_id BIGINT REFERENCES threshold_profile_versions(id);
+
+WITH profiles AS (
+    INSERT INTO threshold_profiles (name, description) VALUES
+        ('geriatric-default', 'Older adults: earlier fever and tachycardia alerts'),
+        ('copd', 'Chronic obstructive pulmonary disease: tolerates lower SpO2'),
+        ('post-op-cardiac', 'After cardiac surgery: tighter heart-rate and SpO2 limits')
+    ON CONFLICT (name) DO NOTHING
+    RETURNING id, name
+)
+INSERT INTO threshold_profile_versions (profile_id, version, rules)
+SELECT id, 1, CASE name
+    WHEN 'geriatric-default' THEN '{"hr_low": 45, "hr_high": 120, "spo2_low": 90, "fever_temperature": 37.8, "hypothermia_temperature": 35.5}'::jsonb
+    WHEN 'copd' THEN '{"hr_low": 45, "hr_high": 125, "spo2_low": 86, "fever_temperature": 38.0, "hypothermia_temperature": 35.5}'::jsonb
+    ELSE '{"hr_low": 50, "hr_high": 110, "spo2_low": 92, "fever_temperature": 38.0, "hypothermia_temperature": 35.5}'::jsonb
+END
+FROM profiles;
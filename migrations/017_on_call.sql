@@ -0,0 +1,15 @@
+-- On-call schedule for night-time alert routing. An override (someone covering)
+-- takes precedence over the scheduled shift for the same time.
+CREATE TABLE IF NOT EXISTS on_call_shifts (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    starts_at TIMESTAMPTZ NOT NULL,
+    ends_at TIMESTAMPTZ NOT NULL,
+    is_override BOOLEAN NOT NULL DEFAULT false,
+    reason TEXT,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CHECK (ends_at > starts_at)
+);
+
+CREATE INDEX idx_on_call_shifts_window ON on_call_shifts(ends_at, starts_at);
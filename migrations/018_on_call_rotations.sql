@@ -0,0 +1,17 @@
+-- Recurring on-call rotations: members take consecutive shifts of `shift_hours`, in
+-- array order, starting at `starts_at`. Rotations and shifts apply to one ward, or to
+-- the whole site when ward_id is NULL.
+CREATE TABLE IF NOT EXISTS on_call_rotations (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    name TEXT NOT NULL,
+    ward_id UUID REFERENCES wards(id) ON DELETE CASCADE,
+    starts_at TIMESTAMPTZ NOT NULL,
+    shift_hours INTEGER NOT NULL CHECK (shift_hours BETWEEN 1 AND 168),
+    members UUID[] NOT NULL CHECK (cardinality(members) >= 1),
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_on_call_rotations_ward ON on_call_rotations(ward_id);
+
+ALTER TABLE on_call_shifts ADD COLUMN ward_id UUID REFERENCES wards(id) ON DELETE CASCADE;
@@ -0,0 +1,46 @@
+-- Pre-aggregated reporting data so reports don't scan the hot readings and alerts tables.
+-- Refreshed concurrently by a background worker; each view needs a unique index for that.
+
+-- Per-patient vitals statistics for each UTC day
+CREATE MATERIALIZED VIEW IF NOT EXISTS patient_daily_stats AS
+SELECT d.patient_id,
+       (r.reading_timestamp AT TIME ZONE 'UTC')::date AS day,
+       COUNT(*) AS readings,
+       AVG(r.heart_rate)::float8 AS heart_rate_avg,
+       MIN(r.heart_rate) AS heart_rate_min,
+       MAX(r.heart_rate) AS heart_rate_max,
+       AVG(r.spo2)::float8 AS spo2_avg,
+       MIN(r.spo2) AS spo2_min,
+       AVG(r.temperature)::float8 AS temperature_avg,
+       MAX(r.temperature) AS temperature_max,
+       COUNT(*) FILTER (WHERE EXISTS (
+           SELECT 1 FROM ml_analysis a WHERE a.sensor_reading_id = r.id AND a.anomaly_detected
+       )) AS anomalies
+FROM sensor_readings r
+JOIN devices d ON d.id = r.device_id
+WHERE d.patient_id IS NOT NULL
+GROUP BY 1, 2;
+
+CREATE UNIQUE INDEX idx_patient_daily_stats_key ON patient_daily_stats(patient_id, day);
+
+-- Alerts raised per patient, UTC day, kind and level, with acknowledgement totals
+CREATE MATERIALIZED VIEW IF NOT EXISTS alert_daily_summary AS
+SELECT patient_id,
+       (raised_at AT TIME ZONE 'UTC')::date AS day,
+       kind,
+       level,
+       COUNT(*) AS raised,
+       COUNT(acknowledged_at) AS acknowledged,
+       -- Summed rather than averaged so ranges of days can be combined exactly
+       COALESCE(SUM(EXTRACT(EPOCH FROM acknowledged_at - raised_at)), 0)::float8 AS ack_seconds_total
+FROM alerts
+WHERE patient_id IS NOT NULL
+GROUP BY 1, 2, 3, 4;
+
+CREATE UNIQUE INDEX idx_alert_daily_summary_key ON alert_daily_summary(patient_id, day, kind, level);
+
+-- When each view was last refreshed, so reports can say how current they are
+CREATE TABLE IF NOT EXISTS reporting_view_refreshes (
+    view_name TEXT PRIMARY KEY,
+    refreshed_at TIMESTAMPTZ NOT NULL
+);
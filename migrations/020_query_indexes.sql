@@ -0,0 +1,15 @@
+-- Composite indexes for the history, report and open-alert query patterns.
+-- The startup check in database.rs warns when any of these are missing.
+
+-- Latest-first reads per device (history, FHIR export, aggregates); supersedes the ascending index
+DROP INDEX IF EXISTS idx_sensor_readings_device_time;
+CREATE INDEX idx_sensor_readings_device_time_desc ON sensor_readings(device_id, reading_timestamp DESC);
+
+-- Per-patient listings ordered by creation (legal hold export, care plans, medications)
+CREATE INDEX idx_notifications_patient_created ON notifications(patient_id, created_at);
+CREATE INDEX idx_care_plans_patient_created ON care_plans(patient_id, created_at);
+CREATE INDEX idx_medications_patient_created ON medications(patient_id, created_at);
+
+-- Open alerts: per patient for handoff summaries, by level for the escalation worker
+CREATE INDEX idx_alerts_open_patient ON alerts(patient_id, raised_at DESC) WHERE acknowledged_at IS NULL;
+CREATE INDEX idx_alerts_open_level ON alerts(level, raised_at) WHERE acknowledged_at IS NULL;
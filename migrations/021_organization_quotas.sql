@@ -0,0 +1,23 @@
+-- Organizations sharing a multi-tenant deployment, each with soft quotas on stored readings
+CREATE TABLE IF NOT EXISTS organizations (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    name TEXT NOT NULL UNIQUE,
+    -- NULL = unlimited
+    max_readings BIGINT CHECK (max_readings > 0),
+    max_bytes BIGINT CHECK (max_bytes > 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+ALTER TABLE patients ADD COLUMN organization_id UUID REFERENCES organizations(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_patients_organization ON patients(organization_id);
+
+-- Usage last measured by the quota worker; ingestion reads this rather than counting rows
+CREATE TABLE IF NOT EXISTS organization_usage (
+    organization_id UUID PRIMARY KEY REFERENCES organizations(id) ON DELETE CASCADE,
+    readings BIGINT NOT NULL,
+    bytes BIGINT NOT NULL,
+    measured_at TIMESTAMPTZ NOT NULL,
+    -- When admins were told the quota was exceeded; cleared once usage is back under it
+    exceeded_notified_at TIMESTAMPTZ
+);
@@ -0,0 +1,24 @@
+-- Billable usage, one row per event, attributed to the organization when it is known
+CREATE TABLE IF NOT EXISTS usage_events (
+    id BIGSERIAL PRIMARY KEY,
+    organization_id UUID REFERENCES organizations(id) ON DELETE SET NULL,
+    metric TEXT NOT NULL CHECK (metric IN ('readings_ingested', 'sse_minutes', 'exports_generated')),
+    quantity BIGINT NOT NULL CHECK (quantity > 0),
+    occurred_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_usage_events_occurred ON usage_events(occurred_at);
+
+-- Per-day totals, recomputed from the events so re-running a rollup never double counts
+CREATE TABLE IF NOT EXISTS usage_daily_rollups (
+    organization_id UUID REFERENCES organizations(id) ON DELETE CASCADE,
+    -- Usage outside any organization rolls up under the nil UUID
+    organization_key UUID GENERATED ALWAYS AS (COALESCE(organization_id, '00000000-0000-0000-0000-000000000000'::uuid)) STORED,
+    day DATE NOT NULL,
+    metric TEXT NOT NULL,
+    quantity BIGINT NOT NULL,
+    rolled_up_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    -- When the billing endpoint accepted this total; cleared if the total changes
+    pushed_at TIMESTAMPTZ,
+    PRIMARY KEY (organization_key, day, metric)
+);
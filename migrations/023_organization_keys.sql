@@ -0,0 +1,9 @@
+-- Per-organization data keys for PHI encryption, stored only wrapped by the master key.
+-- The highest version seals new values; older versions remain for reading.
+CREATE TABLE IF NOT EXISTS organization_keys (
+    organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    version INTEGER NOT NULL CHECK (version > 0),
+    wrapped_key BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (organization_id, version)
+);
@@ -0,0 +1,19 @@
+-- Phones relaying readings for walkers that have no uplink of their own
+CREATE TABLE IF NOT EXISTS device_gateways (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'approved', 'rejected', 'revoked')),
+    requested_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    decided_at TIMESTAMPTZ,
+    decided_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    last_relay_at TIMESTAMPTZ,
+    UNIQUE (device_id, user_id)
+);
+
+CREATE INDEX idx_device_gateways_user ON device_gateways(user_id) WHERE status = 'approved';
+
+-- Provenance: whether the walker sent a reading itself or a paired phone relayed it
+ALTER TABLE sensor_readings ADD COLUMN IF NOT EXISTS ingest_source TEXT NOT NULL DEFAULT 'direct'
+    CHECK (ingest_source IN ('direct', 'relayed'));
+ALTER TABLE sensor_readings ADD COLUMN IF NOT EXISTS relayed_by UUID REFERENCES users(id) ON DELETE SET NULL;
@@ -0,0 +1,3 @@
+-- Patient attributes that alert severity rules can depend on
+ALTER TABLE patients ADD COLUMN IF NOT EXISTS date_of_birth DATE;
+ALTER TABLE patients ADD COLUMN IF NOT EXISTS diagnoses TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,11 @@
+-- Requests counted against each service level objective, one row per objective per sample
+-- (about a minute) with traffic; the request metrics themselves reset on every restart
+CREATE TABLE IF NOT EXISTS slo_samples (
+    id BIGSERIAL PRIMARY KEY,
+    objective TEXT NOT NULL,
+    total BIGINT NOT NULL CHECK (total > 0),
+    good BIGINT NOT NULL CHECK (good >= 0 AND good <= total),
+    sampled_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_slo_samples_sampled ON slo_samples(sampled_at);
@@ -0,0 +1,4 @@
+-- Device managers register and pair walkers without access to patient data
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_role_check;
+ALTER TABLE users ADD CONSTRAINT users_role_check
+    CHECK (role IN ('admin', 'viewer', 'clinician', 'device_manager'));
@@ -0,0 +1,18 @@
+-- Panics caught by the crash reporter, from request handlers and background workers
+CREATE TABLE IF NOT EXISTS incidents (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    -- Set for panics inside a request; matches the response's X-Request-Id
+    request_id TEXT,
+    method TEXT,
+    route TEXT,
+    thread TEXT NOT NULL,
+    message TEXT NOT NULL,
+    location TEXT,
+    backtrace TEXT,
+    occurred_at TIMESTAMPTZ NOT NULL,
+    -- When the incident was forwarded to Sentry, if it was
+    reported_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_incidents_occurred ON incidents(occurred_at DESC);
+CREATE INDEX idx_incidents_request ON incidents(request_id) WHERE request_id IS NOT NULL;
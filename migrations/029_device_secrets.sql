@@ -0,0 +1,4 @@
+-- Per-device HMAC secrets, sealed by the application; secret_hash holds their SHA-256.
+-- Devices without one sign with the shared secret from configuration.
+ALTER TABLE devices ADD COLUMN IF NOT EXISTS secret_ciphertext TEXT;
+ALTER TABLE devices ADD COLUMN IF NOT EXISTS secret_issued_at TIMESTAMPTZ;
@@ -0,0 +1,13 @@
+-- FHIR Patient and Device resources last exported with a reading's Observations, so a
+-- downstream import can resolve the Observations' references. Removed with their record.
+CREATE TABLE IF NOT EXISTS fhir_patients (
+    patient_id UUID PRIMARY KEY REFERENCES patients(id) ON DELETE CASCADE,
+    resource JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS fhir_devices (
+    device_id UUID PRIMARY KEY REFERENCES devices(id) ON DELETE CASCADE,
+    resource JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,26 @@
+-- FHIR bulk data exports ($export): jobs run in the background and write their output as
+-- NDJSON files, one or more per resource type, kept until the job expires
+CREATE TABLE IF NOT EXISTS fhir_export_jobs (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    requested_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    resource_types TEXT[] NOT NULL,
+    since TIMESTAMPTZ,
+    status TEXT NOT NULL DEFAULT 'in_progress' CHECK (status IN ('in_progress', 'completed', 'failed')),
+    -- Resource type being written while in progress
+    progress TEXT,
+    error TEXT,
+    requested_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    completed_at TIMESTAMPTZ,
+    expires_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_fhir_export_jobs_expires ON fhir_export_jobs(expires_at) WHERE expires_at IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS fhir_export_files (
+    job_id UUID NOT NULL REFERENCES fhir_export_jobs(id) ON DELETE CASCADE,
+    resource_type TEXT NOT NULL,
+    part INTEGER NOT NULL,
+    resource_count INTEGER NOT NULL,
+    ndjson TEXT NOT NULL,
+    PRIMARY KEY (job_id, resource_type, part)
+);
@@ -0,0 +1,8 @@
+-- Display preferences each user's requests are formatted with, and the organization whose
+-- patients they are limited to (NULL = not tied to one)
+ALTER TABLE users ADD COLUMN organization_id UUID REFERENCES organizations(id) ON DELETE SET NULL;
+ALTER TABLE users ADD COLUMN locale TEXT NOT NULL DEFAULT 'en';
+ALTER TABLE users ADD COLUMN timezone TEXT NOT NULL DEFAULT 'UTC';
+ALTER TABLE users ADD COLUMN units TEXT NOT NULL DEFAULT 'metric' CHECK (units IN ('metric', 'imperial'));
+
+CREATE INDEX idx_users_organization ON users(organization_id);
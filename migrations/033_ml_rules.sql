@@ -0,0 +1,16 @@
+-- Alert rules defined by admins, evaluated for every reading next to the built-in checks:
+-- alert when `metric comparator value` has held for `duration_seconds` of readings
+CREATE TABLE IF NOT EXISTS ml_rules (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    name TEXT NOT NULL UNIQUE,
+    metric TEXT NOT NULL CHECK (metric IN ('heart_rate', 'spo2', 'temperature')),
+    comparator TEXT NOT NULL CHECK (comparator IN ('>', '>=', '<', '<=', '=', '!=')),
+    value REAL NOT NULL,
+    -- 0 = a single reading is enough
+    duration_seconds INTEGER NOT NULL DEFAULT 0 CHECK (duration_seconds BETWEEN 0 AND 1800),
+    alert_level TEXT NOT NULL CHECK (alert_level IN ('low', 'medium', 'high', 'critical')),
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
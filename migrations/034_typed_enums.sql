@@ -0,0 +1,43 @@
+-- Roles, alert levels and reading classifications as Postgres enums instead of text with
+-- CHECK constraints; each matches the Rust enum it decodes into (`Role`, `AlertLevel`,
+-- `Classification`). Enum order is severity order, so `ORDER BY level` sorts by it.
+CREATE TYPE user_role AS ENUM ('admin', 'clinician', 'viewer', 'device_manager');
+CREATE TYPE alert_level AS ENUM ('none', 'low', 'medium', 'high', 'critical');
+CREATE TYPE reading_classification AS ENUM ('normal', 'warning', 'critical', 'artifact');
+
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_role_check;
+ALTER TABLE users ALTER COLUMN role DROP DEFAULT;
+ALTER TABLE users ALTER COLUMN role TYPE user_role USING role::user_role;
+ALTER TABLE users ALTER COLUMN role SET DEFAULT 'viewer';
+
+ALTER TABLE ml_analysis DROP CONSTRAINT IF EXISTS ml_analysis_alert_level_check;
+ALTER TABLE ml_analysis ALTER COLUMN alert_level TYPE alert_level USING alert_level::alert_level;
+ALTER TABLE ml_analysis ALTER COLUMN classification TYPE reading_classification
+    USING classification::reading_classification;
+
+-- Raised alerts are never 'none'
+ALTER TABLE ml_rules DROP CONSTRAINT IF EXISTS ml_rules_alert_level_check;
+ALTER TABLE ml_rules ALTER COLUMN alert_level TYPE alert_level USING alert_level::alert_level;
+ALTER TABLE ml_rules ADD CONSTRAINT ml_rules_alert_level_check CHECK (alert_level <> 'none');
+
+-- alert_daily_summary reads alerts.level, so it is rebuilt around the new type
+DROP MATERIALIZED VIEW IF EXISTS alert_daily_summary;
+
+ALTER TABLE alerts DROP CONSTRAINT IF EXISTS alerts_level_check;
+ALTER TABLE alerts ALTER COLUMN level TYPE alert_level USING level::alert_level;
+ALTER TABLE alerts ADD CONSTRAINT alerts_level_check CHECK (level <> 'none');
+
+CREATE MATERIALIZED VIEW alert_daily_summary AS
+SELECT patient_id,
+       (raised_at AT TIME ZONE 'UTC')::date AS day,
+       kind,
+       level,
+       COUNT(*) AS raised,
+       COUNT(acknowledged_at) AS acknowledged,
+       -- Summed rather than averaged so ranges of days can be combined exactly
+       COALESCE(SUM(EXTRACT(EPOCH FROM acknowledged_at - raised_at)), 0)::float8 AS ack_seconds_total
+FROM alerts
+WHERE patient_id IS NOT NULL
+GROUP BY 1, 2, 3, 4;
+
+CREATE UNIQUE INDEX idx_alert_daily_summary_key ON alert_daily_summary(patient_id, day, kind, level);
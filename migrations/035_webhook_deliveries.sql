@@ -0,0 +1,23 @@
+-- Every event posted to an outgoing webhook, numbered per webhook so receivers can spot
+-- gaps, and kept byte for byte so it can be redelivered under the same id and sequence
+CREATE TABLE IF NOT EXISTS webhook_sequences (
+    webhook TEXT PRIMARY KEY,
+    last_sequence BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    event_id UUID PRIMARY KEY,
+    webhook TEXT NOT NULL,
+    sequence BIGINT NOT NULL,
+    event_type TEXT NOT NULL,
+    body TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'delivered', 'failed')),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    -- HTTP status of the latest attempt; NULL when it got no response
+    response_status INTEGER,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_attempt_at TIMESTAMPTZ,
+    delivered_at TIMESTAMPTZ,
+    UNIQUE (webhook, sequence)
+);
@@ -0,0 +1,16 @@
+-- Name, logo and contact details of the organization running this deployment, printed on
+-- PDF reports and published as the FHIR Organization `fhir.organization_id`. One row only.
+CREATE TABLE IF NOT EXISTS organization_profile (
+    singleton BOOLEAN PRIMARY KEY DEFAULT true CHECK (singleton),
+    name TEXT NOT NULL,
+    -- URL of the logo image, for clients that render one; the PDF writer is text only
+    logo_url TEXT,
+    phone TEXT,
+    email TEXT,
+    website TEXT,
+    address TEXT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_by UUID REFERENCES users(id) ON DELETE SET NULL
+);
+
+INSERT INTO organization_profile (name) VALUES ('MedHealth Monitor') ON CONFLICT DO NOTHING;
@@ -0,0 +1,16 @@
+-- Each walker's vitals over the trailing week, recomputed by a background job. Statistical
+-- anomaly scores use them in place of population norms, so a patient whose resting heart
+-- rate is 50 is not flagged for being themselves. Only readings since the walker was
+-- claimed count, so a reassigned walker starts afresh with its new patient.
+CREATE TABLE IF NOT EXISTS baselines (
+    device_id UUID PRIMARY KEY REFERENCES devices(id) ON DELETE CASCADE,
+    patient_id UUID REFERENCES patients(id) ON DELETE CASCADE,
+    hr_mean REAL,
+    hr_stddev REAL,
+    hr_samples INTEGER NOT NULL,
+    spo2_mean REAL,
+    spo2_stddev REAL,
+    spo2_samples INTEGER NOT NULL,
+    window_start TIMESTAMPTZ NOT NULL,
+    computed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,26 @@
+-- Alerts move from open to acknowledged (someone is on it) to resolved (dealt with). Vitals
+-- alerts from the ML analysis are stored here too, so staff can triage them like the rest.
+-- Resolving also acknowledges, so `acknowledged_at IS NULL` still means "open" everywhere.
+CREATE TYPE alert_status AS ENUM ('open', 'acknowledged', 'resolved');
+
+ALTER TABLE alerts
+    ADD COLUMN status alert_status NOT NULL DEFAULT 'open',
+    ADD COLUMN resolved_at TIMESTAMPTZ,
+    ADD COLUMN resolved_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    ADD COLUMN resolution TEXT;
+
+UPDATE alerts SET status = 'acknowledged' WHERE acknowledged_at IS NOT NULL;
+
+-- The triage list: everything not yet resolved, newest first
+CREATE INDEX idx_alerts_unresolved ON alerts(raised_at DESC) WHERE status <> 'resolved';
+-- One unresolved vitals alert per walker at a time
+CREATE INDEX idx_alerts_unresolved_vitals ON alerts(device_id) WHERE kind = 'vitals' AND status <> 'resolved';
+
+ALTER TABLE alert_responses DROP CONSTRAINT alert_responses_channel_check;
+ALTER TABLE alert_responses ADD CONSTRAINT alert_responses_channel_check
+    CHECK (channel IN ('inbox', 'sms', 'call', 'voice', 'acknowledgement', 'resolution'));
+
+ALTER TABLE alert_responses DROP CONSTRAINT alert_responses_status_check;
+ALTER TABLE alert_responses ADD CONSTRAINT alert_responses_status_check
+    CHECK (status IN ('sent', 'failed', 'acknowledged', 'resolved',
+                      'queued', 'initiated', 'ringing', 'in-progress', 'completed', 'busy', 'no-answer', 'canceled'));
@@ -0,0 +1,38 @@
+-- Where each user wants vitals alerts sent beyond the in-app inbox, and from which level
+CREATE TABLE IF NOT EXISTS notification_preferences (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    email_enabled BOOLEAN NOT NULL DEFAULT false,
+    -- NULL sends to the account's email
+    email TEXT,
+    sms_enabled BOOLEAN NOT NULL DEFAULT false,
+    phone TEXT,
+    webhook_enabled BOOLEAN NOT NULL DEFAULT false,
+    webhook_url TEXT,
+    -- Signs webhook posts like the outgoing webhooks; NULL sends them unsigned
+    webhook_secret TEXT,
+    min_level alert_level NOT NULL DEFAULT 'high',
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Every message queued for a channel, retried with backoff until delivered or out of attempts
+CREATE TABLE IF NOT EXISTS notification_deliveries (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    patient_id UUID REFERENCES patients(id) ON DELETE CASCADE,
+    alert_id UUID REFERENCES alerts(id) ON DELETE SET NULL,
+    channel TEXT NOT NULL CHECK (channel IN ('email', 'sms', 'webhook')),
+    -- Email address, phone number or URL, as it was when queued
+    target TEXT NOT NULL,
+    level alert_level NOT NULL,
+    subject TEXT NOT NULL,
+    body TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'delivered', 'failed')),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    delivered_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_notification_deliveries_due ON notification_deliveries(next_attempt_at) WHERE status = 'pending';
+CREATE INDEX idx_notification_deliveries_user ON notification_deliveries(user_id, created_at DESC);
@@ -0,0 +1,56 @@
+-- Discharged patients are archived: the record stays readable but takes no new data, their
+-- walkers are deactivated, and they drop off ward lists, the alert triage list and the
+-- care plan and medication workers. Restoring clears the columns again.
+ALTER TABLE patients
+    ADD COLUMN archived_at TIMESTAMPTZ,
+    ADD COLUMN archived_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    ADD COLUMN archive_reason TEXT;
+
+CREATE INDEX idx_patients_archived ON patients(id) WHERE archived_at IS NOT NULL;
+
+-- A patient moving to another organization with their history. Each side confirms through
+-- one of its own admins or clinicians; the move happens when the second side does.
+CREATE TABLE IF NOT EXISTS patient_transfers (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    patient_id UUID NOT NULL REFERENCES patients(id) ON DELETE RESTRICT,
+    -- NULL: the patient was not tied to an organization
+    from_organization_id UUID REFERENCES organizations(id) ON DELETE RESTRICT,
+    to_organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE RESTRICT,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'completed', 'rejected', 'cancelled')),
+    reason TEXT,
+    requested_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    requested_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    source_confirmed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    source_confirmed_at TIMESTAMPTZ,
+    target_confirmed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    target_confirmed_at TIMESTAMPTZ,
+    closed_at TIMESTAMPTZ,
+    -- Records that moved with the patient, counted at completion
+    moved JSONB,
+    CHECK (from_organization_id IS DISTINCT FROM to_organization_id)
+);
+
+-- At most one transfer in flight per patient
+CREATE UNIQUE INDEX idx_patient_transfers_pending ON patient_transfers(patient_id) WHERE status = 'pending';
+CREATE INDEX idx_patient_transfers_patient ON patient_transfers(patient_id, requested_at DESC);
+
+-- Every step of a transfer, who took it and for which side. Actor columns are snapshots so
+-- deleting a user never rewrites this append-only log.
+CREATE TABLE IF NOT EXISTS patient_transfer_events (
+    id BIGSERIAL PRIMARY KEY,
+    transfer_id UUID NOT NULL REFERENCES patient_transfers(id) ON DELETE RESTRICT,
+    action TEXT NOT NULL CHECK (action IN ('requested', 'confirmed', 'rejected', 'cancelled', 'completed')),
+    -- The organization the actor spoke for; NULL for a patient without one
+    organization_id UUID,
+    actor_id UUID,
+    actor_email TEXT,
+    note TEXT,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_patient_transfer_events_transfer ON patient_transfer_events(transfer_id, id);
+
+-- The legal-hold guard raises "<table> is append-only"
+CREATE TRIGGER patient_transfer_events_immutable
+    BEFORE UPDATE OR DELETE ON patient_transfer_events
+    FOR EACH ROW EXECUTE FUNCTION reject_legal_hold_mutation();
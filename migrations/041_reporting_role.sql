@@ -0,0 +1,84 @@
+-- Read-only access for analytics, in two roles. Creating them needs CREATEROLE (or a
+-- superuser) for the migrating user; roles are cluster-wide, so they may already exist.
+--
+-- medhealth_reporting: what the analytics endpoints need and nothing more. With
+--   `reporting.mode = "constrained"` the API runs reports under it, read-only and with a
+--   statement timeout, so a runaway report cannot write or hold the main pool.
+-- medhealth_bi: the group for SQL logins issued to hospital BI tools. It sees only the
+--   `reporting` schema below, whose views keep each login to its own organization's rows.
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_roles WHERE rolname = 'medhealth_reporting') THEN
+        CREATE ROLE medhealth_reporting NOLOGIN;
+    END IF;
+    IF NOT EXISTS (SELECT 1 FROM pg_roles WHERE rolname = 'medhealth_bi') THEN
+        CREATE ROLE medhealth_bi NOLOGIN;
+    END IF;
+    -- Lets the API switch to the reporting role without a second login
+    EXECUTE format('GRANT medhealth_reporting TO %I', current_user);
+    EXECUTE format('GRANT USAGE ON SCHEMA %I TO medhealth_reporting', current_schema());
+END
+$$;
+
+GRANT SELECT ON patient_daily_stats, alert_daily_summary, reporting_view_refreshes, alerts TO medhealth_reporting;
+-- Column grants keep password hashes, secrets and clinical notes out of reach
+GRANT SELECT (id, display_name, organization_id, ward_id, archived_at) ON patients TO medhealth_reporting;
+GRANT SELECT (id, email) ON users TO medhealth_reporting;
+GRANT SELECT (id, name) ON wards TO medhealth_reporting;
+-- For the anomaly heatmap
+GRANT SELECT (id, sensor_reading_id, anomaly_detected, analysis_details) ON ml_analysis TO medhealth_reporting;
+GRANT SELECT (id, device_id, reading_timestamp) ON sensor_readings TO medhealth_reporting;
+GRANT SELECT (id, patient_id) ON devices TO medhealth_reporting;
+
+-- One row per SQL login issued to a BI tool. The login's name is matched against
+-- `session_user`, which a login cannot change with SET ROLE.
+CREATE TABLE IF NOT EXISTS reporting_credentials (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    role_name TEXT NOT NULL UNIQUE,
+    -- NULL: every organization (and patients outside any)
+    organization_id UUID REFERENCES organizations(id) ON DELETE CASCADE,
+    label TEXT NOT NULL,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ,
+    revoked_by UUID REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE SCHEMA IF NOT EXISTS reporting;
+
+-- Views run with their owner's rights, so BI logins need no grants on the tables beneath.
+-- security_barrier keeps a login's own predicates from being evaluated before the filter.
+CREATE VIEW reporting.patients WITH (security_barrier) AS
+SELECT p.id, p.display_name, p.organization_id, p.ward_id, p.created_at, p.archived_at
+FROM patients p
+WHERE EXISTS (
+    SELECT 1 FROM reporting_credentials c
+    WHERE c.role_name = session_user AND c.revoked_at IS NULL AND c.expires_at > now()
+      AND (c.organization_id IS NULL OR c.organization_id = p.organization_id)
+);
+
+CREATE VIEW reporting.patient_daily_stats WITH (security_barrier) AS
+SELECT s.*
+FROM patient_daily_stats s
+JOIN reporting.patients p ON p.id = s.patient_id;
+
+CREATE VIEW reporting.alert_daily_summary WITH (security_barrier) AS
+SELECT s.*
+FROM alert_daily_summary s
+JOIN reporting.patients p ON p.id = s.patient_id;
+
+-- Alert messages and resolution notes can carry clinical detail, so they are left out
+CREATE VIEW reporting.alerts WITH (security_barrier) AS
+SELECT a.id, a.patient_id, a.kind, a.level, a.status, a.raised_at, a.acknowledged_at, a.resolved_at
+FROM alerts a
+JOIN reporting.patients p ON p.id = a.patient_id;
+
+CREATE VIEW reporting.wards AS
+SELECT id, name FROM wards;
+
+CREATE VIEW reporting.view_refreshes AS
+SELECT view_name, refreshed_at FROM reporting_view_refreshes;
+
+GRANT USAGE ON SCHEMA reporting TO medhealth_bi;
+GRANT SELECT ON ALL TABLES IN SCHEMA reporting TO medhealth_bi;
@@ -0,0 +1,4 @@
+-- Columns the analytics query endpoint can aggregate or group by, for when reports run
+-- under the constrained reporting role
+GRANT SELECT (heart_rate, spo2, temperature, quality_score) ON sensor_readings TO medhealth_reporting;
+GRANT SELECT (anomaly_score, classification, alert_level) ON ml_analysis TO medhealth_reporting;
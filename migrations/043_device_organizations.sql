@@ -0,0 +1,12 @@
+-- Walkers belong to an organization, so each organization manages only its own fleet.
+-- Readings are not stamped: they belong to the walker's patient and follow them on transfer.
+ALTER TABLE devices ADD COLUMN organization_id UUID REFERENCES organizations(id) ON DELETE SET NULL;
+
+UPDATE devices d SET organization_id = p.organization_id
+FROM patients p
+WHERE p.id = d.patient_id;
+
+CREATE INDEX idx_devices_organization ON devices(organization_id);
+
+-- A bulk export covers the organization of whoever asked for it
+ALTER TABLE fhir_export_jobs ADD COLUMN organization_id UUID REFERENCES organizations(id) ON DELETE CASCADE;
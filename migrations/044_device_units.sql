@@ -0,0 +1,3 @@
+-- How each walker's firmware reports its readings, e.g. {"temperature": "fahrenheit"};
+-- readings are converted to the stored units on ingestion. Empty means the stored units.
+ALTER TABLE devices ADD COLUMN units JSONB NOT NULL DEFAULT '{}'::jsonb;
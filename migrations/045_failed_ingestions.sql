@@ -0,0 +1,19 @@
+-- Dead-letter queue: readings that passed their signature check but could not be stored,
+-- kept as sent with the reason so they can be inspected and reprocessed after a fix
+CREATE TABLE failed_ingestions (
+    id BIGSERIAL PRIMARY KEY,
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    channel TEXT NOT NULL CHECK (channel IN ('http', 'mqtt', 'gateway')),
+    relayed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    payload JSONB NOT NULL,
+    category TEXT NOT NULL CHECK (category IN ('invalid_reading', 'storage')),
+    error TEXT NOT NULL,
+    attempts INT NOT NULL DEFAULT 1,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    reprocessed_at TIMESTAMPTZ,
+    sensor_reading_id BIGINT REFERENCES sensor_readings(id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_failed_ingestions_pending ON failed_ingestions(received_at) WHERE reprocessed_at IS NULL;
+CREATE INDEX idx_failed_ingestions_device ON failed_ingestions(device_id);
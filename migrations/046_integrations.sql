@@ -0,0 +1,21 @@
+-- Each organization's connections to outside systems: its FHIR server, HL7 endpoint and
+-- webhook defaults. Secrets are sealed under the organization's data keys when encryption
+-- is configured, and are never returned by the API.
+CREATE TABLE integrations (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL CHECK (kind IN ('fhir', 'hl7', 'webhook')),
+    name TEXT NOT NULL,
+    url TEXT NOT NULL,
+    username TEXT,
+    secret TEXT,
+    settings JSONB NOT NULL DEFAULT '{}'::jsonb,
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    last_tested_at TIMESTAMPTZ,
+    last_test_ok BOOLEAN,
+    last_test_error TEXT,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (organization_id, kind, name)
+);
@@ -0,0 +1,25 @@
+-- What dashboards were sent: every broadcast event (heartbeats aside) by type, subject and
+-- time with the SHA-256 of its JSON, and every SSE/WebSocket stream with its user and the
+-- patients it was limited to. Payloads are not kept; they hash to what the client received.
+CREATE TABLE event_log (
+    event_id UUID PRIMARY KEY,
+    event_type TEXT NOT NULL,
+    patient_id UUID,
+    occurred_at TIMESTAMPTZ NOT NULL,
+    payload_sha256 TEXT NOT NULL
+);
+
+CREATE INDEX idx_event_log_occurred ON event_log(occurred_at);
+CREATE INDEX idx_event_log_patient ON event_log(patient_id, occurred_at);
+
+CREATE TABLE stream_sessions (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    transport TEXT NOT NULL CHECK (transport IN ('sse', 'websocket')),
+    -- NULL: every patient
+    patients UUID[],
+    opened_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    closed_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_stream_sessions_user ON stream_sessions(user_id, opened_at);
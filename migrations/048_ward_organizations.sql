@@ -0,0 +1,18 @@
+-- Wards belong to the organization whose admin created them; NULL keeps a ward shared by
+-- the whole deployment, managed only by administrators outside any organization. Existing
+-- wards go to the one organization all their patients belong to, if there is one.
+ALTER TABLE wards ADD COLUMN organization_id UUID REFERENCES organizations(id) ON DELETE CASCADE;
+
+UPDATE wards w SET organization_id = o.organization_id
+FROM (
+    SELECT ward_id, (array_agg(organization_id))[1] AS organization_id
+    FROM patients
+    WHERE ward_id IS NOT NULL
+    GROUP BY ward_id
+    HAVING COUNT(DISTINCT organization_id) = 1 AND COUNT(organization_id) = COUNT(*)
+) o
+WHERE o.ward_id = w.id;
+
+-- Names only need to be unique within an organization
+ALTER TABLE wards DROP CONSTRAINT IF EXISTS wards_name_key;
+CREATE UNIQUE INDEX idx_wards_organization_name ON wards (organization_id, name) NULLS NOT DISTINCT;
@@ -0,0 +1,13 @@
+-- reporting.wards listed every ward to every BI login. Like the other reporting views it now
+-- keeps a login to its own organization: the wards the organization owns and those its
+-- patients are on.
+CREATE OR REPLACE VIEW reporting.wards WITH (security_barrier) AS
+SELECT w.id, w.name
+FROM wards w
+WHERE EXISTS (
+    SELECT 1 FROM reporting_credentials c
+    WHERE c.role_name = session_user AND c.revoked_at IS NULL AND c.expires_at > now()
+      AND (c.organization_id IS NULL
+           OR c.organization_id = w.organization_id
+           OR EXISTS (SELECT 1 FROM patients p WHERE p.ward_id = w.id AND p.organization_id = c.organization_id))
+);
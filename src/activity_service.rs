@@ -0,0 +1,302 @@
+use crate::models::ActivitySegment;
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use sqlx::{FromRow, PgPool};
+use std::time::Duration as StdDuration;
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// Length of one classification window
+pub const WINDOW_MINUTES: i64 = 5;
+/// Steps per window at or above which the patient counts as walking
+const WALKING_MIN_STEPS: i64 = 10;
+/// Net height change per window that turns walking into stairs (about half a flight)
+const STAIRS_MIN_ELEVATION: f32 = 1.5;
+/// Mean motion (g) above which a window without walking counts as standing rather than sitting
+const STANDING_MIN_MOTION: f32 = 0.05;
+
+const CLASSIFY_INTERVAL: StdDuration = StdDuration::from_secs(900);
+
+/// One reading reduced to its motion channels
+#[derive(Debug, Clone, FromRow)]
+pub struct MotionSample {
+    pub at: DateTime<Utc>,
+    pub steps: Option<i32>,
+    pub motion: Option<f32>,
+    pub elevation_change: Option<f32>,
+}
+
+/// Motion channels aggregated over one window
+#[derive(Debug, Clone, PartialEq)]
+pub struct MotionWindow {
+    pub start: DateTime<Utc>,
+    pub readings: i32,
+    /// `None` when no reading in the window carried a step count
+    pub steps: Option<i64>,
+    pub mean_motion: Option<f32>,
+    pub elevation_change: f32,
+}
+
+/// Labels a motion window with an activity. The rule-based classifier is the default;
+/// a learned model can replace it without touching windowing or storage.
+pub trait ActivityClassifier: Send + Sync {
+    /// Stored with each segment so relabelled history can be told apart
+    fn name(&self) -> &'static str;
+    /// `None` when the window has no usable motion data
+    fn classify(&self, window: &MotionWindow) -> Option<&'static str>;
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RuleBasedClassifier;
+
+impl ActivityClassifier for RuleBasedClassifier {
+    fn name(&self) -> &'static str {
+        "rules-v1"
+    }
+
+    fn classify(&self, window: &MotionWindow) -> Option<&'static str> {
+        if window.steps.is_none() && window.mean_motion.is_none() {
+            return None;
+        }
+        if window.steps.unwrap_or(0) >= WALKING_MIN_STEPS {
+            return Some(if window.elevation_change.abs() >= STAIRS_MIN_ELEVATION { "stairs" } else { "walking" });
+        }
+        if window.mean_motion.unwrap_or(0.0) >= STANDING_MIN_MOTION {
+            Some("standing")
+        } else {
+            Some("sitting")
+        }
+    }
+}
+
+/// Bucket time-ordered samples into fixed windows aligned to `origin`
+pub fn windows(samples: &[MotionSample], origin: DateTime<Utc>) -> Vec<MotionWindow> {
+    let width = Duration::minutes(WINDOW_MINUTES);
+    let mut windows: Vec<MotionWindow> = Vec::new();
+    let mut motion: Vec<f32> = Vec::new();
+
+    for sample in samples {
+        let index = (sample.at - origin).num_seconds().div_euclid(width.num_seconds());
+        let start = origin + width * index as i32;
+
+        if windows.last().is_none_or(|w| w.start != start) {
+            finish_window(windows.last_mut(), &mut motion);
+            windows.push(MotionWindow {
+                start,
+                readings: 0,
+                steps: None,
+                mean_motion: None,
+                elevation_change: 0.0,
+            });
+        }
+        let window = windows.last_mut().expect("window pushed above");
+        window.readings += 1;
+        if let Some(steps) = sample.steps {
+            window.steps = Some(window.steps.unwrap_or(0) + i64::from(steps));
+        }
+        window.elevation_change += sample.elevation_change.unwrap_or(0.0);
+        motion.extend(sample.motion);
+    }
+    finish_window(windows.last_mut(), &mut motion);
+
+    windows
+}
+
+fn finish_window(window: Option<&mut MotionWindow>, motion: &mut Vec<f32>) {
+    if let Some(window) = window {
+        if !motion.is_empty() {
+            window.mean_motion = Some(motion.iter().sum::<f32>() / motion.len() as f32);
+        }
+    }
+    motion.clear();
+}
+
+/// Classify windows and merge adjacent ones with the same label into segments
+pub fn segment(device_id: Uuid, windows: &[MotionWindow], classifier: &dyn ActivityClassifier) -> Vec<ActivitySegment> {
+    let width = Duration::minutes(WINDOW_MINUTES);
+    let mut segments: Vec<ActivitySegment> = Vec::new();
+
+    for window in windows {
+        let Some(activity) = classifier.classify(window) else {
+            continue;
+        };
+        let steps = window.steps.unwrap_or(0).min(i64::from(i32::MAX)) as i32;
+
+        match segments.last_mut() {
+            Some(last) if last.activity == activity && last.ended_at == window.start => {
+                last.ended_at = window.start + width;
+                last.steps = last.steps.saturating_add(steps);
+                last.elevation_change += window.elevation_change;
+                last.readings += window.readings;
+            }
+            _ => segments.push(ActivitySegment {
+                device_id,
+                activity: activity.to_string(),
+                started_at: window.start,
+                ended_at: window.start + width,
+                steps,
+                elevation_change: window.elevation_change,
+                readings: window.readings,
+                classifier: classifier.name().to_string(),
+            }),
+        }
+    }
+
+    segments
+}
+
+/// Rebuild the activity segments of every device with readings on `date` (UTC);
+/// returns the number of segments stored
+pub async fn classify_day(pool: &PgPool, date: NaiveDate, classifier: &dyn ActivityClassifier) -> Result<usize> {
+    let start = date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+    let end = start + Duration::days(1);
+
+    let devices: Vec<(Uuid, Option<Uuid>)> = sqlx::query_as(
+        "SELECT d.id, d.patient_id FROM devices d
+         WHERE EXISTS (SELECT 1 FROM sensor_readings r
+                       WHERE r.device_id = d.id AND r.reading_timestamp >= $1 AND r.reading_timestamp < $2)"
+    )
+    .bind(start)
+    .bind(end)
+    .fetch_all(pool)
+    .await?;
+
+    let mut stored = 0;
+    for (device_id, patient_id) in devices {
+        let samples: Vec<MotionSample> = sqlx::query_as(
+            "SELECT reading_timestamp AS at, (metadata->>'steps')::int AS steps,
+                    (metadata->>'motion')::real AS motion, (metadata->>'elevation_change')::real AS elevation_change
+             FROM sensor_readings
+             WHERE device_id = $1 AND reading_timestamp >= $2 AND reading_timestamp < $3
+             ORDER BY reading_timestamp"
+        )
+        .bind(device_id)
+        .bind(start)
+        .bind(end)
+        .fetch_all(pool)
+        .await?;
+
+        let segments = segment(device_id, &windows(&samples, start), classifier);
+
+        let mut tx = pool.begin().await?;
+        sqlx::query("DELETE FROM activity_segments WHERE device_id = $1 AND started_at >= $2 AND started_at < $3")
+            .bind(device_id)
+            .bind(start)
+            .bind(end)
+            .execute(&mut *tx)
+            .await?;
+        for seg in &segments {
+            sqlx::query(
+                "INSERT INTO activity_segments
+                    (device_id, patient_id, activity, started_at, ended_at, steps, elevation_change, readings, classifier)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"
+            )
+            .bind(device_id)
+            .bind(patient_id)
+            .bind(&seg.activity)
+            .bind(seg.started_at)
+            .bind(seg.ended_at)
+            .bind(seg.steps)
+            .bind(seg.elevation_change)
+            .bind(seg.readings)
+            .bind(&seg.classifier)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        stored += segments.len();
+    }
+
+    Ok(stored)
+}
+
+/// Background worker: finalizes yesterday and keeps today's segments current
+pub fn spawn_classifier(pool: PgPool) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CLASSIFY_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let today = Utc::now().date_naive();
+            for date in [today - Duration::days(1), today] {
+                match classify_day(&pool, date, &RuleBasedClassifier).await {
+                    Ok(count) => info!("Activity classification for {}: {} segment(s)", date, count),
+                    Err(e) => error!("Activity classification for {} failed: {}", date, e),
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn origin() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2026-03-01T00:00:00Z").unwrap().with_timezone(&Utc)
+    }
+
+    fn sample(minute: i64, steps: Option<i32>, motion: Option<f32>, elevation: Option<f32>) -> MotionSample {
+        MotionSample {
+            at: origin() + Duration::minutes(minute),
+            steps,
+            motion,
+            elevation_change: elevation,
+        }
+    }
+
+    fn window(steps: Option<i64>, mean_motion: Option<f32>, elevation_change: f32) -> MotionWindow {
+        MotionWindow {
+            start: origin(),
+            readings: 1,
+            steps,
+            mean_motion,
+            elevation_change,
+        }
+    }
+
+    #[test]
+    fn test_rule_based_labels() {
+        let rules = RuleBasedClassifier;
+        assert_eq!(rules.classify(&window(Some(40), Some(0.3), 0.2)), Some("walking"));
+        assert_eq!(rules.classify(&window(Some(40), Some(0.3), -2.8)), Some("stairs"));
+        assert_eq!(rules.classify(&window(Some(2), Some(0.1), 0.0)), Some("standing"));
+        assert_eq!(rules.classify(&window(Some(0), Some(0.01), 0.0)), Some("sitting"));
+        assert_eq!(rules.classify(&window(Some(0), None, 0.0)), Some("sitting"));
+        assert_eq!(rules.classify(&window(None, None, 3.0)), None);
+    }
+
+    #[test]
+    fn test_windows_aggregate_and_align() {
+        let samples = vec![
+            sample(1, Some(10), Some(0.2), Some(0.5)),
+            sample(3, Some(15), Some(0.4), Some(1.5)),
+            sample(7, None, None, None),
+            sample(21, Some(0), Some(0.0), None),
+        ];
+        let windows = windows(&samples, origin());
+        assert_eq!(windows.len(), 3);
+        assert_eq!(windows[0].start, origin());
+        assert_eq!(windows[0].steps, Some(25));
+        assert!((windows[0].mean_motion.unwrap() - 0.3).abs() < 1e-6);
+        assert_eq!(windows[0].elevation_change, 2.0);
+        assert_eq!((windows[1].start, windows[1].steps, windows[1].mean_motion), (origin() + Duration::minutes(5), None, None));
+        assert_eq!(windows[2].start, origin() + Duration::minutes(20));
+    }
+
+    #[test]
+    fn test_segments_merge_adjacent_windows_only() {
+        let mut samples: Vec<_> = (0..15).map(|m| sample(m, Some(5), Some(0.3), None)).collect();
+        samples.extend((15..20).map(|m| sample(m, Some(0), Some(0.0), None)));
+        // Ten silent minutes, then sitting again
+        samples.extend((30..35).map(|m| sample(m, Some(0), Some(0.0), None)));
+
+        let segments = segment(Uuid::nil(), &windows(&samples, origin()), &RuleBasedClassifier);
+        let summary: Vec<_> = segments
+            .iter()
+            .map(|s| (s.activity.as_str(), (s.ended_at - s.started_at).num_minutes(), s.steps))
+            .collect();
+        assert_eq!(summary, vec![("walking", 15, 75), ("sitting", 5, 0), ("sitting", 5, 0)]);
+        assert!(segments.iter().all(|s| s.classifier == "rules-v1"));
+    }
+}
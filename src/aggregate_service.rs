@@ -0,0 +1,185 @@
+use crate::metrics::{CACHE_HITS, CACHE_MISSES};
+use crate::models::VitalsBucket;
+use crate::redis_cache::RedisCache;
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use sqlx::PgPool;
+use tokio::sync::RwLock;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Aggregation granularity for vitals charts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bucket {
+    Minute,
+    FiveMinutes,
+    Hour,
+    Day,
+}
+
+impl Bucket {
+    pub const ALL: [Bucket; 4] = [Bucket::Minute, Bucket::FiveMinutes, Bucket::Hour, Bucket::Day];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "minute" | "1m" => Some(Bucket::Minute),
+            "five_minutes" | "5m" => Some(Bucket::FiveMinutes),
+            "hour" | "1h" => Some(Bucket::Hour),
+            "day" | "1d" => Some(Bucket::Day),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Bucket::Minute => "minute",
+            Bucket::FiveMinutes => "five_minutes",
+            Bucket::Hour => "hour",
+            Bucket::Day => "day",
+        }
+    }
+
+    pub fn width(&self) -> Duration {
+        match self {
+            Bucket::Minute => Duration::minutes(1),
+            Bucket::FiveMinutes => Duration::minutes(5),
+            Bucket::Hour => Duration::hours(1),
+            Bucket::Day => Duration::days(1),
+        }
+    }
+
+    /// Longest range served in one request, a few thousand buckets at most
+    pub fn max_range(&self) -> Duration {
+        match self {
+            Bucket::Minute => Duration::days(1),
+            Bucket::FiveMinutes => Duration::days(8),
+            Bucket::Hour => Duration::days(31),
+            Bucket::Day => Duration::days(366),
+        }
+    }
+
+    /// Widen `[from, to)` to whole buckets so overlapping requests share cache entries
+    pub fn align(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        let floor = |t: DateTime<Utc>| t.duration_trunc(self.width()).unwrap_or(t);
+        let end = floor(to);
+        (floor(from), if end < to { end + self.width() } else { end })
+    }
+}
+
+/// Per-bucket vitals statistics for a patient over `[from, to)`. Buckets start on
+/// multiples of their width since the Unix epoch, i.e. on UTC minute/hour/day boundaries.
+pub async fn vitals_aggregate(
+    pool: &PgPool,
+    patient_id: Uuid,
+    bucket: Bucket,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<VitalsBucket>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT to_timestamp((floor(extract(epoch FROM r.reading_timestamp) / $4) * $4)::float8) AS bucket_start,
+                COUNT(*) AS readings,
+                AVG(r.heart_rate)::float8 AS heart_rate_avg, MIN(r.heart_rate) AS heart_rate_min, MAX(r.heart_rate) AS heart_rate_max,
+                percentile_cont(0.05) WITHIN GROUP (ORDER BY r.heart_rate) AS heart_rate_p05,
+                percentile_cont(0.5) WITHIN GROUP (ORDER BY r.heart_rate) AS heart_rate_p50,
+                percentile_cont(0.95) WITHIN GROUP (ORDER BY r.heart_rate) AS heart_rate_p95,
+                AVG(r.spo2)::float8 AS spo2_avg, MIN(r.spo2) AS spo2_min, MAX(r.spo2) AS spo2_max,
+                percentile_cont(0.05) WITHIN GROUP (ORDER BY r.spo2) AS spo2_p05,
+                percentile_cont(0.5) WITHIN GROUP (ORDER BY r.spo2) AS spo2_p50,
+                percentile_cont(0.95) WITHIN GROUP (ORDER BY r.spo2) AS spo2_p95,
+                AVG(r.temperature)::float8 AS temperature_avg, MIN(r.temperature) AS temperature_min, MAX(r.temperature) AS temperature_max,
+                percentile_cont(0.05) WITHIN GROUP (ORDER BY r.temperature) AS temperature_p05,
+                percentile_cont(0.5) WITHIN GROUP (ORDER BY r.temperature) AS temperature_p50,
+                percentile_cont(0.95) WITHIN GROUP (ORDER BY r.temperature) AS temperature_p95
+         FROM sensor_readings r
+         JOIN devices d ON d.id = r.device_id
+         WHERE d.patient_id = $1 AND r.reading_timestamp >= $2 AND r.reading_timestamp < $3
+         GROUP BY 1
+         ORDER BY 1"
+    )
+    .bind(patient_id)
+    .bind(from)
+    .bind(to)
+    .bind(bucket.width().num_seconds())
+    .fetch_all(pool)
+    .await
+}
+
+/// [`vitals_aggregate`] through the Redis cache; the flag is true on a cache hit.
+/// Cache errors are logged and fall through to the database.
+pub async fn cached_vitals_aggregate(
+    pool: &PgPool,
+    redis: &RwLock<RedisCache>,
+    patient_id: Uuid,
+    bucket: Bucket,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<(Vec<VitalsBucket>, bool), sqlx::Error> {
+    match redis.write().await.get_aggregate(patient_id, bucket.name(), from, to).await {
+        Ok(Some(json)) => match serde_json::from_str(&json) {
+            Ok(buckets) => {
+                CACHE_HITS.inc();
+                return Ok((buckets, true));
+            }
+            Err(e) => warn!(patient_id = %patient_id, "Discarding unreadable cached aggregate: {}", e),
+        },
+        Ok(None) => CACHE_MISSES.inc(),
+        Err(e) => warn!(patient_id = %patient_id, "Aggregate cache unavailable: {}", e),
+    }
+
+    let buckets = vitals_aggregate(pool, patient_id, bucket, from, to).await?;
+    if let Ok(json) = serde_json::to_string(&buckets) {
+        if let Err(e) = redis.write().await.set_aggregate(patient_id, bucket.name(), from, to, &json).await {
+            warn!(patient_id = %patient_id, "Failed to cache aggregate: {}", e);
+        }
+    }
+    Ok((buckets, false))
+}
+
+/// Drop every cached aggregate covering a new reading at `at`
+pub async fn invalidate(redis: &RwLock<RedisCache>, patient_id: Uuid, at: DateTime<Utc>) {
+    let mut redis = redis.write().await;
+    for bucket in Bucket::ALL {
+        if let Err(e) = redis.invalidate_aggregates(patient_id, bucket.name(), Some(at)).await {
+            warn!(patient_id = %patient_id, "Failed to invalidate cached aggregates: {}", e);
+        }
+    }
+}
+
+/// Drop all of a patient's cached aggregates, e.g. after a device changes hands
+pub async fn invalidate_all(redis: &RwLock<RedisCache>, patient_id: Uuid) {
+    let mut redis = redis.write().await;
+    for bucket in Bucket::ALL {
+        if let Err(e) = redis.invalidate_aggregates(patient_id, bucket.name(), None).await {
+            warn!(patient_id = %patient_id, "Failed to invalidate cached aggregates: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_align_widens_to_whole_buckets() {
+        let (from, to) = Bucket::Hour.align(at("2026-03-01T10:15:00Z"), at("2026-03-01T12:00:00Z"));
+        assert_eq!((from, to), (at("2026-03-01T10:00:00Z"), at("2026-03-01T12:00:00Z")));
+
+        let (from, to) = Bucket::Day.align(at("2026-03-01T10:15:00Z"), at("2026-03-03T00:00:01Z"));
+        assert_eq!((from, to), (at("2026-03-01T00:00:00Z"), at("2026-03-04T00:00:00Z")));
+
+        let (from, to) = Bucket::FiveMinutes.align(at("2026-03-01T10:07:30Z"), at("2026-03-01T10:21:00Z"));
+        assert_eq!((from, to), (at("2026-03-01T10:05:00Z"), at("2026-03-01T10:25:00Z")));
+    }
+
+    #[test]
+    fn test_bucket_parsing() {
+        assert_eq!(Bucket::parse("1h"), Some(Bucket::Hour));
+        assert_eq!(Bucket::parse("day"), Some(Bucket::Day));
+        assert_eq!(Bucket::parse("5m"), Some(Bucket::FiveMinutes));
+        assert_eq!(Bucket::parse("1m"), Some(Bucket::Minute));
+        assert_eq!(Bucket::parse("week"), None);
+    }
+}
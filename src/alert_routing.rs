@@ -0,0 +1,197 @@
+use crate::config::AlertRoutingConfig;
+use crate::models::{AlertLevel, OnCallAssignment, OnCallRotation, OnCallShift};
+use crate::rbac::Role;
+use chrono::{DateTime, Duration, Timelike, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Roles allowed to hold the on-call shift
+pub const ON_CALL_ROLES: [Role; 2] = [Role::Clinician, Role::Admin];
+
+/// Who should receive an alert notification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    /// The patient's linked caregivers
+    Caregivers,
+    /// Whoever is on call, falling back to the caregivers when nobody is
+    OnCall,
+}
+
+/// Whether `at` falls in the configured night, in the site's local time
+pub fn is_night(config: &AlertRoutingConfig, at: DateTime<Utc>) -> bool {
+    let hour = (at + Duration::minutes(i64::from(config.utc_offset_minutes))).hour();
+    let (start, end) = (config.night_start_hour, config.night_end_hour);
+    if start < end {
+        (start..end).contains(&hour)
+    } else {
+        hour >= start || hour < end
+    }
+}
+
+pub fn route(config: &AlertRoutingConfig, level: AlertLevel, at: DateTime<Utc>) -> Route {
+    if config.on_call_levels.iter().any(|l| l == level.as_str()) && is_night(config, at) {
+        Route::OnCall
+    } else {
+        Route::Caregivers
+    }
+}
+
+/// The member holding a rotation's shift at `at`, and when that shift ends
+pub fn rotation_slot(rotation: &OnCallRotation, at: DateTime<Utc>) -> Option<(Uuid, DateTime<Utc>)> {
+    if at < rotation.starts_at || rotation.members.is_empty() || rotation.shift_hours < 1 {
+        return None;
+    }
+    let shift = Duration::hours(i64::from(rotation.shift_hours));
+    let slot = (at - rotation.starts_at).num_seconds() / shift.num_seconds();
+    let member = rotation.members[(slot % rotation.members.len() as i64) as usize];
+    Some((member, rotation.starts_at + shift * (slot + 1) as i32))
+}
+
+/// Who is on call at `at` for a ward (or the whole site when `ward_id` is `None`).
+///
+/// The ward's own cover wins over site-wide cover; within each, explicit shifts win over
+/// rotations and overrides over other shifts. Inactive users and roles that cannot be on
+/// call are skipped.
+pub async fn on_call_at(
+    pool: &PgPool,
+    at: DateTime<Utc>,
+    ward_id: Option<Uuid>,
+) -> Result<Option<OnCallAssignment>, sqlx::Error> {
+    for scope in ward_id.map(Some).into_iter().chain([None]) {
+        if let Some(on_call) = on_call_in_scope(pool, at, scope).await? {
+            return Ok(Some(on_call));
+        }
+    }
+    Ok(None)
+}
+
+async fn on_call_in_scope(
+    pool: &PgPool,
+    at: DateTime<Utc>,
+    ward_id: Option<Uuid>,
+) -> Result<Option<OnCallAssignment>, sqlx::Error> {
+    let shift: Option<OnCallShift> = sqlx::query_as(
+        "SELECT s.* FROM on_call_shifts s
+         JOIN users u ON u.id = s.user_id
+         WHERE s.starts_at <= $1 AND s.ends_at > $1 AND s.ward_id IS NOT DISTINCT FROM $2
+           AND u.is_active AND u.role = ANY($3)
+         ORDER BY s.is_override DESC, s.created_at DESC
+         LIMIT 1"
+    )
+    .bind(at)
+    .bind(ward_id)
+    .bind(&ON_CALL_ROLES[..])
+    .fetch_optional(pool)
+    .await?;
+    if let Some(shift) = shift {
+        return Ok(Some(OnCallAssignment {
+            user_id: shift.user_id,
+            source: if shift.is_override { "override" } else { "shift" }.to_string(),
+            shift_id: Some(shift.id),
+            rotation_id: None,
+            ward_id: shift.ward_id,
+            until: shift.ends_at,
+        }));
+    }
+
+    let rotations: Vec<OnCallRotation> = sqlx::query_as(
+        "SELECT * FROM on_call_rotations
+         WHERE starts_at <= $1 AND ward_id IS NOT DISTINCT FROM $2
+         ORDER BY created_at DESC"
+    )
+    .bind(at)
+    .bind(ward_id)
+    .fetch_all(pool)
+    .await?;
+    for rotation in rotations {
+        let Some((user_id, until)) = rotation_slot(&rotation, at) else { continue };
+        let eligible: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM users WHERE id = $1 AND is_active AND role = ANY($2))")
+            .bind(user_id)
+            .bind(&ON_CALL_ROLES[..])
+            .fetch_one(pool)
+            .await?;
+        if eligible {
+            return Ok(Some(OnCallAssignment {
+                user_id,
+                source: "rotation".to_string(),
+                shift_id: None,
+                rotation_id: Some(rotation.id),
+                ward_id: rotation.ward_id,
+                until,
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Who is on call at `at` for the patient's ward
+pub async fn on_call_for_patient(
+    pool: &PgPool,
+    at: DateTime<Utc>,
+    patient_id: Uuid,
+) -> Result<Option<OnCallAssignment>, sqlx::Error> {
+    let ward_id: Option<Uuid> = sqlx::query_scalar("SELECT ward_id FROM patients WHERE id = $1")
+        .bind(patient_id)
+        .fetch_optional(pool)
+        .await?
+        .flatten();
+    on_call_at(pool, at, ward_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(&format!("2026-03-01T{:02}:{:02}:00Z", hour, minute))
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_night_wraps_midnight_and_honours_offset() {
+        let config = AlertRoutingConfig::default();
+        assert!(is_night(&config, at(22, 0)));
+        assert!(is_night(&config, at(3, 30)));
+        assert!(!is_night(&config, at(7, 0)));
+        assert!(!is_night(&config, at(21, 59)));
+
+        // 20:30 UTC is 22:30 at UTC+2
+        let config = AlertRoutingConfig { utc_offset_minutes: 120, ..Default::default() };
+        assert!(is_night(&config, at(20, 30)));
+        assert!(!is_night(&config, at(5, 0)));
+
+        let day_shift = AlertRoutingConfig { night_start_hour: 1, night_end_hour: 5, ..Default::default() };
+        assert!(is_night(&day_shift, at(4, 59)));
+        assert!(!is_night(&day_shift, at(23, 0)));
+    }
+
+    #[test]
+    fn test_only_configured_levels_go_on_call() {
+        let config = AlertRoutingConfig::default();
+        assert_eq!(route(&config, AlertLevel::Critical, at(2, 0)), Route::OnCall);
+        assert_eq!(route(&config, AlertLevel::High, at(2, 0)), Route::Caregivers);
+        assert_eq!(route(&config, AlertLevel::Critical, at(14, 0)), Route::Caregivers);
+    }
+
+    #[test]
+    fn test_rotation_cycles_through_members() {
+        let (a, b, c) = (Uuid::from_u128(1), Uuid::from_u128(2), Uuid::from_u128(3));
+        let rotation = OnCallRotation {
+            id: Uuid::nil(),
+            name: "Nights".to_string(),
+            ward_id: None,
+            starts_at: at(0, 0),
+            shift_hours: 8,
+            members: vec![a, b, c],
+            created_by: None,
+            created_at: at(0, 0),
+        };
+        assert_eq!(rotation_slot(&rotation, at(0, 0)), Some((a, at(8, 0))));
+        assert_eq!(rotation_slot(&rotation, at(9, 15)), Some((b, at(16, 0))));
+        assert_eq!(rotation_slot(&rotation, at(23, 59)), Some((c, at(0, 0) + Duration::hours(24))));
+        assert_eq!(rotation_slot(&rotation, at(0, 0) + Duration::hours(25)).map(|s| s.0), Some(a));
+        assert_eq!(rotation_slot(&rotation, at(0, 0) - Duration::minutes(1)), None);
+    }
+}
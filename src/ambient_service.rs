@@ -0,0 +1,98 @@
+use crate::models::{AmbientSummary, HeatStressReading};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Ambient temperature (°C) that counts as hot on its own
+const HOT_AMBIENT: f32 = 30.0;
+/// Warm air counts as hot when it is also this humid (%)
+const WARM_AMBIENT: f32 = 27.0;
+const HUMID: f32 = 70.0;
+/// Heart rate treated as elevated for heat-stress flagging
+const ELEVATED_HR: i32 = 100;
+
+/// Hot (or warm and humid) surroundings together with an elevated heart rate
+pub fn is_heat_stress(ambient_temperature: Option<f32>, humidity: Option<f32>, heart_rate: i32) -> bool {
+    let hot = ambient_temperature.is_some_and(|t| {
+        t >= HOT_AMBIENT || (t >= WARM_AMBIENT && humidity.is_some_and(|h| h >= HUMID))
+    });
+    hot && heart_rate >= ELEVATED_HR
+}
+
+/// Store ambient conditions for a reading; returns whether it was flagged as heat stress
+pub async fn record_ambient(
+    pool: &PgPool,
+    reading_id: i64,
+    device_id: Uuid,
+    recorded_at: DateTime<Utc>,
+    ambient_temperature: Option<f32>,
+    humidity: Option<f32>,
+    heart_rate: i32,
+) -> Result<bool, sqlx::Error> {
+    let heat_stress = is_heat_stress(ambient_temperature, humidity, heart_rate);
+
+    sqlx::query(
+        "INSERT INTO ambient_readings (sensor_reading_id, device_id, ambient_temperature, humidity, heat_stress, recorded_at)
+         VALUES ($1, $2, $3, $4, $5, $6)"
+    )
+    .bind(reading_id)
+    .bind(device_id)
+    .bind(ambient_temperature)
+    .bind(humidity)
+    .bind(heat_stress)
+    .bind(recorded_at)
+    .execute(pool)
+    .await?;
+
+    Ok(heat_stress)
+}
+
+/// Ambient statistics for a patient since `since`, with the latest heat-stress readings
+pub async fn summarize(pool: &PgPool, patient_id: Uuid, since: DateTime<Utc>) -> Result<AmbientSummary, sqlx::Error> {
+    let mut summary: AmbientSummary = sqlx::query_as(
+        "SELECT COUNT(*) AS readings,
+                AVG(a.ambient_temperature)::float8 AS avg_ambient_temperature,
+                MAX(a.ambient_temperature) AS max_ambient_temperature,
+                AVG(a.humidity)::float8 AS avg_humidity,
+                corr(a.ambient_temperature, r.heart_rate) AS hr_correlation,
+                COUNT(*) FILTER (WHERE a.heat_stress) AS heat_stress_readings
+         FROM ambient_readings a
+         JOIN sensor_readings r ON r.id = a.sensor_reading_id
+         JOIN devices d ON d.id = a.device_id
+         WHERE d.patient_id = $1 AND a.recorded_at >= $2"
+    )
+    .bind(patient_id)
+    .bind(since)
+    .fetch_one(pool)
+    .await?;
+
+    summary.heat_stress = sqlx::query_as::<_, HeatStressReading>(
+        "SELECT a.recorded_at AS at, a.ambient_temperature, a.humidity, r.heart_rate
+         FROM ambient_readings a
+         JOIN sensor_readings r ON r.id = a.sensor_reading_id
+         JOIN devices d ON d.id = a.device_id
+         WHERE d.patient_id = $1 AND a.recorded_at >= $2 AND a.heat_stress
+         ORDER BY a.recorded_at DESC
+         LIMIT 50"
+    )
+    .bind(patient_id)
+    .bind(since)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heat_stress_needs_heat_and_elevated_hr() {
+        assert!(is_heat_stress(Some(32.0), None, 110));
+        assert!(is_heat_stress(Some(28.0), Some(80.0), 105));
+        assert!(!is_heat_stress(Some(28.0), Some(40.0), 105));
+        assert!(!is_heat_stress(Some(35.0), Some(20.0), 80));
+        assert!(!is_heat_stress(None, Some(95.0), 130));
+    }
+}
@@ -0,0 +1,413 @@
+//! Chart queries for `POST /api/analytics/query`, compiled to parameterized SQL.
+//!
+//! A query names a source (`readings` or `alerts`), metrics over its numeric fields,
+//! dimensions to group by, an optional time bucket and filters. Only the fields listed in
+//! each source's catalog below can be referenced, and every value is bound as a parameter,
+//! so no part of the request is ever spliced into the SQL text.
+
+use crate::models::{AnalyticsBucket, AnalyticsFilter, AnalyticsFunction, AnalyticsOp, AnalyticsQuery, AnalyticsSource};
+use chrono::{DateTime, Duration, Utc};
+use sqlx::postgres::PgArguments;
+use sqlx::Arguments;
+use uuid::Uuid;
+
+pub const DEFAULT_DAYS: i64 = 30;
+pub const MAX_DAYS: i64 = 366;
+pub const DEFAULT_LIMIT: i64 = 1000;
+/// Most values in one `in` filter
+const MAX_IN_VALUES: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Number,
+    Uuid,
+    Text,
+}
+
+struct Field {
+    name: &'static str,
+    sql: &'static str,
+    kind: Kind,
+}
+
+const READING_FIELDS: &[Field] = &[
+    Field { name: "heart_rate", sql: "r.heart_rate", kind: Kind::Number },
+    Field { name: "spo2", sql: "r.spo2", kind: Kind::Number },
+    Field { name: "temperature", sql: "r.temperature", kind: Kind::Number },
+    Field { name: "quality_score", sql: "r.quality_score", kind: Kind::Number },
+    Field { name: "anomaly_score", sql: "m.anomaly_score", kind: Kind::Number },
+    Field { name: "patient_id", sql: "d.patient_id", kind: Kind::Uuid },
+    Field { name: "device_id", sql: "r.device_id", kind: Kind::Uuid },
+    Field { name: "ward_id", sql: "p.ward_id", kind: Kind::Uuid },
+    Field { name: "organization_id", sql: "p.organization_id", kind: Kind::Uuid },
+    Field { name: "classification", sql: "m.classification", kind: Kind::Text },
+    Field { name: "alert_level", sql: "m.alert_level::text", kind: Kind::Text },
+];
+
+const ALERT_FIELDS: &[Field] = &[
+    Field { name: "ack_minutes", sql: "EXTRACT(EPOCH FROM a.acknowledged_at - a.raised_at) / 60", kind: Kind::Number },
+    Field { name: "resolve_minutes", sql: "EXTRACT(EPOCH FROM a.resolved_at - a.raised_at) / 60", kind: Kind::Number },
+    Field { name: "escalation_level", sql: "a.escalation_level", kind: Kind::Number },
+    Field { name: "patient_id", sql: "a.patient_id", kind: Kind::Uuid },
+    Field { name: "device_id", sql: "a.device_id", kind: Kind::Uuid },
+    Field { name: "ward_id", sql: "p.ward_id", kind: Kind::Uuid },
+    Field { name: "organization_id", sql: "p.organization_id", kind: Kind::Uuid },
+    Field { name: "kind", sql: "a.kind", kind: Kind::Text },
+    Field { name: "level", sql: "a.level::text", kind: Kind::Text },
+    Field { name: "status", sql: "a.status::text", kind: Kind::Text },
+];
+
+struct Source {
+    fields: &'static [Field],
+    from: &'static str,
+    time: &'static str,
+}
+
+impl Source {
+    fn of(source: AnalyticsSource) -> Self {
+        match source {
+            AnalyticsSource::Readings => Source {
+                fields: READING_FIELDS,
+                from: "sensor_readings r
+                       JOIN devices d ON d.id = r.device_id
+                       JOIN patients p ON p.id = d.patient_id
+                       LEFT JOIN ml_analysis m ON m.sensor_reading_id = r.id",
+                time: "r.reading_timestamp",
+            },
+            AnalyticsSource::Alerts => Source {
+                fields: ALERT_FIELDS,
+                from: "alerts a JOIN patients p ON p.id = a.patient_id",
+                time: "a.raised_at",
+            },
+        }
+    }
+
+    fn field(&self, name: &str) -> Result<&'static Field, String> {
+        self.fields.iter().find(|f| f.name == name).ok_or_else(|| {
+            let known: Vec<&str> = self.fields.iter().map(|f| f.name).collect();
+            format!("Unknown field '{}'; use one of {}", name, known.join(", "))
+        })
+    }
+}
+
+/// A bound value, kept typed so it can be added to fresh arguments for every execution
+#[derive(Debug, Clone, PartialEq)]
+pub enum Param {
+    Number(f64),
+    Numbers(Vec<f64>),
+    Uuid(Uuid),
+    Uuids(Vec<Uuid>),
+    Text(String),
+    Texts(Vec<String>),
+    Time(DateTime<Utc>),
+    Int(i64),
+}
+
+/// Who is asking: the patients they may see (`None` for all) and their organization
+#[derive(Debug, Clone, Default)]
+pub struct Scope {
+    pub patients: Option<Vec<Uuid>>,
+    pub organization_id: Option<Uuid>,
+    /// IANA name of the caller's time zone, for buckets
+    pub timezone: String,
+}
+
+#[derive(Debug)]
+pub struct CompiledQuery {
+    /// Yields one `jsonb` object per row
+    pub sql: String,
+    pub params: Vec<Param>,
+    pub columns: Vec<String>,
+    /// Rows wanted; one more is fetched to tell whether the result was cut off
+    pub limit: i64,
+}
+
+impl CompiledQuery {
+    pub fn arguments(&self) -> PgArguments {
+        let mut args = PgArguments::default();
+        for param in &self.params {
+            match param {
+                Param::Number(v) => args.add(*v),
+                Param::Numbers(v) => args.add(v.clone()),
+                Param::Uuid(v) => args.add(*v),
+                Param::Uuids(v) => args.add(v.clone()),
+                Param::Text(v) => args.add(v.clone()),
+                Param::Texts(v) => args.add(v.clone()),
+                Param::Time(v) => args.add(*v),
+                Param::Int(v) => args.add(*v),
+            }
+        }
+        args
+    }
+}
+
+struct Builder {
+    params: Vec<Param>,
+}
+
+impl Builder {
+    fn bind(&mut self, param: Param) -> String {
+        self.params.push(param);
+        format!("${}", self.params.len())
+    }
+}
+
+fn bucket_unit(bucket: AnalyticsBucket) -> &'static str {
+    match bucket {
+        AnalyticsBucket::Hour => "hour",
+        AnalyticsBucket::Day => "day",
+        AnalyticsBucket::Week => "week",
+        AnalyticsBucket::Month => "month",
+    }
+}
+
+fn function_name(function: AnalyticsFunction) -> &'static str {
+    match function {
+        AnalyticsFunction::Count => "count",
+        AnalyticsFunction::Sum => "sum",
+        AnalyticsFunction::Avg => "avg",
+        AnalyticsFunction::Min => "min",
+        AnalyticsFunction::Max => "max",
+        AnalyticsFunction::Median => "median",
+    }
+}
+
+/// The `[from, to)` range a query covers, defaulting to the last 30 days
+pub fn time_range(query: &AnalyticsQuery, now: DateTime<Utc>) -> Result<(DateTime<Utc>, DateTime<Utc>), String> {
+    let to = query.to.unwrap_or(now);
+    let from = query.from.unwrap_or(to - Duration::days(DEFAULT_DAYS));
+    if from >= to {
+        return Err("from must be before to".into());
+    }
+    if to - from > Duration::days(MAX_DAYS) {
+        return Err(format!("A query can cover at most {} days", MAX_DAYS));
+    }
+    Ok((from, to))
+}
+
+fn filter_values(filter: &AnalyticsFilter) -> Result<Vec<&serde_json::Value>, String> {
+    match (&filter.op, &filter.value) {
+        (AnalyticsOp::In, serde_json::Value::Array(values)) => {
+            if values.is_empty() || values.len() > MAX_IN_VALUES {
+                return Err(format!("'{}' in takes 1 to {} values", filter.field, MAX_IN_VALUES));
+            }
+            Ok(values.iter().collect())
+        }
+        (AnalyticsOp::In, _) => Err(format!("'{}' in takes an array", filter.field)),
+        (_, serde_json::Value::Array(_)) => Err(format!("Only in takes an array ('{}')", filter.field)),
+        (_, value) => Ok(vec![value]),
+    }
+}
+
+fn compile_filter(builder: &mut Builder, field: &Field, filter: &AnalyticsFilter) -> Result<String, String> {
+    let values = filter_values(filter)?;
+    let invalid = || format!("Invalid value for '{}'", field.name);
+    let param = match field.kind {
+        Kind::Number => {
+            let numbers = values.iter().map(|v| v.as_f64().ok_or_else(invalid)).collect::<Result<Vec<_>, _>>()?;
+            if filter.op == AnalyticsOp::In { Param::Numbers(numbers) } else { Param::Number(numbers[0]) }
+        }
+        Kind::Uuid => {
+            let ids = values
+                .iter()
+                .map(|v| v.as_str().and_then(|s| Uuid::parse_str(s).ok()).ok_or_else(invalid))
+                .collect::<Result<Vec<_>, _>>()?;
+            if filter.op == AnalyticsOp::In { Param::Uuids(ids) } else { Param::Uuid(ids[0]) }
+        }
+        Kind::Text => {
+            let texts = values
+                .iter()
+                .map(|v| v.as_str().map(str::to_string).ok_or_else(invalid))
+                .collect::<Result<Vec<_>, _>>()?;
+            if filter.op == AnalyticsOp::In { Param::Texts(texts) } else { Param::Text(texts[0].clone()) }
+        }
+    };
+
+    let operator = match filter.op {
+        AnalyticsOp::Eq => "=",
+        AnalyticsOp::Ne => "<>",
+        AnalyticsOp::In => "= ANY",
+        AnalyticsOp::Gt => ">",
+        AnalyticsOp::Gte => ">=",
+        AnalyticsOp::Lt => "<",
+        AnalyticsOp::Lte => "<=",
+    };
+    let ordered = matches!(filter.op, AnalyticsOp::Gt | AnalyticsOp::Gte | AnalyticsOp::Lt | AnalyticsOp::Lte);
+    if ordered && field.kind != Kind::Number {
+        return Err(format!("'{}' only supports eq, ne and in", field.name));
+    }
+
+    let placeholder = builder.bind(param);
+    let column = match field.kind {
+        Kind::Number => format!("({})::float8", field.sql),
+        _ => field.sql.to_string(),
+    };
+    Ok(if filter.op == AnalyticsOp::In {
+        format!("{} {}({})", column, operator, placeholder)
+    } else {
+        format!("{} {} {}", column, operator, placeholder)
+    })
+}
+
+/// Compile a query for the given caller; errors are meant for the client
+pub fn compile(query: &AnalyticsQuery, scope: &Scope, now: DateTime<Utc>) -> Result<CompiledQuery, String> {
+    let source = Source::of(query.source);
+    let (from, to) = time_range(query, now)?;
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    let mut builder = Builder { params: Vec::new() };
+    let mut select = Vec::new();
+    let mut columns = Vec::new();
+
+    if let Some(bucket) = query.bucket {
+        let tz = builder.bind(Param::Text(scope.timezone.clone()));
+        select.push(format!(
+            "date_trunc('{}', {} AT TIME ZONE {tz}) AT TIME ZONE {tz} AS \"bucket\"",
+            bucket_unit(bucket),
+            source.time,
+        ));
+        columns.push("bucket".to_string());
+    }
+    for name in &query.group_by {
+        let field = source.field(name)?;
+        if field.kind == Kind::Number {
+            return Err(format!("Cannot group by the numeric field '{}'", name));
+        }
+        if columns.iter().any(|c| c == name) {
+            return Err(format!("'{}' is grouped by twice", name));
+        }
+        select.push(format!("{} AS \"{}\"", field.sql, field.name));
+        columns.push(field.name.to_string());
+    }
+    let groups = columns.len();
+
+    for metric in &query.metrics {
+        let function = function_name(metric.function);
+        let (expression, column) = match (metric.function, metric.field.as_deref()) {
+            (AnalyticsFunction::Count, None) => ("COUNT(*)".to_string(), function.to_string()),
+            (_, None) => return Err(format!("{} needs a field", function)),
+            (_, Some(name)) => {
+                let field = source.field(name)?;
+                if field.kind != Kind::Number {
+                    return Err(format!("'{}' is not numeric", name));
+                }
+                let value = format!("({})::float8", field.sql);
+                let expression = match metric.function {
+                    AnalyticsFunction::Count => format!("COUNT({})", value),
+                    AnalyticsFunction::Median => format!("percentile_cont(0.5) WITHIN GROUP (ORDER BY {})", value),
+                    _ => format!("{}({})", function.to_uppercase(), value),
+                };
+                (expression, format!("{}_{}", function, field.name))
+            }
+        };
+        if columns.contains(&column) {
+            return Err(format!("Metric '{}' is asked for twice", column));
+        }
+        select.push(format!("{} AS \"{}\"", expression, column));
+        columns.push(column);
+    }
+
+    let mut conditions = vec![
+        format!("{} >= {}", source.time, builder.bind(Param::Time(from))),
+        format!("{} < {}", source.time, builder.bind(Param::Time(to))),
+    ];
+    if !query.include_archived {
+        conditions.push("p.archived_at IS NULL".into());
+    }
+    if let Some(organization_id) = scope.organization_id {
+        conditions.push(format!("p.organization_id = {}", builder.bind(Param::Uuid(organization_id))));
+    }
+    if let Some(patients) = &scope.patients {
+        conditions.push(format!("p.id = ANY({})", builder.bind(Param::Uuids(patients.clone()))));
+    }
+    for filter in &query.filters {
+        let field = source.field(&filter.field)?;
+        conditions.push(compile_filter(&mut builder, field, filter)?);
+    }
+
+    let mut sql = format!(
+        "SELECT to_jsonb(q) FROM (SELECT {} FROM {} WHERE {}",
+        select.join(", "),
+        source.from,
+        conditions.join(" AND "),
+    );
+    if groups > 0 {
+        let ordinals: Vec<String> = (1..=groups).map(|i| i.to_string()).collect();
+        sql.push_str(&format!(" GROUP BY {0} ORDER BY {0}", ordinals.join(", ")));
+    }
+    sql.push_str(&format!(" LIMIT {}) q", builder.bind(Param::Int(limit + 1))));
+
+    Ok(CompiledQuery { sql, params: builder.params, columns, limit })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AnalyticsMetric;
+
+    fn query(json: serde_json::Value) -> AnalyticsQuery {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2026-10-01T00:00:00Z").unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_compiles_grouped_bucketed_query_with_bound_values() {
+        let q = query(serde_json::json!({
+            "source": "readings",
+            "metrics": [{"fn": "avg", "field": "heart_rate"}, {"fn": "count"}],
+            "group_by": ["ward_id"],
+            "bucket": "day",
+            "filters": [{"field": "spo2", "op": "lt", "value": 92}],
+        }));
+        let scope = Scope { organization_id: Some(Uuid::nil()), timezone: "Europe/Paris".into(), ..Default::default() };
+        let compiled = compile(&q, &scope, now()).unwrap();
+
+        assert_eq!(compiled.columns, ["bucket", "ward_id", "avg_heart_rate", "count"]);
+        assert!(compiled.sql.contains("AVG((r.heart_rate)::float8) AS \"avg_heart_rate\""));
+        assert!(compiled.sql.contains("(r.spo2)::float8 < $5"));
+        assert!(compiled.sql.contains("GROUP BY 1, 2 ORDER BY 1, 2"));
+        assert!(compiled.sql.contains("p.archived_at IS NULL"));
+        assert_eq!(compiled.params[0], Param::Text("Europe/Paris".into()));
+        assert_eq!(compiled.params[3], Param::Uuid(Uuid::nil()));
+        assert_eq!(compiled.params.last(), Some(&Param::Int(DEFAULT_LIMIT + 1)));
+        assert!(!compiled.sql.contains("92"));
+    }
+
+    #[test]
+    fn test_rejects_fields_outside_the_catalog() {
+        let base = || AnalyticsQuery {
+            source: AnalyticsSource::Alerts,
+            metrics: vec![AnalyticsMetric { function: AnalyticsFunction::Count, field: None }],
+            group_by: vec![],
+            bucket: None,
+            filters: vec![],
+            from: None,
+            to: None,
+            include_archived: false,
+            limit: None,
+        };
+        let scope = Scope::default();
+
+        let mut q = base();
+        q.group_by = vec!["message".into()];
+        assert!(compile(&q, &scope, now()).unwrap_err().contains("Unknown field"));
+
+        let mut q = base();
+        q.metrics = vec![AnalyticsMetric { function: AnalyticsFunction::Avg, field: Some("kind".into()) }];
+        assert!(compile(&q, &scope, now()).is_err());
+
+        let mut q = base();
+        q.filters = vec![AnalyticsFilter { field: "level".into(), op: AnalyticsOp::Gt, value: "high".into() }];
+        assert!(compile(&q, &scope, now()).is_err());
+
+        let mut q = base();
+        q.filters = vec![AnalyticsFilter { field: "patient_id".into(), op: AnalyticsOp::Eq, value: "1; DROP".into() }];
+        assert!(compile(&q, &scope, now()).is_err());
+
+        let mut q = base();
+        q.from = Some(now() - Duration::days(400));
+        assert!(compile(&q, &scope, now()).is_err());
+    }
+}
@@ -0,0 +1,226 @@
+//! API versions, for moving JSON field names to snake_case without breaking walkers and
+//! dashboards in the field.
+//!
+//! Version 1, the default, keeps the camelCase names the API started with (`heartRate`,
+//! `elevationChange`, `ambientTemperature`) next to snake_case ones. Version 2 is snake_case
+//! throughout. Clients choose with the `API-Version` header, and responses carrying renamed
+//! fields say which version they are in. Version 1 responses also carry `Deprecation: true`.
+//!
+//! Request bodies are accepted in either casing during the deprecation window (serde
+//! aliases on the models). Version 1 names in a request are counted in
+//! `deprecated_fields_total` so operators can see when the window can close.
+
+use crate::errors::ApiError;
+use crate::metrics::DEPRECATED_FIELDS_TOTAL;
+use actix_web::{http::header::HeaderValue, HttpRequest, HttpResponse, HttpResponseBuilder, ResponseError};
+use serde::Serialize;
+use serde_json::Value;
+
+pub const API_VERSION: &str = "api-version";
+pub const DEPRECATION: &str = "deprecation";
+
+/// Fields renamed in version 2, as (version 1 name, version 2 name)
+pub const RENAMED_FIELDS: &[(&str, &str)] = &[
+    ("heartRate", "heart_rate"),
+    ("elevationChange", "elevation_change"),
+    ("ambientTemperature", "ambient_temperature"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ApiVersion {
+    #[default]
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    /// The version named in the `API-Version` header; version 1 without one
+    pub fn from_request(req: &HttpRequest) -> Result<Self, ApiError> {
+        match req.headers().get(API_VERSION).map(|v| v.to_str().map(str::trim)) {
+            None => Ok(Self::V1),
+            Some(Ok("1")) => Ok(Self::V1),
+            Some(Ok("2")) => Ok(Self::V2),
+            Some(_) => Err(ApiError::BadRequest("Unsupported API-Version; expected 1 or 2".into())),
+        }
+    }
+
+    pub fn number(self) -> u8 {
+        match self {
+            Self::V1 => 1,
+            Self::V2 => 2,
+        }
+    }
+
+    /// Rename the fields of a body serialized by the models (in version 1 casing)
+    pub fn adapt(self, value: Value) -> Value {
+        match self {
+            Self::V1 => value,
+            Self::V2 => rename_keys(value),
+        }
+    }
+
+    /// [`adapt`](Self::adapt) for a JSON Schema, whose `required` lists name fields too
+    pub fn adapt_schema(self, schema: Value) -> Value {
+        match self {
+            Self::V1 => schema,
+            Self::V2 => rename_schema(schema),
+        }
+    }
+
+    /// Respond with `body` in this version's casing, labelled with the version
+    pub fn json<T: Serialize>(self, mut response: HttpResponseBuilder, body: &T) -> HttpResponse {
+        let body = match serde_json::to_value(body) {
+            Ok(value) => self.adapt(value),
+            Err(e) => return ApiError::Internal(e.to_string()).error_response(),
+        };
+        response.insert_header((API_VERSION, self.number().to_string()));
+        if self == Self::V1 {
+            response.insert_header((DEPRECATION, "true"));
+        }
+        response.json(body)
+    }
+}
+
+fn version_2_name(key: &str) -> Option<&'static str> {
+    RENAMED_FIELDS.iter().find(|(v1, _)| *v1 == key).map(|(_, v2)| *v2)
+}
+
+fn rename_keys(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| (version_2_name(&key).map_or(key, str::to_string), rename_keys(value)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(rename_keys).collect()),
+        other => other,
+    }
+}
+
+fn rename_schema(schema: Value) -> Value {
+    match rename_keys(schema) {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| match (key.as_str(), value) {
+                    ("required", Value::Array(names)) => {
+                        let names = names
+                            .into_iter()
+                            .map(|name| match name.as_str().and_then(version_2_name) {
+                                Some(renamed) => Value::from(renamed),
+                                None => name,
+                            })
+                            .collect();
+                        (key, Value::Array(names))
+                    }
+                    (_, value) => (key, rename_schema(value)),
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(rename_schema).collect()),
+        other => other,
+    }
+}
+
+/// Version 1 field names used anywhere in a request body
+pub fn legacy_fields(payload: &str) -> Vec<&'static str> {
+    fn collect(value: &Value, found: &mut Vec<&'static str>) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    if let Some((v1, _)) = RENAMED_FIELDS.iter().find(|(v1, _)| v1 == key) {
+                        if !found.contains(v1) {
+                            found.push(v1);
+                        }
+                    }
+                    collect(value, found);
+                }
+            }
+            Value::Array(items) => items.iter().for_each(|item| collect(item, found)),
+            _ => {}
+        }
+    }
+
+    let mut found = Vec::new();
+    if let Ok(value) = serde_json::from_str::<Value>(payload) {
+        collect(&value, &mut found);
+    }
+    found
+}
+
+/// Count the version 1 names a request used and flag its response as relying on them
+pub fn record_legacy_fields(response: &mut HttpResponse, fields: &[&str]) {
+    if fields.is_empty() {
+        return;
+    }
+    for field in fields {
+        DEPRECATED_FIELDS_TOTAL.with_label_values(&[field]).inc();
+    }
+    response.headers_mut().insert(
+        actix_web::http::header::HeaderName::from_static(DEPRECATION),
+        HeaderValue::from_static("true"),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DeviceVitalsIngest, LatestVitals};
+    use actix_web::test::TestRequest;
+    use serde_json::json;
+
+    #[test]
+    fn test_version_from_header() {
+        let version = |value: Option<&str>| {
+            let mut req = TestRequest::default();
+            if let Some(value) = value {
+                req = req.insert_header((API_VERSION, value));
+            }
+            ApiVersion::from_request(&req.to_http_request())
+        };
+        assert_eq!(version(None).unwrap(), ApiVersion::V1);
+        assert_eq!(version(Some("1")).unwrap(), ApiVersion::V1);
+        assert_eq!(version(Some("2")).unwrap(), ApiVersion::V2);
+        assert!(version(Some("3")).is_err());
+    }
+
+    #[test]
+    fn test_version_2_is_snake_case() {
+        let vitals = LatestVitals {
+            heart_rate: 72,
+            spo2: 98,
+            temperature: 36.8,
+            timestamp: 1700000000,
+            quality_score: Some(0.9),
+            ml_alert: None,
+        };
+        let v1 = serde_json::to_value(&vitals).unwrap();
+        assert_eq!(v1["heartRate"], 72);
+
+        let v2 = ApiVersion::V2.adapt(json!({"status": "accepted", "vitals": v1}));
+        assert_eq!(v2["vitals"]["heart_rate"], 72);
+        assert!(v2["vitals"].get("heartRate").is_none());
+        assert_eq!(v2["vitals"]["quality_score"], json!(0.9f32));
+    }
+
+    #[test]
+    fn test_requests_accepted_in_either_casing() {
+        let legacy = r#"{"heartRate":72,"spo2":98,"temperature":36.8,"timestamp":1,"elevationChange":1.5}"#;
+        let snake = r#"{"heart_rate":72,"spo2":98,"temperature":36.8,"timestamp":1,"elevation_change":1.5}"#;
+        for payload in [legacy, snake] {
+            let body: DeviceVitalsIngest = serde_json::from_str(payload).unwrap();
+            assert_eq!((body.heart_rate, body.elevation_change), (72, Some(1.5)));
+        }
+        assert_eq!(legacy_fields(legacy), ["elevationChange", "heartRate"]);
+        assert!(legacy_fields(snake).is_empty());
+    }
+
+    #[test]
+    fn test_schema_renames_required_fields() {
+        let schema = serde_json::to_value(schemars::schema_for!(DeviceVitalsIngest)).unwrap();
+        assert!(schema["required"].as_array().unwrap().contains(&json!("heartRate")));
+
+        let v2 = ApiVersion::V2.adapt_schema(schema);
+        assert!(v2["required"].as_array().unwrap().contains(&json!("heart_rate")));
+        assert!(v2["properties"].get("heart_rate").is_some() && v2["properties"].get("heartRate").is_none());
+    }
+}
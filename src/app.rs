@@ -0,0 +1,177 @@
+use crate::auth::JwtAuth;
+use crate::config::Settings;
+use crate::database::{create_pool, create_reporting_pool, missing_indexes, run_migrations};
+use crate::device_secrets::DeviceSecrets;
+use crate::fhir_service::FhirService;
+use crate::handlers::AppState;
+use crate::middleware::{redacted_request_line, AuditLogger, CatchPanic, RequestId};
+use crate::ml_service::MlService;
+use crate::notifier::Notifier;
+use crate::phi_crypto::PhiCipher;
+use crate::rate_limit::RateLimit;
+use crate::negotiation::json_config;
+use crate::redis_cache::RedisCache;
+use crate::{event_log, routes, sse};
+use actix_cors::Cors;
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceFactory, ServiceRequest, ServiceResponse},
+    http::header,
+    middleware::Logger,
+    web, App, Error,
+};
+use anyhow::Context;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Connect to PostgreSQL and Redis, run migrations, and construct all services
+pub async fn init_state(settings: &Settings) -> anyhow::Result<AppState> {
+    // Create database pool
+    info!("Connecting to PostgreSQL...");
+    let pool = create_pool(&settings.database)
+        .await
+        .context("Failed to create database pool")?;
+
+    // Run migrations
+    info!("Running database migrations...");
+    run_migrations(&pool)
+        .await
+        .context("Failed to run migrations")?;
+
+    // Missing indexes only slow queries down, so warn rather than refuse to start
+    match missing_indexes(&pool).await {
+        Ok(missing) => {
+            for (table, index) in missing {
+                warn!("Expected index {} on {} is missing; queries on it will scan", index, table);
+            }
+        }
+        Err(e) => warn!("Could not check for missing indexes: {}", e),
+    }
+
+    let reporting_pool = create_reporting_pool(&pool, &settings.database, &settings.reporting)
+        .await
+        .context("Failed to create reporting pool")?;
+
+    // Create Redis cache
+    info!("Connecting to Redis...");
+    let redis = RedisCache::new(&settings.redis)
+        .await
+        .context("Failed to connect to Redis")?;
+
+    // Refuse to start with a key that cannot issue tokens
+    let jwt_auth = JwtAuth::new(&settings.jwt);
+    jwt_auth
+        .check_signing()
+        .context("JWT signing key is misconfigured")?;
+
+    let phi = PhiCipher::new(pool.clone(), &settings.encryption).context("PHI encryption is misconfigured")?;
+    if phi.enabled() {
+        info!("PHI encryption enabled with per-organization keys");
+    }
+
+    let device_secrets = DeviceSecrets::new(&settings.device).context("Device secret encryption is misconfigured")?;
+
+    let notifier = Arc::new(
+        Notifier::new(pool.clone())
+            .with_contact_webhook(&settings.emergency)
+            .with_voice(&settings.voice)
+            .with_routing(&settings.alert_routing)
+            .with_channels(&settings.notifications)
+            .with_consumer_notifications(settings.deployment.consumer_notifications),
+    );
+
+    crate::metrics::init_metrics().context("Failed to register metrics")?;
+
+    let sse_broadcaster = sse::create_broadcaster();
+    let recent_events = sse::RecentEvents::record(&sse_broadcaster);
+    event_log::record(&sse_broadcaster, pool.clone());
+
+    Ok(AppState {
+        pool,
+        reporting_pool,
+        redis: Arc::new(RwLock::new(redis)),
+        jwt_auth: Arc::new(jwt_auth),
+        ml_service: Arc::new(MlService::new(settings.ml.clone())),
+        fhir_service: Arc::new(FhirService::new(settings.fhir.clone())),
+        sse_broadcaster,
+        recent_events,
+        notifier,
+        device_secrets: Arc::new(device_secrets),
+        replay_window_seconds: settings.device.replay_window_seconds,
+        pairing_code_ttl_minutes: settings.device.pairing_code_ttl_minutes,
+        cors: settings.cors.clone(),
+        deployment: settings.deployment.clone(),
+        voice: settings.voice.clone(),
+        retention: settings.retention.clone(),
+        query_debug: settings.query_debug.clone(),
+        quota: settings.quota.clone(),
+        slo: settings.slo.clone(),
+        reporting: settings.reporting.clone(),
+        observability: settings.observability.clone(),
+        rate_limit: settings.rate_limit.clone(),
+        lockout: settings.lockout.clone(),
+        phi: Arc::new(phi),
+    })
+}
+
+/// Build the application (middleware, shared data, routes) around an initialized state.
+///
+/// Called once per worker by `main` and directly by the integration tests, so both
+/// always run the identical app.
+pub fn build_app(
+    state: web::Data<AppState>,
+) -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = Error,
+        InitError = (),
+    >,
+> {
+    // CORS configuration
+    let mut cors = Cors::default()
+        .allowed_methods(vec!["GET", "HEAD", "POST", "OPTIONS"])
+        .allowed_headers(vec![
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            header::ACCEPT,
+        ])
+        .supports_credentials()
+        .max_age(3600);
+
+    if state.cors.allow_any_origin {
+        cors = cors.allow_any_origin();
+    }
+    for origin in &state.cors.allowed_origins {
+        cors = cors.allowed_origin(origin);
+    }
+
+    let pool = state.pool.clone();
+    let sse_broadcaster = state.sse_broadcaster.clone();
+
+    App::new()
+        // Middleware
+        // Innermost, so refused requests are still logged, audited and given a request id
+        .wrap(RateLimit)
+        .wrap(sentry_actix::Sentry::new())
+        // Outside Sentry, whose 5xx capture would repeat the panic, and inside the rest,
+        // so its 500s still get audited and a request id
+        .wrap(CatchPanic)
+        // Logger::default's format, minus stream tokens passed as `?token=`
+        .wrap(
+            Logger::new(r#"%a "%{request_line}xi" %s %b "%{Referer}i" "%{User-Agent}i" %T"#)
+                .custom_request_replace("request_line", redacted_request_line),
+        )
+        .wrap(AuditLogger)
+        .wrap(RequestId)
+        .wrap(cors)
+        // App state
+        .app_data(state)
+        .app_data(web::Data::new(pool))
+        .app_data(web::Data::new(sse_broadcaster))
+        .app_data(json_config())
+        // Routes (central registry)
+        .configure(routes::configure)
+}
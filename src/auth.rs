@@ -1,20 +1,25 @@
-use crate::config::JwtConfig;
+use crate::config::{JwtConfig, LockoutConfig};
 use crate::models::Claims;
+use crate::rbac::Role;
 use anyhow::{anyhow, Result};
-use chrono::Utc;
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use sha2::{Digest, Sha256};
 use sqlx::PgPool;
 use uuid::Uuid;
 
+type HmacSha256 = Hmac<Sha256>;
+
 pub struct JwtAuth {
+    header: Header,
     encoding_key: EncodingKey,
     decoding_key: DecodingKey,
     validation: Validation,
     expiration_hours: i64,
-    refresh_token_days: i64,
 }
 
-
 impl JwtAuth {
     pub fn new(config: &JwtConfig) -> Self {
         let encoding_key = EncodingKey::from_secret(config.secret.as_bytes());
@@ -22,32 +27,44 @@ impl JwtAuth {
         let validation = Validation::default();
 
         Self {
+            header: Header::default(),
             encoding_key,
             decoding_key,
             validation,
             expiration_hours: config.expiration_hours,
-            refresh_token_days: config.refresh_token_days,
         }
     }
 
     /// Generate a new JWT token for a user
-    pub fn generate_token(&self, user_id: Uuid, email: &str, role: &str) -> Result<String> {
+    pub fn generate_token(&self, user_id: Uuid, email: &str, role: Role, org: Option<Uuid>) -> Result<String> {
+        self.sign(user_id, email, role, org, self.expiration_hours * 3600)
+    }
+
+    fn sign(&self, user_id: Uuid, email: &str, role: Role, org: Option<Uuid>, ttl_seconds: i64) -> Result<String> {
         let now = Utc::now().timestamp();
-        let exp = now + (self.expiration_hours * 3600);
+        let exp = now + ttl_seconds;
 
         let claims = Claims {
             sub: email.to_string(),
             user_id,
-            role: role.to_string(),
+            role,
+            org,
             exp,
             iat: now,
             jti: Uuid::new_v4(),
         };
 
-        encode(&Header::default(), &claims, &self.encoding_key)
+        encode(&self.header, &claims, &self.encoding_key)
             .map_err(|e| anyhow!("Token generation failed: {}", e))
     }
 
+    /// Sign and validate a throwaway token so a bad key is caught at startup, not at first login
+    pub fn check_signing(&self) -> Result<()> {
+        let token = self.sign(Uuid::nil(), "signing-check", Role::Viewer, None, 60)?;
+        self.validate_token(&token)?;
+        Ok(())
+    }
+
     /// Validate and decode a JWT token
     pub fn validate_token(&self, token: &str) -> Result<Claims> {
         decode::<Claims>(token, &self.decoding_key, &self.validation)
@@ -92,6 +109,76 @@ pub fn extract_bearer_token(auth_header: Option<&str>) -> Result<String> {
     }
 }
 
+/// Whether an `Authorization: Basic` header carries exactly these credentials.
+/// Digests are compared so the time taken doesn't reveal how much of a guess was right.
+pub fn basic_credentials_match(auth_header: Option<&str>, username: &str, password: &str) -> bool {
+    let Some(decoded) = auth_header
+        .and_then(|header| header.strip_prefix("Basic "))
+        .and_then(|encoded| general_purpose::STANDARD.decode(encoded.trim()).ok())
+    else {
+        return false;
+    };
+    Sha256::digest(&decoded) == Sha256::digest(format!("{}:{}", username, password))
+}
+
+/// How long to lock an account after its `failed_attempts`th wrong password in a row, if at
+/// all: `lock_minutes` at the threshold, doubling with every failure after it
+pub fn lockout_duration(config: &LockoutConfig, failed_attempts: i32) -> Option<Duration> {
+    let over = i64::from(failed_attempts) - i64::from(config.max_failed_attempts);
+    if over < 0 {
+        return None;
+    }
+    let minutes = config.lock_minutes.saturating_mul(1i64 << over.min(32)).min(config.max_lock_minutes);
+    Some(Duration::minutes(minutes))
+}
+
+/// The authentication headers sent with every device request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceAuthHeaders<'a> {
+    pub device_id: &'a str,
+    pub timestamp: i64,
+    pub signature: &'a str,
+}
+
+impl<'a> DeviceAuthHeaders<'a> {
+    /// Parse raw `X-Device-Id`, `X-Timestamp` and `X-Signature` values; the error names the bad header
+    pub fn parse(
+        device_id: Option<&'a str>,
+        timestamp: Option<&'a str>,
+        signature: Option<&'a str>,
+    ) -> Result<Self, &'static str> {
+        let device_id = device_id.filter(|id| !id.is_empty()).ok_or("Missing X-Device-Id")?;
+        let timestamp = timestamp
+            .and_then(|s| s.trim().parse::<i64>().ok())
+            .ok_or("Missing X-Timestamp")?;
+        let signature = signature.filter(|sig| !sig.is_empty()).ok_or("Missing X-Signature")?;
+        Ok(Self { device_id, timestamp, signature })
+    }
+}
+
+/// Replay protection: is the device timestamp within `window_seconds` of `now`?
+pub fn timestamp_within_window(now: i64, timestamp: i64, window_seconds: i64) -> bool {
+    // abs_diff: timestamps come straight from a header and may sit at the i64 extremes
+    now.abs_diff(timestamp) <= window_seconds.unsigned_abs()
+}
+
+/// Base64 HMAC-SHA256 a device sends in `X-Signature`, over `"{timestamp}.{payload}"`
+pub fn device_signature(secret: &str, timestamp: i64, payload: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("{}.{}", timestamp, payload).as_bytes());
+    general_purpose::STANDARD.encode(mac.finalize().into_bytes())
+}
+
+/// Constant-time check of a device signature
+pub fn verify_device_signature(secret: &str, timestamp: i64, payload: &str, signature: &str) -> bool {
+    let Ok(signature) = general_purpose::STANDARD.decode(signature) else {
+        return false;
+    };
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("{}.{}", timestamp, payload).as_bytes());
+    mac.verify_slice(&signature).is_ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -108,14 +195,42 @@ mod tests {
         let auth = JwtAuth::new(&config);
         let user_id = Uuid::new_v4();
         let email = "test@example.com";
-        let role = "viewer";
+        let role = Role::Viewer;
+        let org = Some(Uuid::new_v4());
 
-        let token = auth.generate_token(user_id, email, role).expect("Token generation failed");
+        let token = auth.generate_token(user_id, email, role, org).expect("Token generation failed");
         let claims = auth.validate_token(&token).expect("Token validation failed");
 
         assert_eq!(claims.sub, email);
         assert_eq!(claims.user_id, user_id);
         assert_eq!(claims.role, role);
+        assert_eq!(claims.org, org);
+    }
+
+    #[test]
+    fn test_device_header_parsing() {
+        let headers = DeviceAuthHeaders::parse(Some("WALKER-1"), Some(" 1700000000"), Some("c2ln")).unwrap();
+        assert_eq!(headers.timestamp, 1700000000);
+        assert_eq!(headers.device_id, "WALKER-1");
+
+        assert_eq!(DeviceAuthHeaders::parse(None, Some("1"), Some("s")), Err("Missing X-Device-Id"));
+        assert_eq!(DeviceAuthHeaders::parse(Some("d"), Some("1e9"), Some("s")), Err("Missing X-Timestamp"));
+        assert_eq!(DeviceAuthHeaders::parse(Some("d"), Some("1"), Some("")), Err("Missing X-Signature"));
+        assert!(!verify_device_signature("secret", 1, "{}", "%%%"));
+
+        assert!(timestamp_within_window(1000, 940, 60));
+        assert!(!timestamp_within_window(1000, 939, 60));
+        assert!(!timestamp_within_window(1_700_000_000, i64::MIN, 60));
+    }
+
+    #[test]
+    fn test_basic_credentials_match() {
+        let header = format!("Basic {}", general_purpose::STANDARD.encode("prometheus:scrape-secret"));
+        assert!(basic_credentials_match(Some(&header), "prometheus", "scrape-secret"));
+        assert!(!basic_credentials_match(Some(&header), "prometheus", "scrape-secre"));
+        assert!(!basic_credentials_match(Some("Basic not-base64!"), "prometheus", "scrape-secret"));
+        assert!(!basic_credentials_match(Some("Bearer token"), "prometheus", "scrape-secret"));
+        assert!(!basic_credentials_match(None, "prometheus", "scrape-secret"));
     }
 
     #[test]
@@ -131,4 +246,31 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_lockout_backs_off_exponentially() {
+        let config = LockoutConfig { max_failed_attempts: 5, lock_minutes: 15, max_lock_minutes: 120 };
+        assert_eq!(lockout_duration(&config, 4), None);
+        assert_eq!(lockout_duration(&config, 5), Some(Duration::minutes(15)));
+        assert_eq!(lockout_duration(&config, 6), Some(Duration::minutes(30)));
+        assert_eq!(lockout_duration(&config, 8), Some(Duration::minutes(120)));
+        assert_eq!(lockout_duration(&config, i32::MAX), Some(Duration::minutes(120)));
+    }
+
+    #[test]
+    fn test_misconfigured_key_fails_without_panicking() {
+        let config = JwtConfig {
+            secret: "test_secret_key_minimum_32_chars_long_for_security".to_string(),
+            expiration_hours: 24,
+            refresh_token_days: 7,
+        };
+
+        let mut auth = JwtAuth::new(&config);
+        assert!(auth.check_signing().is_ok());
+
+        // An RSA algorithm paired with an HMAC secret cannot sign
+        auth.header = Header::new(jsonwebtoken::Algorithm::RS256);
+        assert!(auth.generate_token(Uuid::new_v4(), "a@b.com", Role::Viewer, None).is_err());
+        assert!(auth.check_signing().is_err());
+    }
 }
@@ -0,0 +1,80 @@
+use crate::models::Baseline;
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::time::Duration as StdDuration;
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// Trailing window each baseline covers
+pub const BASELINE_WINDOW_DAYS: i64 = 7;
+
+const REFRESH_INTERVAL: StdDuration = StdDuration::from_secs(3600);
+
+/// Recompute every walker's baseline from its readings in the trailing window, since it was
+/// claimed by its current patient. Readings with implausible values or poor signal are left
+/// out, as are walkers with no readings in the window, whose baselines are dropped.
+pub async fn refresh_baselines(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let window_start = Utc::now() - Duration::days(BASELINE_WINDOW_DAYS);
+    let mut tx = pool.begin().await?;
+
+    let refreshed = sqlx::query(
+        "INSERT INTO baselines (device_id, patient_id, hr_mean, hr_stddev, hr_samples,
+                                spo2_mean, spo2_stddev, spo2_samples, window_start, computed_at)
+         SELECT d.id, d.patient_id,
+                AVG(r.heart_rate) FILTER (WHERE r.heart_rate BETWEEN 30 AND 220)::real,
+                STDDEV_SAMP(r.heart_rate) FILTER (WHERE r.heart_rate BETWEEN 30 AND 220)::real,
+                COUNT(*) FILTER (WHERE r.heart_rate BETWEEN 30 AND 220)::int,
+                AVG(r.spo2) FILTER (WHERE r.spo2 BETWEEN 70 AND 100)::real,
+                STDDEV_SAMP(r.spo2) FILTER (WHERE r.spo2 BETWEEN 70 AND 100)::real,
+                COUNT(*) FILTER (WHERE r.spo2 BETWEEN 70 AND 100)::int,
+                GREATEST($1, d.claimed_at), now()
+         FROM devices d
+         JOIN sensor_readings r ON r.device_id = d.id
+         WHERE d.is_active
+           AND r.reading_timestamp >= GREATEST($1, d.claimed_at)
+           AND (r.quality_score IS NULL OR r.quality_score >= 0.5)
+         GROUP BY d.id, d.patient_id, d.claimed_at
+         ON CONFLICT (device_id) DO UPDATE
+         SET patient_id = EXCLUDED.patient_id,
+             hr_mean = EXCLUDED.hr_mean, hr_stddev = EXCLUDED.hr_stddev, hr_samples = EXCLUDED.hr_samples,
+             spo2_mean = EXCLUDED.spo2_mean, spo2_stddev = EXCLUDED.spo2_stddev, spo2_samples = EXCLUDED.spo2_samples,
+             window_start = EXCLUDED.window_start, computed_at = EXCLUDED.computed_at"
+    )
+    .bind(window_start)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    // now() is the transaction's start, so this leaves exactly the rows written above
+    sqlx::query("DELETE FROM baselines WHERE computed_at < now()")
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(refreshed)
+}
+
+/// The walker's baseline, if it was computed for the patient it belongs to now
+pub async fn load_baseline(pool: &PgPool, device_id: Uuid, patient_id: Option<Uuid>) -> Result<Option<Baseline>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM baselines WHERE device_id = $1 AND patient_id IS NOT DISTINCT FROM $2")
+        .bind(device_id)
+        .bind(patient_id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Background worker recomputing baselines every hour
+pub fn spawn_baseline_worker(pool: PgPool) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let started = std::time::Instant::now();
+            match refresh_baselines(&pool).await {
+                Ok(count) => info!("Refreshed {} vitals baselines in {:?}", count, started.elapsed()),
+                Err(e) => error!("Baseline refresh failed: {}", e),
+            }
+        }
+    })
+}
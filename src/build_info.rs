@@ -0,0 +1,47 @@
+//! What this binary is: version, commit and build details embedded by `build.rs`.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Short commit hash, when the build could tell
+pub const GIT_SHA: Option<&str> = option_env!("BUILD_GIT_SHA");
+
+/// Served by `GET /version`
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildInfo {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub git_sha: Option<&'static str>,
+    pub built_at: Option<DateTime<Utc>>,
+    /// Enabled cargo features
+    pub features: Vec<&'static str>,
+    /// `debug` or `release`
+    pub profile: &'static str,
+    pub rustc: &'static str,
+}
+
+pub fn build_info() -> BuildInfo {
+    BuildInfo {
+        name: env!("CARGO_PKG_NAME"),
+        version: VERSION,
+        git_sha: GIT_SHA.filter(|sha| !sha.is_empty()),
+        built_at: env!("BUILD_TIMESTAMP").parse().ok().and_then(|secs| DateTime::from_timestamp(secs, 0)),
+        features: env!("BUILD_FEATURES").split(',').filter(|f| !f.is_empty()).collect(),
+        profile: env!("BUILD_PROFILE"),
+        rustc: env!("BUILD_RUSTC"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_info_embedded() {
+        let info = build_info();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(info.built_at.is_some());
+        assert!(info.rustc.starts_with("rustc "));
+    }
+}
@@ -0,0 +1,323 @@
+//! FHIR bulk data export (`$export`).
+//!
+//! A kick-off records a job and returns at once. The job runs in the background, streaming
+//! rows out of the database into NDJSON files of at most [`FILE_RESOURCES`] resources each.
+//! Files are stored in `fhir_export_files` so any instance can serve them, and are deleted
+//! with their job [`EXPORT_TTL_HOURS`] after it finishes.
+
+use crate::fhir_service::FhirService;
+use crate::models::{BulkExportFile, BulkExportJob, Device, Patient, SensorReading};
+use chrono::SecondsFormat;
+use futures::TryStreamExt;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// Resource types a bulk export can hold, in the order they are written
+pub const RESOURCE_TYPES: &[&str] = &["Patient", "Device", "Observation"];
+pub const FHIR_NDJSON: &str = "application/fhir+ndjson";
+/// `_outputFormat` values meaning NDJSON, as the bulk data specification allows
+pub const OUTPUT_FORMATS: &[&str] = &[FHIR_NDJSON, "application/ndjson", "ndjson"];
+/// Resources per NDJSON file; larger exports are split into parts
+pub const FILE_RESOURCES: usize = 10_000;
+pub const EXPORT_TTL_HOURS: i64 = 24;
+/// A job still in progress after this long was cut off by a restart
+const STALE_AFTER_HOURS: i64 = 6;
+
+/// The resource types named in `_type`, in [`RESOURCE_TYPES`] order; all of them without one
+pub fn parse_types(types: Option<&str>) -> Result<Vec<String>, String> {
+    let Some(types) = types.filter(|t| !t.trim().is_empty()) else {
+        return Ok(RESOURCE_TYPES.iter().map(|t| t.to_string()).collect());
+    };
+    let requested: Vec<&str> = types.split(',').map(str::trim).collect();
+    if let Some(unknown) = requested.iter().find(|t| !RESOURCE_TYPES.contains(t)) {
+        return Err(format!("Unsupported _type '{}'; expected {}", unknown, RESOURCE_TYPES.join(", ")));
+    }
+    Ok(RESOURCE_TYPES
+        .iter()
+        .filter(|t| requested.contains(t))
+        .map(|t| t.to_string())
+        .collect())
+}
+
+/// Record a new job, clearing out expired jobs and ones a restart left unfinished
+pub async fn create_job(
+    pool: &PgPool,
+    requested_by: Uuid,
+    organization_id: Option<Uuid>,
+    resource_types: &[String],
+    since: Option<chrono::DateTime<chrono::Utc>>,
+) -> Result<BulkExportJob, sqlx::Error> {
+    sqlx::query("DELETE FROM fhir_export_jobs WHERE expires_at < now()")
+        .execute(pool)
+        .await?;
+    sqlx::query(
+        "UPDATE fhir_export_jobs
+         SET status = 'failed', error = 'Export was interrupted', completed_at = now(),
+             expires_at = now() + make_interval(hours => $1::int)
+         WHERE status = 'in_progress' AND requested_at < now() - make_interval(hours => $2::int)"
+    )
+    .bind(EXPORT_TTL_HOURS)
+    .bind(STALE_AFTER_HOURS)
+    .execute(pool)
+    .await?;
+
+    sqlx::query_as(
+        "INSERT INTO fhir_export_jobs (requested_by, organization_id, resource_types, since)
+         VALUES ($1, $2, $3, $4) RETURNING *"
+    )
+    .bind(requested_by)
+    .bind(organization_id)
+    .bind(resource_types)
+    .bind(since)
+    .fetch_one(pool)
+    .await
+}
+
+/// Run `job` in the background, marking it completed or failed when it ends
+pub fn spawn_job(pool: PgPool, fhir: Arc<FhirService>, job: BulkExportJob) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let result = run_job(&pool, &fhir, &job).await;
+        let error = match &result {
+            Ok(()) => {
+                info!(job_id = %job.id, "FHIR bulk export completed");
+                None
+            }
+            Err(e) => {
+                error!(job_id = %job.id, "FHIR bulk export failed: {}", e);
+                Some("Export failed; see the server log".to_string())
+            }
+        };
+        let finished = sqlx::query(
+            "UPDATE fhir_export_jobs
+             SET status = CASE WHEN $2::text IS NULL THEN 'completed' ELSE 'failed' END, error = $2,
+                 progress = NULL, completed_at = now(), expires_at = now() + make_interval(hours => $3::int)
+             WHERE id = $1"
+        )
+        .bind(job.id)
+        .bind(error)
+        .bind(EXPORT_TTL_HOURS)
+        .execute(&pool)
+        .await;
+        if let Err(e) = finished {
+            error!(job_id = %job.id, "Failed to record the end of a FHIR bulk export: {}", e);
+        }
+    })
+}
+
+async fn run_job(pool: &PgPool, fhir: &FhirService, job: &BulkExportJob) -> Result<(), sqlx::Error> {
+    for resource_type in &job.resource_types {
+        sqlx::query("UPDATE fhir_export_jobs SET progress = $2 WHERE id = $1")
+            .bind(job.id)
+            .bind(resource_type)
+            .execute(pool)
+            .await?;
+
+        let mut file = NdjsonFile::new(job.id, resource_type);
+        match resource_type.as_str() {
+            "Patient" => {
+                let mut rows = sqlx::query_as::<_, Patient>(
+                    "SELECT id, display_name, date_of_birth FROM patients
+                     WHERE $1::uuid IS NULL OR organization_id = $1 ORDER BY id"
+                )
+                .bind(job.organization_id)
+                .fetch(pool);
+                while let Some(patient) = rows.try_next().await? {
+                    file.push(pool, &fhir.create_patient_resource(&patient)).await?;
+                }
+            }
+            "Device" => {
+                let mut rows = sqlx::query_as::<_, Device>(
+                    "SELECT * FROM devices WHERE $1::uuid IS NULL OR organization_id = $1 ORDER BY id"
+                )
+                .bind(job.organization_id)
+                .fetch(pool);
+                while let Some(device) = rows.try_next().await? {
+                    file.push(pool, &fhir.create_device_resource(&device)).await?;
+                }
+            }
+            _ => {
+                // Observations name their patient through the walker that took the reading
+                let patients: HashMap<Uuid, Option<Uuid>> = sqlx::query_as("SELECT id, patient_id FROM devices")
+                    .fetch_all(pool)
+                    .await?
+                    .into_iter()
+                    .collect();
+                let mut rows = sqlx::query_as::<_, SensorReading>(
+                    "SELECT * FROM sensor_readings
+                     WHERE ($1::timestamptz IS NULL OR received_at >= $1)
+                       AND ($2::uuid IS NULL OR device_id IN (SELECT id FROM devices WHERE organization_id = $2))
+                     ORDER BY id"
+                )
+                .bind(job.since)
+                .bind(job.organization_id)
+                .fetch(pool);
+                while let Some(reading) = rows.try_next().await? {
+                    let subject = patients.get(&reading.device_id).copied().flatten().map(|id| format!("Patient/{}", id));
+                    for observation in fhir.create_observations(&reading, subject) {
+                        file.push(pool, &observation).await?;
+                    }
+                }
+            }
+        }
+        file.flush(pool).await?;
+    }
+    Ok(())
+}
+
+/// The file of one resource type being written, stored each time it fills up
+struct NdjsonFile<'a> {
+    job_id: Uuid,
+    resource_type: &'a str,
+    part: i32,
+    count: usize,
+    ndjson: String,
+}
+
+impl<'a> NdjsonFile<'a> {
+    fn new(job_id: Uuid, resource_type: &'a str) -> Self {
+        Self { job_id, resource_type, part: 1, count: 0, ndjson: String::new() }
+    }
+
+    async fn push(&mut self, pool: &PgPool, resource: &Value) -> Result<(), sqlx::Error> {
+        self.ndjson.push_str(&resource.to_string());
+        self.ndjson.push('\n');
+        self.count += 1;
+        if self.count == FILE_RESOURCES {
+            self.flush(pool).await?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        if self.count == 0 {
+            return Ok(());
+        }
+        sqlx::query(
+            "INSERT INTO fhir_export_files (job_id, resource_type, part, resource_count, ndjson) VALUES ($1, $2, $3, $4, $5)"
+        )
+        .bind(self.job_id)
+        .bind(self.resource_type)
+        .bind(self.part)
+        .bind(self.count as i32)
+        .bind(&self.ndjson)
+        .execute(pool)
+        .await?;
+        self.part += 1;
+        self.count = 0;
+        self.ndjson.clear();
+        Ok(())
+    }
+}
+
+/// A job that has not yet expired
+pub async fn load_job(pool: &PgPool, id: Uuid) -> Result<Option<BulkExportJob>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM fhir_export_jobs WHERE id = $1 AND (expires_at IS NULL OR expires_at > now())")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// The contents of one output file of a completed, unexpired job
+pub async fn load_file(pool: &PgPool, job_id: Uuid, resource_type: &str, part: i32) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT f.ndjson FROM fhir_export_files f JOIN fhir_export_jobs j ON j.id = f.job_id
+         WHERE f.job_id = $1 AND f.resource_type = $2 AND f.part = $3
+           AND j.status = 'completed' AND j.expires_at > now()"
+    )
+    .bind(job_id)
+    .bind(resource_type)
+    .bind(part)
+    .fetch_optional(pool)
+    .await
+}
+
+/// The completion manifest of the bulk data specification. `base` is the `/api/fhir` URL
+/// the kick-off and file URLs hang off.
+pub async fn manifest(pool: &PgPool, job: &BulkExportJob, base: &str) -> Result<Value, sqlx::Error> {
+    let files: Vec<BulkExportFile> = sqlx::query_as(
+        "SELECT resource_type, part, resource_count FROM fhir_export_files
+         WHERE job_id = $1 ORDER BY array_position($2::text[], resource_type), part"
+    )
+    .bind(job.id)
+    .bind(RESOURCE_TYPES)
+    .fetch_all(pool)
+    .await?;
+    Ok(manifest_for(job, &files, base))
+}
+
+fn manifest_for(job: &BulkExportJob, files: &[BulkExportFile], base: &str) -> Value {
+    let mut request = format!("{}/$export?_type={}", base, job.resource_types.join(","));
+    if let Some(since) = job.since {
+        request.push_str(&format!("&_since={}", since.to_rfc3339_opts(SecondsFormat::Micros, true)));
+    }
+    let output: Vec<Value> = files
+        .iter()
+        .map(|file| {
+            json!({
+                "type": file.resource_type,
+                "url": format!("{}/export-files/{}/{}/{}", base, job.id, file.resource_type, file.part),
+                "count": file.resource_count,
+            })
+        })
+        .collect();
+
+    json!({
+        "transactionTime": job.requested_at.to_rfc3339_opts(SecondsFormat::Micros, true),
+        "request": request,
+        "requiresAccessToken": true,
+        "output": output,
+        "error": [],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_parse_types() {
+        assert_eq!(parse_types(None).unwrap(), RESOURCE_TYPES);
+        assert_eq!(parse_types(Some("Observation, Patient")).unwrap(), ["Patient", "Observation"]);
+        assert!(parse_types(Some("Patient,Encounter")).is_err());
+    }
+
+    #[test]
+    fn test_manifest_lists_every_part() {
+        let requested_at = Utc.with_ymd_and_hms(2026, 5, 1, 12, 0, 0).unwrap();
+        let job = BulkExportJob {
+            id: Uuid::nil(),
+            requested_by: None,
+            organization_id: None,
+            resource_types: vec!["Device".into(), "Observation".into()],
+            since: Some(requested_at - chrono::Duration::days(1)),
+            status: "completed".into(),
+            progress: None,
+            error: None,
+            requested_at,
+            completed_at: Some(requested_at),
+            expires_at: None,
+        };
+        let file = |resource_type: &str, part, resource_count| BulkExportFile {
+            resource_type: resource_type.into(),
+            part,
+            resource_count,
+        };
+        let base = "https://ehr.example/api/fhir";
+
+        let manifest = manifest_for(&job, &[file("Device", 1, 3), file("Observation", 1, 10_000), file("Observation", 2, 7)], base);
+        assert_eq!(manifest["transactionTime"], "2026-05-01T12:00:00.000000Z");
+        assert_eq!(
+            manifest["request"],
+            "https://ehr.example/api/fhir/$export?_type=Device,Observation&_since=2026-04-30T12:00:00.000000Z"
+        );
+        let output = manifest["output"].as_array().unwrap();
+        assert_eq!(output.len(), 3);
+        assert_eq!(output[2]["url"], format!("{}/export-files/{}/Observation/2", base, Uuid::nil()));
+        assert_eq!(output[2]["count"], 7);
+    }
+}
@@ -0,0 +1,179 @@
+//! Startup warm-up of the latest-vitals cache.
+//!
+//! `vitals:latest`, each patient's `vitals:latest:{patient}` and the recent readings list only
+//! fill as readings arrive, so after a Redis restart dashboards have nothing cached until some
+//! walker reports again. At startup they are rebuilt from the newest readings of active
+//! devices, as ingestion would have cached them. Patients with no reading among those fall
+//! back to the database until their walker next reports.
+
+use crate::ml_service::{MlAnalysisResult, MlService};
+use crate::models::{AlertLevel, Classification, LatestVitals};
+use crate::redis_cache::{RedisCache, MAX_RECENT_READINGS};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgPool};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{error, info};
+use uuid::Uuid;
+
+#[derive(Debug, FromRow)]
+struct RecentReading {
+    patient_id: Option<Uuid>,
+    heart_rate: Option<i32>,
+    spo2: Option<i32>,
+    temperature: Option<f32>,
+    reading_timestamp: DateTime<Utc>,
+    anomaly_detected: Option<bool>,
+    anomaly_score: Option<f32>,
+    classification: Option<Classification>,
+    alert_level: Option<AlertLevel>,
+    analysis_details: Option<serde_json::Value>,
+}
+
+impl RecentReading {
+    /// The cache entry ingestion wrote for this reading
+    fn to_vitals(&self, ml: &MlService) -> LatestVitals {
+        let (heart_rate, spo2, temperature) =
+            (self.heart_rate.unwrap_or(0), self.spo2.unwrap_or(0), self.temperature.unwrap_or(0.0));
+        let quality_score = ml.assess_signal_quality(heart_rate, spo2, temperature);
+        let analysis = self.alert_level.map(|alert_level| MlAnalysisResult {
+            anomaly_detected: self.anomaly_detected.unwrap_or(false),
+            anomaly_score: self.anomaly_score.unwrap_or(0.0),
+            classification: self.classification.unwrap_or(Classification::Normal),
+            alert_level,
+            quality_score,
+            details: self.analysis_details.clone().unwrap_or_default(),
+        });
+        LatestVitals {
+            heart_rate,
+            spo2,
+            temperature,
+            timestamp: self.reading_timestamp.timestamp(),
+            quality_score: Some(quality_score),
+            ml_alert: analysis.and_then(|a| ml.generate_alert(&a)).map(|a| a.level),
+        }
+    }
+}
+
+/// The newest readings of active devices, newest first, with the patient of each walker
+pub async fn recent_vitals(pool: &PgPool, ml: &MlService) -> Result<Vec<(Option<Uuid>, LatestVitals)>, sqlx::Error> {
+    let readings: Vec<RecentReading> = sqlx::query_as(
+        "SELECT d.patient_id, r.heart_rate, r.spo2, r.temperature, r.reading_timestamp,
+                a.anomaly_detected, a.anomaly_score, a.classification, a.alert_level, a.analysis_details
+         FROM sensor_readings r
+         JOIN devices d ON d.id = r.device_id AND d.is_active
+         LEFT JOIN ml_analysis a ON a.sensor_reading_id = r.id
+         ORDER BY r.reading_timestamp DESC, r.id DESC
+         LIMIT $1"
+    )
+    .bind(MAX_RECENT_READINGS as i64)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(readings.iter().map(|r| (r.patient_id, r.to_vitals(ml))).collect())
+}
+
+/// The first, i.e. newest, of `newest_first` for each patient
+fn newest_per_patient(newest_first: &[(Option<Uuid>, LatestVitals)]) -> Vec<(Uuid, LatestVitals)> {
+    let mut seen = HashSet::new();
+    newest_first
+        .iter()
+        .filter_map(|(patient_id, vitals)| patient_id.filter(|id| seen.insert(*id)).map(|id| (id, vitals.clone())))
+        .collect()
+}
+
+/// Fill whichever of the cached latest vitals, patients' latest vitals and recent readings is
+/// empty; returns the number of readings loaded, or 0 when the cache was already populated
+pub async fn warm_latest_vitals(pool: &PgPool, redis: &RwLock<RedisCache>, ml: &MlService) -> Result<usize> {
+    let recent = recent_vitals(pool, ml).await?;
+    let by_patient = newest_per_patient(&recent);
+    let vitals: Vec<LatestVitals> = recent.into_iter().map(|(_, vitals)| vitals).collect();
+    let written = redis.write().await.warm_latest_vitals(&vitals, &by_patient).await?;
+    Ok(if written { vitals.len() } else { 0 })
+}
+
+/// Background task warming the cache once at startup
+pub fn spawn_warmup(pool: PgPool, redis: Arc<RwLock<RedisCache>>, ml: Arc<MlService>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        match warm_latest_vitals(&pool, &redis, &ml).await {
+            Ok(0) => {}
+            Ok(count) => info!("Warmed the latest vitals cache with {} reading(s)", count),
+            Err(e) => error!("Latest vitals cache warm-up failed: {}", e),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MlConfig;
+    use crate::models::SensorReading;
+    use serde_json::json;
+
+    #[test]
+    fn test_rebuilt_vitals_match_what_ingestion_cached() {
+        let ml = MlService::new(MlConfig {
+            anomaly_threshold: 0.5,
+            enable_alerts: true,
+            critical_hr_low: 40,
+            critical_hr_high: 180,
+            critical_spo2_low: 88,
+            alert_cooldown_seconds: 300,
+            model: None,
+        });
+        let reading = SensorReading {
+            id: 1,
+            device_id: uuid::Uuid::new_v4(),
+            heart_rate: Some(190),
+            spo2: Some(85),
+            temperature: Some(36.9),
+            reading_timestamp: Utc::now(),
+            received_at: Utc::now(),
+            quality_score: None,
+            metadata: json!({}),
+        };
+        let analysis = ml.analyze_reading(&reading);
+        let stored = RecentReading {
+            patient_id: None,
+            heart_rate: reading.heart_rate,
+            spo2: reading.spo2,
+            temperature: reading.temperature,
+            reading_timestamp: reading.reading_timestamp,
+            anomaly_detected: Some(analysis.anomaly_detected),
+            anomaly_score: Some(analysis.anomaly_score),
+            classification: Some(analysis.classification),
+            alert_level: Some(analysis.alert_level),
+            analysis_details: Some(analysis.details.clone()),
+        };
+
+        let vitals = stored.to_vitals(&ml);
+        assert_eq!((vitals.heart_rate, vitals.spo2), (190, 85));
+        assert_eq!(vitals.timestamp, reading.reading_timestamp.timestamp());
+        assert_eq!(vitals.quality_score, Some(analysis.quality_score));
+        assert_eq!(vitals.ml_alert, Some(AlertLevel::Critical));
+
+        // Readings never analysed carry no alert
+        let unanalysed = RecentReading { alert_level: None, ..stored };
+        assert_eq!(unanalysed.to_vitals(&ml).ml_alert, None);
+    }
+
+    #[test]
+    fn test_each_patient_warmed_with_their_newest_reading() {
+        let vitals = |timestamp| LatestVitals {
+            heart_rate: 70,
+            spo2: 97,
+            temperature: 36.6,
+            timestamp,
+            quality_score: None,
+            ml_alert: None,
+        };
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let newest_first = [(Some(a), vitals(4)), (None, vitals(3)), (Some(b), vitals(2)), (Some(a), vitals(1))];
+
+        let by_patient: Vec<(Uuid, i64)> =
+            newest_per_patient(&newest_first).into_iter().map(|(id, v)| (id, v.timestamp)).collect();
+        assert_eq!(by_patient, vec![(a, 4), (b, 2)]);
+    }
+}
@@ -0,0 +1,340 @@
+use crate::emergency_service::notify_care_team;
+use crate::models::{Alert, CarePlan, CarePlanEvaluation};
+use crate::notifier::Notifier;
+use anyhow::Result;
+use chrono::{Duration, NaiveDate, Utc};
+use sqlx::{FromRow, PgPool};
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+use tracing::{error, info};
+
+/// Share of heart-rate samples that must fall inside the plan's range for the day to count
+pub const HR_IN_RANGE_TARGET: f32 = 0.9;
+
+/// How often the worker re-evaluates; runs are idempotent per plan and day
+const EVALUATION_INTERVAL: StdDuration = StdDuration::from_secs(3600);
+
+/// One patient-day of walker data, aggregated across all of the patient's devices
+#[derive(Debug, Clone, Default, FromRow)]
+pub struct DailyActivity {
+    pub readings: i64,
+    pub steps: Option<i64>,
+    pub activity_minutes: i64,
+    pub avg_heart_rate: Option<f64>,
+    pub min_spo2: Option<i32>,
+    pub hr_in_range_ratio: Option<f64>,
+    /// Minutes of walking, stairs or standing from the activity classifier
+    pub usage_minutes: i64,
+}
+
+/// Score a day against the plan's targets; a goal without a target (or without data) is `None`
+pub fn evaluate(plan: &CarePlan, date: NaiveDate, activity: &DailyActivity) -> CarePlanEvaluation {
+    let has_data = activity.readings > 0;
+    let steps = activity.steps.map(|s| s.min(i64::from(i32::MAX)) as i32);
+    let activity_minutes = has_data.then_some(activity.activity_minutes as i32);
+    let hr_in_range_ratio = activity.hr_in_range_ratio.map(|r| r as f32);
+    let usage_minutes = has_data.then_some(activity.usage_minutes.min(1440) as i32);
+
+    let steps_met = plan.target_steps.zip(steps).map(|(target, s)| s >= target);
+    let activity_met = plan
+        .target_activity_minutes
+        .zip(activity_minutes)
+        .map(|(target, m)| m >= target);
+    let usage_met = plan
+        .prescribed_usage_minutes
+        .zip(usage_minutes)
+        .map(|(prescribed, m)| m >= prescribed);
+
+    let hr_met = match (plan.hr_min.is_some() || plan.hr_max.is_some(), hr_in_range_ratio) {
+        (true, Some(ratio)) => Some(ratio >= HR_IN_RANGE_TARGET),
+        _ => None,
+    };
+    let spo2_met = plan.spo2_min.zip(activity.min_spo2).map(|(min, s)| s >= min);
+    let vitals_met = match (hr_met, spo2_met) {
+        (None, None) => None,
+        (hr, spo2) => Some(hr.unwrap_or(true) && spo2.unwrap_or(true)),
+    };
+
+    CarePlanEvaluation {
+        care_plan_id: plan.id,
+        eval_date: date,
+        readings: activity.readings as i32,
+        steps,
+        activity_minutes,
+        avg_heart_rate: activity.avg_heart_rate.map(|v| v as f32),
+        min_spo2: activity.min_spo2,
+        hr_in_range_ratio,
+        usage_minutes,
+        steps_met,
+        activity_met,
+        vitals_met,
+        usage_met,
+    }
+}
+
+/// Aggregate a patient's readings for one UTC day
+pub async fn load_daily_activity(pool: &PgPool, plan: &CarePlan, date: NaiveDate) -> Result<DailyActivity> {
+    let start = date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+    let end = start + Duration::days(1);
+
+    let activity = sqlx::query_as::<_, DailyActivity>(
+        "SELECT COUNT(*) AS readings,
+                SUM((r.metadata->>'steps')::bigint)::bigint AS steps,
+                COUNT(DISTINCT date_trunc('minute', r.reading_timestamp))
+                    FILTER (WHERE (r.metadata->>'steps')::int > 0) AS activity_minutes,
+                AVG(r.heart_rate)::float8 AS avg_heart_rate,
+                MIN(r.spo2) AS min_spo2,
+                (COUNT(r.heart_rate) FILTER (WHERE r.heart_rate BETWEEN COALESCE($4, 0) AND COALESCE($5, 300)))::float8
+                    / NULLIF(COUNT(r.heart_rate), 0) AS hr_in_range_ratio,
+                (SELECT COALESCE(SUM(EXTRACT(EPOCH FROM s.ended_at - s.started_at)) / 60, 0)::bigint
+                 FROM activity_segments s
+                 WHERE s.patient_id = $1 AND s.started_at >= $2 AND s.started_at < $3
+                   AND s.activity <> 'sitting') AS usage_minutes
+         FROM sensor_readings r
+         JOIN devices d ON d.id = r.device_id
+         WHERE d.patient_id = $1 AND r.reading_timestamp >= $2 AND r.reading_timestamp < $3"
+    )
+    .bind(plan.patient_id)
+    .bind(start)
+    .bind(end)
+    .bind(plan.hr_min)
+    .bind(plan.hr_max)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(activity)
+}
+
+/// Evaluate every plan active on `date` and upsert the results; returns the number evaluated
+pub async fn evaluate_day(pool: &PgPool, date: NaiveDate) -> Result<usize> {
+    let plans: Vec<CarePlan> = sqlx::query_as(
+        "SELECT * FROM care_plans
+         WHERE status = 'active' AND start_date <= $1 AND (end_date IS NULL OR end_date >= $1)
+           AND patient_id IN (SELECT id FROM patients WHERE archived_at IS NULL)"
+    )
+    .bind(date)
+    .fetch_all(pool)
+    .await?;
+
+    for plan in &plans {
+        let activity = load_daily_activity(pool, plan, date).await?;
+        let eval = evaluate(plan, date, &activity);
+
+        sqlx::query(
+            "INSERT INTO care_plan_evaluations
+                (care_plan_id, eval_date, readings, steps, activity_minutes, avg_heart_rate, min_spo2,
+                 hr_in_range_ratio, steps_met, activity_met, vitals_met, usage_minutes, usage_met)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+             ON CONFLICT (care_plan_id, eval_date) DO UPDATE SET
+                readings = EXCLUDED.readings, steps = EXCLUDED.steps,
+                activity_minutes = EXCLUDED.activity_minutes, avg_heart_rate = EXCLUDED.avg_heart_rate,
+                min_spo2 = EXCLUDED.min_spo2, hr_in_range_ratio = EXCLUDED.hr_in_range_ratio,
+                steps_met = EXCLUDED.steps_met, activity_met = EXCLUDED.activity_met,
+                vitals_met = EXCLUDED.vitals_met, usage_minutes = EXCLUDED.usage_minutes,
+                usage_met = EXCLUDED.usage_met, evaluated_at = now()"
+        )
+        .bind(eval.care_plan_id)
+        .bind(eval.eval_date)
+        .bind(eval.readings)
+        .bind(eval.steps)
+        .bind(eval.activity_minutes)
+        .bind(eval.avg_heart_rate)
+        .bind(eval.min_spo2)
+        .bind(eval.hr_in_range_ratio)
+        .bind(eval.steps_met)
+        .bind(eval.activity_met)
+        .bind(eval.vitals_met)
+        .bind(eval.usage_minutes)
+        .bind(eval.usage_met)
+        .execute(pool)
+        .await?;
+    }
+
+    Ok(plans.len())
+}
+
+/// Length and first day of the run of days without walker use ending on `through`.
+/// `days` is newest first; a day without readings counts as non-use, a missing day ends the run.
+pub fn non_use_streak(days: &[(NaiveDate, Option<i32>)], through: NaiveDate) -> Option<(NaiveDate, i64)> {
+    let mut expected = through;
+    let mut streak = None;
+    for &(date, usage) in days.iter().skip_while(|(date, _)| *date > through) {
+        if date != expected || usage.unwrap_or(0) > 0 {
+            break;
+        }
+        streak = Some((date, streak.map_or(1, |(_, len)| len + 1)));
+        expected = date - Duration::days(1);
+    }
+    streak
+}
+
+/// Alert caregivers of every patient whose prescribed walker has gone unused for
+/// `alert_days` completed days; each streak alerts once. Returns the alerts raised.
+pub async fn check_non_use(pool: &PgPool, notifier: &Notifier, alert_days: i64, today: NaiveDate) -> Result<usize> {
+    let plans: Vec<CarePlan> = sqlx::query_as(
+        "SELECT * FROM care_plans
+         WHERE status = 'active' AND prescribed_usage_minutes IS NOT NULL
+           AND patient_id IN (SELECT id FROM patients WHERE archived_at IS NULL)"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut raised = 0;
+    for plan in &plans {
+        let days: Vec<(NaiveDate, Option<i32>)> = sqlx::query_as(
+            "SELECT eval_date, usage_minutes FROM care_plan_evaluations
+             WHERE care_plan_id = $1 AND eval_date < $2
+             ORDER BY eval_date DESC LIMIT 90"
+        )
+        .bind(plan.id)
+        .bind(today)
+        .fetch_all(pool)
+        .await?;
+
+        let Some((streak_start, len)) = non_use_streak(&days, today - Duration::days(1)) else {
+            continue;
+        };
+        if len < alert_days {
+            continue;
+        }
+
+        let message = format!("Walker not used for {} consecutive days (since {})", len, streak_start);
+        let alert: Option<Alert> = sqlx::query_as(
+            "INSERT INTO alerts (patient_id, kind, level, message, details)
+             SELECT $1, 'walker_non_use', 'medium'::alert_level, $2, $3
+             WHERE NOT EXISTS (SELECT 1 FROM alerts
+                               WHERE patient_id = $1 AND kind = 'walker_non_use' AND details->>'streak_start' = $4)
+             RETURNING *"
+        )
+        .bind(plan.patient_id)
+        .bind(&message)
+        .bind(serde_json::json!({"care_plan_id": plan.id, "streak_start": streak_start, "days": len}))
+        .bind(streak_start.to_string())
+        .fetch_optional(pool)
+        .await?;
+        let Some(alert) = alert else { continue };
+
+        let patient_name: String = sqlx::query_scalar("SELECT display_name FROM patients WHERE id = $1")
+            .bind(plan.patient_id)
+            .fetch_one(pool)
+            .await?;
+        notify_care_team(pool, notifier, &alert, plan.patient_id, &format!("Walker not used: {}", patient_name)).await?;
+        raised += 1;
+    }
+
+    Ok(raised)
+}
+
+/// Background worker: finalizes yesterday, keeps today's running evaluation fresh and
+/// raises walker non-use alerts
+pub fn spawn_evaluator(pool: PgPool, notifier: Arc<Notifier>, non_use_alert_days: i64) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(EVALUATION_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let today = Utc::now().date_naive();
+            for date in [today - Duration::days(1), today] {
+                match evaluate_day(&pool, date).await {
+                    Ok(count) => info!("Care plan evaluation for {}: {} plan(s)", date, count),
+                    Err(e) => error!("Care plan evaluation for {} failed: {}", date, e),
+                }
+            }
+            match check_non_use(&pool, &notifier, non_use_alert_days, today).await {
+                Ok(0) => {}
+                Ok(count) => info!("Raised {} walker non-use alert(s)", count),
+                Err(e) => error!("Walker non-use check failed: {}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn plan() -> CarePlan {
+        CarePlan {
+            id: Uuid::new_v4(),
+            patient_id: Uuid::new_v4(),
+            title: "Post-op mobility".to_string(),
+            status: "active".to_string(),
+            target_steps: Some(2000),
+            target_activity_minutes: Some(30),
+            prescribed_usage_minutes: Some(60),
+            hr_min: Some(50),
+            hr_max: Some(110),
+            spo2_min: Some(92),
+            start_date: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            end_date: None,
+            created_by: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_goals_met() {
+        let activity = DailyActivity {
+            readings: 500,
+            steps: Some(2500),
+            activity_minutes: 45,
+            avg_heart_rate: Some(78.0),
+            min_spo2: Some(94),
+            hr_in_range_ratio: Some(0.97),
+            usage_minutes: 75,
+        };
+        let eval = evaluate(&plan(), NaiveDate::from_ymd_opt(2024, 1, 2).unwrap(), &activity);
+
+        assert_eq!(eval.steps_met, Some(true));
+        assert_eq!(eval.activity_met, Some(true));
+        assert_eq!(eval.vitals_met, Some(true));
+        assert_eq!(eval.usage_met, Some(true));
+    }
+
+    #[test]
+    fn test_goals_missed_on_low_spo2_and_steps() {
+        let activity = DailyActivity {
+            readings: 100,
+            steps: Some(300),
+            activity_minutes: 10,
+            avg_heart_rate: Some(80.0),
+            min_spo2: Some(88),
+            hr_in_range_ratio: Some(1.0),
+            usage_minutes: 20,
+        };
+        let eval = evaluate(&plan(), NaiveDate::from_ymd_opt(2024, 1, 2).unwrap(), &activity);
+
+        assert_eq!(eval.steps_met, Some(false));
+        assert_eq!(eval.activity_met, Some(false));
+        assert_eq!(eval.vitals_met, Some(false));
+        assert_eq!(eval.usage_met, Some(false));
+    }
+
+    #[test]
+    fn test_no_data_leaves_goals_unevaluated() {
+        let eval = evaluate(&plan(), NaiveDate::from_ymd_opt(2024, 1, 2).unwrap(), &DailyActivity::default());
+
+        assert_eq!(eval.readings, 0);
+        assert_eq!(eval.steps_met, None);
+        assert_eq!(eval.activity_met, None);
+        assert_eq!(eval.vitals_met, None);
+        assert_eq!(eval.usage_minutes, None);
+        assert_eq!(eval.usage_met, None);
+    }
+
+    #[test]
+    fn test_non_use_streak() {
+        let day = |d: u32| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+        let days = [(day(10), Some(0)), (day(9), None), (day(8), Some(0)), (day(7), Some(45)), (day(6), Some(0))];
+
+        assert_eq!(non_use_streak(&days, day(10)), Some((day(8), 3)));
+        // Today's running evaluation is ignored
+        assert_eq!(non_use_streak(&days, day(9)), Some((day(8), 2)));
+        assert_eq!(non_use_streak(&days[3..], day(7)), None);
+        // A gap in evaluations ends the streak
+        assert_eq!(non_use_streak(&[(day(10), Some(0)), (day(8), Some(0))], day(10)), Some((day(10), 1)));
+        assert_eq!(non_use_streak(&days, day(11)), None);
+    }
+}
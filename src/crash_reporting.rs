@@ -0,0 +1,240 @@
+//! Panic capture.
+//!
+//! The panic hook records every panic, whether in a request handler, a background worker
+//! or elsewhere, as a row in `incidents`, and captures it in Sentry when that is
+//! configured (see [`observability`](crate::observability)). [`CatchPanic`](crate::middleware::CatchPanic) turns a panicking handler into
+//! a 500 carrying the request id, which is also stored on the incident.
+
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use chrono::{DateTime, Utc};
+use sentry::integrations::backtrace::current_stacktrace;
+use sentry::protocol::{Event, Exception, Level, Mechanism};
+use serde_json::json;
+use sqlx::PgPool;
+use std::backtrace::Backtrace;
+use std::fmt;
+use std::future::Future;
+use std::panic::PanicHookInfo;
+use std::sync::OnceLock;
+use tokio::sync::mpsc;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+/// Panic messages can quote arbitrary values; keep incidents bounded
+const MAX_MESSAGE_LEN: usize = 2000;
+/// Log target of the hook's log line, which Sentry doesn't turn into a second event
+pub const PANIC_TARGET: &str = "panic";
+
+/// The request a panic happened in, set by `CatchPanic` for the duration of the handler
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    pub request_id: Option<String>,
+    pub method: String,
+    /// Route pattern rather than the path, so patient ids stay out of incidents
+    pub route: String,
+}
+
+tokio::task_local! {
+    static REQUEST: RequestContext;
+}
+
+/// Run a request future with its context visible to the panic hook
+pub async fn in_request<F: Future>(context: RequestContext, fut: F) -> F::Output {
+    REQUEST.scope(context, fut).await
+}
+
+#[derive(Debug, Clone)]
+pub struct Incident {
+    /// Also the Sentry event id, when the panic was captured there
+    pub id: Uuid,
+    pub request: Option<RequestContext>,
+    pub thread: String,
+    pub message: String,
+    pub location: Option<String>,
+    pub backtrace: String,
+    pub occurred_at: DateTime<Utc>,
+    /// Captured in Sentry
+    pub reported: bool,
+}
+
+impl Incident {
+    fn from_panic(info: &PanicHookInfo<'_>) -> Self {
+        let mut message = info.payload_as_str().unwrap_or("non-string panic payload").to_string();
+        if message.len() > MAX_MESSAGE_LEN {
+            let end = message.floor_char_boundary(MAX_MESSAGE_LEN);
+            message.truncate(end);
+        }
+        Self {
+            id: Uuid::new_v4(),
+            request: REQUEST.try_with(Clone::clone).ok(),
+            thread: std::thread::current().name().unwrap_or("unnamed").to_string(),
+            message,
+            location: info.location().map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column())),
+            backtrace: Backtrace::force_capture().to_string(),
+            occurred_at: Utc::now(),
+            reported: false,
+        }
+    }
+}
+
+/// A request whose handler panicked; answers 500 with the request id in body and header
+#[derive(Debug)]
+pub struct HandlerPanicked {
+    pub request_id: Option<String>,
+}
+
+impl fmt::Display for HandlerPanicked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Handler panicked")
+    }
+}
+
+impl ResponseError for HandlerPanicked {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut res = HttpResponse::InternalServerError();
+        if let Some(request_id) = &self.request_id {
+            res.insert_header(("x-request-id", request_id.as_str()));
+        }
+        res.json(json!({"error": "Internal server error", "request_id": self.request_id}))
+    }
+}
+
+static REPORTER: OnceLock<mpsc::UnboundedSender<Incident>> = OnceLock::new();
+
+/// Log every panic and hand it to the reporter. Until the reporter runs, panics also go
+/// to the previous hook so nothing is lost at startup.
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let mut incident = Incident::from_panic(info);
+        incident.reported = capture(&incident);
+        error!(
+            target: PANIC_TARGET,
+            thread = %incident.thread,
+            location = ?incident.location,
+            request_id = ?incident.request.as_ref().and_then(|r| r.request_id.as_deref()),
+            "PANIC: {}",
+            incident.message
+        );
+        match REPORTER.get() {
+            Some(tx) if tx.send(incident).is_ok() => {}
+            _ => previous(info),
+        }
+    }));
+}
+
+/// Capture the panic in Sentry from the panicking thread, so it carries the request's
+/// scope; false when Sentry isn't configured
+fn capture(incident: &Incident) -> bool {
+    let hub = sentry::Hub::current();
+    if hub.client().is_none_or(|client| !client.is_enabled()) {
+        return false;
+    }
+    let request = incident.request.as_ref();
+    let mut event = Event {
+        event_id: incident.id,
+        level: Level::Fatal,
+        exception: vec![Exception {
+            ty: "panic".to_string(),
+            value: Some(incident.message.clone()),
+            stacktrace: current_stacktrace(),
+            mechanism: Some(Mechanism {
+                ty: "panic".to_string(),
+                handled: Some(false),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }]
+        .into(),
+        ..Default::default()
+    };
+    event.tags.insert("thread".to_string(), incident.thread.clone());
+    if let Some(request_id) = request.and_then(|r| r.request_id.clone()) {
+        event.tags.insert("request_id".to_string(), request_id);
+    }
+    hub.capture_event(event);
+    true
+}
+
+async fn record_incident(pool: &PgPool, incident: &Incident) -> Result<(), sqlx::Error> {
+    let request = incident.request.as_ref();
+    sqlx::query(
+        "INSERT INTO incidents (id, request_id, method, route, thread, message, location, backtrace, occurred_at, reported_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, CASE WHEN $10 THEN now() END)"
+    )
+    .bind(incident.id)
+    .bind(request.and_then(|r| r.request_id.as_deref()))
+    .bind(request.map(|r| r.method.as_str()))
+    .bind(request.map(|r| r.route.as_str()))
+    .bind(&incident.thread)
+    .bind(&incident.message)
+    .bind(&incident.location)
+    .bind(&incident.backtrace)
+    .bind(incident.occurred_at)
+    .bind(incident.reported)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Background worker storing incidents from the panic hook.
+///
+/// Panics are handed over on a channel because the hook runs synchronously on the
+/// panicking thread. Only the first reporter started in a process receives them.
+pub fn spawn_reporter(pool: PgPool) -> tokio::task::JoinHandle<()> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    if REPORTER.set(tx).is_err() {
+        warn!("Crash reporter already running; ignoring the new one");
+    }
+
+    tokio::spawn(async move {
+        while let Some(incident) = rx.recv().await {
+            if let Err(e) = record_incident(&pool, &incident).await {
+                error!("Recording incident {} failed: {}", incident.id, e);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[actix_web::test]
+    async fn test_panicking_handler_answers_500_with_request_id() {
+        use crate::middleware::{CatchPanic, RequestId};
+        use actix_web::{test, web, App, HttpResponse};
+
+        async fn explode() -> HttpResponse {
+            panic!("handler exploded")
+        }
+
+        let app = test::init_service(
+            App::new()
+                .wrap(sentry_actix::Sentry::new())
+                .wrap(CatchPanic)
+                .wrap(RequestId)
+                .route("/explode", web::get().to(explode))
+                .route("/fine", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        // Outside a server the error isn't rendered for us, so render it the way actix would
+        let err = test::try_call_service(&app, test::TestRequest::get().uri("/explode").to_request())
+            .await
+            .unwrap_err();
+        let resp = err.error_response();
+        assert_eq!(resp.status(), 500);
+        let request_id = resp.headers().get("x-request-id").unwrap().to_str().unwrap().to_string();
+        let body = actix_web::body::to_bytes(resp.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["request_id"], request_id);
+        assert_eq!(body["error"], "Internal server error");
+
+        // The worker survives and keeps serving
+        let resp = test::call_service(&app, test::TestRequest::get().uri("/fine").to_request()).await;
+        assert_eq!(resp.status(), 200);
+    }
+}
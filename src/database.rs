@@ -1,5 +1,6 @@
-use crate::config::DatabaseConfig;
+use crate::config::{DatabaseConfig, ReportingConfig, ReportingMode};
 use sqlx::postgres::{PgPool, PgPoolOptions};
+use sqlx::Executor;
 use std::time::Duration;
 
 pub async fn create_pool(config: &DatabaseConfig) -> Result<PgPool, sqlx::Error> {
@@ -13,12 +14,73 @@ pub async fn create_pool(config: &DatabaseConfig) -> Result<PgPool, sqlx::Error>
         .await
 }
 
+/// The pool analytics endpoints run on. In constrained mode every connection is read-only,
+/// acts as `medhealth_reporting` (migration 041) and cancels statements past the timeout.
+pub async fn create_reporting_pool(
+    pool: &PgPool,
+    database: &DatabaseConfig,
+    config: &ReportingConfig,
+) -> Result<PgPool, sqlx::Error> {
+    if config.mode == ReportingMode::Shared {
+        return Ok(pool.clone());
+    }
+
+    let switch_role = config.url.is_none();
+    let setup = format!(
+        "{}SET default_transaction_read_only = on; SET statement_timeout = {}",
+        if switch_role { "SET ROLE medhealth_reporting; " } else { "" },
+        config.statement_timeout_ms
+    );
+    PgPoolOptions::new()
+        .max_connections(config.max_connections)
+        .acquire_timeout(Duration::from_secs(10))
+        .idle_timeout(Duration::from_secs(600))
+        .max_lifetime(Duration::from_secs(1800))
+        .after_connect(move |conn, _| {
+            let setup = setup.clone();
+            Box::pin(async move {
+                conn.execute(setup.as_str()).await?;
+                Ok(())
+            })
+        })
+        .connect(config.url.as_deref().unwrap_or(&database.url))
+        .await
+}
+
 pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::migrate::MigrateError> {
     sqlx::migrate!("./migrations")
         .run(pool)
         .await
 }
 
+/// Indexes the hot query paths rely on, as `(table, index)`
+pub const EXPECTED_INDEXES: &[(&str, &str)] = &[
+    ("sensor_readings", "idx_sensor_readings_device_time_desc"),
+    ("notifications", "idx_notifications_patient_created"),
+    ("care_plans", "idx_care_plans_patient_created"),
+    ("medications", "idx_medications_patient_created"),
+    ("alerts", "idx_alerts_patient"),
+    ("alerts", "idx_alerts_open_patient"),
+    ("alerts", "idx_alerts_open_level"),
+    ("alerts", "idx_alerts_unresolved"),
+    ("alerts", "idx_alerts_unresolved_vitals"),
+];
+
+/// Expected indexes that don't exist, e.g. dropped by hand or lost in a partial restore
+pub async fn missing_indexes(pool: &PgPool) -> Result<Vec<(&'static str, &'static str)>, sqlx::Error> {
+    let present: Vec<String> = sqlx::query_scalar(
+        "SELECT indexname::text FROM pg_indexes WHERE schemaname = current_schema()"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(EXPECTED_INDEXES
+        .iter()
+        .filter(|(_, index)| !present.iter().any(|p| p == index))
+        .copied()
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -31,4 +93,9 @@ mod tests {
         
         assert!(result.is_ok());
     }
+
+    #[sqlx::test]
+    async fn test_migrations_create_expected_indexes(pool: PgPool) {
+        assert_eq!(missing_indexes(&pool).await.unwrap(), vec![]);
+    }
 }
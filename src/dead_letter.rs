@@ -0,0 +1,170 @@
+//! Dead-letter queue for readings that passed their signature check but were not stored.
+//!
+//! A reading rejected on validation (say, from a walker whose units are misconfigured) or
+//! lost to a database error is kept in `failed_ingestions` exactly as sent, with the
+//! reason, instead of being dropped. Administrators inspect the queue and reprocess an
+//! entry once the cause is fixed; reprocessing runs the reading through the pipeline
+//! again and updates the same entry rather than queueing another one.
+
+use crate::metrics::INGESTION_FAILURES_TOTAL;
+use crate::models::Device;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// How a reading arrived
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum IngestChannel {
+    Http,
+    Mqtt,
+    /// Relayed by a paired phone
+    Gateway,
+}
+
+impl IngestChannel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IngestChannel::Http => "http",
+            IngestChannel::Mqtt => "mqtt",
+            IngestChannel::Gateway => "gateway",
+        }
+    }
+}
+
+/// Why a reading was not stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum FailureCategory {
+    /// Malformed or out of range once converted to the stored units
+    InvalidReading,
+    /// The database refused or failed the insert
+    Storage,
+}
+
+impl FailureCategory {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FailureCategory::InvalidReading => "invalid_reading",
+            FailureCategory::Storage => "storage",
+        }
+    }
+}
+
+/// Where a reading being ingested came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IngestSource {
+    pub channel: IngestChannel,
+    /// The phone's user, for relayed readings
+    pub relayed_by: Option<Uuid>,
+    /// The dead-letter entry being reprocessed, if any
+    pub retry_of: Option<i64>,
+}
+
+impl IngestSource {
+    pub fn new(channel: IngestChannel) -> Self {
+        Self { channel, relayed_by: None, retry_of: None }
+    }
+
+    pub fn relayed(relayed_by: Uuid) -> Self {
+        Self { relayed_by: Some(relayed_by), ..Self::new(IngestChannel::Gateway) }
+    }
+}
+
+/// Keep a reading `device` sent that could not be stored. A reprocessed entry is updated
+/// in place. Failing to record is logged, never passed on: the reading's own error is what
+/// the sender needs to see.
+pub async fn record_failure(
+    pool: &PgPool,
+    device: &Device,
+    source: &IngestSource,
+    payload: &Map<String, Value>,
+    category: FailureCategory,
+    error: &str,
+) {
+    INGESTION_FAILURES_TOTAL
+        .with_label_values(&[source.channel.as_str(), category.as_str()])
+        .inc();
+
+    let recorded = match source.retry_of {
+        Some(id) => {
+            sqlx::query(
+                "UPDATE failed_ingestions SET category = $2, error = $3, attempts = attempts + 1, last_attempt_at = now()
+                 WHERE id = $1"
+            )
+            .bind(id)
+            .bind(category)
+            .bind(error)
+            .execute(pool)
+            .await
+        }
+        None => {
+            sqlx::query(
+                "INSERT INTO failed_ingestions (device_id, channel, relayed_by, payload, category, error)
+                 VALUES ($1, $2, $3, $4, $5, $6)"
+            )
+            .bind(device.id)
+            .bind(source.channel)
+            .bind(source.relayed_by)
+            .bind(sqlx::types::Json(payload))
+            .bind(category)
+            .bind(error)
+            .execute(pool)
+            .await
+        }
+    };
+    if let Err(e) = recorded {
+        tracing::error!(device_id = %device.device_id, "Failed to dead-letter a reading: {}", e);
+    }
+}
+
+/// Mark a reprocessed entry as stored as `sensor_reading_id`
+pub async fn mark_reprocessed(pool: &PgPool, id: i64, sensor_reading_id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE failed_ingestions SET reprocessed_at = now(), sensor_reading_id = $2, attempts = attempts + 1,
+                                      last_attempt_at = now()
+         WHERE id = $1"
+    )
+    .bind(id)
+    .bind(sensor_reading_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Delete entries received more than `days` ago, as for readings, except for devices linked
+/// to a patient under an active legal hold
+pub async fn purge_expired(pool: &PgPool, days: i64) -> Result<u64, sqlx::Error> {
+    let deleted = sqlx::query(
+        "DELETE FROM failed_ingestions f
+         WHERE f.received_at < now() - make_interval(days => $1::int)
+           AND NOT EXISTS (
+               SELECT 1 FROM devices d
+               JOIN legal_holds h ON h.patient_id = d.patient_id AND h.released_at IS NULL
+               WHERE d.id = f.device_id
+           )"
+    )
+    .bind(days)
+    .execute(pool)
+    .await?
+    .rows_affected();
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels_match_stored_values() {
+        for channel in [IngestChannel::Http, IngestChannel::Mqtt, IngestChannel::Gateway] {
+            assert_eq!(serde_json::to_value(channel).unwrap(), channel.as_str());
+        }
+        for category in [FailureCategory::InvalidReading, FailureCategory::Storage] {
+            assert_eq!(serde_json::to_value(category).unwrap(), category.as_str());
+        }
+    }
+}
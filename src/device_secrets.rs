@@ -0,0 +1,142 @@
+//! Per-device HMAC secrets.
+//!
+//! Each walker signs its requests with its own secret, issued through
+//! `POST /api/admin/devices/{device_id}/secret`, so a secret pulled off one walker can't
+//! sign for the rest of the fleet. HMAC verification needs the secret itself, so `devices`
+//! stores it encrypted (AES-256-GCM, bound to the device row) under the server-only
+//! `device.secret_encryption_key`, next to a SHA-256 hash that the decrypted value must match.
+//!
+//! Walkers not yet issued a secret keep signing with the shared `device.secret` until
+//! `device.require_device_secrets` is set.
+
+use crate::config::DeviceConfig;
+use crate::phi_crypto::{open_bytes, parse_key, seal_bytes};
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose, Engine as _};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use uuid::Uuid;
+
+/// Prefix of secrets sealed under `device.secret_encryption_key`
+const SEALED_PREFIX: &str = "dsec:v1:";
+const SECRET_BYTES: usize = 32;
+
+/// A fresh random secret, as handed to the walker
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; SECRET_BYTES];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Hash stored in `devices.secret_hash`
+pub fn hash_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+/// Seals device secrets for storage and picks the secret a device signs with
+pub struct DeviceSecrets {
+    shared: String,
+    /// `device.secret_encryption_key`; secrets can't be issued without it
+    key: Option<[u8; 32]>,
+    require_device_secrets: bool,
+}
+
+impl DeviceSecrets {
+    pub fn new(config: &DeviceConfig) -> Result<Self> {
+        let key = config
+            .secret_encryption_key
+            .as_deref()
+            .map(parse_key)
+            .transpose()
+            .context("device.secret_encryption_key")?;
+        Ok(Self {
+            shared: config.secret.clone(),
+            key,
+            require_device_secrets: config.require_device_secrets,
+        })
+    }
+
+    fn key(&self) -> Result<&[u8; 32]> {
+        self.key.as_ref().ok_or_else(|| anyhow!("device.secret_encryption_key is not set"))
+    }
+
+    /// `dsec:v1:{base64(nonce || ciphertext)}`, readable only for the same device row
+    pub fn seal(&self, device: Uuid, secret: &str) -> Result<String> {
+        let sealed = seal_bytes(self.key()?, device.as_bytes(), secret.as_bytes());
+        Ok(format!("{}{}", SEALED_PREFIX, general_purpose::STANDARD.encode(sealed)))
+    }
+
+    pub fn open(&self, device: Uuid, sealed: &str) -> Result<String> {
+        let encoded = sealed.strip_prefix(SEALED_PREFIX).ok_or_else(|| anyhow!("not a sealed device secret"))?;
+        let plaintext = open_bytes(self.key()?, device.as_bytes(), &general_purpose::STANDARD.decode(encoded)?)?;
+        Ok(String::from_utf8(plaintext)?)
+    }
+
+    /// The secret `device` signs with: its own when issued, else the shared one while
+    /// that is still accepted (`None` once it isn't)
+    pub fn secret_for(&self, device: Uuid, secret_hash: &str, sealed: Option<&str>) -> Result<Option<Cow<'_, str>>> {
+        let Some(sealed) = sealed else {
+            return Ok((!self.require_device_secrets).then_some(Cow::Borrowed(self.shared.as_str())));
+        };
+        let secret = self.open(device, sealed)?;
+        if hash_secret(&secret) != secret_hash {
+            bail!("device secret does not match its hash");
+        }
+        Ok(Some(Cow::Owned(secret)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> Option<String> {
+        Some(general_purpose::STANDARD.encode([byte; 32]))
+    }
+
+    fn config(secret: &str, secret_encryption_key: Option<String>, require_device_secrets: bool) -> DeviceConfig {
+        DeviceConfig {
+            secret: secret.to_string(),
+            replay_window_seconds: 60,
+            pairing_code_ttl_minutes: 30,
+            require_device_secrets,
+            secret_encryption_key,
+        }
+    }
+
+    fn secrets(require_device_secrets: bool) -> DeviceSecrets {
+        DeviceSecrets::new(&config("shared", key(7), require_device_secrets)).unwrap()
+    }
+
+    #[test]
+    fn test_sealed_secret_opens_for_its_device_only() {
+        let secrets = secrets(false);
+        let (device, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let secret = generate_secret();
+        assert_ne!(secret, generate_secret());
+
+        let sealed = secrets.seal(device, &secret).unwrap();
+        assert!(!sealed.contains(&secret));
+        let hash = hash_secret(&secret);
+        assert_eq!(secrets.secret_for(device, &hash, Some(&sealed)).unwrap().unwrap(), secret);
+
+        // Copied onto another row, swapped for another device's hash, or read under another key
+        assert!(secrets.secret_for(other, &hash, Some(&sealed)).is_err());
+        assert!(secrets.secret_for(device, &hash_secret("other"), Some(&sealed)).is_err());
+        assert!(DeviceSecrets::new(&config("shared", key(8), false)).unwrap().open(device, &sealed).is_err());
+        // The shared secret alone opens nothing, and issues nothing
+        let unkeyed = DeviceSecrets::new(&config("shared", None, false)).unwrap();
+        assert!(unkeyed.open(device, &sealed).is_err());
+        assert!(unkeyed.seal(device, &secret).is_err());
+
+        assert!(DeviceSecrets::new(&config("shared", Some("c2hvcnQ=".to_string()), false)).is_err());
+    }
+
+    #[test]
+    fn test_shared_secret_only_until_required() {
+        let device = Uuid::new_v4();
+        assert_eq!(secrets(false).secret_for(device, "", None).unwrap().unwrap(), "shared");
+        assert!(secrets(true).secret_for(device, "", None).unwrap().is_none());
+    }
+}
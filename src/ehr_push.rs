@@ -0,0 +1,342 @@
+//! Push of stored readings to the EHRs of the patient's organization, on when
+//! `deployment.ehr_push` is (the clinical-mode default).
+//!
+//! Every enabled `fhir` and `hl7` integration of the organization (see `crate::integrations`)
+//! gets each reading:
+//!
+//! - A FHIR server is posted the reading's Observations, with the Patient and Device they
+//!   reference, as a `transaction` Bundle at its base URL. Each entry is a `PUT` under the
+//!   resource's id, so a repeated push updates rather than duplicates.
+//! - An HL7 v2 receiver is sent an `ORU^R01` message over MLLP and must acknowledge it with
+//!   `AA` or `CA`.
+//!
+//! Pushes run after the reading is stored and never hold up ingestion. A failed push is logged
+//! and counted in `ehr_pushes_total`, not retried; the reading stays exportable from
+//! `/api/fhir`. Readings of patients outside any organization are not pushed. As with tests,
+//! integrations set up by an organization's admins may not reach this host or a private
+//! network; those created by administrators not tied to an organization may.
+
+use crate::fhir_service::FhirService;
+use crate::integrations::{fhir_credentials, pinned_client, resolve_target, DEFAULT_TIMEOUT_SECONDS};
+use crate::metrics::EHR_PUSHES_TOTAL;
+use crate::models::{Device, Integration, IntegrationKind, Patient, SensorReading};
+use crate::phi_crypto::PhiCipher;
+use chrono::{DateTime, Utc};
+use reqwest::header;
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use uuid::Uuid;
+
+/// MLLP frame: a vertical tab before the message, a file separator and carriage return after it
+const MLLP_START: u8 = 0x0b;
+const MLLP_END: [u8; 2] = [0x1c, 0x0d];
+/// Acknowledgements longer than this are refused rather than read on
+const MAX_ACK_BYTES: usize = 64 * 1024;
+
+/// An integration readings are pushed to, and whether it may reach internal addresses
+#[derive(FromRow)]
+struct Target {
+    #[sqlx(flatten)]
+    integration: Integration,
+    allow_internal: bool,
+}
+
+/// What is pushed for one reading
+struct Push {
+    reading_id: i64,
+    patient_id: Uuid,
+    transaction: Value,
+    message: String,
+}
+
+/// Push the reading, whose stored Observation `bundle` names `patient`, to every enabled EHR
+/// integration of the patient's organization, in the background
+pub fn push_in_background(
+    pool: &PgPool,
+    phi: &Arc<PhiCipher>,
+    fhir: &FhirService,
+    reading: &SensorReading,
+    device: &Device,
+    patient: &Patient,
+    bundle: &Value,
+) {
+    let push = Push {
+        reading_id: reading.id,
+        patient_id: patient.id,
+        transaction: fhir.create_transaction_bundle(bundle),
+        message: oru_message(fhir.organization_id(), reading, device, patient, Utc::now()),
+    };
+    let pool = pool.clone();
+    let phi = phi.clone();
+    tokio::spawn(async move {
+        if let Err(e) = push_to_integrations(&pool, &phi, &push).await {
+            tracing::warn!(reading_id = push.reading_id, "Failed to load EHR integrations: {}", e);
+        }
+    });
+}
+
+async fn push_to_integrations(pool: &PgPool, phi: &PhiCipher, push: &Push) -> Result<(), sqlx::Error> {
+    let targets: Vec<Target> = sqlx::query_as(
+        "SELECT i.*, i.secret IS NOT NULL AS has_secret,
+                COALESCE((SELECT u.organization_id IS NULL FROM users u WHERE u.id = i.created_by), false) AS allow_internal
+         FROM integrations i
+         WHERE i.organization_id = (SELECT organization_id FROM patients WHERE id = $1)
+           AND i.enabled AND i.kind IN ('fhir', 'hl7')"
+    )
+    .bind(push.patient_id)
+    .fetch_all(pool)
+    .await?;
+
+    for target in targets {
+        let integration = &target.integration;
+        let outcome = match open_secret(phi, integration).await {
+            Ok(secret) => send(integration, secret.as_deref(), target.allow_internal, push).await,
+            Err(e) => Err(e),
+        };
+        match outcome {
+            Ok(()) => EHR_PUSHES_TOTAL.with_label_values(&[integration.kind.as_str(), "delivered"]).inc(),
+            Err(e) => {
+                EHR_PUSHES_TOTAL.with_label_values(&[integration.kind.as_str(), "failed"]).inc();
+                tracing::warn!(
+                    reading_id = push.reading_id,
+                    integration_id = %integration.id,
+                    "Failed to push reading to {} integration: {}",
+                    integration.kind.as_str(),
+                    e
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn open_secret(phi: &PhiCipher, integration: &Integration) -> Result<Option<String>, String> {
+    match &integration.secret {
+        Some(sealed) => match phi.open(sealed).await {
+            Ok(Some(secret)) => Ok(Some(secret)),
+            Ok(None) => Err("the secret's keys were destroyed".to_string()),
+            Err(e) => Err(format!("could not decrypt the secret: {:#}", e)),
+        },
+        None => Ok(None),
+    }
+}
+
+async fn send(integration: &Integration, secret: Option<&str>, allow_internal: bool, push: &Push) -> Result<(), String> {
+    let timeout = Duration::from_secs(integration.settings.timeout_seconds.unwrap_or(DEFAULT_TIMEOUT_SECONDS));
+    let (host, address) = resolve_target(integration.kind, &integration.url, allow_internal).await?;
+    match integration.kind {
+        IntegrationKind::Fhir => {
+            let http = pinned_client(&host, address, timeout, allow_internal);
+            let request = http
+                .post(integration.url.trim_end_matches('/'))
+                .header(header::CONTENT_TYPE, "application/fhir+json")
+                .header(header::ACCEPT, "application/fhir+json")
+                .json(&push.transaction);
+            match fhir_credentials(request, integration, secret).send().await {
+                Ok(response) if response.status().is_success() => Ok(()),
+                Ok(response) => Err(format!("Server answered {}", response.status())),
+                Err(e) => Err(format!("Request failed: {}", e)),
+            }
+        }
+        IntegrationKind::Hl7 => send_mllp(address, &push.message, timeout).await,
+        IntegrationKind::Webhook => Err("Webhooks are not sent readings".to_string()),
+    }
+}
+
+/// Send one HL7 message framed for MLLP and wait for an accepting acknowledgement
+async fn send_mllp(address: SocketAddr, message: &str, timeout: Duration) -> Result<(), String> {
+    let exchange = async {
+        let mut stream = tokio::net::TcpStream::connect(address)
+            .await
+            .map_err(|e| format!("Connection failed: {}", e))?;
+        let mut frame = Vec::with_capacity(message.len() + 3);
+        frame.push(MLLP_START);
+        frame.extend_from_slice(message.as_bytes());
+        frame.extend_from_slice(&MLLP_END);
+        stream.write_all(&frame).await.map_err(|e| format!("Send failed: {}", e))?;
+
+        let mut ack = Vec::new();
+        let mut buffer = [0u8; 4096];
+        while !ack.contains(&MLLP_END[0]) {
+            let read = stream.read(&mut buffer).await.map_err(|e| format!("No acknowledgement: {}", e))?;
+            if read == 0 {
+                return Err("Receiver closed the connection without acknowledging".to_string());
+            }
+            ack.extend_from_slice(&buffer[..read]);
+            if ack.len() > MAX_ACK_BYTES {
+                return Err("Acknowledgement too long".to_string());
+            }
+        }
+        acknowledgement_code(&String::from_utf8_lossy(&ack))
+    };
+    match tokio::time::timeout(timeout, exchange).await {
+        Ok(outcome) => outcome,
+        Err(_) => Err(format!("No acknowledgement within {}s", timeout.as_secs())),
+    }
+}
+
+/// Accept an `ACK` whose `MSA` segment answers `AA` or `CA`
+fn acknowledgement_code(ack: &str) -> Result<(), String> {
+    let msa = ack
+        .trim_start_matches(char::from(MLLP_START))
+        .split(['\r', '\n'])
+        .find(|segment| segment.starts_with("MSA|"));
+    match msa.and_then(|segment| segment.split('|').nth(1)) {
+        Some("AA" | "CA") => Ok(()),
+        Some(code) => Err(format!("Receiver answered {}", code)),
+        None => Err("Acknowledgement has no MSA segment".to_string()),
+    }
+}
+
+/// Escape HL7 v2 delimiters in a field value
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\E\\"),
+            '|' => escaped.push_str("\\F\\"),
+            '^' => escaped.push_str("\\S\\"),
+            '&' => escaped.push_str("\\T\\"),
+            '~' => escaped.push_str("\\R\\"),
+            '\r' | '\n' => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn hl7_timestamp(at: DateTime<Utc>) -> String {
+    at.format("%Y%m%d%H%M%S+0000").to_string()
+}
+
+/// An HL7 v2.5.1 `ORU^R01` carrying the reading's vitals, one `OBX` each, coded like its FHIR
+/// Observations
+fn oru_message(facility: &str, reading: &SensorReading, device: &Device, patient: &Patient, now: DateTime<Utc>) -> String {
+    let observed = hl7_timestamp(reading.reading_timestamp);
+    let mut segments = vec![
+        format!(
+            "MSH|^~\\&|MEDHEALTH|{}|||{}||ORU^R01^ORU_R01|MH{}|P|2.5.1",
+            escape(facility),
+            hl7_timestamp(now),
+            reading.id
+        ),
+        format!(
+            "PID|1||{}^^^MEDHEALTH||{}||{}",
+            patient.id,
+            escape(&patient.display_name),
+            patient.date_of_birth.map(|date| date.format("%Y%m%d").to_string()).unwrap_or_default()
+        ),
+        format!("OBR|1||{}^MEDHEALTH|8716-3^Vital signs^LN|||{}", reading.id, observed),
+    ];
+
+    let temperature = reading.temperature.map(|t| format!("{:.2}", (f64::from(t) * 100.0).round() / 100.0));
+    let vitals = [
+        (reading.heart_rate.map(|hr| hr.to_string()), "8867-4^Heart rate^LN", "/min^beats/minute^UCUM"),
+        (reading.spo2.map(|spo2| spo2.to_string()), "2708-6^Oxygen saturation in Arterial blood^LN", "%^percent^UCUM"),
+        (temperature, "8310-5^Body temperature^LN", "Cel^degrees Celsius^UCUM"),
+    ];
+    for (value, code, unit) in vitals.into_iter().filter_map(|(value, code, unit)| Some((value?, code, unit))) {
+        segments.push(format!(
+            "OBX|{}|NM|{}||{}|{}|||||F|||{}||||{}",
+            segments.len() - 2,
+            code,
+            value,
+            unit,
+            observed,
+            escape(&device.device_id)
+        ));
+    }
+    segments.join("\r")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn reading() -> SensorReading {
+        SensorReading {
+            id: 42,
+            device_id: Uuid::new_v4(),
+            heart_rate: Some(72),
+            spo2: None,
+            temperature: Some(36.8),
+            reading_timestamp: DateTime::parse_from_rfc3339("2026-03-01T08:30:00Z").unwrap().with_timezone(&Utc),
+            received_at: Utc::now(),
+            quality_score: None,
+            metadata: json!({}),
+        }
+    }
+
+    fn device() -> Device {
+        Device {
+            id: Uuid::new_v4(),
+            device_id: "WALKER-7".to_string(),
+            device_name: "Ward 3 walker".to_string(),
+            secret_hash: String::new(),
+            secret_ciphertext: None,
+            secret_issued_at: None,
+            is_active: true,
+            created_at: Utc::now(),
+            last_seen_at: None,
+            metadata: json!({}),
+            patient_id: None,
+            claimed_by: None,
+            claimed_at: None,
+            units: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_oru_message_carries_each_vital() {
+        let patient = Patient {
+            id: Uuid::new_v4(),
+            display_name: "Ada|Walker^".to_string(),
+            date_of_birth: chrono::NaiveDate::from_ymd_opt(1941, 3, 9),
+        };
+        let message = oru_message("org-test-001", &reading(), &device(), &patient, Utc::now());
+        let segments: Vec<&str> = message.split('\r').collect();
+
+        assert_eq!(segments.len(), 5);
+        assert!(segments[0].starts_with("MSH|^~\\&|MEDHEALTH|org-test-001|"));
+        assert!(segments[0].ends_with("|ORU^R01^ORU_R01|MH42|P|2.5.1"));
+        assert_eq!(segments[1], format!("PID|1||{}^^^MEDHEALTH||Ada\\F\\Walker\\S\\||19410309", patient.id));
+        assert_eq!(
+            segments[3],
+            "OBX|1|NM|8867-4^Heart rate^LN||72|/min^beats/minute^UCUM|||||F|||20260301083000+0000||||WALKER-7"
+        );
+        assert!(segments[4].starts_with("OBX|2|NM|8310-5^Body temperature^LN||36.80|Cel^"));
+    }
+
+    #[test]
+    fn test_acknowledgement_codes() {
+        assert!(acknowledgement_code("\u{b}MSH|^~\\&|EHR\rMSA|AA|MH42\u{1c}\r").is_ok());
+        assert!(acknowledgement_code("MSH|^~\\&|EHR\rMSA|CA|MH42").is_ok());
+        assert_eq!(acknowledgement_code("MSH|^~\\&|EHR\rMSA|AE|MH42").unwrap_err(), "Receiver answered AE");
+        assert!(acknowledgement_code("MSH|^~\\&|EHR").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mllp_exchange() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let receiver = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut received = Vec::new();
+            let mut buffer = [0u8; 1024];
+            while !received.ends_with(&MLLP_END) {
+                let read = stream.read(&mut buffer).await.unwrap();
+                received.extend_from_slice(&buffer[..read]);
+            }
+            stream.write_all(b"\x0bMSH|^~\\&|EHR\rMSA|AA|MH42\x1c\r").await.unwrap();
+            received
+        });
+
+        send_mllp(address, "MSH|^~\\&|MEDHEALTH", Duration::from_secs(5)).await.unwrap();
+        assert_eq!(receiver.await.unwrap(), b"\x0bMSH|^~\\&|MEDHEALTH\x1c\r");
+    }
+}
@@ -0,0 +1,386 @@
+use crate::alert_routing::on_call_for_patient;
+use crate::models::{Alert, AlertResponse, Device, DeviceEventIngest, EmergencyContact, MlAlert, TestAlertRequest};
+use crate::notifier::{AlertRecipients, Notifier};
+use crate::sse::{broadcast_alert, SseBroadcaster};
+use crate::voice::alert_script;
+use anyhow::Result;
+use chrono::{TimeZone, Utc};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+const ESCALATION_INTERVAL: StdDuration = StdDuration::from_secs(60);
+
+/// One step to append to an alert's response chain
+#[derive(Debug, Default)]
+pub struct ResponseStep {
+    pub channel: &'static str,
+    pub contact_id: Option<Uuid>,
+    pub user_id: Option<Uuid>,
+    pub target: Option<String>,
+    pub status: &'static str,
+    pub detail: Option<String>,
+    pub provider_ref: Option<String>,
+}
+
+impl ResponseStep {
+    fn outcome(channel: &'static str, target: String, result: Result<()>) -> Self {
+        let (status, detail) = match result {
+            Ok(()) => ("sent", None),
+            Err(e) => ("failed", Some(e.to_string())),
+        };
+        Self {
+            channel,
+            target: Some(target),
+            status,
+            detail,
+            ..Default::default()
+        }
+    }
+
+    /// The in-app notification step, naming whoever the alert was routed to
+    fn inbox(result: Result<AlertRecipients>) -> Self {
+        match result {
+            Ok(AlertRecipients::OnCall(user_id)) => Self {
+                user_id: Some(user_id),
+                ..Self::outcome("inbox", "on-call clinician".to_string(), Ok(()))
+            },
+            Ok(AlertRecipients::Caregivers(count)) => Self::outcome("inbox", format!("{} caregiver(s)", count), Ok(())),
+            Err(e) => Self::outcome("inbox", "caregivers".to_string(), Err(e)),
+        }
+    }
+}
+
+pub async fn record_response(pool: &PgPool, alert_id: Uuid, step: &ResponseStep) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO alert_responses (alert_id, channel, contact_id, user_id, target, status, detail, provider_ref)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"
+    )
+    .bind(alert_id)
+    .bind(step.channel)
+    .bind(step.contact_id)
+    .bind(step.user_id)
+    .bind(&step.target)
+    .bind(step.status)
+    .bind(&step.detail)
+    .bind(&step.provider_ref)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn load_responses(pool: &PgPool, alert_id: Uuid) -> Result<Vec<AlertResponse>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM alert_responses WHERE alert_id = $1 ORDER BY id")
+        .bind(alert_id)
+        .fetch_all(pool)
+        .await
+}
+
+/// Raise a critical alert for an SOS press, broadcast it and run the response chain
+pub async fn raise_sos(
+    pool: &PgPool,
+    notifier: &Notifier,
+    broadcaster: &SseBroadcaster,
+    device: &Device,
+    event: &DeviceEventIngest,
+) -> Result<Alert> {
+    let pressed_at = Utc.timestamp_opt(event.timestamp, 0).single().unwrap_or_else(Utc::now);
+    let message = format!(
+        "SOS button pressed on {} at {} UTC",
+        device.device_name,
+        pressed_at.format("%H:%M")
+    );
+
+    let alert: Alert = sqlx::query_as(
+        "INSERT INTO alerts (patient_id, device_id, kind, level, message, details)
+         VALUES ($1, $2, 'sos', 'critical', $3, $4)
+         RETURNING *"
+    )
+    .bind(device.patient_id)
+    .bind(device.id)
+    .bind(&message)
+    .bind(serde_json::json!({"pressed_at": pressed_at, "payload": event.details}))
+    .fetch_one(pool)
+    .await?;
+
+    broadcast_alert(broadcaster, alert.patient_id, MlAlert {
+        level: alert.level,
+        message: message.clone(),
+        details: serde_json::json!({
+            "alert_id": alert.id,
+            "kind": alert.kind,
+            "patient_id": alert.patient_id,
+            "device_id": device.device_id,
+        }),
+    });
+
+    run_response_chain(pool, notifier, &alert).await?;
+    Ok(alert)
+}
+
+/// Store an alert from a reading's analysis, unless the walker already has an unresolved
+/// vitals alert at this level or above; a sustained excursion is one alert, not one per reading
+pub async fn record_vitals_alert(pool: &PgPool, device: &Device, reading_id: i64, alert: &MlAlert) -> Result<Option<Alert>, sqlx::Error> {
+    let mut details = alert.details.clone();
+    details["reading_id"] = serde_json::json!(reading_id);
+
+    sqlx::query_as(
+        "INSERT INTO alerts (patient_id, device_id, kind, level, message, details)
+         SELECT $1, $2, 'vitals', $3, $4, $5
+         WHERE NOT EXISTS (SELECT 1 FROM alerts
+                           WHERE device_id = $2 AND kind = 'vitals' AND status <> 'resolved' AND level >= $3)
+         RETURNING *"
+    )
+    .bind(device.patient_id)
+    .bind(device.id)
+    .bind(alert.level)
+    .bind(&alert.message)
+    .bind(details)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Notify a patient's caregivers in-app about a non-emergency alert and record the step
+pub async fn notify_care_team(pool: &PgPool, notifier: &Notifier, alert: &Alert, patient_id: Uuid, title: &str) -> Result<()> {
+    let result = notifier.notify_alert(patient_id, alert.level, &alert.kind, title, &alert.message).await;
+    record_response(pool, alert.id, &ResponseStep::inbox(result)).await?;
+    Ok(())
+}
+
+/// Notify caregivers in-app, then every emergency contact in priority order, recording each step
+pub async fn run_response_chain(pool: &PgPool, notifier: &Notifier, alert: &Alert) -> Result<()> {
+    let Some(patient_id) = alert.patient_id else {
+        warn!(alert_id = %alert.id, "Alert from an unclaimed device has nobody to notify");
+        let step = ResponseStep {
+            channel: "inbox",
+            status: "failed",
+            detail: Some("Device is not linked to a patient".to_string()),
+            ..Default::default()
+        };
+        record_response(pool, alert.id, &step).await?;
+        return Ok(());
+    };
+
+    let patient_name: String = sqlx::query_scalar("SELECT display_name FROM patients WHERE id = $1")
+        .bind(patient_id)
+        .fetch_one(pool)
+        .await?;
+    let title = format!("SOS: {}", patient_name);
+
+    let result = notifier.notify_alert(patient_id, alert.level, "sos", &title, &alert.message).await;
+    record_response(pool, alert.id, &ResponseStep::inbox(result)).await?;
+
+    let text = format!("{} for {}. Please check on them.", alert.message, patient_name);
+    notify_emergency_contacts(pool, notifier, alert, patient_id, &text).await
+}
+
+/// Text and/or call each of the patient's emergency contacts in priority order, recording each step
+async fn notify_emergency_contacts(pool: &PgPool, notifier: &Notifier, alert: &Alert, patient_id: Uuid, text: &str) -> Result<()> {
+    let contacts: Vec<EmergencyContact> = sqlx::query_as(
+        "SELECT * FROM emergency_contacts WHERE patient_id = $1 ORDER BY priority, created_at"
+    )
+    .bind(patient_id)
+    .fetch_all(pool)
+    .await?;
+
+    for contact in &contacts {
+        let channels = [("sms", contact.notify_sms), ("call", contact.notify_call)];
+        for (channel, _) in channels.into_iter().filter(|(_, enabled)| *enabled) {
+            let result = notifier.notify_contact(channel, &contact.phone, text, alert.id).await;
+            if let Err(e) = &result {
+                warn!(alert_id = %alert.id, contact_id = %contact.id, "Failed to reach emergency contact: {}", e);
+            }
+            let step = ResponseStep {
+                contact_id: Some(contact.id),
+                ..ResponseStep::outcome(channel, contact.phone.clone(), result)
+            };
+            record_response(pool, alert.id, &step).await?;
+        }
+    }
+
+    Ok(())
+}
+
+// ============ Test Alerts ============
+
+/// Raise a synthetic `kind = "test"` alert and send it down the real alerting chain, so a
+/// site can check its dashboards, inboxes and contact webhook end to end.
+///
+/// With a patient the alert is routed like theirs would be (on-call or caregivers), and
+/// reaches their emergency contacts only when `notify_contacts` is set; without one it goes
+/// to the admins. Test alerts are never voice-escalated.
+pub async fn raise_test_alert(
+    pool: &PgPool,
+    notifier: &Notifier,
+    broadcaster: &SseBroadcaster,
+    request: &TestAlertRequest,
+    requested_by: Uuid,
+) -> Result<Alert> {
+    let message = format!(
+        "[TEST] {}",
+        request.message.as_deref().unwrap_or("Alerting chain check, no action needed")
+    );
+
+    let alert: Alert = sqlx::query_as(
+        "INSERT INTO alerts (patient_id, kind, level, message, details)
+         VALUES ($1, 'test', $2, $3, $4)
+         RETURNING *"
+    )
+    .bind(request.patient_id)
+    .bind(request.level)
+    .bind(&message)
+    .bind(serde_json::json!({"test": true, "requested_by": requested_by}))
+    .fetch_one(pool)
+    .await?;
+
+    broadcast_alert(broadcaster, alert.patient_id, MlAlert {
+        level: alert.level,
+        message: message.clone(),
+        details: serde_json::json!({
+            "alert_id": alert.id,
+            "kind": alert.kind,
+            "test": true,
+            "patient_id": alert.patient_id,
+        }),
+    });
+
+    let Some(patient_id) = request.patient_id else {
+        let result = notifier.notify_admins("test", "[TEST] Alerting check", &message).await;
+        let step = match result {
+            Ok(count) => ResponseStep::outcome("inbox", format!("{} admin(s)", count), Ok(())),
+            Err(e) => ResponseStep::outcome("inbox", "admins".to_string(), Err(e)),
+        };
+        record_response(pool, alert.id, &step).await?;
+        return Ok(alert);
+    };
+
+    let patient_name: String = sqlx::query_scalar("SELECT display_name FROM patients WHERE id = $1")
+        .bind(patient_id)
+        .fetch_one(pool)
+        .await?;
+    let title = format!("[TEST] Alerting check: {}", patient_name);
+    notify_care_team(pool, notifier, &alert, patient_id, &title).await?;
+
+    if request.notify_contacts {
+        let text = format!("{} for {}.", message, patient_name);
+        notify_emergency_contacts(pool, notifier, &alert, patient_id, &text).await?;
+    }
+
+    Ok(alert)
+}
+
+// ============ Voice Escalation ============
+
+/// Claim critical alerts still unacknowledged `after_minutes` after the last call (or the
+/// alert itself) and advance each one contact down its escalation list. Vitals alerts are
+/// triaged by staff rather than called out.
+pub async fn claim_due_escalations(pool: &PgPool, after_minutes: i64) -> Result<Vec<Alert>> {
+    let alerts = sqlx::query_as::<_, Alert>(
+        "UPDATE alerts a SET escalation_level = a.escalation_level + 1, escalated_at = now()
+         WHERE a.level = 'critical' AND a.kind NOT IN ('test', 'vitals') AND a.acknowledged_at IS NULL AND a.patient_id IS NOT NULL
+           AND COALESCE(a.escalated_at, a.raised_at) <= now() - make_interval(mins => $1)
+           AND a.escalation_level < (SELECT COUNT(*) FROM emergency_contacts c WHERE c.patient_id = a.patient_id)
+         RETURNING a.*"
+    )
+    .bind(after_minutes as i32)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(alerts)
+}
+
+/// One worker pass: call the next emergency contact for every overdue critical alert
+pub async fn run_escalation_cycle(pool: &PgPool, notifier: &Notifier, after_minutes: i64) -> Result<usize> {
+    let alerts = claim_due_escalations(pool, after_minutes).await?;
+
+    for alert in &alerts {
+        let Some(patient_id) = alert.patient_id else { continue };
+
+        let contact: EmergencyContact = sqlx::query_as(
+            "SELECT * FROM emergency_contacts WHERE patient_id = $1 ORDER BY priority, created_at OFFSET $2 LIMIT 1"
+        )
+        .bind(patient_id)
+        .bind(i64::from(alert.escalation_level - 1))
+        .fetch_one(pool)
+        .await?;
+        let patient_name: String = sqlx::query_scalar("SELECT display_name FROM patients WHERE id = $1")
+            .bind(patient_id)
+            .fetch_one(pool)
+            .await?;
+
+        let script = alert_script(&alert.kind, &patient_name, &alert.message, alert.escalation_level);
+        let step = match notifier.place_call(&contact.phone, &script, alert.id).await {
+            Ok(call_sid) => ResponseStep {
+                status: "queued",
+                provider_ref: Some(call_sid),
+                ..Default::default()
+            },
+            Err(e) => {
+                warn!(alert_id = %alert.id, contact_id = %contact.id, "Escalation call failed: {}", e);
+                ResponseStep {
+                    status: "failed",
+                    detail: Some(e.to_string()),
+                    ..Default::default()
+                }
+            }
+        };
+        let step = ResponseStep {
+            channel: "voice",
+            contact_id: Some(contact.id),
+            target: Some(contact.phone.clone()),
+            ..step
+        };
+        record_response(pool, alert.id, &step).await?;
+
+        // Keep whoever is on call in the loop while contacts are being called
+        if let Some(on_call) = on_call_for_patient(pool, Utc::now(), patient_id).await? {
+            let title = format!("Unacknowledged: {}", patient_name);
+            let result = notifier
+                .notify_user(on_call.user_id, Some(patient_id), &alert.kind, &title, &alert.message)
+                .await
+                .map(|_| ());
+            let step = ResponseStep {
+                user_id: Some(on_call.user_id),
+                ..ResponseStep::outcome("inbox", "on-call clinician".to_string(), result)
+            };
+            record_response(pool, alert.id, &step).await?;
+        }
+    }
+
+    Ok(alerts.len())
+}
+
+/// Background worker escalating unacknowledged critical alerts by voice call
+pub fn spawn_escalation_worker(pool: PgPool, notifier: Arc<Notifier>, after_minutes: i64) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ESCALATION_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            match run_escalation_cycle(&pool, &notifier, after_minutes).await {
+                Ok(0) => {}
+                Ok(calls) => info!("Placed {} escalation call(s)", calls),
+                Err(e) => error!("Alert escalation cycle failed: {}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_outcome_records_failure_reason() {
+        let sent = ResponseStep::outcome("sms", "+15551234567".to_string(), Ok(()));
+        assert_eq!(sent.status, "sent");
+        assert!(sent.detail.is_none());
+
+        let failed = ResponseStep::outcome("call", "+15551234567".to_string(), Err(anyhow::anyhow!("gateway down")));
+        assert_eq!(failed.status, "failed");
+        assert_eq!(failed.detail.as_deref(), Some("gateway down"));
+    }
+}
@@ -0,0 +1,119 @@
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Errors returned by handlers, rendered as `{"error": ...}` JSON responses
+#[derive(Debug, Error)]
+pub enum ApiError {
+    #[error("{0}")]
+    BadRequest(String),
+
+    #[error("{0}")]
+    Unauthorized(String),
+
+    #[error("{0}")]
+    Forbidden(String),
+
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("{0}")]
+    Conflict(String),
+
+    /// A feature the request needs is switched off or not configured on this server
+    #[error("{0}")]
+    Unavailable(String),
+
+    /// Over a rate limit; retry after this many seconds
+    #[error("Too many requests")]
+    TooManyRequests(u64),
+
+    /// JWT signing failed (usually a misconfigured key); the server is up but cannot issue tokens
+    #[error("Authentication temporarily unavailable")]
+    TokenSigning(#[source] anyhow::Error),
+
+    #[error("Database error")]
+    Database(#[from] sqlx::Error),
+
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl ApiError {
+    /// Wrap a signing failure and raise an operator alert; the caller gets a 503 instead of a panic
+    pub fn token_signing(err: anyhow::Error, user_id: Uuid) -> Self {
+        tracing::error!(
+            alert = "token_signing_failure",
+            user_id = %user_id,
+            error = %err,
+            "ALERT: JWT signing failed, check jwt.secret configuration"
+        );
+        crate::audit_log!("auth", "token_issue", Some(user_id), false);
+        ApiError::TokenSigning(err)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::TokenSigning(_) | ApiError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Database(_) | ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if let ApiError::Database(e) = self {
+            tracing::error!("Database error: {}", e);
+        }
+
+        let mut builder = HttpResponse::build(self.status_code());
+        match self {
+            ApiError::TokenSigning(_) => {
+                builder.insert_header(("Retry-After", "30"));
+            }
+            ApiError::TooManyRequests(seconds) => {
+                builder.insert_header(("Retry-After", seconds.to_string()));
+            }
+            _ => {}
+        }
+        builder.json(serde_json::json!({"error": self.to_string()}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_codes() {
+        assert_eq!(ApiError::BadRequest("x".into()).status_code(), 400);
+        assert_eq!(ApiError::Unauthorized("x".into()).status_code(), 401);
+        assert_eq!(ApiError::Conflict("x".into()).status_code(), 409);
+        assert_eq!(ApiError::Unavailable("x".into()).status_code(), 503);
+        assert_eq!(ApiError::Database(sqlx::Error::RowNotFound).status_code(), 500);
+    }
+
+    #[test]
+    fn test_token_signing_is_503_without_details() {
+        let err = ApiError::token_signing(anyhow::anyhow!("InvalidKeyFormat"), Uuid::new_v4());
+        let resp = err.error_response();
+
+        assert_eq!(resp.status(), 503);
+        assert_eq!(resp.headers().get("Retry-After").unwrap(), "30");
+        assert_eq!(err.to_string(), "Authentication temporarily unavailable");
+    }
+
+    #[test]
+    fn test_too_many_requests_says_when_to_retry() {
+        let resp = ApiError::TooManyRequests(12).error_response();
+
+        assert_eq!(resp.status(), 429);
+        assert_eq!(resp.headers().get("Retry-After").unwrap(), "12");
+    }
+}
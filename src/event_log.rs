@@ -0,0 +1,236 @@
+//! Persistent log of what was sent to dashboards, for reconstructing what a user was shown.
+//!
+//! Every broadcast event but heartbeats is recorded in `event_log` with its type, patient,
+//! time and the SHA-256 of the JSON subscribers received; the payload itself is not kept.
+//! Each SSE and WebSocket stream is recorded in `stream_sessions` with its user, the
+//! patients it was limited to when it opened, and when it opened and closed. Together they
+//! answer which events a user's open dashboards were sent over a period, as support needs
+//! when an alert is reported missed (`GET /api/admin/event-log/users/{user_id}`), and a
+//! payload a client captured can be checked against its hash.
+//!
+//! Events are written in batches by a subscriber of the broadcast channel, so publishing
+//! never waits on the database. Events it falls behind on are counted in
+//! `event_log_missed_total` rather than held up.
+
+use crate::metrics::EVENT_LOG_MISSED_TOTAL;
+use crate::models::SseEvent;
+use crate::sse::{BroadcastEvent, SseBroadcaster, StreamViewer};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+/// Longest an event waits to be written
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+/// Events written per statement
+const MAX_BATCH: usize = 500;
+
+/// Hex SHA-256 of an event's JSON
+pub fn payload_hash(json: &str) -> String {
+    format!("{:x}", Sha256::digest(json.as_bytes()))
+}
+
+/// An event about to be logged
+#[derive(Debug, Clone)]
+struct LoggedEvent {
+    event_id: Uuid,
+    event_type: &'static str,
+    patient_id: Option<Uuid>,
+    occurred_at: DateTime<Utc>,
+    payload_sha256: String,
+}
+
+impl LoggedEvent {
+    /// `None` for heartbeats, which every stream gets and which carry nothing to investigate
+    fn new(event: &BroadcastEvent) -> Option<Self> {
+        if matches!(event.event, SseEvent::Heartbeat { .. }) {
+            return None;
+        }
+        Some(Self {
+            event_id: event.id,
+            event_type: event.event.event_type(),
+            patient_id: event.patient_id,
+            occurred_at: event.occurred_at,
+            payload_sha256: payload_hash(&event.json()),
+        })
+    }
+}
+
+/// Log the events broadcast from now on, for as long as the broadcaster lives
+pub fn record(broadcaster: &SseBroadcaster, pool: PgPool) -> tokio::task::JoinHandle<()> {
+    let mut rx = broadcaster.subscribe();
+    tokio::spawn(async move {
+        let mut batch = Vec::new();
+        let mut flush_interval = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            tokio::select! {
+                received = rx.recv() => match received {
+                    Ok(event) => {
+                        batch.extend(LoggedEvent::new(&event));
+                        if batch.len() >= MAX_BATCH {
+                            flush(&pool, std::mem::take(&mut batch));
+                        }
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("Event log fell behind and missed {} event(s)", missed);
+                        EVENT_LOG_MISSED_TOTAL.inc_by(missed);
+                    }
+                    Err(RecvError::Closed) => {
+                        flush(&pool, batch);
+                        break;
+                    }
+                },
+                _ = flush_interval.tick() => {
+                    if !batch.is_empty() {
+                        flush(&pool, std::mem::take(&mut batch));
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Write a batch in the background, so the subscriber keeps up with the channel meanwhile
+fn flush(pool: &PgPool, batch: Vec<LoggedEvent>) {
+    if batch.is_empty() {
+        return;
+    }
+    let pool = pool.clone();
+    tokio::spawn(async move {
+        if let Err(e) = insert(&pool, &batch).await {
+            error!("Failed to log {} broadcast event(s): {}", batch.len(), e);
+            EVENT_LOG_MISSED_TOTAL.inc_by(batch.len() as u64);
+        }
+    });
+}
+
+async fn insert(pool: &PgPool, batch: &[LoggedEvent]) -> Result<(), sqlx::Error> {
+    let ids: Vec<Uuid> = batch.iter().map(|e| e.event_id).collect();
+    let types: Vec<&str> = batch.iter().map(|e| e.event_type).collect();
+    let patients: Vec<Option<Uuid>> = batch.iter().map(|e| e.patient_id).collect();
+    let times: Vec<DateTime<Utc>> = batch.iter().map(|e| e.occurred_at).collect();
+    let hashes: Vec<&str> = batch.iter().map(|e| e.payload_sha256.as_str()).collect();
+    sqlx::query(
+        "INSERT INTO event_log (event_id, event_type, patient_id, occurred_at, payload_sha256)
+         SELECT * FROM UNNEST($1::uuid[], $2::text[], $3::uuid[], $4::timestamptz[], $5::text[])
+         ON CONFLICT (event_id) DO NOTHING"
+    )
+    .bind(ids)
+    .bind(types)
+    .bind(patients)
+    .bind(times)
+    .bind(hashes)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// An SSE or WebSocket stream, recorded as closed when dropped
+pub struct StreamSession {
+    pool: PgPool,
+    id: Uuid,
+}
+
+impl StreamSession {
+    /// Record a stream opening for `viewer` over `transport` (`sse` or `websocket`). A stream
+    /// that could not be recorded still works; the failure is logged.
+    pub async fn open(pool: &PgPool, transport: &'static str, viewer: &StreamViewer) -> Option<Self> {
+        let patients: Option<Vec<Uuid>> = viewer.patients().map(|patients| patients.iter().copied().collect());
+        let opened = sqlx::query_scalar(
+            "INSERT INTO stream_sessions (user_id, transport, patients) VALUES ($1, $2, $3) RETURNING id"
+        )
+        .bind(viewer.user_id())
+        .bind(transport)
+        .bind(patients)
+        .fetch_one(pool)
+        .await;
+        match opened {
+            Ok(id) => Some(Self { pool: pool.clone(), id }),
+            Err(e) => {
+                error!(user_id = %viewer.user_id(), "Failed to record a stream session: {}", e);
+                None
+            }
+        }
+    }
+}
+
+impl Drop for StreamSession {
+    fn drop(&mut self) {
+        // Dropped during runtime shutdown there is nothing left to record with
+        if tokio::runtime::Handle::try_current().is_err() {
+            return;
+        }
+        let (pool, id) = (self.pool.clone(), self.id);
+        tokio::spawn(async move {
+            let closed = sqlx::query("UPDATE stream_sessions SET closed_at = now() WHERE id = $1")
+                .bind(id)
+                .execute(&pool)
+                .await;
+            if let Err(e) = closed {
+                error!(session_id = %id, "Failed to record a stream session closing: {}", e);
+            }
+        });
+    }
+}
+
+/// Delete events and sessions older than `days`, as for readings, except those about (or
+/// that may have carried) patients under an active legal hold
+pub async fn purge_expired(pool: &PgPool, days: i64) -> Result<u64, sqlx::Error> {
+    let events = sqlx::query(
+        "DELETE FROM event_log e
+         WHERE e.occurred_at < now() - make_interval(days => $1::int)
+           AND NOT EXISTS (
+               SELECT 1 FROM legal_holds h WHERE h.patient_id = e.patient_id AND h.released_at IS NULL
+           )"
+    )
+    .bind(days)
+    .execute(pool)
+    .await?
+    .rows_affected();
+    let sessions = sqlx::query(
+        "DELETE FROM stream_sessions s
+         WHERE COALESCE(s.closed_at, s.opened_at) < now() - make_interval(days => $1::int)
+           AND NOT EXISTS (
+               SELECT 1 FROM legal_holds h
+               WHERE h.released_at IS NULL AND (s.patients IS NULL OR h.patient_id = ANY(s.patients))
+           )"
+    )
+    .bind(days)
+    .execute(pool)
+    .await?
+    .rows_affected();
+    Ok(events + sessions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{EventEnvelope, LatestVitals};
+
+    #[test]
+    fn test_logged_hash_matches_what_subscribers_receive() {
+        let vitals = LatestVitals {
+            heart_rate: 72,
+            spo2: 97,
+            temperature: 36.8,
+            timestamp: 1700000000,
+            quality_score: None,
+            ml_alert: None,
+        };
+        let patient_id = Uuid::new_v4();
+        let event = BroadcastEvent::new(EventEnvelope::new(SseEvent::Vitals(vitals)).for_patient(Some(patient_id))).unwrap();
+
+        let logged = LoggedEvent::new(&event).unwrap();
+        assert_eq!(logged.event_id, event.id);
+        assert_eq!(logged.event_type, "vitals");
+        assert_eq!(logged.patient_id, Some(patient_id));
+        assert_eq!(logged.payload_sha256, payload_hash(&serde_json::to_string(&*event).unwrap()));
+        assert_eq!(logged.payload_sha256.len(), 64);
+
+        let heartbeat = BroadcastEvent::new(EventEnvelope::new(SseEvent::Heartbeat { timestamp: 0 })).unwrap();
+        assert!(LoggedEvent::new(&heartbeat).is_none());
+    }
+}
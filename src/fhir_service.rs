@@ -1,10 +1,11 @@
 use crate::config::FhirConfig;
 use crate::models::{
-    FhirCodeableConcept, FhirCoding, FhirObservationResource, FhirQuantity, FhirReference,
-    SensorReading,
+    CarePlan, Checkin, Device, FhirCodeableConcept, FhirCoding, FhirObservationResource, FhirQuantity, FhirReference,
+    OrganizationProfile, Patient, SensorReading,
 };
-use chrono::Utc;
+use chrono::{DateTime, SecondsFormat, Utc};
 use serde_json::{json, Value};
+use sqlx::PgPool;
 use uuid::Uuid;
 
 pub struct FhirService {
@@ -16,13 +17,18 @@ impl FhirService {
         Self { config }
     }
 
+    /// The id of this deployment's Organization resource, `fhir.organization_id`
+    pub fn organization_id(&self) -> &str {
+        &self.config.organization_id
+    }
+
     /// Convert a sensor reading to FHIR Observation resource for Heart Rate
     pub fn create_heart_rate_observation(
         &self,
         reading: &SensorReading,
         patient_reference: Option<String>,
     ) -> Value {
-        let heart_rate = reading.heart_rate.unwrap_or(0) as f32;
+        let heart_rate = f64::from(reading.heart_rate.unwrap_or(0));
 
         let observation = FhirObservationResource {
             resourceType: "Observation".to_string(),
@@ -49,7 +55,7 @@ impl FhirService {
             },
         };
 
-        serde_json::to_value(observation).unwrap_or(json!({}))
+        with_device_extensions(reading, serde_json::to_value(observation).unwrap_or(json!({})))
     }
 
     /// Convert a sensor reading to FHIR Observation resource for SpO2
@@ -58,7 +64,7 @@ impl FhirService {
         reading: &SensorReading,
         patient_reference: Option<String>,
     ) -> Value {
-        let spo2 = reading.spo2.unwrap_or(0) as f32;
+        let spo2 = f64::from(reading.spo2.unwrap_or(0));
 
         let observation = FhirObservationResource {
             resourceType: "Observation".to_string(),
@@ -85,7 +91,7 @@ impl FhirService {
             },
         };
 
-        serde_json::to_value(observation).unwrap_or(json!({}))
+        with_device_extensions(reading, serde_json::to_value(observation).unwrap_or(json!({})))
     }
 
     /// Convert a sensor reading to FHIR Observation resource for Body Temperature
@@ -94,7 +100,8 @@ impl FhirService {
         reading: &SensorReading,
         patient_reference: Option<String>,
     ) -> Value {
-        let temp = reading.temperature.unwrap_or(0.0);
+        // Round to sensor precision so f32 storage noise doesn't leak into the resource
+        let temp = (f64::from(reading.temperature.unwrap_or(0.0)) * 100.0).round() / 100.0;
 
         let observation = FhirObservationResource {
             resourceType: "Observation".to_string(),
@@ -121,34 +128,129 @@ impl FhirService {
             },
         };
 
-        serde_json::to_value(observation).unwrap_or(json!({}))
+        with_device_extensions(reading, serde_json::to_value(observation).unwrap_or(json!({})))
+    }
+
+    /// Convert a patient record to a FHIR Patient resource
+    pub fn create_patient_resource(&self, patient: &Patient) -> Value {
+        let mut resource = json!({
+            "resourceType": "Patient",
+            "id": patient.id.to_string(),
+            "identifier": [{
+                "system": "http://medhealth.local/patient-id",
+                "value": patient.id.to_string()
+            }],
+            "name": [{"text": patient.display_name}],
+        });
+        if let Some(birth_date) = patient.date_of_birth {
+            resource["birthDate"] = json!(birth_date.to_string());
+        }
+        resource
+    }
+
+    /// This deployment's organization as a FHIR Organization resource; the logo travels in an
+    /// extension since Organization has no element for one
+    pub fn create_organization_resource(&self, profile: &OrganizationProfile) -> Value {
+        let mut resource = json!({
+            "resourceType": "Organization",
+            "id": self.config.organization_id,
+            "identifier": [{
+                "system": "http://medhealth.local/organization",
+                "value": self.config.organization_id
+            }],
+            "active": true,
+            "name": profile.name,
+        });
+
+        let telecom: Vec<Value> = [("phone", &profile.phone), ("email", &profile.email), ("url", &profile.website)]
+            .into_iter()
+            .filter_map(|(system, value)| value.as_ref().map(|value| json!({"system": system, "value": value})))
+            .collect();
+        if !telecom.is_empty() {
+            resource["telecom"] = json!(telecom);
+        }
+        if let Some(address) = &profile.address {
+            let lines: Vec<&str> = address.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+            resource["address"] = json!([{"text": lines.join(", "), "line": lines}]);
+        }
+        if let Some(logo_url) = &profile.logo_url {
+            resource["extension"] = json!([{
+                "url": "http://medhealth.local/fhir/StructureDefinition/organization-logo",
+                "valueUrl": logo_url
+            }]);
+        }
+        resource
+    }
+
+    /// Convert a walker to a FHIR Device resource, linked to its patient when claimed
+    pub fn create_device_resource(&self, device: &Device) -> Value {
+        let mut resource = json!({
+            "resourceType": "Device",
+            "id": device.id.to_string(),
+            "identifier": [{
+                "system": "http://medhealth.local/device-serial",
+                "value": device.device_id
+            }],
+            "status": if device.is_active { "active" } else { "inactive" },
+            "deviceName": [{"name": device.device_name, "type": "user-friendly-name"}],
+            "type": {"text": "Smart walker"},
+        });
+        if let Some(patient_id) = device.patient_id {
+            resource["patient"] = json!({"reference": format!("Patient/{}", patient_id)});
+        }
+        resource
+    }
+
+    /// Bundle entry for `resource`, with the `fullUrl` its references resolve against
+    fn entry(&self, resource: Value) -> Value {
+        let full_url = format!(
+            "{}/{}/{}",
+            self.config.base_url,
+            resource["resourceType"].as_str().unwrap_or_default(),
+            resource["id"].as_str().unwrap_or_default()
+        );
+        json!({"fullUrl": full_url, "resource": resource})
+    }
+
+    /// One Observation per vital present on the reading
+    pub fn create_observations(&self, reading: &SensorReading, patient_reference: Option<String>) -> Vec<Value> {
+        let mut observations = vec![];
+
+        if reading.heart_rate.is_some() {
+            observations.push(self.create_heart_rate_observation(reading, patient_reference.clone()));
+        }
+
+        if reading.spo2.is_some() {
+            observations.push(self.create_spo2_observation(reading, patient_reference.clone()));
+        }
+
+        if reading.temperature.is_some() {
+            observations.push(self.create_temperature_observation(reading, patient_reference));
+        }
+
+        observations
     }
 
-    /// Create a FHIR Bundle containing all observations for a reading
+    /// Create a FHIR Bundle containing all observations for a reading, preceded by the
+    /// Patient and Device they reference so the Bundle stands on its own
     pub fn create_observation_bundle(
         &self,
         reading: &SensorReading,
-        patient_reference: Option<String>,
+        device: Option<&Device>,
+        patient: Option<&Patient>,
     ) -> Value {
+        let patient_reference = patient.map(|p| format!("Patient/{}", p.id));
         let mut entries = vec![];
 
-        if reading.heart_rate.is_some() {
-            entries.push(json!({
-                "resource": self.create_heart_rate_observation(reading, patient_reference.clone())
-            }));
+        if let Some(patient) = patient {
+            entries.push(self.entry(self.create_patient_resource(patient)));
         }
 
-        if reading.spo2.is_some() {
-            entries.push(json!({
-                "resource": self.create_spo2_observation(reading, patient_reference.clone())
-            }));
+        if let Some(device) = device {
+            entries.push(self.entry(self.create_device_resource(device)));
         }
 
-        if reading.temperature.is_some() {
-            entries.push(json!({
-                "resource": self.create_temperature_observation(reading, patient_reference.clone())
-            }));
-        }
+        entries.extend(self.create_observations(reading, patient_reference).into_iter().map(|o| self.entry(o)));
 
         json!({
             "resourceType": "Bundle",
@@ -166,6 +268,191 @@ impl FhirService {
         })
     }
 
+    /// `bundle`'s entries as a `transaction` for another FHIR server, each a `PUT` under the
+    /// resource's own id so sending it again updates rather than duplicates
+    pub fn create_transaction_bundle(&self, bundle: &Value) -> Value {
+        let entries: Vec<Value> = bundle["entry"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|entry| {
+                let resource = &entry["resource"];
+                let mut entry = entry.clone();
+                entry["request"] = json!({
+                    "method": "PUT",
+                    "url": format!(
+                        "{}/{}",
+                        resource["resourceType"].as_str().unwrap_or_default(),
+                        resource["id"].as_str().unwrap_or_default()
+                    )
+                });
+                entry
+            })
+            .collect();
+
+        json!({
+            "resourceType": "Bundle",
+            "id": Uuid::new_v4().to_string(),
+            "type": "transaction",
+            "timestamp": Utc::now().to_rfc3339(),
+            "meta": bundle["meta"],
+            "entry": entries
+        })
+    }
+
+    /// One page of search results as a searchset Bundle; `total` counts every match, not
+    /// just this page's
+    pub fn create_searchset_bundle(&self, entries: Vec<Value>, total: i64, links: Vec<Value>) -> Value {
+        json!({
+            "resourceType": "Bundle",
+            "id": Uuid::new_v4().to_string(),
+            "type": "searchset",
+            "timestamp": Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
+            "meta": {
+                "source": self.config.base_url,
+                "tag": [{
+                    "system": "http://medhealth.local/organization",
+                    "code": self.config.organization_id
+                }]
+            },
+            "total": total,
+            "link": links,
+            "entry": entries
+        })
+    }
+
+    /// Convert a care plan to a FHIR CarePlan plus one Goal per target, as a collection Bundle
+    pub fn create_care_plan_bundle(&self, plan: &CarePlan) -> Value {
+        let subject = json!({"reference": format!("Patient/{}", plan.patient_id)});
+        let lifecycle = match plan.status.as_str() {
+            "draft" => "proposed",
+            "completed" => "completed",
+            "revoked" => "cancelled",
+            _ => "active",
+        };
+
+        let goal = |suffix: &str, text: &str, loinc: &str, display: &str, detail: Value| {
+            json!({
+                "resourceType": "Goal",
+                "id": format!("{}-{}", plan.id, suffix),
+                "lifecycleStatus": lifecycle,
+                "description": {"text": text},
+                "subject": subject,
+                "startDate": plan.start_date.to_string(),
+                "target": [{
+                    "measure": {
+                        "coding": [{"system": "http://loinc.org", "code": loinc, "display": display}]
+                    },
+                    "detailQuantity": detail.get("quantity"),
+                    "detailRange": detail.get("range"),
+                }]
+            })
+        };
+        let quantity = |value: i32, unit: &str, code: &str| {
+            json!({"quantity": {"value": value, "unit": unit, "system": "http://unitsofmeasure.org", "code": code}})
+        };
+
+        let mut goals = Vec::new();
+        if let Some(steps) = plan.target_steps {
+            goals.push(goal("steps", "Daily step count", "41950-7", "Number of steps in 24 hour Measured",
+                quantity(steps, "steps/day", "/d")));
+        }
+        if let Some(minutes) = plan.target_activity_minutes {
+            goals.push(goal("activity", "Daily active minutes", "55411-3", "Exercise duration",
+                quantity(minutes, "minutes/day", "min/d")));
+        }
+        if plan.hr_min.is_some() || plan.hr_max.is_some() {
+            let bound = |v: Option<i32>| v.map(|v| json!({"value": v, "unit": "beats/minute", "system": "http://unitsofmeasure.org", "code": "/min"}));
+            goals.push(goal("heart-rate", "Heart rate within range", "8867-4", "Heart rate",
+                json!({"range": {"low": bound(plan.hr_min), "high": bound(plan.hr_max)}})));
+        }
+        if let Some(spo2) = plan.spo2_min {
+            goals.push(goal("spo2", "Oxygen saturation at or above minimum", "2708-6", "Oxygen saturation in Arterial blood",
+                json!({"range": {"low": {"value": spo2, "unit": "percent", "system": "http://unitsofmeasure.org", "code": "%"}}})));
+        }
+
+        // Drop absent detail[x] choices so each target carries exactly one
+        for goal in &mut goals {
+            if let Some(target) = goal["target"][0].as_object_mut() {
+                target.retain(|_, v| !v.is_null());
+            }
+        }
+
+        let mut period = json!({"start": plan.start_date.to_string()});
+        if let Some(end) = plan.end_date {
+            period["end"] = json!(end.to_string());
+        }
+
+        let care_plan = json!({
+            "resourceType": "CarePlan",
+            "id": plan.id.to_string(),
+            "status": match plan.status.as_str() {
+                "draft" => "draft",
+                "completed" => "completed",
+                "revoked" => "revoked",
+                _ => "active",
+            },
+            "intent": "plan",
+            "title": plan.title,
+            "subject": subject,
+            "period": period,
+            "created": plan.created_at.to_rfc3339(),
+            "goal": goals.iter().map(|g| json!({"reference": format!("Goal/{}", g["id"].as_str().unwrap_or_default())})).collect::<Vec<_>>(),
+        });
+
+        let entries: Vec<Value> = std::iter::once(care_plan)
+            .chain(goals)
+            .map(|resource| json!({"resource": resource}))
+            .collect();
+
+        json!({
+            "resourceType": "Bundle",
+            "id": Uuid::new_v4().to_string(),
+            "type": "collection",
+            "timestamp": Utc::now().to_rfc3339(),
+            "entry": entries
+        })
+    }
+
+    /// Convert a symptom check-in to a FHIR QuestionnaireResponse (one item per answered question)
+    pub fn create_checkin_questionnaire_response(&self, checkin: &Checkin) -> Value {
+        let mut items = Vec::new();
+        let mut integer = |link_id: &str, text: &str, value: Option<i32>| {
+            if let Some(v) = value {
+                items.push(json!({"linkId": link_id, "text": text, "answer": [{"valueInteger": v}]}));
+            }
+        };
+        integer("pain-score", "Pain score (0-10)", checkin.pain_score);
+        integer("dizziness", "Dizziness (0-10)", checkin.dizziness);
+        integer("fatigue", "Fatigue (0-10)", checkin.fatigue);
+
+        let mut boolean = |link_id: &str, text: &str, value: Option<bool>| {
+            if let Some(v) = value {
+                items.push(json!({"linkId": link_id, "text": text, "answer": [{"valueBoolean": v}]}));
+            }
+        };
+        boolean("shortness-of-breath", "Shortness of breath", checkin.shortness_of_breath);
+        boolean("fell-since-last", "Fallen since last check-in", checkin.fell_since_last);
+
+        if let Some(notes) = checkin.notes.as_deref().filter(|n| !n.trim().is_empty()) {
+            items.push(json!({"linkId": "notes", "text": "Notes", "answer": [{"valueString": notes}]}));
+        }
+
+        let mut resource = json!({
+            "resourceType": "QuestionnaireResponse",
+            "id": checkin.id.to_string(),
+            "questionnaire": format!("{}/Questionnaire/walker-checkin", self.config.base_url),
+            "status": "completed",
+            "subject": {"reference": format!("Patient/{}", checkin.patient_id)},
+            "authored": checkin.submitted_at.to_rfc3339(),
+            "item": items,
+        });
+        if let Some(author) = checkin.submitted_by {
+            resource["author"] = json!({"reference": format!("Practitioner/{}", author)});
+        }
+        resource
+    }
+
     /// Validate FHIR resource (basic validation)
     pub fn validate_observation(&self, resource: &Value) -> bool {
         resource.get("resourceType").and_then(|v| v.as_str()) == Some("Observation")
@@ -174,6 +461,91 @@ impl FhirService {
     }
 }
 
+/// Base of the extension URLs carrying walker-reported metadata on Observations
+const METADATA_EXTENSION_BASE: &str = "http://medhealth.local/fhir/StructureDefinition/reading-";
+
+/// Add the reading's surfaced walker metadata (see [`crate::models::SURFACED_READING_METADATA`]) to an
+/// Observation as extensions, e.g. `reading-firmware-version`
+fn with_device_extensions(reading: &SensorReading, mut observation: Value) -> Value {
+    let extensions: Vec<Value> = reading
+        .surfaced_device_metadata()
+        .filter_map(|(key, value)| {
+            let value_key = match value {
+                Value::String(_) => "valueString",
+                Value::Bool(_) => "valueBoolean",
+                Value::Number(n) if n.is_i64() => "valueInteger",
+                Value::Number(_) => "valueDecimal",
+                _ => return None,
+            };
+            Some(json!({"url": format!("{}{}", METADATA_EXTENSION_BASE, key.replace('_', "-")), value_key: value}))
+        })
+        .collect();
+    if !extensions.is_empty() {
+        observation["extension"] = Value::Array(extensions);
+    }
+    observation
+}
+
+/// Keep the Patient and Device resources of an observation bundle, replacing the ones
+/// stored for the same records
+pub async fn store_subjects(pool: &PgPool, bundle: &Value) -> Result<(), sqlx::Error> {
+    for resource in bundle["entry"].as_array().into_iter().flatten().map(|e| &e["resource"]) {
+        let (table, key) = match resource["resourceType"].as_str() {
+            Some("Patient") => ("fhir_patients", "patient_id"),
+            Some("Device") => ("fhir_devices", "device_id"),
+            _ => continue,
+        };
+        let Some(id) = resource["id"].as_str().and_then(|id| Uuid::parse_str(id).ok()) else { continue };
+        sqlx::query(&format!(
+            "INSERT INTO {table} ({key}, resource) VALUES ($1, $2)
+             ON CONFLICT ({key}) DO UPDATE SET resource = EXCLUDED.resource, updated_at = now()
+             WHERE {table}.resource IS DISTINCT FROM EXCLUDED.resource"
+        ))
+        .bind(id)
+        .bind(resource)
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Opaque position after a reading in export order, `{received_at µs}.{id}`
+pub fn export_cursor(received_at: DateTime<Utc>, id: i64) -> String {
+    format!("{}.{}", received_at.timestamp_micros(), id)
+}
+
+/// The reading position an [`export_cursor`] stands for
+pub fn parse_export_cursor(cursor: &str) -> Option<(DateTime<Utc>, i64)> {
+    let (micros, id) = cursor.split_once('.')?;
+    Some((DateTime::from_timestamp_micros(micros.parse().ok()?)?, id.parse().ok()?))
+}
+
+/// Bundle `link`s for a page of `count`: `self`, and `next` continuing after `next_cursor`
+/// when there is more. `url` is the search URL without a query; `params` are the search
+/// parameters to carry from page to page (their values must already be safe in a query string).
+pub fn page_links(
+    url: &str,
+    params: &[(&str, String)],
+    cursor: Option<&str>,
+    count: i64,
+    next_cursor: Option<&str>,
+) -> Vec<Value> {
+    let page_url = |cursor: Option<&str>| {
+        let mut query: String = params.iter().map(|(name, value)| format!("{}={}&", name, value)).collect();
+        query.push_str(&format!("_count={}", count));
+        if let Some(cursor) = cursor {
+            query.push_str(&format!("&_cursor={}", cursor));
+        }
+        format!("{}?{}", url, query)
+    };
+
+    let mut links = vec![json!({"relation": "self", "url": page_url(cursor)})];
+    if let Some(next) = next_cursor {
+        links.push(json!({"relation": "next", "url": page_url(Some(next))}));
+    }
+    links
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -238,18 +610,216 @@ mod tests {
         assert_eq!(observation["valueQuantity"]["value"], 36.8);
     }
 
+    #[test]
+    fn test_surfaced_metadata_as_extensions() {
+        let service = FhirService::new(create_test_config());
+        let reading = SensorReading {
+            metadata: json!({"steps": 4, "device": {"firmware_version": "2.1.0", "signal_strength": -61, "debug_flags": 7}}),
+            ..create_test_reading()
+        };
+
+        let observation = service.create_heart_rate_observation(&reading, None);
+        let extensions = observation["extension"].as_array().unwrap();
+        assert_eq!(extensions.len(), 2);
+        assert_eq!(extensions[0]["url"], "http://medhealth.local/fhir/StructureDefinition/reading-firmware-version");
+        assert_eq!(extensions[0]["valueString"], "2.1.0");
+        assert_eq!(extensions[1]["valueInteger"], -61);
+
+        let plain = service.create_heart_rate_observation(&create_test_reading(), None);
+        assert!(plain.get("extension").is_none());
+    }
+
     #[test]
     fn test_bundle_creation() {
         let service = FhirService::new(create_test_config());
         let reading = create_test_reading();
 
-        let bundle = service.create_observation_bundle(&reading, Some("Patient/123".to_string()));
+        let bundle = service.create_observation_bundle(&reading, None, None);
 
         assert_eq!(bundle["resourceType"], "Bundle");
         assert_eq!(bundle["type"], "collection");
         assert_eq!(bundle["entry"].as_array().unwrap().len(), 3); // HR, SpO2, Temp
     }
 
+    #[test]
+    fn test_bundle_carries_its_patient_and_device() {
+        let service = FhirService::new(create_test_config());
+        let patient = Patient {
+            id: Uuid::new_v4(),
+            display_name: "Ada Walker".to_string(),
+            date_of_birth: chrono::NaiveDate::from_ymd_opt(1941, 3, 9),
+        };
+        let device = Device {
+            id: Uuid::new_v4(),
+            device_id: "WALKER-7".to_string(),
+            device_name: "Ward 3 walker".to_string(),
+            secret_hash: String::new(),
+            secret_ciphertext: None,
+            secret_issued_at: None,
+            is_active: true,
+            created_at: Utc::now(),
+            last_seen_at: None,
+            metadata: json!({}),
+            patient_id: Some(patient.id),
+            claimed_by: None,
+            claimed_at: None,
+            units: Default::default(),
+        };
+        let reading = SensorReading { device_id: device.id, ..create_test_reading() };
+
+        let bundle = service.create_observation_bundle(&reading, Some(&device), Some(&patient));
+        let entries = bundle["entry"].as_array().unwrap();
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[0]["resource"]["birthDate"], "1941-03-09");
+        assert_eq!(entries[1]["resource"]["identifier"][0]["value"], "WALKER-7");
+
+        // Every reference resolves to an entry in the same Bundle
+        let full_urls: Vec<&str> = entries.iter().map(|e| e["fullUrl"].as_str().unwrap()).collect();
+        let resolves = |reference: &Value| {
+            let url = format!("http://localhost:8080/fhir/{}", reference.as_str().unwrap());
+            full_urls.contains(&url.as_str())
+        };
+        assert!(resolves(&entries[1]["resource"]["patient"]["reference"]));
+        for observation in &entries[2..] {
+            assert!(resolves(&observation["resource"]["subject"]["reference"]));
+            assert!(resolves(&observation["resource"]["device"]["reference"]));
+        }
+    }
+
+    #[test]
+    fn test_transaction_bundle_puts_each_resource() {
+        let service = FhirService::new(create_test_config());
+        let reading = create_test_reading();
+        let bundle = service.create_observation_bundle(&reading, None, None);
+
+        let transaction = service.create_transaction_bundle(&bundle);
+        assert_eq!(transaction["type"], "transaction");
+        let entries = transaction["entry"].as_array().unwrap();
+        assert_eq!(entries.len(), 3);
+        for (entry, original) in entries.iter().zip(bundle["entry"].as_array().unwrap()) {
+            assert_eq!(entry["resource"], original["resource"]);
+            assert_eq!(entry["request"]["method"], "PUT");
+            assert_eq!(entry["request"]["url"], format!("Observation/{}", original["resource"]["id"].as_str().unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_organization_resource() {
+        let service = FhirService::new(create_test_config());
+        let mut profile = OrganizationProfile {
+            name: "St. Mary's Rehab".to_string(),
+            logo_url: None,
+            phone: Some("+15551234567".to_string()),
+            email: None,
+            website: Some("https://stmarys.example".to_string()),
+            address: Some("1 Main St\nSpringfield".to_string()),
+            updated_at: Utc::now(),
+            updated_by: None,
+        };
+
+        let organization = service.create_organization_resource(&profile);
+        assert_eq!(organization["id"], "org-test-001");
+        assert_eq!(organization["name"], "St. Mary's Rehab");
+        assert_eq!(organization["telecom"], json!([
+            {"system": "phone", "value": "+15551234567"},
+            {"system": "url", "value": "https://stmarys.example"}
+        ]));
+        assert_eq!(organization["address"][0]["line"], json!(["1 Main St", "Springfield"]));
+        assert!(organization.get("extension").is_none());
+
+        profile.logo_url = Some("https://stmarys.example/logo.png".to_string());
+        let organization = service.create_organization_resource(&profile);
+        assert_eq!(organization["extension"][0]["valueUrl"], "https://stmarys.example/logo.png");
+    }
+
+    #[test]
+    fn test_searchset_page_links() {
+        let url = "https://ehr.example/api/fhir/export";
+        let since = [("_since", "2024-01-01T00:00:00Z".to_string())];
+        let relations = |links: &[Value]| -> Vec<(String, String)> {
+            links
+                .iter()
+                .map(|l| (l["relation"].as_str().unwrap().to_string(), l["url"].as_str().unwrap().to_string()))
+                .collect()
+        };
+
+        let received_at = DateTime::parse_from_rfc3339("2024-01-01T08:30:00.123456Z").unwrap().with_timezone(&Utc);
+        let cursor = export_cursor(received_at, 42);
+        assert_eq!(parse_export_cursor(&cursor), Some((received_at, 42)));
+        assert_eq!(parse_export_cursor("42"), None);
+        assert_eq!(parse_export_cursor("soon.42"), None);
+
+        let first = relations(&page_links(url, &since, None, 10, Some(&cursor)));
+        assert_eq!(first[0], ("self".into(), format!("{}?_since=2024-01-01T00:00:00Z&_count=10", url)));
+        assert_eq!(first[1], ("next".into(), format!("{}?_since=2024-01-01T00:00:00Z&_count=10&_cursor={}", url, cursor)));
+        assert_eq!(first.len(), 2);
+
+        let last = relations(&page_links(url, &[], Some(&cursor), 10, None));
+        assert_eq!(last, vec![("self".into(), format!("{}?_count=10&_cursor={}", url, cursor))]);
+
+        let service = FhirService::new(create_test_config());
+        let bundle = service.create_searchset_bundle(vec![], 42, page_links(url, &[], None, 10, None));
+        assert_eq!((bundle["type"].as_str(), bundle["total"].as_i64()), (Some("searchset"), Some(42)));
+        assert_eq!(bundle["link"][0]["url"], format!("{}?_count=10", url));
+    }
+
+    #[test]
+    fn test_care_plan_bundle() {
+        let service = FhirService::new(create_test_config());
+        let plan = CarePlan {
+            id: Uuid::new_v4(),
+            patient_id: Uuid::new_v4(),
+            title: "Mobility".to_string(),
+            status: "active".to_string(),
+            target_steps: Some(3000),
+            target_activity_minutes: None,
+            prescribed_usage_minutes: None,
+            hr_min: Some(50),
+            hr_max: Some(110),
+            spo2_min: None,
+            start_date: chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            end_date: None,
+            created_by: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+
+        let bundle = service.create_care_plan_bundle(&plan);
+        let entries = bundle["entry"].as_array().unwrap();
+
+        assert_eq!(entries.len(), 3); // CarePlan, steps Goal, heart rate Goal
+        assert_eq!(entries[0]["resource"]["resourceType"], "CarePlan");
+        assert_eq!(entries[0]["resource"]["goal"].as_array().unwrap().len(), 2);
+        assert_eq!(entries[1]["resource"]["target"][0]["detailQuantity"]["value"], 3000);
+        assert_eq!(entries[2]["resource"]["target"][0]["detailRange"]["high"]["value"], 110);
+        assert!(entries[2]["resource"]["target"][0].get("detailQuantity").is_none());
+    }
+
+    #[test]
+    fn test_checkin_questionnaire_response() {
+        let service = FhirService::new(create_test_config());
+        let checkin = Checkin {
+            id: Uuid::new_v4(),
+            patient_id: Uuid::new_v4(),
+            submitted_by: None,
+            pain_score: Some(4),
+            dizziness: None,
+            fatigue: None,
+            shortness_of_breath: Some(false),
+            fell_since_last: None,
+            notes: Some("Knee sore after walk".to_string()),
+            submitted_at: Utc::now(),
+        };
+
+        let resource = service.create_checkin_questionnaire_response(&checkin);
+
+        assert_eq!(resource["resourceType"], "QuestionnaireResponse");
+        assert_eq!(resource["status"], "completed");
+        assert_eq!(resource["item"].as_array().unwrap().len(), 3);
+        assert_eq!(resource["item"][0]["answer"][0]["valueInteger"], 4);
+        assert!(resource.get("author").is_none());
+    }
+
     #[test]
     fn test_observation_validation() {
         let service = FhirService::new(create_test_config());
@@ -0,0 +1,522 @@
+use crate::device_secrets::{generate_secret, hash_secret};
+use crate::emergency_service::{load_responses, raise_test_alert};
+use crate::errors::ApiError;
+use crate::handlers::device::AnalysisInputs;
+use crate::handlers::{require_unscoped, AppState};
+use crate::logging;
+use crate::middleware::AuthenticatedUser;
+use crate::models::*;
+use crate::pairing::create_pairing_code;
+use crate::slo_service;
+use crate::sse::{broadcast_vitals, create_broadcaster};
+use actix_web::{web, HttpResponse, Responder, ResponseError};
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::time::Instant;
+use uuid::Uuid;
+use validator::Validate;
+
+crate::routes::route_registry! {
+    "/selftest" {
+        POST => run_selftest, Jwt, ["admin"];
+    }
+    "/devices/{device_id}/pairing-code" {
+        POST => issue_pairing_code, Jwt, ["admin", "device_manager"];
+    }
+    "/devices/{device_id}/secret" {
+        POST => issue_device_secret, Jwt, ["admin", "device_manager"];
+    }
+    "/test-alert" {
+        POST => inject_test_alert, Jwt, ["admin"];
+    }
+    "/slo" {
+        GET => slo_report, Jwt, ["admin"];
+    }
+    "/log-level" {
+        GET => get_log_level, Jwt, ["admin"];
+        PUT => set_log_level, Jwt, ["admin"];
+    }
+    "/organization" {
+        GET => get_organization_profile, Jwt, ["admin"];
+        PUT => update_organization_profile, Jwt, ["admin"];
+    }
+    "/ml/simulate" {
+        POST => simulate_thresholds, Jwt, ["admin"];
+    }
+    "/users/{user_id}/unlock" {
+        POST => unlock_user, Jwt, ["admin"];
+    }
+}
+
+// ============ Accounts ============
+
+/// Lift a lockout after failed logins and clear the count behind it, for a user of the
+/// caller's organization
+pub async fn unlock_user(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = path.into_inner();
+    let updated = sqlx::query(
+        "UPDATE users SET failed_login_attempts = 0, locked_until = NULL, updated_at = now()
+         WHERE id = $1 AND ($2::uuid IS NULL OR organization_id = $2)"
+    )
+    .bind(user_id)
+    .bind(claims.org)
+    .execute(&state.pool)
+    .await?;
+    if updated.rows_affected() == 0 {
+        return Err(ApiError::NotFound("User not found".into()));
+    }
+
+    crate::audit_log!("auth", "account_unlocked", Some(claims.user_id), true, user_id);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// ============ Threshold Simulation ============
+
+/// Replay stored readings against a proposed threshold set next to the rules each walker is
+/// judged by now, counting the alerts either would have raised. Nothing is stored or pushed.
+pub async fn simulate_thresholds(
+    admin: AuthenticatedUser,
+    state: web::Data<AppState>,
+    body: web::Json<MlSimulationRequest>,
+) -> Result<HttpResponse, ApiError> {
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let body = body.into_inner();
+    if body.from >= body.to {
+        return Err(ApiError::BadRequest("from must be before to".into()));
+    }
+    if body.to - body.from > Duration::days(MAX_SIMULATION_DAYS) {
+        return Err(ApiError::BadRequest(format!("The range can span at most {} days", MAX_SIMULATION_DAYS)));
+    }
+    if let Some(patient_id) = body.patient_id {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM patients
+                            WHERE id = $1 AND ($2::uuid IS NULL OR organization_id = $2))"
+        )
+        .bind(patient_id)
+        .bind(admin.org)
+        .fetch_one(&state.pool)
+        .await?;
+        if !exists {
+            return Err(ApiError::NotFound("Patient not found".into()));
+        }
+    }
+
+    let devices: Vec<Device> = sqlx::query_as(
+        "SELECT d.* FROM devices d
+         WHERE d.patient_id IS NOT NULL AND ($3::uuid IS NULL OR d.patient_id = $3)
+           AND ($4::uuid IS NULL OR d.organization_id = $4)
+           AND EXISTS (SELECT 1 FROM sensor_readings r
+                       WHERE r.device_id = d.id AND r.reading_timestamp >= $1 AND r.reading_timestamp < $2)
+         ORDER BY d.device_id"
+    )
+    .bind(body.from)
+    .bind(body.to)
+    .bind(body.patient_id)
+    .bind(admin.org)
+    .fetch_all(&state.pool)
+    .await?;
+
+    let mut simulation = MlSimulation {
+        from: body.from,
+        to: body.to,
+        patient_id: body.patient_id,
+        devices: devices.len(),
+        readings: 0,
+        current: SimulationCounts::default(),
+        proposed: SimulationCounts::default(),
+    };
+    let ml = &state.ml_service;
+    for device in &devices {
+        let inputs = AnalysisInputs::load(&state, device).await;
+        let lookback = ml.history_lookback(&inputs.rules).max(ml.history_lookback(&body.rules));
+        let history: Vec<SensorReading> = sqlx::query_as(
+            "SELECT * FROM sensor_readings
+             WHERE device_id = $1 AND reading_timestamp >= $2 AND reading_timestamp < $3
+             ORDER BY reading_timestamp, id"
+        )
+        .bind(device.id)
+        .bind(body.from - lookback)
+        .bind(body.to)
+        .fetch_all(&state.pool)
+        .await?;
+
+        simulation.readings += history.iter().filter(|r| r.reading_timestamp >= body.from).count();
+        let (patient, baseline) = (inputs.patient.as_ref(), inputs.baseline.as_ref());
+        simulation.current.add(&ml.simulate(&history, body.from, patient, baseline, &inputs.rules));
+        simulation.proposed.add(&ml.simulate(&history, body.from, patient, baseline, &body.rules));
+    }
+
+    Ok(HttpResponse::Ok().json(simulation))
+}
+
+// ============ Organization Profile ============
+
+/// The deployment's name, logo and contact details (see `migrations/036_organization_profile.sql`)
+pub async fn load_organization_profile(pool: &PgPool) -> Result<OrganizationProfile, sqlx::Error> {
+    sqlx::query_as("SELECT name, logo_url, phone, email, website, address, updated_at, updated_by FROM organization_profile")
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn get_organization_profile(
+    _admin: AuthenticatedUser,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(load_organization_profile(&state.pool).await?))
+}
+
+/// Replace the profile; reports and FHIR resources use it from the next request
+pub async fn update_organization_profile(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    body: web::Json<OrganizationProfileRequest>,
+) -> Result<HttpResponse, ApiError> {
+    require_unscoped(&claims)?;
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    let profile: OrganizationProfile = sqlx::query_as(
+        "UPDATE organization_profile
+         SET name = $1, logo_url = $2, phone = $3, email = $4, website = $5, address = $6,
+             updated_at = now(), updated_by = $7
+         RETURNING name, logo_url, phone, email, website, address, updated_at, updated_by"
+    )
+    .bind(body.name.trim())
+    .bind(&body.logo_url)
+    .bind(&body.phone)
+    .bind(&body.email)
+    .bind(&body.website)
+    .bind(&body.address)
+    .bind(claims.user_id)
+    .fetch_one(&state.pool)
+    .await?;
+
+    crate::audit_log!("admin", "update_organization_profile", Some(claims.user_id), true, &profile.name);
+    Ok(HttpResponse::Ok().json(profile))
+}
+
+// ============ Device Pairing ============
+
+/// Issue a pairing code for the walker to display; any earlier unused code stops working
+pub async fn issue_pairing_code(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let device_id = path.into_inner();
+    let device: Option<Device> = match sqlx::query_as(
+        "SELECT * FROM devices
+         WHERE device_id = $1 AND is_active = true AND ($2::uuid IS NULL OR organization_id = $2)"
+    )
+    .bind(&device_id)
+    .bind(claims.org)
+    .fetch_optional(&state.pool)
+    .await
+    {
+        Ok(d) => d,
+        Err(e) => return HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Database error: {}", e)})),
+    };
+
+    let Some(device) = device else {
+        return HttpResponse::NotFound().json(serde_json::json!({"error": "Device not found"}));
+    };
+
+    match create_pairing_code(&state.pool, device.id, state.pairing_code_ttl_minutes).await {
+        Ok((pairing_code, expires_at)) => {
+            crate::audit_log!("admin", "issue_pairing_code", Some(claims.user_id), true, device_id);
+            HttpResponse::Created().json(PairingCodeResponse {
+                device_id,
+                pairing_code,
+                expires_at,
+            })
+        }
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Failed to issue pairing code: {}", e)})),
+    }
+}
+
+/// Issue the walker its own HMAC secret, replacing any earlier one. The secret is only
+/// returned here; the walker signs with it from then on.
+pub async fn issue_device_secret(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let device_id = path.into_inner();
+    let device: Option<Device> = match sqlx::query_as(
+        "SELECT * FROM devices
+         WHERE device_id = $1 AND is_active = true AND ($2::uuid IS NULL OR organization_id = $2)"
+    )
+    .bind(&device_id)
+    .bind(claims.org)
+    .fetch_optional(&state.pool)
+    .await
+    {
+        Ok(d) => d,
+        Err(e) => return HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Database error: {}", e)})),
+    };
+
+    let Some(device) = device else {
+        return HttpResponse::NotFound().json(serde_json::json!({"error": "Device not found"}));
+    };
+
+    let secret = generate_secret();
+    let sealed = match state.device_secrets.seal(device.id, &secret) {
+        Ok(sealed) => sealed,
+        Err(e) => return HttpResponse::ServiceUnavailable().json(serde_json::json!({"error": format!("Cannot issue device secrets: {}", e)})),
+    };
+    let issued = sqlx::query_scalar(
+        "UPDATE devices SET secret_hash = $2, secret_ciphertext = $3, secret_issued_at = now() WHERE id = $1 RETURNING secret_issued_at"
+    )
+    .bind(device.id)
+    .bind(hash_secret(&secret))
+    .bind(sealed)
+    .fetch_one(&state.pool)
+    .await;
+
+    match issued {
+        Ok(issued_at) => {
+            crate::audit_log!("admin", "issue_device_secret", Some(claims.user_id), true, device_id);
+            HttpResponse::Created().json(DeviceSecretResponse { device_id, secret, issued_at })
+        }
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Failed to issue device secret: {}", e)})),
+    }
+}
+
+// ============ Test Alerts ============
+
+/// Push a flagged synthetic alert through SSE and notifications so a site can check its
+/// alerting chain during commissioning; returns the alert with the steps taken
+pub async fn inject_test_alert(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    body: web::Json<TestAlertRequest>,
+) -> impl Responder {
+    if let Err(e) = body.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": e.to_string()}));
+    }
+
+    if let Some(patient_id) = body.patient_id {
+        let exists: Result<bool, _> = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM patients
+                            WHERE id = $1 AND ($2::uuid IS NULL OR organization_id = $2))"
+        )
+        .bind(patient_id)
+        .bind(claims.org)
+        .fetch_one(&state.pool)
+        .await;
+        match exists {
+            Ok(true) => {}
+            Ok(false) => return HttpResponse::NotFound().json(serde_json::json!({"error": "Patient not found"})),
+            Err(e) => return HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Database error: {}", e)})),
+        }
+    }
+
+    let raised = raise_test_alert(&state.pool, &state.notifier, &state.sse_broadcaster, &body, claims.user_id).await;
+    let alert = match raised {
+        Ok(alert) => alert,
+        Err(e) => {
+            crate::audit_log!("admin", "test_alert", Some(claims.user_id), false);
+            return HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Failed to raise test alert: {}", e)}));
+        }
+    };
+    crate::audit_log!("admin", "test_alert", Some(claims.user_id), true, alert.id);
+
+    match load_responses(&state.pool, alert.id).await {
+        Ok(responses) => HttpResponse::Created().json(AlertWithResponses { alert, responses }),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Database error: {}", e)})),
+    }
+}
+
+// ============ Service Levels ============
+
+/// Rolling 7- and 30-day compliance and error budget for each configured objective
+pub async fn slo_report(
+    _admin: AuthenticatedUser,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    match slo_service::report(&state.pool, &state.slo).await {
+        Ok(objectives) => HttpResponse::Ok().json(serde_json::json!({"objectives": objectives})),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Database error: {}", e)})),
+    }
+}
+
+// ============ Logging ============
+
+pub async fn get_log_level(_admin: AuthenticatedUser) -> impl Responder {
+    match logging::current_filter() {
+        Some(filter) => HttpResponse::Ok().json(LogLevel { filter, previous: None }),
+        None => HttpResponse::ServiceUnavailable().json(serde_json::json!({"error": "Logging is not initialized"})),
+    }
+}
+
+/// Swap the log filter without a restart, e.g. `info,medhealth_backend::sse=debug` while
+/// chasing an incident. The change lasts until the next restart or PUT.
+pub async fn set_log_level(claims: AuthenticatedUser, body: web::Json<LogLevelRequest>) -> impl Responder {
+    if let Err(e) = require_unscoped(&claims) {
+        return e.error_response();
+    }
+    if let Err(e) = body.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": e.to_string()}));
+    }
+    if logging::current_filter().is_none() {
+        return HttpResponse::ServiceUnavailable().json(serde_json::json!({"error": "Logging is not initialized"}));
+    }
+
+    match logging::set_filter(body.filter.trim()) {
+        Ok(previous) => {
+            crate::audit_log!("admin", "set_log_level", Some(claims.user_id), true, &body.filter);
+            HttpResponse::Ok().json(LogLevel {
+                filter: logging::current_filter().unwrap_or_default(),
+                previous: Some(previous),
+            })
+        }
+        Err(e) => HttpResponse::BadRequest().json(serde_json::json!({"error": format!("Invalid filter: {}", e)})),
+    }
+}
+
+// ============ Self-Test ============
+
+/// Run the ingestion pipeline end-to-end on synthetic data inside a rolled-back transaction
+pub async fn run_selftest(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let mut stages = Vec::new();
+    selftest_pipeline(&state, &mut stages).await;
+
+    let passed = stages.iter().all(|s| s.passed);
+    crate::audit_log!("admin", "selftest", Some(claims.user_id), passed);
+
+    let report = SelftestReport { passed, stages };
+    if passed {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
+
+/// Record a stage outcome, returning the value when the stage passed
+fn record<T, E: std::fmt::Display>(
+    stages: &mut Vec<SelftestStage>,
+    stage: &'static str,
+    started: Instant,
+    result: Result<T, E>,
+) -> Option<T> {
+    let (value, error) = match result {
+        Ok(v) => (Some(v), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+
+    stages.push(SelftestStage {
+        stage,
+        passed: error.is_none(),
+        duration_ms: started.elapsed().as_millis(),
+        error,
+    });
+
+    value
+}
+
+/// Each stage depends on the previous one, so the first failure ends the run.
+/// Nothing is committed: the transaction is rolled back (or dropped on failure).
+async fn selftest_pipeline(state: &AppState, stages: &mut Vec<SelftestStage>) -> Option<()> {
+    let started = Instant::now();
+    let mut tx = record(stages, "database", started, state.pool.begin().await)?;
+
+    // Synthetic device and reading
+    let started = Instant::now();
+    let device: Result<Device, _> = sqlx::query_as(
+        "INSERT INTO devices (device_id, device_name, secret_hash, is_active) VALUES ($1, 'Self-test device', '', false) RETURNING *"
+    )
+    .bind(format!("SELFTEST-{}", uuid::Uuid::new_v4()))
+    .fetch_one(&mut *tx)
+    .await;
+    let device = record(stages, "device", started, device)?;
+
+    let started = Instant::now();
+    let reading: Result<SensorReading, _> = sqlx::query_as(
+        "INSERT INTO sensor_readings (device_id, heart_rate, spo2, temperature, reading_timestamp)
+         VALUES ($1, 72, 98, 36.8, now()) RETURNING *"
+    )
+    .bind(device.id)
+    .fetch_one(&mut *tx)
+    .await;
+    let reading = record(stages, "ingest", started, reading)?;
+
+    // ML analysis
+    let started = Instant::now();
+    let ml_result = state.ml_service.analyze_reading(&reading);
+    let stored = sqlx::query(
+        "INSERT INTO ml_analysis (sensor_reading_id, anomaly_detected, anomaly_score, classification, alert_level, analysis_details)
+         VALUES ($1, $2, $3, $4, $5, $6)"
+    )
+    .bind(reading.id)
+    .bind(ml_result.anomaly_detected)
+    .bind(ml_result.anomaly_score)
+    .bind(ml_result.classification)
+    .bind(ml_result.alert_level)
+    .bind(&ml_result.details)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|_| match ml_result.classification {
+        Classification::Normal => Ok(()),
+        other => Err(format!("synthetic normal reading classified as '{}'", other)),
+    });
+    record(stages, "ml", started, stored)?;
+
+    // FHIR generation
+    let started = Instant::now();
+    let bundle = state.fhir_service.create_observation_bundle(&reading, Some(&device), None);
+    let entries: Vec<_> = bundle["entry"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|e| e["resource"]["resourceType"] == "Observation")
+        .cloned()
+        .collect();
+    let fhir = if entries.len() != 3 {
+        Err(format!("expected 3 observations, got {}", entries.len()))
+    } else if !entries.iter().all(|e| state.fhir_service.validate_observation(&e["resource"])) {
+        Err("generated observation failed validation".to_string())
+    } else {
+        sqlx::query("INSERT INTO fhir_observations (sensor_reading_id, resource) VALUES ($1, $2)")
+            .bind(reading.id)
+            .bind(&bundle)
+            .execute(&mut *tx)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    };
+    record(stages, "fhir", started, fhir)?;
+
+    // Redis reachability (the real latest-vitals key is left untouched)
+    let started = Instant::now();
+    let redis = state.redis.write().await.health_check().await;
+    record(stages, "redis", started, redis)?;
+
+    // Broadcast on a private channel so connected dashboards never see synthetic data
+    let started = Instant::now();
+    let broadcaster = create_broadcaster();
+    let mut rx = broadcaster.subscribe();
+    broadcast_vitals(&broadcaster, None, LatestVitals {
+        heart_rate: reading.heart_rate.unwrap_or(0),
+        spo2: reading.spo2.unwrap_or(0),
+        temperature: reading.temperature.unwrap_or(0.0),
+        timestamp: Utc::now().timestamp(),
+        quality_score: Some(ml_result.quality_score),
+        ml_alert: None,
+    });
+    let sse = match rx.try_recv() {
+        Ok(event) if matches!(event.event, SseEvent::Vitals(_)) => Ok(()),
+        Ok(_) => Err("unexpected event type".to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    record(stages, "sse", started, sse)?;
+
+    let started = Instant::now();
+    record(stages, "rollback", started, tx.rollback().await)
+}
@@ -0,0 +1,286 @@
+use crate::emergency_service::{load_responses, record_response, ResponseStep};
+use crate::errors::ApiError;
+use crate::handlers::patients::require_patient_access;
+use crate::handlers::{accessible_patients, AppState};
+use crate::middleware::AuthenticatedUser;
+use crate::rbac::Role;
+use crate::models::*;
+use crate::sse::{broadcast_alert_message, broadcast_alert_status};
+use actix_web::{web, HttpResponse};
+use uuid::Uuid;
+use validator::Validate;
+
+const MESSAGE_COLUMNS: &str = "m.id, m.alert_id, m.parent_id, m.author_id, u.email AS author_email,
+    u.role AS author_role, m.body, m.created_at";
+
+const DEFAULT_ALERT_LIMIT: i64 = 100;
+const MAX_ALERT_LIMIT: i64 = 500;
+
+crate::routes::route_registry! {
+    "/alerts" {
+        GET => search_alerts, Jwt, [];
+    }
+    "/patients/{patient_id}/alerts" {
+        GET => list_alerts, Jwt, [];
+    }
+    "/alerts/{id}" {
+        GET => get_alert, Jwt, [];
+    }
+    "/alerts/{id}/acknowledge" {
+        POST => acknowledge_alert, Jwt, [];
+    }
+    "/alerts/{id}/ack" {
+        POST => acknowledge_alert, Jwt, [];
+    }
+    "/alerts/{id}/resolve" {
+        POST => resolve_alert, Jwt, [];
+    }
+    "/alerts/{id}/messages" {
+        GET => list_messages, Jwt, [];
+        POST => post_message, Jwt, [];
+    }
+}
+
+/// Load an alert the caller may see; alerts from unclaimed devices are admin-only
+pub(crate) async fn load_alert(state: &AppState, claims: &Claims, id: Uuid) -> Result<Alert, ApiError> {
+    let alert: Alert = sqlx::query_as("SELECT * FROM alerts WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Alert not found".into()))?;
+
+    match alert.patient_id {
+        Some(patient_id) => require_patient_access(state, claims, patient_id).await?,
+        None if claims.role == Role::Admin => {}
+        None => return Err(ApiError::Forbidden("Admin role required".into())),
+    }
+    Ok(alert)
+}
+
+/// Alerts across every patient the caller may see, newest first, for triage. Archived
+/// patients are left out unless asked for by `patient_id`.
+pub async fn search_alerts(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    query: web::Query<AlertListQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let limit = query.limit.unwrap_or(DEFAULT_ALERT_LIMIT);
+    if !(1..=MAX_ALERT_LIMIT).contains(&limit) {
+        return Err(ApiError::BadRequest(format!("limit must be between 1 and {}", MAX_ALERT_LIMIT)));
+    }
+    if let Some(patient_id) = query.patient_id {
+        require_patient_access(&state, &claims, patient_id).await?;
+    }
+    let patients: Option<Vec<Uuid>> = accessible_patients(&state, &claims).await?.map(|ids| ids.into_iter().collect());
+
+    let alerts: Vec<Alert> = sqlx::query_as(
+        "SELECT * FROM alerts
+         WHERE ($1::alert_status IS NULL OR status = $1)
+           AND ($2::alert_level IS NULL OR level >= $2)
+           AND ($3::text IS NULL OR kind = $3)
+           AND ($4::uuid IS NULL OR patient_id = $4)
+           AND ($5::uuid[] IS NULL OR patient_id = ANY($5))
+           AND (patient_id IS NOT NULL OR $6)
+           AND ($4::uuid IS NOT NULL OR patient_id IS NULL
+                OR patient_id NOT IN (SELECT id FROM patients WHERE archived_at IS NOT NULL))
+         ORDER BY raised_at DESC
+         LIMIT $7"
+    )
+    .bind(query.status)
+    .bind(query.min_level)
+    .bind(query.kind.as_deref())
+    .bind(query.patient_id)
+    .bind(patients)
+    // Alerts from unclaimed devices are admin-only, as in `load_alert`
+    .bind(claims.role == Role::Admin)
+    .bind(limit)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(alerts))
+}
+
+pub async fn list_alerts(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    require_patient_access(&state, &claims, patient_id).await?;
+
+    let alerts: Vec<Alert> = sqlx::query_as(
+        "SELECT * FROM alerts WHERE patient_id = $1 ORDER BY raised_at DESC LIMIT 100"
+    )
+    .bind(patient_id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(alerts))
+}
+
+/// An alert with its full response chain
+pub async fn get_alert(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let alert = load_alert(&state, &claims, path.into_inner()).await?;
+    let responses = load_responses(&state.pool, alert.id).await?;
+
+    Ok(HttpResponse::Ok().json(AlertWithResponses { alert, responses }))
+}
+
+/// Mark an alert as handled; the acknowledgement closes its response chain
+pub async fn acknowledge_alert(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let alert = load_alert(&state, &claims, path.into_inner()).await?;
+
+    let alert: Alert = sqlx::query_as(
+        "UPDATE alerts SET status = 'acknowledged', acknowledged_at = now(), acknowledged_by = $2
+         WHERE id = $1 AND acknowledged_at IS NULL
+         RETURNING *"
+    )
+    .bind(alert.id)
+    .bind(claims.user_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| ApiError::Conflict("Alert already acknowledged".into()))?;
+
+    let step = ResponseStep {
+        channel: "acknowledgement",
+        user_id: Some(claims.user_id),
+        target: Some(claims.sub.clone()),
+        status: "acknowledged",
+        ..Default::default()
+    };
+    record_response(&state.pool, alert.id, &step).await?;
+
+    crate::audit_log!("alert", "acknowledge", Some(claims.user_id), true, alert.id);
+    broadcast_alert_status(&state.sse_broadcaster, AlertStatusChange::of(&alert, claims.user_id));
+
+    let responses = load_responses(&state.pool, alert.id).await?;
+    Ok(HttpResponse::Ok().json(AlertWithResponses { alert, responses }))
+}
+
+/// Close an alert, with an optional note on what was done; an alert nobody acknowledged is
+/// acknowledged by whoever resolves it
+pub async fn resolve_alert(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: Option<web::Json<ResolveAlertRequest>>,
+) -> Result<HttpResponse, ApiError> {
+    let body = body.map(|b| b.into_inner()).unwrap_or_default();
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let resolution = body.resolution.as_deref().map(str::trim).filter(|r| !r.is_empty());
+    let alert = load_alert(&state, &claims, path.into_inner()).await?;
+
+    let alert: Alert = sqlx::query_as(
+        "UPDATE alerts SET status = 'resolved', resolved_at = now(), resolved_by = $2, resolution = $3,
+             acknowledged_by = CASE WHEN acknowledged_at IS NULL THEN $2 ELSE acknowledged_by END,
+             acknowledged_at = COALESCE(acknowledged_at, now())
+         WHERE id = $1 AND status <> 'resolved'
+         RETURNING *"
+    )
+    .bind(alert.id)
+    .bind(claims.user_id)
+    .bind(resolution)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| ApiError::Conflict("Alert already resolved".into()))?;
+
+    let step = ResponseStep {
+        channel: "resolution",
+        user_id: Some(claims.user_id),
+        target: Some(claims.sub.clone()),
+        status: "resolved",
+        detail: alert.resolution.clone(),
+        ..Default::default()
+    };
+    record_response(&state.pool, alert.id, &step).await?;
+
+    crate::audit_log!("alert", "resolve", Some(claims.user_id), true, alert.id);
+    // Once resolved, the walker's next excursion alerts at once rather than after the cooldown
+    if let (Some(device_id), "vitals") = (alert.device_id, alert.kind.as_str()) {
+        if let Err(e) = state.redis.write().await.clear_alert_cooldowns(device_id).await {
+            tracing::warn!(alert_id = %alert.id, "Failed to clear alert cooldowns: {}", e);
+        }
+    }
+    broadcast_alert_status(&state.sse_broadcaster, AlertStatusChange::of(&alert, claims.user_id));
+
+    let responses = load_responses(&state.pool, alert.id).await?;
+    Ok(HttpResponse::Ok().json(AlertWithResponses { alert, responses }))
+}
+
+// ============ Alert Chat ============
+
+/// The alert's discussion in posting order; clients thread replies by `parent_id`
+pub async fn list_messages(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let alert = load_alert(&state, &claims, path.into_inner()).await?;
+
+    let messages: Vec<AlertMessage> = sqlx::query_as(&format!(
+        "SELECT {} FROM alert_messages m LEFT JOIN users u ON u.id = m.author_id
+         WHERE m.alert_id = $1 ORDER BY m.created_at, m.id",
+        MESSAGE_COLUMNS
+    ))
+    .bind(alert.id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(messages))
+}
+
+/// Post a message (or a reply to `parent_id`) and push it to SSE subscribers
+pub async fn post_message(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Json<AlertMessageRequest>,
+) -> Result<HttpResponse, ApiError> {
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    if body.body.trim().is_empty() {
+        return Err(ApiError::BadRequest("Message must not be blank".into()));
+    }
+    let alert = load_alert(&state, &claims, path.into_inner()).await?;
+
+    if let Some(parent_id) = body.parent_id {
+        let same_alert: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM alert_messages WHERE id = $1 AND alert_id = $2)"
+        )
+        .bind(parent_id)
+        .bind(alert.id)
+        .fetch_one(&state.pool)
+        .await?;
+        if !same_alert {
+            return Err(ApiError::BadRequest("parent_id is not a message on this alert".into()));
+        }
+    }
+
+    let message: AlertMessage = sqlx::query_as(&format!(
+        "WITH m AS (
+            INSERT INTO alert_messages (alert_id, parent_id, author_id, body)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *
+         )
+         SELECT {} FROM m LEFT JOIN users u ON u.id = m.author_id",
+        MESSAGE_COLUMNS
+    ))
+    .bind(alert.id)
+    .bind(body.parent_id)
+    .bind(claims.user_id)
+    .bind(body.body.trim())
+    .fetch_one(&state.pool)
+    .await?;
+
+    crate::audit_log!("alert", "message", Some(claims.user_id), true, alert.id);
+    broadcast_alert_message(&state.sse_broadcaster, alert.patient_id, message.clone());
+
+    Ok(HttpResponse::Created().json(message))
+}
@@ -0,0 +1,51 @@
+use crate::analytics::{compile, Scope};
+use crate::errors::ApiError;
+use crate::handlers::{accessible_patients, AppState};
+use crate::models::*;
+use crate::query_debug;
+use crate::request_context::RequestContext;
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use validator::Validate;
+
+// Ad hoc chart queries; see `crate::analytics` for the fields each source exposes
+crate::routes::route_registry! {
+    "/analytics/query" {
+        POST => run_query, Jwt, ["admin", "clinician"];
+    }
+}
+
+/// Aggregate readings or alerts for a dashboard chart. Rows are limited to the patients the
+/// caller may see, in their organization, and bucketed in their time zone.
+pub async fn run_query(
+    ctx: RequestContext,
+    state: web::Data<AppState>,
+    body: web::Json<AnalyticsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let scope = Scope {
+        patients: accessible_patients(&state, &ctx).await?.map(|ids| ids.into_iter().collect()),
+        organization_id: ctx.organization_id,
+        timezone: ctx.timezone.name().to_string(),
+    };
+    let compiled = compile(&body, &scope, Utc::now()).map_err(ApiError::BadRequest)?;
+
+    let mut rows: Vec<(serde_json::Value,)> = query_debug::fetch_all(
+        &state.reporting_pool,
+        &state.query_debug,
+        "analytics_query",
+        &compiled.sql,
+        || compiled.arguments(),
+    )
+    .await?;
+    let truncated = rows.len() as i64 > compiled.limit;
+    rows.truncate(compiled.limit as usize);
+
+    crate::audit_log!("data_access", "analytics_query", Some(ctx.user_id), true, compiled.columns.join(","));
+
+    Ok(HttpResponse::Ok().json(AnalyticsResult {
+        columns: compiled.columns,
+        rows: rows.into_iter().map(|(row,)| row).collect(),
+        truncated,
+    }))
+}
@@ -0,0 +1,236 @@
+use crate::auth::{extract_bearer_token, lockout_duration};
+use crate::errors::ApiError;
+use crate::handlers::AppState;
+use crate::metrics::AUTH_ATTEMPTS_TOTAL;
+use crate::models::*;
+use crate::request_context::{parse_locale, parse_timezone, RequestContext, Units};
+use actix_web::{web, HttpRequest, HttpResponse};
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use argon2::password_hash::SaltString;
+use chrono::Utc;
+use rand::rngs::OsRng;
+use validator::Validate;
+
+crate::routes::route_registry! {
+    "/signup" {
+        POST => signup, Public, [];
+    }
+    "/login" {
+        POST => login, Public, [];
+    }
+    "/logout" {
+        POST => logout, Jwt, [];
+    }
+    "/preferences" {
+        GET => get_preferences, Jwt, [];
+        PUT => update_preferences, Jwt, [];
+    }
+}
+
+pub async fn signup(
+    state: web::Data<AppState>,
+    body: web::Json<SignupRequest>,
+) -> Result<HttpResponse, ApiError> {
+    // Validate input
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    let email = body.email.trim().to_lowercase();
+
+    // Check if user already exists
+    let existing: Option<User> = sqlx::query_as("SELECT * FROM users WHERE email = $1")
+        .bind(&email)
+        .fetch_optional(&state.pool)
+        .await?;
+
+    if existing.is_some() {
+        return Err(ApiError::Conflict("User already exists".into()));
+    }
+
+    // Hash password
+    let salt = SaltString::generate(&mut OsRng);
+    let argon2 = Argon2::default();
+    let password_hash = argon2
+        .hash_password(body.password.as_bytes(), &salt)
+        .map_err(|_| ApiError::Internal("Password hashing failed".into()))?
+        .to_string();
+
+    // Insert user
+    let user: User = sqlx::query_as(
+        "INSERT INTO users (email, password_hash, role) VALUES ($1, $2, 'viewer') RETURNING *"
+    )
+    .bind(&email)
+    .bind(&password_hash)
+    .fetch_one(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(issue_tokens(&state, user)?))
+}
+
+pub async fn login(
+    state: web::Data<AppState>,
+    body: web::Json<LoginRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user = check_credentials(&state, &body).await;
+    let result = match &user {
+        Ok(_) => "success",
+        Err(ApiError::Forbidden(_)) => "locked",
+        Err(_) => "failure",
+    };
+    AUTH_ATTEMPTS_TOTAL.with_label_values(&[result]).inc();
+
+    Ok(HttpResponse::Ok().json(issue_tokens(&state, user?)?))
+}
+
+/// The active user with these credentials, counting a wrong password against the account
+async fn check_credentials(state: &AppState, body: &LoginRequest) -> Result<User, ApiError> {
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    let email = body.email.trim().to_lowercase();
+
+    // Find user
+    let user: User = sqlx::query_as("SELECT * FROM users WHERE email = $1 AND is_active = true")
+        .bind(&email)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|_| ApiError::Unauthorized("Invalid credentials".into()))?;
+
+    // Check if account is locked
+    if let Some(locked_until) = user.locked_until {
+        if locked_until > Utc::now() {
+            return Err(ApiError::Forbidden("Account temporarily locked".into()));
+        }
+    }
+
+    // Verify password
+    let parsed_hash = PasswordHash::new(&user.password_hash)
+        .map_err(|_| ApiError::Internal("Invalid password hash".into()))?;
+
+    let argon2 = Argon2::default();
+    if argon2.verify_password(body.password.as_bytes(), &parsed_hash).is_err() {
+        record_failed_login(state, &user).await;
+        return Err(ApiError::Unauthorized("Invalid credentials".into()));
+    }
+
+    // Reset failed attempts and update last login
+    let _ = sqlx::query(
+        "UPDATE users SET failed_login_attempts = 0, locked_until = NULL, last_login_at = now() WHERE id = $1"
+    )
+    .bind(user.id)
+    .execute(&state.pool)
+    .await;
+
+    Ok(user)
+}
+
+/// Count a wrong password against `user`, locking the account once there have been too many
+/// in a row
+async fn record_failed_login(state: &AppState, user: &User) {
+    let failed: Result<i32, sqlx::Error> = sqlx::query_scalar(
+        "UPDATE users SET failed_login_attempts = failed_login_attempts + 1 WHERE id = $1 RETURNING failed_login_attempts"
+    )
+    .bind(user.id)
+    .fetch_one(&state.pool)
+    .await;
+    let failed = match failed {
+        Ok(failed) => failed,
+        Err(e) => {
+            tracing::error!(user_id = %user.id, "Failed to count a failed login: {}", e);
+            return;
+        }
+    };
+
+    let Some(duration) = lockout_duration(&state.lockout, failed) else { return };
+    let locked_until = Utc::now() + duration;
+    let locked = sqlx::query("UPDATE users SET locked_until = $2 WHERE id = $1")
+        .bind(user.id)
+        .bind(locked_until)
+        .execute(&state.pool)
+        .await;
+    if let Err(e) = &locked {
+        tracing::error!(user_id = %user.id, "Failed to lock account: {}", e);
+    }
+
+    tracing::warn!(user_id = %user.id, failed, %locked_until, "Account locked after failed logins");
+    crate::audit_log!("auth", "account_locked", Some(user.id), locked.is_ok(), (failed, locked_until.to_rfc3339()));
+}
+
+/// Sign the access and refresh tokens for a user; signing failures alert and map to 503
+fn issue_tokens(state: &AppState, user: User) -> Result<AuthResponse, ApiError> {
+    let token = state
+        .jwt_auth
+        .generate_token(user.id, &user.email, user.role, user.organization_id)
+        .map_err(|e| ApiError::token_signing(e, user.id))?;
+    let refresh_token = state
+        .jwt_auth
+        .generate_token(user.id, &user.email, user.role, user.organization_id)
+        .map_err(|e| ApiError::token_signing(e, user.id))?;
+
+    Ok(AuthResponse {
+        token,
+        refresh_token,
+        user: UserResponse {
+            id: user.id,
+            email: user.email,
+            role: user.role,
+        },
+    })
+}
+
+pub async fn logout(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let auth_header = req.headers().get("Authorization").and_then(|h| h.to_str().ok());
+
+    let token = extract_bearer_token(auth_header)
+        .map_err(|_| ApiError::Unauthorized("Missing token".into()))?;
+
+    let claims = state
+        .jwt_auth
+        .validate_token(&token)
+        .map_err(|_| ApiError::Unauthorized("Invalid token".into()))?;
+
+    // Revoke token
+    let _ = state.jwt_auth.revoke_token(&claims, &state.pool).await;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({"status": "logged_out"})))
+}
+
+/// The caller's settings as this request resolved them, override headers included
+pub async fn get_preferences(ctx: RequestContext) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(UserPreferences {
+        organization_id: ctx.organization_id,
+        locale: ctx.locale.clone(),
+        timezone: ctx.timezone.name().to_string(),
+        units: ctx.units.as_str().to_string(),
+    }))
+}
+
+/// Store the caller's locale, time zone and units; fields left out are kept
+pub async fn update_preferences(
+    ctx: RequestContext,
+    state: web::Data<AppState>,
+    body: web::Json<UpdatePreferencesRequest>,
+) -> Result<HttpResponse, ApiError> {
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let locale = body.locale.as_deref().map(parse_locale).transpose().map_err(ApiError::BadRequest)?;
+    let timezone = body.timezone.as_deref().map(parse_timezone).transpose().map_err(ApiError::BadRequest)?;
+    let units = body.units.as_deref().map(Units::parse).transpose().map_err(ApiError::BadRequest)?;
+
+    let preferences: UserPreferences = sqlx::query_as(
+        "UPDATE users
+         SET locale = COALESCE($2, locale), timezone = COALESCE($3, timezone), units = COALESCE($4, units),
+             updated_at = now()
+         WHERE id = $1
+         RETURNING organization_id, locale, timezone, units"
+    )
+    .bind(ctx.user_id)
+    .bind(locale)
+    .bind(timezone.map(|tz| tz.name()))
+    .bind(units.map(Units::as_str))
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("User not found".into()))?;
+
+    Ok(HttpResponse::Ok().json(preferences))
+}
@@ -0,0 +1,294 @@
+use crate::care_plan_service::non_use_streak;
+use crate::errors::ApiError;
+use crate::handlers::patients::require_active_patient;
+use crate::handlers::{can_access_patient, can_manage_care, AppState};
+use crate::middleware::AuthenticatedUser;
+use crate::models::*;
+use actix_web::{web, HttpResponse};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use validator::Validate;
+
+crate::routes::route_registry! {
+    "/patients/{patient_id}/care-plans" {
+        GET => list_care_plans, Jwt, [];
+        POST => create_care_plan, Jwt, [];
+    }
+    "/care-plans/{id}" {
+        GET => get_care_plan, Jwt, [];
+        PUT => update_care_plan, Jwt, [];
+        DELETE => delete_care_plan, Jwt, [];
+    }
+    "/care-plans/{id}/progress" {
+        GET => care_plan_progress, Jwt, [];
+    }
+    "/patients/{patient_id}/compliance" {
+        GET => walker_compliance, Jwt, [];
+    }
+}
+
+fn check_request(body: &CarePlanRequest) -> Result<(), ApiError> {
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    if let (Some(low), Some(high)) = (body.hr_min, body.hr_max) {
+        if low >= high {
+            return Err(ApiError::BadRequest("hr_min must be below hr_max".into()));
+        }
+    }
+    if let (Some(start), Some(end)) = (body.start_date, body.end_date) {
+        if end < start {
+            return Err(ApiError::BadRequest("end_date must not be before start_date".into()));
+        }
+    }
+    Ok(())
+}
+
+/// Load a care plan the caller is allowed to see
+pub(crate) async fn load_care_plan(state: &AppState, claims: &Claims, id: Uuid) -> Result<CarePlan, ApiError> {
+    let plan: CarePlan = sqlx::query_as("SELECT * FROM care_plans WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Care plan not found".into()))?;
+
+    if !can_access_patient(state, claims, plan.patient_id).await? {
+        return Err(ApiError::Forbidden("Not a caregiver for this patient".into()));
+    }
+    Ok(plan)
+}
+
+// ============ CRUD ============
+
+pub async fn list_care_plans(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+
+    if !can_access_patient(&state, &claims, patient_id).await? {
+        return Err(ApiError::Forbidden("Not a caregiver for this patient".into()));
+    }
+
+    let plans: Vec<CarePlan> = sqlx::query_as(
+        "SELECT * FROM care_plans WHERE patient_id = $1 ORDER BY start_date DESC, created_at DESC"
+    )
+    .bind(patient_id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(plans))
+}
+
+pub async fn create_care_plan(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Json<CarePlanRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+
+    if !can_manage_care(&state, &claims, Some(patient_id)).await? {
+        return Err(ApiError::Forbidden("Clinician role required".into()));
+    }
+    check_request(&body)?;
+
+    require_active_patient(&state.pool, patient_id).await?;
+    if !can_access_patient(&state, &claims, patient_id).await? {
+        return Err(ApiError::Forbidden("Not a caregiver for this patient".into()));
+    }
+
+    let plan: CarePlan = sqlx::query_as(
+        "INSERT INTO care_plans
+            (patient_id, title, status, target_steps, target_activity_minutes, hr_min, hr_max, spo2_min,
+             start_date, end_date, created_by, prescribed_usage_minutes)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, COALESCE($9, CURRENT_DATE), $10, $11, $12)
+         RETURNING *"
+    )
+    .bind(patient_id)
+    .bind(body.title.trim())
+    .bind(&body.status)
+    .bind(body.target_steps)
+    .bind(body.target_activity_minutes)
+    .bind(body.hr_min)
+    .bind(body.hr_max)
+    .bind(body.spo2_min)
+    .bind(body.start_date)
+    .bind(body.end_date)
+    .bind(claims.user_id)
+    .bind(body.prescribed_usage_minutes)
+    .fetch_one(&state.pool)
+    .await?;
+
+    crate::audit_log!("care_plan", "create", Some(claims.user_id), true, plan.id);
+
+    Ok(HttpResponse::Created().json(plan))
+}
+
+pub async fn get_care_plan(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let plan = load_care_plan(&state, &claims, path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(plan))
+}
+
+pub async fn update_care_plan(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Json<CarePlanRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let existing = load_care_plan(&state, &claims, path.into_inner()).await?;
+    if !can_manage_care(&state, &claims, Some(existing.patient_id)).await? {
+        return Err(ApiError::Forbidden("Clinician role required".into()));
+    }
+    check_request(&body)?;
+
+    let plan: CarePlan = sqlx::query_as(
+        "UPDATE care_plans SET
+            title = $2, status = $3, target_steps = $4, target_activity_minutes = $5,
+            hr_min = $6, hr_max = $7, spo2_min = $8, start_date = COALESCE($9, start_date),
+            end_date = $10, prescribed_usage_minutes = $11, updated_at = now()
+         WHERE id = $1
+         RETURNING *"
+    )
+    .bind(existing.id)
+    .bind(body.title.trim())
+    .bind(&body.status)
+    .bind(body.target_steps)
+    .bind(body.target_activity_minutes)
+    .bind(body.hr_min)
+    .bind(body.hr_max)
+    .bind(body.spo2_min)
+    .bind(body.start_date)
+    .bind(body.end_date)
+    .bind(body.prescribed_usage_minutes)
+    .fetch_one(&state.pool)
+    .await?;
+
+    crate::audit_log!("care_plan", "update", Some(claims.user_id), true, plan.id);
+
+    Ok(HttpResponse::Ok().json(plan))
+}
+
+pub async fn delete_care_plan(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let plan = load_care_plan(&state, &claims, path.into_inner()).await?;
+    if !can_manage_care(&state, &claims, Some(plan.patient_id)).await? {
+        return Err(ApiError::Forbidden("Clinician role required".into()));
+    }
+
+    sqlx::query("DELETE FROM care_plans WHERE id = $1")
+        .bind(plan.id)
+        .execute(&state.pool)
+        .await?;
+
+    crate::audit_log!("care_plan", "delete", Some(claims.user_id), true, plan.id);
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// ============ Progress ============
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ProgressQuery {
+    pub days: Option<i64>,
+}
+
+/// Daily goal attainment written by the care plan worker over the last `days` (default 7, max 90)
+pub async fn care_plan_progress(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<ProgressQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let plan = load_care_plan(&state, &claims, path.into_inner()).await?;
+
+    let days = query.days.unwrap_or(7).clamp(1, 90);
+    let since = Utc::now().date_naive() - Duration::days(days - 1);
+
+    let evaluations: Vec<CarePlanEvaluation> = sqlx::query_as(
+        "SELECT care_plan_id, eval_date, readings, steps, activity_minutes, avg_heart_rate, min_spo2,
+                hr_in_range_ratio, usage_minutes, steps_met, activity_met, vitals_met, usage_met
+         FROM care_plan_evaluations
+         WHERE care_plan_id = $1 AND eval_date >= $2
+         ORDER BY eval_date DESC"
+    )
+    .bind(plan.id)
+    .bind(since)
+    .fetch_all(&state.pool)
+    .await?;
+
+    let met = |f: fn(&CarePlanEvaluation) -> Option<bool>| evaluations.iter().filter(|e| f(e) == Some(true)).count();
+
+    Ok(HttpResponse::Ok().json(CarePlanProgress {
+        care_plan_id: plan.id,
+        days: evaluations.len(),
+        steps_met_days: met(|e| e.steps_met),
+        activity_met_days: met(|e| e.activity_met),
+        vitals_met_days: met(|e| e.vitals_met),
+        usage_met_days: met(|e| e.usage_met),
+        evaluations,
+    }))
+}
+
+// ============ Walker Compliance ============
+
+/// Daily walker use against the prescription of the patient's current care plan over the
+/// last `days` (default 14, max 90)
+pub async fn walker_compliance(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<ProgressQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    if !can_access_patient(&state, &claims, patient_id).await? {
+        return Err(ApiError::Forbidden("Not a caregiver for this patient".into()));
+    }
+
+    let plan: (Uuid, i32) = sqlx::query_as(
+        "SELECT id, prescribed_usage_minutes FROM care_plans
+         WHERE patient_id = $1 AND prescribed_usage_minutes IS NOT NULL
+         ORDER BY status = 'active' DESC, start_date DESC, created_at DESC
+         LIMIT 1"
+    )
+    .bind(patient_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("No care plan prescribes walker use".into()))?;
+    let (care_plan_id, prescribed_minutes) = plan;
+
+    let today = Utc::now().date_naive();
+    let since = today - Duration::days(query.days.unwrap_or(14).clamp(1, 90) - 1);
+
+    let days: Vec<ComplianceDay> = sqlx::query_as(
+        "SELECT eval_date AS date, usage_minutes, usage_met AS met
+         FROM care_plan_evaluations
+         WHERE care_plan_id = $1 AND eval_date >= $2
+         ORDER BY eval_date DESC"
+    )
+    .bind(care_plan_id)
+    .bind(since)
+    .fetch_all(&state.pool)
+    .await?;
+
+    let days_met = days.iter().filter(|d| d.met == Some(true)).count();
+    let usage: Vec<_> = days.iter().map(|d| (d.date, d.usage_minutes)).collect();
+
+    Ok(HttpResponse::Ok().json(WalkerCompliance {
+        patient_id,
+        care_plan_id,
+        prescribed_minutes,
+        days_evaluated: days.len(),
+        days_met,
+        compliance_rate: (!days.is_empty()).then(|| days_met as f32 / days.len() as f32),
+        non_use_streak: non_use_streak(&usage, today - Duration::days(1)).map_or(0, |(_, len)| len),
+        days,
+    }))
+}
@@ -0,0 +1,95 @@
+use crate::errors::ApiError;
+use crate::handlers::patients::{load_risk_inputs, require_active_patient, require_patient_access};
+use crate::handlers::AppState;
+use crate::middleware::AuthenticatedUser;
+use crate::models::*;
+use actix_web::{web, HttpResponse};
+use tracing::warn;
+use uuid::Uuid;
+use validator::Validate;
+
+crate::routes::route_registry! {
+    "/patients/{patient_id}/checkins" {
+        GET => list_checkins, Jwt, [];
+        POST => create_checkin, Jwt, [];
+    }
+}
+
+/// Record a self-report; caregivers are notified when it pushes the patient's risk to high
+pub async fn create_checkin(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Json<CheckinRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    if body.is_empty() {
+        return Err(ApiError::BadRequest("Check-in must answer at least one question".into()));
+    }
+
+    require_active_patient(&state.pool, patient_id).await?;
+    require_patient_access(&state, &claims, patient_id).await?;
+
+    let notes = match body.notes.as_deref() {
+        Some(notes) => Some(state.phi.seal_for_patient(patient_id, notes).await.map_err(|e| {
+            tracing::error!("Failed to encrypt check-in notes: {:#}", e);
+            ApiError::Internal("Failed to store check-in".into())
+        })?),
+        None => None,
+    };
+
+    let mut checkin: Checkin = sqlx::query_as(
+        "INSERT INTO checkins (patient_id, submitted_by, pain_score, dizziness, fatigue, shortness_of_breath, fell_since_last, notes)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+         RETURNING *"
+    )
+    .bind(patient_id)
+    .bind(claims.user_id)
+    .bind(body.pain_score)
+    .bind(body.dizziness)
+    .bind(body.fatigue)
+    .bind(body.shortness_of_breath)
+    .bind(body.fell_since_last)
+    .bind(&notes)
+    .fetch_one(&state.pool)
+    .await?;
+    checkin.notes = body.notes.clone();
+
+    crate::audit_log!("checkin", "create", Some(claims.user_id), true, checkin.id);
+
+    let risk = state.ml_service.assess_risk(&load_risk_inputs(&state.pool, patient_id).await?);
+    if risk.level == "high" {
+        let body = format!("Latest check-in raised the risk score to {:.2}: {}", risk.score, risk.factors.join(", "));
+        if let Err(e) = state.notifier.notify_caregivers(patient_id, "checkin_risk", "High risk check-in", &body).await {
+            warn!("Failed to notify caregivers about check-in {}: {}", checkin.id, e);
+        }
+    }
+
+    Ok(HttpResponse::Created().json(serde_json::json!({
+        "checkin": checkin,
+        "risk": risk,
+    })))
+}
+
+pub async fn list_checkins(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    require_patient_access(&state, &claims, patient_id).await?;
+
+    let mut checkins: Vec<Checkin> = sqlx::query_as(
+        "SELECT * FROM checkins WHERE patient_id = $1 ORDER BY submitted_at DESC LIMIT 100"
+    )
+    .bind(patient_id)
+    .fetch_all(&state.pool)
+    .await?;
+    for checkin in &mut checkins {
+        checkin.notes = state.phi.open_field(checkin.notes.take()).await;
+    }
+
+    Ok(HttpResponse::Ok().json(checkins))
+}
@@ -0,0 +1,18 @@
+use crate::errors::ApiError;
+use crate::handlers::AppState;
+use crate::middleware::AuthenticatedUser;
+use actix_web::{web, HttpResponse};
+
+crate::routes::route_registry! {
+    "/deployment" {
+        GET => get_deployment, Jwt, [];
+    }
+}
+
+/// Active deployment mode and feature switches, so clients can adapt their UI
+pub async fn get_deployment(
+    _user: AuthenticatedUser,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(&state.deployment))
+}
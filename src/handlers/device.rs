@@ -0,0 +1,701 @@
+use crate::aggregate_service;
+use crate::ambient_service::record_ambient;
+use crate::api_version::{legacy_fields, record_legacy_fields, ApiVersion};
+use crate::auth::{timestamp_within_window, verify_device_signature, DeviceAuthHeaders};
+use crate::baseline_service::load_baseline;
+use crate::dead_letter::{mark_reprocessed, record_failure, FailureCategory, IngestChannel, IngestSource};
+use crate::ehr_push;
+use crate::emergency_service::{raise_sos, record_vitals_alert};
+use crate::errors::ApiError;
+use crate::fhir_service::store_subjects;
+use crate::handlers::patients::patient_limit_reached;
+use crate::handlers::threshold_profiles::patient_profile;
+use crate::handlers::{can_access_patient, AppState};
+use crate::metrics::{record_device_error, DEVICE_READINGS_TOTAL, ML_ANALYSIS_DURATION, ML_ANALYSIS_REUSED, ML_ANOMALIES_DETECTED};
+use crate::middleware::AuthenticatedUser;
+use crate::ml_service::{AnalysisContext, MlAnalysisResult, MAX_HISTORY_READINGS};
+use crate::models::*;
+use crate::near_fall_service::record_near_fall;
+use crate::negotiation::{json_from_bytes, prefers_representation, PREFERENCE_APPLIED, RETURN_REPRESENTATION};
+use crate::normalization::{normalize_reading, NormalizedReading};
+use crate::pairing::hash_code;
+use crate::rate_limit::{self, Policy};
+use crate::quota_service::{quota_for_patient, QuotaState, QUOTA_WARNING_HEADER};
+use crate::redis_cache::RedisCache;
+use crate::sse::{broadcast_alert, broadcast_vitals};
+use crate::usage_service::{self, UsageMetric};
+use actix_web::{web, HttpRequest, HttpResponse, Responder, ResponseError};
+use chrono::Utc;
+use uuid::Uuid;
+use validator::Validate;
+
+crate::routes::route_registry! {
+    "/device/vitals" {
+        POST => device_ingest, Hmac, [];
+    }
+    "/device/events" {
+        POST => device_event, Hmac, [];
+    }
+    "/devices/claim" {
+        POST => claim_device, Jwt, [];
+    }
+}
+
+/// Check the HMAC headers against the signed payload and resolve the sending device
+async fn verify_device(req: &HttpRequest, state: &AppState, payload: &str) -> Result<Device, ApiError> {
+    let header = |name: &str| req.headers().get(name).and_then(|h| h.to_str().ok());
+
+    let DeviceAuthHeaders { device_id, timestamp, signature } =
+        DeviceAuthHeaders::parse(header("x-device-id"), header("x-timestamp"), header("x-signature"))
+            .map_err(|e| ApiError::Unauthorized(e.into()))?;
+
+    authenticate_device(state, device_id, timestamp, signature, payload).await
+}
+
+/// Resolve an active device and check its signature over `payload`, however it arrived.
+///
+/// Devices sign `"{timestamp}.{body}"` with their own secret, or the shared device
+/// secret until they are issued one.
+pub(crate) async fn authenticate_device(
+    state: &AppState,
+    device_id: &str,
+    timestamp: i64,
+    signature: &str,
+    payload: &str,
+) -> Result<Device, ApiError> {
+    // Verify timestamp (replay protection using configured window)
+    if !timestamp_within_window(Utc::now().timestamp(), timestamp, state.replay_window_seconds) {
+        record_device_error(None, "stale_timestamp");
+        return Err(ApiError::Unauthorized("Timestamp out of range".into()));
+    }
+
+    let device: Device = sqlx::query_as("SELECT * FROM devices WHERE device_id = $1 AND is_active = true")
+        .bind(device_id)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| {
+            record_device_error(None, "unknown_device");
+            ApiError::Unauthorized("Unknown device".into())
+        })?;
+
+    let secret = state
+        .device_secrets
+        .secret_for(device.id, &device.secret_hash, device.secret_ciphertext.as_deref())
+        .map_err(|e| {
+            tracing::error!(device_id, "Failed to open device secret: {}", e);
+            ApiError::Internal("Device secret unavailable".into())
+        })?
+        .ok_or_else(|| ApiError::Unauthorized("Device has no secret issued".into()))?;
+
+    // Verify HMAC signature
+    if !verify_device_signature(&secret, timestamp, payload, signature) {
+        record_device_error(Some(&device.device_id), "signature");
+        return Err(ApiError::Unauthorized("Invalid signature".into()));
+    }
+
+    if let Err(e) = rate_limit::check(state, Policy::Device, &device.device_id).await {
+        record_device_error(Some(&device.device_id), "rate_limited");
+        return Err(e);
+    }
+
+    Ok(device)
+}
+
+/// The signature covers the body as sent, so it may use either API version's field names
+///
+/// With `?dry_run=true` the reading is authenticated, validated and analysed as usual, and the
+/// outcome returned, but nothing is stored, cached or broadcast: for firmware developers
+/// checking their signing and payloads against production rules.
+pub async fn device_ingest(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<DeviceIngestQuery>,
+    body: web::Bytes,
+) -> impl Responder {
+    // The walker signed the bytes it sent, so they are checked before anything is parsed
+    let payload = String::from_utf8_lossy(&body);
+    let device = match verify_device(&req, &state, &payload).await {
+        Ok(d) => d,
+        Err(e) => return e.error_response(),
+    };
+    let version = match ApiVersion::from_request(&req) {
+        Ok(version) => version,
+        Err(e) => return e.error_response(),
+    };
+    let parsed: RawVitals = match json_from_bytes(&req, &body) {
+        Ok(parsed) => parsed,
+        Err(e) => return e.error_response(),
+    };
+    // Units are the device's, so the reading is only range-checked once it is known
+    let source = IngestSource::new(IngestChannel::Http);
+    let parsed = match query.dry_run {
+        true => normalize_reading(parsed, &device.units),
+        false => normalize_for(&state, &device, parsed, &source).await,
+    };
+    let parsed = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => return e.error_response(),
+    };
+
+    let mut response = if query.dry_run {
+        dry_run_vitals(&state, &device, &parsed, version).await
+    } else {
+        let return_vitals = prefers_representation(&req).then_some(version);
+        ingest_vitals(&state, &device, &parsed, &source, return_vitals).await
+    };
+    record_legacy_fields(&mut response, &legacy_fields(&payload));
+    response
+}
+
+/// Convert a reading from `device`'s units and range-check it, dead-lettering it if invalid
+pub(crate) async fn normalize_for(
+    state: &AppState,
+    device: &Device,
+    reading: RawVitals,
+    source: &IngestSource,
+) -> Result<NormalizedReading, ApiError> {
+    let sent = reading.0.clone();
+    let error = match normalize_reading(reading, &device.units) {
+        Ok(normalized) => return Ok(normalized),
+        Err(e) => e,
+    };
+    record_device_error(Some(&device.device_id), "invalid_reading");
+    record_failure(&state.pool, device, source, &sent, FailureCategory::InvalidReading, &error.to_string()).await;
+    Err(error)
+}
+
+/// Store and analyse one reading for `device`, whether the walker sent it itself or a paired
+/// phone relayed it (`source.relayed_by`, recorded as the reading's provenance). Readings
+/// the database fails to store are dead-lettered. With `return_vitals` the response also
+/// carries the vitals as now cached and broadcast, in that API version's casing.
+pub(crate) async fn ingest_vitals(
+    state: &AppState,
+    device: &Device,
+    normalized: &NormalizedReading,
+    source: &IngestSource,
+    return_vitals: Option<ApiVersion>,
+) -> HttpResponse {
+    let body = &normalized.vitals;
+    // Organization storage quota, as last measured by the quota worker
+    let quota = match device.patient_id {
+        Some(patient_id) => quota_for_patient(&state.pool, patient_id).await.unwrap_or_else(|e| {
+            tracing::warn!(device_id = %device.device_id, "Failed to load storage quota: {}", e);
+            None
+        }),
+        None => None,
+    };
+    let quota_state = quota.as_ref().map_or(QuotaState::Within, |q| q.state(&state.quota));
+    if quota_state == QuotaState::Rejecting {
+        record_device_error(Some(&device.device_id), "quota_exceeded");
+        return HttpResponse::InsufficientStorage()
+            .insert_header((QUOTA_WARNING_HEADER, quota.as_ref().map(|q| q.warning()).unwrap_or_default()))
+            .json(serde_json::json!({"error": "Organization storage quota exceeded"}));
+    }
+
+    // Create sensor reading
+    let reading: Result<SensorReading, _> = sqlx::query_as(
+        "INSERT INTO sensor_readings (device_id, heart_rate, spo2, temperature, reading_timestamp, metadata, ingest_source, relayed_by) 
+         VALUES ($1, $2, $3, $4, to_timestamp($5), jsonb_strip_nulls(jsonb_build_object(
+             'steps', $6::int, 'motion', $7::real, 'elevation_change', $8::real,
+             'device', $11::jsonb, 'raw', $12::jsonb)), $9, $10) RETURNING *"
+    )
+    .bind(device.id)
+    .bind(body.heart_rate)
+    .bind(body.spo2)
+    .bind(body.temperature)
+    .bind(body.timestamp)
+    .bind(body.steps)
+    .bind(body.motion)
+    .bind(body.elevation_change)
+    .bind(if source.relayed_by.is_some() { "relayed" } else { "direct" })
+    .bind(source.relayed_by)
+    .bind(body.metadata.as_ref().filter(|m| !m.is_empty()).map(sqlx::types::Json))
+    .bind(normalized.raw.as_ref().map(sqlx::types::Json))
+    .fetch_one(&state.pool)
+    .await;
+
+    let reading = match reading {
+        Ok(r) => r,
+        Err(e) => {
+            record_device_error(Some(&device.device_id), "storage");
+            let error = format!("Database error: {}", e);
+            record_failure(&state.pool, device, source, &normalized.as_sent(), FailureCategory::Storage, &error).await;
+            return HttpResponse::InternalServerError().json(serde_json::json!({"error": error}));
+        }
+    };
+    if let Some(id) = source.retry_of {
+        if let Err(e) = mark_reprocessed(&state.pool, id, reading.id).await {
+            tracing::warn!(failed_ingestion = id, "Failed to mark dead-lettered reading as reprocessed: {}", e);
+        }
+    }
+    DEVICE_READINGS_TOTAL.with_label_values(&[&device.device_id]).inc();
+
+    usage_service::record_in_background(&state.pool, device.patient_id, UsageMetric::ReadingsIngested, 1);
+
+    // Cached chart aggregates covering this reading are now stale
+    if let Some(patient_id) = device.patient_id {
+        aggregate_service::invalidate(&state.redis, patient_id, reading.reading_timestamp).await;
+    }
+
+    // Ambient conditions are kept apart from the clinical reading
+    if body.ambient_temperature.is_some() || body.humidity.is_some() {
+        if let Err(e) = record_ambient(
+            &state.pool,
+            reading.id,
+            device.id,
+            reading.reading_timestamp,
+            body.ambient_temperature,
+            body.humidity,
+            body.heart_rate,
+        )
+        .await
+        {
+            tracing::warn!(reading_id = reading.id, "Failed to store ambient conditions: {}", e);
+        }
+    }
+
+    // Run ML analysis against the patient's threshold profile, if one is assigned
+    let inputs = AnalysisInputs::load(state, device).await;
+    let timer = ML_ANALYSIS_DURATION.start_timer();
+    let mut ml_result = match state.ml_service.reuse_analysis(&reading, &inputs.rules, inputs.context(&[])) {
+        Some(result) => {
+            ML_ANALYSIS_REUSED.inc();
+            result
+        }
+        None => {
+            let recent = recent_readings(state, device, &reading, &inputs.rules).await;
+            let result = state.ml_service.analyze_reading_in_context(&reading, inputs.context(&recent), &inputs.rules);
+            state.ml_service.remember_analysis(&reading, &inputs.rules, inputs.context(&[]), &result);
+            result
+        }
+    };
+    timer.observe_duration();
+    inputs.annotate(&mut ml_result);
+    if ml_result.anomaly_detected {
+        ML_ANOMALIES_DETECTED.with_label_values(&[ml_result.alert_level.as_str()]).inc();
+    }
+    
+    // Store ML analysis
+    let _ = sqlx::query(
+        "INSERT INTO ml_analysis (sensor_reading_id, anomaly_detected, anomaly_score, classification, alert_level, analysis_details, threshold_profile_version_id) 
+         VALUES ($1, $2, $3, $4, $5, $6, $7)"
+    )
+    .bind(reading.id)
+    .bind(ml_result.anomaly_detected)
+    .bind(ml_result.anomaly_score)
+    .bind(ml_result.classification)
+    .bind(ml_result.alert_level)
+    .bind(&ml_result.details)
+    .bind(inputs.profile.as_ref().map(|p| p.version_id))
+    .execute(&state.pool)
+    .await;
+
+    // Create FHIR observations, with the Patient and Device they reference
+    let fhir_patient = match device.patient_id {
+        Some(patient_id) => sqlx::query_as::<_, Patient>("SELECT id, display_name, date_of_birth FROM patients WHERE id = $1")
+            .bind(patient_id)
+            .fetch_optional(&state.pool)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!(reading_id = reading.id, "Failed to load patient for FHIR: {}", e);
+                None
+            }),
+        None => None,
+    };
+    let fhir_bundle = state.fhir_service.create_observation_bundle(&reading, Some(device), fhir_patient.as_ref());
+    
+    let _ = sqlx::query(
+        "INSERT INTO fhir_observations (sensor_reading_id, resource, subject_reference) VALUES ($1, $2, $3)"
+    )
+    .bind(reading.id)
+    .bind(&fhir_bundle)
+    .bind(fhir_patient.as_ref().map(|p| format!("Patient/{}", p.id)))
+    .execute(&state.pool)
+    .await;
+    if let Err(e) = store_subjects(&state.pool, &fhir_bundle).await {
+        tracing::warn!(reading_id = reading.id, "Failed to store FHIR Patient and Device: {}", e);
+    }
+    if let (true, Some(patient)) = (state.deployment.ehr_push, &fhir_patient) {
+        ehr_push::push_in_background(&state.pool, &state.phi, &state.fhir_service, &reading, device, patient, &fhir_bundle);
+    }
+
+    // Prepare vitals for caching and broadcasting
+    let vitals = LatestVitals {
+        heart_rate: body.heart_rate,
+        spo2: body.spo2,
+        temperature: body.temperature,
+        timestamp: body.timestamp,
+        quality_score: Some(ml_result.quality_score),
+        ml_alert: state.ml_service.generate_alert(&ml_result).map(|a| a.level),
+    };
+
+    // Cache in Redis
+    let mut redis = state.redis.write().await;
+    let _ = redis.set_latest_vitals(device.patient_id, &vitals).await;
+    let cooled_down = match vitals.ml_alert {
+        Some(level) => claim_alert_cooldown(&mut redis, device.id, level, state.ml_service.alert_cooldown_seconds()).await,
+        None => false,
+    };
+    drop(redis);
+
+    // Broadcast via SSE
+    broadcast_vitals(&state.sse_broadcaster, device.patient_id, vitals.clone());
+
+    // Broadcast alert if needed, once per cooldown window for each level
+    if let Some(mut alert) = state.ml_service.generate_alert(&ml_result).filter(|_| cooled_down) {
+        let alert_id = match record_vitals_alert(&state.pool, device, reading.id, &alert).await {
+            Ok(Some(stored)) => {
+                alert.details["alert_id"] = serde_json::json!(stored.id);
+                Some(stored.id)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                tracing::warn!(reading_id = reading.id, "Failed to store vitals alert: {}", e);
+                None
+            }
+        };
+        if let Some(patient_id) = device.patient_id {
+            let notifier = state.notifier.clone();
+            let queued = alert.clone();
+            tokio::spawn(async move {
+                if let Err(e) = notifier.notify_channels(patient_id, alert_id, &queued).await {
+                    tracing::warn!(%patient_id, "Failed to queue alert notifications: {:#}", e);
+                }
+            });
+        }
+        broadcast_alert(&state.sse_broadcaster, device.patient_id, alert);
+    }
+
+    let mut response = HttpResponse::Ok();
+    if let (Some(quota), QuotaState::Near | QuotaState::Exceeded) = (&quota, quota_state) {
+        response.insert_header((QUOTA_WARNING_HEADER, quota.warning()));
+    }
+    if let Some(version) = return_vitals {
+        response.insert_header((PREFERENCE_APPLIED, RETURN_REPRESENTATION));
+        return version.json(response, &serde_json::json!({"status": "accepted", "reading_id": reading.id, "vitals": vitals}));
+    }
+    response.json(serde_json::json!({"status": "accepted", "reading_id": reading.id}))
+}
+
+/// Whether an alert at `level` is the device's first within the cooldown window, starting the
+/// window if so. Without Redis every alert goes out: a repeat is better than a missed alert.
+async fn claim_alert_cooldown(redis: &mut RedisCache, device_id: Uuid, level: AlertLevel, seconds: u64) -> bool {
+    if seconds == 0 {
+        return true;
+    }
+    redis.claim_alert_cooldown(device_id, level, seconds).await.unwrap_or_else(|e| {
+        tracing::warn!(%device_id, "Failed to check alert cooldown: {}", e);
+        true
+    })
+}
+
+/// What [`ingest_vitals`] would do with `body`, without doing any of it. The reading is
+/// analysed afresh against the same rules, patient record, baseline and recent readings.
+async fn dry_run_vitals(state: &AppState, device: &Device, normalized: &NormalizedReading, version: ApiVersion) -> HttpResponse {
+    let body = &normalized.vitals;
+    let quota_state = match device.patient_id {
+        Some(patient_id) => quota_for_patient(&state.pool, patient_id).await.unwrap_or_else(|e| {
+            tracing::warn!(device_id = %device.device_id, "Failed to load storage quota: {}", e);
+            None
+        }),
+        None => None,
+    }
+    .map_or(QuotaState::Within, |q| q.state(&state.quota));
+
+    // The reading as it would be stored, metadata included
+    let metadata: serde_json::Map<String, serde_json::Value> = [
+        ("steps", body.steps.map(|v| serde_json::json!(v))),
+        ("motion", body.motion.map(|v| serde_json::json!(v))),
+        ("elevation_change", body.elevation_change.map(|v| serde_json::json!(v))),
+        ("device", body.metadata.as_ref().filter(|m| !m.is_empty()).map(|m| serde_json::json!(m))),
+        ("raw", normalized.raw.as_ref().map(|m| serde_json::json!(m))),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| Some((key.to_string(), value?)))
+    .collect();
+    let reading = SensorReading {
+        id: 0,
+        device_id: device.id,
+        heart_rate: Some(body.heart_rate),
+        spo2: Some(body.spo2),
+        temperature: Some(body.temperature),
+        reading_timestamp: chrono::DateTime::from_timestamp(body.timestamp, 0).unwrap_or_else(Utc::now),
+        received_at: Utc::now(),
+        quality_score: None,
+        metadata: serde_json::Value::Object(metadata),
+    };
+
+    let inputs = AnalysisInputs::load(state, device).await;
+    let recent = recent_readings(state, device, &reading, &inputs.rules).await;
+    let mut analysis = state.ml_service.analyze_reading_in_context(&reading, inputs.context(&recent), &inputs.rules);
+    inputs.annotate(&mut analysis);
+    let alert = state.ml_service.generate_alert(&analysis);
+
+    let vitals = LatestVitals {
+        heart_rate: body.heart_rate,
+        spo2: body.spo2,
+        temperature: body.temperature,
+        timestamp: body.timestamp,
+        quality_score: Some(analysis.quality_score),
+        ml_alert: alert.as_ref().map(|a| a.level),
+    };
+    version.json(HttpResponse::Ok(), &serde_json::json!({
+        "status": "dry_run",
+        "would_accept": quota_state != QuotaState::Rejecting,
+        "quota": quota_state,
+        "device_id": device.device_id,
+        "patient_id": device.patient_id,
+        "metadata": reading.metadata,
+        "analysis": analysis,
+        "alert": alert,
+        "vitals": vitals,
+    }))
+}
+
+/// What a reading from a device is judged against besides its own values
+pub(crate) struct AnalysisInputs {
+    pub(crate) profile: Option<ThresholdProfile>,
+    pub(crate) rules: ThresholdRules,
+    pub(crate) patient: Option<PatientAttributes>,
+    pub(crate) baseline: Option<Baseline>,
+}
+
+impl AnalysisInputs {
+    /// The device's patient's threshold profile, record and baseline; any that fail to load
+    /// are left out rather than failing the reading
+    pub(crate) async fn load(state: &AppState, device: &Device) -> Self {
+        let warn = |what: &str, e: sqlx::Error| tracing::warn!(device_id = %device.device_id, "Failed to load {}: {}", what, e);
+
+        let profile = match device.patient_id {
+            Some(patient_id) => patient_profile(&state.pool, patient_id).await.unwrap_or_else(|e| {
+                warn("threshold profile", e);
+                None
+            }),
+            None => None,
+        };
+        let rules = match &profile {
+            Some(profile) => profile.rules.0.clone(),
+            None => state.ml_service.default_rules(),
+        };
+
+        let patient = match device.patient_id {
+            Some(patient_id) => sqlx::query_as::<_, PatientAttributes>("SELECT date_of_birth, diagnoses FROM patients WHERE id = $1")
+                .bind(patient_id)
+                .fetch_optional(&state.pool)
+                .await
+                .unwrap_or_else(|e| {
+                    warn("patient attributes", e);
+                    None
+                }),
+            None => None,
+        };
+
+        let baseline = load_baseline(&state.pool, device.id, device.patient_id).await.unwrap_or_else(|e| {
+            warn("vitals baseline", e);
+            None
+        });
+
+        Self { profile, rules, patient, baseline }
+    }
+
+    fn context<'a>(&'a self, recent: &'a [SensorReading]) -> AnalysisContext<'a> {
+        AnalysisContext { recent, patient: self.patient.as_ref(), baseline: self.baseline.as_ref() }
+    }
+
+    /// Record which threshold profile version the result was judged by
+    fn annotate(&self, result: &mut MlAnalysisResult) {
+        if let (Some(profile), Some(details)) = (&self.profile, result.details.as_object_mut()) {
+            details.insert(
+                "threshold_profile".into(),
+                serde_json::json!({"name": profile.name, "version": profile.version}),
+            );
+        }
+    }
+}
+
+/// Earlier readings of the device that rate-of-change rules and rule durations look at
+async fn recent_readings(state: &AppState, device: &Device, reading: &SensorReading, rules: &ThresholdRules) -> Vec<SensorReading> {
+    let lookback = state.ml_service.history_lookback(rules);
+    sqlx::query_as(
+        "SELECT * FROM sensor_readings
+         WHERE device_id = $1 AND reading_timestamp >= $2 AND reading_timestamp < $3
+         ORDER BY reading_timestamp DESC LIMIT $4"
+    )
+    .bind(device.id)
+    .bind(reading.reading_timestamp - lookback)
+    .bind(reading.reading_timestamp)
+    .bind(MAX_HISTORY_READINGS as i64)
+    .fetch_all(&state.pool)
+    .await
+    .unwrap_or_else(|e| {
+        tracing::warn!(device_id = %device.device_id, "Failed to load recent readings: {}", e);
+        Vec::new()
+    })
+}
+
+/// Discrete walker events: SOS button presses and on-device near-fall/stumble detection
+pub async fn device_event(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    body: web::Bytes,
+) -> Result<HttpResponse, ApiError> {
+    // The walker signed the bytes it sent, so they are checked before being parsed
+    let device = verify_device(&req, &state, &String::from_utf8_lossy(&body)).await?;
+    let body: DeviceEventIngest = match json_from_bytes(&req, &body) {
+        Ok(body) => body,
+        Err(e) => return Ok(e.error_response()),
+    };
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    match body.event_type.as_str() {
+        "sos" => {
+            let alert = raise_sos(&state.pool, &state.notifier, &state.sse_broadcaster, &device, &body)
+                .await
+                .map_err(|e| {
+                    tracing::error!(device_id = %device.device_id, "SOS handling failed: {}", e);
+                    ApiError::Internal("Failed to raise SOS alert".into())
+                })?;
+            Ok(HttpResponse::Created().json(serde_json::json!({"status": "accepted", "alert_id": alert.id})))
+        }
+        "near_fall" | "stumble" => {
+            let (event, alert) = record_near_fall(&state.pool, &state.notifier, &state.sse_broadcaster, &device, &body)
+                .await
+                .map_err(|e| {
+                    tracing::error!(device_id = %device.device_id, "Near-fall handling failed: {}", e);
+                    ApiError::Internal("Failed to record near-fall event".into())
+                })?;
+            Ok(HttpResponse::Created().json(serde_json::json!({
+                "status": "accepted",
+                "event_id": event.id,
+                "alert_id": alert.map(|a| a.id),
+            })))
+        }
+        other => Err(ApiError::BadRequest(format!("Unsupported event type '{}'", other))),
+    }
+}
+
+// ============ Device Claiming ============
+
+/// Link a device to a patient using the pairing code shown on the walker.
+///
+/// The code is single use and the caller becomes a caregiver of the patient, which is
+/// created from `patient_name` in the caller's organization or must already be accessible
+/// under the deployment's RBAC. The walker joins the patient's organization.
+pub async fn claim_device(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    body: web::Json<DeviceClaimRequest>,
+) -> Result<HttpResponse, ApiError> {
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    if body.patient_id.is_none() && body.patient_name.is_none() {
+        return Err(ApiError::BadRequest("Either patient_id or patient_name is required".into()));
+    }
+
+    let mut tx = state.pool.begin().await?;
+
+    let code: Option<(uuid::Uuid, uuid::Uuid)> = sqlx::query_as(
+        "SELECT id, device_id FROM device_pairing_codes
+         WHERE code_hash = $1 AND used_at IS NULL AND expires_at > now() FOR UPDATE"
+    )
+    .bind(hash_code(&body.pairing_code))
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some((code_id, device_uuid)) = code else {
+        crate::audit_log!("device", "claim", Some(claims.user_id), false);
+        return Err(ApiError::BadRequest("Invalid or expired pairing code".into()));
+    };
+
+    // Only unowned walkers cross scopes; another organization's are not found, even by
+    // callers outside any organization
+    let device: Device = sqlx::query_as(
+        "SELECT * FROM devices
+         WHERE id = $1 AND is_active = true AND (organization_id IS NOT DISTINCT FROM $2 OR organization_id IS NULL)
+         FOR UPDATE"
+    )
+    .bind(device_uuid)
+    .bind(claims.org)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Device not found".into()))?;
+
+    let patient_id = match body.patient_id {
+        Some(patient_id) => {
+            let archived: Option<bool> = sqlx::query_scalar("SELECT archived_at IS NOT NULL FROM patients WHERE id = $1")
+                .bind(patient_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+
+            match archived {
+                None => return Err(ApiError::NotFound("Patient not found".into())),
+                Some(true) => return Err(ApiError::Conflict("Patient record is archived".into())),
+                Some(false) => {}
+            }
+            if !can_access_patient(&state, &claims, patient_id).await? {
+                return Err(ApiError::Forbidden("Not a caregiver for this patient".into()));
+            }
+            patient_id
+        }
+        None => {
+            // Home deployments serve a single patient; further devices join the existing one
+            if patient_limit_reached(&state, &mut tx, claims.org, claims.user_id).await? {
+                return Err(ApiError::Conflict(format!(
+                    "Patient limit reached for {} deployments; claim with an existing patient_id",
+                    state.deployment.mode.name()
+                )));
+            }
+
+            let patient_id: uuid::Uuid = sqlx::query_scalar(
+                "INSERT INTO patients (display_name, organization_id, created_by) VALUES ($1, $2, $3) RETURNING id"
+            )
+            .bind(body.patient_name.as_deref().unwrap_or_default().trim())
+            .bind(claims.org)
+            .bind(claims.user_id)
+            .fetch_one(&mut *tx)
+            .await?;
+            patient_id
+        }
+    };
+
+    // The claimant cares for the device's patient from now on
+    sqlx::query("INSERT INTO patient_caregivers (patient_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+        .bind(patient_id)
+        .bind(claims.user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let claimed_at: chrono::DateTime<Utc> = sqlx::query_scalar(
+        "UPDATE devices
+         SET patient_id = $2, claimed_by = $3, claimed_at = now(),
+             organization_id = (SELECT organization_id FROM patients WHERE id = $2)
+         WHERE id = $1 RETURNING claimed_at"
+    )
+    .bind(device.id)
+    .bind(patient_id)
+    .bind(claims.user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query("UPDATE device_pairing_codes SET used_at = now(), used_by = $2 WHERE id = $1")
+        .bind(code_id)
+        .bind(claims.user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    // The device's earlier readings now count towards this patient (and no longer the previous one)
+    for patient_id in [Some(patient_id), device.patient_id].into_iter().flatten() {
+        aggregate_service::invalidate_all(&state.redis, patient_id).await;
+    }
+
+    crate::audit_log!("device", "claim", Some(claims.user_id), true, device.device_id);
+
+    Ok(HttpResponse::Ok().json(DeviceClaimResponse {
+        device_id: device.device_id,
+        device_name: device.device_name,
+        patient_id,
+        claimed_at,
+    }))
+}
@@ -0,0 +1,127 @@
+use crate::errors::ApiError;
+use crate::handlers::patients::{require_active_patient, require_patient_access};
+use crate::handlers::AppState;
+use crate::middleware::AuthenticatedUser;
+use crate::models::*;
+use actix_web::{web, HttpResponse};
+use uuid::Uuid;
+use validator::Validate;
+
+crate::routes::route_registry! {
+    "/patients/{patient_id}/emergency-contacts" {
+        GET => list_contacts, Jwt, [];
+        POST => create_contact, Jwt, [];
+    }
+    "/emergency-contacts/{id}" {
+        PUT => update_contact, Jwt, [];
+        DELETE => delete_contact, Jwt, [];
+    }
+}
+
+async fn load_contact(state: &AppState, claims: &Claims, id: Uuid) -> Result<EmergencyContact, ApiError> {
+    let contact: EmergencyContact = sqlx::query_as("SELECT * FROM emergency_contacts WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Emergency contact not found".into()))?;
+    require_patient_access(state, claims, contact.patient_id).await?;
+    Ok(contact)
+}
+
+/// Contacts in the order they are reached on SOS
+pub async fn list_contacts(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    require_patient_access(&state, &claims, patient_id).await?;
+
+    let contacts: Vec<EmergencyContact> = sqlx::query_as(
+        "SELECT * FROM emergency_contacts WHERE patient_id = $1 ORDER BY priority, created_at"
+    )
+    .bind(patient_id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(contacts))
+}
+
+pub async fn create_contact(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Json<EmergencyContactRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    require_active_patient(&state.pool, patient_id).await?;
+    require_patient_access(&state, &claims, patient_id).await?;
+
+    let contact: EmergencyContact = sqlx::query_as(
+        "INSERT INTO emergency_contacts (patient_id, name, relationship, phone, priority, notify_sms, notify_call)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)
+         RETURNING *"
+    )
+    .bind(patient_id)
+    .bind(body.name.trim())
+    .bind(&body.relationship)
+    .bind(&body.phone)
+    .bind(body.priority)
+    .bind(body.notify_sms)
+    .bind(body.notify_call)
+    .fetch_one(&state.pool)
+    .await?;
+
+    crate::audit_log!("emergency_contact", "create", Some(claims.user_id), true, contact.id);
+
+    Ok(HttpResponse::Created().json(contact))
+}
+
+pub async fn update_contact(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Json<EmergencyContactRequest>,
+) -> Result<HttpResponse, ApiError> {
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let contact = load_contact(&state, &claims, path.into_inner()).await?;
+
+    let contact: EmergencyContact = sqlx::query_as(
+        "UPDATE emergency_contacts
+         SET name = $2, relationship = $3, phone = $4, priority = $5, notify_sms = $6, notify_call = $7
+         WHERE id = $1
+         RETURNING *"
+    )
+    .bind(contact.id)
+    .bind(body.name.trim())
+    .bind(&body.relationship)
+    .bind(&body.phone)
+    .bind(body.priority)
+    .bind(body.notify_sms)
+    .bind(body.notify_call)
+    .fetch_one(&state.pool)
+    .await?;
+
+    crate::audit_log!("emergency_contact", "update", Some(claims.user_id), true, contact.id);
+
+    Ok(HttpResponse::Ok().json(contact))
+}
+
+pub async fn delete_contact(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let contact = load_contact(&state, &claims, path.into_inner()).await?;
+
+    sqlx::query("DELETE FROM emergency_contacts WHERE id = $1")
+        .bind(contact.id)
+        .execute(&state.pool)
+        .await?;
+
+    crate::audit_log!("emergency_contact", "delete", Some(claims.user_id), true, contact.id);
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,112 @@
+use crate::errors::ApiError;
+use crate::handlers::{require_unscoped, AppState};
+use crate::middleware::AuthenticatedUser;
+use crate::models::*;
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+// Mounted under /api/admin
+crate::routes::route_registry! {
+    "/event-log" {
+        GET => list_events, Jwt, ["admin"];
+    }
+    "/event-log/users/{user_id}" {
+        GET => user_event_history, Jwt, ["admin"];
+    }
+}
+
+const DEFAULT_EVENTS: i64 = 1000;
+const MAX_EVENTS: i64 = 10_000;
+/// Longest period searched at once
+const MAX_RANGE_DAYS: i64 = 31;
+
+/// `from`..`to` of a query: the day before `to` (now) by default
+fn time_range(query: &EventLogQuery) -> Result<(DateTime<Utc>, DateTime<Utc>), ApiError> {
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query.from.unwrap_or(to - Duration::days(1));
+    if from > to {
+        return Err(ApiError::BadRequest("from must not be after to".into()));
+    }
+    if to - from > Duration::days(MAX_RANGE_DAYS) {
+        return Err(ApiError::BadRequest(format!("At most {} days can be searched at once", MAX_RANGE_DAYS)));
+    }
+    Ok((from, to))
+}
+
+/// Logged broadcast events in a period, oldest first, optionally for one patient or type
+pub async fn list_events(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    query: web::Query<EventLogQuery>,
+) -> Result<HttpResponse, ApiError> {
+    require_unscoped(&claims)?;
+    let (from, to) = time_range(&query)?;
+    let limit = query.limit.unwrap_or(DEFAULT_EVENTS).clamp(1, MAX_EVENTS);
+
+    let events: Vec<EventLogEntry> = sqlx::query_as(
+        "SELECT * FROM event_log
+         WHERE occurred_at BETWEEN $1 AND $2
+           AND ($3::uuid IS NULL OR patient_id = $3) AND ($4::text IS NULL OR event_type = $4)
+         ORDER BY occurred_at, event_id
+         LIMIT $5"
+    )
+    .bind(from)
+    .bind(to)
+    .bind(query.patient_id)
+    .bind(&query.event_type)
+    .bind(limit)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(events))
+}
+
+/// The user's SSE and WebSocket streams open during a period, and each logged event those
+/// streams were sent: one that occurred while a stream was open and concerned a patient it
+/// was limited to. An event missing here was never sent to that user's dashboards.
+/// WebSocket clients may have narrowed the event types they took on top of this.
+pub async fn user_event_history(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<EventLogQuery>,
+) -> Result<HttpResponse, ApiError> {
+    require_unscoped(&claims)?;
+    let user_id = path.into_inner();
+    let (from, to) = time_range(&query)?;
+    let limit = query.limit.unwrap_or(DEFAULT_EVENTS).clamp(1, MAX_EVENTS);
+
+    let sessions: Vec<StreamSessionRecord> = sqlx::query_as(
+        "SELECT * FROM stream_sessions
+         WHERE user_id = $1 AND opened_at <= $3 AND (closed_at IS NULL OR closed_at >= $2)
+         ORDER BY opened_at"
+    )
+    .bind(user_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(&state.pool)
+    .await?;
+
+    let events: Vec<SentEvent> = sqlx::query_as(
+        "SELECT s.id AS session_id, e.*
+         FROM stream_sessions s
+         JOIN event_log e ON e.occurred_at >= s.opened_at AND (s.closed_at IS NULL OR e.occurred_at <= s.closed_at)
+                         AND (s.patients IS NULL OR e.patient_id = ANY(s.patients))
+         WHERE s.user_id = $1 AND e.occurred_at BETWEEN $2 AND $3
+           AND ($4::uuid IS NULL OR e.patient_id = $4) AND ($5::text IS NULL OR e.event_type = $5)
+         ORDER BY e.occurred_at, e.event_id, s.opened_at
+         LIMIT $6"
+    )
+    .bind(user_id)
+    .bind(from)
+    .bind(to)
+    .bind(query.patient_id)
+    .bind(&query.event_type)
+    .bind(limit)
+    .fetch_all(&state.pool)
+    .await?;
+
+    crate::audit_log!("event_log", "user_history", Some(claims.user_id), true, user_id);
+    Ok(HttpResponse::Ok().json(UserEventHistory { user_id, from, to, sessions, events }))
+}
@@ -0,0 +1,127 @@
+use crate::dead_letter::IngestSource;
+use crate::errors::ApiError;
+use crate::handlers::device::{ingest_vitals, normalize_for};
+use crate::handlers::AppState;
+use crate::metrics::INGESTION_REPROCESSED_TOTAL;
+use crate::middleware::AuthenticatedUser;
+use crate::models::*;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+
+// Mounted under /api/admin
+crate::routes::route_registry! {
+    "/failed-ingestions" {
+        GET => list_failed_ingestions, Jwt, ["admin", "device_manager"];
+    }
+    "/failed-ingestions/{id}" {
+        GET => get_failed_ingestion, Jwt, ["admin", "device_manager"];
+        DELETE => discard_failed_ingestion, Jwt, ["admin", "device_manager"];
+    }
+    "/failed-ingestions/{id}/reprocess" {
+        POST => reprocess_failed_ingestion, Jwt, ["admin", "device_manager"];
+    }
+}
+
+const DEFAULT_FAILED_INGESTIONS: i64 = 100;
+const MAX_FAILED_INGESTIONS: i64 = 500;
+
+const FAILED_INGESTION_SQL: &str =
+    "SELECT f.id, d.device_id, f.channel, f.relayed_by, f.payload, f.category, f.error, f.attempts, f.received_at,
+            f.last_attempt_at, f.reprocessed_at, f.sensor_reading_id
+     FROM failed_ingestions f JOIN devices d ON d.id = f.device_id";
+
+/// An entry from a walker of the caller's organization; others are not found
+async fn load_failed_ingestion(pool: &PgPool, claims: &Claims, id: i64) -> Result<FailedIngestion, ApiError> {
+    sqlx::query_as(&format!(
+        "{} WHERE f.id = $1 AND ($2::uuid IS NULL OR d.organization_id = $2)",
+        FAILED_INGESTION_SQL
+    ))
+    .bind(id)
+    .bind(claims.org)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Failed ingestion not found".into()))
+}
+
+/// The dead-letter queue of the caller's organization, oldest first
+pub async fn list_failed_ingestions(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    query: web::Query<FailedIngestionQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let limit = query.limit.unwrap_or(DEFAULT_FAILED_INGESTIONS).clamp(1, MAX_FAILED_INGESTIONS);
+    let entries: Vec<FailedIngestion> = sqlx::query_as(&format!(
+        "{} WHERE (f.reprocessed_at IS NOT NULL) = $1 AND ($2::text IS NULL OR f.category = $2)
+           AND ($3::text IS NULL OR d.device_id = $3) AND ($4::uuid IS NULL OR d.organization_id = $4)
+         ORDER BY f.received_at, f.id
+         LIMIT $5",
+        FAILED_INGESTION_SQL
+    ))
+    .bind(query.reprocessed)
+    .bind(query.category)
+    .bind(&query.device_id)
+    .bind(claims.org)
+    .bind(limit)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+pub async fn get_failed_ingestion(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(load_failed_ingestion(&state.pool, &claims, path.into_inner()).await?))
+}
+
+/// Run a dead-lettered reading through ingestion again, with its device's current units.
+/// Answers as ingestion would; a reading refused again stays queued with the new reason.
+pub async fn reprocess_failed_ingestion(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, ApiError> {
+    let entry = load_failed_ingestion(&state.pool, &claims, path.into_inner()).await?;
+    if entry.reprocessed_at.is_some() {
+        return Err(ApiError::Conflict("Already reprocessed".into()));
+    }
+    let device: Device = sqlx::query_as("SELECT * FROM devices WHERE device_id = $1")
+        .bind(&entry.device_id)
+        .fetch_one(&state.pool)
+        .await?;
+    let reading: RawVitals = serde_json::from_value(entry.payload)
+        .map_err(|e| ApiError::Internal(format!("Stored payload is not a reading: {}", e)))?;
+
+    let source = IngestSource { channel: entry.channel, relayed_by: entry.relayed_by, retry_of: Some(entry.id) };
+    let response = match normalize_for(&state, &device, reading, &source).await {
+        Ok(normalized) => ingest_vitals(&state, &device, &normalized, &source, None).await,
+        Err(e) => {
+            INGESTION_REPROCESSED_TOTAL.with_label_values(&["failed"]).inc();
+            crate::audit_log!("failed_ingestion", "reprocess", Some(claims.user_id), false, entry.id);
+            return Err(e);
+        }
+    };
+    let stored = response.status().is_success();
+    INGESTION_REPROCESSED_TOTAL.with_label_values(&[if stored { "stored" } else { "failed" }]).inc();
+
+    crate::audit_log!("failed_ingestion", "reprocess", Some(claims.user_id), stored, entry.id);
+    Ok(response)
+}
+
+/// Drop an entry that will never be stored, e.g. a payload from broken firmware
+pub async fn discard_failed_ingestion(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, ApiError> {
+    let entry = load_failed_ingestion(&state.pool, &claims, path.into_inner()).await?;
+    sqlx::query("DELETE FROM failed_ingestions WHERE id = $1")
+        .bind(entry.id)
+        .execute(&state.pool)
+        .await?;
+
+    crate::audit_log!("failed_ingestion", "discard", Some(claims.user_id), true, entry.id);
+    Ok(HttpResponse::NoContent().finish())
+}
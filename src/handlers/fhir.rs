@@ -0,0 +1,331 @@
+use crate::bulk_export::{self, parse_types, FHIR_NDJSON, OUTPUT_FORMATS};
+use crate::errors::ApiError;
+use crate::fhir_service::{export_cursor, page_links, parse_export_cursor};
+use crate::handlers::admin::load_organization_profile;
+use crate::handlers::care_plans::load_care_plan;
+use crate::handlers::patients::require_patient_access;
+use crate::handlers::AppState;
+use crate::middleware::AuthenticatedUser;
+use crate::models::*;
+use crate::negotiation::{negotiate, ResponseFormat};
+use crate::rbac::{has_role, Role};
+use crate::usage_service::{self, UsageMetric};
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use chrono::SecondsFormat;
+use std::collections::{HashMap, HashSet};
+use validator::Validate;
+
+crate::routes::route_registry! {
+    "/export" {
+        GET => export_fhir_bundle, Jwt, ["admin", "clinician"];
+    }
+    "/$export" {
+        GET => bulk_export, Jwt, ["admin", "clinician"];
+    }
+    "/export-status/{job_id}" {
+        GET => bulk_export_status, Jwt, ["admin", "clinician"];
+    }
+    "/export-files/{job_id}/{resource_type}/{part}" {
+        GET => bulk_export_file, Jwt, ["admin", "clinician"];
+    }
+    "/CarePlan/{id}" {
+        GET => export_care_plan, Jwt, [];
+    }
+    "/QuestionnaireResponse/{id}" {
+        GET => export_checkin, Jwt, [];
+    }
+    "/Organization/{id}" {
+        GET => export_organization, Jwt, [];
+    }
+}
+
+/// Observations of stored readings in the order the server received them, as pages of a
+/// searchset Bundle (or NDJSON with the page links in a `Link` header). Pages continue after
+/// the previous one's last reading, so readings arriving meanwhile don't shift them. Users of
+/// an organization get its walkers' readings.
+pub async fn export_fhir_bundle(
+    req: HttpRequest,
+    user: AuthenticatedUser,
+    state: web::Data<AppState>,
+    query: web::Query<FhirExportQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let format = match negotiate(&req, &[ResponseFormat::FhirJson, ResponseFormat::Json, ResponseFormat::Ndjson]) {
+        Ok(f) => f,
+        Err(resp) => return Ok(resp),
+    };
+    query.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let count = query.count.unwrap_or(DEFAULT_FHIR_PAGE_SIZE).min(MAX_FHIR_PAGE_SIZE);
+    let after = match query.cursor.as_deref() {
+        Some(cursor) => Some(parse_export_cursor(cursor).ok_or_else(|| ApiError::BadRequest("Invalid _cursor".into()))?),
+        None => None,
+    };
+
+    // Observations matching, one per vital present in each reading
+    let total: i64 = sqlx::query_scalar(
+        "SELECT COALESCE(SUM((heart_rate IS NOT NULL)::int + (spo2 IS NOT NULL)::int
+                             + (temperature IS NOT NULL)::int), 0)::bigint
+         FROM sensor_readings
+         WHERE ($1::timestamptz IS NULL OR received_at >= $1)
+           AND ($2::uuid IS NULL OR device_id IN (SELECT id FROM devices WHERE organization_id = $2))"
+    )
+    .bind(query.since)
+    .bind(user.org)
+    .fetch_one(&state.pool)
+    .await?;
+
+    // One more than the page, to tell whether another follows
+    let mut readings: Vec<SensorReading> = sqlx::query_as(
+        "SELECT * FROM sensor_readings
+         WHERE ($1::timestamptz IS NULL OR received_at >= $1)
+           AND ($3::uuid IS NULL OR device_id IN (SELECT id FROM devices WHERE organization_id = $3))
+           AND ($4::timestamptz IS NULL OR (received_at, id) > ($4, $5))
+         ORDER BY received_at, id
+         LIMIT $2 + 1"
+    )
+    .bind(query.since)
+    .bind(count)
+    .bind(user.org)
+    .bind(after.map(|(received_at, _)| received_at))
+    .bind(after.map(|(_, id)| id))
+    .fetch_all(&state.pool)
+    .await?;
+    let next_cursor = if readings.len() as i64 > count {
+        readings.truncate(count as usize);
+        readings.last().map(|last| export_cursor(last.received_at, last.id))
+    } else {
+        None
+    };
+
+    // The walkers and patients the page's Observations reference
+    let device_ids: Vec<uuid::Uuid> = readings.iter().map(|r| r.device_id).collect();
+    let devices: HashMap<uuid::Uuid, Device> = sqlx::query_as::<_, Device>("SELECT * FROM devices WHERE id = ANY($1)")
+        .bind(&device_ids)
+        .fetch_all(&state.pool)
+        .await?
+        .into_iter()
+        .map(|d| (d.id, d))
+        .collect();
+    let patient_ids: Vec<uuid::Uuid> = devices.values().filter_map(|d| d.patient_id).collect();
+    let patients: HashMap<uuid::Uuid, Patient> =
+        sqlx::query_as::<_, Patient>("SELECT id, display_name, date_of_birth FROM patients WHERE id = ANY($1)")
+            .bind(&patient_ids)
+            .fetch_all(&state.pool)
+            .await?
+            .into_iter()
+            .map(|p| (p.id, p))
+            .collect();
+
+    // Convert each reading to FHIR observations; a Patient or Device shared by several
+    // readings is included once
+    let mut included = HashSet::new();
+    let mut entries = Vec::new();
+    for reading in &readings {
+        let device = devices.get(&reading.device_id);
+        let patient = device.and_then(|d| d.patient_id).and_then(|id| patients.get(&id));
+        let bundle = state.fhir_service.create_observation_bundle(reading, device, patient);
+        for mut entry in bundle["entry"].as_array().cloned().unwrap_or_default() {
+            let mode = if entry["resource"]["resourceType"] == "Observation" { "match" } else { "include" };
+            if mode == "include" && !included.insert(entry["fullUrl"].to_string()) {
+                continue;
+            }
+            entry["search"] = serde_json::json!({"mode": mode});
+            entries.push(entry);
+        }
+    }
+    usage_service::record_in_background(&state.pool, None, UsageMetric::ExportsGenerated, 1);
+
+    let connection = req.connection_info();
+    let url = format!("{}://{}{}", connection.scheme(), connection.host(), req.path());
+    let params: Vec<(&str, String)> = query
+        .since
+        .map(|since| ("_since", since.to_rfc3339_opts(SecondsFormat::Micros, true)))
+        .into_iter()
+        .collect();
+    let links = page_links(&url, &params, query.cursor.as_deref(), count, next_cursor.as_deref());
+
+    // NDJSON: one resource per line, no enclosing Bundle
+    if format == ResponseFormat::Ndjson {
+        let body = entries
+            .iter()
+            .filter_map(|entry| entry.get("resource"))
+            .map(|resource| format!("{}\n", resource))
+            .collect::<String>();
+        let link_header = links
+            .iter()
+            .filter(|link| link["relation"] != "self")
+            .filter_map(|link| Some(format!("<{}>; rel=\"{}\"", link["url"].as_str()?, link["relation"].as_str()?)))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let mut response = HttpResponse::Ok();
+        response.content_type(format.content_type());
+        if !link_header.is_empty() {
+            response.insert_header((header::LINK, link_header));
+        }
+        return Ok(response.body(body));
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .json(state.fhir_service.create_searchset_bundle(entries, total, links)))
+}
+
+/// `https://host/api/fhir`, the base the bulk export URLs hang off
+fn fhir_base_url(req: &HttpRequest) -> String {
+    let connection = req.connection_info();
+    format!("{}://{}/api/fhir", connection.scheme(), connection.host())
+}
+
+/// Kick off a bulk export (FHIR Bulk Data Access). Answers `202 Accepted` at once, with the
+/// status URL to poll in `Content-Location`.
+pub async fn bulk_export(
+    req: HttpRequest,
+    user: AuthenticatedUser,
+    state: web::Data<AppState>,
+    query: web::Query<BulkExportQuery>,
+) -> Result<HttpResponse, ApiError> {
+    if let Some(format) = query.output_format.as_deref().filter(|f| !OUTPUT_FORMATS.contains(f)) {
+        return Err(ApiError::BadRequest(format!("Unsupported _outputFormat '{}'; only NDJSON is offered", format)));
+    }
+    let resource_types = parse_types(query.types.as_deref()).map_err(ApiError::BadRequest)?;
+
+    let job = bulk_export::create_job(&state.pool, user.user_id, user.org, &resource_types, query.since).await?;
+    bulk_export::spawn_job(state.pool.clone(), state.fhir_service.clone(), job.clone());
+    usage_service::record_in_background(&state.pool, None, UsageMetric::ExportsGenerated, 1);
+
+    crate::audit_log!("data_access", "fhir_bulk_export", Some(user.user_id), true, job.id);
+
+    Ok(HttpResponse::Accepted()
+        .insert_header((header::CONTENT_LOCATION, format!("{}/export-status/{}", fhir_base_url(&req), job.id)))
+        .finish())
+}
+
+/// A job the caller may see: their own, or any of their organization's for admins
+async fn load_bulk_export(state: &AppState, claims: &Claims, job_id: uuid::Uuid) -> Result<BulkExportJob, ApiError> {
+    let job = bulk_export::load_job(&state.pool, job_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Export not found".into()))?;
+    let admin_of_job = has_role(claims, Role::Admin)
+        && (claims.org.is_none() || claims.org == job.organization_id);
+    if job.requested_by != Some(claims.user_id) && !admin_of_job {
+        return Err(ApiError::NotFound("Export not found".into()));
+    }
+    Ok(job)
+}
+
+/// `202` with `X-Progress` while the job runs, then the manifest of output files, or an
+/// OperationOutcome if it failed
+pub async fn bulk_export_status(
+    req: HttpRequest,
+    user: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<uuid::Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let job = load_bulk_export(&state, &user, path.into_inner()).await?;
+
+    match job.status.as_str() {
+        "in_progress" => Ok(HttpResponse::Accepted()
+            .insert_header(("X-Progress", format!("Exporting {}", job.progress.as_deref().unwrap_or("resources"))))
+            .insert_header((header::RETRY_AFTER, "5"))
+            .finish()),
+        "failed" => Ok(HttpResponse::InternalServerError()
+            .content_type(ResponseFormat::FhirJson.content_type())
+            .json(serde_json::json!({
+                "resourceType": "OperationOutcome",
+                "issue": [{
+                    "severity": "error",
+                    "code": "exception",
+                    "diagnostics": job.error.unwrap_or_else(|| "Export failed".into())
+                }]
+            }))),
+        _ => {
+            let manifest = bulk_export::manifest(&state.pool, &job, &fhir_base_url(&req)).await?;
+            let mut response = HttpResponse::Ok();
+            if let Some(expires_at) = job.expires_at {
+                response.insert_header((header::EXPIRES, expires_at.to_rfc2822()));
+            }
+            Ok(response.json(manifest))
+        }
+    }
+}
+
+/// One NDJSON output file of a completed export
+pub async fn bulk_export_file(
+    user: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<(uuid::Uuid, String, i32)>,
+) -> Result<HttpResponse, ApiError> {
+    let (job_id, resource_type, part) = path.into_inner();
+    let job = load_bulk_export(&state, &user, job_id).await?;
+    let ndjson = bulk_export::load_file(&state.pool, job.id, &resource_type, part)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Export file not found".into()))?;
+
+    crate::audit_log!("data_access", "fhir_bulk_export_download", Some(user.user_id), true, job.id);
+
+    Ok(HttpResponse::Ok().content_type(FHIR_NDJSON).body(ndjson))
+}
+
+/// A care plan as FHIR `CarePlan` with its `Goal` resources
+pub async fn export_care_plan(
+    req: HttpRequest,
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<uuid::Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let format = match negotiate(&req, &[ResponseFormat::FhirJson, ResponseFormat::Json]) {
+        Ok(f) => f,
+        Err(resp) => return Ok(resp),
+    };
+    let plan = load_care_plan(&state, &claims, path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .json(state.fhir_service.create_care_plan_bundle(&plan)))
+}
+
+/// A symptom check-in as FHIR `QuestionnaireResponse`
+pub async fn export_checkin(
+    req: HttpRequest,
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<uuid::Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let format = match negotiate(&req, &[ResponseFormat::FhirJson, ResponseFormat::Json]) {
+        Ok(f) => f,
+        Err(resp) => return Ok(resp),
+    };
+
+    let mut checkin: Checkin = sqlx::query_as("SELECT * FROM checkins WHERE id = $1")
+        .bind(path.into_inner())
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Check-in not found".into()))?;
+    require_patient_access(&state, &claims, checkin.patient_id).await?;
+    checkin.notes = state.phi.open_field(checkin.notes.take()).await;
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .json(state.fhir_service.create_checkin_questionnaire_response(&checkin)))
+}
+
+/// This deployment's organization as FHIR `Organization`; its id is `fhir.organization_id`
+pub async fn export_organization(
+    req: HttpRequest,
+    _user: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let format = match negotiate(&req, &[ResponseFormat::FhirJson, ResponseFormat::Json]) {
+        Ok(f) => f,
+        Err(resp) => return Ok(resp),
+    };
+    if *path != state.fhir_service.organization_id() {
+        return Err(ApiError::NotFound("Organization not found".into()));
+    }
+    let profile = load_organization_profile(&state.pool).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .json(state.fhir_service.create_organization_resource(&profile)))
+}
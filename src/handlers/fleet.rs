@@ -0,0 +1,203 @@
+use crate::device_secrets::{generate_secret, hash_secret};
+use crate::aggregate_service;
+use crate::errors::ApiError;
+use crate::handlers::patients::require_active_patient;
+use crate::handlers::{can_access_patient, AppState};
+use crate::middleware::AuthenticatedUser;
+use crate::models::*;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+
+// Mounted under /api/admin
+crate::routes::route_registry! {
+    "/devices" {
+        GET => list_devices, Jwt, ["admin", "device_manager"];
+        POST => register_device, Jwt, ["admin", "device_manager"];
+    }
+    "/devices/{device_id}" {
+        PATCH => update_device, Jwt, ["admin", "device_manager"];
+        DELETE => deactivate_device, Jwt, ["admin", "device_manager"];
+    }
+    "/devices/{device_id}/patient" {
+        PUT => assign_device, Jwt, ["admin", "device_manager"];
+        DELETE => unassign_device, Jwt, ["admin", "device_manager"];
+    }
+}
+
+const DEVICE_SQL: &str =
+    "SELECT id, device_id, device_name, is_active, patient_id, organization_id, metadata, created_at, last_seen_at,
+            secret_issued_at, units
+     FROM devices";
+
+/// A walker of the caller's organization; other organizations' walkers are not found
+async fn load_device(pool: &PgPool, claims: &Claims, device_id: &str) -> Result<DeviceRecord, ApiError> {
+    sqlx::query_as(&format!("{} WHERE device_id = $1 AND ($2::uuid IS NULL OR organization_id = $2)", DEVICE_SQL))
+        .bind(device_id)
+        .bind(claims.org)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Device not found".into()))
+}
+
+/// The fleet of the caller's organization (every walker for users outside one), newest first
+pub async fn list_devices(
+    user: AuthenticatedUser,
+    state: web::Data<AppState>,
+    query: web::Query<DeviceListQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let devices: Vec<DeviceRecord> = sqlx::query_as(&format!(
+        "{} WHERE ($1::boolean IS NULL OR is_active = $1) AND ($2::uuid IS NULL OR organization_id = $2)
+         ORDER BY created_at DESC",
+        DEVICE_SQL
+    ))
+    .bind(query.active)
+    .bind(user.org)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(devices))
+}
+
+/// Register a walker to the caller's organization and issue its secret, which is returned
+/// only in this response
+pub async fn register_device(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    body: web::Json<DeviceRegistration>,
+) -> Result<HttpResponse, ApiError> {
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    // The row id is chosen up front because the sealed secret is bound to it
+    let id = Uuid::new_v4();
+    let secret = generate_secret();
+    let sealed = state
+        .device_secrets
+        .seal(id, &secret)
+        .map_err(|e| ApiError::Unavailable(format!("Cannot issue device secrets: {}", e)))?;
+    let device: Option<DeviceRecord> = sqlx::query_as(
+        "INSERT INTO devices
+            (id, device_id, device_name, metadata, secret_hash, secret_ciphertext, secret_issued_at, organization_id, units)
+         VALUES ($1, $2, $3, COALESCE($4, '{}'::jsonb), $5, $6, now(), $7, COALESCE($8, '{}'::jsonb))
+         ON CONFLICT (device_id) DO NOTHING
+         RETURNING id, device_id, device_name, is_active, patient_id, organization_id, metadata, created_at,
+                   last_seen_at, secret_issued_at, units"
+    )
+    .bind(id)
+    .bind(&body.device_id)
+    .bind(body.device_name.trim())
+    .bind(&body.metadata)
+    .bind(hash_secret(&secret))
+    .bind(sealed)
+    .bind(claims.org)
+    .bind(body.units.map(sqlx::types::Json))
+    .fetch_optional(&state.pool)
+    .await?;
+    let device = device.ok_or_else(|| ApiError::Conflict("A device with this id is already registered".into()))?;
+
+    crate::audit_log!("device", "register", Some(claims.user_id), true, device.device_id);
+
+    Ok(HttpResponse::Created().json(ProvisionedDevice { device, secret }))
+}
+
+/// Rename, annotate, reactivate or set the units of a walker
+pub async fn update_device(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    body: web::Json<DeviceUpdate>,
+) -> Result<HttpResponse, ApiError> {
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let existing = load_device(&state.pool, &claims, &path.into_inner()).await?;
+
+    sqlx::query(
+        "UPDATE devices SET device_name = COALESCE($2, device_name), metadata = COALESCE($3, metadata),
+                            is_active = COALESCE($4, is_active), units = COALESCE($5, units)
+         WHERE id = $1"
+    )
+    .bind(existing.id)
+    .bind(body.device_name.as_deref().map(str::trim))
+    .bind(&body.metadata)
+    .bind(body.is_active)
+    .bind(body.units.map(sqlx::types::Json))
+    .execute(&state.pool)
+    .await?;
+
+    crate::audit_log!("device", "update", Some(claims.user_id), true, existing.device_id);
+
+    Ok(HttpResponse::Ok().json(load_device(&state.pool, &claims, &existing.device_id).await?))
+}
+
+/// Deactivate a walker: its requests are rejected from now on, while its readings and
+/// patient link are kept. `PATCH` with `is_active: true` brings it back.
+pub async fn deactivate_device(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let device = load_device(&state.pool, &claims, &path.into_inner()).await?;
+
+    sqlx::query("UPDATE devices SET is_active = false WHERE id = $1")
+        .bind(device.id)
+        .execute(&state.pool)
+        .await?;
+
+    crate::audit_log!("device", "deactivate", Some(claims.user_id), true, device.device_id);
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Point a walker at a patient: its readings are recorded and exported as theirs from now on,
+/// and its earlier readings count towards them too. The walker joins the patient's organization.
+pub async fn assign_device(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    body: web::Json<DeviceAssignment>,
+) -> Result<HttpResponse, ApiError> {
+    let device = load_device(&state.pool, &claims, &path.into_inner()).await?;
+    require_active_patient(&state.pool, body.patient_id).await?;
+    if !can_access_patient(&state, &claims, body.patient_id).await? {
+        return Err(ApiError::NotFound("Patient not found".into()));
+    }
+
+    sqlx::query(
+        "UPDATE devices SET patient_id = $2, organization_id = COALESCE(p.organization_id, devices.organization_id)
+         FROM patients p
+         WHERE devices.id = $1 AND p.id = $2"
+    )
+    .bind(device.id)
+    .bind(body.patient_id)
+    .execute(&state.pool)
+    .await?;
+    for patient_id in [Some(body.patient_id), device.patient_id].into_iter().flatten() {
+        aggregate_service::invalidate_all(&state.redis, patient_id).await;
+    }
+
+    crate::audit_log!("device", "assign", Some(claims.user_id), true, device.device_id);
+
+    Ok(HttpResponse::Ok().json(load_device(&state.pool, &claims, &device.device_id).await?))
+}
+
+/// Detach a walker from its patient; its readings are kept but no longer count towards anyone
+pub async fn unassign_device(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let device = load_device(&state.pool, &claims, &path.into_inner()).await?;
+    let Some(patient_id) = device.patient_id else {
+        return Err(ApiError::Conflict("Device is not assigned to a patient".into()));
+    };
+
+    sqlx::query("UPDATE devices SET patient_id = NULL WHERE id = $1")
+        .bind(device.id)
+        .execute(&state.pool)
+        .await?;
+    aggregate_service::invalidate_all(&state.redis, patient_id).await;
+
+    crate::audit_log!("device", "unassign", Some(claims.user_id), true, device.device_id);
+
+    Ok(HttpResponse::Ok().json(load_device(&state.pool, &claims, &device.device_id).await?))
+}
@@ -0,0 +1,190 @@
+use crate::api_version::ApiVersion;
+use crate::dead_letter::IngestSource;
+use crate::errors::ApiError;
+use crate::handlers::device::{ingest_vitals, normalize_for};
+use crate::handlers::{can_access_patient, AppState};
+use crate::middleware::AuthenticatedUser;
+use crate::models::*;
+use crate::negotiation::prefers_representation;
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use uuid::Uuid;
+use validator::Validate;
+
+crate::routes::route_registry! {
+    "/devices/{device_id}/gateways" {
+        GET => list_gateways, Jwt, [];
+        POST => request_gateway, Jwt, [];
+    }
+    "/devices/{device_id}/gateways/{id}" {
+        PUT => decide_gateway, Jwt, [];
+    }
+    "/gateway/vitals" {
+        POST => gateway_ingest, Jwt, [];
+    }
+}
+
+const GATEWAY_SQL: &str =
+    "SELECT g.id, d.device_id, g.user_id, u.email AS user_email, g.status, g.requested_at,
+            g.decided_at, g.decided_by, g.last_relay_at
+     FROM device_gateways g
+     JOIN devices d ON d.id = g.device_id
+     JOIN users u ON u.id = g.user_id";
+
+async fn load_device(state: &AppState, device_id: &str) -> Result<Device, ApiError> {
+    sqlx::query_as("SELECT * FROM devices WHERE device_id = $1 AND is_active = true")
+        .bind(device_id)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Device not found".into()))
+}
+
+/// The device's patient, when the caller cares for them
+async fn require_device_access(state: &AppState, claims: &Claims, device: &Device) -> Result<Uuid, ApiError> {
+    let patient_id = device
+        .patient_id
+        .ok_or_else(|| ApiError::Conflict("Device has not been claimed for a patient".into()))?;
+    if !can_access_patient(state, claims, patient_id).await? {
+        return Err(ApiError::Forbidden("Not a caregiver for this device's patient".into()));
+    }
+    Ok(patient_id)
+}
+
+async fn load_gateway(state: &AppState, id: Uuid) -> Result<DeviceGateway, ApiError> {
+    sqlx::query_as(&format!("{} WHERE g.id = $1", GATEWAY_SQL))
+        .bind(id)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Gateway not found".into()))
+}
+
+/// Ask to relay a walker's readings from the caller's phone.
+///
+/// The request stays pending until a caregiver of the device's patient approves it;
+/// asking again after a rejection or revocation reopens it.
+pub async fn request_gateway(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let device = load_device(&state, &path).await?;
+    if device.patient_id.is_none() {
+        return Err(ApiError::Conflict("Device has not been claimed for a patient".into()));
+    }
+
+    let id: Uuid = sqlx::query_scalar(
+        "INSERT INTO device_gateways (device_id, user_id) VALUES ($1, $2)
+         ON CONFLICT (device_id, user_id) DO UPDATE SET
+             status = CASE WHEN device_gateways.status = 'approved' THEN 'approved' ELSE 'pending' END,
+             requested_at = CASE WHEN device_gateways.status = 'approved' THEN device_gateways.requested_at ELSE now() END
+         RETURNING id"
+    )
+    .bind(device.id)
+    .bind(claims.user_id)
+    .fetch_one(&state.pool)
+    .await?;
+
+    crate::audit_log!("device_gateway", "request", Some(claims.user_id), true, id);
+
+    Ok(HttpResponse::Created().json(load_gateway(&state, id).await?))
+}
+
+/// Phones that have asked to, or may, relay for the device
+pub async fn list_gateways(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let device = load_device(&state, &path).await?;
+    require_device_access(&state, &claims, &device).await?;
+
+    let gateways: Vec<DeviceGateway> = sqlx::query_as(&format!("{} WHERE g.device_id = $1 ORDER BY g.requested_at DESC", GATEWAY_SQL))
+        .bind(device.id)
+        .fetch_all(&state.pool)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(gateways))
+}
+
+/// Approve or reject a pending request, or revoke an approved phone
+pub async fn decide_gateway(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<(String, Uuid)>,
+    body: web::Json<GatewayDecisionRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let (device_id, id) = path.into_inner();
+    let device = load_device(&state, &device_id).await?;
+    require_device_access(&state, &claims, &device).await?;
+
+    let allowed_from: &[&str] = match body.status.as_str() {
+        "approved" | "rejected" => &["pending"],
+        "revoked" => &["approved"],
+        other => return Err(ApiError::BadRequest(format!("Unsupported gateway status '{}'", other))),
+    };
+
+    let current: String = sqlx::query_scalar("SELECT status FROM device_gateways WHERE id = $1 AND device_id = $2")
+        .bind(id)
+        .bind(device.id)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Gateway not found".into()))?;
+    if !allowed_from.contains(&current.as_str()) {
+        return Err(ApiError::Conflict(format!("Gateway is {} and cannot become {}", current, body.status)));
+    }
+
+    let updated = sqlx::query(
+        "UPDATE device_gateways SET status = $3, decided_at = now(), decided_by = $4
+         WHERE id = $1 AND status = $2"
+    )
+    .bind(id)
+    .bind(&current)
+    .bind(&body.status)
+    .bind(claims.user_id)
+    .execute(&state.pool)
+    .await?;
+    if updated.rows_affected() == 0 {
+        return Err(ApiError::Conflict("Gateway was changed concurrently".into()));
+    }
+
+    crate::audit_log!("device_gateway", &body.status, Some(claims.user_id), true, id);
+
+    Ok(HttpResponse::Ok().json(load_gateway(&state, id).await?))
+}
+
+/// A reading relayed by an approved phone, authenticated by its user's JWT instead of the
+/// walker's HMAC signature. Stored with `relayed` provenance.
+pub async fn gateway_ingest(
+    req: HttpRequest,
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    body: web::Json<GatewayVitalsIngest>,
+) -> Result<HttpResponse, ApiError> {
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let return_vitals = prefers_representation(&req).then(|| ApiVersion::from_request(&req)).transpose()?;
+
+    let GatewayVitalsIngest { device_id, reading } = body.into_inner();
+    let device = load_device(&state, &device_id).await?;
+    let relayed = sqlx::query(
+        "UPDATE device_gateways SET last_relay_at = now()
+         WHERE device_id = $1 AND user_id = $2 AND status = 'approved'"
+    )
+    .bind(device.id)
+    .bind(claims.user_id)
+    .execute(&state.pool)
+    .await?;
+    if relayed.rows_affected() == 0 {
+        return Err(ApiError::Forbidden("This account is not an approved gateway for the device".into()));
+    }
+
+    // Only readings from approved phones reach the dead-letter queue
+    let source = IngestSource::relayed(claims.user_id);
+    let reading = normalize_for(&state, &device, reading, &source).await?;
+
+    // Phones may buffer readings while offline, so only readings from the future are refused
+    if reading.vitals.timestamp > Utc::now().timestamp() + state.replay_window_seconds {
+        return Err(ApiError::BadRequest("Reading timestamp is in the future".into()));
+    }
+
+    Ok(ingest_vitals(&state, &device, &reading, &source, return_vitals).await)
+}
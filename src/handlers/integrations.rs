@@ -0,0 +1,232 @@
+use crate::errors::ApiError;
+use crate::handlers::AppState;
+use crate::integrations::{check_url, record_test, test_connection};
+use crate::middleware::AuthenticatedUser;
+use crate::models::*;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+
+// Mounted under /api/admin
+crate::routes::route_registry! {
+    "/integrations" {
+        GET => list_integrations, Jwt, ["admin"];
+        POST => create_integration, Jwt, ["admin"];
+    }
+    "/integrations/{id}" {
+        GET => get_integration, Jwt, ["admin"];
+        PUT => update_integration, Jwt, ["admin"];
+        DELETE => delete_integration, Jwt, ["admin"];
+    }
+    "/integrations/{id}/test" {
+        POST => test_integration, Jwt, ["admin"];
+    }
+}
+
+const INTEGRATION_SQL: &str = "SELECT *, secret IS NOT NULL AS has_secret FROM integrations";
+
+/// An integration of the caller's organization; others are not found
+async fn load_integration(pool: &PgPool, claims: &Claims, id: Uuid) -> Result<Integration, ApiError> {
+    sqlx::query_as(&format!(
+        "{} WHERE id = $1 AND ($2::uuid IS NULL OR organization_id = $2)",
+        INTEGRATION_SQL
+    ))
+    .bind(id)
+    .bind(claims.org)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Integration not found".into()))
+}
+
+fn check_request(body: &IntegrationRequest) -> Result<(), ApiError> {
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    if body.name.trim().is_empty() {
+        return Err(ApiError::BadRequest("name is required".into()));
+    }
+    check_url(body.kind, body.url.trim()).map_err(ApiError::BadRequest)?;
+    if body.username.is_some() && body.kind != IntegrationKind::Fhir {
+        return Err(ApiError::BadRequest("username: only FHIR servers take a user name".into()));
+    }
+    if !body.settings.events.is_empty() && body.kind != IntegrationKind::Webhook {
+        return Err(ApiError::BadRequest("settings.events: only webhooks are sent events".into()));
+    }
+    Ok(())
+}
+
+/// Seal a new secret under the organization's keys; `""` means none
+async fn seal_secret(state: &AppState, organization_id: Uuid, secret: &str) -> Result<Option<String>, ApiError> {
+    if secret.is_empty() {
+        return Ok(None);
+    }
+    let sealed = state.phi.seal_for_organization(organization_id, secret).await.map_err(|e| {
+        tracing::error!("Failed to encrypt integration secret: {:#}", e);
+        ApiError::Internal("Failed to store integration".into())
+    })?;
+    Ok(Some(sealed))
+}
+
+fn name_taken(e: sqlx::Error) -> ApiError {
+    match &e {
+        sqlx::Error::Database(db) if db.is_unique_violation() => {
+            ApiError::Conflict("An integration of this kind with this name already exists".into())
+        }
+        _ => e.into(),
+    }
+}
+
+/// Integrations of the caller's organization; administrators not tied to one see them all,
+/// or one organization's with `?organization_id=`
+pub async fn list_integrations(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    query: web::Query<IntegrationQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let integrations: Vec<Integration> = sqlx::query_as(&format!(
+        "{} WHERE ($1::uuid IS NULL OR organization_id = $1) AND ($2::text IS NULL OR kind = $2)
+         ORDER BY organization_id, kind, name",
+        INTEGRATION_SQL
+    ))
+    .bind(claims.org.or(query.organization_id))
+    .bind(query.kind)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(integrations))
+}
+
+pub async fn get_integration(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(load_integration(&state.pool, &claims, path.into_inner()).await?))
+}
+
+pub async fn create_integration(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    body: web::Json<IntegrationRequest>,
+) -> Result<HttpResponse, ApiError> {
+    check_request(&body)?;
+    // Administrators of one organization configure only its own integrations
+    let organization_id = match (claims.org, body.organization_id) {
+        (Some(own), Some(requested)) if own != requested => {
+            return Err(ApiError::Forbidden("Cannot configure integrations of another organization".into()))
+        }
+        (Some(own), _) => own,
+        (None, Some(requested)) => requested,
+        (None, None) => return Err(ApiError::BadRequest("organization_id is required".into())),
+    };
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM organizations WHERE id = $1)")
+        .bind(organization_id)
+        .fetch_one(&state.pool)
+        .await?;
+    if !exists {
+        return Err(ApiError::NotFound("Organization not found".into()));
+    }
+
+    let secret = seal_secret(&state, organization_id, body.secret.as_deref().unwrap_or_default()).await?;
+    let id: Uuid = sqlx::query_scalar(
+        "INSERT INTO integrations (organization_id, kind, name, url, username, secret, settings, enabled, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+         RETURNING id"
+    )
+    .bind(organization_id)
+    .bind(body.kind)
+    .bind(body.name.trim())
+    .bind(body.url.trim())
+    .bind(&body.username)
+    .bind(secret)
+    .bind(sqlx::types::Json(&body.settings))
+    .bind(body.enabled)
+    .bind(claims.user_id)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(name_taken)?;
+
+    crate::audit_log!("integration", "create", Some(claims.user_id), true, id);
+    Ok(HttpResponse::Created().json(load_integration(&state.pool, &claims, id).await?))
+}
+
+/// Replace an integration's configuration. The stored secret is kept unless one is sent,
+/// and the last test result is cleared, as it tested something else.
+pub async fn update_integration(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Json<IntegrationRequest>,
+) -> Result<HttpResponse, ApiError> {
+    check_request(&body)?;
+    let existing = load_integration(&state.pool, &claims, path.into_inner()).await?;
+    let secret = match body.secret.as_deref() {
+        Some(secret) => Some(seal_secret(&state, existing.organization_id, secret).await?),
+        None => None,
+    };
+
+    sqlx::query(
+        "UPDATE integrations
+         SET kind = $2, name = $3, url = $4, username = $5, secret = CASE WHEN $6 THEN $7 ELSE secret END,
+             settings = $8, enabled = $9, last_tested_at = NULL, last_test_ok = NULL, last_test_error = NULL,
+             updated_at = now()
+         WHERE id = $1"
+    )
+    .bind(existing.id)
+    .bind(body.kind)
+    .bind(body.name.trim())
+    .bind(body.url.trim())
+    .bind(&body.username)
+    .bind(secret.is_some())
+    .bind(secret.flatten())
+    .bind(sqlx::types::Json(&body.settings))
+    .bind(body.enabled)
+    .execute(&state.pool)
+    .await
+    .map_err(name_taken)?;
+
+    crate::audit_log!("integration", "update", Some(claims.user_id), true, existing.id);
+    Ok(HttpResponse::Ok().json(load_integration(&state.pool, &claims, existing.id).await?))
+}
+
+pub async fn delete_integration(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let integration = load_integration(&state.pool, &claims, path.into_inner()).await?;
+    sqlx::query("DELETE FROM integrations WHERE id = $1")
+        .bind(integration.id)
+        .execute(&state.pool)
+        .await?;
+
+    crate::audit_log!("integration", "delete", Some(claims.user_id), true, integration.id);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Try the integration with its stored settings and credentials, disabled or not. Answers
+/// 200 with what was found either way; the outcome is kept on the integration. Admins tied to
+/// an organization cannot reach this host or a private network.
+pub async fn test_integration(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let integration = load_integration(&state.pool, &claims, path.into_inner()).await?;
+    let secret = match &integration.secret {
+        Some(sealed) => match state.phi.open(sealed).await {
+            Ok(Some(secret)) => Some(secret),
+            Ok(None) => return Err(ApiError::Conflict("The secret's keys were destroyed; set a new secret".into())),
+            Err(e) => {
+                tracing::error!(integration_id = %integration.id, "Failed to decrypt integration secret: {:#}", e);
+                return Err(ApiError::Internal("Failed to read integration secret".into()));
+            }
+        },
+        None => None,
+    };
+
+    let result = test_connection(&integration, secret.as_deref(), claims.org.is_none()).await;
+    record_test(&state.pool, integration.id, &result).await?;
+
+    crate::audit_log!("integration", "test", Some(claims.user_id), result.ok, integration.id);
+    Ok(HttpResponse::Ok().json(result))
+}
@@ -0,0 +1,282 @@
+use crate::errors::ApiError;
+use crate::handlers::{can_access_patient, AppState};
+use crate::legal_hold::{self, CustodyActor};
+use crate::middleware::{authenticate_request, AuthenticatedUser};
+use crate::models::*;
+use crate::usage_service::{self, UsageMetric};
+use actix_web::{web, HttpRequest, HttpResponse};
+use uuid::Uuid;
+use validator::Validate;
+
+crate::routes::route_registry! {
+    "/patients/{patient_id}/legal-hold" {
+        GET => get_hold, Jwt, ["admin"];
+        POST => place_hold, Jwt, ["admin"];
+        DELETE => release_hold, Jwt, ["admin"];
+    }
+    "/legal-holds/{id}/exports" {
+        GET => list_exports, Jwt, ["admin"];
+        POST => create_export, Jwt, ["admin"];
+    }
+    "/legal-hold-exports/{id}" {
+        GET => get_export, Jwt, ["admin"];
+    }
+    "/legal-hold-exports/{id}/archive" {
+        GET => download_archive, Jwt, ["admin"];
+    }
+    "/legal-hold-exports/{id}/verify" {
+        POST => verify_export, Jwt, ["admin"];
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct CustodyNoteQuery {
+    /// Why the archive is being accessed, kept in the custody log
+    pub note: Option<String>,
+}
+
+/// Holds on patients of another organization are not found
+async fn require_in_scope(state: &AppState, claims: &Claims, patient_id: Uuid) -> Result<(), ApiError> {
+    if !can_access_patient(state, claims, patient_id).await? {
+        return Err(ApiError::NotFound("Patient not found".into()));
+    }
+    Ok(())
+}
+
+/// The administrator acting on the patient's hold, for the custody log. Legal hold routes are
+/// admin-only in the registry, whatever the deployment mode.
+async fn custody_actor(req: &HttpRequest, state: &AppState, patient_id: Uuid) -> Result<CustodyActor, ApiError> {
+    let claims = authenticate_request(req).await?;
+    require_in_scope(state, &claims, patient_id).await?;
+    Ok(CustodyActor {
+        id: claims.user_id,
+        email: claims.sub,
+        ip: req.peer_addr().map(|addr| addr.ip().to_string()),
+    })
+}
+
+async fn active_hold(state: &AppState, patient_id: Uuid) -> Result<Option<LegalHold>, ApiError> {
+    Ok(
+        sqlx::query_as("SELECT * FROM legal_holds WHERE patient_id = $1 AND released_at IS NULL")
+            .bind(patient_id)
+            .fetch_optional(&state.pool)
+            .await?,
+    )
+}
+
+async fn load_export(state: &AppState, id: Uuid) -> Result<LegalHoldExport, ApiError> {
+    legal_hold::load_export(&state.pool, id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Export not found".into()))
+}
+
+// ============ Holds ============
+
+pub async fn get_hold(
+    admin: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    require_in_scope(&state, &admin, patient_id).await?;
+    let hold = active_hold(&state, patient_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Patient is not under legal hold".into()))?;
+
+    Ok(HttpResponse::Ok().json(hold))
+}
+
+/// Freeze the patient's record: retention purges skip it and the patient cannot be deleted
+pub async fn place_hold(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Json<LegalHoldRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    let actor = custody_actor(&req, &state, patient_id).await?;
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM patients WHERE id = $1)")
+        .bind(patient_id)
+        .fetch_one(&state.pool)
+        .await?;
+    if !exists {
+        return Err(ApiError::NotFound("Patient not found".into()));
+    }
+
+    let hold: Option<LegalHold> = sqlx::query_as(
+        "INSERT INTO legal_holds (patient_id, reason, matter_reference, placed_by)
+         VALUES ($1, $2, $3, $4)
+         ON CONFLICT (patient_id) WHERE released_at IS NULL DO NOTHING
+         RETURNING *"
+    )
+    .bind(patient_id)
+    .bind(body.reason.trim())
+    .bind(&body.matter_reference)
+    .bind(actor.id)
+    .fetch_optional(&state.pool)
+    .await?;
+    let hold = hold.ok_or_else(|| ApiError::Conflict("Patient is already under legal hold".into()))?;
+
+    crate::audit_log!("legal_hold", "place", Some(actor.id), true, hold.id);
+
+    Ok(HttpResponse::Created().json(hold))
+}
+
+/// Lift the active hold; its exports and custody logs are kept
+pub async fn release_hold(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    let actor = custody_actor(&req, &state, patient_id).await?;
+
+    let hold: LegalHold = sqlx::query_as(
+        "UPDATE legal_holds SET released_at = now(), released_by = $2
+         WHERE patient_id = $1 AND released_at IS NULL
+         RETURNING *"
+    )
+    .bind(patient_id)
+    .bind(actor.id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Patient is not under legal hold".into()))?;
+
+    crate::audit_log!("legal_hold", "release", Some(actor.id), true, hold.id);
+
+    Ok(HttpResponse::Ok().json(hold))
+}
+
+// ============ Exports ============
+
+pub async fn list_exports(
+    admin: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let hold_id = path.into_inner();
+    let patient_id: Uuid = sqlx::query_scalar("SELECT patient_id FROM legal_holds WHERE id = $1")
+        .bind(hold_id)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Legal hold not found".into()))?;
+    require_in_scope(&state, &admin, patient_id).await?;
+
+    let exports: Vec<LegalHoldExport> = sqlx::query_as(&format!(
+        "SELECT {} FROM legal_hold_exports WHERE hold_id = $1 ORDER BY created_at",
+        legal_hold::EXPORT_COLUMNS
+    ))
+    .bind(hold_id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(exports))
+}
+
+/// Produce a signed, timestamped archive of everything held for the patient
+pub async fn create_export(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let key = state
+        .retention
+        .export_signing_key
+        .as_deref()
+        .ok_or_else(|| ApiError::Unavailable("Legal hold exports require retention.export_signing_key".into()))?;
+
+    let hold: LegalHold = sqlx::query_as("SELECT * FROM legal_holds WHERE id = $1")
+        .bind(path.into_inner())
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Legal hold not found".into()))?;
+    let actor = custody_actor(&req, &state, hold.patient_id).await?;
+    if hold.released_at.is_some() {
+        return Err(ApiError::Conflict("Legal hold has been released".into()));
+    }
+
+    let export = legal_hold::create_export(&state.pool, &state.phi, key, &hold, &actor).await?;
+    usage_service::record_in_background(&state.pool, Some(hold.patient_id), UsageMetric::ExportsGenerated, 1);
+
+    crate::audit_log!("legal_hold", "export", Some(actor.id), true, export.id);
+
+    Ok(HttpResponse::Created().json(export))
+}
+
+pub async fn get_export(
+    admin: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let export = load_export(&state, path.into_inner()).await?;
+    require_in_scope(&state, &admin, export.patient_id).await?;
+    let custody = legal_hold::load_custody(&state.pool, export.id).await?;
+
+    Ok(HttpResponse::Ok().json(LegalHoldExportWithCustody { export, custody }))
+}
+
+/// Serve the stored archive bytes; every download is entered in the custody log
+pub async fn download_archive(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<CustodyNoteQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let export = load_export(&state, path.into_inner()).await?;
+    let actor = custody_actor(&req, &state, export.patient_id).await?;
+    let archive = legal_hold::load_archive(&state.pool, export.id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Export not found".into()))?;
+
+    let note = query.into_inner().note.filter(|note| !note.trim().is_empty());
+    legal_hold::record_custody_event(&state.pool, export.id, "downloaded", &actor, note).await?;
+
+    crate::audit_log!("legal_hold", "download", Some(actor.id), true, export.id);
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"legal-hold-{}.json\"", export.id),
+        ))
+        .insert_header(("X-Archive-SHA256", export.sha256))
+        .insert_header(("X-Archive-Signature", export.signature))
+        .body(archive))
+}
+
+/// Re-check the stored archive against its hash and signature, and the custody chain
+pub async fn verify_export(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let key = state
+        .retention
+        .export_signing_key
+        .as_deref()
+        .ok_or_else(|| ApiError::Unavailable("Legal hold exports require retention.export_signing_key".into()))?;
+    let export = load_export(&state, path.into_inner()).await?;
+    let actor = custody_actor(&req, &state, export.patient_id).await?;
+    let archive = legal_hold::load_archive(&state.pool, export.id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Export not found".into()))?;
+    let custody = legal_hold::load_custody(&state.pool, export.id).await?;
+
+    let verification = ExportVerification {
+        export_id: export.id,
+        archive_intact: legal_hold::sha256_hex(&archive) == export.sha256,
+        signature_valid: legal_hold::verify_signature(key, &archive, &export.signature),
+        custody_chain_intact: legal_hold::custody_chain_intact(&custody),
+    };
+
+    let outcome = if verification.archive_intact && verification.signature_valid && verification.custody_chain_intact {
+        "all checks passed"
+    } else {
+        "verification FAILED"
+    };
+    legal_hold::record_custody_event(&state.pool, export.id, "verified", &actor, Some(outcome.to_string())).await?;
+
+    Ok(HttpResponse::Ok().json(verification))
+}
@@ -0,0 +1,232 @@
+use crate::errors::ApiError;
+use crate::handlers::patients::require_active_patient;
+use crate::handlers::{can_access_patient, can_manage_care, AppState};
+use crate::medication_service::adherence;
+use crate::middleware::AuthenticatedUser;
+use crate::models::*;
+use actix_web::{web, HttpResponse};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use validator::Validate;
+
+crate::routes::route_registry! {
+    "/patients/{patient_id}/medications" {
+        GET => list_medications, Jwt, [];
+        POST => create_medication, Jwt, [];
+    }
+    "/medications/{id}" {
+        DELETE => stop_medication, Jwt, [];
+    }
+    "/patients/{patient_id}/medication-doses" {
+        GET => list_doses, Jwt, [];
+    }
+    "/medication-doses/{id}/confirm" {
+        POST => confirm_dose, Jwt, [];
+    }
+    "/patients/{patient_id}/adherence" {
+        GET => get_adherence, Jwt, [];
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct WindowQuery {
+    pub days: Option<i64>,
+}
+
+async fn check_patient_access(state: &AppState, claims: &Claims, patient_id: Uuid) -> Result<(), ApiError> {
+    if !can_access_patient(state, claims, patient_id).await? {
+        return Err(ApiError::Forbidden("Not a caregiver for this patient".into()));
+    }
+    Ok(())
+}
+
+// ============ Schedules ============
+
+pub async fn list_medications(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    check_patient_access(&state, &claims, patient_id).await?;
+
+    let medications: Vec<Medication> = sqlx::query_as(
+        "SELECT * FROM medications WHERE patient_id = $1 ORDER BY active DESC, name"
+    )
+    .bind(patient_id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(medications))
+}
+
+pub async fn create_medication(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Json<MedicationRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+
+    if !can_manage_care(&state, &claims, Some(patient_id)).await? {
+        return Err(ApiError::Forbidden("Clinician role required".into()));
+    }
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    if let (Some(start), Some(end)) = (body.start_date, body.end_date) {
+        if end < start {
+            return Err(ApiError::BadRequest("end_date must not be before start_date".into()));
+        }
+    }
+
+    require_active_patient(&state.pool, patient_id).await?;
+    check_patient_access(&state, &claims, patient_id).await?;
+
+    let mut times = body.times.clone();
+    times.sort();
+    times.dedup();
+
+    let medication: Medication = sqlx::query_as(
+        "INSERT INTO medications (patient_id, name, dosage, instructions, times, start_date, end_date, created_by)
+         VALUES ($1, $2, $3, $4, $5, COALESCE($6, CURRENT_DATE), $7, $8)
+         RETURNING *"
+    )
+    .bind(patient_id)
+    .bind(body.name.trim())
+    .bind(body.dosage.trim())
+    .bind(&body.instructions)
+    .bind(&times)
+    .bind(body.start_date)
+    .bind(body.end_date)
+    .bind(claims.user_id)
+    .fetch_one(&state.pool)
+    .await?;
+
+    crate::audit_log!("medication", "create", Some(claims.user_id), true, medication.id);
+
+    Ok(HttpResponse::Created().json(medication))
+}
+
+/// Deactivate a schedule and drop its future pending doses; history is kept
+pub async fn stop_medication(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let medication: Medication = sqlx::query_as("SELECT * FROM medications WHERE id = $1")
+        .bind(path.into_inner())
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Medication not found".into()))?;
+    check_patient_access(&state, &claims, medication.patient_id).await?;
+    if !can_manage_care(&state, &claims, Some(medication.patient_id)).await? {
+        return Err(ApiError::Forbidden("Clinician role required".into()));
+    }
+
+    let mut tx = state.pool.begin().await?;
+    sqlx::query("UPDATE medications SET active = false WHERE id = $1")
+        .bind(medication.id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM medication_doses WHERE medication_id = $1 AND status = 'pending' AND scheduled_at > now()")
+        .bind(medication.id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    crate::audit_log!("medication", "stop", Some(claims.user_id), true, medication.id);
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// ============ Doses & Adherence ============
+
+/// Doses scheduled in the last `days` (default 7, max 90) plus the upcoming day
+pub async fn list_doses(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<WindowQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    check_patient_access(&state, &claims, patient_id).await?;
+
+    let since = Utc::now() - Duration::days(query.days.unwrap_or(7).clamp(1, 90));
+
+    let doses: Vec<MedicationDose> = sqlx::query_as(
+        "SELECT d.* FROM medication_doses d
+         JOIN medications m ON m.id = d.medication_id
+         WHERE m.patient_id = $1 AND d.scheduled_at >= $2
+         ORDER BY d.scheduled_at DESC"
+    )
+    .bind(patient_id)
+    .bind(since)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(doses))
+}
+
+/// Record that a caregiver gave (or deliberately skipped) a dose
+pub async fn confirm_dose(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Json<DoseConfirmRequest>,
+) -> Result<HttpResponse, ApiError> {
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    let row: Option<(Uuid, Uuid)> = sqlx::query_as(
+        "SELECT d.id, m.patient_id FROM medication_doses d
+         JOIN medications m ON m.id = d.medication_id WHERE d.id = $1"
+    )
+    .bind(path.into_inner())
+    .fetch_optional(&state.pool)
+    .await?;
+    let (dose_id, patient_id) = row.ok_or_else(|| ApiError::NotFound("Dose not found".into()))?;
+    check_patient_access(&state, &claims, patient_id).await?;
+
+    // Late confirmation of a missed dose is allowed; confirmed doses are final
+    let dose: MedicationDose = sqlx::query_as(
+        "UPDATE medication_doses
+         SET status = $2, note = $3, confirmed_by = $4, confirmed_at = now()
+         WHERE id = $1 AND status IN ('pending', 'missed')
+         RETURNING *"
+    )
+    .bind(dose_id)
+    .bind(&body.status)
+    .bind(&body.note)
+    .bind(claims.user_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| ApiError::Conflict("Dose already confirmed".into()))?;
+
+    crate::audit_log!("medication", "confirm_dose", Some(claims.user_id), true, patient_id);
+
+    Ok(HttpResponse::Ok().json(dose))
+}
+
+/// Adherence over the last `days` (default 30, max 365)
+pub async fn get_adherence(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<WindowQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    check_patient_access(&state, &claims, patient_id).await?;
+
+    let since = Utc::now() - Duration::days(query.days.unwrap_or(30).clamp(1, 365));
+
+    let counts: Vec<(String, i64)> = sqlx::query_as(
+        "SELECT d.status, COUNT(*) FROM medication_doses d
+         JOIN medications m ON m.id = d.medication_id
+         WHERE m.patient_id = $1 AND d.scheduled_at >= $2 AND d.scheduled_at <= now()
+         GROUP BY d.status"
+    )
+    .bind(patient_id)
+    .bind(since)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(adherence(&counts)))
+}
@@ -0,0 +1,212 @@
+use crate::errors::ApiError;
+use crate::handlers::patients::require_patient_access;
+use crate::handlers::{require_unscoped, AppState};
+use crate::middleware::AuthenticatedUser;
+use crate::ml_service::{anomaly_labels, heatmap_rows};
+use crate::models::*;
+use crate::query_debug;
+use crate::rule_dsl::metric_name;
+use actix_web::{web, HttpResponse};
+use chrono::{Duration, NaiveDate, Utc};
+use uuid::Uuid;
+use validator::Validate;
+
+crate::routes::route_registry! {
+    "/ml/heatmap" {
+        GET => get_heatmap, Jwt, [];
+    }
+    "/ml/rules" {
+        GET => list_rules, Jwt, ["admin", "clinician"];
+        POST => create_rule, Jwt, ["admin"];
+    }
+    "/ml/rules/{id}" {
+        PUT => update_rule, Jwt, ["admin"];
+        DELETE => delete_rule, Jwt, ["admin"];
+    }
+}
+
+const DEFAULT_HEATMAP_DAYS: u32 = 14;
+const MAX_HEATMAP_DAYS: u32 = 90;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct HeatmapQuery {
+    pub patient_id: Uuid,
+    /// `heart_rate`, `spo2`, `temperature`; omitted = any anomaly
+    pub metric: Option<String>,
+    /// Column width such as `1h` or `3h`; must divide the day evenly
+    pub bucket: Option<String>,
+    pub days: Option<u32>,
+}
+
+fn parse_bucket(bucket: Option<&str>) -> Result<u32, ApiError> {
+    let Some(bucket) = bucket else { return Ok(1) };
+    bucket
+        .strip_suffix('h')
+        .and_then(|hours| hours.parse::<u32>().ok())
+        .filter(|hours| *hours > 0 && 24 % hours == 0)
+        .ok_or_else(|| ApiError::BadRequest(format!("bucket '{}' must be 1h, 2h, 3h, 4h, 6h, 8h, 12h or 24h", bucket)))
+}
+
+/// Anomaly counts per UTC day × hour-of-day bucket, for spotting recurring patterns
+/// such as nocturnal desaturation
+pub async fn get_heatmap(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    query: web::Query<HeatmapQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let query = query.into_inner();
+    require_patient_access(&state, &claims, query.patient_id).await?;
+
+    let metric = query.metric.unwrap_or_else(|| "any".to_string());
+    let labels: Option<Vec<String>> = match metric.as_str() {
+        "any" => None,
+        other => Some(
+            anomaly_labels(other)
+                .ok_or_else(|| ApiError::BadRequest(format!("Unknown metric '{}'", other)))?
+                .iter()
+                .map(|label| label.to_string())
+                .collect(),
+        ),
+    };
+    let bucket_hours = parse_bucket(query.bucket.as_deref())?;
+    let days = query.days.unwrap_or(DEFAULT_HEATMAP_DAYS);
+    if !(1..=MAX_HEATMAP_DAYS).contains(&days) {
+        return Err(ApiError::BadRequest(format!("days must be between 1 and {}", MAX_HEATMAP_DAYS)));
+    }
+
+    let start = Utc::now().date_naive() - Duration::days(i64::from(days) - 1);
+    let cells: Vec<(NaiveDate, i32, i64)> = query_debug::fetch_all(
+        &state.reporting_pool,
+        &state.query_debug,
+        "anomaly_heatmap",
+        "SELECT (r.reading_timestamp AT TIME ZONE 'UTC')::date AS day,
+                EXTRACT(HOUR FROM r.reading_timestamp AT TIME ZONE 'UTC')::int / $4 AS bucket,
+                COUNT(*) AS anomalies
+         FROM ml_analysis a
+         JOIN sensor_readings r ON r.id = a.sensor_reading_id
+         JOIN devices d ON d.id = r.device_id
+         WHERE d.patient_id = $1
+           AND r.reading_timestamp >= $2::date::timestamp AT TIME ZONE 'UTC'
+           AND a.anomaly_detected
+           AND ($3::text[] IS NULL OR a.analysis_details->'anomalies' ?| $3)
+         GROUP BY 1, 2",
+        || crate::pg_args![query.patient_id, start, labels.clone(), bucket_hours as i32],
+    )
+    .await?;
+
+    let rows = heatmap_rows(start, days, bucket_hours, &cells);
+    let max_count = rows.iter().flat_map(|row| row.counts.iter().copied()).max().unwrap_or(0);
+
+    Ok(HttpResponse::Ok().json(AnomalyHeatmap {
+        patient_id: query.patient_id,
+        metric,
+        bucket_hours,
+        days,
+        buckets: (0..24 / bucket_hours).map(|i| format!("{:02}:00", i * bucket_hours)).collect(),
+        rows,
+        max_count,
+    }))
+}
+
+/// Every admin-defined rule, disabled ones included
+pub async fn list_rules(_user: AuthenticatedUser, state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let rules: Vec<MlRule> = sqlx::query_as("SELECT * FROM ml_rules ORDER BY created_at, id")
+        .fetch_all(&state.pool)
+        .await?;
+    Ok(HttpResponse::Ok().json(rules))
+}
+
+pub async fn create_rule(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    body: web::Json<MlRuleRequest>,
+) -> Result<HttpResponse, ApiError> {
+    require_unscoped(&claims)?;
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    let rule: Option<MlRule> = sqlx::query_as(
+        "INSERT INTO ml_rules (name, metric, comparator, value, duration_seconds, alert_level, enabled, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+         ON CONFLICT (name) DO NOTHING
+         RETURNING *"
+    )
+    .bind(body.name.trim())
+    .bind(metric_name(body.metric))
+    .bind(&body.comparator)
+    .bind(body.value)
+    .bind(body.duration_seconds)
+    .bind(body.alert_level)
+    .bind(body.enabled)
+    .bind(claims.user_id)
+    .fetch_optional(&state.pool)
+    .await?;
+    let rule = rule.ok_or_else(|| ApiError::Conflict("An ML rule with this name already exists".into()))?;
+
+    state.ml_service.refresh_custom_rules(&state.pool).await?;
+    crate::audit_log!("ml_rule", "create", Some(claims.user_id), true, rule.id);
+
+    Ok(HttpResponse::Created().json(rule))
+}
+
+pub async fn update_rule(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Json<MlRuleRequest>,
+) -> Result<HttpResponse, ApiError> {
+    require_unscoped(&claims)?;
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    let taken: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM ml_rules WHERE name = $1 AND id <> $2)")
+        .bind(body.name.trim())
+        .bind(*path)
+        .fetch_one(&state.pool)
+        .await?;
+    if taken {
+        return Err(ApiError::Conflict("An ML rule with this name already exists".into()));
+    }
+
+    let rule: MlRule = sqlx::query_as(
+        "UPDATE ml_rules
+         SET name = $2, metric = $3, comparator = $4, value = $5, duration_seconds = $6, alert_level = $7,
+             enabled = $8, updated_at = now()
+         WHERE id = $1
+         RETURNING *"
+    )
+    .bind(*path)
+    .bind(body.name.trim())
+    .bind(metric_name(body.metric))
+    .bind(&body.comparator)
+    .bind(body.value)
+    .bind(body.duration_seconds)
+    .bind(body.alert_level)
+    .bind(body.enabled)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("ML rule not found".into()))?;
+
+    state.ml_service.refresh_custom_rules(&state.pool).await?;
+    crate::audit_log!("ml_rule", "update", Some(claims.user_id), true, rule.id);
+
+    Ok(HttpResponse::Ok().json(rule))
+}
+
+pub async fn delete_rule(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    require_unscoped(&claims)?;
+    let deleted = sqlx::query("DELETE FROM ml_rules WHERE id = $1")
+        .bind(*path)
+        .execute(&state.pool)
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("ML rule not found".into()));
+    }
+
+    state.ml_service.refresh_custom_rules(&state.pool).await?;
+    crate::audit_log!("ml_rule", "delete", Some(claims.user_id), true, *path);
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,211 @@
+use crate::auth::{basic_credentials_match, JwtAuth};
+use crate::build_info::build_info;
+use crate::config::{
+    CorsConfig, DeploymentConfig, LockoutConfig, ObservabilityConfig, QueryDebugConfig, QuotaConfig, RateLimitConfig,
+    ReportingConfig, RetentionConfig, SloConfig, VoiceConfig,
+};
+use crate::device_secrets::DeviceSecrets;
+use crate::errors::ApiError;
+use crate::fhir_service::FhirService;
+use crate::metrics::{self, DB_CONNECTIONS_ACTIVE};
+use crate::models::Claims;
+use crate::ml_service::MlService;
+use crate::notifier::Notifier;
+use crate::phi_crypto::PhiCipher;
+use crate::rbac::{has_role, holds_role, Role};
+use crate::redis_cache::RedisCache;
+use crate::sse::{RecentEvents, SseBroadcaster};
+use actix_web::{http::header, web, HttpRequest, HttpResponse, Responder};
+use chrono::Utc;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub mod admin;
+pub mod alerts;
+pub mod analytics;
+pub mod auth;
+pub mod care_plans;
+pub mod checkins;
+pub mod deployment;
+pub mod device;
+pub mod emergency;
+pub mod event_log;
+pub mod failed_ingestions;
+pub mod fhir;
+pub mod fleet;
+pub mod gateways;
+pub mod integrations;
+pub mod legal_holds;
+pub mod medications;
+pub mod ml;
+pub mod notifications;
+pub mod on_call;
+pub mod organizations;
+pub mod patients;
+pub mod reporting;
+pub mod reporting_access;
+pub mod rota;
+pub mod route_listing;
+pub mod schemas;
+pub mod threshold_profiles;
+pub mod transfers;
+pub mod vitals;
+pub mod voice;
+pub mod wards;
+pub mod webhooks;
+
+pub use auth::{login, logout, signup};
+pub use device::{claim_device, device_ingest};
+pub use fhir::export_fhir_bundle;
+pub use route_listing::list_routes;
+pub use vitals::get_latest_vitals;
+
+pub struct AppState {
+    pub pool: PgPool,
+    /// Runs the analytics queries; the main pool unless `reporting.mode` is constrained
+    pub reporting_pool: PgPool,
+    pub redis: Arc<RwLock<RedisCache>>,
+    pub jwt_auth: Arc<JwtAuth>,
+    pub ml_service: Arc<MlService>,
+    pub fhir_service: Arc<FhirService>,
+    pub sse_broadcaster: SseBroadcaster,
+    pub recent_events: Arc<RecentEvents>,
+    pub notifier: Arc<Notifier>,
+    pub device_secrets: Arc<DeviceSecrets>,
+    pub replay_window_seconds: i64,
+    pub pairing_code_ttl_minutes: i64,
+    pub cors: CorsConfig,
+    pub deployment: DeploymentConfig,
+    pub voice: VoiceConfig,
+    pub retention: RetentionConfig,
+    pub query_debug: QueryDebugConfig,
+    pub quota: QuotaConfig,
+    pub slo: SloConfig,
+    pub reporting: ReportingConfig,
+    pub observability: ObservabilityConfig,
+    pub rate_limit: RateLimitConfig,
+    pub lockout: LockoutConfig,
+    pub phi: Arc<PhiCipher>,
+}
+
+crate::routes::route_registry! {
+    "/health" {
+        GET => health_check, Public, [];
+    }
+    "/version" {
+        GET => version, Public, [];
+    }
+    "/metrics" {
+        GET => prometheus_metrics, Public, [];
+    }
+}
+
+/// Whether the caller may act on a patient: admins always, otherwise only caregivers linked
+/// to them, in relaxed (home) deployments too. Users of an organization reach only its own
+/// patients, not those of another one or of none.
+pub async fn can_access_patient(state: &AppState, claims: &Claims, patient_id: uuid::Uuid) -> Result<bool, ApiError> {
+    if let Some(organization_id) = claims.org {
+        let patient_organization: Option<Option<uuid::Uuid>> =
+            sqlx::query_scalar("SELECT organization_id FROM patients WHERE id = $1")
+                .bind(patient_id)
+                .fetch_optional(&state.pool)
+                .await?;
+        if patient_organization != Some(Some(organization_id)) {
+            return Ok(false);
+        }
+    }
+    if has_role(claims, Role::Admin) {
+        return Ok(true);
+    }
+
+    let linked: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM patient_caregivers WHERE patient_id = $1 AND user_id = $2)"
+    )
+    .bind(patient_id)
+    .bind(claims.user_id)
+    .fetch_one(&state.pool)
+    .await?;
+
+    Ok(linked)
+}
+
+/// Patients the caller may act on, as [`can_access_patient`] decides; `None` means all of them
+pub async fn accessible_patients(state: &AppState, claims: &Claims) -> Result<Option<HashSet<uuid::Uuid>>, ApiError> {
+    let everyone = has_role(claims, Role::Admin);
+    if everyone && claims.org.is_none() {
+        return Ok(None);
+    }
+
+    let patients: Vec<uuid::Uuid> = sqlx::query_scalar(
+        "SELECT p.id FROM patients p
+         WHERE ($2 OR EXISTS(SELECT 1 FROM patient_caregivers c WHERE c.patient_id = p.id AND c.user_id = $1))
+           AND ($3::uuid IS NULL OR p.organization_id = $3)"
+    )
+    .bind(claims.user_id)
+    .bind(everyone)
+    .bind(claims.org)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Some(patients.into_iter().collect()))
+}
+
+/// Fail unless the caller is outside any organization. Organizations themselves and settings
+/// shared by the whole deployment are managed only by such administrators.
+pub fn require_unscoped(claims: &Claims) -> Result<(), ApiError> {
+    if claims.org.is_some() {
+        return Err(ApiError::Forbidden("Not available to users of an organization".into()));
+    }
+    Ok(())
+}
+
+/// Clinicians and admins manage care (plans, medications); relaxed (home) deployments
+/// let `patient`'s linked caregivers do so too. `None` for actions not about one patient,
+/// which stay with clinicians.
+pub async fn can_manage_care(state: &AppState, claims: &Claims, patient: Option<uuid::Uuid>) -> Result<bool, ApiError> {
+    Ok(holds_role(&state.pool, &state.deployment, claims, Role::Clinician, patient).await?)
+}
+
+// ============ Health & Version ============
+
+/// What is running, for operators and support
+pub async fn version() -> impl Responder {
+    HttpResponse::Ok().json(build_info())
+}
+
+/// Prometheus scrape endpoint, behind basic auth when `observability.metrics_username` is set
+pub async fn prometheus_metrics(req: HttpRequest, state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let config = &state.observability;
+    if let (Some(username), Some(password)) = (&config.metrics_username, &config.metrics_password) {
+        let auth_header = req.headers().get(header::AUTHORIZATION).and_then(|h| h.to_str().ok());
+        if !basic_credentials_match(auth_header, username, password) {
+            return Ok(HttpResponse::Unauthorized()
+                .insert_header((header::WWW_AUTHENTICATE, "Basic realm=\"metrics\""))
+                .finish());
+        }
+    }
+
+    DB_CONNECTIONS_ACTIVE.set(state.pool.size() as i64 - state.pool.num_idle() as i64);
+    let body = metrics::encode().map_err(|e| ApiError::Internal(format!("Failed to encode metrics: {}", e)))?;
+    Ok(HttpResponse::Ok().content_type("text/plain; version=0.0.4").body(body))
+}
+
+pub async fn health_check(pool: web::Data<PgPool>) -> impl Responder {
+    // Check database connection
+    let db_ok = sqlx::query("SELECT 1").fetch_one(pool.get_ref()).await.is_ok();
+
+    if db_ok {
+        HttpResponse::Ok().json(serde_json::json!({
+            "status": "healthy",
+            "database": "connected",
+            "timestamp": Utc::now().to_rfc3339()
+        }))
+    } else {
+        HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "status": "unhealthy",
+            "database": "disconnected"
+        }))
+    }
+}
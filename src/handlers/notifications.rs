@@ -0,0 +1,153 @@
+use crate::errors::ApiError;
+use crate::handlers::AppState;
+use crate::middleware::AuthenticatedUser;
+use crate::models::{AlertLevel, Notification, NotificationDelivery, NotificationPreferences, UpdateNotificationPreferencesRequest};
+use crate::notifications::load_preferences;
+use actix_web::{web, HttpResponse};
+use uuid::Uuid;
+use validator::Validate;
+
+crate::routes::route_registry! {
+    "/notifications" {
+        GET => list_notifications, Jwt, [];
+    }
+    "/notifications/preferences" {
+        GET => get_notification_preferences, Jwt, [];
+        PUT => update_notification_preferences, Jwt, [];
+    }
+    "/notifications/deliveries" {
+        GET => list_notification_deliveries, Jwt, [];
+    }
+    "/notifications/{id}/read" {
+        POST => mark_notification_read, Jwt, [];
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct NotificationQuery {
+    #[serde(default)]
+    pub unread: bool,
+}
+
+/// The caller's inbox, newest first (at most 100)
+pub async fn list_notifications(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    query: web::Query<NotificationQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let notifications: Vec<Notification> = sqlx::query_as(
+        "SELECT * FROM notifications
+         WHERE user_id = $1 AND (NOT $2 OR read_at IS NULL)
+         ORDER BY created_at DESC LIMIT 100"
+    )
+    .bind(claims.user_id)
+    .bind(query.unread)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(notifications))
+}
+
+pub async fn mark_notification_read(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let updated = sqlx::query(
+        "UPDATE notifications SET read_at = COALESCE(read_at, now()) WHERE id = $1 AND user_id = $2"
+    )
+    .bind(path.into_inner())
+    .bind(claims.user_id)
+    .execute(&state.pool)
+    .await?;
+
+    if updated.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Notification not found".into()));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Where the caller's vitals alerts are sent besides the inbox
+pub async fn get_notification_preferences(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(load_preferences(&state.pool, claims.user_id).await?))
+}
+
+/// Change the caller's channels; fields left out are kept and `""` clears an address.
+/// An enabled channel needs somewhere to send to.
+pub async fn update_notification_preferences(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    body: web::Json<UpdateNotificationPreferencesRequest>,
+) -> Result<HttpResponse, ApiError> {
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let body = body.into_inner();
+    if body.min_level == Some(AlertLevel::None) {
+        return Err(ApiError::BadRequest("min_level must be an alert level".into()));
+    }
+
+    let current = load_preferences(&state.pool, claims.user_id).await?;
+    let clear = |value: Option<String>, current: Option<String>| match value {
+        Some(value) => Some(value).filter(|v| !v.is_empty()),
+        None => current,
+    };
+    let preferences = NotificationPreferences {
+        email_enabled: body.email_enabled.unwrap_or(current.email_enabled),
+        email: clear(body.email, current.email),
+        sms_enabled: body.sms_enabled.unwrap_or(current.sms_enabled),
+        phone: clear(body.phone, current.phone),
+        webhook_enabled: body.webhook_enabled.unwrap_or(current.webhook_enabled),
+        webhook_url: clear(body.webhook_url, current.webhook_url),
+        webhook_secret: clear(body.webhook_secret, current.webhook_secret),
+        webhook_signed: false,
+        min_level: body.min_level.unwrap_or(current.min_level),
+    };
+    if preferences.sms_enabled && preferences.phone.is_none() {
+        return Err(ApiError::BadRequest("SMS needs a phone number".into()));
+    }
+    if preferences.webhook_enabled && preferences.webhook_url.is_none() {
+        return Err(ApiError::BadRequest("Webhooks need a webhook_url".into()));
+    }
+
+    sqlx::query(
+        "INSERT INTO notification_preferences
+             (user_id, email_enabled, email, sms_enabled, phone, webhook_enabled, webhook_url, webhook_secret, min_level)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+         ON CONFLICT (user_id) DO UPDATE
+         SET email_enabled = $2, email = $3, sms_enabled = $4, phone = $5, webhook_enabled = $6,
+             webhook_url = $7, webhook_secret = $8, min_level = $9, updated_at = now()"
+    )
+    .bind(claims.user_id)
+    .bind(preferences.email_enabled)
+    .bind(&preferences.email)
+    .bind(preferences.sms_enabled)
+    .bind(&preferences.phone)
+    .bind(preferences.webhook_enabled)
+    .bind(&preferences.webhook_url)
+    .bind(&preferences.webhook_secret)
+    .bind(preferences.min_level)
+    .execute(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(NotificationPreferences {
+        webhook_signed: preferences.webhook_secret.is_some(),
+        ..preferences
+    }))
+}
+
+/// The caller's email, SMS and webhook deliveries, newest first (at most 100)
+pub async fn list_notification_deliveries(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let deliveries: Vec<NotificationDelivery> = sqlx::query_as(
+        "SELECT * FROM notification_deliveries WHERE user_id = $1 ORDER BY created_at DESC LIMIT 100"
+    )
+    .bind(claims.user_id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(deliveries))
+}
@@ -0,0 +1,117 @@
+use crate::alert_routing::{is_night, on_call_at, ON_CALL_ROLES};
+use crate::errors::ApiError;
+use crate::handlers::{require_unscoped, AppState};
+use crate::middleware::AuthenticatedUser;
+use crate::rbac::Role;
+use crate::models::*;
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use uuid::Uuid;
+use validator::Validate;
+
+crate::routes::route_registry! {
+    "/on-call" {
+        GET => current_on_call, Jwt, [];
+    }
+    "/on-call/overrides" {
+        POST => create_override, Jwt, ["admin", "clinician"];
+    }
+}
+
+/// Who receives night-time alerts right now
+pub async fn current_on_call(
+    user: AuthenticatedUser,
+    state: web::Data<AppState>,
+    query: web::Query<OnCallQuery>,
+) -> Result<HttpResponse, ApiError> {
+    require_rota_scope(&state, &user, query.ward_id).await?;
+    Ok(HttpResponse::Ok().json(on_call_status(&state, query.ward_id).await?))
+}
+
+pub(crate) async fn on_call_status(state: &AppState, ward_id: Option<Uuid>) -> Result<OnCallStatus, sqlx::Error> {
+    let at = Utc::now();
+    let on_call = on_call_at(&state.pool, at, ward_id).await?;
+    let night_routing = state.notifier.routing().is_some_and(|config| is_night(config, at));
+    Ok(OnCallStatus { at, night_routing, ward_id, on_call })
+}
+
+/// 404 unless the ward exists and, for users of an organization, is one of its own;
+/// `None` (site-wide) always passes
+pub(crate) async fn require_ward(state: &AppState, claims: &Claims, ward_id: Option<Uuid>) -> Result<(), ApiError> {
+    let Some(ward_id) = ward_id else { return Ok(()) };
+    let exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM wards WHERE id = $1 AND ($2::uuid IS NULL OR organization_id = $2))"
+    )
+    .bind(ward_id)
+    .bind(claims.org)
+    .fetch_one(&state.pool)
+    .await?;
+    if !exists {
+        return Err(ApiError::NotFound("Ward not found".into()));
+    }
+    Ok(())
+}
+
+/// [`require_ward`] for on-call rotas and shifts. The site-wide rota is shared by the whole
+/// deployment, so only users outside any organization reach it.
+pub(crate) async fn require_rota_scope(state: &AppState, claims: &Claims, ward_id: Option<Uuid>) -> Result<(), ApiError> {
+    if ward_id.is_none() {
+        require_unscoped(claims)?;
+    }
+    require_ward(state, claims, ward_id).await
+}
+
+/// Put someone on call ahead of the schedule. Clinicians may only cover shifts themselves.
+pub async fn create_override(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    body: web::Json<OnCallOverrideRequest>,
+) -> Result<HttpResponse, ApiError> {
+    if !ON_CALL_ROLES.contains(&claims.role) {
+        return Err(ApiError::Forbidden("Clinician role required".into()));
+    }
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    let user_id = body.user_id.unwrap_or(claims.user_id);
+    if user_id != claims.user_id && claims.role != Role::Admin {
+        return Err(ApiError::Forbidden("Only admins can put someone else on call".into()));
+    }
+    let starts_at = body.starts_at.unwrap_or_else(Utc::now);
+    if body.ends_at <= starts_at.max(Utc::now()) {
+        return Err(ApiError::BadRequest("ends_at must be in the future and after starts_at".into()));
+    }
+
+    let role: Option<Role> = sqlx::query_scalar(
+        "SELECT role FROM users WHERE id = $1 AND is_active AND ($2::uuid IS NULL OR organization_id = $2)"
+    )
+    .bind(user_id)
+    .bind(claims.org)
+    .fetch_optional(&state.pool)
+    .await?;
+    match role {
+        None => return Err(ApiError::NotFound("User not found".into())),
+        Some(role) if !ON_CALL_ROLES.contains(&role) => {
+            return Err(ApiError::BadRequest(format!("Users with role '{}' cannot be on call", role)));
+        }
+        Some(_) => {}
+    }
+    require_rota_scope(&state, &claims, body.ward_id).await?;
+
+    let shift: OnCallShift = sqlx::query_as(
+        "INSERT INTO on_call_shifts (user_id, ward_id, starts_at, ends_at, is_override, reason, created_by)
+         VALUES ($1, $2, $3, $4, true, $5, $6)
+         RETURNING *"
+    )
+    .bind(user_id)
+    .bind(body.ward_id)
+    .bind(starts_at)
+    .bind(body.ends_at)
+    .bind(&body.reason)
+    .bind(claims.user_id)
+    .fetch_one(&state.pool)
+    .await?;
+
+    crate::audit_log!("on_call", "override", Some(claims.user_id), true, shift.id);
+
+    Ok(HttpResponse::Created().json(shift))
+}
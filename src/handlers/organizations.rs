@@ -0,0 +1,249 @@
+use crate::errors::ApiError;
+use crate::handlers::{require_unscoped, AppState};
+use crate::middleware::AuthenticatedUser;
+use crate::models::*;
+use crate::usage_service;
+use actix_web::{web, HttpResponse};
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+
+// Mounted under /api/admin
+crate::routes::route_registry! {
+    "/organizations" {
+        GET => list_organizations, Jwt, ["admin"];
+        POST => create_organization, Jwt, ["admin"];
+    }
+    "/organizations/{id}/quota" {
+        PUT => update_quota, Jwt, ["admin"];
+    }
+    "/organizations/{id}/patients/{patient_id}" {
+        PUT => assign_patient, Jwt, ["admin"];
+    }
+    "/organizations/{id}/users/{user_id}" {
+        PUT => assign_user, Jwt, ["admin"];
+    }
+    "/organizations/{id}/keys" {
+        DELETE => shred_keys, Jwt, ["admin"];
+    }
+    "/organizations/{id}/keys/rotate" {
+        POST => rotate_key, Jwt, ["admin"];
+    }
+    "/usage" {
+        GET => get_usage, Jwt, ["admin"];
+    }
+}
+
+/// Longest usage range returned at once
+const MAX_USAGE_DAYS: i64 = 366;
+
+const ORGANIZATION_SQL: &str =
+    "SELECT o.id, o.name, o.max_readings, o.max_bytes, o.created_at,
+            (SELECT COUNT(*) FROM patients p WHERE p.organization_id = o.id) AS patient_count,
+            u.readings, u.bytes, u.measured_at
+     FROM organizations o LEFT JOIN organization_usage u ON u.organization_id = o.id";
+
+async fn load_organization(pool: &PgPool, id: Uuid) -> Result<Organization, ApiError> {
+    sqlx::query_as(&format!("{} WHERE o.id = $1", ORGANIZATION_SQL))
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Organization not found".into()))
+}
+
+/// Every organization with its quotas and last measured usage; administrators of an
+/// organization see only their own
+pub async fn list_organizations(
+    admin: AuthenticatedUser,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let organizations: Vec<Organization> =
+        sqlx::query_as(&format!("{} WHERE $1::uuid IS NULL OR o.id = $1 ORDER BY o.name", ORGANIZATION_SQL))
+            .bind(admin.org)
+            .fetch_all(&state.pool)
+            .await?;
+
+    Ok(HttpResponse::Ok().json(organizations))
+}
+
+pub async fn create_organization(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    body: web::Json<OrganizationRequest>,
+) -> Result<HttpResponse, ApiError> {
+    require_unscoped(&claims)?;
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    let id: Option<Uuid> = sqlx::query_scalar(
+        "INSERT INTO organizations (name, max_readings, max_bytes) VALUES ($1, $2, $3)
+         ON CONFLICT (name) DO NOTHING RETURNING id"
+    )
+    .bind(body.name.trim())
+    .bind(body.quota.max_readings)
+    .bind(body.quota.max_bytes)
+    .fetch_optional(&state.pool)
+    .await?;
+    let id = id.ok_or_else(|| ApiError::Conflict("An organization with this name already exists".into()))?;
+
+    crate::audit_log!("organization", "create", Some(claims.user_id), true, id);
+
+    Ok(HttpResponse::Created().json(load_organization(&state.pool, id).await?))
+}
+
+/// Replace an organization's limits; takes effect on the next ingestion
+pub async fn update_quota(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Json<QuotaRequest>,
+) -> Result<HttpResponse, ApiError> {
+    require_unscoped(&claims)?;
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    let updated = sqlx::query("UPDATE organizations SET max_readings = $2, max_bytes = $3 WHERE id = $1")
+        .bind(*path)
+        .bind(body.max_readings)
+        .bind(body.max_bytes)
+        .execute(&state.pool)
+        .await?;
+    if updated.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Organization not found".into()));
+    }
+
+    crate::audit_log!("organization", "update_quota", Some(claims.user_id), true, *path);
+
+    Ok(HttpResponse::Ok().json(load_organization(&state.pool, *path).await?))
+}
+
+/// Place a patient, with their walkers, in an organization
+pub async fn assign_patient(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, ApiError> {
+    require_unscoped(&claims)?;
+    let (organization_id, patient_id) = path.into_inner();
+    load_organization(&state.pool, organization_id).await?;
+
+    let mut tx = state.pool.begin().await?;
+    let updated = sqlx::query("UPDATE patients SET organization_id = $2 WHERE id = $1")
+        .bind(patient_id)
+        .bind(organization_id)
+        .execute(&mut *tx)
+        .await?;
+    if updated.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Patient not found".into()));
+    }
+    sqlx::query("UPDATE devices SET organization_id = $2 WHERE patient_id = $1")
+        .bind(patient_id)
+        .bind(organization_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    crate::audit_log!("organization", "assign_patient", Some(claims.user_id), true, patient_id);
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Tie a user to an organization, limiting them to its patients. Tokens issued before name
+/// the old organization and stop working, so the user signs in again.
+pub async fn assign_user(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, ApiError> {
+    require_unscoped(&claims)?;
+    let (organization_id, user_id) = path.into_inner();
+    load_organization(&state.pool, organization_id).await?;
+
+    let updated = sqlx::query("UPDATE users SET organization_id = $2, updated_at = now() WHERE id = $1")
+        .bind(user_id)
+        .bind(organization_id)
+        .execute(&state.pool)
+        .await?;
+    if updated.rows_affected() == 0 {
+        return Err(ApiError::NotFound("User not found".into()));
+    }
+
+    crate::audit_log!("organization", "assign_user", Some(claims.user_id), true, user_id);
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+fn require_encryption(state: &AppState) -> Result<(), ApiError> {
+    if !state.phi.enabled() {
+        return Err(ApiError::Unavailable("PHI encryption is not configured".into()));
+    }
+    Ok(())
+}
+
+/// Seal new PHI under a fresh data key; values sealed under older keys stay readable
+pub async fn rotate_key(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    require_unscoped(&claims)?;
+    require_encryption(&state)?;
+    load_organization(&state.pool, *path).await?;
+
+    let version = state.phi.rotate(*path).await.map_err(|e| {
+        tracing::error!("Failed to rotate key for organization {}: {:#}", *path, e);
+        ApiError::Internal("Failed to rotate key".into())
+    })?;
+
+    crate::audit_log!("organization", "rotate_key", Some(claims.user_id), true, *path);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({"organization_id": *path, "key_version": version})))
+}
+
+/// Destroy an organization's data keys, e.g. on offboarding. Irreversible: PHI sealed
+/// under them can no longer be read.
+pub async fn shred_keys(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    require_unscoped(&claims)?;
+    require_encryption(&state)?;
+    load_organization(&state.pool, *path).await?;
+
+    let destroyed = state.phi.shred(*path).await.map_err(|e| {
+        tracing::error!("Failed to shred keys for organization {}: {:#}", *path, e);
+        ApiError::Internal("Failed to shred keys".into())
+    })?;
+
+    crate::audit_log!("organization", "shred_keys", Some(claims.user_id), true, *path);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({"organization_id": *path, "keys_destroyed": destroyed})))
+}
+
+/// Daily usage totals per organization; today's running totals are rolled up first.
+/// Administrators of an organization see only its totals.
+pub async fn get_usage(
+    admin: AuthenticatedUser,
+    state: web::Data<AppState>,
+    query: web::Query<UsageQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let today = Utc::now().date_naive();
+    let to = query.to.unwrap_or(today);
+    let from = query.from.unwrap_or(to - Duration::days(29));
+    if from > to {
+        return Err(ApiError::BadRequest("from must not be after to".into()));
+    }
+    if to - from >= Duration::days(MAX_USAGE_DAYS) {
+        return Err(ApiError::BadRequest(format!("Range is limited to {} days", MAX_USAGE_DAYS)));
+    }
+
+    if (from..=to).contains(&today) {
+        usage_service::roll_up(&state.pool, today).await?;
+    }
+    let mut usage = usage_service::rollups(&state.pool, from, to).await?;
+    if admin.org.is_some() {
+        usage.retain(|rollup| rollup.organization_id == admin.org);
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({"from": from, "to": to, "usage": usage})))
+}
@@ -0,0 +1,821 @@
+use crate::aggregate_service::{self, Bucket};
+use crate::ambient_service;
+use crate::errors::ApiError;
+use crate::handlers::admin::load_organization_profile;
+use crate::handlers::{accessible_patients, can_access_patient, can_manage_care, AppState};
+use crate::middleware::AuthenticatedUser;
+use crate::ml_service::RiskInputs;
+use crate::near_fall_service;
+use crate::models::*;
+use crate::negotiation::{negotiate, ResponseFormat};
+use crate::reports::{render_pdf, Report, ReportSection};
+use crate::request_context::RequestContext;
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+use validator::Validate;
+
+crate::routes::route_registry! {
+    "/patients" {
+        GET => list_patients, Jwt, [];
+        POST => create_patient, Jwt, ["admin", "clinician"];
+    }
+    "/patients/{patient_id}" {
+        GET => get_patient, Jwt, [];
+        PATCH => update_patient, Jwt, [];
+        DELETE => delete_patient, Jwt, ["admin"];
+    }
+    "/patients/{patient_id}/timeline" {
+        GET => get_timeline, Jwt, [];
+    }
+    "/patients/{patient_id}/attributes" {
+        GET => get_attributes, Jwt, [];
+        PUT => update_attributes, Jwt, [];
+    }
+    "/patients/{patient_id}/risk" {
+        GET => get_risk, Jwt, [];
+    }
+    "/patients/{patient_id}/sleep" {
+        GET => get_sleep, Jwt, [];
+    }
+    "/patients/{patient_id}/activity" {
+        GET => get_activity_report, Jwt, [];
+    }
+    "/patients/{patient_id}/near-falls" {
+        GET => get_near_falls, Jwt, [];
+    }
+    "/patients/{patient_id}/ambient" {
+        GET => get_ambient, Jwt, [];
+    }
+    "/patients/{patient_id}/vitals/aggregate" {
+        GET => get_vitals_aggregate, Jwt, [];
+    }
+    "/patients/{patient_id}/archive" {
+        GET => get_record_status, Jwt, [];
+        POST => archive_patient, Jwt, [];
+        DELETE => restore_patient, Jwt, ["admin"];
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct PatientListQuery {
+    /// Include archived patients
+    #[serde(default)]
+    pub archived: bool,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct TimelineQuery {
+    pub days: Option<i64>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct SleepQuery {
+    pub days: Option<i64>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ActivityReportQuery {
+    /// Day to report on in the caller's time zone (default: today)
+    pub date: Option<NaiveDate>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct NearFallQuery {
+    pub weeks: Option<i32>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct AmbientQuery {
+    pub days: Option<i64>,
+}
+
+/// Fail unless the caller may see this patient
+pub(crate) async fn require_patient_access(state: &AppState, claims: &Claims, patient_id: Uuid) -> Result<(), ApiError> {
+    if !can_access_patient(state, claims, patient_id).await? {
+        return Err(ApiError::Forbidden("Not a caregiver for this patient".into()));
+    }
+    Ok(())
+}
+
+/// Fail unless the patient exists and is not archived; archived records are read-only
+pub(crate) async fn require_active_patient(pool: &PgPool, patient_id: Uuid) -> Result<(), ApiError> {
+    let archived: Option<bool> = sqlx::query_scalar("SELECT archived_at IS NOT NULL FROM patients WHERE id = $1")
+        .bind(patient_id)
+        .fetch_optional(pool)
+        .await?;
+    match archived {
+        None => Err(ApiError::NotFound("Patient not found".into())),
+        Some(true) => Err(ApiError::Conflict("Patient record is archived".into())),
+        Some(false) => Ok(()),
+    }
+}
+
+/// Whether the caller may add no more patients under `deployment.max_patients`. The limit
+/// applies per organization, and to users outside one per caregiver, so one household's
+/// patient does not use up another's.
+pub(crate) async fn patient_limit_reached(
+    state: &AppState,
+    conn: &mut PgConnection,
+    organization_id: Option<Uuid>,
+    user_id: Uuid,
+) -> Result<bool, ApiError> {
+    let Some(max) = state.deployment.max_patients else {
+        return Ok(false);
+    };
+    let count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM patients p
+         WHERE CASE WHEN $1::uuid IS NULL
+                    THEN EXISTS(SELECT 1 FROM patient_caregivers c WHERE c.patient_id = p.id AND c.user_id = $2)
+                    ELSE p.organization_id = $1 END"
+    )
+    .bind(organization_id)
+    .bind(user_id)
+    .fetch_one(conn)
+    .await?;
+    Ok(count >= max)
+}
+
+const PATIENT_SQL: &str =
+    "SELECT p.id, p.display_name, p.date_of_birth, p.diagnoses, p.organization_id, p.ward_id, p.created_by,
+            p.created_at, p.archived_at,
+            ARRAY(SELECT d.device_id FROM devices d WHERE d.patient_id = p.id ORDER BY d.device_id) AS devices
+     FROM patients p";
+
+async fn load_patient(pool: &PgPool, patient_id: Uuid) -> Result<PatientRecord, ApiError> {
+    sqlx::query_as(&format!("{} WHERE p.id = $1", PATIENT_SQL))
+        .bind(patient_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Patient not found".into()))
+}
+
+fn check_date_of_birth(date_of_birth: Option<NaiveDate>) -> Result<(), ApiError> {
+    if date_of_birth.is_some_and(|born| born > Utc::now().date_naive()) {
+        return Err(ApiError::BadRequest("date_of_birth cannot be in the future".into()));
+    }
+    Ok(())
+}
+
+/// Gather the last 24 hours of alerts and the latest check-in for risk scoring
+pub(crate) async fn load_risk_inputs(pool: &PgPool, patient_id: Uuid) -> Result<RiskInputs, sqlx::Error> {
+    let since = Utc::now() - Duration::hours(24);
+
+    let (max_anomaly_score, critical_alerts): (Option<f32>, i64) = sqlx::query_as(
+        "SELECT MAX(a.anomaly_score), COUNT(*) FILTER (WHERE a.alert_level = 'critical')
+         FROM ml_analysis a
+         JOIN sensor_readings r ON r.id = a.sensor_reading_id
+         JOIN devices d ON d.id = r.device_id
+         WHERE d.patient_id = $1 AND r.reading_timestamp >= $2"
+    )
+    .bind(patient_id)
+    .bind(since)
+    .fetch_one(pool)
+    .await?;
+
+    let latest_checkin: Option<Checkin> = sqlx::query_as(
+        "SELECT * FROM checkins WHERE patient_id = $1 AND submitted_at >= $2 ORDER BY submitted_at DESC LIMIT 1"
+    )
+    .bind(patient_id)
+    .bind(since)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(RiskInputs {
+        max_anomaly_score,
+        critical_alerts,
+        latest_checkin,
+    })
+}
+
+/// Check-ins, alerts, medication events and rest periods merged newest first (default 7 days, max 90)
+pub async fn get_timeline(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<TimelineQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    require_patient_access(&state, &claims, patient_id).await?;
+
+    let since = Utc::now() - Duration::days(query.days.unwrap_or(7).clamp(1, 90));
+
+    let mut events: Vec<TimelineEvent> = sqlx::query_as(
+        "SELECT c.submitted_at AS occurred_at, 'checkin' AS kind, to_jsonb(c) - 'patient_id' AS data
+         FROM checkins c
+         WHERE c.patient_id = $1 AND c.submitted_at >= $2
+         UNION ALL
+         SELECT r.reading_timestamp, 'alert', jsonb_build_object(
+                    'reading_id', r.id, 'alert_level', a.alert_level,
+                    'classification', a.classification, 'anomaly_score', a.anomaly_score)
+         FROM ml_analysis a
+         JOIN sensor_readings r ON r.id = a.sensor_reading_id
+         JOIN devices d ON d.id = r.device_id
+         WHERE d.patient_id = $1 AND r.reading_timestamp >= $2 AND a.alert_level IN ('medium', 'high', 'critical')
+         UNION ALL
+         SELECT al.raised_at, 'alert', jsonb_build_object(
+                    'alert_id', al.id, 'kind', al.kind, 'alert_level', al.level, 'message', al.message)
+         FROM alerts al
+         WHERE al.patient_id = $1 AND al.raised_at >= $2
+         UNION ALL
+         SELECT COALESCE(md.confirmed_at, md.scheduled_at), 'medication', jsonb_build_object(
+                    'dose_id', md.id, 'name', m.name, 'dosage', m.dosage,
+                    'status', md.status, 'scheduled_at', md.scheduled_at)
+         FROM medication_doses md
+         JOIN medications m ON m.id = md.medication_id
+         WHERE m.patient_id = $1 AND md.scheduled_at >= $2 AND md.status <> 'pending'
+         UNION ALL
+         SELECT (p->>'start')::timestamptz, 'sleep', p
+         FROM sleep_summaries s, jsonb_array_elements(s.periods) p
+         WHERE s.patient_id = $1 AND (p->>'end')::timestamptz >= $2
+         ORDER BY occurred_at DESC
+         LIMIT 500"
+    )
+    .bind(patient_id)
+    .bind(since)
+    .fetch_all(&state.pool)
+    .await?;
+    for event in events.iter_mut().filter(|e| e.kind == "checkin") {
+        state.phi.open_json_field(&mut event.data, "notes").await;
+    }
+
+    Ok(HttpResponse::Ok().json(events))
+}
+
+// ============ Patients ============
+
+/// The patients the caller may see, by name; archived ones only with `archived=true`
+pub async fn list_patients(
+    ctx: RequestContext,
+    state: web::Data<AppState>,
+    query: web::Query<PatientListQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let visible: Option<Vec<Uuid>> = accessible_patients(&state, &ctx).await?.map(|ids| ids.into_iter().collect());
+    let patients: Vec<PatientRecord> = sqlx::query_as(&format!(
+        "{} WHERE ($1::uuid[] IS NULL OR p.id = ANY($1))
+              AND ($2 OR p.archived_at IS NULL)
+              AND ($3::uuid IS NULL OR p.organization_id = $3)
+         ORDER BY p.display_name, p.created_at",
+        PATIENT_SQL
+    ))
+    .bind(visible)
+    .bind(query.archived)
+    .bind(ctx.organization_id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(patients))
+}
+
+/// Add a patient under the caller's organization, with the caller as a caregiver. Walkers
+/// are then assigned by an administrator or claimed with a pairing code.
+pub async fn create_patient(
+    ctx: RequestContext,
+    state: web::Data<AppState>,
+    body: web::Json<PatientRequest>,
+) -> Result<HttpResponse, ApiError> {
+    if !can_manage_care(&state, &ctx, None).await? {
+        return Err(ApiError::Forbidden("Clinician role required".into()));
+    }
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let display_name = body.display_name.trim();
+    if display_name.is_empty() {
+        return Err(ApiError::BadRequest("display_name is required".into()));
+    }
+    check_date_of_birth(body.date_of_birth)?;
+
+    let mut tx = state.pool.begin().await?;
+    // Same limit as claiming a walker for a new patient
+    if patient_limit_reached(&state, &mut tx, ctx.organization_id, ctx.user_id).await? {
+        return Err(ApiError::Conflict(format!(
+            "Patient limit reached for {} deployments",
+            state.deployment.mode.name()
+        )));
+    }
+    let patient_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO patients (display_name, date_of_birth, organization_id, created_by) VALUES ($1, $2, $3, $4)
+         RETURNING id"
+    )
+    .bind(display_name)
+    .bind(body.date_of_birth)
+    .bind(ctx.organization_id)
+    .bind(ctx.user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    sqlx::query("INSERT INTO patient_caregivers (patient_id, user_id) VALUES ($1, $2)")
+        .bind(patient_id)
+        .bind(ctx.user_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    crate::audit_log!("patient", "create", Some(ctx.user_id), true, patient_id);
+
+    Ok(HttpResponse::Created().json(load_patient(&state.pool, patient_id).await?))
+}
+
+pub async fn get_patient(
+    ctx: RequestContext,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    ctx.require_patient_access(&state, patient_id).await?;
+
+    Ok(HttpResponse::Ok().json(load_patient(&state.pool, patient_id).await?))
+}
+
+/// Rename a patient or correct their date of birth
+pub async fn update_patient(
+    ctx: RequestContext,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Json<PatientUpdate>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    if !can_manage_care(&state, &ctx, Some(patient_id)).await? {
+        return Err(ApiError::Forbidden("Clinician role required".into()));
+    }
+    ctx.require_patient_access(&state, patient_id).await?;
+    require_active_patient(&state.pool, patient_id).await?;
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let display_name = body.display_name.as_deref().map(str::trim);
+    if display_name == Some("") {
+        return Err(ApiError::BadRequest("display_name cannot be blank".into()));
+    }
+    check_date_of_birth(body.date_of_birth)?;
+
+    sqlx::query(
+        "UPDATE patients SET display_name = COALESCE($2, display_name), date_of_birth = COALESCE($3, date_of_birth)
+         WHERE id = $1"
+    )
+    .bind(patient_id)
+    .bind(display_name)
+    .bind(body.date_of_birth)
+    .execute(&state.pool)
+    .await?;
+
+    crate::audit_log!("patient", "update", Some(ctx.user_id), true, patient_id);
+
+    Ok(HttpResponse::Ok().json(load_patient(&state.pool, patient_id).await?))
+}
+
+/// Remove a patient entered by mistake, with everything recorded for them. Patients with
+/// walkers, a legal hold or transfer history are archived instead.
+pub async fn delete_patient(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+
+    let mut tx = state.pool.begin().await?;
+    let blockers: Option<(bool, bool, bool)> = sqlx::query_as(
+        "SELECT EXISTS(SELECT 1 FROM devices WHERE patient_id = p.id),
+                EXISTS(SELECT 1 FROM legal_holds WHERE patient_id = p.id),
+                EXISTS(SELECT 1 FROM patient_transfers WHERE patient_id = p.id)
+         FROM patients p WHERE p.id = $1 FOR UPDATE"
+    )
+    .bind(patient_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    match blockers {
+        None => return Err(ApiError::NotFound("Patient not found".into())),
+        Some((true, _, _)) => {
+            return Err(ApiError::Conflict("Patient has walkers assigned; unassign them or archive the record".into()))
+        }
+        Some((_, true, _)) => return Err(ApiError::Conflict("Patient is under legal hold".into())),
+        Some((_, _, true)) => {
+            return Err(ApiError::Conflict("Patient has transfer history; archive the record instead".into()))
+        }
+        Some(_) => {}
+    }
+    sqlx::query("DELETE FROM patients WHERE id = $1")
+        .bind(patient_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    crate::audit_log!("patient", "delete", Some(claims.user_id), true, patient_id);
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Longest diagnosis accepted in the patient record
+const MAX_DIAGNOSIS_LEN: usize = 100;
+
+async fn load_attributes(pool: &PgPool, patient_id: Uuid) -> Result<PatientAttributes, ApiError> {
+    sqlx::query_as("SELECT date_of_birth, diagnoses FROM patients WHERE id = $1")
+        .bind(patient_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Patient not found".into()))
+}
+
+/// Date of birth and diagnoses, which threshold profiles' severity rules match against
+pub async fn get_attributes(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    require_patient_access(&state, &claims, patient_id).await?;
+
+    Ok(HttpResponse::Ok().json(load_attributes(&state.pool, patient_id).await?))
+}
+
+/// Replace the patient's attributes; later readings are judged against them
+pub async fn update_attributes(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Json<PatientAttributes>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    if !can_manage_care(&state, &claims, Some(patient_id)).await? {
+        return Err(ApiError::Forbidden("Clinician role required".into()));
+    }
+    require_patient_access(&state, &claims, patient_id).await?;
+    require_active_patient(&state.pool, patient_id).await?;
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    check_date_of_birth(body.date_of_birth)?;
+    let mut diagnoses: Vec<String> = Vec::new();
+    for diagnosis in body.diagnoses.iter().map(|d| d.trim()).filter(|d| !d.is_empty()) {
+        if diagnosis.len() > MAX_DIAGNOSIS_LEN {
+            return Err(ApiError::BadRequest(format!("Diagnoses are limited to {} characters", MAX_DIAGNOSIS_LEN)));
+        }
+        if !diagnoses.iter().any(|d| d.eq_ignore_ascii_case(diagnosis)) {
+            diagnoses.push(diagnosis.to_string());
+        }
+    }
+
+    let updated = sqlx::query("UPDATE patients SET date_of_birth = $2, diagnoses = $3 WHERE id = $1")
+        .bind(patient_id)
+        .bind(body.date_of_birth)
+        .bind(&diagnoses)
+        .execute(&state.pool)
+        .await?;
+    if updated.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Patient not found".into()));
+    }
+
+    crate::audit_log!("patient", "update_attributes", Some(claims.user_id), true, patient_id);
+
+    Ok(HttpResponse::Ok().json(load_attributes(&state.pool, patient_id).await?))
+}
+
+// ============ Archival ============
+
+async fn load_record_status(pool: &PgPool, patient_id: Uuid) -> Result<PatientRecordStatus, ApiError> {
+    sqlx::query_as(
+        "SELECT id, display_name, organization_id, archived_at, archived_by, archive_reason FROM patients WHERE id = $1"
+    )
+    .bind(patient_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Patient not found".into()))
+}
+
+/// Whether the record is archived, by whom and why
+pub async fn get_record_status(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    require_patient_access(&state, &claims, patient_id).await?;
+
+    Ok(HttpResponse::Ok().json(load_record_status(&state.pool, patient_id).await?))
+}
+
+/// Archive a discharged patient: the record turns read-only, their walkers are deactivated
+/// and they drop off ward lists, alert triage and the scheduled workers
+pub async fn archive_patient(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Json<ArchivePatientRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    if !can_manage_care(&state, &claims, Some(patient_id)).await? {
+        return Err(ApiError::Forbidden("Clinician role required".into()));
+    }
+    require_patient_access(&state, &claims, patient_id).await?;
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let reason = body.reason.trim();
+    if reason.is_empty() {
+        return Err(ApiError::BadRequest("reason is required".into()));
+    }
+
+    let mut tx = state.pool.begin().await?;
+    let archived: Option<bool> =
+        sqlx::query_scalar("SELECT archived_at IS NOT NULL FROM patients WHERE id = $1 FOR UPDATE")
+            .bind(patient_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+    match archived {
+        None => return Err(ApiError::NotFound("Patient not found".into())),
+        Some(true) => return Err(ApiError::Conflict("Patient record is already archived".into())),
+        Some(false) => {}
+    }
+    let transferring: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM patient_transfers WHERE patient_id = $1 AND status = 'pending')"
+    )
+    .bind(patient_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    if transferring {
+        return Err(ApiError::Conflict("Patient has a transfer in progress".into()));
+    }
+
+    sqlx::query("UPDATE patients SET archived_at = now(), archived_by = $2, archive_reason = $3 WHERE id = $1")
+        .bind(patient_id)
+        .bind(claims.user_id)
+        .bind(reason)
+        .execute(&mut *tx)
+        .await?;
+    // Readings and the device link are kept, as with a fleet deactivation
+    sqlx::query("UPDATE devices SET is_active = false WHERE patient_id = $1")
+        .bind(patient_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    crate::audit_log!("patient", "archive", Some(claims.user_id), true, patient_id);
+
+    Ok(HttpResponse::Ok().json(load_record_status(&state.pool, patient_id).await?))
+}
+
+/// Bring an archived patient back; their walkers stay deactivated until reactivated
+pub async fn restore_patient(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+
+    let restored = sqlx::query(
+        "UPDATE patients SET archived_at = NULL, archived_by = NULL, archive_reason = NULL
+         WHERE id = $1 AND archived_at IS NOT NULL"
+    )
+    .bind(patient_id)
+    .execute(&state.pool)
+    .await?;
+    if restored.rows_affected() == 0 {
+        // Tell a missing patient apart from one that was never archived
+        load_record_status(&state.pool, patient_id).await?;
+        return Err(ApiError::Conflict("Patient record is not archived".into()));
+    }
+
+    crate::audit_log!("patient", "restore", Some(claims.user_id), true, patient_id);
+
+    Ok(HttpResponse::Ok().json(load_record_status(&state.pool, patient_id).await?))
+}
+
+pub async fn get_risk(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    require_patient_access(&state, &claims, patient_id).await?;
+
+    let inputs = load_risk_inputs(&state.pool, patient_id).await?;
+    Ok(HttpResponse::Ok().json(state.ml_service.assess_risk(&inputs)))
+}
+
+/// Nightly rest/sleep summaries, newest first (default 14 nights, max 90)
+pub async fn get_sleep(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<SleepQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    require_patient_access(&state, &claims, patient_id).await?;
+
+    let since = Utc::now().date_naive() - Duration::days(query.days.unwrap_or(14).clamp(1, 90));
+
+    let summaries: Vec<SleepSummary> = sqlx::query_as(
+        "SELECT * FROM sleep_summaries WHERE patient_id = $1 AND sleep_date > $2 ORDER BY sleep_date DESC"
+    )
+    .bind(patient_id)
+    .bind(since)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(summaries))
+}
+
+/// Classified activity for one day in the caller's time zone, as JSON or a printable PDF
+/// (`Accept: application/pdf`)
+pub async fn get_activity_report(
+    req: HttpRequest,
+    ctx: RequestContext,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<ActivityReportQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    ctx.require_patient_access(&state, patient_id).await?;
+
+    let format = match negotiate(&req, &[ResponseFormat::Json, ResponseFormat::Pdf]) {
+        Ok(f) => f,
+        Err(resp) => return Ok(resp),
+    };
+
+    let display_name: String = sqlx::query_scalar("SELECT display_name FROM patients WHERE id = $1")
+        .bind(patient_id)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Patient not found".into()))?;
+
+    let date = query.date.unwrap_or_else(|| ctx.today());
+    let (start, end) = ctx.day_bounds(date);
+
+    let segments: Vec<ActivitySegment> = sqlx::query_as(
+        "SELECT device_id, activity, started_at, ended_at, steps, elevation_change, readings, classifier
+         FROM activity_segments
+         WHERE patient_id = $1 AND started_at >= $2 AND started_at < $3
+         ORDER BY started_at"
+    )
+    .bind(patient_id)
+    .bind(start)
+    .bind(end)
+    .fetch_all(&state.pool)
+    .await?;
+
+    let mut minutes = std::collections::BTreeMap::new();
+    for segment in &segments {
+        *minutes.entry(segment.activity.clone()).or_insert(0) += (segment.ended_at - segment.started_at).num_minutes();
+    }
+    let report = DailyActivityReport {
+        patient_id,
+        date,
+        timezone: ctx.timezone.name().to_string(),
+        minutes,
+        steps: segments.iter().map(|s| i64::from(s.steps)).sum(),
+        segments,
+    };
+
+    Ok(match format {
+        ResponseFormat::Pdf => {
+            let profile = load_organization_profile(&state.pool).await?;
+            HttpResponse::Ok()
+                .content_type(format.content_type())
+                .insert_header((
+                    "Content-Disposition",
+                    format!("attachment; filename=\"activity-{}.pdf\"", date.format("%Y%m%d")),
+                ))
+                .body(render_pdf(&activity_report(&profile, &display_name, &report, &ctx)))
+        }
+        _ => HttpResponse::Ok().json(report),
+    })
+}
+
+fn activity_report(
+    profile: &OrganizationProfile,
+    display_name: &str,
+    report: &DailyActivityReport,
+    ctx: &RequestContext,
+) -> Report {
+    let duration = |minutes: i64| format!("{}h{:02}m", minutes / 60, minutes % 60);
+
+    let mut summary = ReportSection::new("Summary");
+    if report.segments.is_empty() {
+        summary.line("No classified motion data for this day.");
+    }
+    for (activity, minutes) in &report.minutes {
+        summary.line(format!("{}: {}", activity, duration(*minutes)));
+    }
+    summary.line(format!("Steps: {}", report.steps));
+
+    let mut segments = ReportSection::new(format!("Segments ({})", report.timezone));
+    for segment in &report.segments {
+        segments.line(format!(
+            "{}-{} {} ({}), {} steps, {}",
+            ctx.local(segment.started_at).format("%H:%M"),
+            ctx.local(segment.ended_at).format("%H:%M"),
+            segment.activity,
+            duration((segment.ended_at - segment.started_at).num_minutes()),
+            segment.steps,
+            ctx.units.length(f64::from(segment.elevation_change)),
+        ));
+    }
+
+    Report {
+        letterhead: Some(profile.into()),
+        title: format!("Daily activity - {}", display_name),
+        subtitle: Some(format!("{} ({})", report.date, report.timezone)),
+        sections: vec![summary, segments],
+    }
+}
+
+/// Weekly near-fall counts (default 8 weeks, max 52) and the most recent events
+pub async fn get_near_falls(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<NearFallQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    require_patient_access(&state, &claims, patient_id).await?;
+
+    let weeks = query.weeks.unwrap_or(8).clamp(2, 52);
+    let weeks = near_fall_service::weekly_counts(&state.pool, patient_id, Utc::now(), weeks).await?;
+    let increasing = match weeks.as_slice() {
+        [.., previous, latest] => near_fall_service::is_rising(latest.events, previous.events),
+        _ => false,
+    };
+
+    let recent: Vec<NearFallEvent> = sqlx::query_as(
+        "SELECT * FROM near_fall_events WHERE patient_id = $1 ORDER BY occurred_at DESC LIMIT 50"
+    )
+    .bind(patient_id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(NearFallTrend {
+        patient_id,
+        weeks,
+        increasing,
+        recent,
+    }))
+}
+
+/// Ambient temperature/humidity around the walker and heat-stress coincidences with an
+/// elevated heart rate (default 7 days, max 30)
+pub async fn get_ambient(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<AmbientQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    require_patient_access(&state, &claims, patient_id).await?;
+
+    let since = Utc::now() - Duration::days(query.days.unwrap_or(7).clamp(1, 30));
+    let summary = ambient_service::summarize(&state.pool, patient_id, since).await?;
+
+    Ok(HttpResponse::Ok().json(summary))
+}
+
+/// Vitals statistics per minute, 5 minutes, hour or day for charts, served from the
+/// aggregate cache when possible
+pub async fn get_vitals_aggregate(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<VitalsAggregateQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    require_patient_access(&state, &claims, patient_id).await?;
+
+    let aggregate = vitals_aggregate(&state, patient_id, query.bucket.as_deref(), query.from, query.to).await?;
+    Ok(HttpResponse::Ok().json(aggregate))
+}
+
+/// Validate the requested bucket and range, then aggregate; shared with `GET /api/vitals/aggregate`
+pub(crate) async fn vitals_aggregate(
+    state: &AppState,
+    patient_id: Uuid,
+    bucket: Option<&str>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<VitalsAggregate, ApiError> {
+    let bucket = match bucket {
+        None => Bucket::Hour,
+        Some(value) => Bucket::parse(value).ok_or_else(|| {
+            ApiError::BadRequest(format!("Unknown bucket '{}' (expected 1m, 5m, 1h or 1d)", value))
+        })?,
+    };
+    let to = to.unwrap_or_else(Utc::now);
+    let from = from.unwrap_or(to - bucket.width() * 24);
+    if from >= to {
+        return Err(ApiError::BadRequest("from must be before to".into()));
+    }
+    if to - from > bucket.max_range() {
+        let days = bucket.max_range().num_days();
+        return Err(ApiError::BadRequest(format!(
+            "Range too long for {} buckets (max {} day{})",
+            bucket.name(),
+            days,
+            if days == 1 { "" } else { "s" }
+        )));
+    }
+    let (from, to) = bucket.align(from, to);
+
+    let (buckets, cached) =
+        aggregate_service::cached_vitals_aggregate(&state.pool, &state.redis, patient_id, bucket, from, to).await?;
+
+    Ok(VitalsAggregate {
+        patient_id,
+        bucket: bucket.name().to_string(),
+        from,
+        to,
+        cached,
+        buckets,
+    })
+}
@@ -0,0 +1,330 @@
+use crate::errors::ApiError;
+use crate::handlers::admin::load_organization_profile;
+use crate::handlers::on_call::require_ward;
+use crate::handlers::patients::require_patient_access;
+use crate::handlers::{can_manage_care, AppState};
+use crate::middleware::AuthenticatedUser;
+use crate::models::*;
+use crate::negotiation::ResponseFormat;
+use crate::query_debug;
+use crate::reporting_service::refreshed_at;
+use crate::reports::{render_csv, render_pdf, Report, ReportSection};
+use crate::usage_service::{self, UsageMetric};
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use uuid::Uuid;
+
+// Served from the materialized views in migration 019, so figures lag by up to one refresh.
+// Reads go through `reporting_pool`, which may be constrained to the reporting role.
+crate::routes::route_registry! {
+    "/reports/patients/{patient_id}/daily" {
+        GET => get_patient_daily_report, Jwt, [];
+    }
+    "/reports/alerts" {
+        GET => get_alert_summary, Jwt, ["admin", "clinician"];
+    }
+}
+
+const DEFAULT_REPORT_DAYS: u32 = 30;
+const MAX_REPORT_DAYS: u32 = 366;
+
+/// First and last UTC day covered by a report: `from` to `to` (today if omitted), or
+/// `days` ending today
+fn report_range(query: &ReportQuery) -> Result<(NaiveDate, NaiveDate), ApiError> {
+    let today = Utc::now().date_naive();
+    if query.from.is_none() && query.to.is_none() {
+        let days = query.days.unwrap_or(DEFAULT_REPORT_DAYS);
+        if !(1..=MAX_REPORT_DAYS).contains(&days) {
+            return Err(ApiError::BadRequest(format!("days must be between 1 and {}", MAX_REPORT_DAYS)));
+        }
+        return Ok((today - Duration::days(i64::from(days) - 1), today));
+    }
+
+    if query.days.is_some() {
+        return Err(ApiError::BadRequest("Give either days or from and to".into()));
+    }
+    let from = query.from.ok_or_else(|| ApiError::BadRequest("from is required with to".into()))?;
+    let to = query.to.unwrap_or(today);
+    if from > to {
+        return Err(ApiError::BadRequest("from must not be after to".into()));
+    }
+    if (to - from).num_days() >= i64::from(MAX_REPORT_DAYS) {
+        return Err(ApiError::BadRequest(format!("A report can cover at most {} days", MAX_REPORT_DAYS)));
+    }
+    Ok((from, to))
+}
+
+/// Daily vitals statistics for one patient
+pub async fn get_patient_daily_report(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<ReportQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    require_patient_access(&state, &claims, patient_id).await?;
+    let (from, to) = report_range(&query)?;
+
+    let days: Vec<PatientDailyStats> = query_debug::fetch_all(
+        &state.reporting_pool,
+        &state.query_debug,
+        "patient_daily_report",
+        "SELECT day, readings, heart_rate_avg, heart_rate_min, heart_rate_max, spo2_avg, spo2_min,
+                temperature_avg, temperature_max, anomalies
+         FROM patient_daily_stats
+         WHERE patient_id = $1 AND day BETWEEN $2 AND $3
+         ORDER BY day",
+        || crate::pg_args![patient_id, from, to],
+    )
+    .await?;
+
+    crate::audit_log!("data_access", "daily_report", Some(claims.user_id), true, patient_id);
+
+    Ok(HttpResponse::Ok().json(PatientDailyReport {
+        patient_id,
+        from,
+        to,
+        refreshed_at: refreshed_at(&state.reporting_pool, "patient_daily_stats").await?,
+        days,
+    }))
+}
+
+/// Alert volume and acknowledgement times by kind and level across the caller's organization
+/// (site-wide for users outside one) or on one ward.
+/// `format=csv` or `format=pdf` lists every alert instead, for quality committee reviews.
+pub async fn get_alert_summary(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    query: web::Query<ReportQuery>,
+) -> Result<HttpResponse, ApiError> {
+    if !can_manage_care(&state, &claims, None).await? {
+        return Err(ApiError::Forbidden("Clinician role required".into()));
+    }
+    let format = match query.format.as_deref() {
+        None | Some("json") => ResponseFormat::Json,
+        Some("csv") => ResponseFormat::Csv,
+        Some("pdf") => ResponseFormat::Pdf,
+        Some(other) => return Err(ApiError::BadRequest(format!("Unknown format '{}'; use json, csv or pdf", other))),
+    };
+    require_ward(&state, &claims, query.ward_id).await?;
+    let (from, to) = report_range(&query)?;
+    if format != ResponseFormat::Json {
+        return export_alerts(&state, &claims, query.ward_id, from, to, format).await;
+    }
+
+    // Ward membership is resolved now, so patients moved between wards report under their current ward
+    let alerts: Vec<AlertSummaryRow> = query_debug::fetch_all(
+        &state.reporting_pool,
+        &state.query_debug,
+        "alert_summary",
+        "SELECT s.kind, s.level,
+                SUM(s.raised)::bigint AS raised,
+                SUM(s.acknowledged)::bigint AS acknowledged,
+                SUM(s.ack_seconds_total) / NULLIF(SUM(s.acknowledged), 0) / 60.0 AS mean_ack_minutes
+         FROM alert_daily_summary s
+         JOIN patients p ON p.id = s.patient_id
+         WHERE s.day BETWEEN $1 AND $2
+           AND ($3::uuid IS NULL OR p.ward_id = $3)
+           AND ($4::uuid IS NULL OR p.organization_id = $4)
+         GROUP BY s.kind, s.level
+         ORDER BY raised DESC, s.kind, s.level",
+        || crate::pg_args![from, to, query.ward_id, claims.org],
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(AlertSummaryReport {
+        ward_id: query.ward_id,
+        from,
+        to,
+        refreshed_at: refreshed_at(&state.reporting_pool, "alert_daily_summary").await?,
+        alerts,
+    }))
+}
+
+const ALERT_REPORT_COLUMNS: [&str; 14] = [
+    "alert_id",
+    "raised_at",
+    "patient_id",
+    "patient",
+    "kind",
+    "level",
+    "status",
+    "message",
+    "acknowledged_at",
+    "acknowledged_by",
+    "minutes_to_acknowledge",
+    "resolved_at",
+    "resolved_by",
+    "resolution",
+];
+
+/// Every alert raised in the range, as CSV or PDF
+async fn export_alerts(
+    state: &AppState,
+    claims: &AuthenticatedUser,
+    ward_id: Option<Uuid>,
+    from: NaiveDate,
+    to: NaiveDate,
+    format: ResponseFormat,
+) -> Result<HttpResponse, ApiError> {
+    let rows: Vec<AlertReportRow> = query_debug::fetch_all(
+        &state.reporting_pool,
+        &state.query_debug,
+        "alert_report",
+        "SELECT a.id, a.raised_at, a.patient_id, p.display_name AS patient_name, a.kind, a.level, a.status, a.message,
+                a.acknowledged_at, ack.email AS acknowledged_by,
+                EXTRACT(EPOCH FROM a.acknowledged_at - a.raised_at)::float8 / 60.0 AS ack_minutes,
+                a.resolved_at, res.email AS resolved_by, a.resolution
+         FROM alerts a
+         JOIN patients p ON p.id = a.patient_id
+         LEFT JOIN users ack ON ack.id = a.acknowledged_by
+         LEFT JOIN users res ON res.id = a.resolved_by
+         WHERE a.raised_at >= $1::date::timestamp AT TIME ZONE 'UTC'
+           AND a.raised_at < ($2::date + 1)::timestamp AT TIME ZONE 'UTC'
+           AND ($3::uuid IS NULL OR p.ward_id = $3)
+           AND ($4::uuid IS NULL OR p.organization_id = $4)
+         ORDER BY a.raised_at, a.id",
+        || crate::pg_args![from, to, ward_id, claims.org],
+    )
+    .await?;
+
+    crate::audit_log!("data_access", "alert_report", Some(claims.user_id), true, ward_id);
+    usage_service::record_in_background(&state.pool, None, UsageMetric::ExportsGenerated, 1);
+
+    let filename = format!("alerts-{}-{}", from.format("%Y%m%d"), to.format("%Y%m%d"));
+    Ok(match format {
+        ResponseFormat::Pdf => {
+            let profile = load_organization_profile(&state.pool).await?;
+            let ward_name: Option<String> = match ward_id {
+                Some(ward_id) => sqlx::query_scalar("SELECT name FROM wards WHERE id = $1")
+                    .bind(ward_id)
+                    .fetch_optional(&state.pool)
+                    .await?,
+                None => None,
+            };
+            HttpResponse::Ok()
+                .content_type(format.content_type())
+                .insert_header(("Content-Disposition", format!("attachment; filename=\"{}.pdf\"", filename)))
+                .body(render_pdf(&alert_report(&profile, ward_name.as_deref(), from, to, &rows)))
+        }
+        _ => {
+            let time = |t: Option<DateTime<Utc>>| t.map(|t| t.to_rfc3339()).unwrap_or_default();
+            let text = |t: &Option<String>| t.clone().unwrap_or_default();
+            let csv_rows: Vec<Vec<String>> = rows
+                .iter()
+                .map(|row| {
+                    vec![
+                        row.id.to_string(),
+                        row.raised_at.to_rfc3339(),
+                        row.patient_id.to_string(),
+                        row.patient_name.clone(),
+                        row.kind.clone(),
+                        row.level.to_string(),
+                        row.status.as_str().to_string(),
+                        row.message.clone(),
+                        time(row.acknowledged_at),
+                        text(&row.acknowledged_by),
+                        row.ack_minutes.map(|m| format!("{:.1}", m)).unwrap_or_default(),
+                        time(row.resolved_at),
+                        text(&row.resolved_by),
+                        text(&row.resolution),
+                    ]
+                })
+                .collect();
+            HttpResponse::Ok()
+                .content_type(format!("{}; charset=utf-8", format.content_type()))
+                .insert_header(("Content-Disposition", format!("attachment; filename=\"{}.csv\"", filename)))
+                .body(render_csv(&ALERT_REPORT_COLUMNS, &csv_rows))
+        }
+    })
+}
+
+fn alert_report(profile: &OrganizationProfile, ward_name: Option<&str>, from: NaiveDate, to: NaiveDate, rows: &[AlertReportRow]) -> Report {
+    let time = |t: &DateTime<Utc>| t.format("%d %b %H:%M").to_string();
+
+    let mut ack_minutes: Vec<f64> = rows.iter().filter_map(|row| row.ack_minutes).collect();
+    ack_minutes.sort_by(f64::total_cmp);
+    let resolved = rows.iter().filter(|row| row.status == AlertStatus::Resolved).count();
+    let mut summary = ReportSection::new("Summary");
+    summary.line(format!(
+        "{} alerts raised, {} acknowledged, {} resolved, {} still open.",
+        rows.len(),
+        ack_minutes.len(),
+        resolved,
+        rows.iter().filter(|row| row.status == AlertStatus::Open).count()
+    ));
+    if let Some(longest) = ack_minutes.last() {
+        let middle = ack_minutes.len() / 2;
+        let median = if ack_minutes.len().is_multiple_of(2) {
+            (ack_minutes[middle - 1] + ack_minutes[middle]) / 2.0
+        } else {
+            ack_minutes[middle]
+        };
+        summary.line(format!("Time to acknowledge: median {:.1} min, longest {:.1} min.", median, longest));
+    }
+    for level in AlertLevel::RAISED.iter().rev() {
+        let at_level: Vec<&AlertReportRow> = rows.iter().filter(|row| row.level == *level).collect();
+        if at_level.is_empty() {
+            continue;
+        }
+        let acknowledged: Vec<f64> = at_level.iter().filter_map(|row| row.ack_minutes).collect();
+        let mean = match acknowledged.len() {
+            0 => "-".to_string(),
+            n => format!("{:.1} min", acknowledged.iter().sum::<f64>() / n as f64),
+        };
+        summary.line(format!(
+            "{}: {} raised, {} acknowledged, mean time to acknowledge {}",
+            level,
+            at_level.len(),
+            acknowledged.len(),
+            mean
+        ));
+    }
+
+    let mut alerts = ReportSection::new("Alerts");
+    if rows.is_empty() {
+        alerts.line("No alerts were raised.");
+    }
+    for row in rows {
+        let acknowledged = match (&row.acknowledged_at, row.ack_minutes) {
+            (Some(at), Some(minutes)) => format!(
+                "acknowledged {} by {} after {:.1} min",
+                time(at),
+                row.acknowledged_by.as_deref().unwrap_or("unknown"),
+                minutes
+            ),
+            _ => "NOT ACKNOWLEDGED".to_string(),
+        };
+        let resolved = match &row.resolved_at {
+            Some(at) => format!(
+                "; resolved {} by {}{}",
+                time(at),
+                row.resolved_by.as_deref().unwrap_or("unknown"),
+                row.resolution.as_deref().map(|r| format!(": {}", r)).unwrap_or_default()
+            ),
+            None => String::new(),
+        };
+        alerts.line(format!(
+            "{} [{}] {} - {} - {} ({}{})",
+            time(&row.raised_at),
+            row.level,
+            row.kind,
+            row.patient_name,
+            row.message,
+            acknowledged,
+            resolved
+        ));
+    }
+
+    Report {
+        letterhead: Some(profile.into()),
+        title: "Alert report".to_string(),
+        subtitle: Some(format!(
+            "{} to {}, {}. All times UTC.",
+            from.format("%d %b %Y"),
+            to.format("%d %b %Y"),
+            ward_name.map_or("all wards".to_string(), |name| format!("ward {}", name))
+        )),
+        sections: vec![summary, alerts],
+    }
+}
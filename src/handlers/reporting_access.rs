@@ -0,0 +1,144 @@
+use crate::errors::ApiError;
+use crate::handlers::AppState;
+use crate::models::*;
+use crate::reporting_service::{bi_password, bi_role_name, create_bi_login, drop_bi_login};
+use crate::request_context::RequestContext;
+use actix_web::{web, HttpResponse};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use validator::Validate;
+
+// Mounted under /api/admin
+crate::routes::route_registry! {
+    "/reporting-credentials" {
+        GET => list_credentials, Jwt, ["admin"];
+        POST => issue_credential, Jwt, ["admin"];
+    }
+    "/reporting-credentials/{id}" {
+        DELETE => revoke_credential, Jwt, ["admin"];
+    }
+}
+
+/// Role DDL fails with `insufficient_privilege` unless the API's database user has CREATEROLE
+fn role_error(e: sqlx::Error) -> ApiError {
+    match &e {
+        sqlx::Error::Database(db) if db.code().as_deref() == Some("42501") => {
+            ApiError::Unavailable("The database user may not manage roles; grant it CREATEROLE".into())
+        }
+        _ => e.into(),
+    }
+}
+
+/// BI logins, newest first. Administrators tied to an organization see only its logins.
+pub async fn list_credentials(
+    ctx: RequestContext,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let credentials: Vec<ReportingCredential> = sqlx::query_as(
+        "SELECT * FROM reporting_credentials
+         WHERE $1::uuid IS NULL OR organization_id = $1
+         ORDER BY created_at DESC"
+    )
+    .bind(ctx.organization_id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(credentials))
+}
+
+/// Issue a read-only SQL login for a BI tool. It reads the `reporting` views and sees only
+/// the rows of its organization; the password is returned only in this response.
+pub async fn issue_credential(
+    ctx: RequestContext,
+    state: web::Data<AppState>,
+    body: web::Json<ReportingCredentialRequest>,
+) -> Result<HttpResponse, ApiError> {
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let label = body.label.trim();
+    if label.is_empty() {
+        return Err(ApiError::BadRequest("label is required".into()));
+    }
+    // Administrators of one organization can only hand out its own data
+    let organization_id = match (ctx.organization_id, body.organization_id) {
+        (Some(own), Some(requested)) if own != requested => {
+            return Err(ApiError::Forbidden("Cannot issue credentials for another organization".into()))
+        }
+        (Some(own), _) => Some(own),
+        (None, requested) => requested,
+    };
+    if let Some(organization_id) = organization_id {
+        let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM organizations WHERE id = $1)")
+            .bind(organization_id)
+            .fetch_one(&state.pool)
+            .await?;
+        if !exists {
+            return Err(ApiError::NotFound("Organization not found".into()));
+        }
+    }
+
+    let role_name = bi_role_name();
+    let password = bi_password();
+    let expires_at = Utc::now() + Duration::days(body.days.unwrap_or(state.reporting.credential_days));
+
+    let mut tx = state.pool.begin().await?;
+    let credential: ReportingCredential = sqlx::query_as(
+        "INSERT INTO reporting_credentials (role_name, organization_id, label, created_by, expires_at)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING *"
+    )
+    .bind(&role_name)
+    .bind(organization_id)
+    .bind(label)
+    .bind(ctx.user_id)
+    .bind(expires_at)
+    .fetch_one(&mut *tx)
+    .await?;
+    create_bi_login(&mut tx, &role_name, &password, expires_at).await.map_err(role_error)?;
+    let database: String = sqlx::query_scalar("SELECT current_database()::text")
+        .fetch_one(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    crate::audit_log!("reporting_credential", "issue", Some(ctx.user_id), true, credential.id);
+
+    Ok(HttpResponse::Created().json(IssuedReportingCredential {
+        credential,
+        password,
+        database,
+        schema: "reporting",
+    }))
+}
+
+/// Revoke a BI login: its open sessions see no rows from now on and the login is dropped
+pub async fn revoke_credential(
+    ctx: RequestContext,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+
+    let mut tx = state.pool.begin().await?;
+    let credential: Option<ReportingCredential> =
+        sqlx::query_as("SELECT * FROM reporting_credentials WHERE id = $1 FOR UPDATE")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?;
+    let credential = credential
+        .filter(|c| ctx.organization_id.is_none() || c.organization_id == ctx.organization_id)
+        .ok_or_else(|| ApiError::NotFound("Credential not found".into()))?;
+    if credential.revoked_at.is_some() {
+        return Err(ApiError::Conflict("Credential is already revoked".into()));
+    }
+
+    sqlx::query("UPDATE reporting_credentials SET revoked_at = now(), revoked_by = $2 WHERE id = $1")
+        .bind(id)
+        .bind(ctx.user_id)
+        .execute(&mut *tx)
+        .await?;
+    drop_bi_login(&mut tx, &credential.role_name).await.map_err(role_error)?;
+    tx.commit().await?;
+
+    crate::audit_log!("reporting_credential", "revoke", Some(ctx.user_id), true, id);
+
+    Ok(HttpResponse::NoContent().finish())
+}
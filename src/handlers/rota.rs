@@ -0,0 +1,181 @@
+use crate::alert_routing::ON_CALL_ROLES;
+use crate::errors::ApiError;
+use crate::handlers::on_call::{on_call_status, require_rota_scope};
+use crate::handlers::AppState;
+use crate::middleware::AuthenticatedUser;
+use crate::models::*;
+use actix_web::{web, HttpResponse};
+use uuid::Uuid;
+use validator::Validate;
+
+// Mounted under /api/admin
+crate::routes::route_registry! {
+    "/oncall" {
+        GET => get_rota, Jwt, ["admin"];
+    }
+    "/oncall/now" {
+        GET => get_on_call_now, Jwt, ["admin"];
+    }
+    "/oncall/rotations" {
+        POST => create_rotation, Jwt, ["admin"];
+    }
+    "/oncall/rotations/{id}" {
+        PUT => update_rotation, Jwt, ["admin"];
+        DELETE => delete_rotation, Jwt, ["admin"];
+    }
+    "/oncall/shifts/{id}" {
+        DELETE => delete_shift, Jwt, ["admin"];
+    }
+}
+
+/// Rotations and shifts on the caller's organization's wards; every one for users outside
+/// any organization
+const IN_ORGANIZATION: &str = "($1::uuid IS NULL OR ward_id IN (SELECT id FROM wards WHERE organization_id = $1))";
+
+/// Every member must be an active user of the caller's organization who can hold the
+/// on-call shift
+async fn check_rotation(state: &AppState, claims: &Claims, body: &OnCallRotationRequest) -> Result<(), ApiError> {
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    require_rota_scope(state, claims, body.ward_id).await?;
+
+    let eligible: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM users
+         WHERE id = ANY($1) AND is_active AND role = ANY($2) AND ($3::uuid IS NULL OR organization_id = $3)"
+    )
+    .bind(&body.members)
+    .bind(&ON_CALL_ROLES[..])
+    .bind(claims.org)
+    .fetch_one(&state.pool)
+    .await?;
+    let mut distinct = body.members.clone();
+    distinct.sort_unstable();
+    distinct.dedup();
+    if eligible as usize != distinct.len() {
+        return Err(ApiError::BadRequest("Every member must be an active clinician or admin".into()));
+    }
+    Ok(())
+}
+
+/// All rotations plus current and upcoming shifts
+pub async fn get_rota(admin: AuthenticatedUser, state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let rotations: Vec<OnCallRotation> = sqlx::query_as(&format!(
+        "SELECT * FROM on_call_rotations WHERE {} ORDER BY ward_id NULLS FIRST, name",
+        IN_ORGANIZATION
+    ))
+    .bind(admin.org)
+    .fetch_all(&state.pool)
+    .await?;
+    let shifts: Vec<OnCallShift> = sqlx::query_as(&format!(
+        "SELECT * FROM on_call_shifts WHERE ends_at > now() AND {} ORDER BY starts_at",
+        IN_ORGANIZATION
+    ))
+    .bind(admin.org)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(OnCallRota { rotations, shifts }))
+}
+
+pub async fn get_on_call_now(
+    admin: AuthenticatedUser,
+    state: web::Data<AppState>,
+    query: web::Query<OnCallQuery>,
+) -> Result<HttpResponse, ApiError> {
+    require_rota_scope(&state, &admin, query.ward_id).await?;
+    Ok(HttpResponse::Ok().json(on_call_status(&state, query.ward_id).await?))
+}
+
+pub async fn create_rotation(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    body: web::Json<OnCallRotationRequest>,
+) -> Result<HttpResponse, ApiError> {
+    check_rotation(&state, &claims, &body).await?;
+
+    let rotation: OnCallRotation = sqlx::query_as(
+        "INSERT INTO on_call_rotations (name, ward_id, starts_at, shift_hours, members, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         RETURNING *"
+    )
+    .bind(body.name.trim())
+    .bind(body.ward_id)
+    .bind(body.starts_at)
+    .bind(body.shift_hours)
+    .bind(&body.members)
+    .bind(claims.user_id)
+    .fetch_one(&state.pool)
+    .await?;
+
+    crate::audit_log!("on_call", "create_rotation", Some(claims.user_id), true, rotation.id);
+
+    Ok(HttpResponse::Created().json(rotation))
+}
+
+pub async fn update_rotation(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Json<OnCallRotationRequest>,
+) -> Result<HttpResponse, ApiError> {
+    check_rotation(&state, &claims, &body).await?;
+
+    let rotation: OnCallRotation = sqlx::query_as(&format!(
+        "UPDATE on_call_rotations SET name = $3, ward_id = $4, starts_at = $5, shift_hours = $6, members = $7
+         WHERE id = $2 AND {}
+         RETURNING *",
+        IN_ORGANIZATION
+    ))
+    .bind(claims.org)
+    .bind(*path)
+    .bind(body.name.trim())
+    .bind(body.ward_id)
+    .bind(body.starts_at)
+    .bind(body.shift_hours)
+    .bind(&body.members)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Rotation not found".into()))?;
+
+    crate::audit_log!("on_call", "update_rotation", Some(claims.user_id), true, rotation.id);
+
+    Ok(HttpResponse::Ok().json(rotation))
+}
+
+pub async fn delete_rotation(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let deleted = sqlx::query(&format!("DELETE FROM on_call_rotations WHERE id = $2 AND {}", IN_ORGANIZATION))
+        .bind(claims.org)
+        .bind(*path)
+        .execute(&state.pool)
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Rotation not found".into()));
+    }
+
+    crate::audit_log!("on_call", "delete_rotation", Some(claims.user_id), true, path.into_inner());
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Cancel a shift or override
+pub async fn delete_shift(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let deleted = sqlx::query(&format!("DELETE FROM on_call_shifts WHERE id = $2 AND {}", IN_ORGANIZATION))
+        .bind(claims.org)
+        .bind(*path)
+        .execute(&state.pool)
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Shift not found".into()));
+    }
+
+    crate::audit_log!("on_call", "delete_shift", Some(claims.user_id), true, path.into_inner());
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,18 @@
+use crate::middleware::AuthenticatedUser;
+use crate::routes::registered_routes;
+use actix_web::{HttpResponse, Responder};
+
+crate::routes::route_registry! {
+    "/routes" {
+        GET => list_routes, Jwt, ["admin"];
+    }
+}
+
+/// Every registered route with its methods, authentication and allowed roles
+pub async fn list_routes(_admin: AuthenticatedUser) -> impl Responder {
+    let routes = registered_routes();
+    HttpResponse::Ok().json(serde_json::json!({
+        "total": routes.len(),
+        "routes": routes,
+    }))
+}
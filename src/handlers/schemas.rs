@@ -0,0 +1,126 @@
+use crate::api_version::{ApiVersion, API_VERSION};
+use crate::errors::ApiError;
+use crate::ml_service::Explanation;
+use crate::models::*;
+use actix_web::{web, HttpRequest, HttpResponse};
+use schemars::schema::RootSchema;
+use schemars::schema_for;
+use serde::Serialize;
+
+crate::routes::route_registry! {
+    "/schemas" {
+        GET => list_schemas, Public, [];
+    }
+    "/schemas/{name}" {
+        GET => get_schema, Public, [];
+    }
+}
+
+type SchemaFn = fn() -> RootSchema;
+
+/// Published payload schemas by name: request bodies, and the responses devices and
+/// integrations consume. Generated from the models, so validation limits stay in sync.
+pub const SCHEMAS: &[(&str, SchemaFn)] = &[
+    ("signup_request", || schema_for!(SignupRequest)),
+    ("login_request", || schema_for!(LoginRequest)),
+    ("auth_response", || schema_for!(AuthResponse)),
+    ("preferences_request", || schema_for!(UpdatePreferencesRequest)),
+    ("notification_preferences_request", || schema_for!(UpdateNotificationPreferencesRequest)),
+    ("device_vitals_ingest", || schema_for!(DeviceVitalsIngest)),
+    ("device_event_ingest", || schema_for!(DeviceEventIngest)),
+    ("mqtt_vitals_message", || schema_for!(MqttVitalsMessage)),
+    ("gateway_vitals_ingest", || schema_for!(GatewayVitalsIngest)),
+    ("gateway_decision_request", || schema_for!(GatewayDecisionRequest)),
+    ("device_claim_request", || schema_for!(DeviceClaimRequest)),
+    ("device_claim_response", || schema_for!(DeviceClaimResponse)),
+    ("pairing_code_response", || schema_for!(PairingCodeResponse)),
+    ("device_registration", || schema_for!(DeviceRegistration)),
+    ("device_update", || schema_for!(DeviceUpdate)),
+    ("device_assignment", || schema_for!(DeviceAssignment)),
+    ("latest_vitals", || schema_for!(LatestVitals)),
+    ("ml_alert", || schema_for!(MlAlert)),
+    ("alert_explanation", || schema_for!(Explanation)),
+    ("threshold_profile_request", || schema_for!(ThresholdProfileRequest)),
+    ("patient_threshold_profile_request", || schema_for!(PatientThresholdProfileRequest)),
+    ("ml_rule_request", || schema_for!(MlRuleRequest)),
+    ("ml_simulation_request", || schema_for!(MlSimulationRequest)),
+    ("patient_request", || schema_for!(PatientRequest)),
+    ("patient_update", || schema_for!(PatientUpdate)),
+    ("patient_attributes", || schema_for!(PatientAttributes)),
+    ("care_plan_request", || schema_for!(CarePlanRequest)),
+    ("medication_request", || schema_for!(MedicationRequest)),
+    ("dose_confirm_request", || schema_for!(DoseConfirmRequest)),
+    ("checkin_request", || schema_for!(CheckinRequest)),
+    ("emergency_contact_request", || schema_for!(EmergencyContactRequest)),
+    ("alert_message_request", || schema_for!(AlertMessageRequest)),
+    ("resolve_alert_request", || schema_for!(ResolveAlertRequest)),
+    ("on_call_override_request", || schema_for!(OnCallOverrideRequest)),
+    ("on_call_rotation_request", || schema_for!(OnCallRotationRequest)),
+    ("ward_request", || schema_for!(WardRequest)),
+    ("legal_hold_request", || schema_for!(LegalHoldRequest)),
+    ("archive_patient_request", || schema_for!(ArchivePatientRequest)),
+    ("patient_transfer_request", || schema_for!(PatientTransferRequest)),
+    ("transfer_decision_request", || schema_for!(TransferDecisionRequest)),
+    ("organization_request", || schema_for!(OrganizationRequest)),
+    ("organization_profile_request", || schema_for!(OrganizationProfileRequest)),
+    ("quota_request", || schema_for!(QuotaRequest)),
+    ("reporting_credential_request", || schema_for!(ReportingCredentialRequest)),
+    ("integration_request", || schema_for!(IntegrationRequest)),
+    ("analytics_query", || schema_for!(AnalyticsQuery)),
+];
+
+#[derive(Debug, Serialize)]
+struct SchemaLink {
+    name: &'static str,
+    href: String,
+}
+
+/// Names of all published schemas with their URLs
+pub async fn list_schemas() -> HttpResponse {
+    let links: Vec<SchemaLink> = SCHEMAS
+        .iter()
+        .map(|(name, _)| SchemaLink { name, href: format!("/api/schemas/{}", name) })
+        .collect();
+    HttpResponse::Ok().json(links)
+}
+
+/// JSON Schema (draft-07) for one payload, so firmware and partners can validate
+/// before sending; field names follow the requested `API-Version`
+pub async fn get_schema(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, ApiError> {
+    let version = ApiVersion::from_request(&req)?;
+    let (_, schema) = SCHEMAS
+        .iter()
+        .find(|(name, _)| *name == path.as_str())
+        .ok_or_else(|| ApiError::NotFound(format!("No schema named '{}'", path)))?;
+    let schema = serde_json::to_value(schema()).map_err(|e| ApiError::Internal(e.to_string()))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/schema+json")
+        .insert_header((API_VERSION, version.number().to_string()))
+        .json(version.adapt_schema(schema)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schemas_carry_validation_limits() {
+        let schema = serde_json::to_value(schema_for!(DeviceVitalsIngest)).unwrap();
+        let properties = &schema["properties"];
+        assert_eq!(properties["heartRate"]["minimum"], 0.0);
+        assert_eq!(properties["heartRate"]["maximum"], 300.0);
+        assert!(properties.get("elevationChange").is_some());
+        let required: Vec<&str> = schema["required"].as_array().unwrap().iter().filter_map(|v| v.as_str()).collect();
+        assert!(required.contains(&"timestamp"));
+        assert!(!required.contains(&"steps"));
+    }
+
+    #[test]
+    fn test_schema_names_are_unique() {
+        let mut names: Vec<&str> = SCHEMAS.iter().map(|(name, _)| *name).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), SCHEMAS.len());
+    }
+}
@@ -0,0 +1,214 @@
+use crate::errors::ApiError;
+use crate::handlers::patients::{require_active_patient, require_patient_access};
+use crate::handlers::{can_manage_care, require_unscoped, AppState};
+use crate::middleware::AuthenticatedUser;
+use crate::ml_service::default_delta_rules;
+use crate::models::*;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+
+crate::routes::route_registry! {
+    "/threshold-profiles" {
+        GET => list_profiles, Jwt, [];
+        POST => create_profile, Jwt, ["admin"];
+    }
+    "/threshold-profiles/{id}" {
+        GET => get_profile, Jwt, [];
+    }
+    "/threshold-profiles/{id}/rules" {
+        PUT => update_rules, Jwt, ["admin"];
+    }
+    "/patients/{patient_id}/threshold-profile" {
+        GET => get_patient_thresholds, Jwt, [];
+        PUT => assign_profile, Jwt, [];
+    }
+}
+
+/// Profiles joined with their latest rules version
+const CURRENT_PROFILES: &str =
+    "SELECT p.id, p.name, p.description, p.created_at, v.id AS version_id, v.version, v.rules
+     FROM threshold_profiles p
+     JOIN LATERAL (SELECT * FROM threshold_profile_versions
+                   WHERE profile_id = p.id ORDER BY version DESC LIMIT 1) v ON true";
+
+fn check_rules(rules: &ThresholdRules) -> Result<(), ApiError> {
+    rules.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    if rules.hr_low >= rules.hr_high {
+        return Err(ApiError::BadRequest("hr_low must be below hr_high".into()));
+    }
+    if rules.hypothermia_temperature >= rules.fever_temperature {
+        return Err(ApiError::BadRequest("hypothermia_temperature must be below fever_temperature".into()));
+    }
+    Ok(())
+}
+
+async fn load_profile(pool: &PgPool, id: Uuid) -> Result<ThresholdProfile, ApiError> {
+    sqlx::query_as(&format!("{} WHERE p.id = $1", CURRENT_PROFILES))
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Threshold profile not found".into()))
+}
+
+/// The profile (with its current rules version) assigned to a patient, if any
+pub(crate) async fn patient_profile(pool: &PgPool, patient_id: Uuid) -> Result<Option<ThresholdProfile>, sqlx::Error> {
+    sqlx::query_as(&format!(
+        "{} JOIN patients pt ON pt.threshold_profile_id = p.id WHERE pt.id = $1",
+        CURRENT_PROFILES
+    ))
+    .bind(patient_id)
+    .fetch_optional(pool)
+    .await
+}
+
+async fn effective_thresholds(state: &AppState, patient_id: Uuid) -> Result<EffectiveThresholds, sqlx::Error> {
+    let profile = patient_profile(&state.pool, patient_id).await?;
+    let mut rules = match &profile {
+        Some(profile) => profile.rules.0.clone(),
+        None => state.ml_service.default_rules(),
+    };
+    rules.delta_rules.get_or_insert_with(default_delta_rules);
+    Ok(EffectiveThresholds { patient_id, profile, rules })
+}
+
+// ============ Profiles ============
+
+pub async fn list_profiles(_user: AuthenticatedUser, state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let profiles: Vec<ThresholdProfile> = sqlx::query_as(&format!("{} ORDER BY p.name", CURRENT_PROFILES))
+        .fetch_all(&state.pool)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(profiles))
+}
+
+pub async fn create_profile(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    body: web::Json<ThresholdProfileRequest>,
+) -> Result<HttpResponse, ApiError> {
+    require_unscoped(&claims)?;
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    check_rules(&body.rules)?;
+
+    let mut tx = state.pool.begin().await?;
+    let id: Option<Uuid> = sqlx::query_scalar(
+        "INSERT INTO threshold_profiles (name, description) VALUES ($1, $2) ON CONFLICT (name) DO NOTHING RETURNING id"
+    )
+    .bind(body.name.trim())
+    .bind(&body.description)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let id = id.ok_or_else(|| ApiError::Conflict("A threshold profile with this name already exists".into()))?;
+
+    sqlx::query(
+        "INSERT INTO threshold_profile_versions (profile_id, version, rules, created_by) VALUES ($1, 1, $2, $3)"
+    )
+    .bind(id)
+    .bind(sqlx::types::Json(&body.rules))
+    .bind(claims.user_id)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    crate::audit_log!("threshold_profile", "create", Some(claims.user_id), true, id);
+
+    Ok(HttpResponse::Created().json(load_profile(&state.pool, id).await?))
+}
+
+/// A profile with its full version history
+pub async fn get_profile(
+    _user: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let profile = load_profile(&state.pool, path.into_inner()).await?;
+
+    let versions: Vec<ThresholdProfileVersion> = sqlx::query_as(
+        "SELECT * FROM threshold_profile_versions WHERE profile_id = $1 ORDER BY version DESC"
+    )
+    .bind(profile.id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ThresholdProfileHistory { profile, versions }))
+}
+
+/// Publish new rules as the next version; earlier versions stay for traceability
+pub async fn update_rules(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Json<ThresholdRules>,
+) -> Result<HttpResponse, ApiError> {
+    require_unscoped(&claims)?;
+    check_rules(&body)?;
+
+    let mut tx = state.pool.begin().await?;
+    // Lock the profile so concurrent updates get consecutive version numbers
+    let exists: Option<Uuid> = sqlx::query_scalar("SELECT id FROM threshold_profiles WHERE id = $1 FOR UPDATE")
+        .bind(*path)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let id = exists.ok_or_else(|| ApiError::NotFound("Threshold profile not found".into()))?;
+
+    sqlx::query(
+        "INSERT INTO threshold_profile_versions (profile_id, version, rules, created_by)
+         SELECT $1, COALESCE(MAX(version), 0) + 1, $2, $3 FROM threshold_profile_versions WHERE profile_id = $1"
+    )
+    .bind(id)
+    .bind(sqlx::types::Json(&*body))
+    .bind(claims.user_id)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    crate::audit_log!("threshold_profile", "update_rules", Some(claims.user_id), true, id);
+
+    Ok(HttpResponse::Ok().json(load_profile(&state.pool, id).await?))
+}
+
+// ============ Patient Assignment ============
+
+pub async fn get_patient_thresholds(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    require_patient_access(&state, &claims, patient_id).await?;
+
+    Ok(HttpResponse::Ok().json(effective_thresholds(&state, patient_id).await?))
+}
+
+pub async fn assign_profile(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Json<PatientThresholdProfileRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    if !can_manage_care(&state, &claims, Some(patient_id)).await? {
+        return Err(ApiError::Forbidden("Clinician role required".into()));
+    }
+    require_patient_access(&state, &claims, patient_id).await?;
+    require_active_patient(&state.pool, patient_id).await?;
+
+    if let Some(profile_id) = body.profile_id {
+        load_profile(&state.pool, profile_id).await?;
+    }
+
+    let updated = sqlx::query("UPDATE patients SET threshold_profile_id = $2 WHERE id = $1")
+        .bind(patient_id)
+        .bind(body.profile_id)
+        .execute(&state.pool)
+        .await?;
+    if updated.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Patient not found".into()));
+    }
+
+    crate::audit_log!("threshold_profile", "assign", Some(claims.user_id), true, patient_id);
+
+    Ok(HttpResponse::Ok().json(effective_thresholds(&state, patient_id).await?))
+}
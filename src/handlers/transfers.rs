@@ -0,0 +1,497 @@
+use crate::errors::ApiError;
+use crate::handlers::patients::require_patient_access;
+use crate::handlers::{can_manage_care, AppState};
+use crate::middleware::AuthenticatedUser;
+use crate::models::*;
+use crate::rbac::{has_role, Role};
+use actix_web::{web, HttpResponse};
+use sqlx::{Postgres, Transaction};
+use tracing::warn;
+use uuid::Uuid;
+use validator::Validate;
+
+crate::routes::route_registry! {
+    "/patients/{patient_id}/transfers" {
+        GET => list_patient_transfers, Jwt, [];
+        POST => request_transfer, Jwt, ["admin", "clinician"];
+    }
+    "/transfers" {
+        GET => list_transfers, Jwt, ["admin", "clinician"];
+    }
+    "/transfers/{id}" {
+        GET => get_transfer, Jwt, ["admin", "clinician"];
+    }
+    "/transfers/{id}/confirm" {
+        POST => confirm_transfer, Jwt, ["admin", "clinician"];
+    }
+    "/transfers/{id}/reject" {
+        POST => reject_transfer, Jwt, ["admin", "clinician"];
+    }
+    "/transfers/{id}/cancel" {
+        POST => cancel_transfer, Jwt, ["admin", "clinician"];
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct TransferListQuery {
+    /// `pending` (default), `completed`, `rejected`, `cancelled` or `all`
+    pub status: Option<String>,
+}
+
+/// One side of a transfer: the organization the patient leaves or joins
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Side {
+    Source,
+    Target,
+}
+
+/// Which side the caller may act for. Each side is represented by its own staff; a patient
+/// outside any organization is represented by an administrator outside any organization.
+async fn side_of(
+    state: &AppState,
+    claims: &Claims,
+    organization_id: Option<Uuid>,
+    transfer: &PatientTransfer,
+) -> Result<Option<Side>, ApiError> {
+    if !can_manage_care(state, claims, None).await? {
+        return Ok(None);
+    }
+    Ok(match organization_id {
+        Some(org) if transfer.from_organization_id == Some(org) => Some(Side::Source),
+        Some(org) if transfer.to_organization_id == org => Some(Side::Target),
+        None if transfer.from_organization_id.is_none() && has_role(claims, Role::Admin) => {
+            Some(Side::Source)
+        }
+        _ => None,
+    })
+}
+
+async fn load_transfer(state: &AppState, id: Uuid) -> Result<PatientTransfer, ApiError> {
+    sqlx::query_as("SELECT * FROM patient_transfers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Transfer not found".into()))
+}
+
+async fn with_events(state: &AppState, transfer: PatientTransfer) -> Result<PatientTransferWithEvents, ApiError> {
+    let events: Vec<PatientTransferEvent> =
+        sqlx::query_as("SELECT * FROM patient_transfer_events WHERE transfer_id = $1 ORDER BY id")
+            .bind(transfer.id)
+            .fetch_all(&state.pool)
+            .await?;
+    Ok(PatientTransferWithEvents { transfer, events })
+}
+
+async fn record_event(
+    tx: &mut Transaction<'_, Postgres>,
+    transfer_id: Uuid,
+    action: &str,
+    organization_id: Option<Uuid>,
+    claims: Option<&Claims>,
+    note: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO patient_transfer_events (transfer_id, action, organization_id, actor_id, actor_email, note)
+         VALUES ($1, $2, $3, $4, $5, $6)"
+    )
+    .bind(transfer_id)
+    .bind(action)
+    .bind(organization_id)
+    .bind(claims.map(|c| c.user_id))
+    .bind(claims.map(|c| c.sub.as_str()))
+    .bind(note)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Lock a pending transfer for a decision and work out which side the caller stands for
+async fn lock_pending(
+    tx: &mut Transaction<'_, Postgres>,
+    state: &AppState,
+    claims: &Claims,
+    id: Uuid,
+) -> Result<(PatientTransfer, Side, Option<Uuid>), ApiError> {
+    let transfer: PatientTransfer = sqlx::query_as("SELECT * FROM patient_transfers WHERE id = $1 FOR UPDATE")
+        .bind(id)
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Transfer not found".into()))?;
+    let organization_id = claims.org;
+    let side = side_of(state, claims, organization_id, &transfer)
+        .await?
+        .ok_or_else(|| ApiError::Forbidden("Only staff of the two organizations may act on a transfer".into()))?;
+    if transfer.status != "pending" {
+        return Err(ApiError::Conflict(format!("Transfer is already {}", transfer.status)));
+    }
+    Ok((transfer, side, organization_id))
+}
+
+/// Move the patient to the target organization with their walkers. Their readings, alerts,
+/// plans, medications and check-ins are keyed by patient and go with them; links to the source
+/// organization's caregivers and its ward are dropped, and the target's confirmer becomes a
+/// caregiver.
+async fn complete(tx: &mut Transaction<'_, Postgres>, transfer: &PatientTransfer) -> Result<serde_json::Value, sqlx::Error> {
+    let patient_id = transfer.patient_id;
+    sqlx::query("UPDATE patients SET organization_id = $2, ward_id = NULL WHERE id = $1")
+        .bind(patient_id)
+        .bind(transfer.to_organization_id)
+        .execute(&mut **tx)
+        .await?;
+    let devices = sqlx::query("UPDATE devices SET organization_id = $2 WHERE patient_id = $1")
+        .bind(patient_id)
+        .bind(transfer.to_organization_id)
+        .execute(&mut **tx)
+        .await?;
+    let caregivers = sqlx::query(
+        "DELETE FROM patient_caregivers pc USING users u
+         WHERE pc.patient_id = $1 AND u.id = pc.user_id AND u.organization_id = $2"
+    )
+    .bind(patient_id)
+    .bind(transfer.from_organization_id)
+    .execute(&mut **tx)
+    .await?;
+    // Whoever confirmed for the new organization takes the patient over
+    if let Some(receiver) = transfer.target_confirmed_by {
+        sqlx::query("INSERT INTO patient_caregivers (patient_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+            .bind(patient_id)
+            .bind(receiver)
+            .execute(&mut **tx)
+            .await?;
+    }
+
+    let (readings, alerts, care_plans, medications, checkins): (i64, i64, i64, i64, i64) = sqlx::query_as(
+        "SELECT
+            (SELECT COUNT(*) FROM sensor_readings r JOIN devices d ON d.id = r.device_id WHERE d.patient_id = $1),
+            (SELECT COUNT(*) FROM alerts WHERE patient_id = $1),
+            (SELECT COUNT(*) FROM care_plans WHERE patient_id = $1),
+            (SELECT COUNT(*) FROM medications WHERE patient_id = $1),
+            (SELECT COUNT(*) FROM checkins WHERE patient_id = $1)"
+    )
+    .bind(patient_id)
+    .fetch_one(&mut **tx)
+    .await?;
+    let moved = serde_json::json!({
+        "readings": readings,
+        "alerts": alerts,
+        "care_plans": care_plans,
+        "medications": medications,
+        "checkins": checkins,
+        "devices": devices.rows_affected(),
+        "caregiver_links_removed": caregivers.rows_affected(),
+    });
+
+    sqlx::query("UPDATE patient_transfers SET status = 'completed', closed_at = now(), moved = $2 WHERE id = $1")
+        .bind(transfer.id)
+        .bind(&moved)
+        .execute(&mut **tx)
+        .await?;
+    Ok(moved)
+}
+
+/// Re-seal the patient's check-in notes under the new organization's key, so they stay
+/// readable if the old organization's keys are later shredded. Failures are logged and
+/// leave the note sealed as it was.
+async fn reseal_notes(state: &AppState, patient_id: Uuid) {
+    if !state.phi.enabled() {
+        return;
+    }
+    let notes: Vec<(Uuid, String)> = match sqlx::query_as(
+        "SELECT id, notes FROM checkins WHERE patient_id = $1 AND notes IS NOT NULL"
+    )
+    .bind(patient_id)
+    .fetch_all(&state.pool)
+    .await
+    {
+        Ok(notes) => notes,
+        Err(e) => {
+            warn!("Could not load check-in notes to re-seal for patient {}: {}", patient_id, e);
+            return;
+        }
+    };
+    for (id, sealed) in notes {
+        let resealed = match state.phi.open(&sealed).await {
+            Ok(Some(plaintext)) => state.phi.seal_for_patient(patient_id, &plaintext).await,
+            Ok(None) => continue,
+            Err(e) => Err(e),
+        };
+        let result = match resealed {
+            Ok(value) => sqlx::query("UPDATE checkins SET notes = $2 WHERE id = $1")
+                .bind(id)
+                .bind(value)
+                .execute(&state.pool)
+                .await
+                .map(|_| ())
+                .map_err(anyhow::Error::from),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            warn!("Could not re-seal check-in {} after transfer: {:#}", id, e);
+        }
+    }
+}
+
+// ============ Handlers ============
+
+/// Ask to move a patient to another organization. Staff of either organization may ask;
+/// their side counts as confirmed and the other side still has to confirm.
+pub async fn request_transfer(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Json<PatientTransferRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let reason = body.reason.as_deref().map(str::trim).filter(|r| !r.is_empty());
+
+    let from_organization_id: Option<Uuid> = sqlx::query_scalar("SELECT organization_id FROM patients WHERE id = $1")
+        .bind(patient_id)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Patient not found".into()))?;
+    if from_organization_id == Some(body.to_organization_id) {
+        return Err(ApiError::BadRequest("Patient already belongs to that organization".into()));
+    }
+    let target_exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM organizations WHERE id = $1)")
+        .bind(body.to_organization_id)
+        .fetch_one(&state.pool)
+        .await?;
+    if !target_exists {
+        return Err(ApiError::NotFound("Organization not found".into()));
+    }
+
+    let draft = PatientTransfer {
+        id: Uuid::nil(),
+        patient_id,
+        from_organization_id,
+        to_organization_id: body.to_organization_id,
+        status: "pending".into(),
+        reason: None,
+        requested_by: None,
+        requested_at: chrono::Utc::now(),
+        source_confirmed_by: None,
+        source_confirmed_at: None,
+        target_confirmed_by: None,
+        target_confirmed_at: None,
+        closed_at: None,
+        moved: None,
+    };
+    let organization_id = claims.org;
+    let side = side_of(&state, &claims, organization_id, &draft)
+        .await?
+        .ok_or_else(|| ApiError::Forbidden("Only staff of the two organizations may request a transfer".into()))?;
+    if side == Side::Source {
+        require_patient_access(&state, &claims, patient_id).await?;
+    }
+
+    let mut tx = state.pool.begin().await?;
+    let (source_by, target_by) = match side {
+        Side::Source => (Some(claims.user_id), None),
+        Side::Target => (None, Some(claims.user_id)),
+    };
+    let id: Uuid = sqlx::query_scalar(
+        "INSERT INTO patient_transfers
+            (patient_id, from_organization_id, to_organization_id, reason, requested_by,
+             source_confirmed_by, source_confirmed_at, target_confirmed_by, target_confirmed_at)
+         VALUES ($1, $2, $3, $4, $5, $6, CASE WHEN $6 IS NULL THEN NULL ELSE now() END,
+                 $7, CASE WHEN $7 IS NULL THEN NULL ELSE now() END)
+         ON CONFLICT (patient_id) WHERE status = 'pending' DO NOTHING
+         RETURNING id"
+    )
+    .bind(patient_id)
+    .bind(from_organization_id)
+    .bind(body.to_organization_id)
+    .bind(reason)
+    .bind(claims.user_id)
+    .bind(source_by)
+    .bind(target_by)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::Conflict("Patient already has a transfer in progress".into()))?;
+    record_event(&mut tx, id, "requested", organization_id, Some(&claims), reason).await?;
+    tx.commit().await?;
+
+    crate::audit_log!("patient_transfer", "request", Some(claims.user_id), true, id);
+
+    Ok(HttpResponse::Created().json(with_events(&state, load_transfer(&state, id).await?).await?))
+}
+
+/// Confirm for the caller's side. Once both sides have, the patient moves in the same step.
+pub async fn confirm_transfer(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: Option<web::Json<TransferDecisionRequest>>,
+) -> Result<HttpResponse, ApiError> {
+    let body = body.map(|b| b.into_inner()).unwrap_or_default();
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let id = path.into_inner();
+
+    let mut tx = state.pool.begin().await?;
+    let (transfer, side, organization_id) = lock_pending(&mut tx, &state, &claims, id).await?;
+    let already = match side {
+        Side::Source => transfer.source_confirmed_at.is_some(),
+        Side::Target => transfer.target_confirmed_at.is_some(),
+    };
+    if already {
+        return Err(ApiError::Conflict("Your organization has already confirmed this transfer".into()));
+    }
+
+    let column = match side {
+        Side::Source => "source",
+        Side::Target => "target",
+    };
+    let transfer: PatientTransfer = sqlx::query_as(&format!(
+        "UPDATE patient_transfers SET {column}_confirmed_by = $2, {column}_confirmed_at = now()
+         WHERE id = $1 RETURNING *"
+    ))
+    .bind(id)
+    .bind(claims.user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    record_event(&mut tx, id, "confirmed", organization_id, Some(&claims), body.note.as_deref()).await?;
+
+    let completed = transfer.source_confirmed_at.is_some() && transfer.target_confirmed_at.is_some();
+    if completed {
+        let moved = complete(&mut tx, &transfer).await?;
+        record_event(&mut tx, id, "completed", Some(transfer.to_organization_id), None, Some(&moved.to_string())).await?;
+    }
+    tx.commit().await?;
+
+    crate::audit_log!("patient_transfer", "confirm", Some(claims.user_id), true, id);
+    if completed {
+        crate::audit_log!("patient", "transfer", Some(claims.user_id), true, transfer.patient_id);
+        reseal_notes(&state, transfer.patient_id).await;
+    }
+
+    Ok(HttpResponse::Ok().json(with_events(&state, load_transfer(&state, id).await?).await?))
+}
+
+/// Decline a transfer for the caller's side; the patient stays where they are
+pub async fn reject_transfer(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: Option<web::Json<TransferDecisionRequest>>,
+) -> Result<HttpResponse, ApiError> {
+    close(claims, state, path.into_inner(), body, "rejected").await
+}
+
+/// Withdraw a transfer the caller's organization asked for
+pub async fn cancel_transfer(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: Option<web::Json<TransferDecisionRequest>>,
+) -> Result<HttpResponse, ApiError> {
+    close(claims, state, path.into_inner(), body, "cancelled").await
+}
+
+async fn close(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    id: Uuid,
+    body: Option<web::Json<TransferDecisionRequest>>,
+    status: &str,
+) -> Result<HttpResponse, ApiError> {
+    let body = body.map(|b| b.into_inner()).unwrap_or_default();
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    let mut tx = state.pool.begin().await?;
+    let (transfer, side, organization_id) = lock_pending(&mut tx, &state, &claims, id).await?;
+    // Rejecting is the other side's answer; cancelling is the asking side taking it back
+    let requesting_side = match (transfer.source_confirmed_at, transfer.target_confirmed_at) {
+        (Some(_), None) => Side::Source,
+        _ => Side::Target,
+    };
+    if status == "rejected" && side == requesting_side {
+        return Err(ApiError::Conflict("The requesting organization cancels rather than rejects".into()));
+    }
+    if status == "cancelled" && side != requesting_side {
+        return Err(ApiError::Conflict("Only the requesting organization may cancel; reject instead".into()));
+    }
+
+    sqlx::query("UPDATE patient_transfers SET status = $2, closed_at = now() WHERE id = $1")
+        .bind(id)
+        .bind(status)
+        .execute(&mut *tx)
+        .await?;
+    record_event(&mut tx, id, status, organization_id, Some(&claims), body.note.as_deref()).await?;
+    tx.commit().await?;
+
+    let action = if status == "rejected" { "reject" } else { "cancel" };
+    crate::audit_log!("patient_transfer", action, Some(claims.user_id), true, id);
+
+    Ok(HttpResponse::Ok().json(with_events(&state, load_transfer(&state, id).await?).await?))
+}
+
+/// Transfers of a patient, newest first, each with its log
+pub async fn list_patient_transfers(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let patient_id = path.into_inner();
+    require_patient_access(&state, &claims, patient_id).await?;
+
+    let transfers: Vec<PatientTransfer> =
+        sqlx::query_as("SELECT * FROM patient_transfers WHERE patient_id = $1 ORDER BY requested_at DESC")
+            .bind(patient_id)
+            .fetch_all(&state.pool)
+            .await?;
+    let mut out = Vec::with_capacity(transfers.len());
+    for transfer in transfers {
+        out.push(with_events(&state, transfer).await?);
+    }
+    Ok(HttpResponse::Ok().json(out))
+}
+
+/// Transfers in or out of the caller's organization (all of them for an administrator
+/// outside any organization), newest first
+pub async fn list_transfers(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    query: web::Query<TransferListQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let status = query.status.as_deref().unwrap_or("pending");
+    if !["pending", "completed", "rejected", "cancelled", "all"].contains(&status) {
+        return Err(ApiError::BadRequest(
+            "status must be pending, completed, rejected, cancelled or all".into(),
+        ));
+    }
+    let organization_id = claims.org;
+    if organization_id.is_none() && !has_role(&claims, Role::Admin) {
+        return Ok(HttpResponse::Ok().json(Vec::<PatientTransfer>::new()));
+    }
+
+    let transfers: Vec<PatientTransfer> = sqlx::query_as(
+        "SELECT * FROM patient_transfers
+         WHERE ($1::uuid IS NULL OR from_organization_id = $1 OR to_organization_id = $1)
+           AND ($2 = 'all' OR status = $2)
+         ORDER BY requested_at DESC LIMIT 200"
+    )
+    .bind(organization_id)
+    .bind(status)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(transfers))
+}
+
+/// One transfer with its log, for staff of either organization
+pub async fn get_transfer(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let transfer = load_transfer(&state, path.into_inner()).await?;
+    let organization_id = claims.org;
+    let platform_admin = organization_id.is_none() && has_role(&claims, Role::Admin);
+    if !platform_admin && side_of(&state, &claims, organization_id, &transfer).await?.is_none() {
+        return Err(ApiError::Forbidden("Only staff of the two organizations may view a transfer".into()));
+    }
+
+    Ok(HttpResponse::Ok().json(with_events(&state, transfer).await?))
+}
@@ -0,0 +1,287 @@
+use crate::api_version::ApiVersion;
+use crate::errors::ApiError;
+use crate::handlers::patients::{require_patient_access, vitals_aggregate};
+use crate::handlers::AppState;
+use crate::middleware::AuthenticatedUser;
+use crate::models::*;
+use crate::negotiation::{negotiate, ResponseFormat};
+use crate::query_debug;
+use crate::sse::{self, StreamViewer};
+use crate::ws;
+use actix_web::{error::ErrorInternalServerError, web, HttpRequest, HttpResponse};
+use async_stream::try_stream;
+use chrono::{DateTime, Duration, Utc};
+use futures::{Stream, TryStreamExt};
+use sqlx::PgPool;
+use std::collections::HashSet;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::error;
+use uuid::Uuid;
+use validator::Validate;
+
+crate::routes::route_registry! {
+    "/vitals/latest" {
+        GET => get_latest_vitals, Jwt, [];
+    }
+    "/vitals/poll" {
+        GET => poll_vitals, Jwt, [];
+    }
+    "/vitals/history" {
+        GET => get_vitals_history, Jwt, [];
+    }
+    "/vitals/aggregate" {
+        GET => get_vitals_aggregate, Jwt, [];
+    }
+    "/stream/vitals" {
+        GET => sse::stream_vitals, Jwt, [];
+    }
+    "/ws/vitals" {
+        GET => ws::stream_vitals, Jwt, [];
+    }
+}
+
+/// The newest vitals, optionally waiting for a reading at least as fresh as `min_timestamp`.
+///
+/// A device that has just sent a reading (over MQTT, say, where it's queued) passes the
+/// reading's timestamp to see it reflected. When nothing that fresh arrives within
+/// `wait_seconds` the newest vitals available are returned anyway.
+///
+/// Only readings of patients the caller may see count, as on the streams; admins outside
+/// any organization get the deployment's newest.
+pub async fn get_latest_vitals(
+    req: HttpRequest,
+    user: AuthenticatedUser,
+    state: web::Data<AppState>,
+    query: web::Query<LatestVitalsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    query.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let version = ApiVersion::from_request(&req)?;
+    let viewer = StreamViewer::load(&state, user.into_inner()).await?;
+
+    // Subscribe before reading the cache so a reading stored in between isn't missed
+    let mut updates = state.sse_broadcaster.subscribe();
+    let latest = current_latest_vitals(&state, viewer.patients()).await;
+    let Some(min_timestamp) = query.min_timestamp.filter(|min| latest.timestamp < *min) else {
+        return Ok(version.json(HttpResponse::Ok(), &latest));
+    };
+
+    let wait = std::time::Duration::from_secs(query.wait_seconds.unwrap_or(DEFAULT_LATEST_WAIT_SECONDS));
+    let fresher = tokio::time::timeout(wait, async {
+        loop {
+            match updates.recv().await {
+                Ok(event) if viewer.may_see(&event) => match &event.event {
+                    SseEvent::Vitals(vitals) if vitals.timestamp >= min_timestamp => return Some(vitals.clone()),
+                    _ => continue,
+                },
+                Ok(_) => continue,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+    .await
+    .ok()
+    .flatten();
+
+    let vitals = match fresher {
+        Some(vitals) => vitals,
+        None => current_latest_vitals(&state, viewer.patients()).await,
+    };
+    Ok(version.json(HttpResponse::Ok(), &vitals))
+}
+
+/// Long-polling fallback for `/api/stream/vitals`, for clients behind proxies that cut SSE.
+///
+/// Returns the events broadcast after `since` straight away, or else waits up to
+/// `wait_seconds` for the next one. An empty `events` means nothing happened; poll again
+/// with the returned `cursor` either way.
+pub async fn poll_vitals(
+    user: AuthenticatedUser,
+    state: web::Data<AppState>,
+    query: web::Query<VitalsPollQuery>,
+) -> Result<HttpResponse, ApiError> {
+    query.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let viewer = StreamViewer::load(&state, user.into_inner()).await?;
+
+    // Subscribe first so nothing broadcast while the kept events are read is missed
+    let mut updates = state.sse_broadcaster.subscribe();
+    let mut events = match query.since {
+        Some(since) => state.recent_events.since(since),
+        None => Vec::new(),
+    };
+    events.retain(|e| viewer.may_see(e));
+    let cursor = query.since.or_else(|| state.recent_events.newest());
+
+    if events.is_empty() {
+        let wait = std::time::Duration::from_secs(query.wait_seconds.unwrap_or(DEFAULT_POLL_WAIT_SECONDS));
+        let next = tokio::time::timeout(wait, async {
+            loop {
+                match updates.recv().await {
+                    Ok(event) if matches!(event.event, SseEvent::Heartbeat { .. }) => continue,
+                    Ok(event) if viewer.may_see(&event) => return Some(EventEnvelope::clone(&event)),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Ok(_) => continue,
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+        .await;
+        if let Ok(Some(envelope)) = next {
+            events.push(envelope);
+        }
+    }
+
+    // Whatever else is already queued goes out with this response
+    while let Ok(event) = updates.try_recv() {
+        if !matches!(event.event, SseEvent::Heartbeat { .. })
+            && viewer.may_see(&event)
+            && events.iter().all(|e| e.id != event.id)
+        {
+            events.push(EventEnvelope::clone(&event));
+        }
+    }
+
+    let cursor = events.last().map(|e| e.id).or(cursor);
+    Ok(HttpResponse::Ok().json(VitalsPoll { events, cursor }))
+}
+
+/// Cached vitals, else the newest stored reading, else zeroes; only from `patients` unless
+/// that is `None`
+async fn current_latest_vitals(state: &AppState, patients: Option<&HashSet<Uuid>>) -> LatestVitals {
+    let patients: Option<Vec<Uuid>> = patients.map(|p| p.iter().copied().collect());
+
+    // Try Redis first
+    let mut redis = state.redis.write().await;
+    let cached = match &patients {
+        None => redis.get_latest_vitals().await,
+        Some(patients) => redis.get_patients_latest_vitals(patients).await,
+    };
+    if let Ok(Some(vitals)) = cached {
+        return vitals;
+    }
+    drop(redis);
+
+    // Fallback to database
+    let reading: Result<SensorReading, _> = sqlx::query_as(
+        "SELECT r.* FROM sensor_readings r
+         JOIN devices d ON d.id = r.device_id
+         WHERE $1::uuid[] IS NULL OR d.patient_id = ANY($1)
+         ORDER BY r.reading_timestamp DESC LIMIT 1"
+    )
+    .bind(patients)
+    .fetch_one(&state.pool)
+    .await;
+
+    match reading {
+        Ok(r) => LatestVitals {
+            heart_rate: r.heart_rate.unwrap_or(0),
+            spo2: r.spo2.unwrap_or(0),
+            temperature: r.temperature.unwrap_or(0.0),
+            timestamp: r.reading_timestamp.timestamp(),
+            quality_score: r.quality_score,
+            ml_alert: None,
+        },
+        Err(_) => LatestVitals {
+            heart_rate: 0,
+            spo2: 0,
+            temperature: 0.0,
+            timestamp: 0,
+            quality_score: None,
+            ml_alert: None,
+        },
+    }
+}
+
+const DEFAULT_HISTORY_LIMIT: i64 = 1000;
+const MAX_HISTORY_LIMIT: i64 = 10_000;
+
+const HISTORY_SQL: &str = "SELECT r.* FROM sensor_readings r
+     JOIN devices d ON d.id = r.device_id
+     WHERE d.patient_id = $1 AND r.reading_timestamp >= $2 AND r.reading_timestamp < $3
+     ORDER BY r.reading_timestamp, r.id";
+
+/// A patient's raw readings in `[from, to)`, oldest first.
+///
+/// JSON responses are capped at `limit` rows. With `Accept: application/x-ndjson` the whole
+/// range is streamed in chunks, so memory stays flat however large the range is.
+pub async fn get_vitals_history(
+    req: HttpRequest,
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    query: web::Query<VitalsHistoryQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let query = query.into_inner();
+    require_patient_access(&state, &claims, query.patient_id).await?;
+
+    let format = match negotiate(&req, &[ResponseFormat::Json, ResponseFormat::Ndjson]) {
+        Ok(f) => f,
+        Err(resp) => return Ok(resp),
+    };
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query.from.unwrap_or(to - Duration::hours(24));
+    if from >= to {
+        return Err(ApiError::BadRequest("from must be before to".into()));
+    }
+
+    crate::audit_log!("data_access", "vitals_history", Some(claims.user_id), true, query.patient_id);
+
+    if format == ResponseFormat::Ndjson {
+        return Ok(HttpResponse::Ok()
+            .content_type(format.content_type())
+            .streaming(history_ndjson(state.pool.clone(), query.patient_id, from, to)));
+    }
+
+    let limit = query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT).clamp(1, MAX_HISTORY_LIMIT);
+    let readings: Vec<SensorReading> = query_debug::fetch_all(
+        &state.pool,
+        &state.query_debug,
+        "vitals_history",
+        &format!("{} LIMIT $4", HISTORY_SQL),
+        || crate::pg_args![query.patient_id, from, to, limit],
+    )
+    .await?;
+
+    let readings: Vec<SensorReading> = readings.into_iter().map(SensorReading::without_unsurfaced_metadata).collect();
+    Ok(HttpResponse::Ok().json(readings))
+}
+
+/// One JSON line per reading, pulled from the database as the client reads.
+///
+/// The stream owns its pool handle and holds one connection until the last row is sent.
+/// A database error after the headers have gone out aborts the body, so the client sees
+/// a truncated transfer rather than a silently short result.
+fn history_ndjson(
+    pool: PgPool,
+    patient_id: Uuid,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> impl Stream<Item = Result<web::Bytes, actix_web::Error>> {
+    try_stream! {
+        let mut rows = sqlx::query_as::<_, SensorReading>(HISTORY_SQL)
+            .bind(patient_id)
+            .bind(from)
+            .bind(to)
+            .fetch(&pool);
+        while let Some(reading) = rows.try_next().await.map_err(|e| {
+            error!("Vitals history stream failed: {}", e);
+            ErrorInternalServerError("history stream failed")
+        })? {
+            let mut line = serde_json::to_vec(&reading.without_unsurfaced_metadata()).map_err(ErrorInternalServerError)?;
+            line.push(b'\n');
+            yield web::Bytes::from(line);
+        }
+    }
+}
+
+/// Downsampled vitals for long-range charts: min/max/avg and 5th/50th/95th percentiles of
+/// each vital per `interval` (1m, 5m, 1h or 1d) instead of every raw reading
+pub async fn get_vitals_aggregate(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    query: web::Query<VitalsIntervalQuery>,
+) -> Result<HttpResponse, ApiError> {
+    require_patient_access(&state, &claims, query.patient_id).await?;
+
+    let aggregate = vitals_aggregate(&state, query.patient_id, query.interval.as_deref(), query.from, query.to).await?;
+    Ok(HttpResponse::Ok().json(aggregate))
+}
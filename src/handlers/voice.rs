@@ -0,0 +1,65 @@
+use crate::emergency_service::{record_response, ResponseStep};
+use crate::errors::ApiError;
+use crate::handlers::AppState;
+use crate::voice::{twilio_signature, CALL_STATUSES};
+use actix_web::{web, HttpRequest, HttpResponse};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+crate::routes::route_registry! {
+    "/voice/status" {
+        POST => call_status_callback, Public, [];
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct CallbackQuery {
+    pub alert_id: Uuid,
+}
+
+/// Twilio call-status callback; authenticated by `X-Twilio-Signature`, recorded on the alert
+pub async fn call_status_callback(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<CallbackQuery>,
+    form: web::Form<BTreeMap<String, String>>,
+) -> Result<HttpResponse, ApiError> {
+    let (Some(token), Some(callback_base)) = (&state.voice.auth_token, &state.voice.callback_base_url) else {
+        return Err(ApiError::NotFound("Voice calls are not enabled".into()));
+    };
+
+    // Twilio signs the exact URL it was given when the call was placed
+    let url = format!("{}{}", callback_base.trim_end_matches('/'), req.uri());
+    let signature = req.headers().get("x-twilio-signature").and_then(|h| h.to_str().ok());
+    if signature != Some(twilio_signature(token, &url, &form).as_str()) {
+        return Err(ApiError::Unauthorized("Invalid Twilio signature".into()));
+    }
+
+    let call_sid = form.get("CallSid").cloned();
+    let status = form
+        .get("CallStatus")
+        .and_then(|s| CALL_STATUSES.iter().find(|known| **known == s.as_str()).copied())
+        .ok_or_else(|| ApiError::BadRequest("Unknown CallStatus".into()))?;
+
+    let contact_id: Option<Uuid> = sqlx::query_scalar(
+        "SELECT contact_id FROM alert_responses WHERE alert_id = $1 AND provider_ref = $2 ORDER BY id LIMIT 1"
+    )
+    .bind(query.alert_id)
+    .bind(&call_sid)
+    .fetch_optional(&state.pool)
+    .await?
+    .flatten();
+
+    let step = ResponseStep {
+        channel: "voice",
+        contact_id,
+        target: form.get("To").cloned(),
+        status,
+        detail: form.get("CallDuration").map(|d| format!("duration {}s", d)),
+        provider_ref: call_sid,
+        ..Default::default()
+    };
+    record_response(&state.pool, query.alert_id, &step).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,371 @@
+use crate::errors::ApiError;
+use crate::handlers::admin::load_organization_profile;
+use crate::handlers::{can_access_patient, can_manage_care, AppState};
+use crate::middleware::AuthenticatedUser;
+use crate::models::*;
+use crate::negotiation::{negotiate, ResponseFormat};
+use crate::reports::{render_pdf, Report, ReportSection};
+use crate::usage_service::{self, UsageMetric};
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+
+crate::routes::route_registry! {
+    "/wards" {
+        GET => list_wards, Jwt, [];
+        POST => create_ward, Jwt, ["admin"];
+    }
+    "/wards/{id}/patients/{patient_id}" {
+        PUT => assign_patient, Jwt, ["admin", "clinician"];
+        DELETE => unassign_patient, Jwt, ["admin", "clinician"];
+    }
+    "/wards/{id}/handoff" {
+        GET => get_handoff, Jwt, ["admin", "clinician"];
+    }
+}
+
+/// Default handoff window when `since` is omitted (one shift)
+const DEFAULT_HANDOFF_HOURS: i64 = 12;
+/// Oldest `since` honoured; earlier values are clamped
+const MAX_HANDOFF_DAYS: i64 = 7;
+const MAX_EXCURSIONS_PER_PATIENT: i64 = 10;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct HandoffQuery {
+    pub since: Option<DateTime<Utc>>,
+}
+
+/// Patient counts only cover `organization_id` (every organization if `None`)
+const IN_ORGANIZATION: &str = "($1::uuid IS NULL OR p.organization_id = $1)";
+/// Users of an organization see only its own wards, not those shared by the deployment
+const WARD_IN_ORGANIZATION: &str = "($1::uuid IS NULL OR w.organization_id = $1)";
+
+async fn load_ward(pool: &PgPool, organization_id: Option<Uuid>, id: Uuid) -> Result<Ward, ApiError> {
+    sqlx::query_as(&format!(
+        "SELECT w.id, w.name, w.created_at,
+                (SELECT COUNT(*) FROM patients p WHERE p.ward_id = w.id AND p.archived_at IS NULL AND {}) AS patient_count
+         FROM wards w WHERE w.id = $2 AND {}",
+        IN_ORGANIZATION, WARD_IN_ORGANIZATION
+    ))
+    .bind(organization_id)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Ward not found".into()))
+}
+
+// ============ Wards ============
+
+pub async fn list_wards(user: AuthenticatedUser, state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let wards: Vec<Ward> = sqlx::query_as(&format!(
+        "SELECT w.id, w.name, w.created_at, COUNT(p.id) AS patient_count
+         FROM wards w LEFT JOIN patients p ON p.ward_id = w.id AND p.archived_at IS NULL AND {}
+         WHERE {}
+         GROUP BY w.id ORDER BY w.name",
+        IN_ORGANIZATION, WARD_IN_ORGANIZATION
+    ))
+    .bind(user.org)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(wards))
+}
+
+pub async fn create_ward(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    body: web::Json<WardRequest>,
+) -> Result<HttpResponse, ApiError> {
+    body.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    // Owned by the creator's organization; shared by the deployment when they are outside one
+    let id: Option<Uuid> = sqlx::query_scalar(
+        "INSERT INTO wards (name, organization_id) VALUES ($1, $2) ON CONFLICT DO NOTHING RETURNING id"
+    )
+    .bind(body.name.trim())
+    .bind(claims.org)
+    .fetch_optional(&state.pool)
+    .await?;
+    let id = id.ok_or_else(|| ApiError::Conflict("A ward with this name already exists".into()))?;
+
+    crate::audit_log!("ward", "create", Some(claims.user_id), true, id);
+
+    Ok(HttpResponse::Created().json(load_ward(&state.pool, claims.org, id).await?))
+}
+
+pub async fn assign_patient(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, ApiError> {
+    set_patient_ward(&claims, state, path.1, Some(path.0)).await
+}
+
+pub async fn unassign_patient(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, ApiError> {
+    let (ward_id, patient_id) = path.into_inner();
+    let current: Option<Option<Uuid>> = sqlx::query_scalar("SELECT ward_id FROM patients WHERE id = $1")
+        .bind(patient_id)
+        .fetch_optional(&state.pool)
+        .await?;
+    if current.flatten() != Some(ward_id) {
+        return Err(ApiError::NotFound("Patient is not on this ward".into()));
+    }
+    set_patient_ward(&claims, state, patient_id, None).await
+}
+
+async fn set_patient_ward(
+    claims: &Claims,
+    state: web::Data<AppState>,
+    patient_id: Uuid,
+    ward_id: Option<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    if !can_manage_care(&state, claims, None).await? {
+        return Err(ApiError::Forbidden("Clinician role required".into()));
+    }
+    if !can_access_patient(&state, claims, patient_id).await? {
+        return Err(ApiError::NotFound("Patient not found".into()));
+    }
+    if let Some(ward_id) = ward_id {
+        load_ward(&state.pool, claims.org, ward_id).await?;
+    }
+
+    let updated = sqlx::query("UPDATE patients SET ward_id = $2 WHERE id = $1")
+        .bind(patient_id)
+        .bind(ward_id)
+        .execute(&state.pool)
+        .await?;
+    if updated.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Patient not found".into()));
+    }
+
+    crate::audit_log!("ward", "assign_patient", Some(claims.user_id), true, patient_id);
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// ============ Shift Handoff ============
+
+/// Alerts, notable vitals excursions, rest periods and open items for every patient on the ward since
+/// `since` (default: the last 12 hours), as JSON or a printable PDF (`Accept: application/pdf`)
+pub async fn get_handoff(
+    req: HttpRequest,
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<HandoffQuery>,
+) -> Result<HttpResponse, ApiError> {
+    if !can_manage_care(&state, &claims, None).await? {
+        return Err(ApiError::Forbidden("Clinician role required".into()));
+    }
+
+    let format = match negotiate(&req, &[ResponseFormat::Json, ResponseFormat::Pdf]) {
+        Ok(f) => f,
+        Err(resp) => return Ok(resp),
+    };
+
+    let now = Utc::now();
+    let since = query
+        .since
+        .unwrap_or(now - Duration::hours(DEFAULT_HANDOFF_HOURS))
+        .max(now - Duration::days(MAX_HANDOFF_DAYS));
+    if since > now {
+        return Err(ApiError::BadRequest("since must not be in the future".into()));
+    }
+
+    let ward = load_ward(&state.pool, claims.org, path.into_inner()).await?;
+    let summary = build_handoff(&state.pool, claims.org, &ward, since, now).await?;
+
+    crate::audit_log!("ward", "handoff", Some(claims.user_id), true, ward.id);
+
+    if format == ResponseFormat::Pdf {
+        usage_service::record_in_background(&state.pool, None, UsageMetric::ExportsGenerated, 1);
+    }
+
+    Ok(match format {
+        ResponseFormat::Pdf => {
+            let profile = load_organization_profile(&state.pool).await?;
+            HttpResponse::Ok()
+                .content_type(format.content_type())
+                .insert_header((
+                    "Content-Disposition",
+                    format!("attachment; filename=\"handoff-{}.pdf\"", now.format("%Y%m%d-%H%M")),
+                ))
+                .body(render_pdf(&handoff_report(&profile, &summary)))
+        }
+        _ => HttpResponse::Ok().json(summary),
+    })
+}
+
+async fn build_handoff(
+    pool: &PgPool,
+    organization_id: Option<Uuid>,
+    ward: &Ward,
+    since: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Result<HandoffSummary, sqlx::Error> {
+    let patients: Vec<(Uuid, String)> = sqlx::query_as(&format!(
+        "SELECT p.id, p.display_name FROM patients p
+         WHERE p.ward_id = $2 AND p.archived_at IS NULL AND {}
+         ORDER BY p.display_name",
+        IN_ORGANIZATION
+    ))
+    .bind(organization_id)
+    .bind(ward.id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut handoffs = Vec::with_capacity(patients.len());
+    for (patient_id, display_name) in patients {
+        let alerts: Vec<Alert> = sqlx::query_as(
+            "SELECT * FROM alerts WHERE patient_id = $1 AND raised_at >= $2 ORDER BY raised_at"
+        )
+        .bind(patient_id)
+        .bind(since)
+        .fetch_all(pool)
+        .await?;
+
+        // Worst readings first
+        let excursions: Vec<VitalsExcursion> = sqlx::query_as(
+            "SELECT r.reading_timestamp AS at, r.heart_rate, r.spo2, r.temperature, a.alert_level, a.classification
+             FROM ml_analysis a
+             JOIN sensor_readings r ON r.id = a.sensor_reading_id
+             JOIN devices d ON d.id = r.device_id
+             WHERE d.patient_id = $1 AND r.reading_timestamp >= $2 AND a.alert_level IN ('high', 'critical')
+             ORDER BY a.alert_level = 'critical' DESC, a.anomaly_score DESC NULLS LAST, r.reading_timestamp DESC
+             LIMIT $3"
+        )
+        .bind(patient_id)
+        .bind(since)
+        .bind(MAX_EXCURSIONS_PER_PATIENT)
+        .fetch_all(pool)
+        .await?;
+
+        // Open items regardless of when they started, plus doses missed during the window
+        let unresolved: Vec<UnresolvedItem> = sqlx::query_as(
+            "SELECT 'unacknowledged_alert' AS kind, message AS description, raised_at AS since
+             FROM alerts WHERE patient_id = $1 AND acknowledged_at IS NULL
+             UNION ALL
+             SELECT 'missed_dose', m.name || ' ' || m.dosage, d.scheduled_at
+             FROM medication_doses d JOIN medications m ON m.id = d.medication_id
+             WHERE m.patient_id = $1 AND d.status = 'missed' AND d.scheduled_at >= $2
+             UNION ALL
+             SELECT 'overdue_dose', m.name || ' ' || m.dosage, d.scheduled_at
+             FROM medication_doses d JOIN medications m ON m.id = d.medication_id
+             WHERE m.patient_id = $1 AND d.status = 'pending' AND d.scheduled_at <= $3
+             ORDER BY since"
+        )
+        .bind(patient_id)
+        .bind(since)
+        .bind(now)
+        .fetch_all(pool)
+        .await?;
+
+        // Rest periods that ended during the window, for context on overnight vitals
+        let rest_periods: Vec<sqlx::types::Json<RestPeriod>> = sqlx::query_scalar(
+            "SELECT p FROM sleep_summaries s, jsonb_array_elements(s.periods) p
+             WHERE s.patient_id = $1 AND (p->>'end')::timestamptz >= $2
+             ORDER BY (p->>'start')::timestamptz"
+        )
+        .bind(patient_id)
+        .bind(since)
+        .fetch_all(pool)
+        .await?;
+
+        handoffs.push(PatientHandoff {
+            patient_id,
+            display_name,
+            alerts,
+            excursions,
+            unresolved,
+            rest_periods: rest_periods.into_iter().map(|p| p.0).collect(),
+        });
+    }
+
+    Ok(HandoffSummary {
+        ward_id: ward.id,
+        ward_name: ward.name.clone(),
+        since,
+        generated_at: now,
+        patients: handoffs,
+    })
+}
+
+fn handoff_report(profile: &OrganizationProfile, summary: &HandoffSummary) -> Report {
+    let time = |t: &DateTime<Utc>| t.format("%d %b %H:%M").to_string();
+
+    let sections = summary
+        .patients
+        .iter()
+        .map(|patient| {
+            let mut section = ReportSection::new(&patient.display_name);
+
+            if patient.alerts.is_empty() && patient.excursions.is_empty() && patient.unresolved.is_empty() {
+                section.line("Nothing to report.");
+            }
+            for alert in &patient.alerts {
+                let status = match alert.status {
+                    AlertStatus::Open => "OPEN",
+                    AlertStatus::Acknowledged => "acknowledged",
+                    AlertStatus::Resolved => "resolved",
+                };
+                section.line(format!(
+                    "Alert {} [{}] {} - {} ({})",
+                    time(&alert.raised_at),
+                    alert.level,
+                    alert.kind,
+                    alert.message,
+                    status
+                ));
+            }
+            for excursion in &patient.excursions {
+                let value = |v: Option<i32>| v.map_or("-".to_string(), |v| v.to_string());
+                section.line(format!(
+                    "Vitals {} [{}] HR {} SpO2 {} Temp {}",
+                    time(&excursion.at),
+                    excursion.alert_level.map_or("-", |level| level.as_str()),
+                    value(excursion.heart_rate),
+                    value(excursion.spo2),
+                    excursion.temperature.map_or("-".to_string(), |t| format!("{:.1}", t)),
+                ));
+            }
+            for rest in &patient.rest_periods {
+                section.line(format!(
+                    "{} {} to {} ({}h{:02}m), avg HR {}",
+                    if rest.kind == "sleep" { "Slept" } else { "Rested" },
+                    time(&rest.start),
+                    time(&rest.end),
+                    rest.minutes / 60,
+                    rest.minutes % 60,
+                    rest.avg_heart_rate.map_or("-".to_string(), |hr| format!("{:.0}", hr)),
+                ));
+            }
+            for item in &patient.unresolved {
+                section.line(format!(
+                    "Unresolved: {} since {} - {}",
+                    item.kind.replace('_', " "),
+                    time(&item.since),
+                    item.description
+                ));
+            }
+            section
+        })
+        .collect();
+
+    Report {
+        letterhead: Some(profile.into()),
+        title: format!("Shift handoff - {}", summary.ward_name),
+        subtitle: Some(format!(
+            "Since {} UTC, generated {} UTC, {} patient(s)",
+            summary.since.format("%Y-%m-%d %H:%M"),
+            summary.generated_at.format("%Y-%m-%d %H:%M"),
+            summary.patients.len()
+        )),
+        sections,
+    }
+}
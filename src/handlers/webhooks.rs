@@ -0,0 +1,60 @@
+use crate::errors::ApiError;
+use crate::handlers::{require_unscoped, AppState};
+use crate::middleware::AuthenticatedUser;
+use crate::models::*;
+use crate::webhooks::{self, Webhook};
+use actix_web::{web, HttpResponse};
+use uuid::Uuid;
+
+crate::routes::route_registry! {
+    "/webhooks/{id}/deliveries" {
+        GET => list_deliveries, Jwt, ["admin"];
+    }
+    "/webhooks/{id}/redeliver/{event_id}" {
+        POST => redeliver, Jwt, ["admin"];
+    }
+}
+
+const DEFAULT_DELIVERIES: i64 = 100;
+const MAX_DELIVERIES: i64 = 1000;
+
+fn configured<'a>(state: &'a AppState, id: &str) -> Result<&'a Webhook, ApiError> {
+    state
+        .notifier
+        .webhook(id)
+        .ok_or_else(|| ApiError::NotFound(format!("Webhook '{}' is not configured", id)))
+}
+
+/// A webhook's deliveries in sequence order, for finding the events a receiver missed
+pub async fn list_deliveries(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<WebhookDeliveriesQuery>,
+) -> Result<HttpResponse, ApiError> {
+    require_unscoped(&claims)?;
+    let webhook = configured(&state, &path)?;
+    let limit = query.limit.unwrap_or(DEFAULT_DELIVERIES).clamp(1, MAX_DELIVERIES);
+    let deliveries = webhooks::list_deliveries(&state.pool, webhook.id, query.after_sequence.unwrap_or(0), limit).await?;
+    Ok(HttpResponse::Ok().json(deliveries))
+}
+
+/// Post a recorded event to its webhook again, under its original id and sequence. Responds
+/// with the delivery as of this attempt, whether or not the receiver accepted it.
+pub async fn redeliver(
+    claims: AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<(String, Uuid)>,
+) -> Result<HttpResponse, ApiError> {
+    require_unscoped(&claims)?;
+    let (id, event_id) = path.into_inner();
+    let webhook = configured(&state, &id)?;
+    let delivery = state
+        .notifier
+        .redeliver(webhook, event_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Webhook event not found".into()))?;
+
+    crate::audit_log!("webhook", "redeliver", Some(claims.user_id), true, event_id);
+    Ok(HttpResponse::Ok().json(delivery))
+}
@@ -0,0 +1,121 @@
+use crate::config::HeartbeatConfig;
+use crate::redis_cache::RedisCache;
+use crate::sse::SseBroadcaster;
+use anyhow::{bail, Result};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Status reported with every heartbeat ping
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemStatus {
+    /// `ok`, or `degraded` when a backing service is unreachable
+    pub status: &'static str,
+    pub database: bool,
+    pub redis: bool,
+    pub version: &'static str,
+    pub uptime_seconds: u64,
+    pub sse_subscribers: usize,
+}
+
+impl SystemStatus {
+    pub fn new(database: bool, redis: bool, uptime: Duration, sse_subscribers: usize) -> Self {
+        Self {
+            status: if database && redis { "ok" } else { "degraded" },
+            database,
+            redis,
+            version: env!("CARGO_PKG_VERSION"),
+            uptime_seconds: uptime.as_secs(),
+            sse_subscribers,
+        }
+    }
+
+    pub fn healthy(&self) -> bool {
+        self.database && self.redis
+    }
+}
+
+/// Check the backing services the way `/health` does, plus Redis
+pub async fn check_status(
+    pool: &PgPool,
+    redis: &RwLock<RedisCache>,
+    broadcaster: &SseBroadcaster,
+    started: Instant,
+) -> SystemStatus {
+    let database = sqlx::query("SELECT 1").fetch_one(pool).await.is_ok();
+    let redis = redis.write().await.health_check().await.is_ok();
+    SystemStatus::new(database, redis, started.elapsed(), broadcaster.receiver_count())
+}
+
+/// healthchecks.io convention: success pings the check URL, failure its `/fail` endpoint
+pub fn ping_url(url: &str, status: &SystemStatus) -> String {
+    let url = url.trim_end_matches('/');
+    if status.healthy() {
+        url.to_string()
+    } else {
+        format!("{}/fail", url)
+    }
+}
+
+/// POST the status to the monitor
+pub async fn send_heartbeat(http: &reqwest::Client, url: &str, status: &SystemStatus) -> Result<()> {
+    let resp = http.post(ping_url(url, status)).json(status).send().await?;
+    if !resp.status().is_success() {
+        bail!("Heartbeat monitor returned {}", resp.status());
+    }
+    Ok(())
+}
+
+/// Background worker pinging the external monitor every interval.
+///
+/// The monitor alerts when pings stop arriving, which catches a dead process, host or
+/// network that no in-process check can report.
+pub fn spawn_heartbeat_worker(
+    pool: PgPool,
+    redis: Arc<RwLock<RedisCache>>,
+    broadcaster: SseBroadcaster,
+    config: HeartbeatConfig,
+) -> Option<tokio::task::JoinHandle<()>> {
+    let url = config.url?;
+    let started = Instant::now();
+    info!("Heartbeat enabled every {}s", config.interval_seconds);
+
+    Some(tokio::spawn(async move {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_seconds))
+            .build()
+            .unwrap_or_default();
+        let mut interval = tokio::time::interval(Duration::from_secs(config.interval_seconds));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+
+            let status = check_status(&pool, &redis, &broadcaster, started).await;
+            if !status.healthy() {
+                warn!(database = status.database, redis = status.redis, "Reporting degraded status to the heartbeat monitor");
+            }
+            if let Err(e) = send_heartbeat(&http, &url, &status).await {
+                warn!("Heartbeat ping failed: {:#}", e);
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_degraded_status_pings_fail_endpoint() {
+        let ok = SystemStatus::new(true, true, Duration::from_secs(90), 2);
+        assert_eq!(ok.status, "ok");
+        assert_eq!(ping_url("https://hc-ping.com/abc/", &ok), "https://hc-ping.com/abc");
+
+        let degraded = SystemStatus::new(true, false, Duration::from_secs(90), 0);
+        assert_eq!(degraded.status, "degraded");
+        assert_eq!(ping_url("https://hc-ping.com/abc", &degraded), "https://hc-ping.com/abc/fail");
+    }
+}
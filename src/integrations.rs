@@ -0,0 +1,268 @@
+//! Organizations' connections to outside systems, managed under `/api/admin/integrations`.
+//!
+//! An integration is a FHIR server, an HL7 v2 receiver or a webhook, with the credentials
+//! and settings to reach it. [`test_connection`] tries one with what is stored: a FHIR
+//! server must serve its `CapabilityStatement` at `/metadata`, a webhook must accept a
+//! signed `integration_test` event, and an HL7 receiver must accept a TCP connection.
+//!
+//! Clinical deployments push readings to the `fhir` and `hl7` integrations (see
+//! `crate::ehr_push`). Webhook delivery does not read them yet; it still goes to the endpoints
+//! in the configuration file.
+//!
+//! Tests run by an organization's admins may not reach this host or a private network: the
+//! target is resolved once, refused when any address is loopback, private or link-local, and
+//! connected to at that address without following redirects. Administrators not tied to an
+//! organization may test internal targets.
+
+use crate::models::{EventEnvelope, Integration, IntegrationKind, IntegrationTestResult, WebhookEvent};
+use crate::webhooks::{signature, ID_HEADER, SEQUENCE_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use chrono::Utc;
+use reqwest::header;
+use sqlx::PgPool;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+pub const DEFAULT_TIMEOUT_SECONDS: u64 = 10;
+
+/// Check `url` is where an integration of `kind` can be reached; the error says what is expected
+pub fn check_url(kind: IntegrationKind, url: &str) -> Result<(), String> {
+    match kind {
+        IntegrationKind::Hl7 => mllp_address(url).map(|_| ()),
+        IntegrationKind::Fhir | IntegrationKind::Webhook => match reqwest::Url::parse(url) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https") && parsed.has_host() => Ok(()),
+            _ => Err(format!("url: '{}' must be an http or https URL", url)),
+        },
+    }
+}
+
+/// `host:port` of an `mllp://host:port` URL
+fn mllp_address(url: &str) -> Result<&str, String> {
+    let address = url.strip_prefix("mllp://").map(|rest| rest.trim_end_matches('/'));
+    match address {
+        Some(address)
+            if address
+                .rsplit_once(':')
+                .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok()) =>
+        {
+            Ok(address)
+        }
+        _ => Err(format!("url: '{}' must be an mllp://host:port address", url)),
+    }
+}
+
+/// Whether `ip` is on this host or a private network
+fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && b & 0xc0 == 64)
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_internal(IpAddr::V4(v4)),
+            None => {
+                let first = v6.segments()[0];
+                // Unique local fc00::/7 and link-local fe80::/10
+                v6.is_loopback() || v6.is_unspecified() || first & 0xfe00 == 0xfc00 || first & 0xffc0 == 0xfe80
+            }
+        },
+    }
+}
+
+/// The host of an integration's `url` and the address to connect to, refusing internal
+/// addresses unless `allow_internal`
+pub(crate) async fn resolve_target(kind: IntegrationKind, url: &str, allow_internal: bool) -> Result<(String, SocketAddr), String> {
+    let (host, port) = match kind {
+        IntegrationKind::Hl7 => {
+            let (host, port) = mllp_address(url)?.rsplit_once(':').expect("checked by mllp_address");
+            (host.to_string(), port.parse::<u16>().map_err(|e| e.to_string())?)
+        }
+        IntegrationKind::Fhir | IntegrationKind::Webhook => {
+            let parsed = reqwest::Url::parse(url).map_err(|e| format!("url: {}", e))?;
+            let host = parsed.host_str().ok_or("url: has no host")?.to_string();
+            (host, parsed.port_or_known_default().unwrap_or(80))
+        }
+    };
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host(format!("{}:{}", host, port))
+        .await
+        .map_err(|e| format!("Could not resolve {}: {}", host, e))?
+        .collect();
+    if !allow_internal && addresses.iter().any(|address| is_internal(address.ip())) {
+        return Err(format!(
+            "{} is on this host or a private network; only administrators not tied to an organization may test it",
+            host
+        ));
+    }
+    match addresses.first() {
+        Some(address) => Ok((host, *address)),
+        None => Err(format!("Could not resolve {}", host)),
+    }
+}
+
+/// Try to reach `integration`, authenticating with its opened `secret`. Internal targets are
+/// refused unless `allow_internal`.
+pub async fn test_connection(integration: &Integration, secret: Option<&str>, allow_internal: bool) -> IntegrationTestResult {
+    let timeout = Duration::from_secs(integration.settings.timeout_seconds.unwrap_or(DEFAULT_TIMEOUT_SECONDS));
+    let started = Instant::now();
+
+    let (status, outcome) = match resolve_target(integration.kind, &integration.url, allow_internal).await {
+        Err(e) => (None, Err(e)),
+        Ok((_, address)) if integration.kind == IntegrationKind::Hl7 => (None, test_hl7(address, timeout).await),
+        Ok((host, address)) => {
+            let http = pinned_client(&host, address, timeout, allow_internal);
+            match integration.kind {
+                IntegrationKind::Fhir => test_fhir(&http, integration, secret).await,
+                _ => test_webhook(&http, integration, secret).await,
+            }
+        }
+    };
+    IntegrationTestResult {
+        ok: outcome.is_ok(),
+        status,
+        latency_ms: started.elapsed().as_millis() as u64,
+        error: outcome.err(),
+        tested_at: Utc::now(),
+    }
+}
+
+/// A client that connects `host` to the `address` just checked, not whatever the name resolves
+/// to next, and follows redirects only when `allow_internal`
+pub(crate) fn pinned_client(host: &str, address: SocketAddr, timeout: Duration, allow_internal: bool) -> reqwest::Client {
+    let mut builder = reqwest::Client::builder().timeout(timeout).resolve(host, address);
+    if !allow_internal {
+        builder = builder.redirect(reqwest::redirect::Policy::none());
+    }
+    builder.build().unwrap_or_default()
+}
+
+/// Authenticate a FHIR request: basic auth with a user name, otherwise the secret as a bearer token
+pub(crate) fn fhir_credentials(request: reqwest::RequestBuilder, integration: &Integration, secret: Option<&str>) -> reqwest::RequestBuilder {
+    match (&integration.username, secret) {
+        (Some(username), password) => request.basic_auth(username, password),
+        (None, Some(token)) => request.bearer_auth(token),
+        (None, None) => request,
+    }
+}
+
+async fn test_fhir(
+    http: &reqwest::Client,
+    integration: &Integration,
+    secret: Option<&str>,
+) -> (Option<u16>, Result<(), String>) {
+    let request = http
+        .get(format!("{}/metadata", integration.url.trim_end_matches('/')))
+        .header(header::ACCEPT, "application/fhir+json");
+
+    let response = match fhir_credentials(request, integration, secret).send().await {
+        Ok(response) => response,
+        Err(e) => return (None, Err(format!("Request failed: {}", e))),
+    };
+    let status = response.status();
+    if !status.is_success() {
+        return (Some(status.as_u16()), Err(format!("Server answered {}", status)));
+    }
+    let capability = response.json::<serde_json::Value>().await.ok();
+    let outcome = match capability.as_ref().and_then(|body| body["resourceType"].as_str()) {
+        Some("CapabilityStatement") => Ok(()),
+        _ => Err("No CapabilityStatement at /metadata; is this a FHIR server's base URL?".to_string()),
+    };
+    (Some(status.as_u16()), outcome)
+}
+
+/// Post an `integration_test` event signed like any other delivery, outside the sequence
+async fn test_webhook(
+    http: &reqwest::Client,
+    integration: &Integration,
+    secret: Option<&str>,
+) -> (Option<u16>, Result<(), String>) {
+    let envelope = EventEnvelope::new(WebhookEvent::IntegrationTest { integration_id: integration.id });
+    let body = match serde_json::to_string(&envelope) {
+        Ok(body) => body,
+        Err(e) => return (None, Err(e.to_string())),
+    };
+    let timestamp = Utc::now().timestamp();
+    let mut request = http
+        .post(&integration.url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(ID_HEADER, envelope.id.to_string())
+        .header(SEQUENCE_HEADER, "0")
+        .header(TIMESTAMP_HEADER, timestamp.to_string());
+    if let Some(secret) = secret {
+        request = request.header(SIGNATURE_HEADER, signature(secret, timestamp, 0, &body));
+    }
+
+    match request.body(body).send().await {
+        Ok(response) if response.status().is_success() => (Some(response.status().as_u16()), Ok(())),
+        Ok(response) => (Some(response.status().as_u16()), Err(format!("Receiver answered {}", response.status()))),
+        Err(e) => (None, Err(format!("Request failed: {}", e))),
+    }
+}
+
+async fn test_hl7(address: SocketAddr, timeout: Duration) -> Result<(), String> {
+    match tokio::time::timeout(timeout, tokio::net::TcpStream::connect(address)).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(format!("Connection failed: {}", e)),
+        Err(_) => Err(format!("No connection within {}s", timeout.as_secs())),
+    }
+}
+
+/// Keep the outcome of a test on the integration, for the listing
+pub async fn record_test(pool: &PgPool, id: Uuid, result: &IntegrationTestResult) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE integrations SET last_tested_at = $2, last_test_ok = $3, last_test_error = $4 WHERE id = $1"
+    )
+    .bind(id)
+    .bind(result.tested_at)
+    .bind(result.ok)
+    .bind(&result.error)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_urls_checked_by_kind() {
+        assert!(check_url(IntegrationKind::Fhir, "https://fhir.example.org/r4").is_ok());
+        assert!(check_url(IntegrationKind::Webhook, "http://hooks.example.org:8080/hooks").is_ok());
+        assert!(check_url(IntegrationKind::Fhir, "mllp://hl7.example.org:2575").is_err());
+        assert!(check_url(IntegrationKind::Webhook, "not a url").is_err());
+
+        assert!(check_url(IntegrationKind::Hl7, "mllp://hl7.example.org:2575").is_ok());
+        assert!(check_url(IntegrationKind::Hl7, "mllp://hl7.example.org").is_err());
+        assert!(check_url(IntegrationKind::Hl7, "https://hl7.example.org:2575").is_err());
+        assert!(check_url(IntegrationKind::Hl7, "mllp://:2575").is_err());
+    }
+
+    #[test]
+    fn test_internal_addresses() {
+        for ip in ["127.0.0.1", "10.0.0.5", "172.16.4.1", "192.168.1.10", "169.254.169.254", "100.64.0.1", "0.0.0.0", "::1", "fd00::1", "fe80::1", "::ffff:10.0.0.5"] {
+            assert!(is_internal(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["203.0.113.5", "8.8.8.8", "2001:db8::1", "::ffff:8.8.8.8"] {
+            assert!(!is_internal(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_internal_targets_refused_unless_allowed() {
+        for (kind, url) in [
+            (IntegrationKind::Webhook, "http://10.0.0.5:8080/hooks"),
+            (IntegrationKind::Fhir, "http://localhost/fhir"),
+            (IntegrationKind::Hl7, "mllp://127.0.0.1:2575"),
+        ] {
+            let refused = resolve_target(kind, url, false).await.unwrap_err();
+            assert!(refused.contains("private network"), "{}", refused);
+            assert!(resolve_target(kind, url, true).await.is_ok(), "{}", url);
+        }
+    }
+}
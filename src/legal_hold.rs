@@ -0,0 +1,323 @@
+//! Legal-hold export archives and their chain of custody.
+//!
+//! An export is a single JSON document holding every record kept for the patient,
+//! with a per-section manifest (row count and SHA-256). The archive bytes are stored
+//! verbatim next to their SHA-256 and an HMAC-SHA256 signature, and every action on
+//! an export is appended to a custody log in which each event hashes its predecessor.
+
+use crate::models::{CustodyEvent, LegalHold, LegalHoldExport};
+use crate::phi_crypto::PhiCipher;
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, SubsecRound, Utc};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const ARCHIVE_FORMAT: &str = "smart-walker-legal-hold-export/1";
+
+/// Record sections included in every archive, each selecting the rows for patient `$1`
+const SECTIONS: &[(&str, &str)] = &[
+    ("caregivers", "SELECT u.id AS user_id, u.email, u.role, pc.created_at FROM patient_caregivers pc JOIN users u ON u.id = pc.user_id WHERE pc.patient_id = $1 ORDER BY pc.created_at"),
+    ("devices", "SELECT id, device_id, device_name, is_active, created_at, last_seen_at, metadata, claimed_by, claimed_at FROM devices WHERE patient_id = $1 ORDER BY created_at"),
+    ("sensor_readings", "SELECT r.* FROM sensor_readings r JOIN devices d ON d.id = r.device_id WHERE d.patient_id = $1 ORDER BY r.reading_timestamp, r.id"),
+    ("ml_analysis", "SELECT a.* FROM ml_analysis a JOIN sensor_readings r ON r.id = a.sensor_reading_id JOIN devices d ON d.id = r.device_id WHERE d.patient_id = $1 ORDER BY a.id"),
+    ("care_plans", "SELECT * FROM care_plans WHERE patient_id = $1 ORDER BY created_at"),
+    ("care_plan_evaluations", "SELECT e.* FROM care_plan_evaluations e JOIN care_plans c ON c.id = e.care_plan_id WHERE c.patient_id = $1 ORDER BY e.id"),
+    ("medications", "SELECT * FROM medications WHERE patient_id = $1 ORDER BY created_at"),
+    ("medication_doses", "SELECT d.* FROM medication_doses d JOIN medications m ON m.id = d.medication_id WHERE m.patient_id = $1 ORDER BY d.scheduled_at, d.id"),
+    ("checkins", "SELECT * FROM checkins WHERE patient_id = $1 ORDER BY submitted_at"),
+    ("emergency_contacts", "SELECT * FROM emergency_contacts WHERE patient_id = $1 ORDER BY priority, created_at"),
+    ("alerts", "SELECT * FROM alerts WHERE patient_id = $1 ORDER BY raised_at"),
+    ("alert_responses", "SELECT r.* FROM alert_responses r JOIN alerts a ON a.id = r.alert_id WHERE a.patient_id = $1 ORDER BY r.id"),
+    ("alert_messages", "SELECT m.* FROM alert_messages m JOIN alerts a ON a.id = m.alert_id WHERE a.patient_id = $1 ORDER BY m.created_at"),
+];
+
+pub const EXPORT_COLUMNS: &str =
+    "id, hold_id, patient_id, created_by, created_at, sha256, signature, length(archive) AS byte_size";
+
+/// Who is acting on an export, as recorded in the custody log
+#[derive(Debug, Clone)]
+pub struct CustodyActor {
+    pub id: Uuid,
+    pub email: String,
+    pub ip: Option<String>,
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Base64 HMAC-SHA256 of the archive bytes
+pub fn sign_archive(key: &str, archive: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(key.as_bytes()).unwrap();
+    mac.update(archive);
+    general_purpose::STANDARD.encode(mac.finalize().into_bytes())
+}
+
+pub fn verify_signature(key: &str, archive: &[u8], signature: &str) -> bool {
+    let Ok(expected) = general_purpose::STANDARD.decode(signature) else {
+        return false;
+    };
+    let mut mac = HmacSha256::new_from_slice(key.as_bytes()).unwrap();
+    mac.update(archive);
+    mac.verify_slice(&expected).is_ok()
+}
+
+/// Hash of a custody event over its predecessor's hash and every recorded field
+pub fn custody_hash(event: &CustodyEvent) -> String {
+    let field = |value: Option<&str>| value.unwrap_or("").replace('|', "\\|");
+    let material = [
+        field(event.prev_hash.as_deref()),
+        event.export_id.to_string(),
+        event.action.clone(),
+        event.actor_id.map(|id| id.to_string()).unwrap_or_default(),
+        field(event.actor_email.as_deref()),
+        field(event.actor_ip.as_deref()),
+        field(event.note.as_deref()),
+        event.recorded_at.to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+    ]
+    .join("|");
+    sha256_hex(material.as_bytes())
+}
+
+/// True when every event links to its predecessor and its stored hash matches its contents
+pub fn custody_chain_intact(events: &[CustodyEvent]) -> bool {
+    let mut prev: Option<&str> = None;
+    events.iter().all(|event| {
+        let linked = event.prev_hash.as_deref() == prev;
+        prev = Some(&event.hash);
+        linked && custody_hash(event) == event.hash
+    })
+}
+
+/// Assemble the archive document for a held patient
+async fn build_archive(
+    tx: &mut Transaction<'_, Postgres>,
+    phi: &PhiCipher,
+    hold: &LegalHold,
+    export_id: Uuid,
+    actor: &CustodyActor,
+    generated_at: DateTime<Utc>,
+) -> Result<Vec<u8>, sqlx::Error> {
+    let patient: Value = sqlx::query_scalar("SELECT to_jsonb(p) FROM patients p WHERE p.id = $1")
+        .bind(hold.patient_id)
+        .fetch_one(&mut **tx)
+        .await?;
+
+    let mut records = Map::new();
+    let mut manifest = Map::new();
+    for (name, query) in SECTIONS {
+        let mut rows: Value = sqlx::query_scalar(&format!(
+            "SELECT COALESCE(jsonb_agg(to_jsonb(t)), '[]'::jsonb) FROM ({}) t",
+            query
+        ))
+        .bind(hold.patient_id)
+        .fetch_one(&mut **tx)
+        .await?;
+        // The archive must stand on its own, so encrypted PHI goes in as plaintext
+        if *name == "checkins" {
+            for row in rows.as_array_mut().into_iter().flatten() {
+                phi.open_json_field(row, "notes").await;
+            }
+        }
+
+        let serialized = serde_json::to_vec(&rows).unwrap_or_default();
+        manifest.insert(
+            name.to_string(),
+            json!({
+                "count": rows.as_array().map_or(0, Vec::len),
+                "sha256": sha256_hex(&serialized),
+            }),
+        );
+        records.insert(name.to_string(), rows);
+    }
+
+    let archive = json!({
+        "format": ARCHIVE_FORMAT,
+        "export_id": export_id,
+        "generated_at": generated_at,
+        "generated_by": {"id": actor.id, "email": actor.email},
+        "legal_hold": hold,
+        "patient": patient,
+        "manifest": manifest,
+        "records": records,
+    });
+    Ok(serde_json::to_vec_pretty(&archive).unwrap_or_default())
+}
+
+/// Build, sign and store an export of the held patient's record, opening its custody log
+pub async fn create_export(
+    pool: &PgPool,
+    phi: &PhiCipher,
+    signing_key: &str,
+    hold: &LegalHold,
+    actor: &CustodyActor,
+) -> Result<LegalHoldExport, sqlx::Error> {
+    let export_id = Uuid::new_v4();
+    // Postgres keeps microseconds; truncate so the stored timestamp matches the archive
+    let generated_at = Utc::now().trunc_subsecs(6);
+
+    // Repeatable read so every section comes from the same snapshot
+    let mut tx = pool.begin().await?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+        .execute(&mut *tx)
+        .await?;
+
+    let archive = build_archive(&mut tx, phi, hold, export_id, actor, generated_at).await?;
+    let export: LegalHoldExport = sqlx::query_as(&format!(
+        "INSERT INTO legal_hold_exports (id, hold_id, patient_id, created_by, created_at, archive, sha256, signature)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING {}",
+        EXPORT_COLUMNS
+    ))
+    .bind(export_id)
+    .bind(hold.id)
+    .bind(hold.patient_id)
+    .bind(actor.id)
+    .bind(generated_at)
+    .bind(&archive)
+    .bind(sha256_hex(&archive))
+    .bind(sign_archive(signing_key, &archive))
+    .fetch_one(&mut *tx)
+    .await?;
+
+    append_custody_event(&mut tx, export_id, "created", actor, None).await?;
+    tx.commit().await?;
+
+    Ok(export)
+}
+
+pub async fn load_export(pool: &PgPool, export_id: Uuid) -> Result<Option<LegalHoldExport>, sqlx::Error> {
+    sqlx::query_as(&format!("SELECT {} FROM legal_hold_exports WHERE id = $1", EXPORT_COLUMNS))
+        .bind(export_id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn load_archive(pool: &PgPool, export_id: Uuid) -> Result<Option<Vec<u8>>, sqlx::Error> {
+    sqlx::query_scalar("SELECT archive FROM legal_hold_exports WHERE id = $1")
+        .bind(export_id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn load_custody(pool: &PgPool, export_id: Uuid) -> Result<Vec<CustodyEvent>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM custody_events WHERE export_id = $1 ORDER BY id")
+        .bind(export_id)
+        .fetch_all(pool)
+        .await
+}
+
+/// Append an event to an export's custody log
+pub async fn record_custody_event(
+    pool: &PgPool,
+    export_id: Uuid,
+    action: &str,
+    actor: &CustodyActor,
+    note: Option<String>,
+) -> Result<CustodyEvent, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let event = append_custody_event(&mut tx, export_id, action, actor, note).await?;
+    tx.commit().await?;
+    Ok(event)
+}
+
+async fn append_custody_event(
+    tx: &mut Transaction<'_, Postgres>,
+    export_id: Uuid,
+    action: &str,
+    actor: &CustodyActor,
+    note: Option<String>,
+) -> Result<CustodyEvent, sqlx::Error> {
+    // Serialize appends per export so the chain cannot fork
+    sqlx::query("SELECT 1 FROM legal_hold_exports WHERE id = $1 FOR UPDATE")
+        .bind(export_id)
+        .execute(&mut **tx)
+        .await?;
+    let prev_hash: Option<String> =
+        sqlx::query_scalar("SELECT hash FROM custody_events WHERE export_id = $1 ORDER BY id DESC LIMIT 1")
+            .bind(export_id)
+            .fetch_optional(&mut **tx)
+            .await?;
+
+    let mut event = CustodyEvent {
+        id: 0,
+        export_id,
+        action: action.to_string(),
+        actor_id: Some(actor.id),
+        actor_email: Some(actor.email.clone()),
+        actor_ip: actor.ip.clone(),
+        note,
+        recorded_at: Utc::now().trunc_subsecs(6),
+        prev_hash,
+        hash: String::new(),
+    };
+    event.hash = custody_hash(&event);
+
+    event.id = sqlx::query_scalar(
+        "INSERT INTO custody_events (export_id, action, actor_id, actor_email, actor_ip, note, recorded_at, prev_hash, hash)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING id"
+    )
+    .bind(event.export_id)
+    .bind(&event.action)
+    .bind(event.actor_id)
+    .bind(&event.actor_email)
+    .bind(&event.actor_ip)
+    .bind(&event.note)
+    .bind(event.recorded_at)
+    .bind(&event.prev_hash)
+    .bind(&event.hash)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(prev_hash: Option<String>, action: &str) -> CustodyEvent {
+        let mut event = CustodyEvent {
+            id: 0,
+            export_id: Uuid::nil(),
+            action: action.to_string(),
+            actor_id: Some(Uuid::nil()),
+            actor_email: Some("counsel@example.com".to_string()),
+            actor_ip: None,
+            note: None,
+            recorded_at: Utc::now().trunc_subsecs(6),
+            prev_hash,
+            hash: String::new(),
+        };
+        event.hash = custody_hash(&event);
+        event
+    }
+
+    #[test]
+    fn test_signature_round_trip_and_tamper() {
+        let key = "k".repeat(32);
+        let signature = sign_archive(&key, b"{\"records\":{}}");
+        assert!(verify_signature(&key, b"{\"records\":{}}", &signature));
+        assert!(!verify_signature(&key, b"{\"records\":{ }}", &signature));
+        assert!(!verify_signature("other-key", b"{\"records\":{}}", &signature));
+        assert!(!verify_signature(&key, b"{\"records\":{}}", "not base64!"));
+    }
+
+    #[test]
+    fn test_custody_chain_detects_edits_and_gaps() {
+        let created = event(None, "created");
+        let downloaded = event(Some(created.hash.clone()), "downloaded");
+        let verified = event(Some(downloaded.hash.clone()), "verified");
+        assert!(custody_chain_intact(&[created.clone(), downloaded.clone(), verified.clone()]));
+
+        let mut edited = downloaded.clone();
+        edited.note = Some("copy for opposing counsel".to_string());
+        assert!(!custody_chain_intact(&[created.clone(), edited, verified.clone()]));
+
+        assert!(!custody_chain_intact(&[created, verified]));
+    }
+}
@@ -1,13 +1,65 @@
 // Library root - exposes modules for integration tests
 
+pub mod activity_service;
+pub mod aggregate_service;
+pub mod alert_routing;
+pub mod analytics;
+pub mod ambient_service;
+pub mod api_version;
+pub mod app;
 pub mod auth;
+pub mod baseline_service;
+pub mod build_info;
+pub mod bulk_export;
+pub mod cache_warmup;
+pub mod care_plan_service;
 pub mod config;
+pub mod crash_reporting;
 pub mod database;
+pub mod dead_letter;
+pub mod device_secrets;
+pub mod ehr_push;
+pub mod emergency_service;
+pub mod errors;
+pub mod event_log;
 pub mod fhir_service;
 pub mod handlers;
+pub mod heartbeat_service;
+pub mod integrations;
+pub mod legal_hold;
 pub mod logging;
+pub mod medication_service;
+pub mod metrics;
 pub mod middleware;
+pub mod ml_model;
 pub mod ml_service;
 pub mod models;
+pub mod mqtt_ingest;
+pub mod near_fall_service;
+pub mod negotiation;
+pub mod normalization;
+pub mod notifications;
+pub mod notifier;
+pub mod observability;
+pub mod pairing;
+pub mod phi_crypto;
+pub mod query_debug;
+pub mod quota_service;
+pub mod rate_limit;
+pub mod rbac;
 pub mod redis_cache;
+pub mod replay;
+pub mod request_context;
+pub mod reporting_service;
+pub mod reports;
+pub mod retention_service;
+pub mod routes;
+pub mod rule_dsl;
+pub mod seed;
+pub mod sleep_service;
+pub mod slo_service;
 pub mod sse;
+pub mod usage_service;
+pub mod voice;
+pub mod webhooks;
+pub mod ws;
@@ -1,7 +1,11 @@
 use tracing::subscriber::set_global_default;
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
-use tracing_subscriber::{fmt, layer::SubscriberExt, EnvFilter, Registry};
+use tracing_subscriber::{fmt, layer::SubscriberExt, reload, EnvFilter, Registry};
 use std::path::Path;
+use std::sync::OnceLock;
+
+/// Swaps the active filter at runtime (`PUT /api/admin/log-level`)
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
 
 /// Initialize HIPAA-compliant logging
 pub fn init_logging(log_dir: impl AsRef<Path>, log_level: &str) -> anyhow::Result<()> {
@@ -24,23 +28,41 @@ pub fn init_logging(log_dir: impl AsRef<Path>, log_level: &str) -> anyhow::Resul
         .with_target(true)
         .with_thread_ids(true);
 
-    // Environment filter
+    // Environment filter, reloadable so operators can raise verbosity without a restart
     let env_filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new(log_level));
+    let (env_filter, filter_handle) = reload::Layer::new(env_filter);
 
     // Combine layers
     let subscriber = Registry::default()
         .with(env_filter)
         .with(file_layer)
-        .with(console_layer);
+        .with(console_layer)
+        .with(crate::observability::sentry_layer());
 
     set_global_default(subscriber)?;
+    let _ = FILTER.set(filter_handle);
 
     tracing::info!("Logging initialized with level: {}", log_level);
 
     Ok(())
 }
 
+/// The active filter directives, e.g. `info,medhealth_backend::sse=debug`; `None` before
+/// [`init_logging`]
+pub fn current_filter() -> Option<String> {
+    FILTER.get()?.with_current(|filter| filter.to_string()).ok()
+}
+
+/// Replace the filter with `directives` (`EnvFilter` syntax), returning the previous one
+pub fn set_filter(directives: &str) -> anyhow::Result<String> {
+    let handle = FILTER.get().ok_or_else(|| anyhow::anyhow!("Logging is not initialized"))?;
+    let filter = EnvFilter::builder().parse(directives)?;
+    let previous = handle.with_current(|filter| filter.to_string())?;
+    handle.reload(filter)?;
+    Ok(previous)
+}
+
 /// Audit log macro for HIPAA compliance
 /// DO NOT log PHI (Protected Health Information) directly
 #[macro_export]
@@ -78,5 +100,9 @@ mod tests {
         let temp_dir = tempdir().unwrap();
         let result = init_logging(temp_dir.path(), "info");
         assert!(result.is_ok());
+
+        assert_eq!(set_filter("info,medhealth_backend::sse=debug").unwrap(), "info");
+        assert_eq!(current_filter().unwrap(), "medhealth_backend::sse=debug,info");
+        assert!(set_filter("medhealth_backend::sse=loud").is_err());
     }
 }
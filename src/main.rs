@@ -1,127 +1,216 @@
-mod auth;
-mod config;
-mod database;
-mod fhir_service;
-mod handlers;
-mod logging;
-mod middleware;
-mod ml_service;
-mod models;
-mod redis_cache;
-mod sse;
-
-use crate::config::Settings;
-use crate::database::{create_pool, run_migrations};
-use crate::handlers::AppState;
-use crate::middleware::{AuditLogger, RequestId};
-use actix_cors::Cors;
-use actix_web::{http::header, middleware::Logger, web, App, HttpServer};
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use medhealth_backend::app::{build_app, init_state};
+use medhealth_backend::{
+    activity_service, baseline_service, cache_warmup, care_plan_service, crash_reporting, emergency_service,
+    heartbeat_service, medication_service, ml_service, mqtt_ingest, notifications, observability, quota_service, replay,
+    reporting_service, retention_service, seed, sleep_service, slo_service, usage_service,
+};
+use medhealth_backend::config::Settings;
+use medhealth_backend::database::{create_pool, run_migrations};
+use medhealth_backend::device_secrets::DeviceSecrets;
+use medhealth_backend::logging;
+use medhealth_backend::phi_crypto::PhiCipher;
+use actix_web::{web, HttpServer};
 use tracing::info;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    // Load configuration
-    let settings = Settings::new().expect("Failed to load configuration");
+    // Load and validate configuration before anything else starts
+    let settings = match Settings::new() {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("❌ Failed to load configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Subcommands run instead of the server (and may run next to one, so skip validation)
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("replay") {
+        std::process::exit(run_replay(&settings, &args[1..]).await);
+    }
+    if args.first().map(String::as_str) == Some("seed") {
+        std::process::exit(run_seed(&settings, &args[1..]).await);
+    }
+
+    if let Err(problems) = settings.validate() {
+        eprintln!("❌ Invalid configuration ({} problem(s)):", problems.len());
+        for problem in &problems {
+            eprintln!("   - {}", problem);
+        }
+        std::process::exit(1);
+    }
 
     // Initialize logging
     let log_dir = std::path::Path::new(&settings.logging.audit_log_path)
         .parent()
         .unwrap_or(std::path::Path::new("./logs"));
     
+    // Sentry first, so the logging layer and panic hook have a client to report to
+    let sentry_guard = observability::init_sentry(&settings.observability);
     logging::init_logging(log_dir, &settings.logging.level)
         .expect("Failed to initialize logging");
+    crash_reporting::install_panic_hook();
 
     info!("🚀 MedHealth Backend starting...");
-    info!("Configuration loaded: {}", settings.server.bind_addr);
+    info!("Configuration loaded: {} (profile: {})", settings.server.bind_addr, settings.profile.name());
+    info!("Sentry error tracking: {}", if sentry_guard.is_some() { observability::release() } else { "disabled".into() });
     info!("PHI encryption in logs: {}", if settings.logging.enable_phi_encryption { "enabled" } else { "disabled" });
 
-    // Create database pool
-    info!("Connecting to PostgreSQL...");
-    let pool = create_pool(&settings.database)
-        .await
-        .expect("Failed to create database pool");
-
-    // Run migrations
-    info!("Running database migrations...");
-    run_migrations(&pool)
-        .await
-        .expect("Failed to run migrations");
-
-    // Create Redis cache
-    info!("Connecting to Redis...");
-    let redis = redis_cache::RedisCache::new(&settings.redis)
+    // Connect backing services and create app state
+    let app_state = init_state(&settings)
         .await
-        .expect("Failed to connect to Redis");
-    let redis = Arc::new(RwLock::new(redis));
+        .map(web::Data::new)
+        .expect("Failed to initialize services");
 
-    // Initialize services
-    let jwt_auth = Arc::new(auth::JwtAuth::new(&settings.jwt));
-    let ml_service = Arc::new(ml_service::MlService::new(settings.ml.clone()));
-    let fhir_service = Arc::new(fhir_service::FhirService::new(settings.fhir.clone()));
-    let sse_broadcaster = sse::create_broadcaster();
-
-    // Create app state
-    let app_state = web::Data::new(AppState {
-        pool: pool.clone(),
-        redis: redis.clone(),
-        jwt_auth: jwt_auth.clone(),
-        ml_service: ml_service.clone(),
-        fhir_service: fhir_service.clone(),
-        sse_broadcaster: sse_broadcaster.clone(),
-        device_secret: settings.device.secret.clone(),
-        replay_window_seconds: settings.device.replay_window_seconds,
-    });
+    // Background workers
+    crash_reporting::spawn_reporter(app_state.pool.clone());
+    cache_warmup::spawn_warmup(app_state.pool.clone(), app_state.redis.clone(), app_state.ml_service.clone());
+    ml_service::spawn_rule_refresher(app_state.pool.clone(), app_state.ml_service.clone());
+    baseline_service::spawn_baseline_worker(app_state.pool.clone());
+    care_plan_service::spawn_evaluator(
+        app_state.pool.clone(),
+        app_state.notifier.clone(),
+        settings.compliance.non_use_alert_days,
+    );
+    sleep_service::spawn_sleep_worker(app_state.pool.clone());
+    activity_service::spawn_classifier(app_state.pool.clone());
+    medication_service::spawn_reminder_worker(
+        app_state.pool.clone(),
+        app_state.notifier.clone(),
+        app_state.sse_broadcaster.clone(),
+    );
+    if app_state.notifier.voice_enabled() {
+        emergency_service::spawn_escalation_worker(
+            app_state.pool.clone(),
+            app_state.notifier.clone(),
+            settings.voice.escalate_after_minutes,
+        );
+    }
+    reporting_service::spawn_view_refresher(app_state.pool.clone());
+    quota_service::spawn_quota_worker(
+        app_state.pool.clone(),
+        app_state.notifier.clone(),
+        settings.quota.clone(),
+    );
+    usage_service::spawn_rollup_worker(app_state.pool.clone(), settings.billing.clone());
+    if let Some(days) = settings.retention.sensor_readings_days {
+        retention_service::spawn_purge_worker(app_state.pool.clone(), days);
+    }
+    slo_service::spawn_sampler(app_state.pool.clone(), settings.slo.clone());
+    if settings.mqtt.enabled {
+        mqtt_ingest::spawn_mqtt_ingest(app_state.clone(), settings.mqtt.clone());
+    }
+    notifications::spawn_dispatcher(app_state.pool.clone(), settings.notifications.clone());
+    heartbeat_service::spawn_heartbeat_worker(
+        app_state.pool.clone(),
+        app_state.redis.clone(),
+        app_state.sse_broadcaster.clone(),
+        settings.heartbeat.clone(),
+    );
 
     info!("✅ All services initialized successfully");
     info!("🌐 Starting server on {}", settings.server.bind_addr);
 
     let bind_addr = settings.server.bind_addr.clone();
-    let cors_origins = settings.cors.allowed_origins.clone();
 
-    HttpServer::new(move || {
-        // CORS configuration
-        let mut cors = Cors::default()
-            .allowed_methods(vec!["GET", "POST", "OPTIONS"])
-            .allowed_headers(vec![
-                header::CONTENT_TYPE,
-                header::AUTHORIZATION,
-                header::ACCEPT,
-            ])
-            .supports_credentials()
-            .max_age(3600);
+    HttpServer::new(move || build_app(app_state.clone()))
+        .workers(settings.server.workers.unwrap_or(4))
+        .bind(bind_addr)?
+        .run()
+        .await
+}
+
+/// `replay`: re-send stored readings to a server; exits non-zero if any were rejected
+async fn run_replay(settings: &Settings, args: &[String]) -> i32 {
+    let args = match replay::ReplayArgs::parse(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("❌ {}\n{}", e, replay::USAGE);
+            return 2;
+        }
+    };
+    let pool = match create_pool(&settings.database).await {
+        Ok(pool) => pool,
+        Err(e) => {
+            eprintln!("❌ Failed to connect to the database: {}", e);
+            return 1;
+        }
+    };
+
+    let secrets = match DeviceSecrets::new(&settings.device) {
+        Ok(secrets) => secrets,
+        Err(e) => {
+            eprintln!("❌ Device secret encryption is misconfigured: {:#}", e);
+            return 1;
+        }
+    };
+    match replay::replay(&pool, &secrets, &args).await {
+        Ok(summary) => {
+            println!(
+                "Replayed {} reading(s) to {}: {} rejected, {} skipped (missing vitals)",
+                summary.sent, args.target, summary.rejected, summary.skipped
+            );
+            if summary.rejected > 0 { 1 } else { 0 }
+        }
+        Err(e) => {
+            eprintln!("❌ Replay failed: {:#}", e);
+            1
+        }
+    }
+}
 
-        for origin in &cors_origins {
-            cors = cors.allowed_origin(origin);
+/// `seed`: migrate an empty database and load a demo dataset into it
+async fn run_seed(settings: &Settings, args: &[String]) -> i32 {
+    let args = match seed::SeedArgs::parse(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("❌ {}\n{}", e, seed::USAGE);
+            return 2;
+        }
+    };
+    let pool = match create_pool(&settings.database).await {
+        Ok(pool) => pool,
+        Err(e) => {
+            eprintln!("❌ Failed to connect to the database: {}", e);
+            return 1;
         }
+    };
+    if let Err(e) = run_migrations(&pool).await {
+        eprintln!("❌ Failed to run migrations: {}", e);
+        return 1;
+    }
+    let phi = match PhiCipher::new(pool.clone(), &settings.encryption) {
+        Ok(phi) => phi,
+        Err(e) => {
+            eprintln!("❌ PHI encryption is misconfigured: {:#}", e);
+            return 1;
+        }
+    };
 
-        App::new()
-            // Middleware
-            .wrap(Logger::default())
-            .wrap(AuditLogger)
-            .wrap(RequestId)
-            .wrap(cors)
-            // App state
-            .app_data(app_state.clone())
-            .app_data(web::Data::new(pool.clone()))
-            .app_data(web::Data::new(sse_broadcaster.clone()))
-            // Health check
-            .route("/health", web::get().to(handlers::health_check))
-            // Authentication routes
-            .route("/auth/signup", web::post().to(handlers::signup))
-            .route("/auth/login", web::post().to(handlers::login))
-            .route("/auth/logout", web::post().to(handlers::logout))
-            // API routes (JWT protected)
-            .route("/api/vitals/latest", web::get().to(handlers::get_latest_vitals))
-            .route("/api/fhir/export", web::get().to(handlers::export_fhir_bundle))
-            // SSE stream (JWT protected in production)
-            .route("/api/stream/vitals", web::get().to(sse::stream_vitals))
-            // Device ingestion (HMAC protected)
-            .route("/api/device/vitals", web::post().to(handlers::device_ingest))
-    })
-    .workers(settings.server.workers.unwrap_or(4))
-    .bind(bind_addr)?
-    .run()
-    .await
+    match seed::seed(&pool, &phi, &args).await {
+        Ok(summary) => {
+            println!(
+                "Seeded {} organization(s), {} user(s), {} patient(s), {} walker(s), {} reading(s) ({} anomalous), \
+                 {} alert(s) and {} note(s)",
+                summary.organizations,
+                summary.users,
+                summary.patients,
+                summary.devices,
+                summary.readings,
+                summary.anomalies,
+                summary.alerts,
+                summary.notes
+            );
+            println!(
+                "Sign in as superadmin@{0} (every organization) or e.g. nurse.riverside@{0}, password '{1}'",
+                seed::EMAIL_DOMAIN,
+                args.password
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("❌ Seeding failed: {:#}", e);
+            1
+        }
+    }
 }
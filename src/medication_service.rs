@@ -0,0 +1,174 @@
+use crate::models::{Adherence, MedicationReminder};
+use crate::notifier::Notifier;
+use crate::sse::{broadcast_reminder, SseBroadcaster};
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// A pending dose is marked missed once it is this many minutes overdue
+pub const MISSED_AFTER_MINUTES: i64 = 120;
+
+const REMINDER_INTERVAL: StdDuration = StdDuration::from_secs(60);
+
+/// Summarize dose statuses; pending doses are excluded from the ratio
+pub fn adherence(counts: &[(String, i64)]) -> Adherence {
+    let mut result = Adherence::default();
+    for (status, count) in counts {
+        match status.as_str() {
+            "taken" => result.taken += count,
+            "skipped" => result.skipped += count,
+            "missed" => result.missed += count,
+            _ => result.pending += count,
+        }
+    }
+
+    let resolved = result.taken + result.skipped + result.missed;
+    result.ratio = (resolved > 0).then(|| result.taken as f64 / resolved as f64);
+    result
+}
+
+/// Materialize doses for active schedules from today through tomorrow (idempotent)
+pub async fn schedule_doses(pool: &PgPool) -> Result<u64> {
+    let today = Utc::now().date_naive();
+
+    let result = sqlx::query(
+        "INSERT INTO medication_doses (medication_id, scheduled_at)
+         SELECT m.id, (day::date + t) AT TIME ZONE 'UTC'
+         FROM medications m
+         CROSS JOIN unnest(m.times) AS t
+         CROSS JOIN generate_series($1::date, $2::date, interval '1 day') AS day
+         WHERE m.active
+           AND m.patient_id IN (SELECT id FROM patients WHERE archived_at IS NULL)
+           AND day::date >= m.start_date
+           AND (m.end_date IS NULL OR day::date <= m.end_date)
+           AND (day::date + t) AT TIME ZONE 'UTC' >= m.created_at
+         ON CONFLICT (medication_id, scheduled_at) DO NOTHING"
+    )
+    .bind(today)
+    .bind(today + Duration::days(1))
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Claim every due, not yet reminded dose (each dose is returned exactly once)
+pub async fn claim_due_reminders(pool: &PgPool) -> Result<Vec<MedicationReminder>> {
+    let reminders = sqlx::query_as::<_, MedicationReminder>(
+        "UPDATE medication_doses d SET reminded_at = now()
+         FROM medications m
+         WHERE m.id = d.medication_id
+           AND d.status = 'pending' AND d.reminded_at IS NULL AND d.scheduled_at <= now()
+         RETURNING d.id AS dose_id, m.id AS medication_id, m.patient_id, m.name, m.dosage, d.scheduled_at"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(reminders)
+}
+
+/// Mark overdue pending doses as missed
+pub async fn mark_missed(pool: &PgPool) -> Result<u64> {
+    let result = sqlx::query(
+        "UPDATE medication_doses SET status = 'missed'
+         WHERE status = 'pending' AND scheduled_at < now() - make_interval(mins => $1)"
+    )
+    .bind(MISSED_AFTER_MINUTES as i32)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Hand a claimed dose back, so the next pass reminds of it again
+async fn release_reminder(pool: &PgPool, dose_id: Uuid) -> Result<()> {
+    sqlx::query("UPDATE medication_doses SET reminded_at = NULL WHERE id = $1 AND status = 'pending'")
+        .bind(dose_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// One worker pass: schedule, remind (notifier + SSE), expire; returns reminders sent.
+/// A dose whose caregivers could not be notified is released for the next pass, without
+/// holding up the others.
+pub async fn run_reminder_cycle(pool: &PgPool, notifier: &Notifier, broadcaster: &SseBroadcaster) -> Result<usize> {
+    schedule_doses(pool).await?;
+
+    let reminders = claim_due_reminders(pool).await?;
+    let mut sent = 0;
+    for reminder in &reminders {
+        let title = format!("Medication due: {}", reminder.name);
+        let body = format!(
+            "{} {} was due at {} UTC. Confirm once given.",
+            reminder.name,
+            reminder.dosage,
+            reminder.scheduled_at.format("%H:%M")
+        );
+        if let Err(e) = notifier
+            .notify_caregivers(reminder.patient_id, "medication_reminder", &title, &body)
+            .await
+        {
+            error!(dose_id = %reminder.dose_id, "Failed to send medication reminder: {}", e);
+            if let Err(e) = release_reminder(pool, reminder.dose_id).await {
+                error!(dose_id = %reminder.dose_id, "Failed to release medication reminder: {}", e);
+            }
+            continue;
+        }
+        broadcast_reminder(broadcaster, reminder.clone());
+        sent += 1;
+    }
+
+    mark_missed(pool).await?;
+    Ok(sent)
+}
+
+/// Background worker delivering medication reminders every minute
+pub fn spawn_reminder_worker(
+    pool: PgPool,
+    notifier: Arc<Notifier>,
+    broadcaster: SseBroadcaster,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(REMINDER_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            match run_reminder_cycle(&pool, &notifier, &broadcaster).await {
+                Ok(0) => {}
+                Ok(sent) => info!("Sent {} medication reminder(s)", sent),
+                Err(e) => error!("Medication reminder cycle failed: {}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adherence_ratio_ignores_pending() {
+        let counts = vec![
+            ("taken".to_string(), 6),
+            ("skipped".to_string(), 1),
+            ("missed".to_string(), 1),
+            ("pending".to_string(), 2),
+        ];
+        let result = adherence(&counts);
+
+        assert_eq!(result.taken, 6);
+        assert_eq!(result.pending, 2);
+        assert_eq!(result.ratio, Some(0.75));
+    }
+
+    #[test]
+    fn test_adherence_without_resolved_doses() {
+        let result = adherence(&[("pending".to_string(), 3)]);
+        assert_eq!(result.ratio, None);
+    }
+}
@@ -1,9 +1,13 @@
 use prometheus::{
-    Encoder, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Histogram, HistogramVec,
+    core::Collector, proto::Metric, Encoder, IntCounter, IntCounterVec, IntGauge, Histogram, HistogramVec,
     Opts, Registry, TextEncoder,
 };
 use lazy_static::lazy_static;
-use actix_web::{HttpResponse, Responder};
+use std::time::Duration;
+
+/// Request duration buckets in seconds. SLO latency thresholds must be one of these,
+/// so 200 ms is a bucket of its own.
+pub const HTTP_DURATION_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.2, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
 
 lazy_static! {
     pub static ref REGISTRY: Registry = Registry::new();
@@ -18,19 +22,14 @@ lazy_static! {
         prometheus::HistogramOpts::new(
             "http_request_duration_seconds",
             "HTTP request duration in seconds"
-        ),
+        ).buckets(HTTP_DURATION_BUCKETS.to_vec()),
         &["method", "endpoint"]
     ).unwrap();
 
     // Authentication metrics
     pub static ref AUTH_ATTEMPTS_TOTAL: IntCounterVec = IntCounterVec::new(
         Opts::new("auth_attempts_total", "Total authentication attempts"),
-        &["result"] // "success" or "failure"
-    ).unwrap();
-
-    pub static ref ACTIVE_SESSIONS: IntGauge = IntGauge::new(
-        "active_sessions",
-        "Number of active user sessions"
+        &["result"] // "success", "failure", "locked" or "token_rejected"
     ).unwrap();
 
     // Device metrics
@@ -39,11 +38,33 @@ lazy_static! {
         &["device_id"]
     ).unwrap();
 
+    /// `device_id` is "unverified" when the request named no active device
     pub static ref DEVICE_ERRORS_TOTAL: IntCounterVec = IntCounterVec::new(
         Opts::new("device_errors_total", "Total device errors"),
         &["device_id", "error_type"]
     ).unwrap();
 
+    /// Authenticated readings kept in the dead-letter queue (see `crate::dead_letter`)
+    pub static ref INGESTION_FAILURES_TOTAL: IntCounterVec = IntCounterVec::new(
+        Opts::new("ingestion_failures_total", "Authenticated readings that could not be stored"),
+        &["channel", "category"] // "http", "mqtt" or "gateway"; "invalid_reading" or "storage"
+    ).unwrap();
+
+    pub static ref INGESTION_REPROCESSED_TOTAL: IntCounterVec = IntCounterVec::new(
+        Opts::new("ingestion_reprocessed_total", "Dead-lettered readings reprocessed"),
+        &["outcome"] // "stored" or "failed"
+    ).unwrap();
+
+    pub static ref RATE_LIMITED_TOTAL: IntCounterVec = IntCounterVec::new(
+        Opts::new("rate_limited_total", "Requests refused for exceeding a rate limit"),
+        &["policy"] // "auth", "device" or "api"
+    ).unwrap();
+
+    pub static ref DEPRECATED_FIELDS_TOTAL: IntCounterVec = IntCounterVec::new(
+        Opts::new("deprecated_fields_total", "Requests using API version 1 field names, by field"),
+        &["field"]
+    ).unwrap();
+
     // ML metrics
     pub static ref ML_ANOMALIES_DETECTED: IntCounterVec = IntCounterVec::new(
         Opts::new("ml_anomalies_detected", "Total anomalies detected by ML"),
@@ -57,16 +78,21 @@ lazy_static! {
         )
     ).unwrap();
 
+    pub static ref ML_ANALYSIS_REUSED: IntCounter = IntCounter::new(
+        "ml_analysis_reused_total",
+        "Readings identical to their device's previous one, whose analysis was reused"
+    ).unwrap();
+
     // Database metrics
     pub static ref DB_CONNECTIONS_ACTIVE: IntGauge = IntGauge::new(
         "db_connections_active",
-        "Number of active database connections"
+        "Number of database connections in use, as of the last scrape"
     ).unwrap();
 
     pub static ref DB_QUERY_DURATION: HistogramVec = HistogramVec::new(
         prometheus::HistogramOpts::new(
             "db_query_duration_seconds",
-            "Database query duration in seconds"
+            "Duration of the labelled read queries (see query_debug) in seconds"
         ),
         &["query_type"]
     ).unwrap();
@@ -93,56 +119,140 @@ lazy_static! {
         &["event_type"]
     ).unwrap();
 
-    // Vitals metrics (anonymized for HIPAA compliance)
-    pub static ref VITALS_HR_CURRENT: IntGauge = IntGauge::new(
-        "vitals_heart_rate_current",
-        "Current heart rate reading"
+    /// Readings pushed to organizations' EHR integrations (see `crate::ehr_push`)
+    pub static ref EHR_PUSHES_TOTAL: IntCounterVec = IntCounterVec::new(
+        Opts::new("ehr_pushes_total", "Readings pushed to EHR integrations"),
+        &["kind", "outcome"] // "fhir" or "hl7"; "delivered" or "failed"
     ).unwrap();
 
-    pub static ref VITALS_SPO2_CURRENT: IntGauge = IntGauge::new(
-        "vitals_spo2_current",
-        "Current SpO2 reading"
+    pub static ref EVENT_LOG_MISSED_TOTAL: IntCounter = IntCounter::new(
+        "event_log_missed_total",
+        "Broadcast events the event log fell too far behind to record, or failed to store"
     ).unwrap();
 }
 
-/// Initialize Prometheus metrics
+/// Initialize Prometheus metrics. Safe to call again (every `init_state` does), as the
+/// metrics themselves are process-wide.
 pub fn init_metrics() -> Result<(), prometheus::Error> {
-    REGISTRY.register(Box::new(HTTP_REQUESTS_TOTAL.clone()))?;
-    REGISTRY.register(Box::new(HTTP_REQUEST_DURATION.clone()))?;
-    REGISTRY.register(Box::new(AUTH_ATTEMPTS_TOTAL.clone()))?;
-    REGISTRY.register(Box::new(ACTIVE_SESSIONS.clone()))?;
-    REGISTRY.register(Box::new(DEVICE_READINGS_TOTAL.clone()))?;
-    REGISTRY.register(Box::new(DEVICE_ERRORS_TOTAL.clone()))?;
-    REGISTRY.register(Box::new(ML_ANOMALIES_DETECTED.clone()))?;
-    REGISTRY.register(Box::new(ML_ANALYSIS_DURATION.clone()))?;
-    REGISTRY.register(Box::new(DB_CONNECTIONS_ACTIVE.clone()))?;
-    REGISTRY.register(Box::new(DB_QUERY_DURATION.clone()))?;
-    REGISTRY.register(Box::new(CACHE_HITS.clone()))?;
-    REGISTRY.register(Box::new(CACHE_MISSES.clone()))?;
-    REGISTRY.register(Box::new(SSE_CONNECTIONS_ACTIVE.clone()))?;
-    REGISTRY.register(Box::new(SSE_EVENTS_SENT.clone()))?;
-    REGISTRY.register(Box::new(VITALS_HR_CURRENT.clone()))?;
-    REGISTRY.register(Box::new(VITALS_SPO2_CURRENT.clone()))?;
-    
+    let collectors: Vec<Box<dyn Collector>> = vec![
+        Box::new(HTTP_REQUESTS_TOTAL.clone()),
+        Box::new(HTTP_REQUEST_DURATION.clone()),
+        Box::new(AUTH_ATTEMPTS_TOTAL.clone()),
+        Box::new(DEVICE_READINGS_TOTAL.clone()),
+        Box::new(DEVICE_ERRORS_TOTAL.clone()),
+        Box::new(INGESTION_FAILURES_TOTAL.clone()),
+        Box::new(INGESTION_REPROCESSED_TOTAL.clone()),
+        Box::new(RATE_LIMITED_TOTAL.clone()),
+        Box::new(DEPRECATED_FIELDS_TOTAL.clone()),
+        Box::new(ML_ANOMALIES_DETECTED.clone()),
+        Box::new(ML_ANALYSIS_DURATION.clone()),
+        Box::new(ML_ANALYSIS_REUSED.clone()),
+        Box::new(DB_CONNECTIONS_ACTIVE.clone()),
+        Box::new(DB_QUERY_DURATION.clone()),
+        Box::new(CACHE_HITS.clone()),
+        Box::new(CACHE_MISSES.clone()),
+        Box::new(SSE_CONNECTIONS_ACTIVE.clone()),
+        Box::new(SSE_EVENTS_SENT.clone()),
+        Box::new(EVENT_LOG_MISSED_TOTAL.clone()),
+        Box::new(EHR_PUSHES_TOTAL.clone()),
+    ];
+    for collector in collectors {
+        match REGISTRY.register(collector) {
+            Ok(()) | Err(prometheus::Error::AlreadyReg) => {}
+            Err(e) => return Err(e),
+        }
+    }
     Ok(())
 }
 
-/// Prometheus metrics endpoint handler
-pub async fn metrics_handler() -> impl Responder {
-    let encoder = TextEncoder::new();
-    let metric_families = REGISTRY.gather();
-    let mut buffer = Vec::new();
-    
-    if let Err(e) = encoder.encode(&metric_families, &mut buffer) {
-        return HttpResponse::InternalServerError().body(format!("Failed to encode metrics: {}", e));
+/// Count a refused device request. `device_id` must belong to an active device, so label
+/// values stay bounded; anything else is counted as "unverified".
+pub fn record_device_error(device_id: Option<&str>, error_type: &str) {
+    DEVICE_ERRORS_TOTAL
+        .with_label_values(&[device_id.unwrap_or("unverified"), error_type])
+        .inc();
+}
+
+/// Counts an SSE or WebSocket subscriber for as long as it is held
+pub struct StreamConnection(());
+
+impl StreamConnection {
+    pub fn open() -> Self {
+        SSE_CONNECTIONS_ACTIVE.inc();
+        Self(())
+    }
+}
+
+impl Drop for StreamConnection {
+    fn drop(&mut self) {
+        SSE_CONNECTIONS_ACTIVE.dec();
+    }
+}
+
+/// Count a finished request; `endpoint` is the matched route pattern, keeping label values bounded
+pub fn record_request(method: &str, endpoint: &str, status: u16, elapsed: Duration) {
+    HTTP_REQUESTS_TOTAL
+        .with_label_values(&[method, endpoint, &status.to_string()])
+        .inc();
+    HTTP_REQUEST_DURATION
+        .with_label_values(&[method, endpoint])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Cumulative request counts since the process started
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RequestTotals {
+    pub total: u64,
+    /// Slower than the latency threshold asked for
+    pub slow: u64,
+    /// Answered with a 5xx status
+    pub errors: u64,
+}
+
+/// Totals for requests to `endpoint` (`*` for every route) with `method` (`None` for any),
+/// read from the request counter and duration histogram. `latency` must be a bucket bound.
+pub fn request_totals(method: Option<&str>, endpoint: &str, latency: Option<Duration>) -> RequestTotals {
+    let matches = |metric: &Metric| {
+        metric.get_label().iter().all(|label| match label.get_name() {
+            "method" => method.is_none_or(|m| m == label.get_value()),
+            "endpoint" => endpoint == "*" || endpoint == label.get_value(),
+            _ => true,
+        })
+    };
+    let label = |metric: &Metric, name: &str| {
+        metric.get_label().iter().find(|l| l.get_name() == name).map(|l| l.get_value().to_string())
+    };
+
+    let mut totals = RequestTotals::default();
+    for family in HTTP_REQUEST_DURATION.collect() {
+        for metric in family.get_metric().iter().filter(|m| matches(m)) {
+            let histogram = metric.get_histogram();
+            totals.total += histogram.get_sample_count();
+            if let Some(latency) = latency {
+                let fast = histogram
+                    .get_bucket()
+                    .iter()
+                    .find(|b| b.get_upper_bound() >= latency.as_secs_f64())
+                    .map_or(histogram.get_sample_count(), |b| b.get_cumulative_count());
+                totals.slow += histogram.get_sample_count() - fast;
+            }
+        }
     }
-    
-    match String::from_utf8(buffer) {
-        Ok(metrics) => HttpResponse::Ok()
-            .content_type("text/plain; version=0.0.4")
-            .body(metrics),
-        Err(e) => HttpResponse::InternalServerError().body(format!("Failed to convert metrics: {}", e)),
+    for family in HTTP_REQUESTS_TOTAL.collect() {
+        for metric in family.get_metric().iter().filter(|m| matches(m)) {
+            if label(metric, "status").is_some_and(|s| s.starts_with('5')) {
+                totals.errors += metric.get_counter().get_value() as u64;
+            }
+        }
     }
+    totals
+}
+
+/// Every registered metric in the Prometheus text exposition format
+pub fn encode() -> Result<Vec<u8>, prometheus::Error> {
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&REGISTRY.gather(), &mut buffer)?;
+    Ok(buffer)
 }
 
 #[cfg(test)]
@@ -151,10 +261,15 @@ mod tests {
 
     #[test]
     fn test_metrics_initialization() {
-        let result = init_metrics();
-        assert!(result.is_ok());
+        assert!(init_metrics().is_ok());
+        assert!(init_metrics().is_ok(), "registering twice is harmless");
+
+        AUTH_ATTEMPTS_TOTAL.with_label_values(&["success"]).inc();
+        let text = String::from_utf8(encode().unwrap()).unwrap();
+        assert!(text.contains("auth_attempts_total{result=\"success\"}"));
     }
 
+
     #[test]
     fn test_http_requests_counter() {
         HTTP_REQUESTS_TOTAL
@@ -167,4 +282,20 @@ mod tests {
         
         assert!(metric >= 1);
     }
+
+    #[test]
+    fn test_request_totals_split_slow_and_failed() {
+        let endpoint = "/test/request-totals";
+        record_request("POST", endpoint, 201, Duration::from_millis(20));
+        record_request("POST", endpoint, 201, Duration::from_millis(350));
+        record_request("POST", endpoint, 503, Duration::from_millis(5));
+        record_request("GET", endpoint, 200, Duration::from_millis(900));
+
+        let post = request_totals(Some("POST"), endpoint, Some(Duration::from_millis(200)));
+        assert_eq!(post, RequestTotals { total: 3, slow: 1, errors: 1 });
+
+        let any = request_totals(None, endpoint, None);
+        assert_eq!(any, RequestTotals { total: 4, slow: 0, errors: 1 });
+        assert!(request_totals(None, "*", None).total >= 4);
+    }
 }
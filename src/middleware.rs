@@ -1,11 +1,215 @@
+use crate::auth::extract_bearer_token;
+use crate::crash_reporting::{in_request, HandlerPanicked, RequestContext};
+use crate::errors::ApiError;
+use crate::handlers::AppState;
+use crate::models::Claims;
+use crate::rbac::{holds_role, Role};
 use actix_web::{
-    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
-    Error, HttpMessage,
+    body::EitherBody,
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    web, Error, FromRequest, HttpMessage, HttpRequest,
 };
-use futures::future::LocalBoxFuture;
+use futures::future::{FutureExt, LocalBoxFuture};
 use std::future::{ready, Ready};
+use std::ops::Deref;
+use std::panic::AssertUnwindSafe;
 use std::rc::Rc;
 use tracing::{info, warn};
+use uuid::Uuid;
+
+/// The caller of a JWT-protected route, taken by handlers as `claims: AuthenticatedUser`.
+///
+/// Extracting it reads the bearer token, validates it and checks it has not been revoked,
+/// failing the request with 401 otherwise. The claims are then kept in the request
+/// extensions, so later extractions and the audit log reuse them without another lookup.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser(pub Claims);
+
+impl AuthenticatedUser {
+    pub fn into_inner(self) -> Claims {
+        self.0
+    }
+}
+
+impl Deref for AuthenticatedUser {
+    type Target = Claims;
+
+    fn deref(&self) -> &Claims {
+        &self.0
+    }
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = ApiError;
+    type Future = LocalBoxFuture<'static, Result<Self, ApiError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move { authenticate_request(&req).await.map(AuthenticatedUser) })
+    }
+}
+
+/// Verify the request's bearer token once, caching the claims in the request extensions
+pub async fn authenticate_request(req: &HttpRequest) -> Result<Claims, ApiError> {
+    if let Some(claims) = req.extensions().get::<Claims>() {
+        return Ok(claims.clone());
+    }
+
+    let state = req
+        .app_data::<web::Data<AppState>>()
+        .ok_or_else(|| ApiError::Internal("Application state not configured".into()))?;
+
+    let auth_header = req.headers().get("Authorization").and_then(|h| h.to_str().ok());
+    let token = extract_bearer_token(auth_header)
+        .map_err(|_| ApiError::Unauthorized("Missing token".into()))?;
+
+    let claims = verify_token(state, &token).await?;
+    req.extensions_mut().insert(claims.clone());
+    Ok(claims)
+}
+
+/// [`authenticate_request`] for event streams, which also take the token as `?token=`:
+/// browsers can't set headers on `EventSource` or `WebSocket` connections
+pub async fn authenticate_stream_request(req: &HttpRequest) -> Result<Claims, ApiError> {
+    if req.headers().contains_key("Authorization") {
+        return authenticate_request(req).await;
+    }
+
+    let state = req
+        .app_data::<web::Data<AppState>>()
+        .ok_or_else(|| ApiError::Internal("Application state not configured".into()))?;
+
+    let token = web::Query::<StreamToken>::from_query(req.query_string())
+        .map_err(|_| ApiError::Unauthorized("Missing token".into()))?
+        .into_inner()
+        .token;
+
+    let claims = verify_token(state, &token).await?;
+    req.extensions_mut().insert(claims.clone());
+    Ok(claims)
+}
+
+#[derive(serde::Deserialize)]
+struct StreamToken {
+    token: String,
+}
+
+/// Validate a token and check it has not been revoked and its organization claim is current
+async fn verify_token(state: &AppState, token: &str) -> Result<Claims, ApiError> {
+    let verified = check_token(state, token).await;
+    if matches!(verified, Err(ApiError::Unauthorized(_))) {
+        crate::metrics::AUTH_ATTEMPTS_TOTAL.with_label_values(&["token_rejected"]).inc();
+    }
+    verified
+}
+
+async fn check_token(state: &AppState, token: &str) -> Result<Claims, ApiError> {
+    let claims = state
+        .jwt_auth
+        .validate_token(token)
+        .map_err(|_| ApiError::Unauthorized("Invalid token".into()))?;
+
+    if state.jwt_auth.is_token_revoked(claims.jti, &state.pool).await.unwrap_or(false) {
+        return Err(ApiError::Unauthorized("Token revoked".into()));
+    }
+
+    // Handlers scope by the claim, so a token from before the user moved must not be honoured
+    let organization_id: Option<Option<Uuid>> = sqlx::query_scalar("SELECT organization_id FROM users WHERE id = $1")
+        .bind(claims.user_id)
+        .fetch_optional(&state.pool)
+        .await?;
+    if organization_id != Some(claims.org) {
+        return Err(ApiError::Unauthorized("Organization changed; sign in again".into()));
+    }
+
+    Ok(claims)
+}
+
+/// The request line as access logs print it (`%r`), with any `token` query parameter
+/// masked so stream credentials don't end up in log files
+pub fn redacted_request_line(req: &ServiceRequest) -> String {
+    let query = req
+        .query_string()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some(("token", _)) => "token=[redacted]",
+            _ => pair,
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    let target = if query.is_empty() { req.path().to_string() } else { format!("{}?{}", req.path(), query) };
+    format!("{} {} {:?}", req.method(), target, req.version())
+}
+
+/// Route middleware admitting only callers holding a role (see [`crate::rbac`]).
+///
+/// Answers 401 without a valid token and 403 without the role, before the handler or its
+/// body extractors run. The registry wraps every route that lists roles in one of these.
+#[derive(Debug, Clone, Copy)]
+pub struct RequireRole(pub Role);
+
+impl<S, B> Transform<S, ServiceRequest> for RequireRole
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequireRoleMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireRoleMiddleware {
+            service: Rc::new(service),
+            role: self.0,
+        }))
+    }
+}
+
+pub struct RequireRoleMiddleware<S> {
+    service: Rc<S>,
+    role: Role,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireRoleMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let svc = self.service.clone();
+        let role = self.role;
+
+        Box::pin(async move {
+            if let Err(e) = authorize_role(req.request(), role).await {
+                return Ok(req.error_response(e).map_into_right_body());
+            }
+            svc.call(req).await.map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
+
+/// Authenticate the caller and require `role` of them
+pub async fn authorize_role(req: &HttpRequest, role: Role) -> Result<Claims, ApiError> {
+    let claims = authenticate_request(req).await?;
+    let state = req
+        .app_data::<web::Data<AppState>>()
+        .ok_or_else(|| ApiError::Internal("Application state not configured".into()))?;
+    if !holds_role(&state.pool, &state.deployment, &claims, role, None).await? {
+        return Err(ApiError::Forbidden(format!("{} role required", role.label())));
+    }
+    Ok(claims)
+}
 
 /// Audit logging middleware for HIPAA compliance
 pub struct AuditLogger;
@@ -57,9 +261,6 @@ where
                 .and_then(|h| h.to_str().ok())
                 .map(|s| s.to_string());
 
-            // Extract user info if available (from JWT middleware)
-            let user_email = req.extensions().get::<String>().cloned();
-
             let start_time = std::time::Instant::now();
             let res = svc.call(req).await;
             let elapsed = start_time.elapsed();
@@ -67,7 +268,12 @@ where
             match &res {
                 Ok(response) => {
                     let status = response.status().as_u16();
-                    
+                    // Set once a handler has authenticated the caller
+                    let user_email = response.request().extensions().get::<Claims>().map(|c| c.sub.clone());
+                    let endpoint = response.request().match_pattern().unwrap_or_else(|| "unmatched".to_string());
+                    crate::metrics::record_request(&method, &endpoint, status, elapsed);
+                    crate::observability::report_slow_request(&method, &endpoint, status, elapsed);
+
                     // Log all API requests
                     if path.starts_with("/api/") || path.starts_with("/auth/") {
                         info!(
@@ -106,6 +312,7 @@ where
                     }
                 }
                 Err(err) => {
+                    crate::metrics::record_request(&method, "unmatched", err.as_response_error().status_code().as_u16(), elapsed);
                     warn!(
                         method = %method,
                         path = %path,
@@ -175,3 +382,77 @@ where
         })
     }
 }
+
+/// Turns a panicking handler into a 500 carrying the request id, instead of a dropped
+/// connection. The panic itself is recorded by the crash reporter's hook, which sees the
+/// request through [`in_request`].
+pub struct CatchPanic;
+
+impl<S, B> Transform<S, ServiceRequest> for CatchPanic
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = CatchPanicMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CatchPanicMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct CatchPanicMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for CatchPanicMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let context = RequestContext {
+            request_id: req.extensions().get::<String>().cloned(),
+            method: req.method().to_string(),
+            route: req.match_pattern().unwrap_or_else(|| "unmatched".to_string()),
+        };
+        let request_id = context.request_id.clone();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            // The request can't be kept to build a response from (routing needs it to be
+            // the only handle), so the 500 is returned as an error
+            in_request(context, AssertUnwindSafe(fut).catch_unwind())
+                .await
+                .unwrap_or_else(|_| Err(HandlerPanicked { request_id }.into()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_request_line_masks_stream_token() {
+        let req = TestRequest::with_uri("/api/stream/vitals?token=eyJhbGciOi.abc&since=1").to_srv_request();
+        assert_eq!(redacted_request_line(&req), "GET /api/stream/vitals?token=[redacted]&since=1 HTTP/1.1");
+
+        let req = TestRequest::with_uri("/api/vitals/latest").to_srv_request();
+        assert_eq!(redacted_request_line(&req), "GET /api/vitals/latest HTTP/1.1");
+    }
+}
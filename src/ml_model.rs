@@ -0,0 +1,230 @@
+//! Optional learned anomaly score, merged with the rule-based one in `MlService`.
+//!
+//! The model is an ONNX graph taking one `[1, 10]` f32 input, the values in [`FEATURES`]
+//! order, and returning the anomaly score as the first value of its first output. Scores
+//! outside 0..1 are clamped. Missing vitals are 0, as they are for the rules.
+
+use crate::models::SensorReading;
+use anyhow::{bail, Context};
+use chrono::Duration;
+use tract_onnx::prelude::*;
+
+/// Earlier readings of the device the history features are computed over
+pub const MODEL_HISTORY_MINUTES: i64 = 15;
+
+/// The model's inputs, in order
+pub const FEATURES: [&str; 10] = [
+    "heart_rate",
+    "spo2",
+    "temperature",
+    "heart_rate_change",
+    "spo2_change",
+    "temperature_change",
+    "heart_rate_mean",
+    "heart_rate_stddev",
+    "spo2_mean",
+    "history_readings",
+];
+
+pub struct AnomalyModel {
+    plan: TypedSimplePlan<TypedModel>,
+}
+
+impl AnomalyModel {
+    pub fn load(path: &str) -> TractResult<Self> {
+        Self::from_inference_model(tract_onnx::onnx().model_for_path(path)?)
+    }
+
+    fn from_inference_model(model: InferenceModel) -> TractResult<Self> {
+        let plan = model
+            .with_input_fact(0, f32::fact([1, FEATURES.len()]).into())?
+            .into_optimized()?
+            .into_runnable()?;
+        Ok(Self { plan })
+    }
+
+    pub fn score(&self, features: &[f32; FEATURES.len()]) -> TractResult<f32> {
+        let input = tract_ndarray::Array2::from_shape_vec((1, FEATURES.len()), features.to_vec())?.into_tensor();
+        let outputs = self.plan.run(tvec!(input.into()))?;
+        let score = outputs
+            .first()
+            .context("model has no outputs")?
+            .to_array_view::<f32>()?
+            .iter()
+            .next()
+            .copied()
+            .context("model returned an empty output")?;
+        if score.is_nan() {
+            bail!("model returned NaN");
+        }
+        Ok(score.clamp(0.0, 1.0))
+    }
+}
+
+/// The reading's values and summaries of the device's readings in the
+/// [`MODEL_HISTORY_MINUTES`] before it; `recent` may hold older readings too
+pub fn features(reading: &SensorReading, recent: &[SensorReading]) -> [f32; FEATURES.len()] {
+    let window_start = reading.reading_timestamp - Duration::minutes(MODEL_HISTORY_MINUTES);
+    let mut history: Vec<&SensorReading> = recent
+        .iter()
+        .filter(|r| r.reading_timestamp < reading.reading_timestamp && r.reading_timestamp >= window_start)
+        .collect();
+    history.sort_by_key(|r| r.reading_timestamp);
+
+    let hr = reading.heart_rate.unwrap_or(0) as f32;
+    let spo2 = reading.spo2.unwrap_or(0) as f32;
+    let temp = reading.temperature.unwrap_or(0.0);
+
+    // Changes since the latest earlier reading with a signal; no signal now means no change
+    let change = |current: f32, value: fn(&SensorReading) -> Option<f32>| {
+        history
+            .iter()
+            .rev()
+            .find_map(|r| value(r).filter(|v| *v > 0.0))
+            .filter(|_| current > 0.0)
+            .map_or(0.0, |previous| current - previous)
+    };
+    let hr_change = change(hr, |r| r.heart_rate.map(|v| v as f32));
+    let spo2_change = change(spo2, |r| r.spo2.map(|v| v as f32));
+    let temp_change = change(temp, |r| r.temperature);
+
+    let with_current = |value: fn(&SensorReading) -> Option<f32>| -> Vec<f32> {
+        history
+            .iter()
+            .copied()
+            .chain(std::iter::once(reading))
+            .filter_map(value)
+            .filter(|v| *v > 0.0)
+            .collect()
+    };
+    let hrs = with_current(|r| r.heart_rate.map(|v| v as f32));
+    let spo2s = with_current(|r| r.spo2.map(|v| v as f32));
+    let (hr_mean, hr_stddev) = mean_and_stddev(&hrs);
+    let (spo2_mean, _) = mean_and_stddev(&spo2s);
+
+    [hr, spo2, temp, hr_change, spo2_change, temp_change, hr_mean, hr_stddev, spo2_mean, history.len() as f32]
+}
+
+/// Population mean and standard deviation; zeros when there are no values
+fn mean_and_stddev(values: &[f32]) -> (f32, f32) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    let mean = values.iter().sum::<f32>() / values.len() as f32;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / values.len() as f32;
+    (mean, variance.sqrt())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use tract_onnx::pb;
+
+    fn float_tensor(name: &str, dims: Vec<i64>, values: Vec<f32>) -> pb::TensorProto {
+        pb::TensorProto {
+            name: name.to_string(),
+            dims,
+            data_type: pb::tensor_proto::DataType::Float as i32,
+            float_data: values,
+            ..Default::default()
+        }
+    }
+
+    fn node(op_type: &str, input: &[&str], output: &str) -> pb::NodeProto {
+        pb::NodeProto {
+            op_type: op_type.to_string(),
+            input: input.iter().map(|i| i.to_string()).collect(),
+            output: vec![output.to_string()],
+            ..Default::default()
+        }
+    }
+
+    /// `sigmoid(features · weights + bias)`
+    pub(crate) fn logistic_model(weights: [f32; FEATURES.len()], bias: f32) -> AnomalyModel {
+        let value = |name: &str, dims: &[i64]| pb::ValueInfoProto {
+            name: name.to_string(),
+            r#type: Some(pb::TypeProto {
+                value: Some(pb::type_proto::Value::TensorType(pb::type_proto::Tensor {
+                    elem_type: pb::tensor_proto::DataType::Float as i32,
+                    shape: Some(pb::TensorShapeProto {
+                        dim: dims
+                            .iter()
+                            .map(|d| pb::tensor_shape_proto::Dimension {
+                                value: Some(pb::tensor_shape_proto::dimension::Value::DimValue(*d)),
+                                ..Default::default()
+                            })
+                            .collect(),
+                    }),
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let graph = pb::GraphProto {
+            name: "logistic".to_string(),
+            input: vec![value("features", &[1, FEATURES.len() as i64])],
+            output: vec![value("score", &[1, 1])],
+            initializer: vec![
+                float_tensor("weights", vec![FEATURES.len() as i64, 1], weights.to_vec()),
+                float_tensor("bias", vec![1], vec![bias]),
+            ],
+            node: vec![
+                node("MatMul", &["features", "weights"], "logit"),
+                node("Add", &["logit", "bias"], "shifted"),
+                node("Sigmoid", &["shifted"], "score"),
+            ],
+            ..Default::default()
+        };
+        let proto = pb::ModelProto {
+            ir_version: 7,
+            opset_import: vec![pb::OperatorSetIdProto { domain: String::new(), version: 13 }],
+            graph: Some(graph),
+            ..Default::default()
+        };
+        AnomalyModel::from_inference_model(tract_onnx::onnx().model_for_proto_model(&proto).unwrap()).unwrap()
+    }
+
+    pub(crate) fn reading(hr: i32, seconds_ago: i64) -> SensorReading {
+        SensorReading {
+            id: 0,
+            device_id: uuid::Uuid::nil(),
+            heart_rate: Some(hr),
+            spo2: Some(97),
+            temperature: Some(36.8),
+            reading_timestamp: chrono::DateTime::from_timestamp(1_700_000_000 - seconds_ago, 0).unwrap(),
+            received_at: chrono::Utc::now(),
+            quality_score: None,
+            metadata: serde_json::json!({}),
+        }
+    }
+
+    #[test]
+    fn test_features_summarize_the_recent_window() {
+        // Newest first, as the ingestion handler loads them; the last is outside the window
+        let recent = [reading(80, 60), reading(70, 120), reading(0, 180), reading(150, 3600)];
+        let features = features(&reading(90, 0), &recent);
+
+        assert_eq!(features[0..3], [90.0, 97.0, 36.8]);
+        assert_eq!(features[3], 10.0);
+        assert_eq!(features[4], 0.0);
+        assert_eq!(features[6], 80.0);
+        assert!((features[7] - 8.165).abs() < 0.01);
+        assert_eq!(features[8], 97.0);
+        assert_eq!(features[9], 3.0);
+
+        let alone = super::features(&reading(90, 0), &[]);
+        assert_eq!(alone[3..], [0.0, 0.0, 0.0, 90.0, 0.0, 97.0, 0.0]);
+    }
+
+    #[test]
+    fn test_model_scores_the_feature_vector() {
+        let mut weights = [0.0; FEATURES.len()];
+        weights[3] = 0.1;
+        let model = logistic_model(weights, -3.0);
+
+        let steady = model.score(&features(&reading(80, 0), &[reading(80, 60)])).unwrap();
+        let jump = model.score(&features(&reading(140, 0), &[reading(80, 60)])).unwrap();
+        assert!(steady < 0.1, "{}", steady);
+        assert!(jump > 0.9, "{}", jump);
+    }
+}
@@ -1,22 +1,499 @@
 use crate::config::MlConfig;
-use crate::models::{MlAlert, SensorReading};
+use crate::ml_model::{self, AnomalyModel};
+use crate::rule_dsl::{metric_name, Condition, Op};
+use crate::models::{
+    AlertLevel, Baseline, Checkin, Classification, DeltaRule, HeatmapRow, MlAlert, MlRule, PatientAttributes, RiskAssessment, SensorReading, SeverityRule, SimulationCounts,
+    ThresholdRules, VitalMetric,
+};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use schemars::JsonSchema;
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, RwLock};
+use tracing::{error, info, warn};
+use uuid::Uuid;
 // ML computations (currently unused but available for future expansion)
 use serde_json::json;
 
+// Labels recorded in `analysis_details.anomalies`
+pub const BRADYCARDIA: &str = "Bradycardia detected (low heart rate)";
+pub const TACHYCARDIA: &str = "Tachycardia detected (high heart rate)";
+pub const HYPOXEMIA: &str = "Hypoxemia detected (low SpO2)";
+pub const FEVER: &str = "Fever detected";
+pub const HYPOTHERMIA: &str = "Hypothermia risk";
+pub const POOR_SIGNAL: &str = "Poor signal quality detected";
+pub const STATISTICAL_HR: &str = "Statistical HR anomaly";
+pub const STATISTICAL_SPO2: &str = "Statistical SpO2 anomaly";
+pub const HR_RISE: &str = "Rapid heart rate rise";
+pub const HR_DROP: &str = "Rapid heart rate drop";
+pub const SPO2_RISE: &str = "Rapid SpO2 rise";
+pub const SPO2_DROP: &str = "Rapid SpO2 drop";
+pub const TEMPERATURE_RISE: &str = "Rapid temperature rise";
+pub const TEMPERATURE_DROP: &str = "Rapid temperature drop";
+pub const COMPOSITE: &str = "Composite condition matched";
+pub const CUSTOM_RULE: &str = "Custom rule matched";
+pub const MODEL_ANOMALY: &str = "Model-detected anomaly";
+
+/// Mean and standard deviation z-scores use for patients without a baseline yet
+const POPULATION_HR: (f32, f32) = (70.0, 12.0);
+const POPULATION_SPO2: (f32, f32) = (97.0, 2.0);
+
+/// Most earlier readings a reading is analysed with, newest kept
+pub const MAX_HISTORY_READINGS: usize = 500;
+
+/// How often rules changed through another instance are picked up
+const RULE_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+fn delta_label(metric: VitalMetric, rising: bool) -> &'static str {
+    match (metric, rising) {
+        (VitalMetric::HeartRate, true) => HR_RISE,
+        (VitalMetric::HeartRate, false) => HR_DROP,
+        (VitalMetric::Spo2, true) => SPO2_RISE,
+        (VitalMetric::Spo2, false) => SPO2_DROP,
+        (VitalMetric::Temperature, true) => TEMPERATURE_RISE,
+        (VitalMetric::Temperature, false) => TEMPERATURE_DROP,
+    }
+}
+
+/// How explanation summaries name a vital sign
+fn metric_label(metric: VitalMetric) -> &'static str {
+    match metric {
+        VitalMetric::HeartRate => "Heart rate",
+        VitalMetric::Spo2 => "SpO2",
+        VitalMetric::Temperature => "Temperature",
+    }
+}
+
+/// Rate-of-change rules for patients whose threshold profile sets none
+pub fn default_delta_rules() -> Vec<DeltaRule> {
+    vec![
+        DeltaRule { metric: VitalMetric::HeartRate, change: 30.0, window_minutes: 5, critical: false },
+        DeltaRule { metric: VitalMetric::Spo2, change: -5.0, window_minutes: 10, critical: false },
+    ]
+}
+
+/// Longest window any delta rule looks back over, i.e. how much history analysis needs
+pub fn delta_lookback(rules: &ThresholdRules) -> Duration {
+    let minutes = match &rules.delta_rules {
+        Some(rules) => rules.iter().map(|r| r.window_minutes).max().unwrap_or(0),
+        None => default_delta_rules().iter().map(|r| r.window_minutes).max().unwrap_or(0),
+    };
+    Duration::minutes(minutes)
+}
+
+/// Whether a severity rule covers this patient and the anomalies found
+fn severity_rule_applies(rule: &SeverityRule, age: Option<i32>, patient: &PatientAttributes, anomalies: &[&str]) -> bool {
+    let in_band = |bound: Option<i32>, within: fn(i32, i32) -> bool| match bound {
+        Some(bound) => age.is_some_and(|age| within(age, bound)),
+        None => true,
+    };
+    let diagnosed = rule.diagnoses.is_empty()
+        || rule.diagnoses.iter().any(|d| patient.diagnoses.iter().any(|p| p.eq_ignore_ascii_case(d)));
+    let involved = match rule.metric {
+        Some(metric) => anomaly_labels(metric_name(metric))
+            .is_some_and(|labels| anomalies.iter().any(|a| labels.contains(a))),
+        None => true,
+    };
+    in_band(rule.min_age, |age, min| age >= min) && in_band(rule.max_age, |age, max| age <= max) && diagnosed && involved
+}
+
+/// What a reading is judged against beyond its own values
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AnalysisContext<'a> {
+    /// Earlier readings of the same device, for rate-of-change rules
+    pub recent: &'a [SensorReading],
+    /// The patient's record, for severity rules
+    pub patient: Option<&'a PatientAttributes>,
+    /// The walker's trailing-week vitals, for statistical scores
+    pub baseline: Option<&'a Baseline>,
+}
+
+/// A rate-of-change rule that fired, recorded in `analysis_details.deltas`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeltaMatch {
+    pub metric: VitalMetric,
+    /// Change observed since `since`
+    pub observed: f32,
+    /// The rule's threshold and window
+    pub change: f32,
+    pub window_minutes: i64,
+    pub since: DateTime<Utc>,
+    pub critical: bool,
+}
+
+/// Which kind of check an [`Explanation`] describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExplanationCheck {
+    Threshold,
+    SignalQuality,
+    Statistical,
+    RateOfChange,
+    Composite,
+    CustomRule,
+    Model,
+}
+
+/// How far a reading is from the mean its z-score was computed against
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct BaselineDeviation {
+    pub mean: f32,
+    pub stddev: f32,
+    /// Reading minus mean
+    pub deviation: f32,
+    pub zscore: f32,
+    /// The walker's own trailing-week baseline rather than population norms
+    pub personal: bool,
+}
+
+/// Why one check fired, recorded in `analysis_details.explanations` in the order the checks
+/// ran. The dashboard renders these, so fields are only ever added, never renamed or removed.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct Explanation {
+    /// The label recorded in `analysis_details.anomalies`
+    pub anomaly: String,
+    pub check: ExplanationCheck,
+    /// The vital sign checked; none for composite conditions, signal quality and the model
+    pub metric: Option<VitalMetric>,
+    /// Composite or custom rule name
+    pub rule: Option<String>,
+    /// What was compared with `threshold`: the reading, the size of its z-score, its change
+    /// over the window, the signal quality or the model score
+    pub observed: Option<f32>,
+    /// `<`, `<=`, `>`, `>=`, `=` or `!=`, read as `observed comparator threshold`
+    pub comparator: Option<String>,
+    pub threshold: Option<f32>,
+    pub window_minutes: Option<i64>,
+    pub baseline: Option<BaselineDeviation>,
+    /// The reading's values the check looked at, by metric name
+    pub values: BTreeMap<String, f32>,
+    /// Added to the rule score, which is halved and capped at 1
+    pub weight: f32,
+    /// The level the check raises the alert to at least, before severity rules
+    pub level: AlertLevel,
+    /// One line for people, e.g. "Heart rate 195 bpm above 180 bpm"
+    pub summary: String,
+}
+
+impl Explanation {
+    fn new(anomaly: &str, check: ExplanationCheck, weight: f32, level: AlertLevel, summary: String) -> Self {
+        Self {
+            anomaly: anomaly.to_string(),
+            check,
+            metric: None,
+            rule: None,
+            observed: None,
+            comparator: None,
+            threshold: None,
+            window_minutes: None,
+            baseline: None,
+            values: BTreeMap::new(),
+            weight,
+            level,
+            summary,
+        }
+    }
+
+    /// A single vital compared with a threshold
+    fn compared(mut self, metric: VitalMetric, observed: f32, comparator: &str, threshold: f32) -> Self {
+        self.metric = Some(metric);
+        self.observed = Some(observed);
+        self.comparator = Some(comparator.to_string());
+        self.threshold = Some(threshold);
+        self
+    }
+
+    fn with_value(mut self, metric: VitalMetric, value: f32) -> Self {
+        self.values.insert(metric_name(metric).to_string(), value);
+        self
+    }
+}
+
+/// Anomaly labels concerning one vital sign (`heart_rate`, `spo2` or `temperature`)
+pub fn anomaly_labels(metric: &str) -> Option<&'static [&'static str]> {
+    match metric {
+        "heart_rate" => Some(&[BRADYCARDIA, TACHYCARDIA, STATISTICAL_HR, HR_RISE, HR_DROP]),
+        "spo2" => Some(&[HYPOXEMIA, STATISTICAL_SPO2, SPO2_RISE, SPO2_DROP]),
+        "temperature" => Some(&[FEVER, HYPOTHERMIA, TEMPERATURE_RISE, TEMPERATURE_DROP]),
+        _ => None,
+    }
+}
+
+/// Lay out `(date, bucket, count)` cells as one row per day from `start`, zero-filled
+pub fn heatmap_rows(start: NaiveDate, days: u32, bucket_hours: u32, cells: &[(NaiveDate, i32, i64)]) -> Vec<HeatmapRow> {
+    let buckets = (24 / bucket_hours.max(1)) as usize;
+    let mut rows: Vec<HeatmapRow> = start
+        .iter_days()
+        .take(days as usize)
+        .map(|date| HeatmapRow { date, counts: vec![0; buckets] })
+        .collect();
+
+    for (date, bucket, count) in cells {
+        let row = (*date - start).num_days();
+        if let (Ok(row), Ok(bucket)) = (usize::try_from(row), usize::try_from(*bucket)) {
+            if let Some(cell) = rows.get_mut(row).and_then(|r| r.counts.get_mut(bucket)) {
+                *cell += count;
+            }
+        }
+    }
+    rows
+}
+
+/// Whether a rule's comparison holds for a reading; a missing or zero (no signal) value never does
+fn custom_rule_holds(rule: &MlRule, reading: &SensorReading) -> bool {
+    let Some(op) = Op::from_symbol(&rule.comparator) else {
+        return false;
+    };
+    rule.metric.value(reading).filter(|v| *v > 0.0).is_some_and(|v| op.holds(v, rule.value))
+}
+
+/// When a rule's comparison started holding without a break over the device's readings,
+/// if it holds for `reading` and has done so for at least the rule's duration
+fn custom_rule_held_since(rule: &MlRule, reading: &SensorReading, recent: &[SensorReading]) -> Option<DateTime<Utc>> {
+    if !custom_rule_holds(rule, reading) {
+        return None;
+    }
+    let needed = reading.reading_timestamp - Duration::seconds(i64::from(rule.duration_seconds));
+    let mut earlier: Vec<&SensorReading> =
+        recent.iter().filter(|r| r.reading_timestamp < reading.reading_timestamp).collect();
+    earlier.sort_by_key(|r| std::cmp::Reverse(r.reading_timestamp));
+
+    let mut since = reading.reading_timestamp;
+    for earlier in earlier {
+        if since <= needed || !custom_rule_holds(rule, earlier) {
+            break;
+        }
+        since = earlier.reading_timestamp;
+    }
+    (since <= needed).then_some(since)
+}
+
+/// Background worker keeping the admin-defined rules in step with the database, loading
+/// them at startup and then picking up changes made through other instances
+pub fn spawn_rule_refresher(pool: PgPool, ml: Arc<MlService>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RULE_REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = ml.refresh_custom_rules(&pool).await {
+                error!("ML rule refresh failed: {}", e);
+            }
+        }
+    })
+}
+
+/// How long after a reading an identical one from the same device reuses its analysis
+const REUSE_WINDOW_SECONDS: i64 = 60;
+
+/// A device's last analysed reading, with everything its result depended on
+#[derive(Debug, Clone)]
+struct PreviousAnalysis {
+    heart_rate: Option<i32>,
+    spo2: Option<i32>,
+    temperature: Option<f32>,
+    reading_timestamp: DateTime<Utc>,
+    rules: ThresholdRules,
+    patient: Option<PatientAttributes>,
+    baseline: Option<Baseline>,
+    result: MlAnalysisResult,
+}
+
+impl PreviousAnalysis {
+    /// Whether analysing `reading` would yield the same result. Values are compared
+    /// exactly: rounding could hide a reading that has just crossed a threshold.
+    ///
+    /// Rate-of-change rules only look at earlier readings, and an identical previous one
+    /// adds no change, so a result without deltas carries over; one with deltas may not,
+    /// as the readings that caused them age out of the window.
+    fn matches(&self, reading: &SensorReading, rules: &ThresholdRules, context: AnalysisContext<'_>) -> bool {
+        let elapsed = reading.reading_timestamp - self.reading_timestamp;
+        self.heart_rate == reading.heart_rate
+            && self.spo2 == reading.spo2
+            && self.temperature == reading.temperature
+            && elapsed >= Duration::zero()
+            && elapsed <= Duration::seconds(REUSE_WINDOW_SECONDS)
+            && self.reading_timestamp.date_naive() == reading.reading_timestamp.date_naive()
+            && self.rules == *rules
+            && self.patient.as_ref() == context.patient
+            && self.baseline.as_ref() == context.baseline
+            && self.result.details.get("deltas").is_none()
+    }
+}
+
 pub struct MlService {
     config: MlConfig,
+    /// Last analysis per device, for skipping readings identical to the previous one
+    previous: Mutex<HashMap<Uuid, PreviousAnalysis>>,
+    /// Enabled rules from `ml_rules`, applied to every reading
+    custom_rules: RwLock<Vec<MlRule>>,
+    /// `ml.model`, when configured and loadable
+    model: Option<AnomalyModel>,
 }
 
 impl MlService {
+    /// Loads `ml.model` if set; a model that fails to load is logged and the rules used alone
     pub fn new(config: MlConfig) -> Self {
-        Self { config }
+        let model = config.model.as_ref().and_then(|model| match AnomalyModel::load(&model.path) {
+            Ok(loaded) => {
+                info!(path = %model.path, weight = model.weight, "Loaded anomaly model");
+                Some(loaded)
+            }
+            Err(e) => {
+                error!(path = %model.path, "Failed to load anomaly model, using the rules alone: {:#}", e);
+                None
+            }
+        });
+        Self::with_model(config, model)
+    }
+
+    fn with_model(config: MlConfig, model: Option<AnomalyModel>) -> Self {
+        Self { config, previous: Mutex::new(HashMap::new()), custom_rules: RwLock::new(Vec::new()), model }
+    }
+
+    /// Replace the admin-defined rules; analyses kept for reuse were judged by the old ones
+    pub fn set_custom_rules(&self, rules: Vec<MlRule>) {
+        let rules: Vec<MlRule> = rules.into_iter().filter(|r| r.enabled).collect();
+        let mut current = self.custom_rules.write().unwrap_or_else(|e| e.into_inner());
+        if *current != rules {
+            *current = rules;
+            self.previous.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        }
+    }
+
+    /// Reload the admin-defined rules from the database
+    pub async fn refresh_custom_rules(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        let rules = sqlx::query_as("SELECT * FROM ml_rules WHERE enabled ORDER BY created_at, id")
+            .fetch_all(pool)
+            .await?;
+        self.set_custom_rules(rules);
+        Ok(())
     }
 
-    /// Analyze sensor reading for anomalies
+    /// How far back a reading's device history must go for the longest rule duration
+    pub fn custom_rule_lookback(&self) -> Duration {
+        let rules = self.custom_rules.read().unwrap_or_else(|e| e.into_inner());
+        Duration::seconds(rules.iter().map(|r| i64::from(r.duration_seconds)).max().unwrap_or(0))
+    }
+
+    /// How far back a reading's device history must go for the model's features
+    pub fn model_lookback(&self) -> Duration {
+        match self.model {
+            Some(_) => Duration::minutes(ml_model::MODEL_HISTORY_MINUTES),
+            None => Duration::zero(),
+        }
+    }
+
+    /// How far back a reading's device history must go for every check under `rules`
+    pub fn history_lookback(&self, rules: &ThresholdRules) -> Duration {
+        delta_lookback(rules).max(self.custom_rule_lookback()).max(self.model_lookback())
+    }
+
+    /// How long a device's alerts at one level are held back after one goes out; 0 holds none back
+    pub fn alert_cooldown_seconds(&self) -> u64 {
+        self.config.alert_cooldown_seconds
+    }
+
+    /// The model's score and weight for `reading`; `None` without a model or when inference fails
+    fn model_score(&self, reading: &SensorReading, recent: &[SensorReading]) -> Option<(f32, f32)> {
+        let model = self.model.as_ref()?;
+        let weight = self.config.model.as_ref().map_or(0.0, |m| m.weight);
+        match model.score(&ml_model::features(reading, recent)) {
+            Ok(score) => Some((score, weight)),
+            Err(e) => {
+                warn!(device_id = %reading.device_id, "Anomaly model inference failed, using the rules alone: {:#}", e);
+                None
+            }
+        }
+    }
+
+    /// The analysis of the device's previous reading, when `reading` is identical to it
+    /// and would be judged the same way; stationary sensors repeat readings often.
+    /// `context.recent` is not needed for this. Never with a model configured, whose score
+    /// follows the device's recent history even when the reading repeats.
+    pub fn reuse_analysis(
+        &self,
+        reading: &SensorReading,
+        rules: &ThresholdRules,
+        context: AnalysisContext<'_>,
+    ) -> Option<MlAnalysisResult> {
+        if self.model.is_some() {
+            return None;
+        }
+        // A rule with a duration can start to match as identical readings go on
+        let custom_rules = self.custom_rules.read().unwrap_or_else(|e| e.into_inner());
+        if custom_rules.iter().any(|rule| rule.duration_seconds > 0 && custom_rule_holds(rule, reading)) {
+            return None;
+        }
+        drop(custom_rules);
+
+        let previous = self.previous.lock().unwrap_or_else(|e| e.into_inner());
+        previous
+            .get(&reading.device_id)
+            .filter(|p| p.matches(reading, rules, context))
+            .map(|p| p.result.clone())
+    }
+
+    /// Keep `result` for [`reuse_analysis`](Self::reuse_analysis) of the device's next reading
+    pub fn remember_analysis(
+        &self,
+        reading: &SensorReading,
+        rules: &ThresholdRules,
+        context: AnalysisContext<'_>,
+        result: &MlAnalysisResult,
+    ) {
+        let mut previous = self.previous.lock().unwrap_or_else(|e| e.into_inner());
+        previous.insert(
+            reading.device_id,
+            PreviousAnalysis {
+                heart_rate: reading.heart_rate,
+                spo2: reading.spo2,
+                temperature: reading.temperature,
+                reading_timestamp: reading.reading_timestamp,
+                rules: rules.clone(),
+                patient: context.patient.cloned(),
+                baseline: context.baseline.cloned(),
+                result: result.clone(),
+            },
+        );
+    }
+
+    /// Alert thresholds from configuration, used for patients without a threshold profile
+    pub fn default_rules(&self) -> ThresholdRules {
+        ThresholdRules {
+            hr_low: self.config.critical_hr_low,
+            hr_high: self.config.critical_hr_high,
+            spo2_low: self.config.critical_spo2_low,
+            fever_temperature: 38.0,
+            hypothermia_temperature: 35.5,
+            delta_rules: Some(default_delta_rules()),
+            composite_rules: Vec::new(),
+            severity_rules: Vec::new(),
+        }
+    }
+
+    /// Analyze sensor reading for anomalies against the configured thresholds
     pub fn analyze_reading(&self, reading: &SensorReading) -> MlAnalysisResult {
+        self.analyze_reading_with(reading, &self.default_rules())
+    }
+
+    /// Analyze sensor reading for anomalies against a patient's threshold rules
+    pub fn analyze_reading_with(&self, reading: &SensorReading, rules: &ThresholdRules) -> MlAnalysisResult {
+        self.analyze_reading_in_context(reading, AnalysisContext::default(), rules)
+    }
+
+    /// Analyze sensor reading against a patient's threshold rules, including rate-of-change
+    /// rules over the device's recent readings and severity rules for the patient
+    pub fn analyze_reading_in_context(
+        &self,
+        reading: &SensorReading,
+        context: AnalysisContext<'_>,
+        rules: &ThresholdRules,
+    ) -> MlAnalysisResult {
         let mut anomalies = Vec::new();
         let mut anomaly_score = 0.0;
-        let mut alert_level = "none".to_string();
+        let mut alert_level = AlertLevel::None;
+        let mut explanations = Vec::new();
 
         // Extract values with defaults for Option types
         let hr = reading.heart_rate.unwrap_or(0);
@@ -25,37 +502,63 @@ impl MlService {
 
         // 1. Critical threshold checks
         if hr > 0 {
-            if hr < self.config.critical_hr_low {
-                anomalies.push("Bradycardia detected (low heart rate)");
+            if hr < rules.hr_low {
+                anomalies.push(BRADYCARDIA);
                 anomaly_score += 0.8;
-                alert_level = "critical".to_string();
-            } else if hr > self.config.critical_hr_high {
-                anomalies.push("Tachycardia detected (high heart rate)");
+                alert_level = AlertLevel::Critical;
+                let summary = format!("Heart rate {} bpm below {} bpm", hr, rules.hr_low);
+                explanations.push(
+                    Explanation::new(BRADYCARDIA, ExplanationCheck::Threshold, 0.8, AlertLevel::Critical, summary)
+                        .compared(VitalMetric::HeartRate, hr as f32, "<", rules.hr_low as f32)
+                        .with_value(VitalMetric::HeartRate, hr as f32),
+                );
+            } else if hr > rules.hr_high {
+                anomalies.push(TACHYCARDIA);
                 anomaly_score += 0.8;
-                alert_level = "critical".to_string();
+                alert_level = AlertLevel::Critical;
+                let summary = format!("Heart rate {} bpm above {} bpm", hr, rules.hr_high);
+                explanations.push(
+                    Explanation::new(TACHYCARDIA, ExplanationCheck::Threshold, 0.8, AlertLevel::Critical, summary)
+                        .compared(VitalMetric::HeartRate, hr as f32, ">", rules.hr_high as f32)
+                        .with_value(VitalMetric::HeartRate, hr as f32),
+                );
             }
         }
 
-        if spo2 > 0 && spo2 < self.config.critical_spo2_low {
-            anomalies.push("Hypoxemia detected (low SpO2)");
+        if spo2 > 0 && spo2 < rules.spo2_low {
+            anomalies.push(HYPOXEMIA);
             anomaly_score += 0.9;
-            alert_level = "critical".to_string();
+            alert_level = AlertLevel::Critical;
+            let summary = format!("SpO2 {}% below {}%", spo2, rules.spo2_low);
+            explanations.push(
+                Explanation::new(HYPOXEMIA, ExplanationCheck::Threshold, 0.9, AlertLevel::Critical, summary)
+                    .compared(VitalMetric::Spo2, spo2 as f32, "<", rules.spo2_low as f32)
+                    .with_value(VitalMetric::Spo2, spo2 as f32),
+            );
         }
 
         // 2. Temperature anomalies
         if temp > 0.0 {
-            if temp > 38.0 {
-                anomalies.push("Fever detected");
+            if temp > rules.fever_temperature {
+                anomalies.push(FEVER);
                 anomaly_score += 0.6;
-                if alert_level == "none" {
-                    alert_level = "high".to_string();
-                }
-            } else if temp < 35.5 {
-                anomalies.push("Hypothermia risk");
+                alert_level = alert_level.max(AlertLevel::High);
+                let summary = format!("Temperature {:.1} °C above {:.1} °C", temp, rules.fever_temperature);
+                explanations.push(
+                    Explanation::new(FEVER, ExplanationCheck::Threshold, 0.6, AlertLevel::High, summary)
+                        .compared(VitalMetric::Temperature, temp, ">", rules.fever_temperature)
+                        .with_value(VitalMetric::Temperature, temp),
+                );
+            } else if temp < rules.hypothermia_temperature {
+                anomalies.push(HYPOTHERMIA);
                 anomaly_score += 0.7;
-                if alert_level == "none" {
-                    alert_level = "high".to_string();
-                }
+                alert_level = alert_level.max(AlertLevel::High);
+                let summary = format!("Temperature {:.1} °C below {:.1} °C", temp, rules.hypothermia_temperature);
+                explanations.push(
+                    Explanation::new(HYPOTHERMIA, ExplanationCheck::Threshold, 0.7, AlertLevel::High, summary)
+                        .compared(VitalMetric::Temperature, temp, "<", rules.hypothermia_temperature)
+                        .with_value(VitalMetric::Temperature, temp),
+                );
             }
         }
 
@@ -63,56 +566,292 @@ impl MlService {
         let quality_score = self.assess_signal_quality(hr, spo2, temp);
         
         if quality_score < 0.5 {
-            anomalies.push("Poor signal quality detected");
-            if alert_level == "none" {
-                alert_level = "low".to_string();
-            }
+            anomalies.push(POOR_SIGNAL);
+            alert_level = alert_level.max(AlertLevel::Low);
+            let summary = format!("Signal quality {:.2} below 0.50", quality_score);
+            let mut explanation = Explanation::new(POOR_SIGNAL, ExplanationCheck::SignalQuality, 0.0, AlertLevel::Low, summary);
+            explanation.observed = Some(quality_score);
+            explanation.comparator = Some("<".to_string());
+            explanation.threshold = Some(0.5);
+            explanation.values = [("heart_rate", hr as f32), ("spo2", spo2 as f32), ("temperature", temp)]
+                .into_iter()
+                .map(|(metric, value)| (metric.to_string(), value))
+                .collect();
+            explanations.push(explanation);
         }
 
-        // 4. Statistical anomaly detection (simplified z-score)
-        let hr_zscore = self.calculate_zscore(hr as f32, 70.0, 12.0);
-        let spo2_zscore = self.calculate_zscore(spo2 as f32, 97.0, 2.0);
+        // 4. Statistical anomaly detection (simplified z-score), against the walker's own
+        // trailing week once it has enough readings and population norms until then
+        let hr_baseline = context.baseline.and_then(Baseline::heart_rate);
+        let spo2_baseline = context.baseline.and_then(Baseline::spo2);
+        let (hr_mean, hr_stddev) = hr_baseline.unwrap_or(POPULATION_HR);
+        let (spo2_mean, spo2_stddev) = spo2_baseline.unwrap_or(POPULATION_SPO2);
+        let hr_zscore = self.calculate_zscore(hr as f32, hr_mean, hr_stddev);
+        let spo2_zscore = self.calculate_zscore(spo2 as f32, spo2_mean, spo2_stddev);
         
+        let statistical = |anomaly: &str, metric: VitalMetric, value: f32, mean: f32, stddev: f32, zscore: f32, personal: bool| {
+            let against = if personal { "the walker's baseline" } else { "population norms" };
+            let summary = format!("{} {} is {:.1} standard deviations from {} ({:.1})", metric_label(metric), value, zscore, against, mean);
+            let mut explanation = Explanation::new(anomaly, ExplanationCheck::Statistical, 0.5, AlertLevel::None, summary)
+                .compared(metric, zscore.abs(), ">", 3.0)
+                .with_value(metric, value);
+            explanation.baseline = Some(BaselineDeviation { mean, stddev, deviation: value - mean, zscore, personal });
+            explanation
+        };
+
         if hr_zscore.abs() > 3.0 {
-            anomalies.push("Statistical HR anomaly");
+            anomalies.push(STATISTICAL_HR);
             anomaly_score += 0.5;
+            explanations.push(statistical(STATISTICAL_HR, VitalMetric::HeartRate, hr as f32, hr_mean, hr_stddev, hr_zscore, hr_baseline.is_some()));
         }
 
         if spo2_zscore.abs() > 3.0 {
-            anomalies.push("Statistical SpO2 anomaly");
+            anomalies.push(STATISTICAL_SPO2);
             anomaly_score += 0.5;
+            explanations.push(statistical(STATISTICAL_SPO2, VitalMetric::Spo2, spo2 as f32, spo2_mean, spo2_stddev, spo2_zscore, spo2_baseline.is_some()));
         }
 
-        // 5. Classification
+        // 5. Rate of change, which catches deterioration before absolute thresholds do
+        let default_deltas;
+        let delta_rules = match &rules.delta_rules {
+            Some(delta_rules) => delta_rules,
+            None => {
+                default_deltas = default_delta_rules();
+                &default_deltas
+            }
+        };
+        let deltas = self.detect_temporal_anomalies(reading, context.recent, delta_rules);
+        for delta in &deltas {
+            let label = delta_label(delta.metric, delta.observed > 0.0);
+            let level = if delta.critical { AlertLevel::Critical } else { AlertLevel::High };
+            anomalies.push(label);
+            anomaly_score += 0.6;
+            alert_level = alert_level.max(level);
+
+            let summary = format!(
+                "{} changed by {:+.1} in {} minutes (rule: {:+.1})",
+                metric_label(delta.metric), delta.observed, delta.window_minutes, delta.change
+            );
+            let comparator = if delta.change > 0.0 { ">=" } else { "<=" };
+            let mut explanation = Explanation::new(label, ExplanationCheck::RateOfChange, 0.6, level, summary)
+                .compared(delta.metric, delta.observed, comparator, delta.change);
+            if let Some(value) = delta.metric.value(reading) {
+                explanation = explanation.with_value(delta.metric, value);
+            }
+            explanation.window_minutes = Some(delta.window_minutes);
+            explanations.push(explanation);
+        }
+
+        // 6. Conditions combining several vitals; rules are validated on save, so a condition
+        // that no longer parses is skipped rather than failing the reading
+        let mut composites = Vec::new();
+        for rule in &rules.composite_rules {
+            let condition = match Condition::parse(&rule.condition) {
+                Ok(condition) => condition,
+                Err(e) => {
+                    tracing::warn!(rule = %rule.name, "Skipping unparseable composite rule: {}", e);
+                    continue;
+                }
+            };
+            if !condition.evaluate(reading) {
+                continue;
+            }
+            let values: serde_json::Map<String, serde_json::Value> = condition
+                .metrics()
+                .into_iter()
+                .map(|metric| (metric_name(metric).to_string(), json!(metric.value(reading))))
+                .collect();
+            composites.push(json!({
+                "name": rule.name,
+                "condition": condition.to_string(),
+                "critical": rule.critical,
+                "values": values,
+            }));
+
+            let level = if rule.critical { AlertLevel::Critical } else { AlertLevel::High };
+            anomaly_score += 0.7;
+            alert_level = alert_level.max(level);
+
+            let summary = format!("Rule '{}' matched: {}", rule.name, condition);
+            let mut explanation = Explanation::new(COMPOSITE, ExplanationCheck::Composite, 0.7, level, summary);
+            explanation.rule = Some(rule.name.clone());
+            for metric in condition.metrics() {
+                if let Some(value) = metric.value(reading) {
+                    explanation = explanation.with_value(metric, value);
+                }
+            }
+            explanations.push(explanation);
+        }
+        if !composites.is_empty() {
+            anomalies.push(COMPOSITE);
+        }
+
+        // 7. Rules admins defined, each held for its duration over the device's readings
+        let mut custom = Vec::new();
+        for rule in self.custom_rules.read().unwrap_or_else(|e| e.into_inner()).iter() {
+            let Some(since) = custom_rule_held_since(rule, reading, context.recent) else {
+                continue;
+            };
+            custom.push(json!({
+                "id": rule.id,
+                "name": rule.name,
+                "condition": format!("{} {} {}", metric_name(rule.metric), rule.comparator, rule.value),
+                "duration_seconds": rule.duration_seconds,
+                "observed": rule.metric.value(reading),
+                "since": since,
+            }));
+
+            anomaly_score += 0.7;
+            alert_level = alert_level.max(rule.alert_level);
+
+            let observed = rule.metric.value(reading).unwrap_or(0.0);
+            let condition = format!("{} {} {}", metric_name(rule.metric), rule.comparator, rule.value);
+            let summary = match rule.duration_seconds {
+                0 => format!("Rule '{}' matched: {}", rule.name, condition),
+                seconds => format!("Rule '{}' matched: {} for {} s", rule.name, condition, seconds),
+            };
+            let mut explanation = Explanation::new(CUSTOM_RULE, ExplanationCheck::CustomRule, 0.7, rule.alert_level, summary)
+                .compared(rule.metric, observed, &rule.comparator, rule.value)
+                .with_value(rule.metric, observed);
+            explanation.rule = Some(rule.name.clone());
+            explanations.push(explanation);
+        }
+        if !custom.is_empty() {
+            anomalies.push(CUSTOM_RULE);
+        }
+
+        // 8. The learned score, which can flag patterns no rule describes
+        let model_score = self.model_score(reading, context.recent);
+        if let Some((score, _)) = model_score.filter(|(score, _)| *score >= self.config.anomaly_threshold) {
+            anomalies.push(MODEL_ANOMALY);
+            anomaly_score += 0.5;
+            alert_level = alert_level.max(AlertLevel::Medium);
+
+            let threshold = self.config.anomaly_threshold;
+            let summary = format!("Model score {:.2} at or above {:.2}", score, threshold);
+            let mut explanation = Explanation::new(MODEL_ANOMALY, ExplanationCheck::Model, 0.5, AlertLevel::Medium, summary);
+            explanation.observed = Some(score);
+            explanation.comparator = Some(">=".to_string());
+            explanation.threshold = Some(threshold);
+            explanations.push(explanation);
+        }
+
+        // 9. Severity recalibrated for the patient's age and diagnoses, once every anomaly is known
+        let mut adjustments = Vec::new();
+        if let Some(patient) = context.patient.filter(|_| alert_level != AlertLevel::None) {
+            let age = patient.age_on(reading.reading_timestamp.date_naive());
+            for rule in rules.severity_rules.iter().filter(|r| severity_rule_applies(r, age, patient, &anomalies)) {
+                let from = alert_level;
+                alert_level = alert_level.shift(rule.shift);
+                adjustments.push(json!({"rule": rule, "from": from, "to": alert_level}));
+            }
+        }
+
+        // 10. Classification
         let classification = if anomaly_score == 0.0 {
-            "normal"
+            Classification::Normal
         } else if anomaly_score < 0.5 {
-            "warning"
-        } else if anomaly_score < 0.8 {
-            "critical"
+            Classification::Warning
         } else {
-            "critical"
+            Classification::Critical
         };
 
-        // Normalize anomaly score to 0-1
-        let final_score = (anomaly_score / 2.0_f32).min(1.0);
+        // Normalize anomaly score to 0-1, then merge in the model's; the merge can raise the
+        // score but never lowers it, so the model cannot silence a rule that fired
+        let rule_score = (anomaly_score / 2.0_f32).min(1.0);
+        let final_score = match model_score {
+            Some((score, weight)) => rule_score.max((1.0 - weight) * rule_score + weight * score),
+            None => rule_score,
+        };
+
+        let mut details = json!({
+            "anomalies": anomalies,
+            "explanations": explanations,
+            "hr_zscore": hr_zscore,
+            "spo2_zscore": spo2_zscore,
+        });
+        if hr_baseline.is_some() || spo2_baseline.is_some() {
+            details["baseline"] = json!({
+                "hr_mean": hr_mean,
+                "hr_stddev": hr_stddev,
+                "spo2_mean": spo2_mean,
+                "spo2_stddev": spo2_stddev,
+                "patient_hr": hr_baseline.is_some(),
+                "patient_spo2": spo2_baseline.is_some(),
+            });
+        }
+        if !deltas.is_empty() {
+            details["deltas"] = json!(deltas);
+        }
+        if !composites.is_empty() {
+            details["composites"] = json!(composites);
+        }
+        if !custom.is_empty() {
+            details["custom_rules"] = json!(custom);
+        }
+        if !adjustments.is_empty() {
+            details["severity_adjustments"] = json!(adjustments);
+        }
+        if let Some((score, weight)) = model_score {
+            details["model"] = json!({"score": score, "weight": weight, "rule_score": rule_score});
+        }
 
         MlAnalysisResult {
             anomaly_detected: !anomalies.is_empty(),
             anomaly_score: final_score,
-            classification: classification.to_string(),
+            classification,
             alert_level,
             quality_score,
-            details: json!({
-                "anomalies": anomalies,
-                "hr_zscore": hr_zscore,
-                "spo2_zscore": spo2_zscore,
-            }),
+            details,
+        }
+    }
+
+    /// Replay one device's readings against `rules` without storing anything, counting the
+    /// alerts they would have raised. `history` is oldest first and may start before `from`
+    /// so the first counted readings have their lookback; only readings from `from` on count.
+    pub fn simulate(
+        &self,
+        history: &[SensorReading],
+        from: DateTime<Utc>,
+        patient: Option<&PatientAttributes>,
+        baseline: Option<&Baseline>,
+        rules: &ThresholdRules,
+    ) -> SimulationCounts {
+        let lookback = self.history_lookback(rules);
+        let cooldown = Duration::seconds(self.config.alert_cooldown_seconds as i64);
+        let mut counts = SimulationCounts::default();
+        let mut last_pushed: HashMap<AlertLevel, DateTime<Utc>> = HashMap::new();
+
+        let mut start = 0;
+        for (i, reading) in history.iter().enumerate() {
+            if reading.reading_timestamp < from {
+                continue;
+            }
+            while history[start].reading_timestamp < reading.reading_timestamp - lookback {
+                start += 1;
+            }
+            let recent = &history[start.max(i.saturating_sub(MAX_HISTORY_READINGS))..i];
+            let analysis = self.analyze_reading_in_context(reading, AnalysisContext { recent, patient, baseline }, rules);
+            for label in analysis.details["anomalies"].as_array().into_iter().flatten().filter_map(|l| l.as_str()) {
+                *counts.anomalies.entry(label.to_string()).or_default() += 1;
+            }
+
+            let Some(alert) = self.generate_alert(&analysis) else {
+                continue;
+            };
+            counts.alerting_readings += 1;
+            let at = reading.reading_timestamp;
+            if last_pushed.get(&alert.level).is_none_or(|pushed| at - *pushed >= cooldown) {
+                last_pushed.insert(alert.level, at);
+                *counts.alerts.entry(alert.level).or_default() += 1;
+                counts.total_alerts += 1;
+            }
         }
+        counts
     }
 
     /// Assess signal quality based on reading values
-    fn assess_signal_quality(&self, hr: i32, spo2: i32, temp: f32) -> f32 {
+    pub fn assess_signal_quality(&self, hr: i32, spo2: i32, temp: f32) -> f32 {
         let mut quality: f32 = 1.0;
 
         // Penalize if values are zero (no signal)
@@ -127,7 +866,7 @@ impl MlService {
         }
 
         // Penalize unrealistic values
-        if hr > 250 || spo2 > 100 || temp > 43.0 || temp < 30.0 {
+        if hr > 250 || spo2 > 100 || !(30.0..=43.0).contains(&temp) {
             quality -= 0.3;
         }
 
@@ -152,43 +891,136 @@ impl MlService {
             return None;
         }
 
-        let message = match analysis.alert_level.as_str() {
-            "critical" => "Critical vital signs detected! Immediate attention required.".to_string(),
-            "high" => "Abnormal vital signs detected. Medical review recommended.".to_string(),
-            "medium" => "Unusual vital signs pattern detected.".to_string(),
-            "low" => "Minor data quality issues detected.".to_string(),
-            _ => return None,
+        let message = match analysis.alert_level {
+            AlertLevel::Critical => "Critical vital signs detected! Immediate attention required.".to_string(),
+            AlertLevel::High => "Abnormal vital signs detected. Medical review recommended.".to_string(),
+            AlertLevel::Medium => "Unusual vital signs pattern detected.".to_string(),
+            AlertLevel::Low => "Minor data quality issues detected.".to_string(),
+            AlertLevel::None => return None,
         };
 
         Some(MlAlert {
-            level: analysis.alert_level.clone(),
+            level: analysis.alert_level,
             message,
             details: analysis.details.clone(),
         })
     }
 
-    /// Advanced: Time-series anomaly detection (placeholder for future implementation)
-    pub fn detect_temporal_anomalies(&self, _readings: &[SensorReading]) -> Vec<String> {
-        // TODO: Implement sliding window analysis, trend detection, etc.
-        // This could use LSTM, isolation forest, or other ML models
-        vec![]
+    /// Combine recent vitals anomalies with the latest self-report into a patient risk score
+    pub fn assess_risk(&self, inputs: &RiskInputs) -> RiskAssessment {
+        let mut score = 0.0_f32;
+        let mut factors = Vec::new();
+
+        // Vitals: worst anomaly in the window plus repeated critical alerts
+        if let Some(max_anomaly) = inputs.max_anomaly_score {
+            if max_anomaly >= self.config.anomaly_threshold {
+                factors.push(format!("Anomalous vitals (score {:.2})", max_anomaly));
+            }
+            score += 0.5 * max_anomaly;
+        }
+        if inputs.critical_alerts > 0 {
+            factors.push(format!("{} critical alert(s) in 24h", inputs.critical_alerts));
+            score += 0.2 * (inputs.critical_alerts.min(3) as f32 / 3.0);
+        }
+
+        // Symptoms: dizziness and falls matter most for walker users
+        if let Some(checkin) = &inputs.latest_checkin {
+            if checkin.fell_since_last == Some(true) {
+                factors.push("Reported a fall".to_string());
+                score += 0.3;
+            }
+            if checkin.dizziness.unwrap_or(0) >= 5 {
+                factors.push("Reported dizziness".to_string());
+                score += 0.15;
+            }
+            if checkin.shortness_of_breath == Some(true) {
+                factors.push("Reported shortness of breath".to_string());
+                score += 0.2;
+            }
+            if checkin.pain_score.unwrap_or(0) >= 7 {
+                factors.push("Severe pain".to_string());
+                score += 0.15;
+            }
+        }
+
+        let score = score.min(1.0);
+        let level = if score >= 0.6 {
+            "high"
+        } else if score >= 0.3 {
+            "moderate"
+        } else {
+            "low"
+        };
+
+        RiskAssessment {
+            score,
+            level: level.to_string(),
+            factors,
+        }
+    }
+
+    /// Rate-of-change rules that `reading` trips against earlier readings of the same device.
+    ///
+    /// Each rule compares against every earlier reading inside its window and reports the
+    /// largest change in the rule's direction; missing or zero (no signal) values are skipped.
+    pub fn detect_temporal_anomalies(
+        &self,
+        reading: &SensorReading,
+        recent: &[SensorReading],
+        rules: &[DeltaRule],
+    ) -> Vec<DeltaMatch> {
+        let mut matches = Vec::new();
+        for rule in rules {
+            let Some(current) = rule.metric.value(reading).filter(|v| *v > 0.0) else {
+                continue;
+            };
+            let window_start = reading.reading_timestamp - Duration::minutes(rule.window_minutes);
+
+            let strongest = recent
+                .iter()
+                .filter(|r| r.reading_timestamp < reading.reading_timestamp && r.reading_timestamp >= window_start)
+                .filter_map(|r| Some((current - rule.metric.value(r).filter(|v| *v > 0.0)?, r.reading_timestamp)))
+                .filter(|(observed, _)| if rule.change > 0.0 { *observed >= rule.change } else { *observed <= rule.change })
+                .max_by(|(a, _), (b, _)| a.abs().total_cmp(&b.abs()));
+
+            if let Some((observed, since)) = strongest {
+                matches.push(DeltaMatch {
+                    metric: rule.metric,
+                    observed,
+                    change: rule.change,
+                    window_minutes: rule.window_minutes,
+                    since,
+                    critical: rule.critical,
+                });
+            }
+        }
+        matches
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct MlAnalysisResult {
     pub anomaly_detected: bool,
     pub anomaly_score: f32,
-    pub classification: String,
-    pub alert_level: String,
+    pub classification: Classification,
+    pub alert_level: AlertLevel,
     pub quality_score: f32,
     pub details: serde_json::Value,
 }
 
+/// Inputs to `assess_risk`, gathered over the last 24 hours
+#[derive(Debug, Clone, Default)]
+pub struct RiskInputs {
+    pub max_anomaly_score: Option<f32>,
+    pub critical_alerts: i64,
+    pub latest_checkin: Option<Checkin>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::Utc;
+    use crate::models::CompositeRule;
+    use chrono::{Datelike, Utc};
     use uuid::Uuid;
 
     fn create_test_config() -> MlConfig {
@@ -198,6 +1030,8 @@ mod tests {
             critical_hr_low: 40,
             critical_hr_high: 180,
             critical_spo2_low: 88,
+            alert_cooldown_seconds: 300,
+            model: None,
         }
     }
 
@@ -222,7 +1056,7 @@ mod tests {
         
         let result = service.analyze_reading(&reading);
         
-        assert_eq!(result.classification, "normal");
+        assert_eq!(result.classification, Classification::Normal);
         assert!(!result.anomaly_detected);
     }
 
@@ -234,7 +1068,7 @@ mod tests {
         let result = service.analyze_reading(&reading);
         
         assert!(result.anomaly_detected);
-        assert_eq!(result.alert_level, "critical");
+        assert_eq!(result.alert_level, AlertLevel::Critical);
     }
 
     #[test]
@@ -245,7 +1079,7 @@ mod tests {
         let result = service.analyze_reading(&reading);
         
         assert!(result.anomaly_detected);
-        assert_eq!(result.alert_level, "critical");
+        assert_eq!(result.alert_level, AlertLevel::Critical);
     }
 
     #[test]
@@ -256,7 +1090,7 @@ mod tests {
         let result = service.analyze_reading(&reading);
         
         assert!(result.anomaly_detected);
-        assert_eq!(result.alert_level, "high");
+        assert_eq!(result.alert_level, AlertLevel::High);
     }
 
     #[test]
@@ -268,4 +1102,443 @@ mod tests {
         
         assert!(result.quality_score < 0.5);
     }
+
+    #[test]
+    fn test_risk_combines_vitals_and_symptoms() {
+        let service = MlService::new(create_test_config());
+
+        let calm = service.assess_risk(&RiskInputs::default());
+        assert_eq!(calm.level, "low");
+        assert!(calm.factors.is_empty());
+
+        let checkin = Checkin {
+            id: Uuid::new_v4(),
+            patient_id: Uuid::new_v4(),
+            submitted_by: None,
+            pain_score: Some(3),
+            dizziness: Some(7),
+            fatigue: None,
+            shortness_of_breath: None,
+            fell_since_last: Some(true),
+            notes: None,
+            submitted_at: Utc::now(),
+        };
+        let risky = service.assess_risk(&RiskInputs {
+            max_anomaly_score: Some(0.9),
+            critical_alerts: 1,
+            latest_checkin: Some(checkin),
+        });
+        assert_eq!(risky.level, "high");
+        assert_eq!(risky.factors.len(), 4);
+        assert!(risky.score <= 1.0);
+    }
+
+    #[test]
+    fn test_heatmap_rows_zero_fill_and_bounds() {
+        let start = NaiveDate::from_ymd_opt(2026, 10, 1).unwrap();
+        let day2 = NaiveDate::from_ymd_opt(2026, 10, 2).unwrap();
+        let outside = NaiveDate::from_ymd_opt(2026, 10, 9).unwrap();
+
+        let rows = heatmap_rows(start, 3, 6, &[(day2, 0, 2), (day2, 3, 5), (outside, 1, 9), (start, 4, 1)]);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].counts, vec![0, 0, 0, 0]);
+        assert_eq!(rows[1].counts, vec![2, 0, 0, 5]);
+        assert_eq!(rows[2].date, NaiveDate::from_ymd_opt(2026, 10, 3).unwrap());
+    }
+
+    #[test]
+    fn test_anomaly_labels_match_analysis() {
+        let service = MlService::new(create_test_config());
+        let reading = SensorReading {
+            id: 1,
+            device_id: Uuid::new_v4(),
+            heart_rate: Some(75),
+            spo2: Some(82),
+            temperature: Some(36.8),
+            reading_timestamp: Utc::now(),
+            received_at: Utc::now(),
+            quality_score: None,
+            metadata: serde_json::json!({}),
+        };
+        let result = service.analyze_reading(&reading);
+        let labels = anomaly_labels("spo2").unwrap();
+        for anomaly in result.details["anomalies"].as_array().unwrap() {
+            assert!(labels.contains(&anomaly.as_str().unwrap()));
+        }
+        assert!(anomaly_labels("steps").is_none());
+    }
+
+    #[test]
+    fn test_profile_rules_override_defaults() {
+        let service = MlService::new(create_test_config());
+        let reading = create_test_reading(125, 89, 37.9);
+        assert_eq!(service.analyze_reading(&reading).alert_level, AlertLevel::None);
+
+        let cardiac = ThresholdRules {
+            hr_low: 50,
+            hr_high: 110,
+            spo2_low: 92,
+            fever_temperature: 37.8,
+            hypothermia_temperature: 35.5,
+            delta_rules: None,
+            composite_rules: Vec::new(),
+            severity_rules: Vec::new(),
+        };
+        let result = service.analyze_reading_with(&reading, &cardiac);
+        let anomalies = result.details["anomalies"].as_array().unwrap();
+        assert!(anomalies.contains(&json!(TACHYCARDIA)));
+        assert!(anomalies.contains(&json!(HYPOXEMIA)));
+        assert!(anomalies.contains(&json!(FEVER)));
+        assert_eq!(result.alert_level, AlertLevel::Critical);
+    }
+
+    #[test]
+    fn test_delta_rules_catch_rapid_changes_within_window() {
+        let service = MlService::new(create_test_config());
+        let at = |minutes_ago: i64, hr: i32, spo2: i32| {
+            let mut reading = create_test_reading(hr, spo2, 36.8);
+            reading.reading_timestamp = Utc::now() - Duration::minutes(minutes_ago);
+            reading
+        };
+        let current = at(0, 112, 92);
+        let rules = service.default_rules();
+
+        // +37 bpm over 4 minutes and -6 SpO2 over 8 minutes; both below absolute thresholds
+        let recent = [at(4, 75, 98), at(8, 80, 98), at(30, 60, 99)];
+        let result = service.analyze_reading_in_context(&current, AnalysisContext { recent: &recent, patient: None, baseline: None }, &rules);
+        let anomalies = result.details["anomalies"].as_array().unwrap();
+        assert!(anomalies.contains(&json!(HR_RISE)));
+        assert!(anomalies.contains(&json!(SPO2_DROP)));
+        assert_eq!(result.alert_level, AlertLevel::High);
+        assert_eq!(result.details["deltas"][0]["observed"], json!(37.0));
+
+        // The 52 bpm rise happened outside the 5 minute window
+        let matches = service.detect_temporal_anomalies(&current, &[at(30, 60, 92)], rules.delta_rules.as_deref().unwrap());
+        assert!(matches.is_empty());
+
+        // Readings without history, or with rules disabled, are judged on absolute thresholds
+        assert_eq!(service.analyze_reading_with(&current, &rules).alert_level, AlertLevel::None);
+        let disabled = ThresholdRules { delta_rules: Some(vec![]), ..rules.clone() };
+        assert_eq!(service.analyze_reading_in_context(&current, AnalysisContext { recent: &recent, patient: None, baseline: None }, &disabled).alert_level, AlertLevel::None);
+
+        let critical = ThresholdRules {
+            delta_rules: Some(vec![DeltaRule { metric: VitalMetric::Spo2, change: -5.0, window_minutes: 10, critical: true }]),
+            ..rules
+        };
+        assert_eq!(service.analyze_reading_in_context(&current, AnalysisContext { recent: &recent, patient: None, baseline: None }, &critical).alert_level, AlertLevel::Critical);
+    }
+
+    #[test]
+    fn test_composite_rules_record_the_matched_condition() {
+        let service = MlService::new(create_test_config());
+        let rules = ThresholdRules {
+            composite_rules: vec![CompositeRule {
+                name: "Possible sepsis".into(),
+                condition: "HR > 120 AND SpO2 < 92 AND temp > 38".into(),
+                critical: true,
+            }],
+            ..service.default_rules()
+        };
+
+        // Each value alone stays inside the absolute thresholds
+        let result = service.analyze_reading_with(&create_test_reading(125, 90, 37.9), &rules);
+        assert!(result.details.get("composites").is_none());
+
+        let result = service.analyze_reading_with(&create_test_reading(125, 90, 38.2), &rules);
+        assert_eq!(result.alert_level, AlertLevel::Critical);
+        assert!(result.details["anomalies"].as_array().unwrap().contains(&json!(COMPOSITE)));
+        let matched = &result.details["composites"][0];
+        assert_eq!(matched["name"], "Possible sepsis");
+        assert_eq!(matched["condition"], "heart_rate > 120 AND spo2 < 92 AND temperature > 38");
+        assert_eq!(matched["values"]["heart_rate"], json!(125.0));
+    }
+
+    #[test]
+    fn test_custom_rules_hold_for_their_duration() {
+        let service = MlService::new(create_test_config());
+        let rule = |duration_seconds, enabled| MlRule {
+            id: Uuid::new_v4(),
+            name: "Sustained high heart rate".into(),
+            metric: VitalMetric::HeartRate,
+            comparator: ">".into(),
+            value: 100.0,
+            duration_seconds,
+            alert_level: AlertLevel::Medium,
+            enabled,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        service.set_custom_rules(vec![rule(120, true), rule(0, false)]);
+        assert_eq!(service.custom_rule_lookback(), Duration::seconds(120));
+
+        let reading = create_test_reading(110, 98, 36.8);
+        let earlier = |seconds_ago, hr| SensorReading {
+            heart_rate: Some(hr),
+            reading_timestamp: reading.reading_timestamp - Duration::seconds(seconds_ago),
+            ..reading.clone()
+        };
+        let analyze = |recent: &[SensorReading]| {
+            let context = AnalysisContext { recent, patient: None, baseline: None };
+            service.analyze_reading_in_context(&reading, context, &service.default_rules())
+        };
+
+        // Above 100 for only a minute, then for two minutes straight
+        let result = analyze(&[earlier(60, 105), earlier(90, 95), earlier(150, 120)]);
+        assert!(result.details.get("custom_rules").is_none());
+        assert_eq!(result.alert_level, AlertLevel::None);
+
+        let result = analyze(&[earlier(60, 105), earlier(120, 104), earlier(180, 95)]);
+        assert_eq!(result.alert_level, AlertLevel::Medium);
+        assert!(result.details["anomalies"].as_array().unwrap().contains(&json!(CUSTOM_RULE)));
+        let matched = &result.details["custom_rules"][0];
+        assert_eq!(matched["condition"], "heart_rate > 100");
+        assert_eq!(matched["observed"], json!(110.0));
+
+        // A sustained rule may match on the next identical reading, so it is never reused
+        service.remember_analysis(&reading, &service.default_rules(), AnalysisContext::default(), &result);
+        assert!(service.reuse_analysis(&reading, &service.default_rules(), AnalysisContext::default()).is_none());
+    }
+
+    #[test]
+    fn test_severity_rules_recalibrate_by_age_and_diagnosis() {
+        let service = MlService::new(create_test_config());
+        let rules = ThresholdRules {
+            severity_rules: vec![
+                SeverityRule { min_age: Some(80), max_age: None, diagnoses: vec![], metric: Some(VitalMetric::HeartRate), shift: 1 },
+                SeverityRule { min_age: None, max_age: Some(40), diagnoses: vec![], metric: None, shift: -1 },
+                SeverityRule { min_age: None, max_age: None, diagnoses: vec!["COPD".into()], metric: Some(VitalMetric::Spo2), shift: -2 },
+            ],
+            ..service.default_rules()
+        };
+        let born = |years_ago: i32| PatientAttributes {
+            date_of_birth: NaiveDate::from_ymd_opt(Utc::now().date_naive().year() - years_ago, 1, 1),
+            diagnoses: vec![],
+        };
+        let level = |reading: &SensorReading, patient: &PatientAttributes| {
+            let context = AnalysisContext { recent: &[], patient: Some(patient), baseline: None };
+            service.analyze_reading_in_context(reading, context, &rules).alert_level
+        };
+
+        // A fever is "high" by default; the same reading lands differently by age
+        let febrile = create_test_reading(75, 97, 38.5);
+        assert_eq!(level(&febrile, &born(55)), AlertLevel::High);
+        assert_eq!(level(&febrile, &born(30)), AlertLevel::Medium);
+        assert_eq!(level(&febrile, &born(85)), AlertLevel::High);
+
+        // A heart rate rise ("high") escalates for the elderly only
+        let mut earlier = create_test_reading(75, 97, 36.8);
+        earlier.reading_timestamp = Utc::now() - Duration::minutes(3);
+        let rising = create_test_reading(110, 97, 36.8);
+        let rise_level = |patient: &PatientAttributes| {
+            let context = AnalysisContext { recent: std::slice::from_ref(&earlier), patient: Some(patient), baseline: None };
+            service.analyze_reading_in_context(&rising, context, &rules).alert_level
+        };
+        assert_eq!(rise_level(&born(55)), AlertLevel::High);
+        assert_eq!(rise_level(&born(85)), AlertLevel::Critical);
+
+        // Low SpO2 is expected with COPD; unknown ages never match an age band
+        let copd = PatientAttributes { date_of_birth: None, diagnoses: vec!["copd".into()] };
+        let hypoxemic = create_test_reading(75, 86, 36.8);
+        let context = AnalysisContext { recent: &[], patient: Some(&copd), baseline: None };
+        let result = service.analyze_reading_in_context(&hypoxemic, context, &rules);
+        assert_eq!(result.alert_level, AlertLevel::Medium);
+        assert_eq!(result.details["severity_adjustments"][0]["from"], "critical");
+
+        // Without a patient record the defaults apply
+        assert_eq!(service.analyze_reading_with(&hypoxemic, &rules).alert_level, AlertLevel::Critical);
+    }
+
+    #[test]
+    fn test_identical_reading_reuses_previous_analysis() {
+        let service = MlService::new(create_test_config());
+        let rules = service.default_rules();
+        let first = create_test_reading(195, 98, 36.8);
+        let result = service.analyze_reading_with(&first, &rules);
+        service.remember_analysis(&first, &rules, AnalysisContext::default(), &result);
+
+        let mut next = first.clone();
+        next.reading_timestamp += Duration::seconds(5);
+        let reused = service.reuse_analysis(&next, &rules, AnalysisContext::default()).unwrap();
+        assert_eq!(reused.alert_level, result.alert_level);
+        assert_eq!(reused.details, result.details);
+
+        // Any change to what the result depends on means a fresh analysis
+        let mut warmer = next.clone();
+        warmer.temperature = Some(36.81);
+        assert!(service.reuse_analysis(&warmer, &rules, AnalysisContext::default()).is_none());
+        let stricter = ThresholdRules { hr_high: 200, ..rules.clone() };
+        assert!(service.reuse_analysis(&next, &stricter, AnalysisContext::default()).is_none());
+        let patient = PatientAttributes { date_of_birth: None, diagnoses: vec!["copd".into()] };
+        assert!(service.reuse_analysis(&next, &rules, AnalysisContext { patient: Some(&patient), ..Default::default() }).is_none());
+        let baseline = test_baseline(50.0, 4.0, 500);
+        assert!(service.reuse_analysis(&next, &rules, AnalysisContext { baseline: Some(&baseline), ..Default::default() }).is_none());
+        let mut other_device = next.clone();
+        other_device.device_id = Uuid::new_v4();
+        assert!(service.reuse_analysis(&other_device, &rules, AnalysisContext::default()).is_none());
+        let mut late = next.clone();
+        late.reading_timestamp += Duration::seconds(REUSE_WINDOW_SECONDS);
+        assert!(service.reuse_analysis(&late, &rules, AnalysisContext::default()).is_none());
+        let mut out_of_order = next.clone();
+        out_of_order.reading_timestamp -= Duration::seconds(10);
+        assert!(service.reuse_analysis(&out_of_order, &rules, AnalysisContext::default()).is_none());
+
+        // A rate-of-change finding can lapse as the window moves, so it is never reused
+        let mut earlier = create_test_reading(75, 97, 36.8);
+        earlier.device_id = first.device_id;
+        earlier.reading_timestamp = first.reading_timestamp - Duration::minutes(3);
+        let context = AnalysisContext { recent: std::slice::from_ref(&earlier), patient: None, baseline: None };
+        let result = service.analyze_reading_in_context(&first, context, &rules);
+        assert!(result.details.get("deltas").is_some());
+        service.remember_analysis(&first, &rules, AnalysisContext::default(), &result);
+        assert!(service.reuse_analysis(&next, &rules, AnalysisContext::default()).is_none());
+    }
+
+    fn test_baseline(hr_mean: f32, hr_stddev: f32, samples: i32) -> Baseline {
+        Baseline {
+            device_id: Uuid::new_v4(),
+            patient_id: None,
+            hr_mean: Some(hr_mean),
+            hr_stddev: Some(hr_stddev),
+            hr_samples: samples,
+            spo2_mean: None,
+            spo2_stddev: None,
+            spo2_samples: 0,
+            window_start: Utc::now() - Duration::days(7),
+            computed_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_zscores_use_the_patients_baseline() {
+        let service = MlService::new(create_test_config());
+        let rules = service.default_rules();
+        let score = |hr: i32, baseline: Option<&Baseline>| {
+            let context = AnalysisContext { baseline, ..Default::default() };
+            service.analyze_reading_in_context(&create_test_reading(hr, 97, 36.8), context, &rules)
+        };
+        let statistical = |result: &MlAnalysisResult| result.details["anomalies"].as_array().unwrap().contains(&json!(STATISTICAL_HR));
+
+        // Habitually fast at rest: 112 is ordinary for them, unusual for the population
+        let fast = test_baseline(100.0, 8.0, 500);
+        assert!(statistical(&score(112, None)));
+        let result = score(112, Some(&fast));
+        assert!(!statistical(&result));
+        assert_eq!(result.details["baseline"]["hr_mean"], 100.0);
+        assert_eq!(result.details["baseline"]["patient_spo2"], false);
+
+        // Spread is floored, so a very steady week does not make every beat an anomaly
+        let steady = test_baseline(50.0, 0.5, 500);
+        assert!(!statistical(&score(60, Some(&steady))));
+        assert!(statistical(&score(70, Some(&steady))));
+
+        // Too few readings for a baseline: population norms, and nothing recorded
+        let result = score(112, Some(&test_baseline(100.0, 8.0, 20)));
+        assert!(statistical(&result));
+        assert!(result.details.get("baseline").is_none());
+    }
+
+    #[test]
+    fn test_simulation_counts_alerts_after_the_cooldown() {
+        let service = MlService::new(create_test_config());
+        let from = Utc::now() - Duration::hours(1);
+        // A reading a minute for 12 minutes, the first two before the range
+        let history: Vec<SensorReading> = (-2..10)
+            .map(|minute| {
+                let mut reading = create_test_reading(195, 85, 36.8);
+                reading.reading_timestamp = from + Duration::minutes(minute);
+                reading
+            })
+            .collect();
+
+        let counts = service.simulate(&history, from, None, None, &service.default_rules());
+        assert_eq!(counts.alerting_readings, 10);
+        assert_eq!(counts.alerts[&AlertLevel::Critical], 2);
+        assert_eq!(counts.alerts[&AlertLevel::Low], 0);
+        assert_eq!(counts.total_alerts, 2);
+        assert_eq!(counts.anomalies[TACHYCARDIA], 10);
+
+        let relaxed = ThresholdRules { hr_high: 200, spo2_low: 80, ..service.default_rules() };
+        let counts = service.simulate(&history, from, None, None, &relaxed);
+        assert_eq!(counts.total_alerts, 0);
+        assert!(!counts.anomalies.contains_key(TACHYCARDIA));
+
+        let mut total = SimulationCounts::default();
+        total.add(&service.simulate(&history, from, None, None, &service.default_rules()));
+        total.add(&counts);
+        assert_eq!(total.alerts[&AlertLevel::Critical], 2);
+    }
+
+    #[test]
+    fn test_explanations_describe_each_check() {
+        let service = MlService::new(create_test_config());
+        let rules = service.default_rules();
+        let result = service.analyze_reading_in_context(
+            &create_test_reading(195, 97, 36.8),
+            AnalysisContext { baseline: Some(&test_baseline(100.0, 8.0, 500)), ..Default::default() },
+            &rules,
+        );
+
+        let explanations = result.details["explanations"].as_array().unwrap();
+        assert_eq!(explanations.len(), 2);
+        assert_eq!(explanations[0]["anomaly"], TACHYCARDIA);
+        assert_eq!(explanations[0]["check"], "threshold");
+        assert_eq!(explanations[0]["metric"], "heart_rate");
+        assert_eq!(explanations[0]["comparator"], ">");
+        assert_eq!(explanations[0]["threshold"], 180.0);
+        assert_eq!(explanations[0]["values"], json!({"heart_rate": 195.0}));
+        assert_eq!(explanations[0]["level"], "critical");
+        assert_eq!(explanations[0]["summary"], "Heart rate 195 bpm above 180 bpm");
+
+        assert_eq!(explanations[1]["anomaly"], STATISTICAL_HR);
+        assert_eq!(explanations[1]["baseline"]["mean"], 100.0);
+        assert_eq!(explanations[1]["baseline"]["deviation"], 95.0);
+        assert_eq!(explanations[1]["baseline"]["personal"], true);
+        assert!(explanations[1]["observed"].as_f64().unwrap() > 3.0);
+
+        // Every anomaly is explained, in the same order
+        let labels: Vec<_> = explanations.iter().map(|e| e["anomaly"].clone()).collect();
+        assert_eq!(json!(labels), result.details["anomalies"]);
+        assert_eq!(service.analyze_reading(&create_test_reading(72, 98, 36.8)).details["explanations"], json!([]));
+    }
+
+    #[test]
+    fn test_model_score_is_merged_with_the_rules() {
+        use crate::config::MlModelConfig;
+        use crate::ml_model::tests::{logistic_model, reading};
+
+        // Flags heart rate climbing since the previous reading
+        let mut weights = [0.0; ml_model::FEATURES.len()];
+        weights[3] = 0.25;
+        let config = MlConfig { model: Some(MlModelConfig { path: "unused.onnx".to_string(), weight: 0.5 }), ..create_test_config() };
+        let service = MlService::with_model(config, Some(logistic_model(weights, -3.0)));
+        let rules = service.default_rules();
+        let analyze = |hr: i32, previous_hr: i32| {
+            let recent = [reading(previous_hr, 60)];
+            service.analyze_reading_in_context(&reading(hr, 0), AnalysisContext { recent: &recent, ..Default::default() }, &rules)
+        };
+
+        // A rise too small for any rule
+        assert!(!MlService::new(create_test_config())
+            .analyze_reading_in_context(&reading(105, 0), AnalysisContext { recent: &[reading(80, 60)], ..Default::default() }, &rules)
+            .anomaly_detected);
+        let result = analyze(105, 80);
+        assert_eq!(result.details["anomalies"], json!([MODEL_ANOMALY]));
+        assert_eq!(result.alert_level, AlertLevel::Medium);
+        let model_score = result.details["model"]["score"].as_f64().unwrap() as f32;
+        assert!(model_score > 0.95);
+        assert!((result.anomaly_score - (0.5 * 0.25 + 0.5 * model_score)).abs() < 1e-6);
+
+        // A low model score does not water down a critical reading
+        let result = analyze(190, 190);
+        assert_eq!(result.alert_level, AlertLevel::Critical);
+        assert!(result.details["model"]["score"].as_f64().unwrap() < 0.1);
+        assert_eq!(result.anomaly_score, result.details["model"]["rule_score"].as_f64().unwrap() as f32);
+
+        assert!(!analyze(80, 80).anomaly_detected);
+
+        // A repeated reading is scored afresh, as its history has moved on
+        let result = analyze(105, 80);
+        service.remember_analysis(&reading(105, 0), &rules, AnalysisContext::default(), &result);
+        assert!(service.reuse_analysis(&reading(105, 0), &rules, AnalysisContext::default()).is_none());
+    }
 }
@@ -1,6 +1,10 @@
-use chrono::{DateTime, Utc};
+use crate::dead_letter::{FailureCategory, IngestChannel};
+use crate::rbac::Role;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use std::collections::BTreeMap;
 use uuid::Uuid;
 use validator::Validate;
 
@@ -12,16 +16,17 @@ pub struct User {
     pub email: String,
     #[serde(skip_serializing)]
     pub password_hash: String,
-    pub role: String,
+    pub role: Role,
     pub is_active: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub last_login_at: Option<DateTime<Utc>>,
     pub failed_login_attempts: i32,
     pub locked_until: Option<DateTime<Utc>>,
+    pub organization_id: Option<Uuid>,
 }
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
 pub struct SignupRequest {
     #[validate(email)]
     pub email: String,
@@ -29,25 +34,43 @@ pub struct SignupRequest {
     pub password: String,
 }
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
 pub struct LoginRequest {
     #[validate(email)]
     pub email: String,
     pub password: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct AuthResponse {
     pub token: String,
     pub refresh_token: String,
     pub user: UserResponse,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct UserResponse {
     pub id: Uuid,
     pub email: String,
-    pub role: String,
+    pub role: Role,
+}
+
+/// A user's stored display preferences and organization (see [`crate::request_context`])
+#[derive(Debug, Clone, FromRow, Serialize, JsonSchema)]
+pub struct UserPreferences {
+    pub organization_id: Option<Uuid>,
+    pub locale: String,
+    pub timezone: String,
+    pub units: String,
+}
+
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct UpdatePreferencesRequest {
+    #[validate(length(min = 2, max = 35))]
+    pub locale: Option<String>,
+    #[validate(length(min = 1, max = 64))]
+    pub timezone: Option<String>,
+    pub units: Option<String>,
 }
 
 // ============ Device Models ============
@@ -58,10 +81,202 @@ pub struct Device {
     pub device_id: String,
     pub device_name: String,
     pub secret_hash: String,
+    /// The walker's own HMAC secret, sealed by `crate::device_secrets`
+    pub secret_ciphertext: Option<String>,
+    pub secret_issued_at: Option<DateTime<Utc>>,
     pub is_active: bool,
     pub created_at: DateTime<Utc>,
     pub last_seen_at: Option<DateTime<Utc>>,
     pub metadata: serde_json::Value,
+    pub patient_id: Option<Uuid>,
+    pub claimed_by: Option<Uuid>,
+    pub claimed_at: Option<DateTime<Utc>>,
+    pub units: sqlx::types::Json<DeviceUnits>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TemperatureUnit {
+    #[default]
+    Celsius,
+    Fahrenheit,
+}
+
+/// How a walker's firmware reports its readings, which [`crate::normalization`] converts
+/// to the stored units on ingestion; `{}` is the stored units
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeviceUnits {
+    /// Unit of `temperature` and `ambientTemperature`
+    pub temperature: TemperatureUnit,
+}
+
+/// Claim a device with the pairing code shown on the walker.
+///
+/// Links to an existing patient the caller cares for, or creates one from `patient_name`.
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct DeviceClaimRequest {
+    #[validate(length(min = 6, max = 16))]
+    pub pairing_code: String,
+    pub patient_id: Option<Uuid>,
+    #[validate(length(min = 1, max = 200))]
+    pub patient_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DeviceClaimResponse {
+    pub device_id: String,
+    pub device_name: String,
+    pub patient_id: Uuid,
+    pub claimed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PairingCodeResponse {
+    pub device_id: String,
+    pub pairing_code: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A walker as listed for administrators; its secret is never included
+#[derive(Debug, Clone, FromRow, Serialize, JsonSchema)]
+pub struct DeviceRecord {
+    pub id: Uuid,
+    pub device_id: String,
+    pub device_name: String,
+    pub is_active: bool,
+    pub patient_id: Option<Uuid>,
+    pub organization_id: Option<Uuid>,
+    pub metadata: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: Option<DateTime<Utc>>,
+    pub secret_issued_at: Option<DateTime<Utc>>,
+    #[schemars(with = "DeviceUnits")]
+    pub units: sqlx::types::Json<DeviceUnits>,
+}
+
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct DeviceRegistration {
+    /// Serial the walker sends as `X-Device-Id`
+    #[validate(length(min = 1, max = 100), custom(function = "validate_device_serial"))]
+    pub device_id: String,
+    #[validate(length(min = 1, max = 200))]
+    pub device_name: String,
+    #[serde(default)]
+    pub metadata: Option<serde_json::Value>,
+    /// How the walker's firmware reports; the stored units when absent
+    #[serde(default)]
+    pub units: Option<DeviceUnits>,
+}
+
+/// Fields to change; absent ones are left as they are
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct DeviceUpdate {
+    #[validate(length(min = 1, max = 200))]
+    pub device_name: Option<String>,
+    pub metadata: Option<serde_json::Value>,
+    pub is_active: Option<bool>,
+    /// How the walker's firmware reports, e.g. after a firmware update
+    pub units: Option<DeviceUnits>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeviceListQuery {
+    /// Only active (`true`) or deactivated (`false`) walkers
+    pub active: Option<bool>,
+}
+
+/// A reading in the dead-letter queue (see [`crate::dead_letter`])
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct FailedIngestion {
+    pub id: i64,
+    /// The walker's serial
+    pub device_id: String,
+    pub channel: IngestChannel,
+    pub relayed_by: Option<Uuid>,
+    /// The reading as sent
+    pub payload: serde_json::Value,
+    pub category: FailureCategory,
+    /// Why it was last refused
+    pub error: String,
+    pub attempts: i32,
+    pub received_at: DateTime<Utc>,
+    pub last_attempt_at: DateTime<Utc>,
+    pub reprocessed_at: Option<DateTime<Utc>>,
+    /// The reading stored when it was reprocessed
+    pub sensor_reading_id: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FailedIngestionQuery {
+    /// Entries already reprocessed (`true`) or still pending (`false`, the default)
+    #[serde(default)]
+    pub reprocessed: bool,
+    pub category: Option<FailureCategory>,
+    /// A walker's serial
+    pub device_id: Option<String>,
+    /// At most 500; 100 by default
+    pub limit: Option<i64>,
+}
+
+/// A newly registered walker with its secret, shown this once
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ProvisionedDevice {
+    #[serde(flatten)]
+    pub device: DeviceRecord,
+    pub secret: String,
+}
+
+/// The patient whose readings a walker records from now on
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct DeviceAssignment {
+    pub patient_id: Uuid,
+}
+
+/// Serials travel in a header: letters, digits and `-_.:` only
+fn validate_device_serial(serial: &str) -> Result<(), validator::ValidationError> {
+    if !serial.chars().all(|c| c.is_ascii_alphanumeric() || "-_.:".contains(c)) {
+        return Err(validator::ValidationError::new("invalid_serial"));
+    }
+    Ok(())
+}
+
+/// A walker's own HMAC secret, shown once when issued
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DeviceSecretResponse {
+    pub device_id: String,
+    pub secret: String,
+    pub issued_at: DateTime<Utc>,
+}
+
+/// A phone paired to relay a walker's readings under its user's JWT
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct DeviceGateway {
+    pub id: Uuid,
+    pub device_id: String,
+    pub user_id: Uuid,
+    pub user_email: String,
+    /// `pending`, `approved`, `rejected` or `revoked`
+    pub status: String,
+    pub requested_at: DateTime<Utc>,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub decided_by: Option<Uuid>,
+    pub last_relay_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GatewayDecisionRequest {
+    /// `approved`, `rejected` or `revoked`
+    pub status: String,
+}
+
+/// A reading relayed by a paired phone; same wire format as the walker's own upload
+#[derive(Debug, Serialize, Deserialize, Validate, JsonSchema)]
+pub struct GatewayVitalsIngest {
+    #[validate(length(min = 1, max = 100))]
+    pub device_id: String,
+    #[serde(flatten)]
+    pub reading: RawVitals,
 }
 
 // ============ Sensor Reading Models ============
@@ -79,50 +294,700 @@ pub struct SensorReading {
     pub metadata: serde_json::Value,
 }
 
-#[derive(Debug, Serialize, Deserialize, Validate)]
+impl SensorReading {
+    /// The walker-reported metadata named in [`SURFACED_READING_METADATA`]
+    pub fn surfaced_device_metadata(&self) -> impl Iterator<Item = (&'static str, &serde_json::Value)> {
+        SURFACED_READING_METADATA
+            .iter()
+            .filter_map(|key| Some((*key, self.metadata.get("device")?.get(key)?)))
+    }
+
+    /// Drop walker-reported metadata that isn't surfaced, before the reading is served
+    pub fn without_unsurfaced_metadata(mut self) -> Self {
+        if let Some(device) = self.metadata.get_mut("device").and_then(|d| d.as_object_mut()) {
+            device.retain(|key, _| SURFACED_READING_METADATA.contains(&key.as_str()));
+        }
+        self
+    }
+}
+
+/// Fields named in API version 1's camelCase are accepted in either casing until it is
+/// retired (see [`crate::api_version`]); signatures cover the body as sent
+#[derive(Debug, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(rename_all = "snake_case")]
 pub struct DeviceVitalsIngest {
+    #[serde(rename = "heartRate", alias = "heart_rate")]
     #[validate(range(min = 0, max = 300))]
-    pub heartRate: i32,
+    pub heart_rate: i32,
     #[validate(range(min = 0, max = 100))]
     pub spo2: i32,
     #[validate(range(min = 25.0, max = 45.0))]
     pub temperature: f32,
     pub timestamp: i64,
+    /// Steps counted since the previous reading (walkers with a gait sensor)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 0, max = 10000))]
+    pub steps: Option<i32>,
+    /// Mean acceleration magnitude above gravity since the previous reading, in g
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 0.0, max = 16.0))]
+    pub motion: Option<f32>,
+    /// Barometric height change since the previous reading, in metres (positive is up)
+    #[serde(default, rename = "elevationChange", alias = "elevation_change", skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = -50.0, max = 50.0))]
+    pub elevation_change: Option<f32>,
+    /// Air temperature around the walker, °C (stored apart from the patient's vitals)
+    #[serde(default, rename = "ambientTemperature", alias = "ambient_temperature", skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = -40.0, max = 60.0))]
+    pub ambient_temperature: Option<f32>,
+    /// Relative humidity, %
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 0.0, max = 100.0))]
+    pub humidity: Option<f32>,
+    /// What the walker reports about itself (firmware version, sensor mode, signal strength),
+    /// kept with the reading under `metadata.device`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(custom(function = "validate_reading_metadata"))]
+    pub metadata: Option<BTreeMap<String, serde_json::Value>>,
+}
+
+/// A reading as the walker sent it, before [`crate::normalization`] converts it with the
+/// device's units; documented, and once converted validated, as [`DeviceVitalsIngest`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RawVitals(pub serde_json::Map<String, serde_json::Value>);
+
+impl JsonSchema for RawVitals {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        DeviceVitalsIngest::schema_name()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        DeviceVitalsIngest::json_schema(gen)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeviceIngestQuery {
+    /// Authenticate, validate and analyse the reading without storing it
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+pub const MAX_READING_METADATA_KEYS: usize = 16;
+pub const MAX_READING_METADATA_BYTES: usize = 1024;
+const MAX_READING_METADATA_KEY_LEN: usize = 32;
+const MAX_READING_METADATA_STRING_LEN: usize = 64;
+
+/// Device metadata keys surfaced in history responses and as FHIR Observation extensions;
+/// the rest are stored but not passed on
+pub const SURFACED_READING_METADATA: &[&str] = &["firmware_version", "sensor_mode", "signal_strength"];
+
+/// A few flat, snake_case keys with short scalar values
+fn validate_reading_metadata(metadata: &BTreeMap<String, serde_json::Value>) -> Result<(), validator::ValidationError> {
+    if metadata.len() > MAX_READING_METADATA_KEYS {
+        return Err(validator::ValidationError::new("too_many_metadata_keys"));
+    }
+    let key_ok = |key: &str| {
+        key.len() <= MAX_READING_METADATA_KEY_LEN
+            && key.starts_with(|c: char| c.is_ascii_lowercase())
+            && key.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    };
+    if !metadata.keys().all(|key| key_ok(key)) {
+        return Err(validator::ValidationError::new("invalid_metadata_key"));
+    }
+    let value_ok = |value: &serde_json::Value| match value {
+        serde_json::Value::String(s) => s.chars().count() <= MAX_READING_METADATA_STRING_LEN,
+        serde_json::Value::Number(_) | serde_json::Value::Bool(_) => true,
+        _ => false,
+    };
+    if !metadata.values().all(value_ok) {
+        return Err(validator::ValidationError::new("invalid_metadata_value"));
+    }
+    if serde_json::to_vec(metadata).map_or(true, |bytes| bytes.len() > MAX_READING_METADATA_BYTES) {
+        return Err(validator::ValidationError::new("metadata_too_large"));
+    }
+    Ok(())
+}
+
+/// A reading published over MQTT. Without headers, the signature and its timestamp travel
+/// next to the reading, signed as `"{timestamp}.{vitals}"` like the HTTP body.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct MqttVitalsMessage {
+    pub timestamp: i64,
+    pub signature: String,
+    pub vitals: RawVitals,
+}
+
+/// Longest `GET /api/vitals/latest` waits for fresher vitals
+pub const MAX_LATEST_WAIT_SECONDS: u64 = 30;
+pub const DEFAULT_LATEST_WAIT_SECONDS: u64 = 10;
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct LatestVitalsQuery {
+    /// Wait for vitals at least this fresh (Unix seconds, as in `timestamp`)
+    pub min_timestamp: Option<i64>,
+    #[validate(range(max = MAX_LATEST_WAIT_SECONDS))]
+    pub wait_seconds: Option<u64>,
+}
+
+/// Longest `GET /api/vitals/poll` holds a request open; the default stays under the
+/// 30-second idle timeout common to proxies
+pub const MAX_POLL_WAIT_SECONDS: u64 = 60;
+pub const DEFAULT_POLL_WAIT_SECONDS: u64 = 25;
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct VitalsPollQuery {
+    /// `cursor` from the previous poll
+    pub since: Option<Uuid>,
+    #[validate(range(max = MAX_POLL_WAIT_SECONDS))]
+    pub wait_seconds: Option<u64>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Serialized in API version 1's casing; version 2 responses are renamed by
+/// [`crate::api_version`]. Event payloads keep this casing until the next event schema version.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(rename_all = "snake_case")]
 pub struct LatestVitals {
-    pub heartRate: i32,
+    #[serde(rename = "heartRate", alias = "heart_rate")]
+    pub heart_rate: i32,
     pub spo2: i32,
     pub temperature: f32,
     pub timestamp: i64,
     pub quality_score: Option<f32>,
-    pub ml_alert: Option<String>,
+    pub ml_alert: Option<AlertLevel>,
+}
+
+/// Vitals statistics for one bucket (minute to day); percentiles are interpolated
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct VitalsBucket {
+    pub bucket_start: DateTime<Utc>,
+    pub readings: i64,
+    pub heart_rate_avg: Option<f64>,
+    pub heart_rate_min: Option<i32>,
+    pub heart_rate_max: Option<i32>,
+    pub heart_rate_p05: Option<f64>,
+    pub heart_rate_p50: Option<f64>,
+    pub heart_rate_p95: Option<f64>,
+    pub spo2_avg: Option<f64>,
+    pub spo2_min: Option<i32>,
+    pub spo2_max: Option<i32>,
+    pub spo2_p05: Option<f64>,
+    pub spo2_p50: Option<f64>,
+    pub spo2_p95: Option<f64>,
+    pub temperature_avg: Option<f64>,
+    pub temperature_min: Option<f32>,
+    pub temperature_max: Option<f32>,
+    pub temperature_p05: Option<f64>,
+    pub temperature_p50: Option<f64>,
+    pub temperature_p95: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VitalsAggregateQuery {
+    /// `1m`, `5m`, `1h` (default) or `1d`; `minute`, `five_minutes`, `hour` and `day` also work
+    pub bucket: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// `GET /api/vitals/aggregate`: [`VitalsAggregateQuery`] with the patient as a parameter
+#[derive(Debug, Deserialize)]
+pub struct VitalsIntervalQuery {
+    pub patient_id: Uuid,
+    /// Bucket width, as [`VitalsAggregateQuery::bucket`]
+    pub interval: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VitalsAggregate {
+    pub patient_id: Uuid,
+    pub bucket: String,
+    /// The requested range widened to whole buckets
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub cached: bool,
+    pub buckets: Vec<VitalsBucket>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VitalsHistoryQuery {
+    pub patient_id: Uuid,
+    /// Defaults to 24 hours before `to`
+    pub from: Option<DateTime<Utc>>,
+    /// Defaults to now
+    pub to: Option<DateTime<Utc>>,
+    /// Row cap for JSON responses; NDJSON streams the whole range
+    pub limit: Option<i64>,
 }
 
 // ============ ML Analysis Models ============
 
+/// Severity of an analysis or alert, stored as the Postgres enum `alert_level`. Levels are
+/// ordered, so the more severe of two is their `max`. `None` only describes analyses;
+/// alerts are raised at [`AlertLevel::RAISED`] levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "alert_level", rename_all = "lowercase")]
+pub enum AlertLevel {
+    None,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl AlertLevel {
+    /// Levels an alert can be raised at, least severe first
+    pub const RAISED: [AlertLevel; 4] = [AlertLevel::Low, AlertLevel::Medium, AlertLevel::High, AlertLevel::Critical];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        [AlertLevel::None].into_iter().chain(Self::RAISED).find(|level| level.as_str() == name)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertLevel::None => "none",
+            AlertLevel::Low => "low",
+            AlertLevel::Medium => "medium",
+            AlertLevel::High => "high",
+            AlertLevel::Critical => "critical",
+        }
+    }
+
+    /// This level moved `steps` along [`AlertLevel::RAISED`] (negative lowers), staying
+    /// within it; `None` stays `None`
+    pub fn shift(self, steps: i32) -> Self {
+        match Self::RAISED.iter().position(|level| *level == self) {
+            Some(index) => Self::RAISED[(index as i32 + steps).clamp(0, Self::RAISED.len() as i32 - 1) as usize],
+            None => self,
+        }
+    }
+}
+
+impl std::fmt::Display for AlertLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What the analysis made of a reading, stored as the Postgres enum `reading_classification`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "reading_classification", rename_all = "lowercase")]
+pub enum Classification {
+    Normal,
+    Warning,
+    Critical,
+    /// Readings that can't be trusted, e.g. from a sensor fault
+    Artifact,
+}
+
+impl Classification {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Classification::Normal => "normal",
+            Classification::Warning => "warning",
+            Classification::Critical => "critical",
+            Classification::Artifact => "artifact",
+        }
+    }
+}
+
+impl std::fmt::Display for Classification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[derive(Debug, Clone, FromRow, Serialize)]
 pub struct MlAnalysis {
     pub id: i64,
     pub sensor_reading_id: i64,
     pub anomaly_detected: bool,
     pub anomaly_score: Option<f32>,
-    pub classification: Option<String>,
-    pub alert_level: Option<String>,
+    pub classification: Option<Classification>,
+    pub alert_level: Option<AlertLevel>,
     pub analysis_details: serde_json::Value,
     pub analyzed_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct MlAlert {
-    pub level: String,
+    pub level: AlertLevel,
     pub message: String,
     pub details: serde_json::Value,
 }
 
+/// One UTC day of the anomaly heat map; `counts[i]` covers hours `i * bucket_hours ..`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HeatmapRow {
+    pub date: NaiveDate,
+    pub counts: Vec<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AnomalyHeatmap {
+    pub patient_id: Uuid,
+    pub metric: String,
+    pub bucket_hours: u32,
+    pub days: u32,
+    /// Column labels, e.g. `"00:00"`, `"01:00"`, ...
+    pub buckets: Vec<String>,
+    pub rows: Vec<HeatmapRow>,
+    pub max_count: i64,
+}
+
+// ============ Threshold Profile Models ============
+
+/// Alert thresholds applied by the vitals analysis
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+pub struct ThresholdRules {
+    #[validate(range(min = 20, max = 150))]
+    pub hr_low: i32,
+    #[validate(range(min = 60, max = 250))]
+    pub hr_high: i32,
+    #[validate(range(min = 70, max = 100))]
+    pub spo2_low: i32,
+    #[validate(range(min = 37.0, max = 41.0))]
+    pub fever_temperature: f32,
+    #[validate(range(min = 32.0, max = 36.5))]
+    pub hypothermia_temperature: f32,
+    /// Rate-of-change rules; omitted means the built-in defaults, `[]` disables them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(nested, length(max = 10))]
+    pub delta_rules: Option<Vec<DeltaRule>>,
+    /// Conditions over several vitals at once, e.g. `HR > 120 AND SpO2 < 92 AND temp > 38`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[validate(nested, length(max = 20))]
+    pub composite_rules: Vec<CompositeRule>,
+    /// Shift alert severity for patients matching an age band or diagnosis
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[validate(nested, length(max = 20))]
+    pub severity_rules: Vec<SeverityRule>,
+}
+
+/// Recalibrates the severity of alerts for matching patients, e.g. one level up for
+/// heart rate anomalies in patients aged 80 or over
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+pub struct SeverityRule {
+    #[validate(range(min = 0, max = 130))]
+    pub min_age: Option<i32>,
+    #[validate(range(min = 0, max = 130))]
+    pub max_age: Option<i32>,
+    /// Matches patients with any of these diagnoses (case insensitive); empty matches all
+    #[serde(default)]
+    #[validate(length(max = 20))]
+    pub diagnoses: Vec<String>,
+    /// Only alerts involving this vital; omitted applies to any alert
+    pub metric: Option<VitalMetric>,
+    /// Levels to move along `low`, `medium`, `high`, `critical` (negative lowers)
+    #[validate(range(min = -3, max = 3))]
+    pub shift: i32,
+}
+
+/// A named condition in the rule language of `crate::rule_dsl`, evaluated per reading
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+pub struct CompositeRule {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[validate(length(max = 500), custom(function = "validate_condition"))]
+    pub condition: String,
+    /// Raise a critical rather than a high alert
+    #[serde(default)]
+    pub critical: bool,
+}
+
+fn validate_condition(condition: &str) -> Result<(), validator::ValidationError> {
+    crate::rule_dsl::Condition::parse(condition).map(|_| ()).map_err(|e| {
+        let mut error = validator::ValidationError::new("invalid_condition");
+        error.message = Some(e.into());
+        error
+    })
+}
+
+/// A vital sign tracked by rate-of-change rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VitalMetric {
+    HeartRate,
+    Spo2,
+    Temperature,
+}
+
+impl VitalMetric {
+    pub fn value(&self, reading: &SensorReading) -> Option<f32> {
+        match self {
+            VitalMetric::HeartRate => reading.heart_rate.map(|v| v as f32),
+            VitalMetric::Spo2 => reading.spo2.map(|v| v as f32),
+            VitalMetric::Temperature => reading.temperature,
+        }
+    }
+}
+
+impl TryFrom<String> for VitalMetric {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, String> {
+        match name.as_str() {
+            "heart_rate" => Ok(VitalMetric::HeartRate),
+            "spo2" => Ok(VitalMetric::Spo2),
+            "temperature" => Ok(VitalMetric::Temperature),
+            _ => Err(format!("Unknown vital '{}'", name)),
+        }
+    }
+}
+
+/// Fires when a vital changes by at least `change` (negative for drops) within
+/// `window_minutes`, e.g. heart rate +30 bpm in 5 minutes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+pub struct DeltaRule {
+    pub metric: VitalMetric,
+    #[validate(custom(function = "validate_delta_change"))]
+    pub change: f32,
+    #[validate(range(min = 1, max = 240))]
+    pub window_minutes: i64,
+    /// Raise a critical rather than a high alert
+    #[serde(default)]
+    pub critical: bool,
+}
+
+fn validate_delta_change(change: f32) -> Result<(), validator::ValidationError> {
+    if change == 0.0 || change.abs() > 100.0 {
+        return Err(validator::ValidationError::new("invalid_change"));
+    }
+    Ok(())
+}
+
+// ============ ML Rule Models ============
+
+/// An admin-defined alert rule, evaluated for every reading next to the built-in checks:
+/// alert when `metric comparator value` has held for `duration_seconds`
+#[derive(Debug, Clone, PartialEq, FromRow, Serialize)]
+pub struct MlRule {
+    pub id: Uuid,
+    pub name: String,
+    #[sqlx(try_from = "String")]
+    pub metric: VitalMetric,
+    pub comparator: String,
+    pub value: f32,
+    pub duration_seconds: i32,
+    pub alert_level: AlertLevel,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct MlRuleRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    pub metric: VitalMetric,
+    /// `>`, `>=`, `<`, `<=`, `=` or `!=`
+    #[validate(custom(function = "validate_comparator"))]
+    pub comparator: String,
+    #[validate(range(min = 0.0, max = 300.0))]
+    pub value: f32,
+    /// How long the comparison must hold over the device's consecutive readings; 0 (the
+    /// default) alerts on a single reading
+    #[serde(default)]
+    #[validate(range(min = 0, max = 1800))]
+    pub duration_seconds: i32,
+    /// `low`, `medium`, `high` or `critical`
+    #[validate(custom(function = "validate_alert_level"))]
+    pub alert_level: AlertLevel,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+fn validate_comparator(comparator: &str) -> Result<(), validator::ValidationError> {
+    match crate::rule_dsl::Op::from_symbol(comparator) {
+        Some(_) => Ok(()),
+        None => Err(validator::ValidationError::new("invalid_comparator")),
+    }
+}
+
+/// A threshold profile with its current (latest) rules
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct ThresholdProfile {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub version_id: i64,
+    pub version: i32,
+    pub rules: sqlx::types::Json<ThresholdRules>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct ThresholdProfileVersion {
+    pub id: i64,
+    pub profile_id: Uuid,
+    pub version: i32,
+    pub rules: sqlx::types::Json<ThresholdRules>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ThresholdProfileHistory {
+    #[serde(flatten)]
+    pub profile: ThresholdProfile,
+    /// Every version, newest first
+    pub versions: Vec<ThresholdProfileVersion>,
+}
+
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct ThresholdProfileRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[validate(length(max = 500))]
+    pub description: Option<String>,
+    #[validate(nested)]
+    pub rules: ThresholdRules,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct PatientThresholdProfileRequest {
+    /// `null` reverts the patient to the configured defaults
+    pub profile_id: Option<Uuid>,
+}
+
+/// Thresholds currently applied to a patient's readings
+#[derive(Debug, Serialize)]
+pub struct EffectiveThresholds {
+    pub patient_id: Uuid,
+    /// `None` when the configured defaults apply
+    pub profile: Option<ThresholdProfile>,
+    pub rules: ThresholdRules,
+}
+
+/// Longest range `POST /api/admin/ml/simulate` replays
+pub const MAX_SIMULATION_DAYS: i64 = 31;
+
+/// A proposed threshold set to replay stored readings against; nothing is stored or sent
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct MlSimulationRequest {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Only this patient's walkers; every claimed walker when omitted
+    pub patient_id: Option<Uuid>,
+    #[validate(nested)]
+    pub rules: ThresholdRules,
+}
+
+/// What a threshold set raised, or would have raised, over the replayed readings
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SimulationCounts {
+    /// Readings whose analysis produced an alert
+    pub alerting_readings: i64,
+    /// Alerts pushed by level, held back by the cooldown as ingestion does
+    pub alerts: BTreeMap<AlertLevel, i64>,
+    pub total_alerts: i64,
+    /// Readings flagged with each anomaly label
+    pub anomalies: BTreeMap<String, i64>,
+}
+
+impl Default for SimulationCounts {
+    fn default() -> Self {
+        Self {
+            alerting_readings: 0,
+            alerts: AlertLevel::RAISED.into_iter().map(|level| (level, 0)).collect(),
+            total_alerts: 0,
+            anomalies: BTreeMap::new(),
+        }
+    }
+}
+
+impl SimulationCounts {
+    pub fn add(&mut self, other: &SimulationCounts) {
+        self.alerting_readings += other.alerting_readings;
+        for (level, count) in &other.alerts {
+            *self.alerts.entry(*level).or_default() += count;
+        }
+        self.total_alerts += other.total_alerts;
+        for (label, count) in &other.anomalies {
+            *self.anomalies.entry(label.clone()).or_default() += count;
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MlSimulation {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub patient_id: Option<Uuid>,
+    pub devices: usize,
+    pub readings: usize,
+    /// Each walker judged against its patient's current threshold profile, or the defaults
+    pub current: SimulationCounts,
+    /// Every walker judged against the proposed rules
+    pub proposed: SimulationCounts,
+}
+
 // ============ FHIR Models ============
 
+/// Readings per page of `GET /api/fhir/export`; larger `_count`s are capped
+pub const DEFAULT_FHIR_PAGE_SIZE: i64 = 100;
+pub const MAX_FHIR_PAGE_SIZE: i64 = 1000;
+
+/// FHIR search parameters of the export; `limit` is the name `_count` had before paging
+#[derive(Debug, Deserialize, Validate)]
+pub struct FhirExportQuery {
+    #[serde(rename = "_count", alias = "limit")]
+    #[validate(range(min = 1))]
+    pub count: Option<i64>,
+    /// Where the page starts, from the previous page's `next` link
+    #[serde(rename = "_cursor")]
+    pub cursor: Option<String>,
+    /// Only readings the server received at or after this instant, for incremental sync
+    #[serde(rename = "_since")]
+    pub since: Option<DateTime<Utc>>,
+}
+
+/// Parameters of a bulk `$export` kick-off; `_type` is a comma-separated list of resource types
+#[derive(Debug, Deserialize)]
+pub struct BulkExportQuery {
+    #[serde(rename = "_type")]
+    pub types: Option<String>,
+    /// Only Observations of readings received at or after this instant
+    #[serde(rename = "_since")]
+    pub since: Option<DateTime<Utc>>,
+    #[serde(rename = "_outputFormat")]
+    pub output_format: Option<String>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct BulkExportJob {
+    pub id: Uuid,
+    pub requested_by: Option<Uuid>,
+    /// Only this organization's patients, walkers and readings are exported; all if `None`
+    pub organization_id: Option<Uuid>,
+    pub resource_types: Vec<String>,
+    pub since: Option<DateTime<Utc>>,
+    /// `in_progress`, `completed` or `failed`
+    pub status: String,
+    pub progress: Option<String>,
+    pub error: Option<String>,
+    pub requested_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// One NDJSON file of a bulk export, without its contents
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct BulkExportFile {
+    pub resource_type: String,
+    pub part: i32,
+    pub resource_count: i32,
+}
+
 #[derive(Debug, Clone, FromRow, Serialize)]
 pub struct FhirObservation {
     pub id: Uuid,
@@ -134,6 +999,7 @@ pub struct FhirObservation {
 }
 
 #[derive(Debug, Serialize)]
+#[allow(non_snake_case)] // FHIR JSON element names
 pub struct FhirObservationResource {
     pub resourceType: String,
     pub id: String,
@@ -165,52 +1031,1787 @@ pub struct FhirReference {
 
 #[derive(Debug, Serialize)]
 pub struct FhirQuantity {
-    pub value: f32,
+    pub value: f64,
     pub unit: String,
     pub system: String,
     pub code: String,
 }
 
-// ============ Audit Log Models ============
+// ============ Care Plan Models ============
 
-#[derive(Debug, Clone, FromRow)]
-pub struct AuditLog {
-    pub id: i64,
-    pub event_type: String,
-    pub user_id: Option<Uuid>,
-    pub device_id: Option<Uuid>,
-    pub action: String,
-    pub resource_type: Option<String>,
-    pub resource_id: Option<String>,
-    pub ip_address: Option<std::net::IpAddr>,
-    pub user_agent: Option<String>,
-    pub success: bool,
-    pub error_message: Option<String>,
-    pub metadata: serde_json::Value,
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct CarePlan {
+    pub id: Uuid,
+    pub patient_id: Uuid,
+    pub title: String,
+    pub status: String,
+    pub target_steps: Option<i32>,
+    pub target_activity_minutes: Option<i32>,
+    /// Prescribed daily walker use (walking, stairs or standing with the walker)
+    pub prescribed_usage_minutes: Option<i32>,
+    pub hr_min: Option<i32>,
+    pub hr_max: Option<i32>,
+    pub spo2_min: Option<i32>,
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>,
+    pub created_by: Option<Uuid>,
     pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
-// ============ JWT Claims ============
+/// Body for creating or replacing a care plan
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct CarePlanRequest {
+    #[validate(length(min = 1, max = 200))]
+    pub title: String,
+    #[serde(default = "default_care_plan_status")]
+    #[validate(custom(function = "validate_care_plan_status"))]
+    pub status: String,
+    #[validate(range(min = 1, max = 100000))]
+    pub target_steps: Option<i32>,
+    #[validate(range(min = 1, max = 1440))]
+    pub target_activity_minutes: Option<i32>,
+    #[validate(range(min = 1, max = 1440))]
+    pub prescribed_usage_minutes: Option<i32>,
+    #[validate(range(min = 0, max = 300))]
+    pub hr_min: Option<i32>,
+    #[validate(range(min = 0, max = 300))]
+    pub hr_max: Option<i32>,
+    #[validate(range(min = 0, max = 100))]
+    pub spo2_min: Option<i32>,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+}
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Claims {
-    pub sub: String,  // user email
-    pub user_id: Uuid,
-    pub role: String,
-    pub exp: i64,     // expiration timestamp
-    pub iat: i64,     // issued at
-    pub jti: Uuid,    // JWT ID (for revocation)
+fn default_care_plan_status() -> String {
+    "active".to_string()
 }
 
-// ============ SSE Event Models ============
+fn validate_care_plan_status(status: &str) -> Result<(), validator::ValidationError> {
+    match status {
+        "draft" | "active" | "completed" | "revoked" => Ok(()),
+        _ => Err(validator::ValidationError::new("invalid_status")),
+    }
+}
 
-#[derive(Debug, Serialize, Clone)]
-#[serde(tag = "type")]
-pub enum SseEvent {
-    #[serde(rename = "vitals")]
-    Vitals { data: LatestVitals },
-    #[serde(rename = "alert")]
-    Alert { data: MlAlert },
-    #[serde(rename = "heartbeat")]
-    Heartbeat { timestamp: i64 },
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct CarePlanEvaluation {
+    pub care_plan_id: Uuid,
+    pub eval_date: NaiveDate,
+    pub readings: i32,
+    pub steps: Option<i32>,
+    pub activity_minutes: Option<i32>,
+    pub avg_heart_rate: Option<f32>,
+    pub min_spo2: Option<i32>,
+    pub hr_in_range_ratio: Option<f32>,
+    /// Minutes the walker was in use; `None` on days without readings
+    pub usage_minutes: Option<i32>,
+    pub steps_met: Option<bool>,
+    pub activity_met: Option<bool>,
+    pub vitals_met: Option<bool>,
+    pub usage_met: Option<bool>,
+}
+
+/// Goal attainment over a window of daily evaluations
+#[derive(Debug, Serialize)]
+pub struct CarePlanProgress {
+    pub care_plan_id: Uuid,
+    pub days: usize,
+    pub steps_met_days: usize,
+    pub activity_met_days: usize,
+    pub vitals_met_days: usize,
+    pub usage_met_days: usize,
+    pub evaluations: Vec<CarePlanEvaluation>,
+}
+
+/// One day of walker use against the prescription
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct ComplianceDay {
+    pub date: NaiveDate,
+    pub usage_minutes: Option<i32>,
+    pub met: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WalkerCompliance {
+    pub patient_id: Uuid,
+    pub care_plan_id: Uuid,
+    pub prescribed_minutes: i32,
+    pub days_evaluated: usize,
+    pub days_met: usize,
+    /// Share of evaluated days meeting the prescription
+    pub compliance_rate: Option<f32>,
+    /// Consecutive completed days without any walker use, up to yesterday
+    pub non_use_streak: i64,
+    pub days: Vec<ComplianceDay>,
+}
+
+// ============ Medication Models ============
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Medication {
+    pub id: Uuid,
+    pub patient_id: Uuid,
+    pub name: String,
+    pub dosage: String,
+    pub instructions: Option<String>,
+    /// Daily administration times (UTC)
+    pub times: Vec<NaiveTime>,
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>,
+    pub active: bool,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct MedicationRequest {
+    #[validate(length(min = 1, max = 200))]
+    pub name: String,
+    #[validate(length(min = 1, max = 100))]
+    pub dosage: String,
+    #[validate(length(max = 1000))]
+    pub instructions: Option<String>,
+    #[validate(length(min = 1, max = 24))]
+    pub times: Vec<NaiveTime>,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct MedicationDose {
+    pub id: Uuid,
+    pub medication_id: Uuid,
+    pub scheduled_at: DateTime<Utc>,
+    pub status: String,
+    pub reminded_at: Option<DateTime<Utc>>,
+    pub confirmed_by: Option<Uuid>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+    pub note: Option<String>,
+}
+
+/// Caregiver confirmation of a dose (`taken` or `skipped`)
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct DoseConfirmRequest {
+    #[validate(custom(function = "validate_dose_status"))]
+    pub status: String,
+    #[validate(length(max = 1000))]
+    pub note: Option<String>,
+}
+
+fn validate_dose_status(status: &str) -> Result<(), validator::ValidationError> {
+    match status {
+        "taken" | "skipped" => Ok(()),
+        _ => Err(validator::ValidationError::new("invalid_status")),
+    }
+}
+
+/// Dose counts over a window; `ratio` is taken / (taken + skipped + missed)
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct Adherence {
+    pub taken: i64,
+    pub skipped: i64,
+    pub missed: i64,
+    pub pending: i64,
+    pub ratio: Option<f64>,
+}
+
+/// Reminder pushed to caregivers and the walker display when a dose is due
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MedicationReminder {
+    pub dose_id: Uuid,
+    pub medication_id: Uuid,
+    pub patient_id: Uuid,
+    pub name: String,
+    pub dosage: String,
+    pub scheduled_at: DateTime<Utc>,
+}
+
+// ============ Check-in Models ============
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Checkin {
+    pub id: Uuid,
+    pub patient_id: Uuid,
+    pub submitted_by: Option<Uuid>,
+    pub pain_score: Option<i32>,
+    pub dizziness: Option<i32>,
+    pub fatigue: Option<i32>,
+    pub shortness_of_breath: Option<bool>,
+    pub fell_since_last: Option<bool>,
+    pub notes: Option<String>,
+    pub submitted_at: DateTime<Utc>,
+}
+
+/// Self-report from the companion app; scores are 0 (none) to 10 (worst)
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct CheckinRequest {
+    #[validate(range(min = 0, max = 10))]
+    pub pain_score: Option<i32>,
+    #[validate(range(min = 0, max = 10))]
+    pub dizziness: Option<i32>,
+    #[validate(range(min = 0, max = 10))]
+    pub fatigue: Option<i32>,
+    pub shortness_of_breath: Option<bool>,
+    pub fell_since_last: Option<bool>,
+    #[validate(length(max = 2000))]
+    pub notes: Option<String>,
+}
+
+impl CheckinRequest {
+    pub fn is_empty(&self) -> bool {
+        self.pain_score.is_none()
+            && self.dizziness.is_none()
+            && self.fatigue.is_none()
+            && self.shortness_of_breath.is_none()
+            && self.fell_since_last.is_none()
+            && self.notes.as_deref().is_none_or(|n| n.trim().is_empty())
+    }
+}
+
+// ============ Patient Timeline & Risk Models ============
+
+/// The patient record fields exported as a FHIR `Patient`
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Patient {
+    pub id: Uuid,
+    pub display_name: String,
+    pub date_of_birth: Option<NaiveDate>,
+}
+
+/// A patient as listed for their caregivers and staff
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct PatientRecord {
+    pub id: Uuid,
+    pub display_name: String,
+    pub date_of_birth: Option<NaiveDate>,
+    pub diagnoses: Vec<String>,
+    pub organization_id: Option<Uuid>,
+    pub ward_id: Option<Uuid>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub archived_at: Option<DateTime<Utc>>,
+    /// Serials of the walkers currently assigned
+    pub devices: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct PatientRequest {
+    #[validate(length(min = 1, max = 200))]
+    pub display_name: String,
+    pub date_of_birth: Option<NaiveDate>,
+}
+
+/// Fields to change; absent ones are left as they are
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct PatientUpdate {
+    #[validate(length(min = 1, max = 200))]
+    pub display_name: Option<String>,
+    pub date_of_birth: Option<NaiveDate>,
+}
+
+/// One entry in a patient's merged timeline (check-ins, alerts, medication events)
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct TimelineEvent {
+    pub occurred_at: DateTime<Utc>,
+    pub kind: String,
+    pub data: serde_json::Value,
+}
+
+/// Patient record fields that severity rules are evaluated against
+#[derive(Debug, Clone, Default, PartialEq, FromRow, Serialize, Deserialize, Validate, JsonSchema)]
+pub struct PatientAttributes {
+    pub date_of_birth: Option<NaiveDate>,
+    #[serde(default)]
+    #[validate(length(max = 50))]
+    pub diagnoses: Vec<String>,
+}
+
+impl PatientAttributes {
+    /// Age in whole years on `date`
+    pub fn age_on(&self, date: NaiveDate) -> Option<i32> {
+        let born = self.date_of_birth?;
+        let years = date.years_since(born)?;
+        i32::try_from(years).ok()
+    }
+}
+
+/// Readings of a vital a baseline needs before scores are judged against it
+pub const MIN_BASELINE_SAMPLES: i32 = 100;
+/// Spreads below these are widened to them, so a very steady week does not turn ordinary
+/// variation into anomalies
+pub const MIN_BASELINE_HR_STDDEV: f32 = 5.0;
+pub const MIN_BASELINE_SPO2_STDDEV: f32 = 1.0;
+
+/// A walker's vitals over the trailing week (see `baseline_service`)
+#[derive(Debug, Clone, PartialEq, FromRow, Serialize)]
+pub struct Baseline {
+    pub device_id: Uuid,
+    pub patient_id: Option<Uuid>,
+    pub hr_mean: Option<f32>,
+    pub hr_stddev: Option<f32>,
+    pub hr_samples: i32,
+    pub spo2_mean: Option<f32>,
+    pub spo2_stddev: Option<f32>,
+    pub spo2_samples: i32,
+    pub window_start: DateTime<Utc>,
+    pub computed_at: DateTime<Utc>,
+}
+
+impl Baseline {
+    /// Heart rate mean and standard deviation, once there are enough samples
+    pub fn heart_rate(&self) -> Option<(f32, f32)> {
+        Self::usable(self.hr_mean, self.hr_stddev, self.hr_samples, MIN_BASELINE_HR_STDDEV)
+    }
+
+    /// SpO2 mean and standard deviation, once there are enough samples
+    pub fn spo2(&self) -> Option<(f32, f32)> {
+        Self::usable(self.spo2_mean, self.spo2_stddev, self.spo2_samples, MIN_BASELINE_SPO2_STDDEV)
+    }
+
+    fn usable(mean: Option<f32>, stddev: Option<f32>, samples: i32, min_stddev: f32) -> Option<(f32, f32)> {
+        (samples >= MIN_BASELINE_SAMPLES).then_some((mean?, stddev?.max(min_stddev)))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RiskAssessment {
+    /// 0.0 (no concern) to 1.0
+    pub score: f32,
+    /// `low`, `moderate` or `high`
+    pub level: String,
+    pub factors: Vec<String>,
+}
+
+// ============ Ambient Models ============
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct HeatStressReading {
+    pub at: DateTime<Utc>,
+    pub ambient_temperature: Option<f32>,
+    pub humidity: Option<f32>,
+    pub heart_rate: Option<i32>,
+}
+
+/// Ambient conditions over a window and how they relate to the patient's heart rate
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct AmbientSummary {
+    pub readings: i64,
+    pub avg_ambient_temperature: Option<f64>,
+    pub max_ambient_temperature: Option<f32>,
+    pub avg_humidity: Option<f64>,
+    /// Pearson correlation of ambient temperature and heart rate; `None` without variation
+    pub hr_correlation: Option<f64>,
+    pub heat_stress_readings: i64,
+    #[sqlx(skip)]
+    pub heat_stress: Vec<HeatStressReading>,
+}
+
+// ============ Sleep Models ============
+
+/// A sustained stretch of low heart rate without walker motion
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RestPeriod {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub minutes: i64,
+    /// `sleep` for long periods, otherwise `rest`
+    pub kind: String,
+    pub avg_heart_rate: Option<f32>,
+    pub min_heart_rate: Option<i32>,
+    pub readings: i32,
+}
+
+/// Rest periods for the night ending on `sleep_date` (noon to noon UTC)
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct SleepSummary {
+    pub patient_id: Uuid,
+    pub sleep_date: NaiveDate,
+    pub period_count: i32,
+    pub rest_minutes: i32,
+    pub sleep_minutes: i32,
+    pub longest_minutes: i32,
+    pub avg_heart_rate: Option<f32>,
+    /// Heart rate below which a still reading counted as resting
+    pub rest_hr_threshold: Option<f32>,
+    pub periods: sqlx::types::Json<Vec<RestPeriod>>,
+    pub computed_at: DateTime<Utc>,
+}
+
+// ============ Activity Models ============
+
+/// Consecutive motion windows sharing one activity label
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct ActivitySegment {
+    pub device_id: Uuid,
+    /// `walking`, `stairs`, `standing` or `sitting`
+    pub activity: String,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub steps: i32,
+    pub elevation_change: f32,
+    pub readings: i32,
+    pub classifier: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DailyActivityReport {
+    pub patient_id: Uuid,
+    pub date: NaiveDate,
+    /// IANA time zone the day's boundaries were taken in
+    pub timezone: String,
+    /// Classified minutes per activity; unclassified time is omitted
+    pub minutes: std::collections::BTreeMap<String, i64>,
+    pub steps: i64,
+    pub segments: Vec<ActivitySegment>,
+}
+
+// ============ Emergency Models ============
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct EmergencyContact {
+    pub id: Uuid,
+    pub patient_id: Uuid,
+    pub name: String,
+    pub relationship: Option<String>,
+    pub phone: String,
+    pub priority: i32,
+    pub notify_sms: bool,
+    pub notify_call: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct EmergencyContactRequest {
+    #[validate(length(min = 1, max = 200))]
+    pub name: String,
+    #[validate(length(max = 100))]
+    pub relationship: Option<String>,
+    /// E.164, e.g. `+15551234567`
+    #[validate(custom(function = "validate_phone"))]
+    pub phone: String,
+    #[validate(range(min = 1, max = 100))]
+    #[serde(default = "default_contact_priority")]
+    pub priority: i32,
+    #[serde(default = "default_true")]
+    pub notify_sms: bool,
+    #[serde(default)]
+    pub notify_call: bool,
+}
+
+fn default_contact_priority() -> i32 {
+    1
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn validate_phone(phone: &str) -> Result<(), validator::ValidationError> {
+    let digits = phone.strip_prefix('+').unwrap_or_default();
+    if (8..=15).contains(&digits.len()) && digits.bytes().all(|b| b.is_ascii_digit()) {
+        Ok(())
+    } else {
+        Err(validator::ValidationError::new("invalid_phone"))
+    }
+}
+
+/// Event pushed by the walker outside the vitals stream (HMAC-signed like vitals).
+///
+/// `near_fall` and `stumble` events may carry `speed` (m/s) and `surface` in `details`.
+#[derive(Debug, Serialize, Deserialize, Validate, JsonSchema)]
+pub struct DeviceEventIngest {
+    #[validate(custom(function = "validate_device_event_type"))]
+    pub event_type: String,
+    pub timestamp: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+fn validate_device_event_type(event_type: &str) -> Result<(), validator::ValidationError> {
+    match event_type {
+        "sos" | "near_fall" | "stumble" => Ok(()),
+        _ => Err(validator::ValidationError::new("unknown_event_type")),
+    }
+}
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Alert {
+    pub id: Uuid,
+    pub patient_id: Option<Uuid>,
+    pub device_id: Option<Uuid>,
+    pub kind: String,
+    pub level: AlertLevel,
+    pub message: String,
+    pub details: serde_json::Value,
+    pub raised_at: DateTime<Utc>,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    pub acknowledged_by: Option<Uuid>,
+    /// Voice calls placed so far while the alert stayed unacknowledged
+    pub escalation_level: i32,
+    pub escalated_at: Option<DateTime<Utc>>,
+    pub status: AlertStatus,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub resolved_by: Option<Uuid>,
+    /// What was done about it, given when resolving
+    pub resolution: Option<String>,
+}
+
+/// Where an alert stands, stored as the Postgres enum `alert_status`. Resolving an alert
+/// acknowledges it too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "alert_status", rename_all = "lowercase")]
+pub enum AlertStatus {
+    Open,
+    Acknowledged,
+    Resolved,
+}
+
+impl AlertStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertStatus::Open => "open",
+            AlertStatus::Acknowledged => "acknowledged",
+            AlertStatus::Resolved => "resolved",
+        }
+    }
+}
+
+/// Filters for `GET /api/alerts`
+#[derive(Debug, Deserialize)]
+pub struct AlertListQuery {
+    pub status: Option<AlertStatus>,
+    /// Only alerts at this level or above
+    pub min_level: Option<AlertLevel>,
+    pub kind: Option<String>,
+    pub patient_id: Option<Uuid>,
+    /// At most 500; 100 by default
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize, Validate, JsonSchema)]
+pub struct ResolveAlertRequest {
+    #[validate(length(max = 2000))]
+    pub resolution: Option<String>,
+}
+
+/// An alert acknowledged or resolved, pushed as the `alert_status` SSE event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertStatusChange {
+    pub alert_id: Uuid,
+    pub patient_id: Option<Uuid>,
+    pub kind: String,
+    pub level: AlertLevel,
+    pub status: AlertStatus,
+    pub changed_by: Option<Uuid>,
+    pub changed_at: DateTime<Utc>,
+}
+
+impl AlertStatusChange {
+    /// The alert's latest change, made by `user_id`
+    pub fn of(alert: &Alert, user_id: Uuid) -> Self {
+        Self {
+            alert_id: alert.id,
+            patient_id: alert.patient_id,
+            kind: alert.kind.clone(),
+            level: alert.level,
+            status: alert.status,
+            changed_by: Some(user_id),
+            changed_at: alert.resolved_at.or(alert.acknowledged_at).unwrap_or_else(Utc::now),
+        }
+    }
+}
+
+/// One step of an alert's response chain
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct AlertResponse {
+    pub id: i64,
+    pub alert_id: Uuid,
+    pub channel: String,
+    pub contact_id: Option<Uuid>,
+    pub user_id: Option<Uuid>,
+    pub target: Option<String>,
+    pub status: String,
+    pub detail: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// Provider identifier, e.g. the Twilio call SID
+    pub provider_ref: Option<String>,
+}
+
+/// A chat message on an alert; `parent_id` threads replies
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AlertMessage {
+    pub id: Uuid,
+    pub alert_id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub author_id: Option<Uuid>,
+    pub author_email: Option<String>,
+    pub author_role: Option<Role>,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct AlertMessageRequest {
+    #[validate(length(min = 1, max = 4000))]
+    pub body: String,
+    pub parent_id: Option<Uuid>,
+}
+
+/// `EnvFilter` directives, e.g. `info,medhealth_backend::sse=debug`
+#[derive(Debug, Deserialize, Validate)]
+pub struct LogLevelRequest {
+    #[validate(length(min = 1, max = 1000))]
+    pub filter: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LogLevel {
+    pub filter: String,
+    /// The filter this one replaced
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous: Option<String>,
+}
+
+/// A synthetic alert for commissioning, raised with `kind = "test"` and a `[TEST]` message
+#[derive(Debug, Deserialize, Validate)]
+pub struct TestAlertRequest {
+    /// Route it as this patient's alert would be; without one only admins are notified
+    pub patient_id: Option<Uuid>,
+    #[serde(default = "default_test_alert_level")]
+    #[validate(custom(function = "validate_alert_level"))]
+    pub level: AlertLevel,
+    #[validate(length(min = 1, max = 500))]
+    pub message: Option<String>,
+    /// Also text or call the patient's emergency contacts
+    #[serde(default)]
+    pub notify_contacts: bool,
+}
+
+fn default_test_alert_level() -> AlertLevel {
+    AlertLevel::Critical
+}
+
+fn validate_alert_level(level: &AlertLevel) -> Result<(), validator::ValidationError> {
+    match level {
+        AlertLevel::None => Err(validator::ValidationError::new("invalid_level")),
+        AlertLevel::Low | AlertLevel::Medium | AlertLevel::High | AlertLevel::Critical => Ok(()),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AlertWithResponses {
+    #[serde(flatten)]
+    pub alert: Alert,
+    pub responses: Vec<AlertResponse>,
+}
+
+// ============ On-Call Models ============
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct OnCallShift {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    /// Overrides win over scheduled shifts covering the same time
+    pub is_override: bool,
+    pub reason: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    /// `None` for site-wide cover
+    pub ward_id: Option<Uuid>,
+}
+
+/// Take over on-call until `ends_at`; defaults to the caller, starting now, site-wide
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct OnCallOverrideRequest {
+    pub user_id: Option<Uuid>,
+    pub ward_id: Option<Uuid>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: DateTime<Utc>,
+    #[validate(length(max = 500))]
+    pub reason: Option<String>,
+}
+
+/// Members take consecutive shifts of `shift_hours` in order, from `starts_at`
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct OnCallRotation {
+    pub id: Uuid,
+    pub name: String,
+    /// `None` for a site-wide rotation
+    pub ward_id: Option<Uuid>,
+    pub starts_at: DateTime<Utc>,
+    pub shift_hours: i32,
+    pub members: Vec<Uuid>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct OnCallRotationRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    pub ward_id: Option<Uuid>,
+    pub starts_at: DateTime<Utc>,
+    #[validate(range(min = 1, max = 168))]
+    pub shift_hours: i32,
+    #[validate(length(min = 1, max = 50))]
+    pub members: Vec<Uuid>,
+}
+
+/// Who is on call at a given time and where that came from
+#[derive(Debug, Clone, Serialize)]
+pub struct OnCallAssignment {
+    pub user_id: Uuid,
+    /// `override`, `shift` or `rotation`
+    pub source: String,
+    pub shift_id: Option<Uuid>,
+    pub rotation_id: Option<Uuid>,
+    pub ward_id: Option<Uuid>,
+    pub until: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OnCallStatus {
+    pub at: DateTime<Utc>,
+    /// Whether night-time routing is in effect right now
+    pub night_routing: bool,
+    pub ward_id: Option<Uuid>,
+    pub on_call: Option<OnCallAssignment>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OnCallQuery {
+    pub ward_id: Option<Uuid>,
+}
+
+/// The whole rota: every rotation plus current and upcoming shifts
+#[derive(Debug, Serialize)]
+pub struct OnCallRota {
+    pub rotations: Vec<OnCallRotation>,
+    pub shifts: Vec<OnCallShift>,
+}
+
+// ============ Near-Fall Models ============
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct NearFallEvent {
+    pub id: i64,
+    pub device_id: Uuid,
+    pub patient_id: Option<Uuid>,
+    /// `near_fall` or `stumble`
+    pub event_type: String,
+    pub occurred_at: DateTime<Utc>,
+    pub speed: Option<f32>,
+    pub surface: Option<String>,
+    pub details: serde_json::Value,
+    pub received_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct NearFallWeek {
+    pub week_start: DateTime<Utc>,
+    pub events: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NearFallTrend {
+    pub patient_id: Uuid,
+    /// Rolling 7-day windows ending now, oldest first
+    pub weeks: Vec<NearFallWeek>,
+    /// Whether the latest week would raise a fall-risk alert
+    pub increasing: bool,
+    pub recent: Vec<NearFallEvent>,
+}
+
+// ============ Ward Models ============
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Ward {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub patient_count: i64,
+}
+
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct WardRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+}
+
+/// A vitals reading the ML pipeline rated high or critical
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct VitalsExcursion {
+    pub at: DateTime<Utc>,
+    pub heart_rate: Option<i32>,
+    pub spo2: Option<i32>,
+    pub temperature: Option<f32>,
+    pub alert_level: Option<AlertLevel>,
+    pub classification: Option<Classification>,
+}
+
+/// Something the incoming shift still has to act on
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct UnresolvedItem {
+    pub kind: String,
+    pub description: String,
+    pub since: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PatientHandoff {
+    pub patient_id: Uuid,
+    pub display_name: String,
+    pub alerts: Vec<Alert>,
+    pub excursions: Vec<VitalsExcursion>,
+    pub unresolved: Vec<UnresolvedItem>,
+    pub rest_periods: Vec<RestPeriod>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HandoffSummary {
+    pub ward_id: Uuid,
+    pub ward_name: String,
+    pub since: DateTime<Utc>,
+    pub generated_at: DateTime<Utc>,
+    pub patients: Vec<PatientHandoff>,
+}
+
+// ============ Reporting Models ============
+
+#[derive(Debug, Deserialize)]
+pub struct ReportQuery {
+    /// Whole UTC days ending today
+    pub days: Option<u32>,
+    /// First and last UTC day, instead of `days`
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub ward_id: Option<Uuid>,
+    /// `json` (the default), or `csv` or `pdf` where a report offers them
+    pub format: Option<String>,
+}
+
+/// One row of the `patient_daily_stats` materialized view
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct PatientDailyStats {
+    pub day: NaiveDate,
+    pub readings: i64,
+    pub heart_rate_avg: Option<f64>,
+    pub heart_rate_min: Option<i32>,
+    pub heart_rate_max: Option<i32>,
+    pub spo2_avg: Option<f64>,
+    pub spo2_min: Option<i32>,
+    pub temperature_avg: Option<f64>,
+    pub temperature_max: Option<f32>,
+    pub anomalies: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PatientDailyReport {
+    pub patient_id: Uuid,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// `None` until the background worker has refreshed the view once
+    pub refreshed_at: Option<DateTime<Utc>>,
+    pub days: Vec<PatientDailyStats>,
+}
+
+/// Alerts of one kind and level over the report range
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct AlertSummaryRow {
+    pub kind: String,
+    pub level: AlertLevel,
+    pub raised: i64,
+    pub acknowledged: i64,
+    pub mean_ack_minutes: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AlertSummaryReport {
+    pub ward_id: Option<Uuid>,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub refreshed_at: Option<DateTime<Utc>>,
+    pub alerts: Vec<AlertSummaryRow>,
+}
+
+/// One alert of the quality committee export of `GET /api/reports/alerts`
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct AlertReportRow {
+    pub id: Uuid,
+    pub raised_at: DateTime<Utc>,
+    pub patient_id: Uuid,
+    pub patient_name: String,
+    pub kind: String,
+    pub level: AlertLevel,
+    pub status: AlertStatus,
+    pub message: String,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    /// Email of whoever acknowledged it
+    pub acknowledged_by: Option<String>,
+    pub ack_minutes: Option<f64>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub resolved_by: Option<String>,
+    pub resolution: Option<String>,
+}
+
+/// A read-only SQL login issued to a BI tool
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct ReportingCredential {
+    pub id: Uuid,
+    /// The login's user name
+    pub role_name: String,
+    /// Organization whose rows the login sees; `None` for all of them
+    pub organization_id: Option<Uuid>,
+    pub label: String,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub revoked_by: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct ReportingCredentialRequest {
+    /// What the login is for, e.g. "Quality dashboard"
+    #[validate(length(min = 1, max = 200))]
+    pub label: String,
+    pub organization_id: Option<Uuid>,
+    /// Lifetime in days (default `reporting.credential_days`)
+    #[validate(range(min = 1, max = 366))]
+    pub days: Option<i64>,
+}
+
+/// A new BI login with its password, shown this once
+#[derive(Debug, Serialize)]
+pub struct IssuedReportingCredential {
+    #[serde(flatten)]
+    pub credential: ReportingCredential,
+    pub password: String,
+    pub database: String,
+    /// Schema holding the views the login may read
+    pub schema: &'static str,
+}
+
+// ============ Analytics Models ============
+
+/// A chart query for `POST /api/analytics/query`, compiled by `crate::analytics` to SQL over
+/// whitelisted fields only
+#[derive(Debug, Clone, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AnalyticsQuery {
+    pub source: AnalyticsSource,
+    #[validate(length(min = 1, max = 8))]
+    pub metrics: Vec<AnalyticsMetric>,
+    /// Dimensions to group by, e.g. `["ward_id", "level"]`
+    #[serde(default)]
+    #[validate(length(max = 3))]
+    pub group_by: Vec<String>,
+    /// Also group by time, in the caller's time zone
+    pub bucket: Option<AnalyticsBucket>,
+    #[serde(default)]
+    #[validate(length(max = 10))]
+    pub filters: Vec<AnalyticsFilter>,
+    /// Start of the range (default 30 days before `to`)
+    pub from: Option<DateTime<Utc>>,
+    /// End of the range, exclusive (default now)
+    pub to: Option<DateTime<Utc>>,
+    /// Archived patients are left out unless this is set
+    #[serde(default)]
+    pub include_archived: bool,
+    /// Most rows returned (default 1000, at most 10000)
+    #[validate(range(min = 1, max = 10000))]
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalyticsSource {
+    /// Walker readings with their ML analysis
+    Readings,
+    Alerts,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AnalyticsMetric {
+    #[serde(rename = "fn")]
+    pub function: AnalyticsFunction,
+    /// Numeric field; not used by `count`
+    pub field: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalyticsFunction {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+    Median,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalyticsBucket {
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AnalyticsFilter {
+    pub field: String,
+    pub op: AnalyticsOp,
+    /// A number or string; an array of them for `in`
+    pub value: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalyticsOp {
+    Eq,
+    Ne,
+    In,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AnalyticsResult {
+    /// Column names in order: `bucket`, the group-bys, then one per metric
+    pub columns: Vec<String>,
+    pub rows: Vec<serde_json::Value>,
+    /// More groups matched than `limit`
+    pub truncated: bool,
+}
+
+// ============ Legal Hold Models ============
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct LegalHold {
+    pub id: Uuid,
+    pub patient_id: Uuid,
+    pub reason: String,
+    pub matter_reference: Option<String>,
+    pub placed_by: Option<Uuid>,
+    pub placed_at: DateTime<Utc>,
+    pub released_by: Option<Uuid>,
+    pub released_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct LegalHoldRequest {
+    #[validate(length(min = 1, max = 2000))]
+    pub reason: String,
+    #[validate(length(max = 200))]
+    pub matter_reference: Option<String>,
+}
+
+/// Export metadata; the archive bytes are only served by the download endpoint
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct LegalHoldExport {
+    pub id: Uuid,
+    pub hold_id: Uuid,
+    pub patient_id: Uuid,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub sha256: String,
+    /// Base64 HMAC-SHA256 of the archive bytes
+    pub signature: String,
+    pub byte_size: i32,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct CustodyEvent {
+    pub id: i64,
+    pub export_id: Uuid,
+    pub action: String,
+    pub actor_id: Option<Uuid>,
+    pub actor_email: Option<String>,
+    pub actor_ip: Option<String>,
+    pub note: Option<String>,
+    pub recorded_at: DateTime<Utc>,
+    pub prev_hash: Option<String>,
+    pub hash: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LegalHoldExportWithCustody {
+    #[serde(flatten)]
+    pub export: LegalHoldExport,
+    pub custody: Vec<CustodyEvent>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportVerification {
+    pub export_id: Uuid,
+    pub archive_intact: bool,
+    pub signature_valid: bool,
+    pub custody_chain_intact: bool,
+}
+
+// ============ Archival & Transfer Models ============
+
+/// Where a patient's record stands: archived (read-only) or active, and with which organization
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct PatientRecordStatus {
+    pub id: Uuid,
+    pub display_name: String,
+    pub organization_id: Option<Uuid>,
+    pub archived_at: Option<DateTime<Utc>>,
+    pub archived_by: Option<Uuid>,
+    pub archive_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct ArchivePatientRequest {
+    /// e.g. "Discharged home 2026-03-02"
+    #[validate(length(min = 1, max = 2000))]
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct PatientTransfer {
+    pub id: Uuid,
+    pub patient_id: Uuid,
+    pub from_organization_id: Option<Uuid>,
+    pub to_organization_id: Uuid,
+    /// `pending`, `completed`, `rejected` or `cancelled`
+    pub status: String,
+    pub reason: Option<String>,
+    pub requested_by: Option<Uuid>,
+    pub requested_at: DateTime<Utc>,
+    pub source_confirmed_by: Option<Uuid>,
+    pub source_confirmed_at: Option<DateTime<Utc>>,
+    pub target_confirmed_by: Option<Uuid>,
+    pub target_confirmed_at: Option<DateTime<Utc>>,
+    pub closed_at: Option<DateTime<Utc>>,
+    /// Counts of the records that moved, set on completion
+    pub moved: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct PatientTransferEvent {
+    pub id: i64,
+    pub transfer_id: Uuid,
+    pub action: String,
+    pub organization_id: Option<Uuid>,
+    pub actor_id: Option<Uuid>,
+    pub actor_email: Option<String>,
+    pub note: Option<String>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PatientTransferWithEvents {
+    #[serde(flatten)]
+    pub transfer: PatientTransfer,
+    pub events: Vec<PatientTransferEvent>,
+}
+
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct PatientTransferRequest {
+    pub to_organization_id: Uuid,
+    #[validate(length(min = 1, max = 2000))]
+    pub reason: Option<String>,
+}
+
+/// Confirming, rejecting or cancelling a transfer, with an optional note for its log
+#[derive(Debug, Default, Deserialize, Validate, JsonSchema)]
+pub struct TransferDecisionRequest {
+    #[validate(length(max = 2000))]
+    pub note: Option<String>,
+}
+
+// ============ Organization Models ============
+
+/// An organization with its storage quotas and the usage last measured against them
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Organization {
+    pub id: Uuid,
+    pub name: String,
+    pub max_readings: Option<i64>,
+    pub max_bytes: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub patient_count: i64,
+    pub readings: Option<i64>,
+    pub bytes: Option<i64>,
+    pub measured_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct OrganizationRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub quota: QuotaRequest,
+}
+
+/// Storage limits for an organization; omitted limits are unlimited
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct QuotaRequest {
+    #[validate(range(min = 1))]
+    pub max_readings: Option<i64>,
+    #[validate(range(min = 1))]
+    pub max_bytes: Option<i64>,
+}
+
+/// The organization running this deployment, as shown on reports and in FHIR. Distinct from
+/// the [`Organization`]s patients are grouped into.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct OrganizationProfile {
+    pub name: String,
+    pub logo_url: Option<String>,
+    pub phone: Option<String>,
+    pub email: Option<String>,
+    pub website: Option<String>,
+    pub address: Option<String>,
+    pub updated_at: DateTime<Utc>,
+    pub updated_by: Option<Uuid>,
+}
+
+impl OrganizationProfile {
+    /// Contact details as one line, e.g. for a report letterhead
+    pub fn contact_line(&self) -> Option<String> {
+        let address = self.address.as_deref().map(|a| a.lines().map(str::trim).collect::<Vec<_>>().join(", "));
+        let parts: Vec<String> = [address, self.phone.clone(), self.email.clone(), self.website.clone()]
+            .into_iter()
+            .flatten()
+            .filter(|part| !part.is_empty())
+            .collect();
+        (!parts.is_empty()).then(|| parts.join(" | "))
+    }
+}
+
+/// Replaces the whole profile; omitted details are cleared
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct OrganizationProfileRequest {
+    #[validate(length(min = 1, max = 200))]
+    pub name: String,
+    #[validate(url, length(max = 2000))]
+    pub logo_url: Option<String>,
+    /// E.164, e.g. `+15551234567`
+    #[validate(custom(function = "validate_phone"))]
+    pub phone: Option<String>,
+    #[validate(email)]
+    pub email: Option<String>,
+    #[validate(url, length(max = 2000))]
+    pub website: Option<String>,
+    /// Postal address; lines separated by newlines
+    #[validate(length(max = 500))]
+    pub address: Option<String>,
+}
+
+/// One organization's total for a metric on a UTC day
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct UsageRollup {
+    pub organization_id: Option<Uuid>,
+    pub day: NaiveDate,
+    pub metric: String,
+    pub quantity: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+// ============ Integration Models ============
+
+/// The kind of outside system an [`Integration`] connects to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum IntegrationKind {
+    /// A FHIR server observations are pushed to, at its base URL
+    Fhir,
+    /// An HL7 v2 receiver, at `mllp://host:port`
+    Hl7,
+    /// An outbound webhook, signed as described in `crate::webhooks`
+    Webhook,
+}
+
+impl IntegrationKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IntegrationKind::Fhir => "fhir",
+            IntegrationKind::Hl7 => "hl7",
+            IntegrationKind::Webhook => "webhook",
+        }
+    }
+}
+
+/// An organization's connection to an outside system, as last configured and tested. The
+/// secret is only ever shown as `has_secret`.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Integration {
+    pub id: Uuid,
+    pub organization_id: Uuid,
+    pub kind: IntegrationKind,
+    pub name: String,
+    pub url: String,
+    pub username: Option<String>,
+    /// Sealed under the organization's keys when encryption is configured
+    #[serde(skip)]
+    pub secret: Option<String>,
+    pub has_secret: bool,
+    pub settings: sqlx::types::Json<IntegrationSettings>,
+    pub enabled: bool,
+    pub last_tested_at: Option<DateTime<Utc>>,
+    pub last_test_ok: Option<bool>,
+    pub last_test_error: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Settings shared by every kind of integration, plus webhook defaults
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct IntegrationSettings {
+    /// Request (or connect) timeout; 10 seconds when unset
+    #[validate(range(min = 1, max = 60))]
+    pub timeout_seconds: Option<u64>,
+    /// Webhooks only: the event types posted, e.g. `usage_daily`; every event when empty
+    #[validate(length(max = 50))]
+    pub events: Vec<String>,
+}
+
+/// Creates or replaces an integration
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct IntegrationRequest {
+    pub kind: IntegrationKind,
+    /// Tells integrations of the same kind apart, e.g. "Regional HIE"
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[validate(length(min = 1, max = 2000))]
+    pub url: String,
+    /// Basic auth user for FHIR servers; without one a FHIR secret is sent as a bearer token
+    #[validate(length(min = 1, max = 200))]
+    pub username: Option<String>,
+    /// FHIR password or bearer token, or a webhook's signing secret. Omitted on update keeps
+    /// the stored one; an empty string clears it.
+    #[validate(length(max = 4096))]
+    pub secret: Option<String>,
+    #[serde(default)]
+    #[validate(nested)]
+    pub settings: IntegrationSettings,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Required of administrators not tied to an organization; ignored on update
+    pub organization_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IntegrationQuery {
+    pub organization_id: Option<Uuid>,
+    pub kind: Option<IntegrationKind>,
+}
+
+/// What `POST /api/admin/integrations/{id}/test` found
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrationTestResult {
+    pub ok: bool,
+    /// The HTTP status answered, for FHIR servers and webhooks
+    pub status: Option<u16>,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+    pub tested_at: DateTime<Utc>,
+}
+
+// ============ Notification Models ============
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Notification {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub patient_id: Option<Uuid>,
+    pub kind: String,
+    pub title: String,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub read_at: Option<DateTime<Utc>>,
+}
+
+/// The channels a user's vitals alerts go to besides the inbox; see `crate::notifications`
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct NotificationPreferences {
+    pub email_enabled: bool,
+    /// Unset sends to the account's email
+    pub email: Option<String>,
+    pub sms_enabled: bool,
+    pub phone: Option<String>,
+    pub webhook_enabled: bool,
+    pub webhook_url: Option<String>,
+    #[serde(skip)]
+    pub webhook_secret: Option<String>,
+    /// Whether webhook posts are signed; the secret itself is never returned
+    #[sqlx(skip)]
+    pub webhook_signed: bool,
+    /// Alerts below this level are not sent
+    pub min_level: AlertLevel,
+}
+
+impl Default for NotificationPreferences {
+    fn default() -> Self {
+        Self {
+            email_enabled: false,
+            email: None,
+            sms_enabled: false,
+            phone: None,
+            webhook_enabled: false,
+            webhook_url: None,
+            webhook_secret: None,
+            webhook_signed: false,
+            min_level: AlertLevel::High,
+        }
+    }
+}
+
+/// Fields left out are kept; send `""` to clear an address or the webhook secret
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct UpdateNotificationPreferencesRequest {
+    pub email_enabled: Option<bool>,
+    #[validate(length(max = 320), custom(function = "validate_email_or_empty"))]
+    pub email: Option<String>,
+    pub sms_enabled: Option<bool>,
+    /// E.164, e.g. `+15551234567`
+    #[validate(custom(function = "validate_phone_or_empty"))]
+    pub phone: Option<String>,
+    pub webhook_enabled: Option<bool>,
+    /// `https://` (or `http://`) URL the alerts are posted to
+    #[validate(length(max = 2000), custom(function = "validate_webhook_url_or_empty"))]
+    pub webhook_url: Option<String>,
+    #[validate(length(max = 200))]
+    pub webhook_secret: Option<String>,
+    pub min_level: Option<AlertLevel>,
+}
+
+fn validate_email_or_empty(email: &str) -> Result<(), validator::ValidationError> {
+    use validator::ValidateEmail;
+    if email.is_empty() || email.validate_email() {
+        Ok(())
+    } else {
+        Err(validator::ValidationError::new("invalid_email"))
+    }
+}
+
+fn validate_phone_or_empty(phone: &str) -> Result<(), validator::ValidationError> {
+    if phone.is_empty() {
+        Ok(())
+    } else {
+        validate_phone(phone)
+    }
+}
+
+fn validate_webhook_url_or_empty(url: &str) -> Result<(), validator::ValidationError> {
+    match reqwest::Url::parse(url) {
+        _ if url.is_empty() => Ok(()),
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") && parsed.host().is_some() => Ok(()),
+        _ => Err(validator::ValidationError::new("invalid_webhook_url")),
+    }
+}
+
+/// One message queued for a user's email, SMS or webhook
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct NotificationDelivery {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub patient_id: Option<Uuid>,
+    pub alert_id: Option<Uuid>,
+    pub channel: String,
+    pub target: String,
+    pub level: AlertLevel,
+    pub subject: String,
+    pub body: String,
+    /// `pending`, `delivered` or `failed` (out of attempts)
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+// ============ Audit Log Models ============
+
+#[derive(Debug, Clone, FromRow)]
+pub struct AuditLog {
+    pub id: i64,
+    pub event_type: String,
+    pub user_id: Option<Uuid>,
+    pub device_id: Option<Uuid>,
+    pub action: String,
+    pub resource_type: Option<String>,
+    pub resource_id: Option<String>,
+    pub ip_address: Option<std::net::IpAddr>,
+    pub user_agent: Option<String>,
+    pub success: bool,
+    pub error_message: Option<String>,
+    pub metadata: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A broadcast event as recorded in the event log (see `crate::event_log`)
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct EventLogEntry {
+    pub event_id: Uuid,
+    pub event_type: String,
+    pub patient_id: Option<Uuid>,
+    pub occurred_at: DateTime<Utc>,
+    /// Hex SHA-256 of the event's JSON, as WebSocket clients receive it and SSE clients in `data:`
+    pub payload_sha256: String,
+}
+
+/// An SSE or WebSocket stream a user had open
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct StreamSessionRecord {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    /// `sse` or `websocket`
+    pub transport: String,
+    /// Patients whose events the stream carried when it opened; `None` for all
+    pub patients: Option<Vec<Uuid>>,
+    pub opened_at: DateTime<Utc>,
+    /// `None` while open, or when the server stopped without closing it
+    pub closed_at: Option<DateTime<Utc>>,
+}
+
+/// A logged event and the stream it was sent on
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct SentEvent {
+    pub session_id: Uuid,
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub event: EventLogEntry,
+}
+
+/// What a user's dashboard was sent between two times
+#[derive(Debug, Clone, Serialize)]
+pub struct UserEventHistory {
+    pub user_id: Uuid,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub sessions: Vec<StreamSessionRecord>,
+    pub events: Vec<SentEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EventLogQuery {
+    pub patient_id: Option<Uuid>,
+    pub event_type: Option<String>,
+    /// Defaults to a day before `to`
+    pub from: Option<DateTime<Utc>>,
+    /// Defaults to now
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+}
+
+// ============ Admin Models ============
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SelftestStage {
+    pub stage: &'static str,
+    pub passed: bool,
+    pub duration_ms: u128,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SelftestReport {
+    pub passed: bool,
+    pub stages: Vec<SelftestStage>,
+}
+
+/// Compliance with one objective over a rolling window
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct SloWindow {
+    pub days: i32,
+    pub total: i64,
+    pub good: i64,
+    /// Fraction of good requests; unset without traffic
+    pub compliance: Option<f64>,
+    pub met: bool,
+    /// Share of the allowed bad requests still unspent; negative once the budget is blown
+    pub error_budget_remaining: Option<f64>,
+}
+
+impl SloWindow {
+    pub fn new(days: i32, total: i64, good: i64, target: f64) -> Self {
+        let compliance = (total > 0).then(|| good as f64 / total as f64);
+        let allowed_bad = (1.0 - target) * total as f64;
+        Self {
+            days,
+            total,
+            good,
+            compliance,
+            met: compliance.is_none_or(|c| c >= target),
+            error_budget_remaining: (total > 0).then(|| 1.0 - (total - good) as f64 / allowed_bad),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SloStatus {
+    #[serde(flatten)]
+    pub objective: crate::config::SloObjective,
+    pub windows: Vec<SloWindow>,
+}
+
+// ============ JWT Claims ============
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: String,  // user email
+    pub user_id: Uuid,
+    pub role: Role,
+    /// Organization the user belonged to when the token was issued; checked against the
+    /// current one on every request, so moving a user between organizations signs them out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org: Option<Uuid>,
+    pub exp: i64,     // expiration timestamp
+    pub iat: i64,     // issued at
+    pub jti: Uuid,    // JWT ID (for revocation)
+}
+
+// ============ Event Models ============
+
+/// Version of the event envelope and payloads; schemas live in `schemas/events/v{N}`.
+/// Adding optional fields keeps the version, anything else bumps it.
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+/// Events broadcast since the client's cursor, for clients that can't hold an SSE stream
+#[derive(Debug, Serialize)]
+pub struct VitalsPoll {
+    pub events: Vec<EventEnvelope>,
+    /// Pass as `since` on the next poll
+    pub cursor: Option<Uuid>,
+}
+
+/// Envelope for every published event (SSE, webhooks and the in-process broadcaster):
+/// `{version, type, id, occurred_at, data}`, with `type` and `data` coming from the event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventEnvelope<E = SseEvent> {
+    pub version: u32,
+    pub id: Uuid,
+    pub occurred_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: E,
+    /// Patient the event concerns, deciding which streams may receive it; never published
+    #[serde(skip)]
+    pub patient_id: Option<Uuid>,
+}
+
+impl<E> EventEnvelope<E> {
+    pub fn new(event: E) -> Self {
+        Self {
+            version: EVENT_SCHEMA_VERSION,
+            id: Uuid::new_v4(),
+            occurred_at: Utc::now(),
+            event,
+            patient_id: None,
+        }
+    }
+
+    /// Limit the event to streams allowed to see this patient (see [`crate::sse::StreamViewer`])
+    pub fn for_patient(mut self, patient_id: Option<Uuid>) -> Self {
+        self.patient_id = patient_id;
+        self
+    }
+}
+
+/// Events streamed to dashboards and walker displays
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum SseEvent {
+    Vitals(LatestVitals),
+    Alert(MlAlert),
+    Heartbeat { timestamp: i64 },
+    Reminder(MedicationReminder),
+    AlertMessage(AlertMessage),
+    AlertStatus(AlertStatusChange),
+}
+
+impl SseEvent {
+    /// Every [`event_type`](Self::event_type)
+    pub const EVENT_TYPES: &'static [&'static str] = &["vitals", "alert", "heartbeat", "reminder", "alert_message", "alert_status"];
+
+    /// The envelope `type`, also used as the SSE `event:` name
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            SseEvent::Vitals(_) => "vitals",
+            SseEvent::Alert(_) => "alert",
+            SseEvent::Heartbeat { .. } => "heartbeat",
+            SseEvent::Reminder(_) => "reminder",
+            SseEvent::AlertMessage(_) => "alert_message",
+            SseEvent::AlertStatus(_) => "alert_status",
+        }
+    }
+}
+
+/// Messages a client sends over `/api/ws/vitals`
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsClientMessage {
+    /// Receive only these event types from now on (empty: all of them)
+    Subscribe { events: Vec<String> },
+}
+
+/// Events posted to outbound webhooks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// A closed day of usage for the billing endpoint
+    UsageDaily { day: NaiveDate, usage: Vec<UsageRollup> },
+    /// Ask the contact gateway to text (`channel = "sms"`) or call (`"call"`) a phone number
+    ContactRequested { channel: String, to: String, message: String, alert_id: Uuid },
+    /// Sent by a connectivity test of a webhook integration, unsequenced (`X-Webhook-Sequence: 0`)
+    IntegrationTest { integration_id: Uuid },
+}
+
+impl WebhookEvent {
+    /// The envelope `type`
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            WebhookEvent::UsageDaily { .. } => "usage_daily",
+            WebhookEvent::ContactRequested { .. } => "contact_requested",
+            WebhookEvent::IntegrationTest { .. } => "integration_test",
+        }
+    }
+}
+
+/// One event posted to an outgoing webhook (see [`crate::webhooks`]), without its body
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct WebhookDelivery {
+    pub event_id: Uuid,
+    pub webhook: String,
+    pub sequence: i64,
+    pub event_type: String,
+    /// `pending`, `delivered` or `failed`, as of the latest attempt
+    pub status: String,
+    pub attempts: i32,
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookDeliveriesQuery {
+    /// Only deliveries after this sequence number, e.g. the last one a receiver processed
+    pub after_sequence: Option<i64>,
+    pub limit: Option<i64>,
 }
@@ -0,0 +1,158 @@
+//! MQTT ingestion for walker gateways that publish readings rather than POST them.
+//!
+//! Subscribes to `mqtt.topic` (e.g. `devices/+/vitals`, where the `+` level is the device
+//! id) and feeds each message through the same pipeline as `POST /api/device/vitals`:
+//! storage, ML analysis, FHIR, cache and SSE. Messages are [`MqttVitalsMessage`]s, signed
+//! with the device's secret exactly as over HTTP; the signature covers `vitals` as published.
+
+use crate::api_version::legacy_fields;
+use crate::config::MqttConfig;
+use crate::dead_letter::{IngestChannel, IngestSource};
+use crate::errors::ApiError;
+use crate::handlers::device::{authenticate_device, ingest_vitals, normalize_for};
+use crate::handlers::AppState;
+use crate::metrics::{record_device_error, DEPRECATED_FIELDS_TOTAL};
+use crate::models::{Device, MqttVitalsMessage};
+use actix_web::web;
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, Publish, QoS, Transport};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+/// Messages waiting for the ingestion pipeline; beyond this they are dropped
+const BACKLOG: usize = 1000;
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// The device id in `topic`: the level at the position of `pattern`'s `+`
+pub fn device_from_topic<'a>(pattern: &str, topic: &'a str) -> Option<&'a str> {
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let levels: Vec<&str> = topic.split('/').collect();
+    if pattern.len() != levels.len() {
+        return None;
+    }
+    let mut device = None;
+    for (expected, level) in pattern.iter().zip(levels) {
+        match *expected {
+            "+" => device = Some(level),
+            _ if *expected != level => return None,
+            _ => {}
+        }
+    }
+    device.filter(|d| !d.is_empty())
+}
+
+/// The reading exactly as published, which is what the walker signed
+#[derive(Deserialize)]
+struct SignedVitals<'a> {
+    #[serde(borrow)]
+    vitals: &'a RawValue,
+}
+
+/// Authenticate one message and ingest its reading. A rejection names the device only once it
+/// has authenticated: the topic is anyone's to publish to, so its device id would let them add
+/// metric series without bound.
+async fn ingest_message(state: &AppState, pattern: &str, publish: &Publish) -> Result<(), (Option<String>, ApiError)> {
+    let (device, message) = authenticate_message(state, pattern, publish).await.map_err(|e| (None, e))?;
+    ingest_reading(state, &device, message).await.map_err(|e| (Some(device.device_id), e))
+}
+
+/// The device that signed the message, and the message
+async fn authenticate_message(state: &AppState, pattern: &str, publish: &Publish) -> Result<(Device, MqttVitalsMessage), ApiError> {
+    let device_id = device_from_topic(pattern, &publish.topic)
+        .ok_or_else(|| ApiError::BadRequest(format!("Topic {} names no device", publish.topic)))?;
+    let message: MqttVitalsMessage =
+        serde_json::from_slice(&publish.payload).map_err(|e| ApiError::BadRequest(format!("Invalid message: {}", e)))?;
+
+    let SignedVitals { vitals } =
+        serde_json::from_slice(&publish.payload).map_err(|e| ApiError::BadRequest(format!("Invalid message: {}", e)))?;
+    let device = authenticate_device(state, device_id, message.timestamp, &message.signature, vitals.get()).await?;
+    for field in legacy_fields(vitals.get()) {
+        DEPRECATED_FIELDS_TOTAL.with_label_values(&[field]).inc();
+    }
+    Ok((device, message))
+}
+
+async fn ingest_reading(state: &AppState, device: &Device, message: MqttVitalsMessage) -> Result<(), ApiError> {
+    let source = IngestSource::new(IngestChannel::Mqtt);
+    let reading = normalize_for(state, device, message.vitals, &source).await?;
+
+    let response = ingest_vitals(state, device, &reading, &source, None).await;
+    if !response.status().is_success() {
+        return Err(ApiError::Unavailable(format!("Ingestion answered {}", response.status())));
+    }
+    Ok(())
+}
+
+fn options(config: &MqttConfig) -> MqttOptions {
+    let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if let (Some(username), Some(password)) = (&config.username, &config.password) {
+        options.set_credentials(username, password);
+    }
+    if config.tls {
+        options.set_transport(Transport::tls_with_default_config());
+    }
+    options
+}
+
+/// Background task subscribing to the broker and ingesting what walkers publish.
+///
+/// The broker connection is kept up on its own, resubscribing after every reconnect, while
+/// messages are ingested one at a time in arrival order so each device's readings are
+/// analysed against the ones before them.
+pub fn spawn_mqtt_ingest(state: web::Data<AppState>, config: MqttConfig) -> tokio::task::JoinHandle<()> {
+    let (tx, mut rx) = mpsc::channel::<Publish>(BACKLOG);
+
+    let topic = config.topic.clone();
+    tokio::spawn(async move {
+        while let Some(publish) = rx.recv().await {
+            if let Err((device, e)) = ingest_message(&state, &topic, &publish).await {
+                record_device_error(device.as_deref(), "mqtt_rejected");
+                warn!(topic = %publish.topic, "MQTT reading rejected: {}", e);
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        let (client, mut eventloop) = AsyncClient::new(options(&config), 10);
+        loop {
+            match eventloop.poll().await {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    info!(host = %config.host, topic = %config.topic, "Connected to MQTT broker");
+                    if let Err(e) = client.try_subscribe(&config.topic, QoS::AtLeastOnce) {
+                        warn!("MQTT subscribe failed: {}", e);
+                    }
+                }
+                Ok(Event::Incoming(Packet::Publish(publish))) => {
+                    if let Err(e) = tx.try_send(publish) {
+                        let topic = &e.into_inner().topic;
+                        // Not yet authenticated, so not counted under the topic's device
+                        record_device_error(None, "mqtt_backlog");
+                        warn!(topic = %topic, "MQTT ingestion backlog full; dropping a reading");
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("MQTT connection error: {}; reconnecting in {:?}", e, RECONNECT_DELAY);
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_from_topic() {
+        assert_eq!(device_from_topic("devices/+/vitals", "devices/WALKER-7/vitals"), Some("WALKER-7"));
+        assert_eq!(device_from_topic("site/ward3/+", "site/ward3/pi-001"), Some("pi-001"));
+        assert_eq!(device_from_topic("devices/+/vitals", "devices/WALKER-7/events"), None);
+        assert_eq!(device_from_topic("devices/+/vitals", "devices/WALKER-7/vitals/extra"), None);
+        assert_eq!(device_from_topic("devices/+/vitals", "devices//vitals"), None);
+    }
+}
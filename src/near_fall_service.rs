@@ -0,0 +1,162 @@
+use crate::emergency_service::notify_care_team;
+use crate::models::{Alert, Device, DeviceEventIngest, MlAlert, NearFallEvent, NearFallWeek};
+use crate::notifier::Notifier;
+use crate::sse::{broadcast_alert, SseBroadcaster};
+use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Near-falls in the latest week needed before a rise counts
+pub const FALL_RISK_MIN_EVENTS: i64 = 3;
+/// Week-over-week growth factor that raises a fall-risk alert
+const FALL_RISK_GROWTH: f64 = 1.5;
+const MAX_SURFACE_LEN: usize = 64;
+
+/// Whether near-falls rose enough from the previous week to the latest one to alert
+pub fn is_rising(latest: i64, previous: i64) -> bool {
+    latest >= FALL_RISK_MIN_EVENTS && latest > previous && latest as f64 >= previous as f64 * FALL_RISK_GROWTH
+}
+
+/// Speed and surface from the event details; malformed values are dropped rather than rejected
+fn context(details: Option<&serde_json::Value>) -> (Option<f32>, Option<String>) {
+    let speed = details
+        .and_then(|d| d.get("speed"))
+        .and_then(serde_json::Value::as_f64)
+        .filter(|s| s.is_finite() && *s >= 0.0)
+        .map(|s| s as f32);
+    let surface = details
+        .and_then(|d| d.get("surface"))
+        .and_then(serde_json::Value::as_str)
+        .map(|s| s.trim().chars().take(MAX_SURFACE_LEN).collect::<String>())
+        .filter(|s| !s.is_empty());
+    (speed, surface)
+}
+
+/// Near-fall counts for `weeks` rolling 7-day windows ending at `now`, oldest first
+pub async fn weekly_counts(
+    pool: &PgPool,
+    patient_id: Uuid,
+    now: DateTime<Utc>,
+    weeks: i32,
+) -> Result<Vec<NearFallWeek>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT $2 - (w + 1) * interval '7 days' AS week_start, COUNT(e.id) AS events
+         FROM generate_series(0, $3 - 1) w
+         LEFT JOIN near_fall_events e ON e.patient_id = $1
+              AND e.occurred_at >= $2 - (w + 1) * interval '7 days'
+              AND e.occurred_at < $2 - w * interval '7 days'
+         GROUP BY w
+         ORDER BY w DESC"
+    )
+    .bind(patient_id)
+    .bind(now)
+    .bind(weeks)
+    .fetch_all(pool)
+    .await
+}
+
+/// Store a near-fall or stumble and raise a fall-risk alert if the patient's weekly
+/// near-fall count has risen sharply (at most one such alert per week)
+pub async fn record_near_fall(
+    pool: &PgPool,
+    notifier: &Notifier,
+    broadcaster: &SseBroadcaster,
+    device: &Device,
+    event: &DeviceEventIngest,
+) -> Result<(NearFallEvent, Option<Alert>)> {
+    let occurred_at = Utc.timestamp_opt(event.timestamp, 0).single().unwrap_or_else(Utc::now);
+    let (speed, surface) = context(event.details.as_ref());
+
+    let stored: NearFallEvent = sqlx::query_as(
+        "INSERT INTO near_fall_events (device_id, patient_id, event_type, occurred_at, speed, surface, details)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)
+         RETURNING *"
+    )
+    .bind(device.id)
+    .bind(device.patient_id)
+    .bind(&event.event_type)
+    .bind(occurred_at)
+    .bind(speed)
+    .bind(&surface)
+    .bind(event.details.clone().unwrap_or_else(|| serde_json::json!({})))
+    .fetch_one(pool)
+    .await?;
+
+    let Some(patient_id) = device.patient_id else {
+        return Ok((stored, None));
+    };
+    let weeks = weekly_counts(pool, patient_id, Utc::now(), 2).await?;
+    let (previous, latest) = (weeks[0].events, weeks[1].events);
+    if !is_rising(latest, previous) {
+        return Ok((stored, None));
+    }
+
+    let message = format!(
+        "Near-falls rising: {} in the last 7 days, up from {} the week before",
+        latest, previous
+    );
+    let alert: Option<Alert> = sqlx::query_as(
+        "INSERT INTO alerts (patient_id, device_id, kind, level, message, details)
+         SELECT $1, $2, 'fall_risk', 'high'::alert_level, $3, $4
+         WHERE NOT EXISTS (SELECT 1 FROM alerts
+                           WHERE patient_id = $1 AND kind = 'fall_risk' AND raised_at > now() - interval '7 days')
+         RETURNING *"
+    )
+    .bind(patient_id)
+    .bind(device.id)
+    .bind(&message)
+    .bind(serde_json::json!({"latest_week": latest, "previous_week": previous, "event_id": stored.id}))
+    .fetch_optional(pool)
+    .await?;
+    let Some(alert) = alert else {
+        return Ok((stored, None));
+    };
+
+    broadcast_alert(broadcaster, Some(patient_id), MlAlert {
+        level: alert.level,
+        message: message.clone(),
+        details: serde_json::json!({
+            "alert_id": alert.id,
+            "kind": alert.kind,
+            "patient_id": patient_id,
+            "device_id": device.device_id,
+        }),
+    });
+
+    let patient_name: String = sqlx::query_scalar("SELECT display_name FROM patients WHERE id = $1")
+        .bind(patient_id)
+        .fetch_one(pool)
+        .await?;
+    notify_care_team(pool, notifier, &alert, patient_id, &format!("Fall risk: {}", patient_name)).await?;
+
+    Ok((stored, Some(alert)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rising_needs_volume_and_growth() {
+        assert!(is_rising(3, 0));
+        assert!(is_rising(3, 2));
+        assert!(is_rising(6, 4));
+        assert!(!is_rising(2, 0));
+        assert!(!is_rising(5, 4));
+        assert!(!is_rising(4, 4));
+    }
+
+    #[test]
+    fn test_context_is_lenient() {
+        let details = serde_json::json!({"speed": 0.8, "surface": "  carpet ", "tilt": 14});
+        assert_eq!(context(Some(&details)), (Some(0.8), Some("carpet".to_string())));
+
+        let bad = serde_json::json!({"speed": -1, "surface": 7});
+        assert_eq!(context(Some(&bad)), (None, None));
+        assert_eq!(context(None), (None, None));
+
+        let long = serde_json::json!({"surface": "x".repeat(200)});
+        assert_eq!(context(Some(&long)).1.unwrap().len(), MAX_SURFACE_LEN);
+    }
+}
@@ -0,0 +1,199 @@
+use actix_web::{
+    error::{InternalError, JsonPayloadError},
+    http::header::{self, Header},
+    mime, web, HttpMessage, HttpRequest, HttpResponse,
+};
+use serde::de::DeserializeOwned;
+
+pub const FHIR_JSON: &str = "application/fhir+json";
+pub const NDJSON: &str = "application/x-ndjson";
+pub const CSV: &str = "text/csv";
+pub const PDF: &str = "application/pdf";
+
+// ============ Request Content-Type ============
+
+/// JSON extractor config for regular API routes (`application/json` only)
+pub fn json_config() -> web::JsonConfig {
+    web::JsonConfig::default()
+        .content_type_required(true)
+        .content_type(|mime| is_json(&mime))
+        .error_handler(|err, _req| json_error(err, &["application/json"]))
+}
+
+/// JSON extractor config for FHIR routes (`application/fhir+json` or `application/json`)
+pub fn fhir_json_config() -> web::JsonConfig {
+    web::JsonConfig::default()
+        .content_type_required(true)
+        .content_type(|mime| is_json(&mime) || is_fhir_json(&mime))
+        .error_handler(|err, _req| json_error(err, &[FHIR_JSON, "application/json"]))
+}
+
+/// Parse a JSON body read as bytes, for handlers that also need it verbatim (signature
+/// checks); rejects it as [`json_config`] would
+pub fn json_from_bytes<T: DeserializeOwned>(req: &HttpRequest, body: &[u8]) -> Result<T, actix_web::Error> {
+    if !req.mime_type().ok().flatten().is_some_and(|mime| is_json(&mime)) {
+        return Err(json_error(JsonPayloadError::ContentType, &["application/json"]));
+    }
+    serde_json::from_slice(body).map_err(|e| json_error(JsonPayloadError::Deserialize(e), &["application/json"]))
+}
+
+fn is_json(mime: &mime::Mime) -> bool {
+    mime.type_() == mime::APPLICATION && mime.subtype() == mime::JSON && mime.suffix().is_none()
+}
+
+fn is_fhir_json(mime: &mime::Mime) -> bool {
+    mime.type_() == mime::APPLICATION && mime.subtype() == "fhir" && mime.suffix() == Some(mime::JSON)
+}
+
+/// Convert body extraction failures into JSON error responses (415 for wrong Content-Type)
+fn json_error(err: JsonPayloadError, expected: &[&str]) -> actix_web::Error {
+    let response = match &err {
+        JsonPayloadError::ContentType => HttpResponse::UnsupportedMediaType().json(serde_json::json!({
+            "error": "Unsupported Content-Type",
+            "expected": expected,
+        })),
+        _ => HttpResponse::BadRequest().json(serde_json::json!({"error": err.to_string()})),
+    };
+
+    InternalError::from_response(err, response).into()
+}
+
+// ============ Response Format Negotiation ============
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseFormat {
+    Json,
+    FhirJson,
+    Ndjson,
+    Csv,
+    Pdf,
+}
+
+impl ResponseFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ResponseFormat::Json => "application/json",
+            ResponseFormat::FhirJson => FHIR_JSON,
+            ResponseFormat::Ndjson => NDJSON,
+            ResponseFormat::Csv => CSV,
+            ResponseFormat::Pdf => PDF,
+        }
+    }
+
+    fn matches(&self, mime: &mime::Mime) -> bool {
+        let essence = self.content_type();
+        let type_ = essence.split('/').next().unwrap_or(essence);
+
+        if mime.type_() == mime::STAR {
+            return true;
+        }
+        if mime.type_().as_str() != type_ {
+            return false;
+        }
+        mime.subtype() == mime::STAR || mime.essence_str() == essence
+    }
+}
+
+/// Pick the response format for a request out of `offered` (server preference order).
+///
+/// A missing or unparsable Accept header selects the first offered format; an Accept
+/// header that matches none of them yields a 406 response listing what is available.
+pub fn negotiate(req: &HttpRequest, offered: &[ResponseFormat]) -> Result<ResponseFormat, HttpResponse> {
+    let default = offered.first().copied().unwrap_or(ResponseFormat::Json);
+
+    if req.headers().get(header::ACCEPT).is_none() {
+        return Ok(default);
+    }
+
+    let accept = match header::Accept::parse(req) {
+        Ok(accept) => accept,
+        Err(_) => return Ok(default),
+    };
+
+    for mime in accept.ranked() {
+        if let Some(format) = offered.iter().find(|f| f.matches(&mime)) {
+            return Ok(*format);
+        }
+    }
+
+    Err(HttpResponse::NotAcceptable().json(serde_json::json!({
+        "error": "Not Acceptable",
+        "available": offered.iter().map(|f| f.content_type()).collect::<Vec<_>>(),
+    })))
+}
+
+// ============ Prefer ============
+
+pub const PREFERENCE_APPLIED: &str = "preference-applied";
+pub const RETURN_REPRESENTATION: &str = "return=representation";
+
+/// Whether the client asked for the resulting resource in the response
+/// (`Prefer: return=representation`, RFC 7240)
+pub fn prefers_representation(req: &HttpRequest) -> bool {
+    req.headers()
+        .get_all("prefer")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|preference| preference.split(';').next())
+        .any(|preference| preference.trim().eq_ignore_ascii_case(RETURN_REPRESENTATION))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    const FHIR_FORMATS: &[ResponseFormat] = &[
+        ResponseFormat::FhirJson,
+        ResponseFormat::Json,
+        ResponseFormat::Ndjson,
+    ];
+
+    #[test]
+    fn test_missing_accept_uses_default() {
+        let req = TestRequest::default().to_http_request();
+        assert_eq!(negotiate(&req, FHIR_FORMATS).unwrap(), ResponseFormat::FhirJson);
+    }
+
+    #[test]
+    fn test_wildcard_uses_default() {
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, "*/*"))
+            .to_http_request();
+        assert_eq!(negotiate(&req, FHIR_FORMATS).unwrap(), ResponseFormat::FhirJson);
+    }
+
+    #[test]
+    fn test_quality_ranking() {
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, "application/json;q=0.5, application/x-ndjson"))
+            .to_http_request();
+        assert_eq!(negotiate(&req, FHIR_FORMATS).unwrap(), ResponseFormat::Ndjson);
+    }
+
+    #[test]
+    fn test_unsupported_accept_is_406() {
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, "text/csv"))
+            .to_http_request();
+        let resp = negotiate(&req, FHIR_FORMATS).unwrap_err();
+        assert_eq!(resp.status(), 406);
+    }
+
+    #[test]
+    fn test_prefer_return_representation() {
+        let prefers = |value: &str| prefers_representation(&TestRequest::default().insert_header(("Prefer", value)).to_http_request());
+        assert!(prefers("return=representation"));
+        assert!(prefers("respond-async, return=representation; charset=utf-8"));
+        assert!(!prefers("return=minimal"));
+        assert!(!prefers_representation(&TestRequest::default().to_http_request()));
+    }
+
+    #[test]
+    fn test_content_type_predicates() {
+        assert!(is_json(&mime::APPLICATION_JSON));
+        assert!(!is_json(&"application/fhir+json".parse().unwrap()));
+        assert!(is_fhir_json(&"application/fhir+json".parse().unwrap()));
+        assert!(!is_fhir_json(&mime::TEXT_PLAIN));
+    }
+}
@@ -0,0 +1,160 @@
+//! Unit normalization of walker readings on ingestion.
+//!
+//! Not every firmware reports in the stored units: some walkers send temperatures in °F,
+//! others whole-number vitals as floats (`"heartRate": 72.4`). A reading arrives as
+//! [`RawVitals`] and is converted with its device's [`DeviceUnits`] to °C and whole
+//! numbers, then range-checked as a [`DeviceVitalsIngest`]. Every value changed on the way
+//! is kept as sent under the reading's `metadata.raw`, along with the units it was read in.
+
+use crate::errors::ApiError;
+use crate::models::{DeviceUnits, DeviceVitalsIngest, RawVitals, TemperatureUnit};
+use serde_json::{Map, Value};
+use validator::Validate;
+
+enum Kind {
+    /// Stored as an integer; floats are rounded
+    Whole,
+    /// Stored in °C; converted from the device's temperature unit
+    Temperature,
+}
+
+/// Fields normalization may change, by every name they are accepted under
+const FIELDS: &[(&[&str], Kind)] = &[
+    (&["heartRate", "heart_rate"], Kind::Whole),
+    (&["spo2"], Kind::Whole),
+    (&["steps"], Kind::Whole),
+    (&["temperature"], Kind::Temperature),
+    (&["ambientTemperature", "ambient_temperature"], Kind::Temperature),
+];
+
+/// A reading in the stored units
+#[derive(Debug)]
+pub struct NormalizedReading {
+    pub vitals: DeviceVitalsIngest,
+    /// The values normalization changed, as sent and under the names they were sent with,
+    /// plus the device's `units`; `None` when the reading needed no conversion
+    pub raw: Option<Map<String, Value>>,
+}
+
+impl NormalizedReading {
+    /// The reading as the walker sent it
+    pub fn as_sent(&self) -> Map<String, Value> {
+        let mut reading = match serde_json::to_value(&self.vitals) {
+            Ok(Value::Object(reading)) => reading,
+            _ => Map::new(),
+        };
+        if let Some(raw) = &self.raw {
+            restore_raw(&mut reading, raw);
+        }
+        reading
+    }
+}
+
+fn celsius(fahrenheit: f64) -> f64 {
+    ((fahrenheit - 32.0) * 5.0 / 9.0 * 100.0).round() / 100.0
+}
+
+/// Convert `reading` from `units` to the stored units and validate it
+pub fn normalize_reading(reading: RawVitals, units: &DeviceUnits) -> Result<NormalizedReading, ApiError> {
+    let RawVitals(mut reading) = reading;
+    let mut raw = Map::new();
+
+    for (names, kind) in FIELDS {
+        for name in *names {
+            let Some(value) = reading.get_mut(*name) else { continue };
+            let Some(sent) = value.as_f64() else { continue };
+            let normalized = match kind {
+                Kind::Whole if value.is_f64() => Value::from(sent.round() as i64),
+                Kind::Temperature if units.temperature == TemperatureUnit::Fahrenheit => Value::from(celsius(sent)),
+                _ => continue,
+            };
+            raw.insert(name.to_string(), std::mem::replace(value, normalized));
+        }
+    }
+
+    let vitals: DeviceVitalsIngest =
+        serde_json::from_value(Value::Object(reading)).map_err(|e| ApiError::BadRequest(format!("Invalid reading: {}", e)))?;
+    vitals.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    let raw = (!raw.is_empty()).then(|| {
+        raw.insert("units".into(), serde_json::to_value(units).unwrap_or_default());
+        raw
+    });
+    Ok(NormalizedReading { vitals, raw })
+}
+
+/// Put the values recorded under `metadata.raw` back into a stored `reading`, making it the
+/// payload the walker originally sent
+pub fn restore_raw(reading: &mut Map<String, Value>, raw: &Map<String, Value>) {
+    for (name, value) in raw {
+        let Some((names, _)) = FIELDS.iter().find(|(names, _)| names.contains(&name.as_str())) else { continue };
+        for other in *names {
+            reading.remove(*other);
+        }
+        reading.insert(name.clone(), value.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn raw_vitals(value: Value) -> RawVitals {
+        serde_json::from_value(value).unwrap()
+    }
+
+    const FAHRENHEIT: DeviceUnits = DeviceUnits { temperature: TemperatureUnit::Fahrenheit };
+
+    #[test]
+    fn test_stored_units_pass_through() {
+        let reading = raw_vitals(json!({"heartRate": 72, "spo2": 97, "temperature": 36.8, "timestamp": 1700000000}));
+        let normalized = normalize_reading(reading, &DeviceUnits::default()).unwrap();
+        assert_eq!(normalized.vitals.heart_rate, 72);
+        assert_eq!(normalized.vitals.temperature, 36.8);
+        assert!(normalized.raw.is_none());
+    }
+
+    #[test]
+    fn test_fahrenheit_converted_and_raw_kept() {
+        let reading = raw_vitals(json!({
+            "heartRate": 72, "spo2": 97, "temperature": 98.6, "ambientTemperature": 68.0, "timestamp": 1700000000
+        }));
+        let normalized = normalize_reading(reading, &FAHRENHEIT).unwrap();
+        assert_eq!(normalized.vitals.temperature, 37.0);
+        assert_eq!(normalized.vitals.ambient_temperature, Some(20.0));
+        assert_eq!(
+            Value::Object(normalized.raw.unwrap()),
+            json!({"temperature": 98.6, "ambientTemperature": 68.0, "units": {"temperature": "fahrenheit"}})
+        );
+    }
+
+    #[test]
+    fn test_float_vitals_rounded() {
+        let reading = raw_vitals(json!({"heart_rate": 72.6, "spo2": 96.4, "temperature": 36.8, "steps": 12.0, "timestamp": 1700000000}));
+        let normalized = normalize_reading(reading, &DeviceUnits::default()).unwrap();
+        assert_eq!((normalized.vitals.heart_rate, normalized.vitals.spo2, normalized.vitals.steps), (73, 96, Some(12)));
+        let raw = normalized.raw.unwrap();
+        assert_eq!(raw["heart_rate"], json!(72.6));
+        assert_eq!(raw["spo2"], json!(96.4));
+        assert_eq!(raw["steps"], json!(12.0));
+    }
+
+    #[test]
+    fn test_range_checked_after_conversion() {
+        // 36.8 read as °F is far below any body temperature
+        let reading = raw_vitals(json!({"heartRate": 72, "spo2": 97, "temperature": 36.8, "timestamp": 1700000000}));
+        assert!(matches!(normalize_reading(reading, &FAHRENHEIT), Err(ApiError::BadRequest(_))));
+
+        let reading = raw_vitals(json!({"heartRate": 300.6, "spo2": 97, "temperature": 36.8, "timestamp": 1700000000}));
+        assert!(matches!(normalize_reading(reading, &DeviceUnits::default()), Err(ApiError::BadRequest(_))));
+    }
+
+    #[test]
+    fn test_restore_raw_gives_payload_as_sent() {
+        let sent = json!({"heart_rate": 72.4, "spo2": 97, "temperature": 98.6, "timestamp": 1700000000});
+        let normalized = normalize_reading(raw_vitals(sent.clone()), &FAHRENHEIT).unwrap();
+
+        assert_eq!(Value::Object(normalized.as_sent()), sent);
+    }
+}
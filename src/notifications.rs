@@ -0,0 +1,411 @@
+//! Vitals alerts delivered to users' own channels: SMTP email, Twilio-style SMS and
+//! generic webhooks.
+//!
+//! When a vitals alert is raised at one of `notifications.levels`, [`queue_alert`] writes a
+//! `notification_deliveries` row for every channel each recipient enabled in their
+//! `notification_preferences` (and the server has configured). The [`Dispatcher`] worker
+//! sends due rows; a failed attempt is retried after [`backoff`] until `max_attempts` is
+//! reached, then the delivery is marked `failed`.
+//!
+//! Webhook posts carry the delivery as JSON with `X-Webhook-Id` (the delivery id, the same
+//! on every retry), `X-Webhook-Timestamp` and, when the user set a secret,
+//! `X-Webhook-Signature` computed as [`webhooks::signature`] with sequence `0`: user
+//! webhooks are not sequenced.
+
+use crate::config::NotificationsConfig;
+use crate::models::{AlertLevel, MlAlert, NotificationDelivery, NotificationPreferences};
+use crate::webhooks;
+use anyhow::{anyhow, bail, Result};
+use chrono::Utc;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use sqlx::PgPool;
+use std::time::Duration;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+pub const EMAIL: &str = "email";
+pub const SMS: &str = "sms";
+pub const WEBHOOK: &str = "webhook";
+
+/// How often the dispatcher looks for due deliveries
+const DISPATCH_INTERVAL: Duration = Duration::from_secs(15);
+/// Deliveries sent per dispatch cycle
+const DISPATCH_BATCH: i64 = 100;
+/// A claimed delivery is left alone this long, so a crash mid-send cannot lose it
+const CLAIM_LEASE_SECONDS: f64 = 300.0;
+/// No retry waits longer than this
+const MAX_BACKOFF: Duration = Duration::from_secs(3600);
+/// Delivered and failed deliveries are kept this long
+const DELIVERY_RETENTION_DAYS: i32 = 30;
+
+/// Wait before retrying a delivery that has failed `attempts` times: the base delay,
+/// doubled for each earlier failure, capped at an hour
+pub fn backoff(attempts: i32, base_seconds: u64) -> Duration {
+    let doublings = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    Duration::from_secs(base_seconds.saturating_mul(1 << doublings)).min(MAX_BACKOFF)
+}
+
+/// The `(channel, target)` pairs `preferences` asks for at `level`. Channels without an
+/// address and those the server has not configured are skipped.
+pub fn channels_for(
+    preferences: &NotificationPreferences,
+    account_email: &str,
+    level: AlertLevel,
+    config: &NotificationsConfig,
+) -> Vec<(&'static str, String)> {
+    if level < preferences.min_level {
+        return Vec::new();
+    }
+    let email = preferences.email.as_deref().unwrap_or(account_email);
+    [
+        (EMAIL, preferences.email_enabled && config.email_enabled(), Some(email).filter(|e| !e.is_empty())),
+        (SMS, preferences.sms_enabled && config.sms_enabled(), preferences.phone.as_deref()),
+        (WEBHOOK, preferences.webhook_enabled, preferences.webhook_url.as_deref()),
+    ]
+    .into_iter()
+    .filter_map(|(channel, enabled, target)| Some((channel, target.filter(|_| enabled)?.to_string())))
+    .collect()
+}
+
+/// Queue `alert` for each of `users`' channels; returns the number of deliveries queued.
+/// Nothing is queued for levels outside `notifications.levels`. Users who never set
+/// preferences have no channels, unless `email_by_default` mails them at their account's
+/// address (consumer deployments, see `deployment.consumer_notifications`).
+pub async fn queue_alert(
+    pool: &PgPool,
+    config: &NotificationsConfig,
+    users: &[Uuid],
+    patient_id: Uuid,
+    alert_id: Option<Uuid>,
+    alert: &MlAlert,
+    email_by_default: bool,
+) -> Result<usize> {
+    if !config.levels.iter().any(|level| level == alert.level.as_str()) || users.is_empty() {
+        return Ok(0);
+    }
+
+    let recipients: Vec<(Uuid, String, bool)> = sqlx::query_as(
+        "SELECT u.id, u.email, p.user_id IS NOT NULL FROM users u
+         LEFT JOIN notification_preferences p ON p.user_id = u.id
+         WHERE u.id = ANY($1) AND u.is_active AND ($2 OR p.user_id IS NOT NULL)"
+    )
+    .bind(users)
+    .bind(email_by_default)
+    .fetch_all(pool)
+    .await?;
+
+    let subject = format!("[{}] Walker alert", alert.level.as_str().to_uppercase());
+    let mut queued = 0;
+    for (user_id, account_email, has_preferences) in recipients {
+        let mut preferences = load_preferences(pool, user_id).await?;
+        preferences.email_enabled |= !has_preferences;
+        for (channel, target) in channels_for(&preferences, &account_email, alert.level, config) {
+            sqlx::query(
+                "INSERT INTO notification_deliveries (user_id, patient_id, alert_id, channel, target, level, subject, body)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"
+            )
+            .bind(user_id)
+            .bind(patient_id)
+            .bind(alert_id)
+            .bind(channel)
+            .bind(&target)
+            .bind(alert.level)
+            .bind(&subject)
+            .bind(&alert.message)
+            .execute(pool)
+            .await?;
+            queued += 1;
+        }
+    }
+
+    if queued > 0 {
+        info!(patient_id = %patient_id, level = %alert.level, queued, "Alert queued for notification channels");
+    }
+    Ok(queued)
+}
+
+/// A user's channel preferences; the defaults when they never set any
+pub async fn load_preferences(pool: &PgPool, user_id: Uuid) -> Result<NotificationPreferences, sqlx::Error> {
+    let preferences: Option<NotificationPreferences> = sqlx::query_as(
+        "SELECT email_enabled, email, sms_enabled, phone, webhook_enabled, webhook_url, webhook_secret, min_level
+         FROM notification_preferences WHERE user_id = $1"
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(preferences
+        .map(|p| NotificationPreferences { webhook_signed: p.webhook_secret.is_some(), ..p })
+        .unwrap_or_default())
+}
+
+/// Sends queued deliveries over SMTP, the SMS API and plain HTTP
+pub struct Dispatcher {
+    pool: PgPool,
+    http: reqwest::Client,
+    config: NotificationsConfig,
+    mailer: Option<(AsyncSmtpTransport<Tokio1Executor>, Mailbox)>,
+}
+
+impl Dispatcher {
+    pub fn new(pool: PgPool, config: NotificationsConfig) -> Result<Self> {
+        let timeout = Duration::from_secs(config.timeout_seconds);
+        let mailer = match (&config.smtp_host, &config.smtp_from) {
+            (Some(host), Some(from)) => {
+                let mut builder = if config.smtp_starttls {
+                    AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?
+                } else {
+                    AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+                };
+                builder = builder.port(config.smtp_port).timeout(Some(timeout));
+                if let (Some(username), Some(password)) = (&config.smtp_username, &config.smtp_password) {
+                    builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+                }
+                Some((builder.build(), from.parse()?))
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            pool,
+            http: reqwest::Client::builder().timeout(timeout).build().unwrap_or_default(),
+            config,
+            mailer,
+        })
+    }
+
+    /// Send every due delivery once; returns how many were delivered
+    pub async fn run_cycle(&self) -> Result<usize> {
+        sqlx::query(
+            "DELETE FROM notification_deliveries
+             WHERE status <> 'pending' AND created_at < now() - make_interval(days => $1)"
+        )
+        .bind(DELIVERY_RETENTION_DAYS)
+        .execute(&self.pool)
+        .await?;
+
+        let due: Vec<NotificationDelivery> = sqlx::query_as(
+            "UPDATE notification_deliveries
+             SET attempts = attempts + 1, next_attempt_at = now() + make_interval(secs => $1)
+             WHERE id IN (
+                 SELECT id FROM notification_deliveries
+                 WHERE status = 'pending' AND next_attempt_at <= now()
+                 ORDER BY next_attempt_at
+                 LIMIT $2
+                 FOR UPDATE SKIP LOCKED
+             )
+             RETURNING *"
+        )
+        .bind(CLAIM_LEASE_SECONDS)
+        .bind(DISPATCH_BATCH)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut delivered = 0;
+        for delivery in &due {
+            match self.send(delivery).await {
+                Ok(()) => {
+                    sqlx::query(
+                        "UPDATE notification_deliveries
+                         SET status = 'delivered', delivered_at = now(), last_error = NULL
+                         WHERE id = $1"
+                    )
+                    .bind(delivery.id)
+                    .execute(&self.pool)
+                    .await?;
+                    delivered += 1;
+                }
+                Err(e) => {
+                    let retry = backoff(delivery.attempts, self.config.retry_base_seconds);
+                    let gave_up = delivery.attempts >= self.config.max_attempts;
+                    warn!(
+                        delivery_id = %delivery.id,
+                        channel = %delivery.channel,
+                        attempts = delivery.attempts,
+                        gave_up,
+                        "Notification delivery failed: {:#}", e
+                    );
+                    sqlx::query(
+                        "UPDATE notification_deliveries
+                         SET status = CASE WHEN $3 THEN 'failed' ELSE 'pending' END, last_error = $2,
+                             next_attempt_at = now() + make_interval(secs => $4)
+                         WHERE id = $1"
+                    )
+                    .bind(delivery.id)
+                    .bind(format!("{:#}", e))
+                    .bind(gave_up)
+                    .bind(retry.as_secs_f64())
+                    .execute(&self.pool)
+                    .await?;
+                }
+            }
+        }
+        Ok(delivered)
+    }
+
+    /// One attempt at a delivery over its channel
+    pub async fn send(&self, delivery: &NotificationDelivery) -> Result<()> {
+        match delivery.channel.as_str() {
+            EMAIL => self.send_email(delivery).await,
+            SMS => self.send_sms(delivery).await,
+            WEBHOOK => self.send_webhook(delivery).await,
+            other => bail!("Unknown notification channel '{}'", other),
+        }
+    }
+
+    async fn send_email(&self, delivery: &NotificationDelivery) -> Result<()> {
+        let Some((mailer, from)) = &self.mailer else {
+            bail!("Email is not configured");
+        };
+        let message = Message::builder()
+            .from(from.clone())
+            .to(delivery.target.parse()?)
+            .subject(&delivery.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(delivery.body.clone())?;
+        mailer.send(message).await?;
+        Ok(())
+    }
+
+    /// Twilio's Messages API, or anything speaking the same form post
+    async fn send_sms(&self, delivery: &NotificationDelivery) -> Result<()> {
+        let (Some(sid), Some(token), Some(from)) = (
+            &self.config.sms_account_sid,
+            &self.config.sms_auth_token,
+            &self.config.sms_from_number,
+        ) else {
+            bail!("SMS is not configured");
+        };
+        let url = format!(
+            "{}/2010-04-01/Accounts/{}/Messages.json",
+            self.config.sms_api_base_url.trim_end_matches('/'),
+            sid
+        );
+        let text = format!("{}: {}", delivery.subject, delivery.body);
+
+        let response = self
+            .http
+            .post(&url)
+            .basic_auth(sid, Some(token))
+            .form(&[("To", delivery.target.as_str()), ("From", from.as_str()), ("Body", text.as_str())])
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            bail!("SMS API returned {}", response.status());
+        }
+        Ok(())
+    }
+
+    async fn send_webhook(&self, delivery: &NotificationDelivery) -> Result<()> {
+        let secret: Option<String> = sqlx::query_scalar(
+            "SELECT webhook_secret FROM notification_preferences WHERE user_id = $1"
+        )
+        .bind(delivery.user_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .flatten();
+
+        let body = serde_json::to_string(&serde_json::json!({
+            "id": delivery.id,
+            "alert_id": delivery.alert_id,
+            "patient_id": delivery.patient_id,
+            "level": delivery.level,
+            "subject": delivery.subject,
+            "message": delivery.body,
+            "created_at": delivery.created_at,
+        }))?;
+        let timestamp = Utc::now().timestamp();
+        let mut request = self
+            .http
+            .post(&delivery.target)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(webhooks::ID_HEADER, delivery.id.to_string())
+            .header(webhooks::TIMESTAMP_HEADER, timestamp.to_string());
+        if let Some(secret) = &secret {
+            request = request.header(webhooks::SIGNATURE_HEADER, webhooks::signature(secret, timestamp, 0, &body));
+        }
+
+        let response = request.body(body).send().await.map_err(|e| anyhow!(e))?;
+        if !response.status().is_success() {
+            bail!("Webhook returned {}", response.status());
+        }
+        Ok(())
+    }
+}
+
+/// Background worker sending due deliveries every [`DISPATCH_INTERVAL`]
+pub fn spawn_dispatcher(pool: PgPool, config: NotificationsConfig) -> Option<tokio::task::JoinHandle<()>> {
+    let dispatcher = match Dispatcher::new(pool, config) {
+        Ok(dispatcher) => dispatcher,
+        Err(e) => {
+            error!("Notification channels disabled: {:#}", e);
+            return None;
+        }
+    };
+
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(DISPATCH_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            match dispatcher.run_cycle().await {
+                Ok(0) => {}
+                Ok(delivered) => info!("Delivered {} channel notification(s)", delivered),
+                Err(e) => error!("Notification dispatch cycle failed: {}", e),
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_an_hour() {
+        assert_eq!(backoff(1, 30), Duration::from_secs(30));
+        assert_eq!(backoff(2, 30), Duration::from_secs(60));
+        assert_eq!(backoff(4, 30), Duration::from_secs(240));
+        assert_eq!(backoff(10, 30), MAX_BACKOFF);
+        assert_eq!(backoff(i32::MAX, u64::MAX), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn test_channels_follow_preferences_level_and_server_config() {
+        let config = NotificationsConfig {
+            smtp_host: Some("smtp.example.com".to_string()),
+            ..Default::default()
+        };
+        let preferences = NotificationPreferences {
+            email_enabled: true,
+            sms_enabled: true,
+            phone: Some("+15551234567".to_string()),
+            webhook_enabled: true,
+            webhook_url: None,
+            ..Default::default()
+        };
+
+        // SMS is not configured on the server and the webhook has no URL
+        assert_eq!(
+            channels_for(&preferences, "nurse@example.com", AlertLevel::Critical, &config),
+            vec![(EMAIL, "nurse@example.com".to_string())]
+        );
+        assert!(channels_for(&preferences, "nurse@example.com", AlertLevel::Medium, &config).is_empty());
+
+        let preferences = NotificationPreferences {
+            email: Some("pager@example.com".to_string()),
+            webhook_url: Some("https://hooks.example.com/walker".to_string()),
+            min_level: AlertLevel::Medium,
+            ..preferences
+        };
+        assert_eq!(
+            channels_for(&preferences, "nurse@example.com", AlertLevel::Medium, &config),
+            vec![
+                (EMAIL, "pager@example.com".to_string()),
+                (WEBHOOK, "https://hooks.example.com/walker".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,288 @@
+use crate::alert_routing::{on_call_for_patient, route, Route};
+use crate::config::{AlertRoutingConfig, EmergencyConfig, NotificationsConfig, VoiceConfig};
+use crate::models::{AlertLevel, EventEnvelope, MlAlert, WebhookDelivery, WebhookEvent};
+use crate::notifications;
+use crate::voice;
+use crate::webhooks::{self, Webhook};
+use anyhow::{bail, Result};
+use chrono::Utc;
+use sqlx::PgPool;
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Delivers user-facing notifications.
+///
+/// Every notification lands in the recipient's in-app inbox (`notifications` table),
+/// which the companion app polls via `GET /api/notifications`. People without an
+/// account (emergency contacts) are reached by SMS or phone call through the contact
+/// webhook, which fronts whatever gateway the deployment uses, and by Twilio voice
+/// calls when alerts escalate. Vitals alerts also go to users' own email, SMS and
+/// webhook channels through [`notifications`].
+pub struct Notifier {
+    pool: PgPool,
+    http: reqwest::Client,
+    contact_webhook: Option<Webhook>,
+    voice: Option<VoiceConfig>,
+    routing: Option<AlertRoutingConfig>,
+    channels: Option<NotificationsConfig>,
+    email_by_default: bool,
+}
+
+/// Who an alert notification reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertRecipients {
+    OnCall(Uuid),
+    Caregivers(usize),
+}
+
+enum Audience {
+    OnCall(Uuid),
+    Caregivers(Vec<Uuid>),
+}
+
+impl Notifier {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            http: reqwest::Client::new(),
+            contact_webhook: None,
+            voice: None,
+            routing: None,
+            channels: None,
+            email_by_default: false,
+        }
+    }
+
+    /// Enable SMS/call delivery to emergency contacts
+    pub fn with_contact_webhook(mut self, config: &EmergencyConfig) -> Self {
+        self.http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.webhook_timeout_seconds))
+            .build()
+            .unwrap_or_default();
+        self.contact_webhook = config.contact_webhook_url.as_ref().map(|url| Webhook {
+            id: webhooks::CONTACT,
+            url: url.clone(),
+            secret: config.contact_webhook_secret.clone(),
+        });
+        self
+    }
+
+    /// Enable Twilio voice calls (ignored unless `voice.enabled`)
+    pub fn with_voice(mut self, config: &VoiceConfig) -> Self {
+        self.voice = config.enabled.then(|| config.clone());
+        self
+    }
+
+    /// Route night-time alerts to the on-call clinician; without it caregivers get everything
+    pub fn with_routing(mut self, config: &AlertRoutingConfig) -> Self {
+        self.routing = Some(config.clone());
+        self
+    }
+
+    /// Queue vitals alerts for users' email, SMS and webhook channels
+    pub fn with_channels(mut self, config: &NotificationsConfig) -> Self {
+        self.channels = Some(config.clone());
+        self
+    }
+
+    /// Mail vitals alerts to users who never set notification preferences, as consumer
+    /// (home) deployments do for family caregivers
+    pub fn with_consumer_notifications(mut self, enabled: bool) -> Self {
+        self.email_by_default = enabled;
+        self
+    }
+
+    pub fn voice_enabled(&self) -> bool {
+        self.voice.is_some()
+    }
+
+    pub fn routing(&self) -> Option<&AlertRoutingConfig> {
+        self.routing.as_ref()
+    }
+
+    /// The configured outgoing webhook called `id`
+    pub fn webhook(&self, id: &str) -> Option<&Webhook> {
+        self.contact_webhook.as_ref().filter(|webhook| webhook.id == id)
+    }
+
+    /// Post a recorded webhook event again (see [`webhooks::redeliver`])
+    pub async fn redeliver(&self, webhook: &Webhook, event_id: Uuid) -> Result<Option<WebhookDelivery>, sqlx::Error> {
+        webhooks::redeliver(&self.pool, &self.http, webhook, event_id).await
+    }
+
+    /// Notify a single user; returns the notification id
+    pub async fn notify_user(
+        &self,
+        user_id: Uuid,
+        patient_id: Option<Uuid>,
+        kind: &str,
+        title: &str,
+        body: &str,
+    ) -> Result<Uuid> {
+        let id: Uuid = sqlx::query_scalar(
+            "INSERT INTO notifications (user_id, patient_id, kind, title, body) VALUES ($1, $2, $3, $4, $5) RETURNING id"
+        )
+        .bind(user_id)
+        .bind(patient_id)
+        .bind(kind)
+        .bind(title)
+        .bind(body)
+        .fetch_one(&self.pool)
+        .await?;
+
+        info!(notification_id = %id, user_id = %user_id, kind = kind, "Notification delivered");
+        Ok(id)
+    }
+
+    /// Notify everyone linked as a caregiver of a patient; returns the number of recipients
+    pub async fn notify_caregivers(&self, patient_id: Uuid, kind: &str, title: &str, body: &str) -> Result<usize> {
+        let users: Vec<Uuid> = sqlx::query_scalar("SELECT user_id FROM patient_caregivers WHERE patient_id = $1")
+            .bind(patient_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        for user_id in &users {
+            self.notify_user(*user_id, Some(patient_id), kind, title, body).await?;
+        }
+
+        Ok(users.len())
+    }
+
+    /// Notify every active admin about an operational issue; returns the number of recipients
+    pub async fn notify_admins(&self, kind: &str, title: &str, body: &str) -> Result<usize> {
+        let users: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM users WHERE role = 'admin' AND is_active")
+            .fetch_all(&self.pool)
+            .await?;
+
+        for user_id in &users {
+            self.notify_user(*user_id, None, kind, title, body).await?;
+        }
+
+        Ok(users.len())
+    }
+
+    /// Whoever should handle an alert right now: the on-call clinician for night-time alerts
+    /// at the routed levels, otherwise the patient's caregivers. Falls back to the caregivers
+    /// when nobody is on call.
+    async fn alert_audience(&self, patient_id: Uuid, level: AlertLevel) -> Result<Audience> {
+        let now = Utc::now();
+        if self.routing.as_ref().is_some_and(|config| route(config, level, now) == Route::OnCall) {
+            match on_call_for_patient(&self.pool, now, patient_id).await? {
+                Some(on_call) => return Ok(Audience::OnCall(on_call.user_id)),
+                None => warn!(patient_id = %patient_id, level = %level, "Nobody is on call; notifying caregivers"),
+            }
+        }
+
+        let caregivers = sqlx::query_scalar("SELECT user_id FROM patient_caregivers WHERE patient_id = $1")
+            .bind(patient_id)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(Audience::Caregivers(caregivers))
+    }
+
+    /// Notify whoever should handle an alert right now (see `alert_audience`)
+    pub async fn notify_alert(
+        &self,
+        patient_id: Uuid,
+        level: AlertLevel,
+        kind: &str,
+        title: &str,
+        body: &str,
+    ) -> Result<AlertRecipients> {
+        match self.alert_audience(patient_id, level).await? {
+            Audience::OnCall(user_id) => {
+                self.notify_user(user_id, Some(patient_id), kind, title, body).await?;
+                Ok(AlertRecipients::OnCall(user_id))
+            }
+            Audience::Caregivers(users) => {
+                for user_id in &users {
+                    self.notify_user(*user_id, Some(patient_id), kind, title, body).await?;
+                }
+                Ok(AlertRecipients::Caregivers(users.len()))
+            }
+        }
+    }
+
+    /// Queue a vitals alert for the channels of whoever should handle it; returns the number
+    /// of deliveries queued (none unless channels are enabled and the level is dispatched)
+    pub async fn notify_channels(&self, patient_id: Uuid, alert_id: Option<Uuid>, alert: &MlAlert) -> Result<usize> {
+        let Some(config) = &self.channels else {
+            return Ok(0);
+        };
+        if !config.levels.iter().any(|level| level == alert.level.as_str()) {
+            return Ok(0);
+        }
+
+        let users = match self.alert_audience(patient_id, alert.level).await? {
+            Audience::OnCall(user_id) => vec![user_id],
+            Audience::Caregivers(users) => users,
+        };
+        notifications::queue_alert(&self.pool, config, &users, patient_id, alert_id, alert, self.email_by_default).await
+    }
+
+    /// Ask the contact webhook to text (`channel = "sms"`) or call (`"call"`) a phone number
+    pub async fn notify_contact(&self, channel: &str, phone: &str, message: &str, alert_id: Uuid) -> Result<()> {
+        let Some(webhook) = &self.contact_webhook else {
+            bail!("No contact webhook configured");
+        };
+
+        let envelope = EventEnvelope::new(WebhookEvent::ContactRequested {
+            channel: channel.to_string(),
+            to: phone.to_string(),
+            message: message.to_string(),
+            alert_id,
+        });
+        webhooks::deliver(&self.pool, &self.http, webhook, &envelope).await?;
+
+        info!(alert_id = %alert_id, channel = channel, "Emergency contact notified");
+        Ok(())
+    }
+
+    /// Place a Twilio call reading `script` aloud; returns the call SID. Status callbacks
+    /// are posted to `/api/voice/status?alert_id=...` on the configured callback base URL.
+    pub async fn place_call(&self, to: &str, script: &str, alert_id: Uuid) -> Result<String> {
+        let Some(voice) = &self.voice else {
+            bail!("Voice calls are not enabled");
+        };
+        let (Some(sid), Some(token), Some(from), Some(callback_base)) = (
+            &voice.account_sid,
+            &voice.auth_token,
+            &voice.from_number,
+            &voice.callback_base_url,
+        ) else {
+            bail!("Voice calls are not fully configured");
+        };
+
+        let url = format!(
+            "{}/2010-04-01/Accounts/{}/Calls.json",
+            voice.api_base_url.trim_end_matches('/'),
+            sid
+        );
+        let callback = format!("{}/api/voice/status?alert_id={}", callback_base.trim_end_matches('/'), alert_id);
+        let twiml = voice::twiml(script, &voice.tts_voice);
+
+        let response = self
+            .http
+            .post(&url)
+            .basic_auth(sid, Some(token))
+            .form(&[
+                ("To", to),
+                ("From", from.as_str()),
+                ("Twiml", twiml.as_str()),
+                ("StatusCallback", callback.as_str()),
+                ("StatusCallbackEvent", "initiated ringing answered completed"),
+            ])
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            bail!("Twilio returned {}", response.status());
+        }
+
+        let body: serde_json::Value = response.json().await?;
+        let call_sid = body["sid"].as_str().unwrap_or_default().to_string();
+        info!(alert_id = %alert_id, call_sid = %call_sid, "Escalation call placed");
+        Ok(call_sid)
+    }
+}
@@ -0,0 +1,135 @@
+//! Optional Sentry error tracking.
+//!
+//! With `observability.sentry_dsn` set, Sentry receives handler errors (5xx responses,
+//! through the `sentry-actix` middleware), `error!` logs such as worker failures (through
+//! the tracing layer), panics (from the crash reporter's hook) and requests slower than
+//! `observability.slow_transaction_ms`. Without it every piece is a no-op.
+
+use crate::build_info;
+use crate::config::ObservabilityConfig;
+use crate::crash_reporting::PANIC_TARGET;
+use sentry::integrations::tracing::EventFilter;
+use sentry::protocol::{Event, Map, Value};
+use std::borrow::Cow;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tracing::{Level, Metadata};
+
+/// Request headers kept on events; the rest (bearer tokens, device signatures, cookies)
+/// never leave the process
+const FORWARDED_HEADERS: &[&str] = &["accept", "content-type", "content-length", "user-agent"];
+
+/// Set once Sentry is running; requests slower than this are reported
+static SLOW_REQUEST: OnceLock<Duration> = OnceLock::new();
+
+/// `medhealth-backend@<version>`, plus `+<git sha>` when the build knew it
+pub fn release() -> Cow<'static, str> {
+    let version = concat!(env!("CARGO_PKG_NAME"), "@", env!("CARGO_PKG_VERSION"));
+    match build_info::GIT_SHA {
+        Some(sha) if !sha.is_empty() => format!("{}+{}", version, sha).into(),
+        _ => version.into(),
+    }
+}
+
+/// Start the Sentry client; keep the guard alive for the life of the process so queued
+/// events are flushed on shutdown. `None` when no DSN is configured.
+pub fn init_sentry(config: &ObservabilityConfig) -> Option<sentry::ClientInitGuard> {
+    let dsn = config.sentry_dsn.as_deref()?.parse().ok()?;
+    let _ = SLOW_REQUEST.set(Duration::from_millis(config.slow_transaction_ms));
+    Some(sentry::init(sentry::ClientOptions {
+        dsn: Some(dsn),
+        release: Some(release()),
+        environment: config.environment.clone().map(Cow::Owned),
+        send_default_pii: false,
+        before_send: Some(Arc::new(|event| Some(scrub(event)))),
+        ..Default::default()
+    }))
+}
+
+/// Drop request data that could identify a caller or carry PHI
+fn scrub(mut event: Event<'static>) -> Event<'static> {
+    if let Some(request) = event.request.as_mut() {
+        request.headers.retain(|name, _| FORWARDED_HEADERS.contains(&name.to_ascii_lowercase().as_str()));
+        request.query_string = None;
+        request.cookies = None;
+        request.data = None;
+        if let Some(url) = request.url.as_mut() {
+            url.set_query(None);
+        }
+    }
+    event
+}
+
+/// Errors become events and warnings breadcrumbs; info and below stay local
+pub fn event_filter(metadata: &Metadata<'_>) -> EventFilter {
+    match *metadata.level() {
+        // The panic hook captures panics itself, with the stack trace
+        _ if metadata.target() == PANIC_TARGET => EventFilter::Ignore,
+        Level::ERROR => EventFilter::Event,
+        Level::WARN => EventFilter::Breadcrumb,
+        _ => EventFilter::Ignore,
+    }
+}
+
+/// Tracing layer forwarding logs per [`event_filter`]
+pub fn sentry_layer<S>() -> sentry::integrations::tracing::SentryLayer<S>
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    sentry::integrations::tracing::layer().event_filter(event_filter)
+}
+
+/// Report a request that took longer than `observability.slow_transaction_ms`. Events
+/// group by route, so a slow endpoint shows up as one issue with a count rather than one
+/// per request.
+pub fn report_slow_request(method: &str, route: &str, status: u16, elapsed: Duration) {
+    if SLOW_REQUEST.get().is_none_or(|threshold| elapsed < *threshold) {
+        return;
+    }
+    let mut extra = Map::new();
+    extra.insert("duration_ms".into(), Value::from(elapsed.as_millis() as u64));
+    extra.insert("status".into(), Value::from(status));
+    sentry::capture_event(Event {
+        level: sentry::Level::Warning,
+        message: Some(format!("Slow request: {} {}", method, route)),
+        fingerprint: vec!["slow-request".into(), method.to_string().into(), route.to_string().into()].into(),
+        transaction: Some(format!("{} {}", method, route)),
+        extra,
+        ..Default::default()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sentry::protocol::Request;
+
+    #[test]
+    fn test_release_names_package_version() {
+        assert!(release().starts_with(concat!("medhealth-backend@", env!("CARGO_PKG_VERSION"))));
+    }
+
+    #[test]
+    fn test_scrub_drops_credentials_and_query() {
+        let event = Event {
+            request: Some(Request {
+                url: "https://api.example.com/api/patients/1/vitals?from=2024-01-01".parse().ok(),
+                query_string: Some("from=2024-01-01".to_string()),
+                headers: [
+                    ("Authorization".to_string(), "Bearer secret".to_string()),
+                    ("X-Device-Signature".to_string(), "sig".to_string()),
+                    ("User-Agent".to_string(), "walker/1.0".to_string()),
+                ]
+                .into_iter()
+                .collect(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let request = scrub(event).request.unwrap();
+        assert_eq!(request.headers.keys().collect::<Vec<_>>(), ["User-Agent"]);
+        assert_eq!(request.query_string, None);
+        assert_eq!(request.url.unwrap().as_str(), "https://api.example.com/api/patients/1/vitals");
+    }
+}
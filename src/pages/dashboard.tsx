@@ -50,7 +50,9 @@ export default function Dashboard() {
     setExporting(true);
     try {
       const token = getAuthToken();
-      const bundle = await apiGetJson<any>(`/api/fhir/export?limit=100`);
+      // Pages run oldest first, so start a day back to get recent readings
+      const since = new Date(Date.now() - 24 * 60 * 60 * 1000).toISOString();
+      const bundle = await apiGetJson<any>(`/api/fhir/export?limit=100&_since=${encodeURIComponent(since)}`);
       
       // Download as JSON file
       const blob = new Blob([JSON.stringify(bundle, null, 2)], { type: "application/json" });
@@ -90,7 +92,8 @@ export default function Dashboard() {
     // Listen for vitals events
     eventSource.addEventListener("vitals", (event) => {
       try {
-        const data = JSON.parse(event.data);
+        // Events arrive in an envelope: {version, type, id, occurred_at, data}
+        const { data } = JSON.parse(event.data);
         const reading: Reading = {
           t: data.timestamp ? data.timestamp * 1000 : Date.now(),
           heartRate: Number(data.heartRate ?? data.hr ?? 0),
@@ -109,7 +112,7 @@ export default function Dashboard() {
     // Listen for alert events
     eventSource.addEventListener("alert", (event) => {
       try {
-        const alert: MlAlert = JSON.parse(event.data);
+        const alert: MlAlert = JSON.parse(event.data).data;
         setAlerts((prev) => [alert, ...prev.slice(0, 4)]); // Keep last 5 alerts
         
         // Show notification
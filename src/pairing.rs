@@ -0,0 +1,79 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Unambiguous characters for codes typed from the walker display (no 0/O, 1/I/L)
+const ALPHABET: &[u8] = b"23456789ABCDEFGHJKMNPQRSTUVWXYZ";
+const CODE_LEN: usize = 8;
+
+/// Generate a pairing code formatted for display, e.g. `K7F3-9QXM`
+pub fn generate_code() -> String {
+    let mut rng = rand::thread_rng();
+    let chars: String = (0..CODE_LEN)
+        .map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char)
+        .collect();
+    format!("{}-{}", &chars[..CODE_LEN / 2], &chars[CODE_LEN / 2..])
+}
+
+/// Canonical form of a user-entered code (case, dashes and spaces ignored)
+pub fn normalize_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Hash stored in `device_pairing_codes`; the plaintext code is never persisted
+pub fn hash_code(code: &str) -> String {
+    format!("{:x}", Sha256::digest(normalize_code(code).as_bytes()))
+}
+
+/// Issue a fresh pairing code for a device, invalidating any outstanding ones
+pub async fn create_pairing_code(
+    pool: &PgPool,
+    device_uuid: Uuid,
+    ttl_minutes: i64,
+) -> Result<(String, DateTime<Utc>)> {
+    let code = generate_code();
+    let expires_at = Utc::now() + Duration::minutes(ttl_minutes);
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("UPDATE device_pairing_codes SET expires_at = now() WHERE device_id = $1 AND used_at IS NULL")
+        .bind(device_uuid)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("INSERT INTO device_pairing_codes (device_id, code_hash, expires_at) VALUES ($1, $2, $3)")
+        .bind(device_uuid)
+        .bind(hash_code(&code))
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok((code, expires_at))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_code_format() {
+        let code = generate_code();
+        assert_eq!(code.len(), CODE_LEN + 1);
+        assert_eq!(&code[4..5], "-");
+        assert!(normalize_code(&code).bytes().all(|b| ALPHABET.contains(&b)));
+    }
+
+    #[test]
+    fn test_hash_ignores_formatting() {
+        assert_eq!(hash_code("K7F3-9QXM"), hash_code("k7f3 9qxm"));
+        assert_ne!(hash_code("K7F3-9QXM"), hash_code("K7F3-9QXN"));
+    }
+}
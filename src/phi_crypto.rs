@@ -0,0 +1,345 @@
+//! Envelope encryption of PHI free text, keyed per organization.
+//!
+//! Each organization gets random AES-256-GCM data keys (DEKs), stored only wrapped by the
+//! master key from `encryption.master_key`. Sealed values name the organization and key
+//! version they were written with, so they stay readable after a patient moves and after
+//! a key rotation. Deleting an organization's keys (offboarding, or containing a
+//! compromise) makes everything sealed under them unreadable without touching other tenants.
+//!
+//! Unwrapped DEKs are cached per process, but every open first checks the key is still in
+//! `organization_keys`. Shredding on one worker or instance therefore stops all of them
+//! decrypting at once; only values already opened by requests in flight still get out.
+//!
+//! The master key only ever wraps and unwraps DEKs, so moving it into a KMS means
+//! replacing `MasterKey::wrap` and `MasterKey::unwrap` with KMS calls.
+
+use crate::config::EncryptionConfig;
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose, Engine as _};
+use rand::RngCore;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Prefix of sealed values; anything else is legacy or single-tenant plaintext
+const SEALED_PREFIX: &str = "phi:v1:";
+
+/// AES-256-GCM with a random nonce prepended to the ciphertext
+pub(crate) fn seal_bytes(key: &[u8; 32], aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, key).expect("32-byte AES-256 key"));
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+
+    let mut sealed = plaintext.to_vec();
+    key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(aad), &mut sealed)
+        .expect("AES-GCM input within size limits");
+    [nonce.as_slice(), &sealed].concat()
+}
+
+pub(crate) fn open_bytes(key: &[u8; 32], aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>> {
+    if sealed.len() < NONCE_LEN {
+        bail!("sealed value is truncated");
+    }
+    let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, key).expect("32-byte AES-256 key"));
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| anyhow!("bad nonce"))?;
+
+    let mut in_out = ciphertext.to_vec();
+    let plaintext = key
+        .open_in_place(nonce, Aad::from(aad), &mut in_out)
+        .map_err(|_| anyhow!("decryption failed (wrong key or tampered value)"))?;
+    Ok(plaintext.to_vec())
+}
+
+/// Parse a base64 AES-256 key
+pub fn parse_key(encoded: &str) -> Result<[u8; 32]> {
+    let bytes = general_purpose::STANDARD.decode(encoded.trim()).context("not valid base64")?;
+    bytes.try_into().map_err(|b: Vec<u8>| anyhow!("expected 32 bytes, got {}", b.len()))
+}
+
+/// Key-encryption key; DEKs are bound to their organization and version when wrapped
+pub struct MasterKey([u8; 32]);
+
+impl MasterKey {
+    pub fn new(key: [u8; 32]) -> Self {
+        Self(key)
+    }
+
+    pub fn wrap(&self, organization_id: Uuid, version: i32, dek: &[u8; 32]) -> Vec<u8> {
+        seal_bytes(&self.0, key_aad(organization_id, version).as_bytes(), dek)
+    }
+
+    pub fn unwrap(&self, organization_id: Uuid, version: i32, wrapped: &[u8]) -> Result<[u8; 32]> {
+        let dek = open_bytes(&self.0, key_aad(organization_id, version).as_bytes(), wrapped)?;
+        dek.try_into().map_err(|_| anyhow!("unwrapped key has the wrong length"))
+    }
+}
+
+fn key_aad(organization_id: Uuid, version: i32) -> String {
+    format!("{}:{}", organization_id, version)
+}
+
+/// A sealed value: `phi:v1:{organization_id}:{key_version}:{base64(nonce || ciphertext)}`
+fn format_sealed(organization_id: Uuid, version: i32, sealed: &[u8]) -> String {
+    format!("{}{}:{}:{}", SEALED_PREFIX, organization_id, version, general_purpose::STANDARD.encode(sealed))
+}
+
+fn parse_sealed(value: &str) -> Result<Option<(Uuid, i32, Vec<u8>)>> {
+    let Some(rest) = value.strip_prefix(SEALED_PREFIX) else {
+        return Ok(None);
+    };
+    let mut parts = rest.splitn(3, ':');
+    let (Some(org), Some(version), Some(data)) = (parts.next(), parts.next(), parts.next()) else {
+        bail!("malformed sealed value");
+    };
+    Ok(Some((
+        org.parse().context("bad organization id")?,
+        version.parse().context("bad key version")?,
+        general_purpose::STANDARD.decode(data).context("bad ciphertext encoding")?,
+    )))
+}
+
+/// Seals and opens PHI with the owning organization's data keys.
+///
+/// Without a master key (single-tenant deployments) values pass through unchanged, as do
+/// values for patients outside any organization.
+pub struct PhiCipher {
+    pool: PgPool,
+    master: Option<MasterKey>,
+    /// Unwrapped DEKs by (organization, version)
+    keys: RwLock<HashMap<(Uuid, i32), [u8; 32]>>,
+}
+
+impl PhiCipher {
+    pub fn new(pool: PgPool, config: &EncryptionConfig) -> Result<Self> {
+        let master = config
+            .master_key
+            .as_deref()
+            .map(|key| parse_key(key).map(MasterKey::new))
+            .transpose()
+            .context("encryption.master_key")?;
+        Ok(Self { pool, master, keys: RwLock::new(HashMap::new()) })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.master.is_some()
+    }
+
+    /// Seal `plaintext` under the active key of the patient's organization
+    pub async fn seal_for_patient(&self, patient_id: Uuid, plaintext: &str) -> Result<String> {
+        if self.master.is_none() {
+            return Ok(plaintext.to_string());
+        }
+        let organization_id: Option<Uuid> = sqlx::query_scalar("SELECT organization_id FROM patients WHERE id = $1")
+            .bind(patient_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .flatten();
+        let Some(organization_id) = organization_id else {
+            return Ok(plaintext.to_string());
+        };
+        self.seal_for_organization(organization_id, plaintext).await
+    }
+
+    /// Seal `plaintext` under the organization's active key, e.g. credentials it configured
+    pub async fn seal_for_organization(&self, organization_id: Uuid, plaintext: &str) -> Result<String> {
+        let Some(master) = &self.master else {
+            return Ok(plaintext.to_string());
+        };
+        let active: Option<(i32, Vec<u8>)> = sqlx::query_as(
+            "SELECT version, wrapped_key FROM organization_keys WHERE organization_id = $1 ORDER BY version DESC LIMIT 1"
+        )
+        .bind(organization_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        let (version, dek) = match active {
+            Some((version, wrapped)) => (version, self.cached_key(master, organization_id, version, &wrapped)?),
+            None => self.create_key(organization_id).await?,
+        };
+
+        let aad = key_aad(organization_id, version);
+        Ok(format_sealed(organization_id, version, &seal_bytes(&dek, aad.as_bytes(), plaintext.as_bytes())))
+    }
+
+    /// Open a sealed value; plaintext is returned as is. `None` when the organization's
+    /// keys have been destroyed, by this process or any other.
+    pub async fn open(&self, value: &str) -> Result<Option<String>> {
+        let Some((organization_id, version, sealed)) = parse_sealed(value)? else {
+            return Ok(Some(value.to_string()));
+        };
+        let master = self.master.as_ref().ok_or_else(|| anyhow!("encrypted PHI found but no master key is configured"))?;
+
+        // Looked up every time, so a key shredded elsewhere is not served from the cache
+        let wrapped: Option<Vec<u8>> = sqlx::query_scalar(
+            "SELECT wrapped_key FROM organization_keys WHERE organization_id = $1 AND version = $2"
+        )
+        .bind(organization_id)
+        .bind(version)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(wrapped) = wrapped else {
+            self.keys.write().unwrap().remove(&(organization_id, version));
+            return Ok(None);
+        };
+        let dek = self.cached_key(master, organization_id, version, &wrapped)?;
+
+        let plaintext = open_bytes(&dek, key_aad(organization_id, version).as_bytes(), &sealed)?;
+        Ok(Some(String::from_utf8(plaintext)?))
+    }
+
+    /// `open` for display: failures are logged and shown as absent
+    pub async fn open_field(&self, value: Option<String>) -> Option<String> {
+        match self.open(value.as_deref()?).await {
+            Ok(plaintext) => plaintext,
+            Err(e) => {
+                warn!("Failed to decrypt PHI field: {:#}", e);
+                None
+            }
+        }
+    }
+
+    /// Open a string field of a JSON row in place (rows built with `to_jsonb`)
+    pub async fn open_json_field(&self, row: &mut serde_json::Value, field: &str) {
+        let Some(value) = row.get_mut(field) else {
+            return;
+        };
+        if let Some(sealed) = value.as_str().filter(|v| v.starts_with(SEALED_PREFIX)) {
+            *value = self.open_field(Some(sealed.to_string())).await.into();
+        }
+    }
+
+    /// Start sealing new values under a fresh key; older versions stay readable
+    pub async fn rotate(&self, organization_id: Uuid) -> Result<i32> {
+        Ok(self.create_key(organization_id).await?.0)
+    }
+
+    /// Destroy every key of an organization, making its sealed PHI permanently unreadable on
+    /// every instance (see the module docs)
+    pub async fn shred(&self, organization_id: Uuid) -> Result<u64> {
+        let deleted = sqlx::query("DELETE FROM organization_keys WHERE organization_id = $1")
+            .bind(organization_id)
+            .execute(&self.pool)
+            .await?;
+        self.keys.write().unwrap().retain(|(org, _), _| *org != organization_id);
+        Ok(deleted.rows_affected())
+    }
+
+    async fn create_key(&self, organization_id: Uuid) -> Result<(i32, [u8; 32])> {
+        let master = self.master.as_ref().ok_or_else(|| anyhow!("no master key is configured"))?;
+        let mut dek = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut dek);
+
+        // Racing writers may both create a key; the loser retries on the next version
+        for _ in 0..3 {
+            let next: i32 = sqlx::query_scalar(
+                "SELECT COALESCE(MAX(version), 0) + 1 FROM organization_keys WHERE organization_id = $1"
+            )
+            .bind(organization_id)
+            .fetch_one(&self.pool)
+            .await?;
+            let inserted = sqlx::query(
+                "INSERT INTO organization_keys (organization_id, version, wrapped_key) VALUES ($1, $2, $3)
+                 ON CONFLICT DO NOTHING"
+            )
+            .bind(organization_id)
+            .bind(next)
+            .bind(master.wrap(organization_id, next, &dek))
+            .execute(&self.pool)
+            .await?;
+            if inserted.rows_affected() == 1 {
+                self.keys.write().unwrap().insert((organization_id, next), dek);
+                return Ok((next, dek));
+            }
+        }
+        bail!("could not create a data key for organization {}", organization_id)
+    }
+
+    fn cached_key(&self, master: &MasterKey, organization_id: Uuid, version: i32, wrapped: &[u8]) -> Result<[u8; 32]> {
+        if let Some(dek) = self.keys.read().unwrap().get(&(organization_id, version)) {
+            return Ok(*dek);
+        }
+        let dek = master.unwrap(organization_id, version, wrapped)?;
+        self.keys.write().unwrap().insert((organization_id, version), dek);
+        Ok(dek)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrapped_keys_are_bound_to_their_organization() {
+        let master = MasterKey::new([7u8; 32]);
+        let org = Uuid::new_v4();
+        let dek = [42u8; 32];
+
+        let wrapped = master.wrap(org, 1, &dek);
+        assert_eq!(master.unwrap(org, 1, &wrapped).unwrap(), dek);
+        assert!(master.unwrap(org, 2, &wrapped).is_err());
+        assert!(master.unwrap(Uuid::new_v4(), 1, &wrapped).is_err());
+        assert!(MasterKey::new([8u8; 32]).unwrap(org, 1, &wrapped).is_err());
+    }
+
+    #[test]
+    fn test_sealed_values_round_trip_and_detect_tampering() {
+        let org = Uuid::new_v4();
+        let dek = [3u8; 32];
+        let sealed = seal_bytes(&dek, b"aad", b"Knee sore after walk");
+        let formatted = format_sealed(org, 4, &sealed);
+
+        let (parsed_org, version, bytes) = parse_sealed(&formatted).unwrap().unwrap();
+        assert_eq!((parsed_org, version), (org, 4));
+        assert_eq!(open_bytes(&dek, b"aad", &bytes).unwrap(), b"Knee sore after walk");
+
+        let mut tampered = bytes.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(open_bytes(&dek, b"aad", &tampered).is_err());
+        assert!(parse_sealed("plain notes").unwrap().is_none());
+        assert!(parse_sealed("phi:v1:not-a-uuid:1:AAAA").is_err());
+    }
+
+    #[sqlx::test]
+    async fn test_rotation_keeps_old_values_and_shredding_destroys_them(pool: PgPool) {
+        let config = EncryptionConfig { master_key: Some(general_purpose::STANDARD.encode([9u8; 32])) };
+        let cipher = PhiCipher::new(pool.clone(), &config).unwrap();
+        let org: Uuid = sqlx::query_scalar("INSERT INTO organizations (name) VALUES ('Ward Org') RETURNING id")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let patient: Uuid = sqlx::query_scalar("INSERT INTO patients (display_name, organization_id) VALUES ('P', $1) RETURNING id")
+            .bind(org)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        let first = cipher.seal_for_patient(patient, "dizzy in the morning").await.unwrap();
+        assert!(first.starts_with(SEALED_PREFIX));
+        assert_eq!(cipher.rotate(org).await.unwrap(), 2);
+        let second = cipher.seal_for_patient(patient, "better today").await.unwrap();
+        assert!(second.contains(":2:"));
+
+        // A fresh cipher has to unwrap both versions from the database
+        let reopened = PhiCipher::new(pool.clone(), &config).unwrap();
+        assert_eq!(reopened.open(&first).await.unwrap().as_deref(), Some("dizzy in the morning"));
+        assert_eq!(reopened.open(&second).await.unwrap().as_deref(), Some("better today"));
+        assert_eq!(reopened.open("legacy plaintext").await.unwrap().as_deref(), Some("legacy plaintext"));
+
+        assert_eq!(cipher.shred(org).await.unwrap(), 2);
+        assert_eq!(cipher.open(&first).await.unwrap(), None);
+        assert_eq!(cipher.open_field(Some(second.clone())).await, None);
+        // Another instance that had the keys cached stops decrypting too
+        assert_eq!(reopened.open(&first).await.unwrap(), None);
+        assert_eq!(reopened.open(&second).await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert!(parse_key(&general_purpose::STANDARD.encode([1u8; 32])).is_ok());
+        assert!(parse_key(&general_purpose::STANDARD.encode([1u8; 16])).is_err());
+        assert!(parse_key("not base64!").is_err());
+    }
+}
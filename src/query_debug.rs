@@ -0,0 +1,90 @@
+use crate::config::QueryDebugConfig;
+use crate::metrics::DB_QUERY_DURATION;
+use sqlx::postgres::{PgArguments, PgRow};
+use sqlx::{FromRow, PgPool};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Build `PgArguments` from values, for queries whose arguments must be bound more than once
+#[macro_export]
+macro_rules! pg_args {
+    ($($value:expr),* $(,)?) => {{
+        #[allow(unused_imports)]
+        use sqlx::Arguments as _;
+        #[allow(unused_mut)]
+        let mut args = sqlx::postgres::PgArguments::default();
+        $( args.add($value); )*
+        args
+    }};
+}
+
+/// Whether a query that took `elapsed` should be explained, given a uniform `draw` in `[0, 1)`
+pub fn should_explain(config: &QueryDebugConfig, elapsed: Duration, draw: f64) -> bool {
+    config.enabled
+        && elapsed >= Duration::from_millis(config.slow_query_ms)
+        && draw < config.sample_rate
+}
+
+/// `EXPLAIN ANALYZE` executes the statement, so only plain reads are ever explained
+fn is_read_only(sql: &str) -> bool {
+    sql.trim_start()
+        .get(..6)
+        .is_some_and(|keyword| keyword.eq_ignore_ascii_case("select"))
+}
+
+/// Run a read query and, in debug mode, log the plan of a sample of the slow ones.
+///
+/// `args` is called once per execution because `PgArguments` can't be cloned. The explain
+/// runs in the background so the caller isn't slowed down a second time.
+pub async fn fetch_all<T>(
+    pool: &PgPool,
+    config: &QueryDebugConfig,
+    label: &'static str,
+    sql: &str,
+    args: impl Fn() -> PgArguments,
+) -> Result<Vec<T>, sqlx::Error>
+where
+    T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+{
+    let started = Instant::now();
+    let rows = sqlx::query_as_with(sql, args()).fetch_all(pool).await?;
+    let elapsed = started.elapsed();
+    DB_QUERY_DURATION.with_label_values(&[label]).observe(elapsed.as_secs_f64());
+
+    if is_read_only(sql) && should_explain(config, elapsed, rand::random()) {
+        let pool = pool.clone();
+        let explain = format!("EXPLAIN (ANALYZE, BUFFERS) {}", sql);
+        let args = args();
+        tokio::spawn(async move {
+            match sqlx::query_scalar_with::<_, String, _>(&explain, args).fetch_all(&pool).await {
+                Ok(plan) => warn!(query = label, elapsed_ms = elapsed.as_millis() as u64, "Slow query plan:\n{}", plan.join("\n")),
+                Err(e) => debug!("Could not explain slow query {}: {}", label, e),
+            }
+        });
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_sampled_slow_queries_are_explained() {
+        let config = QueryDebugConfig { enabled: true, slow_query_ms: 100, sample_rate: 0.25 };
+        let slow = Duration::from_millis(150);
+
+        assert!(should_explain(&config, slow, 0.1));
+        assert!(!should_explain(&config, slow, 0.5));
+        assert!(!should_explain(&config, Duration::from_millis(50), 0.0));
+        assert!(!should_explain(&QueryDebugConfig { enabled: false, ..config }, slow, 0.0));
+    }
+
+    #[test]
+    fn test_writes_are_never_explained() {
+        assert!(is_read_only("\n  SELECT * FROM sensor_readings"));
+        assert!(is_read_only("select 1"));
+        assert!(!is_read_only("DELETE FROM sensor_readings"));
+        assert!(!is_read_only("WITH gone AS (DELETE FROM alerts RETURNING id) SELECT count(*) FROM gone"));
+    }
+}
@@ -0,0 +1,199 @@
+use crate::config::QuotaConfig;
+use crate::notifier::Notifier;
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Header on accepted ingestion responses once an organization nears its quota
+pub const QUOTA_WARNING_HEADER: &str = "X-Quota-Warning";
+
+/// Where an organization stands against its storage quota, least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuotaState {
+    Within,
+    Near,
+    /// Over quota but inside the grace margin: still accepted, admins notified
+    Exceeded,
+    /// Past `reject_ratio`: ingestion is refused
+    Rejecting,
+}
+
+/// Usage against a single limit; `None` means unlimited
+pub fn limit_state(used: i64, limit: Option<i64>, config: &QuotaConfig) -> QuotaState {
+    let Some(limit) = limit else {
+        return QuotaState::Within;
+    };
+    let ratio = used as f64 / limit as f64;
+    if ratio > config.reject_ratio {
+        QuotaState::Rejecting
+    } else if ratio >= 1.0 {
+        QuotaState::Exceeded
+    } else if ratio >= config.warn_ratio {
+        QuotaState::Near
+    } else {
+        QuotaState::Within
+    }
+}
+
+/// An organization's limits with the usage last measured against them
+#[derive(Debug, Clone, FromRow)]
+pub struct OrganizationQuota {
+    pub organization_id: Uuid,
+    pub name: String,
+    pub max_readings: Option<i64>,
+    pub max_bytes: Option<i64>,
+    pub readings: i64,
+    pub bytes: i64,
+}
+
+impl OrganizationQuota {
+    /// The worse of the readings and bytes states
+    pub fn state(&self, config: &QuotaConfig) -> QuotaState {
+        limit_state(self.readings, self.max_readings, config).max(limit_state(self.bytes, self.max_bytes, config))
+    }
+
+    /// `X-Quota-Warning` value listing the share of each limit in use, e.g. `readings=95%; bytes=40%`
+    pub fn warning(&self) -> String {
+        [("readings", self.readings, self.max_readings), ("bytes", self.bytes, self.max_bytes)]
+            .iter()
+            .filter_map(|(name, used, limit)| limit.map(|limit| format!("{}={}%", name, used * 100 / limit)))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+/// Quota of the organization a patient belongs to; `None` if it has none or was never measured
+pub async fn quota_for_patient(pool: &PgPool, patient_id: Uuid) -> Result<Option<OrganizationQuota>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT o.id AS organization_id, o.name, o.max_readings, o.max_bytes, u.readings, u.bytes
+         FROM patients p
+         JOIN organizations o ON o.id = p.organization_id
+         JOIN organization_usage u ON u.organization_id = o.id
+         WHERE p.id = $1"
+    )
+    .bind(patient_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Re-measure every organization's stored readings (count and on-disk row size).
+///
+/// Readings are attributed through their device's current patient, so a device moved
+/// between organizations takes its history with it.
+pub async fn measure_usage(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "INSERT INTO organization_usage (organization_id, readings, bytes, measured_at)
+         SELECT o.id, COUNT(r.id), COALESCE(SUM(pg_column_size(r.*)), 0), now()
+         FROM organizations o
+         LEFT JOIN patients p ON p.organization_id = o.id
+         LEFT JOIN devices d ON d.patient_id = p.id
+         LEFT JOIN sensor_readings r ON r.device_id = d.id
+         GROUP BY o.id
+         ON CONFLICT (organization_id) DO UPDATE
+         SET readings = EXCLUDED.readings, bytes = EXCLUDED.bytes, measured_at = EXCLUDED.measured_at"
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Measure usage and notify admins once per organization each time it goes over quota;
+/// returns the number of organizations newly over
+pub async fn check_quotas(pool: &PgPool, notifier: &Notifier) -> Result<usize> {
+    measure_usage(pool).await?;
+
+    let exceeded: Vec<OrganizationQuota> = sqlx::query_as(
+        "UPDATE organization_usage u SET exceeded_notified_at = now()
+         FROM organizations o
+         WHERE o.id = u.organization_id AND u.exceeded_notified_at IS NULL
+           AND (u.readings >= o.max_readings OR u.bytes >= o.max_bytes)
+         RETURNING o.id AS organization_id, o.name, o.max_readings, o.max_bytes, u.readings, u.bytes"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    // Back under quota: the next breach notifies again
+    sqlx::query(
+        "UPDATE organization_usage u SET exceeded_notified_at = NULL
+         FROM organizations o
+         WHERE o.id = u.organization_id AND u.exceeded_notified_at IS NOT NULL
+           AND NOT COALESCE(u.readings >= o.max_readings, false)
+           AND NOT COALESCE(u.bytes >= o.max_bytes, false)"
+    )
+    .execute(pool)
+    .await?;
+
+    for quota in &exceeded {
+        warn!(organization_id = %quota.organization_id, usage = %quota.warning(), "Organization over storage quota");
+        notifier
+            .notify_admins(
+                "quota_exceeded",
+                &format!("{} is over its storage quota", quota.name),
+                &format!("Stored readings are at {} of quota. Ingestion is refused past the grace margin.", quota.warning()),
+            )
+            .await?;
+    }
+    Ok(exceeded.len())
+}
+
+/// Background worker re-measuring organization usage every `measure_interval_minutes`
+pub fn spawn_quota_worker(pool: PgPool, notifier: Arc<Notifier>, config: QuotaConfig) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(config.measure_interval_minutes * 60));
+        loop {
+            interval.tick().await;
+
+            match check_quotas(&pool, &notifier).await {
+                Ok(0) => {}
+                Ok(n) => info!("{} organization(s) went over their storage quota", n),
+                Err(e) => error!("Quota measurement failed: {}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quota(readings: i64, max_readings: Option<i64>, bytes: i64, max_bytes: Option<i64>) -> OrganizationQuota {
+        OrganizationQuota {
+            organization_id: Uuid::new_v4(),
+            name: "Test Org".into(),
+            max_readings,
+            max_bytes,
+            readings,
+            bytes,
+        }
+    }
+
+    #[test]
+    fn test_limit_states() {
+        let config = QuotaConfig::default();
+
+        assert_eq!(limit_state(1_000_000, None, &config), QuotaState::Within);
+        assert_eq!(limit_state(89, Some(100), &config), QuotaState::Within);
+        assert_eq!(limit_state(90, Some(100), &config), QuotaState::Near);
+        assert_eq!(limit_state(100, Some(100), &config), QuotaState::Exceeded);
+        assert_eq!(limit_state(110, Some(100), &config), QuotaState::Exceeded);
+        assert_eq!(limit_state(111, Some(100), &config), QuotaState::Rejecting);
+    }
+
+    #[test]
+    fn test_worst_limit_wins_and_warning_lists_limits() {
+        let config = QuotaConfig::default();
+
+        let q = quota(50, Some(100), 950, Some(1000));
+        assert_eq!(q.state(&config), QuotaState::Near);
+        assert_eq!(q.warning(), "readings=50%; bytes=95%");
+
+        let unlimited_bytes = quota(120, Some(100), 1 << 40, None);
+        assert_eq!(unlimited_bytes.state(&config), QuotaState::Rejecting);
+        assert_eq!(unlimited_bytes.warning(), "readings=120%");
+    }
+}
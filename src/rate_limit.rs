@@ -0,0 +1,231 @@
+//! Request rate limits, counted in Redis so every worker and instance shares them.
+//!
+//! Each [`Policy`] allows a number of requests per minute to one client: an IP for
+//! `/auth/*`, a walker for device ingestion, a user (an IP without a token) for the rest of
+//! `/api`. Counts are kept per fixed minute, and the previous minute's count is weighted by
+//! how much of it still falls inside the sliding window, which smooths bursts at the turn
+//! of a minute without logging every request. If Redis is unavailable requests are let
+//! through: limits protect the service and must not take it down with them.
+
+use crate::auth::extract_bearer_token;
+use crate::config::RateLimitConfig;
+use crate::errors::ApiError;
+use crate::handlers::AppState;
+use crate::metrics::RATE_LIMITED_TOTAL;
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::{header, Method},
+    web, Error,
+};
+use chrono::Utc;
+use futures::future::LocalBoxFuture;
+use std::future::{ready, Ready};
+use std::net::SocketAddr;
+use std::rc::Rc;
+
+pub const WINDOW_SECONDS: i64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// `/auth/*`, per client IP
+    Auth,
+    /// Device ingestion, per authenticated walker
+    Device,
+    /// The rest of `/api`, per user or client IP
+    Api,
+}
+
+impl Policy {
+    pub fn name(self) -> &'static str {
+        match self {
+            Policy::Auth => "auth",
+            Policy::Device => "device",
+            Policy::Api => "api",
+        }
+    }
+
+    fn limit(self, config: &RateLimitConfig) -> u32 {
+        match self {
+            Policy::Auth => config.auth_per_minute,
+            Policy::Device => config.device_per_minute,
+            Policy::Api => config.api_per_minute,
+        }
+    }
+
+    /// The policy [`RateLimit`] applies to a path. Device ingestion is left to
+    /// [`check`] once the walker is known: its id header alone could be anyone's.
+    pub fn for_path(path: &str) -> Option<Policy> {
+        if path.starts_with("/auth/") {
+            Some(Policy::Auth)
+        } else if path.starts_with("/api/device/") {
+            None
+        } else if path.starts_with("/api/") {
+            Some(Policy::Api)
+        } else {
+            None
+        }
+    }
+}
+
+/// Seconds until a request would be allowed again, if the `current` minute's count (this
+/// request included) and the `previous` one's, weighted by the part of it still inside the
+/// window `elapsed` seconds into this minute, add up to more than `limit`
+pub fn retry_after(previous: u64, current: u64, elapsed: i64, limit: u32) -> Option<u64> {
+    let window = WINDOW_SECONDS as f64;
+    let (previous, current, limit) = (previous as f64, current as f64, limit as f64);
+    let elapsed = elapsed as f64;
+    if previous * (window - elapsed) / window + current <= limit {
+        return None;
+    }
+
+    let wait = if current <= limit {
+        // The previous minute's share has to shrink to what is left of the limit
+        window * (1.0 - (limit - current) / previous) - elapsed
+    } else {
+        // Not before this minute is over, and then its share has to shrink in turn
+        (window - elapsed) + window * (1.0 - (limit - 1.0).max(0.0) / current)
+    };
+    Some(wait.ceil().max(1.0) as u64)
+}
+
+/// Count a request by `client` under `policy`, failing with a 429 once over its limit
+pub async fn check(state: &AppState, policy: Policy, client: &str) -> Result<(), ApiError> {
+    if !state.rate_limit.enabled {
+        return Ok(());
+    }
+
+    let now = Utc::now().timestamp();
+    let key = format!("{}:{}", policy.name(), client);
+    let counted = state.redis.read().await.count_request(&key, WINDOW_SECONDS, now).await;
+    let (previous, current) = match counted {
+        Ok(counts) => counts,
+        Err(e) => {
+            tracing::warn!(policy = policy.name(), "Rate limit not checked: {}", e);
+            return Ok(());
+        }
+    };
+
+    match retry_after(previous, current, now.rem_euclid(WINDOW_SECONDS), policy.limit(&state.rate_limit)) {
+        None => Ok(()),
+        Some(seconds) => {
+            RATE_LIMITED_TOTAL.with_label_values(&[policy.name()]).inc();
+            Err(ApiError::TooManyRequests(seconds))
+        }
+    }
+}
+
+/// The client's IP: the connection's peer, or what the proxy forwarded when trusted
+fn client_ip(req: &ServiceRequest, trust_forwarded_for: bool) -> String {
+    let forwarded = trust_forwarded_for
+        .then(|| req.connection_info().realip_remote_addr().map(str::to_string))
+        .flatten();
+    match forwarded {
+        // Without forwarding headers this is the peer address again, port included
+        Some(addr) => addr.parse::<SocketAddr>().map(|addr| addr.ip().to_string()).unwrap_or(addr),
+        None => req.peer_addr().map(|addr| addr.ip().to_string()).unwrap_or_else(|| "unknown".to_string()),
+    }
+}
+
+/// Who a request counts against: its user when it carries a valid token, else its IP
+fn client_key(req: &ServiceRequest, state: &AppState, policy: Policy) -> String {
+    if policy == Policy::Api {
+        let auth_header = req.headers().get(header::AUTHORIZATION).and_then(|h| h.to_str().ok());
+        // Signature and expiry only; revocation is the handler's business
+        if let Ok(claims) = extract_bearer_token(auth_header).and_then(|token| state.jwt_auth.validate_token(&token)) {
+            return format!("user:{}", claims.user_id);
+        }
+    }
+    format!("ip:{}", client_ip(req, state.rate_limit.trust_forwarded_for))
+}
+
+/// Rate limits `/auth/*` and `/api/*` by [`Policy::for_path`]
+pub struct RateLimit;
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RateLimitMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let svc = self.service.clone();
+
+        Box::pin(async move {
+            let policy = Policy::for_path(req.path()).filter(|_| req.method() != Method::OPTIONS);
+            let state = req.app_data::<web::Data<AppState>>().cloned();
+            if let (Some(policy), Some(state)) = (policy, state) {
+                let client = client_key(&req, &state, policy);
+                if let Err(e) = check(&state, policy, &client).await {
+                    return Ok(req.error_response(e).map_into_right_body());
+                }
+            }
+            svc.call(req).await.map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policies_by_path() {
+        assert_eq!(Policy::for_path("/auth/login"), Some(Policy::Auth));
+        assert_eq!(Policy::for_path("/api/patients"), Some(Policy::Api));
+        assert_eq!(Policy::for_path("/api/device/vitals"), None);
+        assert_eq!(Policy::for_path("/health"), None);
+        assert_eq!(Policy::for_path("/metrics"), None);
+    }
+
+    #[test]
+    fn test_under_limit_allowed() {
+        assert_eq!(retry_after(0, 10, 30, 10), None);
+        // Half of the previous minute's 10 still counts 30 seconds in
+        assert_eq!(retry_after(10, 5, 30, 10), None);
+        assert_eq!(retry_after(10, 6, 30, 10), Some(6));
+    }
+
+    #[test]
+    fn test_previous_minute_share_decays() {
+        // 20 last minute, 5 now: 20 × (60 - t)/60 + 5 ≤ 10 from t = 45
+        assert_eq!(retry_after(20, 5, 0, 10), Some(45));
+        assert_eq!(retry_after(20, 5, 40, 10), Some(5));
+    }
+
+    #[test]
+    fn test_over_limit_waits_into_next_minute() {
+        // 20 this minute: wait 10s for it to end, then until 20 × (60 - t)/60 + 1 ≤ 10
+        assert_eq!(retry_after(0, 20, 50, 10), Some(10 + 33));
+        assert!(retry_after(0, 11, 59, 10).unwrap() >= 1);
+    }
+}
@@ -0,0 +1,230 @@
+//! Role-based access control.
+//!
+//! Routes declare the roles allowed to call them in their `route_registry!` entry; the
+//! registry wraps each such route in [`RequireRole`](crate::middleware::RequireRole) for the
+//! least privileged of them. Admins pass every check. Relaxed (home) deployments let a
+//! patient's linked caregivers through clinician checks on that patient too, so family
+//! members manage their patient's care. Those checks are made by the handlers, which know
+//! the patient; route-level clinician checks stay clinician-only.
+
+use crate::config::DeploymentConfig;
+use crate::models::Claims;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgHasArrayType, PgTypeInfo};
+use sqlx::PgPool;
+use std::fmt;
+use uuid::Uuid;
+
+/// A user's role, stored in `users.role` (Postgres enum `user_role`) and carried in tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "user_role", rename_all = "snake_case")]
+pub enum Role {
+    Admin,
+    /// Manages care plans, medications, wards and reports for their patients
+    Clinician,
+    /// Read access to linked patients; the default for new accounts
+    Viewer,
+    /// Registers and pairs walkers without access to patient data
+    DeviceManager,
+}
+
+impl Role {
+    pub const ALL: &'static [Role] = &[Role::Admin, Role::Clinician, Role::Viewer, Role::DeviceManager];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|role| role.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Role::Admin => "admin",
+            Role::Clinician => "clinician",
+            Role::Viewer => "viewer",
+            Role::DeviceManager => "device_manager",
+        }
+    }
+
+    /// Shown in the 403 body, e.g. "Clinician role required"
+    pub fn label(&self) -> &'static str {
+        match self {
+            Role::Admin => "Admin",
+            Role::Clinician => "Clinician",
+            Role::Viewer => "Viewer",
+            Role::DeviceManager => "Device manager",
+        }
+    }
+
+    /// Whether this role may do what `required` may
+    pub fn includes(&self, required: Role) -> bool {
+        match (self, required) {
+            (Role::Admin, _) => true,
+            (Role::Clinician, Role::Viewer) => true,
+            (role, required) => *role == required,
+        }
+    }
+
+    /// The role a registry entry's role list asks for: its least privileged member, since
+    /// every role in the list must get through. `None` for an empty (unrestricted) list.
+    ///
+    /// Panics on a list no single check can express, so a typo fails at startup instead of
+    /// leaving the route open.
+    pub fn required_by(names: &[&str]) -> Option<Self> {
+        let roles: Vec<Role> = names
+            .iter()
+            .map(|name| Self::parse(name).unwrap_or_else(|| panic!("unknown role '{}' in route registry", name)))
+            .collect();
+        if roles.is_empty() {
+            return None;
+        }
+        let required = roles.iter().copied().find(|role| roles.iter().all(|other| other.includes(*role)));
+        Some(required.unwrap_or_else(|| panic!("route registry roles {:?} need more than one check", names)))
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl PgHasArrayType for Role {
+    fn array_type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("_user_role")
+    }
+}
+
+/// Whether the caller's own role includes `required`
+pub fn has_role(claims: &Claims, required: Role) -> bool {
+    claims.role.includes(required)
+}
+
+/// Whether the caller gets through a check for `required` on `patient` (`None` for checks
+/// not about one patient): [`has_role`], or in relaxed deployments a clinician check on a
+/// patient by one of its linked caregivers
+pub async fn holds_role(
+    pool: &PgPool,
+    deployment: &DeploymentConfig,
+    claims: &Claims,
+    required: Role,
+    patient: Option<Uuid>,
+) -> Result<bool, sqlx::Error> {
+    if has_role(claims, required) {
+        return Ok(true);
+    }
+    let Some(patient_id) = patient else {
+        return Ok(false);
+    };
+    if required != Role::Clinician || deployment.strict_rbac {
+        return Ok(false);
+    }
+    sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM patient_caregivers WHERE user_id = $1 AND patient_id = $2)")
+        .bind(claims.user_id)
+        .bind(patient_id)
+        .fetch_one(pool)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DeploymentMode;
+    use serde_json::json;
+
+    fn claims(role: &str) -> Claims {
+        Claims {
+            sub: "user@example.com".to_string(),
+            user_id: uuid::Uuid::nil(),
+            role: Role::parse(role).unwrap(),
+            org: None,
+            exp: 0,
+            iat: 0,
+            jti: uuid::Uuid::nil(),
+        }
+    }
+
+    #[test]
+    fn test_role_hierarchy() {
+        assert!(has_role(&claims("admin"), Role::DeviceManager));
+        assert!(has_role(&claims("clinician"), Role::Viewer));
+        assert!(!has_role(&claims("clinician"), Role::Admin));
+        assert!(!has_role(&claims("viewer"), Role::Clinician));
+        assert!(!has_role(&claims("device_manager"), Role::Viewer));
+    }
+
+    #[test]
+    fn test_tokens_with_unknown_roles_do_not_decode() {
+        let token = |role: &str| json!({"sub": "user@example.com", "user_id": uuid::Uuid::nil(), "role": role, "exp": 0, "iat": 0, "jti": uuid::Uuid::nil()});
+        assert_eq!(serde_json::from_value::<Claims>(token("device_manager")).unwrap().role, Role::DeviceManager);
+        assert!(serde_json::from_value::<Claims>(token("superuser")).is_err());
+    }
+
+    #[test]
+    fn test_required_by_picks_least_privileged() {
+        assert_eq!(Role::required_by(&[]), None);
+        assert_eq!(Role::required_by(&["admin"]), Some(Role::Admin));
+        assert_eq!(Role::required_by(&["admin", "clinician"]), Some(Role::Clinician));
+        assert_eq!(Role::required_by(&["device_manager", "admin"]), Some(Role::DeviceManager));
+        assert_eq!(Role::parse("device_manager"), Some(Role::DeviceManager));
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown role")]
+    fn test_required_by_rejects_unknown_roles() {
+        Role::required_by(&["admin", "nurse"]);
+    }
+
+    #[sqlx::test]
+    async fn test_relaxed_checks_only_cover_the_linked_patient(pool: PgPool) {
+        let deployment = DeploymentConfig {
+            mode: DeploymentMode::Home,
+            max_patients: None,
+            strict_rbac: false,
+            consumer_notifications: true,
+            ehr_push: false,
+        };
+        let user: Uuid = sqlx::query_scalar("INSERT INTO users (email, password_hash, role) VALUES ('family@example.com', 'x', 'viewer') RETURNING id")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let mut patients = vec![];
+        for name in ["Linked", "Other"] {
+            let patient: Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ($1) RETURNING id")
+                .bind(name)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            patients.push(patient);
+        }
+        sqlx::query("INSERT INTO patient_caregivers (patient_id, user_id) VALUES ($1, $2)")
+            .bind(patients[0])
+            .bind(user)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let caregiver = Claims { user_id: user, ..claims("viewer") };
+
+        assert!(holds_role(&pool, &deployment, &caregiver, Role::Clinician, Some(patients[0])).await.unwrap());
+        assert!(!holds_role(&pool, &deployment, &caregiver, Role::Clinician, Some(patients[1])).await.unwrap());
+        // Route-level checks name no patient and stay with clinicians
+        assert!(!holds_role(&pool, &deployment, &caregiver, Role::Clinician, None).await.unwrap());
+        assert!(!holds_role(&pool, &deployment, &caregiver, Role::Admin, Some(patients[0])).await.unwrap());
+
+        let strict = DeploymentConfig { strict_rbac: true, ..deployment };
+        assert!(!holds_role(&pool, &strict, &caregiver, Role::Clinician, Some(patients[0])).await.unwrap());
+    }
+
+    #[test]
+    fn test_registry_role_lists_resolve() {
+        for route in crate::routes::registered_routes() {
+            let Some(required) = Role::required_by(route.roles) else {
+                continue;
+            };
+            // Every listed role must actually get through the resolved check
+            for name in route.roles {
+                assert!(Role::parse(name).unwrap().includes(required), "{} {}", route.method, route.path);
+            }
+        }
+    }
+}
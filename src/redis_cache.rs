@@ -1,11 +1,52 @@
 use crate::config::RedisConfig;
-use crate::models::LatestVitals;
+use crate::models::{AlertLevel, LatestVitals};
+use chrono::{DateTime, Utc};
 use redis::{aio::ConnectionManager, AsyncCommands, RedisError};
-use serde_json;
+use uuid::Uuid;
 
 const LATEST_VITALS_KEY: &str = "vitals:latest";
 const RECENT_READINGS_KEY: &str = "readings:recent";
-const MAX_RECENT_READINGS: isize = 100;
+/// Length of the recent readings list
+pub const MAX_RECENT_READINGS: isize = 100;
+/// Cached aggregates are dropped this long after the patient's last cache write
+const AGGREGATE_TTL_SECONDS: i64 = 3600;
+
+/// One hash per (patient, bucket) holding every cached range as a `{from}:{to}` field,
+/// so a new reading can find and drop exactly the ranges it falls into. The version is
+/// bumped whenever the cached bucket format changes.
+fn aggregate_key(patient_id: Uuid, bucket: &str) -> String {
+    format!("agg:v2:{}:{}", patient_id, bucket)
+}
+
+/// The newest vitals from any of a patient's walkers
+fn patient_latest_vitals_key(patient_id: Uuid) -> String {
+    format!("vitals:latest:{}", patient_id)
+}
+
+/// Set while a device's alert at a level is cooling down
+fn alert_cooldown_key(device_id: Uuid, level: AlertLevel) -> String {
+    format!("alert:cooldown:{}:{}", device_id, level)
+}
+
+/// Requests counted against a rate-limited client in one window
+fn rate_limit_key(client: &str, window: i64) -> String {
+    format!("ratelimit:{}:{}", client, window)
+}
+
+fn aggregate_field(from: DateTime<Utc>, to: DateTime<Utc>) -> String {
+    format!("{}:{}", from.timestamp(), to.timestamp())
+}
+
+/// Whether the range encoded in an aggregate field covers `at`
+fn field_covers(field: &str, at: DateTime<Utc>) -> bool {
+    let Some((from, to)) = field.split_once(':') else {
+        return true;
+    };
+    match (from.parse::<i64>(), to.parse::<i64>()) {
+        (Ok(from), Ok(to)) => (from..to).contains(&at.timestamp()),
+        _ => true,
+    }
+}
 
 pub struct RedisCache {
     client: ConnectionManager,
@@ -20,11 +61,14 @@ impl RedisCache {
         Ok(Self { client: conn })
     }
 
-    /// Store the latest vitals reading
-    pub async fn set_latest_vitals(&mut self, vitals: &LatestVitals) -> Result<(), RedisError> {
+    /// Store the latest vitals reading, also as its patient's latest when the walker has one
+    pub async fn set_latest_vitals(&mut self, patient_id: Option<Uuid>, vitals: &LatestVitals) -> Result<(), RedisError> {
         let json = serde_json::to_string(vitals)
             .map_err(|e| RedisError::from((redis::ErrorKind::TypeError, "Serialization failed", e.to_string())))?;
         
+        if let Some(patient_id) = patient_id {
+            self.client.set::<_, _, ()>(patient_latest_vitals_key(patient_id), &json).await?;
+        }
         self.client.set::<_, _, ()>(LATEST_VITALS_KEY, json).await?;
         
         // Also add to recent readings list (LPUSH + LTRIM for max 100)
@@ -37,6 +81,57 @@ impl RedisCache {
         Ok(())
     }
 
+    /// Fill the latest vitals and recent readings from `newest_first`, and each patient's
+    /// latest from `by_patient`, leaving any alone that a reading has already been cached in;
+    /// returns whether anything was written
+    pub async fn warm_latest_vitals(
+        &mut self,
+        newest_first: &[LatestVitals],
+        by_patient: &[(Uuid, LatestVitals)],
+    ) -> Result<bool, RedisError> {
+        let Some(latest) = newest_first.first() else {
+            return Ok(false);
+        };
+        let serialize = |vitals: &LatestVitals| {
+            serde_json::to_string(vitals)
+                .map_err(|e| RedisError::from((redis::ErrorKind::TypeError, "Serialization failed", e.to_string())))
+        };
+
+        // One script, so a reading ingested meanwhile is never overwritten or reordered
+        let script = redis::Script::new(
+            r"
+            local written = 0
+            if redis.call('EXISTS', KEYS[1]) == 0 then
+                redis.call('SET', KEYS[1], ARGV[1])
+                written = 1
+            end
+            local recent = tonumber(ARGV[2])
+            if redis.call('EXISTS', KEYS[2]) == 0 and recent > 0 then
+                redis.call('RPUSH', KEYS[2], unpack(ARGV, 3, 2 + recent))
+                written = 1
+            end
+            for i = 3, #KEYS do
+                if redis.call('SET', KEYS[i], ARGV[recent + i], 'NX') then
+                    written = 1
+                end
+            end
+            return written
+            ",
+        );
+        let recent = &newest_first[..newest_first.len().min(MAX_RECENT_READINGS as usize)];
+        let mut invocation = script.key(LATEST_VITALS_KEY);
+        invocation.key(RECENT_READINGS_KEY).arg(serialize(latest)?).arg(recent.len());
+        for vitals in recent {
+            invocation.arg(serialize(vitals)?);
+        }
+        // Patients' keys follow, each with its vitals after the recent readings
+        for (patient_id, vitals) in by_patient {
+            invocation.key(patient_latest_vitals_key(*patient_id)).arg(serialize(vitals)?);
+        }
+        let written: i32 = invocation.invoke_async(&mut self.client).await?;
+        Ok(written == 1)
+    }
+
     /// Get the latest vitals reading
     pub async fn get_latest_vitals(&mut self) -> Result<Option<LatestVitals>, RedisError> {
         let json: Option<String> = self.client.get(LATEST_VITALS_KEY).await?;
@@ -51,6 +146,21 @@ impl RedisCache {
         }
     }
 
+    /// The newest cached vitals among `patients`; `None` when none of them has any cached
+    pub async fn get_patients_latest_vitals(&mut self, patients: &[Uuid]) -> Result<Option<LatestVitals>, RedisError> {
+        if patients.is_empty() {
+            return Ok(None);
+        }
+        let keys: Vec<String> = patients.iter().map(|id| patient_latest_vitals_key(*id)).collect();
+        let json_list: Vec<Option<String>> = self.client.mget(keys).await?;
+
+        Ok(json_list
+            .iter()
+            .flatten()
+            .filter_map(|json| serde_json::from_str::<LatestVitals>(json).ok())
+            .max_by_key(|vitals| vitals.timestamp))
+    }
+
     /// Get recent readings (last N readings)
     pub async fn get_recent_readings(&mut self, count: isize) -> Result<Vec<LatestVitals>, RedisError> {
         let json_list: Vec<String> = self.client.lrange(RECENT_READINGS_KEY, 0, count - 1).await?;
@@ -65,6 +175,88 @@ impl RedisCache {
         Ok(readings)
     }
 
+    pub async fn get_aggregate(
+        &mut self,
+        patient_id: Uuid,
+        bucket: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Option<String>, RedisError> {
+        self.client.hget(aggregate_key(patient_id, bucket), aggregate_field(from, to)).await
+    }
+
+    pub async fn set_aggregate(
+        &mut self,
+        patient_id: Uuid,
+        bucket: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        json: &str,
+    ) -> Result<(), RedisError> {
+        let key = aggregate_key(patient_id, bucket);
+        self.client.hset::<_, _, _, ()>(&key, aggregate_field(from, to), json).await?;
+        self.client.expire::<_, ()>(&key, AGGREGATE_TTL_SECONDS).await?;
+        Ok(())
+    }
+
+    /// Drop cached ranges containing `at`, or every range when `at` is `None`
+    pub async fn invalidate_aggregates(
+        &mut self,
+        patient_id: Uuid,
+        bucket: &str,
+        at: Option<DateTime<Utc>>,
+    ) -> Result<usize, RedisError> {
+        let key = aggregate_key(patient_id, bucket);
+        let fields: Vec<String> = self.client.hkeys(&key).await?;
+        let stale: Vec<String> = fields
+            .into_iter()
+            .filter(|field| at.is_none_or(|at| field_covers(field, at)))
+            .collect();
+        if stale.is_empty() {
+            return Ok(0);
+        }
+        self.client.hdel::<_, _, ()>(&key, &stale).await?;
+        Ok(stale.len())
+    }
+
+    /// Start the device's cooldown for alerts at `level`, unless one is already running.
+    /// Returns whether it started, i.e. whether this alert should go out.
+    pub async fn claim_alert_cooldown(&mut self, device_id: Uuid, level: AlertLevel, seconds: u64) -> Result<bool, RedisError> {
+        let started: Option<String> = redis::cmd("SET")
+            .arg(alert_cooldown_key(device_id, level))
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(seconds)
+            .query_async(&mut self.client)
+            .await?;
+        Ok(started.is_some())
+    }
+
+    /// End every running alert cooldown of the device, so its next alert goes out at once
+    pub async fn clear_alert_cooldowns(&mut self, device_id: Uuid) -> Result<(), RedisError> {
+        let keys: Vec<String> = AlertLevel::RAISED.iter().map(|level| alert_cooldown_key(device_id, *level)).collect();
+        self.client.del::<_, ()>(keys).await
+    }
+
+    /// Count a request against `client` in the `window_seconds` window containing `now`.
+    /// Returns the previous window's count and the current one's, this request included.
+    /// Takes `&self` as it runs on every request: a clone of the connection needs no lock.
+    pub async fn count_request(&self, client: &str, window_seconds: i64, now: i64) -> Result<(u64, u64), RedisError> {
+        let window = now.div_euclid(window_seconds);
+        let current = rate_limit_key(client, window);
+        let mut conn = self.client.clone();
+        let (count, previous): (u64, Option<u64>) = redis::pipe()
+            .atomic()
+            .incr(&current, 1)
+            .expire(&current, window_seconds * 2)
+            .ignore()
+            .get(rate_limit_key(client, window - 1))
+            .query_async(&mut conn)
+            .await?;
+        Ok((previous.unwrap_or(0), count))
+    }
+
     /// Check if Redis is healthy
     pub async fn health_check(&mut self) -> Result<bool, RedisError> {
         let _: String = redis::cmd("PING").query_async(&mut self.client).await?;
@@ -76,6 +268,18 @@ impl RedisCache {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_aggregate_field_covers_half_open_range() {
+        let at = |ts: i64| DateTime::from_timestamp(ts, 0).unwrap();
+        let field = aggregate_field(at(3600), at(7200));
+        assert!(field_covers(&field, at(3600)));
+        assert!(field_covers(&field, at(7199)));
+        assert!(!field_covers(&field, at(7200)));
+        assert!(!field_covers(&field, at(0)));
+        // Unparseable entries are treated as stale
+        assert!(field_covers("garbage", at(0)));
+    }
+
     #[tokio::test]
     async fn test_redis_latest_vitals() {
         // This requires a running Redis instance
@@ -92,7 +296,7 @@ mod tests {
         let mut cache = RedisCache::new(&config).await.expect("Redis connection failed");
 
         let vitals = LatestVitals {
-            heartRate: 75,
+            heart_rate: 75,
             spo2: 98,
             temperature: 36.5,
             timestamp: 1234567890,
@@ -100,10 +304,25 @@ mod tests {
             ml_alert: None,
         };
 
-        cache.set_latest_vitals(&vitals).await.expect("Failed to set vitals");
+        let patient_id = Uuid::new_v4();
+        cache.set_latest_vitals(Some(patient_id), &vitals).await.expect("Failed to set vitals");
         
         let retrieved = cache.get_latest_vitals().await.expect("Failed to get vitals");
         assert!(retrieved.is_some());
-        assert_eq!(retrieved.unwrap().heartRate, 75);
+        assert_eq!(retrieved.unwrap().heart_rate, 75);
+
+        let retrieved = cache.get_patients_latest_vitals(&[Uuid::new_v4(), patient_id]).await.expect("Failed to get vitals");
+        assert_eq!(retrieved.map(|v| v.timestamp), Some(1234567890));
+        assert!(cache.get_patients_latest_vitals(&[Uuid::new_v4()]).await.unwrap().is_none());
+
+        // Warming fills a patient's empty key but leaves one already cached alone
+        let warmed = Uuid::new_v4();
+        let older = LatestVitals { timestamp: 1234500000, ..vitals.clone() };
+        let by_patient = [(warmed, older.clone()), (patient_id, older.clone())];
+        assert!(cache.warm_latest_vitals(std::slice::from_ref(&older), &by_patient).await.unwrap());
+        let retrieved = cache.get_patients_latest_vitals(&[warmed]).await.unwrap();
+        assert_eq!(retrieved.map(|v| v.timestamp), Some(1234500000));
+        let retrieved = cache.get_patients_latest_vitals(&[patient_id]).await.unwrap();
+        assert_eq!(retrieved.map(|v| v.timestamp), Some(1234567890));
     }
 }
@@ -0,0 +1,242 @@
+//! `replay` subcommand: re-send stored readings through the ingestion pipeline.
+//!
+//! Readings are read from the database, rebuilt as the payload the walker originally
+//! sent and signed with the walker's secret, then POSTed to `/api/device/vitals` on the
+//! target server (this one or a staging copy). Each request is signed at send time, so
+//! the replay window applies to the request while the body keeps the original timestamp.
+
+use crate::auth::device_signature;
+use crate::device_secrets::DeviceSecrets;
+use crate::models::DeviceVitalsIngest;
+use crate::normalization::restore_raw;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use sqlx::types::Json;
+use sqlx::{FromRow, PgPool};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+use std::time::Duration;
+
+pub const USAGE: &str = "usage: medhealth-backend replay --from <RFC3339> --to <RFC3339> --target <base URL> \
+[--device <device id>] [--delay-ms <ms>]";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayArgs {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Base URL of the receiving server, e.g. `https://staging.example.com`
+    pub target: String,
+    /// Limit to one walker (`devices.device_id`)
+    pub device: Option<String>,
+    /// Pause between requests
+    pub delay: Duration,
+}
+
+impl ReplayArgs {
+    /// Parse the arguments following `replay`
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut from = None;
+        let mut to = None;
+        let mut target = None;
+        let mut device = None;
+        let mut delay = Duration::ZERO;
+
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().cloned().ok_or_else(|| format!("{} needs a value", flag));
+            match flag.as_str() {
+                "--from" => from = Some(parse_time("--from", &value()?)?),
+                "--to" => to = Some(parse_time("--to", &value()?)?),
+                "--target" => target = Some(value()?.trim_end_matches('/').to_string()),
+                "--device" => device = Some(value()?),
+                "--delay-ms" => {
+                    let ms = value()?;
+                    delay = Duration::from_millis(ms.parse().map_err(|_| format!("--delay-ms: '{}' is not a number", ms))?);
+                }
+                other => return Err(format!("unknown argument '{}'", other)),
+            }
+        }
+
+        let from = from.ok_or("--from is required")?;
+        let to = to.ok_or("--to is required")?;
+        let target = target.ok_or("--target is required")?;
+        if from >= to {
+            return Err("--from must be before --to".to_string());
+        }
+        if !target.starts_with("http://") && !target.starts_with("https://") {
+            return Err(format!("--target: '{}' must be an http(s) URL", target));
+        }
+
+        Ok(Self { from, to, target, device, delay })
+    }
+}
+
+fn parse_time(flag: &str, value: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|_| format!("{}: '{}' is not an RFC 3339 timestamp", flag, value))
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReplaySummary {
+    pub sent: usize,
+    /// Requests the target answered with a non-success status
+    pub rejected: usize,
+    /// Stored readings missing a vital, which the ingestion payload cannot express
+    pub skipped: usize,
+}
+
+#[derive(Debug, FromRow)]
+struct StoredReading {
+    id: i64,
+    device_uuid: Uuid,
+    device_id: String,
+    secret_hash: String,
+    secret_ciphertext: Option<String>,
+    heart_rate: Option<i32>,
+    spo2: Option<i32>,
+    temperature: Option<f32>,
+    reading_timestamp: DateTime<Utc>,
+    steps: Option<i32>,
+    motion: Option<f32>,
+    elevation_change: Option<f32>,
+    ambient_temperature: Option<f32>,
+    humidity: Option<f32>,
+    device_metadata: Option<Json<BTreeMap<String, serde_json::Value>>>,
+    /// Values as sent, where ingestion converted them (see [`crate::normalization`])
+    raw: Option<Json<serde_json::Map<String, serde_json::Value>>>,
+}
+
+/// Replay every stored reading in `[from, to)` in timestamp order
+pub async fn replay(pool: &PgPool, secrets: &DeviceSecrets, args: &ReplayArgs) -> Result<ReplaySummary> {
+    let readings: Vec<StoredReading> = sqlx::query_as(
+        "SELECT r.id, d.id AS device_uuid, d.device_id, d.secret_hash, d.secret_ciphertext, r.heart_rate, r.spo2, r.temperature, r.reading_timestamp,
+                (r.metadata->>'steps')::int AS steps, (r.metadata->>'motion')::real AS motion,
+                (r.metadata->>'elevation_change')::real AS elevation_change, a.ambient_temperature, a.humidity,
+                r.metadata->'device' AS device_metadata, r.metadata->'raw' AS raw
+         FROM sensor_readings r JOIN devices d ON d.id = r.device_id
+         LEFT JOIN ambient_readings a ON a.sensor_reading_id = r.id
+         WHERE r.reading_timestamp >= $1 AND r.reading_timestamp < $2
+           AND ($3::text IS NULL OR d.device_id = $3)
+         ORDER BY r.reading_timestamp, r.id"
+    )
+    .bind(args.from)
+    .bind(args.to)
+    .bind(&args.device)
+    .fetch_all(pool)
+    .await
+    .context("Failed to load readings")?;
+
+    let client = reqwest::Client::new();
+    let url = format!("{}/api/device/vitals", args.target);
+    let mut summary = ReplaySummary::default();
+
+    for reading in readings {
+        let (Some(heart_rate), Some(spo2), Some(temperature)) = (reading.heart_rate, reading.spo2, reading.temperature) else {
+            summary.skipped += 1;
+            continue;
+        };
+        let body = serde_json::to_value(DeviceVitalsIngest {
+            heart_rate,
+            spo2,
+            temperature,
+            timestamp: reading.reading_timestamp.timestamp(),
+            steps: reading.steps,
+            motion: reading.motion,
+            elevation_change: reading.elevation_change,
+            ambient_temperature: reading.ambient_temperature,
+            humidity: reading.humidity,
+            metadata: reading.device_metadata.map(|m| m.0),
+        })?;
+        // The walker signed what it sent, in its own units
+        let body = match (body, &reading.raw) {
+            (serde_json::Value::Object(mut body), Some(raw)) => {
+                restore_raw(&mut body, raw);
+                serde_json::Value::Object(body)
+            }
+            (body, _) => body,
+        }
+        .to_string();
+
+        let secret = secrets
+            .secret_for(reading.device_uuid, &reading.secret_hash, reading.secret_ciphertext.as_deref())
+            .with_context(|| format!("Failed to open the secret of {}", reading.device_id))?
+            .with_context(|| format!("{} has no secret issued", reading.device_id))?;
+
+        let now = Utc::now().timestamp();
+        let resp = client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header("X-Device-Id", &reading.device_id)
+            .header("X-Timestamp", now.to_string())
+            .header("X-Signature", device_signature(&secret, now, &body))
+            .body(body)
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach {}", url))?;
+
+        if resp.status().is_success() {
+            summary.sent += 1;
+        } else {
+            summary.rejected += 1;
+            let status = resp.status();
+            eprintln!(
+                "reading {} ({} at {}): {} {}",
+                reading.id,
+                reading.device_id,
+                reading.reading_timestamp,
+                status,
+                resp.text().await.unwrap_or_default()
+            );
+            if status == reqwest::StatusCode::UNAUTHORIZED && summary.sent == 0 && summary.rejected == 1 {
+                bail!("Target rejected the device signature; check device.secret matches the target");
+            }
+        }
+
+        if !args.delay.is_zero() {
+            tokio::time::sleep(args.delay).await;
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_replay_args() {
+        let parsed = ReplayArgs::parse(&args(&[
+            "--from", "2026-10-01T00:00:00Z",
+            "--to", "2026-10-02T00:00:00+02:00",
+            "--target", "https://staging.example.com/",
+            "--device", "WALKER-7",
+            "--delay-ms", "250",
+        ]))
+        .unwrap();
+
+        assert_eq!(parsed.target, "https://staging.example.com");
+        assert_eq!(parsed.device.as_deref(), Some("WALKER-7"));
+        assert_eq!(parsed.delay, Duration::from_millis(250));
+        assert_eq!(parsed.to.to_rfc3339(), "2026-10-01T22:00:00+00:00");
+    }
+
+    #[test]
+    fn test_parse_replay_args_errors() {
+        let window = ["--from", "2026-10-02T00:00:00Z", "--to", "2026-10-01T00:00:00Z"];
+        assert!(ReplayArgs::parse(&args(&window)).unwrap_err().contains("required"));
+
+        let mut reversed = args(&window);
+        reversed.extend(args(&["--target", "http://localhost:8080"]));
+        assert_eq!(ReplayArgs::parse(&reversed).unwrap_err(), "--from must be before --to");
+
+        assert!(ReplayArgs::parse(&args(&["--from", "yesterday"])).unwrap_err().contains("RFC 3339"));
+        assert!(ReplayArgs::parse(&args(&["--target"])).unwrap_err().contains("needs a value"));
+        assert!(ReplayArgs::parse(&args(&["--since", "x"])).unwrap_err().contains("unknown argument"));
+    }
+}
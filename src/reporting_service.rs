@@ -0,0 +1,123 @@
+use chrono::{DateTime, Utc};
+use rand::distributions::{Alphanumeric, DistString};
+use sqlx::{PgConnection, PgPool};
+use std::time::Duration;
+use tracing::{error, info};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Materialized views backing the reporting endpoints
+pub const REPORTING_VIEWS: &[&str] = &["patient_daily_stats", "alert_daily_summary"];
+
+/// Refresh every reporting view without blocking readers, recording when each finished
+pub async fn refresh_views(pool: &PgPool) -> Result<(), sqlx::Error> {
+    for view in REPORTING_VIEWS {
+        // View names come from the constant above, never from input
+        sqlx::query(&format!("REFRESH MATERIALIZED VIEW CONCURRENTLY {}", view))
+            .execute(pool)
+            .await?;
+        sqlx::query(
+            "INSERT INTO reporting_view_refreshes (view_name, refreshed_at) VALUES ($1, now())
+             ON CONFLICT (view_name) DO UPDATE SET refreshed_at = EXCLUDED.refreshed_at"
+        )
+        .bind(view)
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
+
+/// When `view` was last refreshed; `None` if only the migration has populated it
+pub async fn refreshed_at(pool: &PgPool, view: &str) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    sqlx::query_scalar("SELECT refreshed_at FROM reporting_view_refreshes WHERE view_name = $1")
+        .bind(view)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Background worker refreshing the reporting views every 15 minutes
+pub fn spawn_view_refresher(pool: PgPool) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let started = std::time::Instant::now();
+            match refresh_views(&pool).await {
+                Ok(()) => info!("Refreshed reporting views in {:?}", started.elapsed()),
+                Err(e) => error!("Reporting view refresh failed: {}", e),
+            }
+        }
+    })
+}
+
+/// Group of the SQL logins issued to BI tools; it may read the `reporting` schema only
+pub const BI_GROUP: &str = "medhealth_bi";
+
+/// Sessions a BI login may hold open at once
+const BI_CONNECTION_LIMIT: u32 = 5;
+
+/// A fresh login name, `bi_` and 12 hex digits
+pub fn bi_role_name() -> String {
+    format!("bi_{}", &uuid::Uuid::new_v4().simple().to_string()[..12])
+}
+
+/// A login password of letters and digits only
+pub fn bi_password() -> String {
+    Alphanumeric.sample_string(&mut rand::rngs::OsRng, 32)
+}
+
+/// Create a BI login in `BI_GROUP`, read-only and looking in `reporting` by default.
+///
+/// Role DDL takes no bind parameters, so the name and password are spliced in; both come
+/// from the generators above and never from input.
+pub async fn create_bi_login(
+    conn: &mut PgConnection,
+    role_name: &str,
+    password: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(&format!(
+        "CREATE ROLE {} LOGIN PASSWORD '{}' VALID UNTIL '{}' CONNECTION LIMIT {} IN ROLE {}",
+        role_name,
+        password,
+        expires_at.to_rfc3339(),
+        BI_CONNECTION_LIMIT,
+        BI_GROUP
+    ))
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query(&format!("ALTER ROLE {} SET search_path = reporting", role_name))
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query(&format!("ALTER ROLE {} SET default_transaction_read_only = on", role_name))
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Drop a BI login. Its open sessions already see no rows once the credential is revoked.
+pub async fn drop_bi_login(conn: &mut PgConnection, role_name: &str) -> Result<(), sqlx::Error> {
+    // Names are read back from `reporting_credentials`, which only holds generated ones
+    sqlx::query(&format!("DROP ROLE IF EXISTS {}", role_name))
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bi_login_names_and_passwords_are_safe_to_splice() {
+        let name = bi_role_name();
+        assert_eq!(name.len(), 15);
+        assert!(name.starts_with("bi_") && name[3..].chars().all(|c| c.is_ascii_hexdigit()));
+
+        let password = bi_password();
+        assert_eq!(password.len(), 32);
+        assert!(password.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(password, bi_password());
+    }
+}
@@ -0,0 +1,342 @@
+//! Printable reports.
+//!
+//! Handlers assemble a [`Report`] (title plus headed sections of text lines, under the
+//! organization's letterhead) and render it with [`render_pdf`]. The writer is deliberately minimal: standard Helvetica fonts, A4
+//! pages, automatic wrapping and pagination, ASCII text only.
+//!
+//! Tabular exports go through [`render_csv`] instead.
+
+use crate::models::OrganizationProfile;
+use std::fmt::Write as _;
+
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 50.0;
+const BODY_SIZE: f32 = 10.0;
+const LEADING: f32 = 14.0;
+/// Characters per line for 10pt Helvetica across the printable width
+const WRAP_AT: usize = 95;
+
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub letterhead: Option<Letterhead>,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub sections: Vec<ReportSection>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReportSection {
+    pub heading: String,
+    pub lines: Vec<String>,
+}
+
+impl ReportSection {
+    pub fn new(heading: impl Into<String>) -> Self {
+        Self {
+            heading: heading.into(),
+            lines: Vec::new(),
+        }
+    }
+
+    pub fn line(&mut self, line: impl Into<String>) -> &mut Self {
+        self.lines.push(line.into());
+        self
+    }
+}
+
+/// Who issued a report, printed above its title
+#[derive(Debug, Clone, Default)]
+pub struct Letterhead {
+    pub name: String,
+    pub contact: Option<String>,
+}
+
+impl From<&OrganizationProfile> for Letterhead {
+    fn from(profile: &OrganizationProfile) -> Self {
+        Self {
+            name: profile.name.clone(),
+            contact: profile.contact_line(),
+        }
+    }
+}
+
+/// A laid-out line: font resource, size and text
+struct Line {
+    font: &'static str,
+    size: f32,
+    text: String,
+}
+
+/// Render a report as a PDF document
+pub fn render_pdf(report: &Report) -> Vec<u8> {
+    let lines = layout(report);
+    let pages = paginate(&lines);
+
+    let mut objects: Vec<String> = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        String::new(), // page tree, filled in once page object numbers are known
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>".to_string(),
+    ];
+
+    let mut kids = Vec::new();
+    for (index, page) in pages.iter().enumerate() {
+        let content = page_content(page, index + 1, pages.len());
+        objects.push(format!("<< /Length {} >>\nstream\n{}\nendstream", content.len(), content));
+        let content_ref = objects.len();
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Contents {} 0 R /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> >>",
+            PAGE_WIDTH, PAGE_HEIGHT, content_ref
+        ));
+        kids.push(format!("{} 0 R", objects.len()));
+    }
+    objects[1] = format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), kids.len());
+
+    let mut out = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        let _ = write!(out, "{} 0 obj\n{}\nendobj\n", index + 1, object);
+    }
+
+    let xref_at = out.len();
+    let _ = write!(out, "xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        let _ = writeln!(out, "{:010} 00000 n ", offset);
+    }
+    let _ = write!(
+        out,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref_at
+    );
+
+    out.into_bytes()
+}
+
+fn layout(report: &Report) -> Vec<Line> {
+    let mut lines = Vec::new();
+    if let Some(letterhead) = &report.letterhead {
+        lines.push(Line { font: "F2", size: BODY_SIZE, text: letterhead.name.clone() });
+        for contact in letterhead.contact.iter().flat_map(|c| wrap(c, WRAP_AT)) {
+            lines.push(Line { font: "F1", size: 8.0, text: contact });
+        }
+        lines.push(Line { font: "F1", size: BODY_SIZE, text: String::new() });
+    }
+
+    lines.push(Line { font: "F2", size: 16.0, text: report.title.clone() });
+    if let Some(subtitle) = &report.subtitle {
+        lines.push(Line { font: "F1", size: BODY_SIZE, text: subtitle.clone() });
+    }
+
+    for section in &report.sections {
+        lines.push(Line { font: "F1", size: BODY_SIZE, text: String::new() });
+        lines.push(Line { font: "F2", size: 12.0, text: section.heading.clone() });
+        for line in &section.lines {
+            for wrapped in wrap(line, WRAP_AT) {
+                lines.push(Line { font: "F1", size: BODY_SIZE, text: wrapped });
+            }
+        }
+    }
+
+    lines
+}
+
+fn paginate(lines: &[Line]) -> Vec<&[Line]> {
+    // Leave room for the page footer
+    let per_page = ((PAGE_HEIGHT - 2.0 * MARGIN - LEADING) / LEADING) as usize;
+    let pages: Vec<&[Line]> = lines.chunks(per_page.max(1)).collect();
+    if pages.is_empty() {
+        vec![&[]]
+    } else {
+        pages
+    }
+}
+
+fn page_content(lines: &[Line], page: usize, total: usize) -> String {
+    let mut content = String::new();
+    let mut y = PAGE_HEIGHT - MARGIN;
+    for line in lines {
+        if !line.text.is_empty() {
+            let _ = writeln!(
+                content,
+                "BT /{} {} Tf {} {} Td ({}) Tj ET",
+                line.font,
+                line.size,
+                MARGIN,
+                y,
+                escape(&line.text)
+            );
+        }
+        y -= LEADING;
+    }
+    let _ = write!(
+        content,
+        "BT /F1 8 Tf {} {} Td (Page {} of {}) Tj ET",
+        MARGIN,
+        MARGIN / 2.0,
+        page,
+        total
+    );
+    content
+}
+
+/// Escape PDF string delimiters; anything outside printable ASCII becomes `?`
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            ' '..='~' => out.push(c),
+            _ => out.push('?'),
+        }
+    }
+    out
+}
+
+/// Greedy word wrap; words longer than `width` are split
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for word in text.split_whitespace() {
+        let mut word = word;
+        while word.chars().count() > width {
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            let split = word.char_indices().nth(width).map(|(i, _)| i).unwrap_or(word.len());
+            lines.push(word[..split].to_string());
+            word = &word[split..];
+        }
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+
+    if !current.is_empty() || lines.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+/// Render rows as RFC 4180 CSV under a header row
+pub fn render_csv(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut out = String::new();
+    for row in std::iter::once(header.iter().map(|h| h.to_string()).collect::<Vec<_>>()).chain(rows.iter().cloned()) {
+        let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+        out.push_str(&fields.join(","));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+/// Quote fields that need it. Text a spreadsheet would run as a formula gets a leading `'`.
+fn csv_field(field: &str) -> String {
+    let field = if field.starts_with(['=', '+', '-', '@']) && field.parse::<f64>().is_err() {
+        format!("'{}", field)
+    } else {
+        field.to_string()
+    };
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pdf_structure_and_xref_offsets() {
+        let mut section = ReportSection::new("Bed 4 (Ada)");
+        section.line("HR 142 at 03:12 UTC");
+        let report = Report {
+            letterhead: None,
+            title: "Shift handoff".to_string(),
+            subtitle: None,
+            sections: vec![section],
+        };
+
+        let pdf = String::from_utf8(render_pdf(&report)).unwrap();
+        assert!(pdf.starts_with("%PDF-1.4"));
+        assert!(pdf.ends_with("%%EOF\n"));
+        assert!(pdf.contains("(Bed 4 \\(Ada\\)) Tj"));
+
+        // Every xref entry must point at the start of its object
+        let xref = &pdf[pdf.find("xref\n").unwrap()..];
+        for (index, entry) in xref.lines().skip(3).take_while(|l| l.ends_with(" n ")).enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(&format!("{} 0 obj", index + 1)));
+        }
+    }
+
+    #[test]
+    fn test_csv_quotes_and_defuses_fields() {
+        let csv = render_csv(
+            &["message", "minutes"],
+            &[
+                vec!["HR 142, sustained".to_string(), "-4.5".to_string()],
+                vec!["Said \"help\"\nthen fell".to_string(), String::new()],
+                vec!["=HYPERLINK(\"x\")".to_string(), "3".to_string()],
+            ],
+        );
+        assert_eq!(
+            csv,
+            "message,minutes\r\n\"HR 142, sustained\",-4.5\r\n\"Said \"\"help\"\"\nthen fell\",\r\n\"'=HYPERLINK(\"\"x\"\")\",3\r\n"
+        );
+    }
+
+    #[test]
+    fn test_long_reports_paginate() {
+        let mut section = ReportSection::new("Readings");
+        for i in 0..200 {
+            section.line(format!("Reading {}", i));
+        }
+        let report = Report {
+            letterhead: None,
+            title: "Long".to_string(),
+            subtitle: None,
+            sections: vec![section],
+        };
+
+        let pdf = String::from_utf8(render_pdf(&report)).unwrap();
+        assert!(pdf.contains("/Count 4"));
+        assert!(pdf.contains("(Page 4 of 4)"));
+    }
+
+    #[test]
+    fn test_letterhead_precedes_the_title() {
+        let report = Report {
+            letterhead: Some(Letterhead {
+                name: "St. Mary's Rehab".to_string(),
+                contact: Some("1 Main St, Springfield | +15551234567".to_string()),
+            }),
+            title: "Daily activity".to_string(),
+            ..Default::default()
+        };
+
+        let pdf = String::from_utf8(render_pdf(&report)).unwrap();
+        let name = pdf.find("(St. Mary's Rehab) Tj").unwrap();
+        let contact = pdf.find("/F1 8 Tf 50 778 Td (1 Main St, Springfield | +15551234567) Tj").unwrap();
+        assert!(name < contact && contact < pdf.find("(Daily activity) Tj").unwrap());
+    }
+
+    #[test]
+    fn test_wrap_and_escape() {
+        assert_eq!(wrap("one two three", 7), vec!["one two", "three"]);
+        assert_eq!(wrap("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(wrap("", 10), vec![""]);
+        assert_eq!(escape("SpO\u{2082} 91%"), "SpO? 91%");
+    }
+}
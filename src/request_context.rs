@@ -0,0 +1,314 @@
+//! Who a request is for and how to present its results.
+//!
+//! [`RequestContext`] joins the authenticated caller with their organization and display
+//! preferences, so handlers take one extractor instead of each re-reading the user row.
+//! Preferences are stored per user (`PUT /auth/preferences`) and can be overridden for
+//! a single request:
+//! - `Accept-Language` picks the locale (its highest weighted tag)
+//! - `Time-Zone` picks an IANA time zone for day boundaries and printed times
+//! - `Measurement-Units` picks `metric` or `imperial`
+
+use crate::errors::ApiError;
+use crate::handlers::patients::require_patient_access;
+use crate::handlers::AppState;
+use crate::middleware::authenticate_request;
+use crate::models::{Claims, UserPreferences};
+use actix_web::{dev::Payload, web, FromRequest, HttpMessage, HttpRequest};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use futures::future::LocalBoxFuture;
+use std::ops::Deref;
+use uuid::Uuid;
+
+pub const TIME_ZONE: &str = "time-zone";
+pub const MEASUREMENT_UNITS: &str = "measurement-units";
+pub const DEFAULT_LOCALE: &str = "en";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Units {
+    #[default]
+    Metric,
+    Imperial,
+}
+
+impl Units {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "metric" => Ok(Self::Metric),
+            "imperial" => Ok(Self::Imperial),
+            other => Err(format!("Unsupported units '{}'; expected metric or imperial", other)),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Metric => "metric",
+            Self::Imperial => "imperial",
+        }
+    }
+
+    /// A length stored in metres, as printed in these units
+    pub fn length(self, meters: f64) -> String {
+        match self {
+            Self::Metric => format!("{:+.1} m", meters),
+            Self::Imperial => format!("{:+.1} ft", meters * 3.280_84),
+        }
+    }
+
+    /// A temperature stored in degrees Celsius, as printed in these units
+    pub fn temperature(self, celsius: f64) -> String {
+        match self {
+            Self::Metric => format!("{:.1} °C", celsius),
+            Self::Imperial => format!("{:.1} °F", celsius * 9.0 / 5.0 + 32.0),
+        }
+    }
+}
+
+/// A locale tag such as `en` or `pt-BR`, normalized to lowercase language and uppercase region
+pub fn parse_locale(value: &str) -> Result<String, String> {
+    let value = value.trim();
+    let mut parts = value.split(['-', '_']);
+    let language = parts.next().unwrap_or_default();
+    let valid_language = (2..=3).contains(&language.len()) && language.chars().all(|c| c.is_ascii_alphabetic());
+    let subtags: Vec<&str> = parts.collect();
+    let valid_subtags = subtags
+        .iter()
+        .all(|s| (2..=8).contains(&s.len()) && s.chars().all(|c| c.is_ascii_alphanumeric()));
+    if !valid_language || !valid_subtags {
+        return Err(format!("Invalid locale '{}'", value));
+    }
+
+    let mut locale = language.to_ascii_lowercase();
+    for subtag in subtags {
+        locale.push('-');
+        if subtag.len() == 2 {
+            locale.push_str(&subtag.to_ascii_uppercase());
+        } else {
+            locale.push_str(subtag);
+        }
+    }
+    Ok(locale)
+}
+
+/// An IANA time zone name such as `Europe/London`
+pub fn parse_timezone(value: &str) -> Result<Tz, String> {
+    value
+        .trim()
+        .parse::<Tz>()
+        .map_err(|_| format!("Unknown time zone '{}'; expected an IANA name such as Europe/London", value.trim()))
+}
+
+/// The highest weighted tag of an `Accept-Language` header, ignoring `*` and tags that
+/// aren't valid locales; `None` if none is left
+pub fn preferred_locale(accept_language: &str) -> Option<String> {
+    let mut best: Option<(f32, String)> = None;
+    for item in accept_language.split(',') {
+        let mut params = item.split(';');
+        let tag = params.next().unwrap_or_default().trim();
+        let weight = params
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        let Ok(locale) = parse_locale(tag) else {
+            continue;
+        };
+        if weight > 0.0 && best.as_ref().is_none_or(|(w, _)| weight > *w) {
+            best = Some((weight, locale));
+        }
+    }
+    best.map(|(_, locale)| locale)
+}
+
+/// The caller of a JWT-protected route with their organization, locale, time zone and units,
+/// taken by handlers as `ctx: RequestContext`.
+///
+/// Fails with 401 like [`AuthenticatedUser`](crate::middleware::AuthenticatedUser), and with
+/// 400 if an override header is invalid. Built once per request and kept in its extensions.
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    pub user: Claims,
+    pub organization_id: Option<Uuid>,
+    pub locale: String,
+    pub timezone: Tz,
+    pub units: Units,
+}
+
+impl Deref for RequestContext {
+    type Target = Claims;
+
+    fn deref(&self) -> &Claims {
+        &self.user
+    }
+}
+
+impl FromRequest for RequestContext {
+    type Error = ApiError;
+    type Future = LocalBoxFuture<'static, Result<Self, ApiError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            if let Some(ctx) = req.extensions().get::<RequestContext>() {
+                return Ok(ctx.clone());
+            }
+
+            let user = authenticate_request(&req).await?;
+            let state = req
+                .app_data::<web::Data<AppState>>()
+                .ok_or_else(|| ApiError::Internal("Application state not configured".into()))?;
+            let stored = load_preferences(&state.pool, user.user_id).await?;
+
+            let ctx = RequestContext::resolve(user, stored, &req)?;
+            req.extensions_mut().insert(ctx.clone());
+            Ok(ctx)
+        })
+    }
+}
+
+impl RequestContext {
+    /// Combine stored preferences with the request's override headers. Stored values that
+    /// no longer parse (a time zone dropped from the database) fall back to the defaults.
+    pub fn resolve(user: Claims, stored: Option<UserPreferences>, req: &HttpRequest) -> Result<Self, ApiError> {
+        let header = |name: &str| -> Result<Option<&str>, ApiError> {
+            req.headers()
+                .get(name)
+                .map(|v| v.to_str().map_err(|_| ApiError::BadRequest(format!("Invalid {} header", name))))
+                .transpose()
+        };
+
+        // The token's claim, which authentication has checked is still current
+        let mut ctx = RequestContext {
+            organization_id: user.org,
+            user,
+            locale: DEFAULT_LOCALE.to_string(),
+            timezone: Tz::UTC,
+            units: Units::Metric,
+        };
+        if let Some(stored) = stored {
+            ctx.locale = parse_locale(&stored.locale).unwrap_or(ctx.locale);
+            ctx.timezone = parse_timezone(&stored.timezone).unwrap_or(ctx.timezone);
+            ctx.units = Units::parse(&stored.units).unwrap_or(ctx.units);
+        }
+
+        if let Some(locale) = header("accept-language")?.and_then(preferred_locale) {
+            ctx.locale = locale;
+        }
+        if let Some(timezone) = header(TIME_ZONE)? {
+            ctx.timezone = parse_timezone(timezone).map_err(ApiError::BadRequest)?;
+        }
+        if let Some(units) = header(MEASUREMENT_UNITS)? {
+            ctx.units = Units::parse(units).map_err(ApiError::BadRequest)?;
+        }
+        Ok(ctx)
+    }
+
+    /// Today's date where the caller is
+    pub fn today(&self) -> NaiveDate {
+        Utc::now().with_timezone(&self.timezone).date_naive()
+    }
+
+    /// The instants a local calendar day starts and ends at; 23 or 25 hours apart across
+    /// daylight saving changes
+    pub fn day_bounds(&self, date: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+        let start_of = |date: NaiveDate| {
+            let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+            // Zones that skip midnight start the day at the first instant after the gap
+            self.timezone
+                .from_local_datetime(&midnight)
+                .earliest()
+                .or_else(|| self.timezone.from_local_datetime(&(midnight + chrono::Duration::hours(1))).earliest())
+                .map(|t| t.with_timezone(&Utc))
+                .unwrap_or_else(|| midnight.and_utc())
+        };
+        (start_of(date), start_of(date.succ_opt().unwrap_or(date)))
+    }
+
+    /// An instant as a local time in the caller's zone
+    pub fn local(&self, instant: DateTime<Utc>) -> DateTime<Tz> {
+        instant.with_timezone(&self.timezone)
+    }
+
+    /// Fail unless the caller may see this patient (see [`require_patient_access`])
+    pub async fn require_patient_access(&self, state: &AppState, patient_id: Uuid) -> Result<(), ApiError> {
+        require_patient_access(state, &self.user, patient_id).await
+    }
+}
+
+/// The stored preferences of a user; `None` if the user no longer exists
+pub async fn load_preferences(pool: &sqlx::PgPool, user_id: Uuid) -> Result<Option<UserPreferences>, sqlx::Error> {
+    sqlx::query_as("SELECT organization_id, locale, timezone, units FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rbac::Role;
+    use actix_web::test::TestRequest;
+
+    fn claims() -> Claims {
+        Claims {
+            sub: "nurse@example.com".into(),
+            user_id: Uuid::nil(),
+            role: Role::Clinician,
+            org: None,
+            exp: 0,
+            iat: 0,
+            jti: Uuid::nil(),
+        }
+    }
+
+    #[test]
+    fn test_locale_parsing() {
+        assert_eq!(parse_locale("pt_br").unwrap(), "pt-BR");
+        assert_eq!(parse_locale("zh-Hant-TW").unwrap(), "zh-Hant-TW");
+        assert!(parse_locale("english").is_err());
+        assert_eq!(preferred_locale("fr;q=0.4, de-DE, *;q=0.1").as_deref(), Some("de-DE"));
+        assert_eq!(preferred_locale("*"), None);
+    }
+
+    #[test]
+    fn test_headers_override_stored_preferences() {
+        let stored = UserPreferences {
+            organization_id: None,
+            locale: "en-GB".into(),
+            timezone: "Europe/London".into(),
+            units: "imperial".into(),
+        };
+
+        let req = TestRequest::default().to_http_request();
+        let ctx = RequestContext::resolve(claims(), Some(stored.clone()), &req).unwrap();
+        assert_eq!((ctx.locale.as_str(), ctx.timezone, ctx.units), ("en-GB", Tz::Europe__London, Units::Imperial));
+
+        let req = TestRequest::default()
+            .insert_header(("Accept-Language", "es"))
+            .insert_header((TIME_ZONE, "America/Chicago"))
+            .insert_header((MEASUREMENT_UNITS, "metric"))
+            .to_http_request();
+        let ctx = RequestContext::resolve(claims(), Some(stored), &req).unwrap();
+        assert_eq!((ctx.locale.as_str(), ctx.timezone, ctx.units), ("es", Tz::America__Chicago, Units::Metric));
+
+        let req = TestRequest::default().insert_header((TIME_ZONE, "Mars/Olympus")).to_http_request();
+        assert!(matches!(RequestContext::resolve(claims(), None, &req), Err(ApiError::BadRequest(_))));
+    }
+
+    #[test]
+    fn test_day_bounds_follow_daylight_saving() {
+        let req = TestRequest::default().insert_header((TIME_ZONE, "Europe/London")).to_http_request();
+        let ctx = RequestContext::resolve(claims(), None, &req).unwrap();
+
+        let (start, end) = ctx.day_bounds(NaiveDate::from_ymd_opt(2026, 3, 29).unwrap());
+        assert_eq!(start.to_rfc3339(), "2026-03-29T00:00:00+00:00");
+        assert_eq!(end.to_rfc3339(), "2026-03-29T23:00:00+00:00");
+        assert_eq!(ctx.local(end).format("%H:%M").to_string(), "00:00");
+    }
+
+    #[test]
+    fn test_units_format() {
+        assert_eq!(Units::Metric.length(2.0), "+2.0 m");
+        assert_eq!(Units::Imperial.length(-1.0), "-3.3 ft");
+        assert_eq!(Units::Imperial.temperature(37.0), "98.6 °F");
+    }
+}
@@ -0,0 +1,65 @@
+use crate::{dead_letter, event_log};
+use sqlx::PgPool;
+use std::time::Duration;
+use tracing::{error, info};
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+/// Rows deleted per statement, keeping each purge transaction short
+const PURGE_BATCH: i64 = 5000;
+
+/// Delete sensor readings older than `days`, except for devices linked to a patient
+/// under an active legal hold. Analysis and FHIR rows cascade with their reading.
+pub async fn run_purge_cycle(pool: &PgPool, days: i64) -> Result<u64, sqlx::Error> {
+    let mut purged = 0;
+    loop {
+        let deleted = sqlx::query(
+            "DELETE FROM sensor_readings WHERE id IN (
+                 SELECT r.id FROM sensor_readings r
+                 WHERE r.reading_timestamp < now() - make_interval(days => $1::int)
+                   AND NOT EXISTS (
+                       SELECT 1 FROM devices d
+                       JOIN legal_holds h ON h.patient_id = d.patient_id AND h.released_at IS NULL
+                       WHERE d.id = r.device_id
+                   )
+                 LIMIT $2
+             )"
+        )
+        .bind(days)
+        .bind(PURGE_BATCH)
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        purged += deleted;
+        if deleted < PURGE_BATCH as u64 {
+            return Ok(purged);
+        }
+    }
+}
+
+/// Background worker applying the sensor reading retention period every hour, to the
+/// dead-letter queue and the event log as well
+pub fn spawn_purge_worker(pool: PgPool, days: i64) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            match run_purge_cycle(&pool, days).await {
+                Ok(0) => {}
+                Ok(purged) => info!("Purged {} sensor reading(s) past the {}-day retention period", purged, days),
+                Err(e) => error!("Retention purge failed: {}", e),
+            }
+            match dead_letter::purge_expired(&pool, days).await {
+                Ok(0) => {}
+                Ok(purged) => info!("Purged {} dead-lettered reading(s) past the {}-day retention period", purged, days),
+                Err(e) => error!("Dead-letter purge failed: {}", e),
+            }
+            match event_log::purge_expired(&pool, days).await {
+                Ok(0) => {}
+                Ok(purged) => info!("Purged {} event log row(s) past the {}-day retention period", purged, days),
+                Err(e) => error!("Event log purge failed: {}", e),
+            }
+        }
+    })
+}
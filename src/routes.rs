@@ -0,0 +1,295 @@
+use crate::handlers::{
+    self, admin, alerts, analytics, auth, care_plans, checkins, deployment, device, emergency, event_log,
+    failed_ingestions, fhir, fleet, gateways, integrations, legal_holds, medications, ml, notifications, on_call,
+    organizations, patients, reporting, reporting_access, rota, route_listing, schemas, threshold_profiles, transfers, vitals,
+    voice, wards, webhooks,
+};
+use crate::middleware::RequireRole;
+use crate::negotiation::fhir_json_config;
+use crate::rbac::Role;
+use actix_web::{
+    guard,
+    http::{header, Method},
+    middleware::DefaultHeaders,
+    web, HttpRequest, HttpResponse, Route,
+};
+use serde::Serialize;
+
+/// How a route authenticates its caller
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthKind {
+    Public,
+    Jwt,
+    Hmac,
+}
+
+/// Registry entry describing one method on one path, relative to its scope
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteInfo {
+    pub method: &'static str,
+    pub path: &'static str,
+    pub auth: AuthKind,
+    /// Roles allowed to call the route (empty = any authenticated caller)
+    pub roles: &'static [&'static str],
+}
+
+/// A registry entry resolved to its full mounted path
+#[derive(Debug, Clone, Serialize)]
+pub struct RegisteredRoute {
+    pub method: &'static str,
+    pub path: String,
+    pub auth: AuthKind,
+    pub roles: &'static [&'static str],
+}
+
+/// Declares a module's routes once and generates its `ROUTES` table and `configure`.
+///
+/// Each path becomes a single actix resource so unsupported methods get a 405 with an
+/// `Allow` header, plain `OPTIONS` requests are answered, and `GET` routes also serve `HEAD`.
+/// Routes listing roles are only served to callers holding them (see [`restrict`]).
+macro_rules! route_registry {
+    ($( $path:literal { $( $method:ident => $handler:path, $auth:ident, [$($role:literal),*] );+ $(;)? } )*) => {
+        pub const ROUTES: &[$crate::routes::RouteInfo] = &[
+            $( $( $crate::routes::RouteInfo {
+                method: stringify!($method),
+                path: $path,
+                auth: $crate::routes::AuthKind::$auth,
+                roles: &[$($role),*],
+            }, )+ )*
+        ];
+
+        /// Register this module's routes on an actix app or scope
+        pub fn configure(cfg: &mut actix_web::web::ServiceConfig) {
+            $(
+                cfg.service(
+                    actix_web::web::resource($path)
+                        $( .route($crate::routes::restrict(
+                            $crate::routes::method_route(stringify!($method)).to($handler),
+                            &[$($role),*],
+                        )) )+
+                        .default_service(actix_web::web::to($crate::routes::unmatched_method)),
+                );
+            )*
+        }
+    };
+}
+pub(crate) use route_registry;
+
+/// Every module registry with the scope it is mounted under (must mirror `configure`)
+pub const SCOPES: &[(&str, &[RouteInfo])] = &[
+    ("", handlers::ROUTES),
+    ("/auth", auth::ROUTES),
+    ("/api", alerts::ROUTES),
+    ("/api", analytics::ROUTES),
+    ("/api", care_plans::ROUTES),
+    ("/api", checkins::ROUTES),
+    ("/api", deployment::ROUTES),
+    ("/api", device::ROUTES),
+    ("/api", emergency::ROUTES),
+    ("/api", gateways::ROUTES),
+    ("/api", legal_holds::ROUTES),
+    ("/api", medications::ROUTES),
+    ("/api", ml::ROUTES),
+    ("/api", notifications::ROUTES),
+    ("/api", on_call::ROUTES),
+    ("/api", patients::ROUTES),
+    ("/api", reporting::ROUTES),
+    ("/api", route_listing::ROUTES),
+    ("/api", schemas::ROUTES),
+    ("/api", threshold_profiles::ROUTES),
+    ("/api", transfers::ROUTES),
+    ("/api", vitals::ROUTES),
+    ("/api", voice::ROUTES),
+    ("/api", wards::ROUTES),
+    ("/api", webhooks::ROUTES),
+    ("/api/fhir", fhir::ROUTES),
+    ("/api/admin", admin::ROUTES),
+    ("/api/admin", fleet::ROUTES),
+    ("/api/admin", event_log::ROUTES),
+    ("/api/admin", failed_ingestions::ROUTES),
+    ("/api/admin", integrations::ROUTES),
+    ("/api/admin", rota::ROUTES),
+    ("/api/admin", organizations::ROUTES),
+    ("/api/admin", reporting_access::ROUTES),
+];
+
+/// Mount the whole application: `/auth`, `/api`, `/api/fhir` and `/api/admin` scopes.
+///
+/// Exposed so integration tests and other binaries can embed the exact route tree.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.configure(handlers::configure)
+        .service(
+            web::scope("/auth")
+                .wrap(no_store())
+                .configure(auth::configure),
+        )
+        .service(
+            web::scope("/api")
+                .wrap(no_store())
+                .service(
+                    web::scope("/fhir")
+                        .app_data(fhir_json_config())
+                        .configure(fhir::configure),
+                )
+                .service(
+                    web::scope("/admin")
+                        .configure(admin::configure)
+                        .configure(fleet::configure)
+                        .configure(event_log::configure)
+                        .configure(failed_ingestions::configure)
+                        .configure(integrations::configure)
+                        .configure(rota::configure)
+                        .configure(organizations::configure)
+                        .configure(reporting_access::configure),
+                )
+                .configure(alerts::configure)
+                .configure(analytics::configure)
+                .configure(care_plans::configure)
+                .configure(checkins::configure)
+                .configure(deployment::configure)
+                .configure(device::configure)
+                .configure(emergency::configure)
+                .configure(gateways::configure)
+                .configure(legal_holds::configure)
+                .configure(medications::configure)
+                .configure(ml::configure)
+                .configure(notifications::configure)
+                .configure(on_call::configure)
+                .configure(patients::configure)
+                .configure(reporting::configure)
+                .configure(route_listing::configure)
+                .configure(schemas::configure)
+                .configure(threshold_profiles::configure)
+                .configure(transfers::configure)
+                .configure(vitals::configure)
+                .configure(voice::configure)
+                .configure(wards::configure)
+                .configure(webhooks::configure),
+        );
+}
+
+/// Responses under `/auth` and `/api` carry credentials or PHI and must never be cached
+fn no_store() -> DefaultHeaders {
+    DefaultHeaders::new().add((header::CACHE_CONTROL, "no-store"))
+}
+
+/// All registered routes with their full paths
+pub fn registered_routes() -> Vec<RegisteredRoute> {
+    SCOPES
+        .iter()
+        .flat_map(|(prefix, routes)| {
+            routes.iter().map(move |r| RegisteredRoute {
+                method: r.method,
+                path: format!("{}{}", prefix, r.path),
+                auth: r.auth,
+                roles: r.roles,
+            })
+        })
+        .collect()
+}
+
+/// Wrap a route in a [`RequireRole`] check for its registry roles, if it lists any
+pub fn restrict(route: Route, roles: &[&str]) -> Route {
+    match Role::required_by(roles) {
+        Some(role) => route.wrap(RequireRole(role)),
+        None => route,
+    }
+}
+
+/// Build a method-guarded route; GET routes also answer HEAD (actix drops the body)
+pub fn method_route(method: &str) -> Route {
+    match method {
+        "GET" => web::route().guard(guard::Any(guard::Get()).or(guard::Head())),
+        other => web::route().method(Method::from_bytes(other.as_bytes()).expect("valid HTTP method in route registry")),
+    }
+}
+
+/// Methods registered for a full path pattern, including the implicit HEAD and OPTIONS
+pub fn allowed_methods(path: &str) -> Vec<&'static str> {
+    let mut methods: Vec<&'static str> = registered_routes()
+        .into_iter()
+        .filter(|r| r.path == path)
+        .map(|r| r.method)
+        .collect();
+
+    if methods.contains(&"GET") {
+        methods.push("HEAD");
+    }
+    methods.push("OPTIONS");
+    methods
+}
+
+/// Fallback for a matched path with no route for the request method
+pub async fn unmatched_method(req: HttpRequest) -> HttpResponse {
+    let pattern = req.match_pattern().unwrap_or_else(|| req.path().to_string());
+    let allow = allowed_methods(&pattern).join(", ");
+
+    if req.method() == Method::OPTIONS {
+        return HttpResponse::NoContent()
+            .insert_header((header::ALLOW, allow))
+            .finish();
+    }
+
+    HttpResponse::MethodNotAllowed()
+        .insert_header((header::ALLOW, allow))
+        .json(serde_json::json!({"error": "Method not allowed"}))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test::{call_service, init_service, TestRequest}, App};
+
+    #[test]
+    fn test_registry_has_unique_method_path_pairs() {
+        let routes = registered_routes();
+        for (i, a) in routes.iter().enumerate() {
+            for b in &routes[i + 1..] {
+                assert!(!(a.method == b.method && a.path == b.path), "duplicate route {} {}", a.method, a.path);
+            }
+        }
+    }
+
+    #[test]
+    fn test_allowed_methods_include_implicit() {
+        assert_eq!(allowed_methods("/api/vitals/latest"), vec!["GET", "HEAD", "OPTIONS"]);
+        assert_eq!(allowed_methods("/api/device/vitals"), vec!["POST", "OPTIONS"]);
+    }
+
+    #[actix_web::test]
+    async fn test_registry_matches_mounted_scopes() {
+        let app = init_service(App::new().configure(configure)).await;
+
+        // Every registry path must resolve to a mounted resource advertising its method
+        for route in registered_routes() {
+            // Fill dynamic segments like `{device_id}` with a placeholder value
+            let uri: String = route
+                .path
+                .split('/')
+                .map(|seg| if seg.starts_with('{') { "x" } else { seg })
+                .collect::<Vec<_>>()
+                .join("/");
+            let req = TestRequest::default()
+                .method(Method::OPTIONS)
+                .uri(&uri)
+                .to_request();
+            let resp = call_service(&app, req).await;
+            assert_eq!(resp.status(), 204, "{} not mounted", route.path);
+
+            let allow = resp.headers().get(header::ALLOW).unwrap().to_str().unwrap().to_string();
+            assert!(allow.contains(route.method), "{} missing {}", route.path, route.method);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_method_not_allowed_and_no_store() {
+        let app = init_service(App::new().configure(configure)).await;
+
+        let req = TestRequest::get().uri("/api/device/vitals").to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), 405);
+        assert_eq!(resp.headers().get(header::ALLOW).unwrap(), "POST, OPTIONS");
+        assert_eq!(resp.headers().get(header::CACHE_CONTROL).unwrap(), "no-store");
+    }
+}
@@ -0,0 +1,338 @@
+//! Condition language for composite alert rules, e.g. `HR > 120 AND SpO2 < 92 AND temp > 38`.
+//!
+//! Comparisons of a vital (`hr`/`heart_rate`, `spo2`, `temp`/`temperature`) against a number
+//! are combined with `AND`, `OR`, `NOT` and parentheses; keywords and metric names are case
+//! insensitive. A comparison on a missing or zero (no signal) value is false.
+
+use crate::models::{SensorReading, VitalMetric};
+use std::fmt;
+
+/// Deepest nesting accepted, so hostile conditions cannot exhaust the stack
+const MAX_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Eq,
+    Ne,
+}
+
+impl Op {
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        [Op::Gt, Op::Ge, Op::Lt, Op::Le, Op::Eq, Op::Ne].into_iter().find(|op| op.symbol() == symbol)
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Eq => "=",
+            Op::Ne => "!=",
+        }
+    }
+
+    pub fn holds(&self, left: f32, right: f32) -> bool {
+        match self {
+            Op::Gt => left > right,
+            Op::Ge => left >= right,
+            Op::Lt => left < right,
+            Op::Le => left <= right,
+            Op::Eq => left == right,
+            Op::Ne => left != right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Compare(VitalMetric, Op, f32),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let condition = parser.or(0)?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(condition),
+            Some(token) => Err(format!("unexpected '{}'", token)),
+        }
+    }
+
+    pub fn evaluate(&self, reading: &SensorReading) -> bool {
+        match self {
+            Condition::Compare(metric, op, value) => {
+                metric.value(reading).filter(|v| *v > 0.0).is_some_and(|v| op.holds(v, *value))
+            }
+            Condition::And(a, b) => a.evaluate(reading) && b.evaluate(reading),
+            Condition::Or(a, b) => a.evaluate(reading) || b.evaluate(reading),
+            Condition::Not(a) => !a.evaluate(reading),
+        }
+    }
+
+    /// Every metric the condition refers to, in order of first use
+    pub fn metrics(&self) -> Vec<VitalMetric> {
+        let mut metrics = Vec::new();
+        self.collect_metrics(&mut metrics);
+        metrics
+    }
+
+    fn collect_metrics(&self, metrics: &mut Vec<VitalMetric>) {
+        match self {
+            Condition::Compare(metric, _, _) => {
+                if !metrics.contains(metric) {
+                    metrics.push(*metric);
+                }
+            }
+            Condition::And(a, b) | Condition::Or(a, b) => {
+                a.collect_metrics(metrics);
+                b.collect_metrics(metrics);
+            }
+            Condition::Not(a) => a.collect_metrics(metrics),
+        }
+    }
+}
+
+/// Canonical form, which parses back to the same condition
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::Compare(metric, op, value) => write!(f, "{} {} {}", metric_name(*metric), op.symbol(), value),
+            Condition::And(a, b) => write!(f, "{} AND {}", Operand(a, "AND"), Operand(b, "AND")),
+            Condition::Or(a, b) => write!(f, "{} OR {}", Operand(a, "OR"), Operand(b, "OR")),
+            Condition::Not(a) => write!(f, "NOT {}", Operand(a, "NOT")),
+        }
+    }
+}
+
+/// A subcondition, parenthesised unless precedence already groups it
+struct Operand<'a>(&'a Condition, &'static str);
+
+impl fmt::Display for Operand<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.0, self.1) {
+            (Condition::Compare(..) | Condition::Not(..), _)
+            | (Condition::And(..), "AND" | "OR")
+            | (Condition::Or(..), "OR") => write!(f, "{}", self.0),
+            _ => write!(f, "({})", self.0),
+        }
+    }
+}
+
+pub fn metric_name(metric: VitalMetric) -> &'static str {
+    match metric {
+        VitalMetric::HeartRate => "heart_rate",
+        VitalMetric::Spo2 => "spo2",
+        VitalMetric::Temperature => "temperature",
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Metric(VitalMetric),
+    Op(Op),
+    Number(f32),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Metric(metric) => write!(f, "{}", metric_name(*metric)),
+            Token::Op(op) => write!(f, "{}", op.symbol()),
+            Token::Number(n) => write!(f, "{}", n),
+            Token::And => write!(f, "AND"),
+            Token::Or => write!(f, "OR"),
+            Token::Not => write!(f, "NOT"),
+            Token::Open => write!(f, "("),
+            Token::Close => write!(f, ")"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let (token, len) = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => (Token::Open, 1),
+            ')' => (Token::Close, 1),
+            '>' if next == Some('=') => (Token::Op(Op::Ge), 2),
+            '>' => (Token::Op(Op::Gt), 1),
+            '<' if next == Some('=') => (Token::Op(Op::Le), 2),
+            '<' => (Token::Op(Op::Lt), 1),
+            '=' if next == Some('=') => (Token::Op(Op::Eq), 2),
+            '=' => (Token::Op(Op::Eq), 1),
+            '!' if next == Some('=') => (Token::Op(Op::Ne), 2),
+            '!' => (Token::Not, 1),
+            '&' if next == Some('&') => (Token::And, 2),
+            '|' if next == Some('|') => (Token::Or, 2),
+            c if c.is_ascii_digit() || c == '.' || c == '-' => {
+                let len = chars[i + 1..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit() || **c == '.')
+                    .count()
+                    + 1;
+                let text: String = chars[i..i + len].iter().collect();
+                let number = text.parse::<f32>().map_err(|_| format!("invalid number '{}'", text))?;
+                (Token::Number(number), len)
+            }
+            c if c.is_ascii_alphabetic() => {
+                let len = chars[i..].iter().take_while(|c| c.is_ascii_alphanumeric() || **c == '_').count();
+                let word: String = chars[i..i + len].iter().collect::<String>().to_ascii_lowercase();
+                let token = match word.as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    "hr" | "heart_rate" => Token::Metric(VitalMetric::HeartRate),
+                    "spo2" => Token::Metric(VitalMetric::Spo2),
+                    "temp" | "temperature" => Token::Metric(VitalMetric::Temperature),
+                    _ => return Err(format!("unknown word '{}'", word)),
+                };
+                (token, len)
+            }
+            other => return Err(format!("unexpected character '{}'", other)),
+        };
+        tokens.push(token);
+        i += len;
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, expected: &Token) -> bool {
+        if self.tokens.get(self.pos) == Some(expected) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self, depth: usize) -> Result<Condition, String> {
+        let mut condition = self.and(depth)?;
+        while self.eat(&Token::Or) {
+            condition = Condition::Or(Box::new(condition), Box::new(self.and(depth)?));
+        }
+        Ok(condition)
+    }
+
+    fn and(&mut self, depth: usize) -> Result<Condition, String> {
+        let mut condition = self.unary(depth)?;
+        while self.eat(&Token::And) {
+            condition = Condition::And(Box::new(condition), Box::new(self.unary(depth)?));
+        }
+        Ok(condition)
+    }
+
+    fn unary(&mut self, depth: usize) -> Result<Condition, String> {
+        if depth > MAX_DEPTH {
+            return Err("condition is nested too deeply".into());
+        }
+        match self.next() {
+            Some(Token::Not) => Ok(Condition::Not(Box::new(self.unary(depth + 1)?))),
+            Some(Token::Open) => {
+                let condition = self.or(depth + 1)?;
+                if !self.eat(&Token::Close) {
+                    return Err("missing ')'".into());
+                }
+                Ok(condition)
+            }
+            Some(Token::Metric(metric)) => {
+                let Some(Token::Op(op)) = self.next() else {
+                    return Err(format!("expected a comparison after '{}'", metric_name(metric)));
+                };
+                let Some(Token::Number(value)) = self.next() else {
+                    return Err(format!("expected a number after '{} {}'", metric_name(metric), op.symbol()));
+                };
+                Ok(Condition::Compare(metric, op, value))
+            }
+            Some(token) => Err(format!("unexpected '{}'", token)),
+            None => Err("condition is incomplete".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn reading(hr: i32, spo2: i32, temp: f32) -> SensorReading {
+        SensorReading {
+            id: 1,
+            device_id: Uuid::new_v4(),
+            heart_rate: Some(hr),
+            spo2: Some(spo2),
+            temperature: Some(temp),
+            reading_timestamp: Utc::now(),
+            received_at: Utc::now(),
+            quality_score: None,
+            metadata: serde_json::json!({}),
+        }
+    }
+
+    #[test]
+    fn test_parse_and_evaluate_with_precedence() {
+        let sepsis = Condition::parse("HR > 120 AND SpO2 < 92 AND temp > 38").unwrap();
+        assert!(sepsis.evaluate(&reading(125, 90, 38.4)));
+        assert!(!sepsis.evaluate(&reading(125, 95, 38.4)));
+        assert_eq!(sepsis.metrics(), vec![VitalMetric::HeartRate, VitalMetric::Spo2, VitalMetric::Temperature]);
+
+        // AND binds tighter than OR
+        let either = Condition::parse("hr >= 150 or spo2 <= 90 and not (temp < 37)").unwrap();
+        assert!(either.evaluate(&reading(150, 98, 36.5)));
+        assert!(either.evaluate(&reading(80, 88, 37.5)));
+        assert!(!either.evaluate(&reading(80, 88, 36.5)));
+        assert_eq!(either.to_string(), "heart_rate >= 150 OR spo2 <= 90 AND NOT temperature < 37");
+        assert_eq!(Condition::parse(&either.to_string()).unwrap(), either);
+    }
+
+    #[test]
+    fn test_missing_values_never_match() {
+        let low = Condition::parse("spo2 < 92").unwrap();
+        assert!(!low.evaluate(&reading(80, 0, 36.8)));
+        assert!(Condition::parse("NOT spo2 >= 92").unwrap().evaluate(&reading(80, 0, 36.8)));
+    }
+
+    #[test]
+    fn test_rejects_malformed_conditions() {
+        for bad in ["", "hr >", "hr > 120 AND", "(hr > 120", "hr > 120)", "pulse > 120", "hr 120", "hr > 1.2.3"] {
+            assert!(Condition::parse(bad).is_err(), "{:?} should not parse", bad);
+        }
+        let deep = format!("{}hr > 1{}", "(".repeat(40), ")".repeat(40));
+        assert!(Condition::parse(&deep).is_err());
+    }
+}
@@ -0,0 +1,530 @@
+//! `seed` subcommand: fill an empty database with a demo dataset.
+//!
+//! The `demo` profile creates two organizations with a user of every role, a ward,
+//! patients and their walkers, then `--days` of readings every ten minutes. A few
+//! episodes per patient push a vital out of range; those readings are analysed as
+//! anomalies and raise the vitals alerts staff would see, with check-in notes and alert
+//! threads alongside. A fixed `--seed` produces the same dataset.
+//!
+//! The demo users are admins and staff of every role, so seeding refuses a database that
+//! already has users unless `--force` is given. Without `--password` they get a random one,
+//! printed once.
+//!
+//! Walkers are registered without a secret of their own, so `bin/device-sim` can keep
+//! them sending with the shared `device.secret`.
+
+use crate::phi_crypto::PhiCipher;
+use crate::reporting_service;
+use anyhow::{bail, Context, Result};
+use argon2::password_hash::SaltString;
+use argon2::{Argon2, PasswordHasher};
+use chrono::{DateTime, Duration, DurationRound, NaiveDate, Timelike, Utc};
+use rand::rngs::{OsRng, StdRng};
+use rand::distributions::{Alphanumeric, DistString};
+use rand::{Rng, SeedableRng};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub const USAGE: &str = "usage: medhealth-backend seed --profile demo [--days <n>] [--seed <n>] [--password <password>] [--force]";
+
+/// Demo users share the domain, which is how an already seeded database is recognised
+pub const EMAIL_DOMAIN: &str = "demo.medhealth.example";
+/// Length of the password generated when `--password` is not given
+const GENERATED_PASSWORD_LENGTH: usize = 20;
+/// Readings are stored at this spacing rather than the firmware's rate, to keep the dataset small
+const READING_INTERVAL_MINUTES: i64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeedProfile {
+    Demo,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeedArgs {
+    pub profile: SeedProfile,
+    /// Days of readings, ending now
+    pub days: u32,
+    pub seed: Option<u64>,
+    /// Password of every demo user; random unless `--password` is given
+    pub password: String,
+    /// Seed even though the database already has users
+    pub force: bool,
+}
+
+impl SeedArgs {
+    /// Parse the arguments following `seed`
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut profile = None;
+        let mut days = 30;
+        let mut seed = None;
+        let mut password = None;
+        let mut force = false;
+
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().cloned().ok_or_else(|| format!("{} needs a value", flag));
+            match flag.as_str() {
+                "--profile" => {
+                    profile = Some(match value()?.as_str() {
+                        "demo" => SeedProfile::Demo,
+                        other => return Err(format!("--profile: unknown profile '{}'", other)),
+                    })
+                }
+                "--days" => {
+                    let n = value()?;
+                    days = n.parse().map_err(|_| format!("--days: '{}' is not a number", n))?;
+                }
+                "--seed" => {
+                    let n = value()?;
+                    seed = Some(n.parse().map_err(|_| format!("--seed: '{}' is not a number", n))?);
+                }
+                "--password" => password = Some(value()?),
+                "--force" => force = true,
+                other => return Err(format!("unknown argument '{}'", other)),
+            }
+        }
+
+        let profile = profile.ok_or("--profile is required")?;
+        if !(1..=365).contains(&days) {
+            return Err("--days must be between 1 and 365".to_string());
+        }
+        let password = match password {
+            Some(password) if password.len() < 8 => return Err("--password must be at least 8 characters".to_string()),
+            Some(password) => password,
+            None => Alphanumeric.sample_string(&mut OsRng, GENERATED_PASSWORD_LENGTH),
+        };
+
+        Ok(Self { profile, days, seed, password, force })
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SeedSummary {
+    pub organizations: usize,
+    pub users: usize,
+    pub patients: usize,
+    pub devices: usize,
+    pub readings: usize,
+    pub anomalies: usize,
+    pub alerts: usize,
+    pub notes: usize,
+}
+
+/// A vital pushed out of range for a while, as the ML analysis would flag it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anomaly {
+    Tachycardia,
+    Desaturation,
+    Fever,
+}
+
+impl Anomaly {
+    const ALL: [Anomaly; 3] = [Anomaly::Tachycardia, Anomaly::Desaturation, Anomaly::Fever];
+
+    fn message(self) -> &'static str {
+        match self {
+            Anomaly::Tachycardia => "Heart rate above 120 bpm",
+            Anomaly::Desaturation => "SpO2 below 90%",
+            Anomaly::Fever => "Temperature above 38 °C",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DemoReading {
+    pub at: DateTime<Utc>,
+    pub heart_rate: i32,
+    pub spo2: i32,
+    pub temperature: f32,
+    pub steps: i32,
+    pub anomaly: Option<Anomaly>,
+}
+
+/// Consecutive readings out of range with the same anomaly
+#[derive(Debug, Clone, PartialEq)]
+pub struct Episode {
+    pub anomaly: Anomaly,
+    pub start: DateTime<Utc>,
+    pub readings: usize,
+}
+
+struct Person {
+    role: &'static str,
+    name: &'static str,
+}
+
+const STAFF: [Person; 4] = [
+    Person { role: "admin", name: "admin" },
+    Person { role: "clinician", name: "nurse" },
+    Person { role: "viewer", name: "family" },
+    Person { role: "device_manager", name: "technician" },
+];
+
+struct DemoOrganization {
+    name: &'static str,
+    slug: &'static str,
+    ward: &'static str,
+    patients: [(&'static str, &'static str, &'static [&'static str]); 3],
+}
+
+const ORGANIZATIONS: [DemoOrganization; 2] = [
+    DemoOrganization {
+        name: "Riverside Care Home (demo)",
+        slug: "riverside",
+        ward: "Riverside East Wing (demo)",
+        patients: [
+            ("Margaret Hill", "1938-04-12", &["COPD", "Hypertension"]),
+            ("Arthur Webb", "1942-11-03", &["Atrial fibrillation"]),
+            ("Doris Kaye", "1935-07-21", &["Osteoarthritis"]),
+        ],
+    },
+    DemoOrganization {
+        name: "Hillcrest Clinic (demo)",
+        slug: "hillcrest",
+        ward: "Hillcrest Rehabilitation (demo)",
+        patients: [
+            ("Walter Brooks", "1940-02-28", &["Post hip replacement"]),
+            ("Edith Lane", "1944-09-15", &["Heart failure", "Type 2 diabetes"]),
+            ("Harold Finch", "1939-12-01", &["Parkinson's disease"]),
+        ],
+    },
+];
+
+const CHECKIN_NOTES: [&str; 4] = [
+    "Slept poorly, a little breathless on the stairs",
+    "Good walk to the garden and back",
+    "Felt dizzy after lunch, sat down for a while",
+    "Knee stiff this morning, easing by the afternoon",
+];
+
+/// Readings every ten minutes over `days` ending at `end`, with two or three episodes
+/// placed at random, away from the edges of the window
+pub fn demo_readings(rng: &mut StdRng, end: DateTime<Utc>, days: u32) -> (Vec<DemoReading>, Vec<Episode>) {
+    let interval = Duration::minutes(READING_INTERVAL_MINUTES);
+    let count = (Duration::days(days as i64).num_minutes() / READING_INTERVAL_MINUTES) as usize;
+    let start = end - interval * count as i32;
+
+    let resting_hr = rng.gen_range(62..78);
+    let resting_spo2 = rng.gen_range(94..98);
+    let mut readings: Vec<DemoReading> = (0..count)
+        .map(|i| {
+            let at = start + interval * i as i32;
+            // Up and about during the day, resting at night
+            let awake = (7..22).contains(&at.hour());
+            let exertion = if awake { rng.gen_range(0..18) } else { 0 };
+            DemoReading {
+                at,
+                heart_rate: resting_hr + exertion + rng.gen_range(-3..=3),
+                spo2: (resting_spo2 + rng.gen_range(-1..=1)).min(100),
+                temperature: 36.6 + rng.gen_range(-0.3..0.3),
+                steps: if awake { rng.gen_range(0..120) } else { 0 },
+                anomaly: None,
+            }
+        })
+        .collect();
+
+    let mut episodes = Vec::new();
+    let margin = count / 10;
+    for _ in 0..rng.gen_range(2..=3) {
+        let anomaly = Anomaly::ALL[rng.gen_range(0..Anomaly::ALL.len())];
+        let length = rng.gen_range(3..=8);
+        let first = rng.gen_range(margin..count - length - margin);
+        if readings[first..first + length].iter().any(|r| r.anomaly.is_some()) {
+            continue;
+        }
+        for reading in &mut readings[first..first + length] {
+            match anomaly {
+                Anomaly::Tachycardia => reading.heart_rate = rng.gen_range(125..150),
+                Anomaly::Desaturation => reading.spo2 = rng.gen_range(84..90),
+                Anomaly::Fever => reading.temperature = 38.2 + rng.gen_range(0.0..1.0),
+            }
+            reading.anomaly = Some(anomaly);
+        }
+        episodes.push(Episode { anomaly, start: readings[first].at, readings: length });
+    }
+    episodes.sort_by_key(|e| e.start);
+
+    (readings, episodes)
+}
+
+fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| anyhow::anyhow!("Password hashing failed: {}", e))
+}
+
+/// Seed the demo dataset in one transaction. Refuses to run twice on the same database, and
+/// on one that already has users unless `args.force`.
+pub async fn seed(pool: &PgPool, phi: &PhiCipher, args: &SeedArgs) -> Result<SeedSummary> {
+    let seeded: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM users WHERE email LIKE '%@' || $1)")
+        .bind(EMAIL_DOMAIN)
+        .fetch_one(pool)
+        .await?;
+    if seeded {
+        bail!("The demo dataset is already seeded (users @{} exist)", EMAIL_DOMAIN);
+    }
+    let users: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users").fetch_one(pool).await?;
+    if users > 0 && !args.force {
+        bail!(
+            "The database already has {} user(s); the demo dataset is meant for an empty one. \
+             Pass --force to add it anyway",
+            users
+        );
+    }
+
+    let mut rng = StdRng::seed_from_u64(args.seed.unwrap_or_else(|| OsRng.gen()));
+    let end = Utc::now().duration_trunc(Duration::minutes(READING_INTERVAL_MINUTES))?;
+    let password_hash = hash_password(&args.password)?;
+    let mut summary = SeedSummary::default();
+    let mut notes = Vec::new();
+
+    let mut tx = pool.begin().await?;
+
+    // Deployment-wide administrator, outside any organization
+    sqlx::query("INSERT INTO users (email, password_hash, role) VALUES ($1, $2, 'admin')")
+        .bind(format!("superadmin@{}", EMAIL_DOMAIN))
+        .bind(&password_hash)
+        .execute(&mut *tx)
+        .await?;
+    summary.users += 1;
+
+    for org in &ORGANIZATIONS {
+        let organization_id: Uuid = sqlx::query_scalar("INSERT INTO organizations (name) VALUES ($1) RETURNING id")
+            .bind(org.name)
+            .fetch_one(&mut *tx)
+            .await
+            .with_context(|| format!("Failed to create {}", org.name))?;
+        summary.organizations += 1;
+
+        let mut staff = Vec::new();
+        for person in &STAFF {
+            let id: Uuid = sqlx::query_scalar(
+                "INSERT INTO users (email, password_hash, role, organization_id)
+                 VALUES ($1, $2, $3::user_role, $4) RETURNING id"
+            )
+            .bind(format!("{}.{}@{}", person.name, org.slug, EMAIL_DOMAIN))
+            .bind(&password_hash)
+            .bind(person.role)
+            .bind(organization_id)
+            .fetch_one(&mut *tx)
+            .await?;
+            staff.push((person.role, id));
+            summary.users += 1;
+        }
+        let user = |role: &str| staff.iter().find(|(r, _)| *r == role).map(|(_, id)| *id).unwrap_or_default();
+        let (nurse, family) = (user("clinician"), user("viewer"));
+
+        let ward_id: Uuid = sqlx::query_scalar("INSERT INTO wards (name, organization_id) VALUES ($1, $2) RETURNING id")
+            .bind(org.ward)
+            .bind(organization_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        for (number, (name, born, diagnoses)) in org.patients.iter().enumerate() {
+            let patient_id: Uuid = sqlx::query_scalar(
+                "INSERT INTO patients (display_name, date_of_birth, diagnoses, organization_id, ward_id, created_by)
+                 VALUES ($1, $2, $3, $4, $5, $6) RETURNING id"
+            )
+            .bind(*name)
+            .bind(NaiveDate::parse_from_str(born, "%Y-%m-%d")?)
+            .bind(*diagnoses)
+            .bind(organization_id)
+            .bind(ward_id)
+            .bind(nurse)
+            .fetch_one(&mut *tx)
+            .await?;
+            summary.patients += 1;
+
+            // The nurse cares for everyone on the ward, the family member for the first patient
+            sqlx::query("INSERT INTO patient_caregivers (patient_id, user_id) SELECT $1, unnest($2::uuid[])")
+                .bind(patient_id)
+                .bind(if number == 0 { vec![nurse, family] } else { vec![nurse] })
+                .execute(&mut *tx)
+                .await?;
+
+            let device_id: Uuid = sqlx::query_scalar(
+                "INSERT INTO devices (device_id, device_name, secret_hash, patient_id, organization_id, claimed_by, claimed_at, last_seen_at)
+                 VALUES ($1, $2, '', $3, $4, $5, $6, $7) RETURNING id"
+            )
+            .bind(format!("DEMO-{}-{:03}", org.slug.to_uppercase(), number + 1))
+            .bind(format!("{}'s walker", name))
+            .bind(patient_id)
+            .bind(organization_id)
+            .bind(nurse)
+            .bind(end - Duration::days(args.days as i64))
+            .bind(end)
+            .fetch_one(&mut *tx)
+            .await?;
+            summary.devices += 1;
+
+            let (readings, episodes) = demo_readings(&mut rng, end, args.days);
+            insert_readings(&mut tx, device_id, &readings).await?;
+            summary.readings += readings.len();
+            summary.anomalies += readings.iter().filter(|r| r.anomaly.is_some()).count();
+
+            // Earlier episodes were dealt with; the latest is still waiting for someone
+            for (i, episode) in episodes.iter().enumerate() {
+                let latest = i + 1 == episodes.len();
+                let alert_id: Uuid = sqlx::query_scalar(
+                    "INSERT INTO alerts (patient_id, device_id, kind, level, message, details, raised_at,
+                                         status, acknowledged_at, acknowledged_by, resolved_at, resolved_by, resolution)
+                     VALUES ($1, $2, 'vitals', 'high', $3, $4, $5,
+                             $6::alert_status, $7, $8, $7, $8, $9)
+                     RETURNING id"
+                )
+                .bind(patient_id)
+                .bind(device_id)
+                .bind(episode.anomaly.message())
+                .bind(serde_json::json!({"seeded": true, "readings": episode.readings}))
+                .bind(episode.start)
+                .bind(if latest { "open" } else { "resolved" })
+                .bind((!latest).then(|| episode.start + Duration::minutes(35)))
+                .bind((!latest).then_some(nurse))
+                .bind((!latest).then_some("Checked on the patient; settled after rest"))
+                .fetch_one(&mut *tx)
+                .await?;
+                summary.alerts += 1;
+
+                if !latest {
+                    sqlx::query(
+                        "INSERT INTO alert_messages (alert_id, author_id, body, created_at) VALUES ($1, $2, $3, $4)"
+                    )
+                    .bind(alert_id)
+                    .bind(nurse)
+                    .bind("Seen at the bedside, repeat observations normal. Will keep an eye on it tonight.")
+                    .bind(episode.start + Duration::minutes(30))
+                    .execute(&mut *tx)
+                    .await?;
+                    summary.notes += 1;
+                }
+            }
+
+            for day in (1..args.days.min(7)).rev() {
+                notes.push((patient_id, family, end - Duration::days(day as i64), rng.gen_range(0..CHECKIN_NOTES.len())));
+            }
+        }
+    }
+
+    tx.commit().await?;
+
+    // Sealed under each organization's key, which needs the patients committed
+    for (patient_id, submitted_by, submitted_at, note) in notes {
+        let sealed = phi.seal_for_patient(patient_id, CHECKIN_NOTES[note]).await?;
+        sqlx::query(
+            "INSERT INTO checkins (patient_id, submitted_by, pain_score, fatigue, notes, submitted_at)
+             VALUES ($1, $2, $3, $4, $5, $6)"
+        )
+        .bind(patient_id)
+        .bind(submitted_by)
+        .bind(rng.gen_range(0..5))
+        .bind(rng.gen_range(1..6))
+        .bind(sealed)
+        .bind(submitted_at)
+        .execute(pool)
+        .await?;
+        summary.notes += 1;
+    }
+
+    reporting_service::refresh_views(pool).await.context("Failed to refresh the reporting views")?;
+
+    Ok(summary)
+}
+
+/// One walker's readings and their analysis, in two statements
+async fn insert_readings(tx: &mut sqlx::PgConnection, device_id: Uuid, readings: &[DemoReading]) -> Result<()> {
+    let at: Vec<DateTime<Utc>> = readings.iter().map(|r| r.at).collect();
+    let heart_rate: Vec<i32> = readings.iter().map(|r| r.heart_rate).collect();
+    let spo2: Vec<i32> = readings.iter().map(|r| r.spo2).collect();
+    let temperature: Vec<f32> = readings.iter().map(|r| r.temperature).collect();
+    let steps: Vec<i32> = readings.iter().map(|r| r.steps).collect();
+    let level: Vec<&str> = readings.iter().map(|r| if r.anomaly.is_some() { "high" } else { "none" }).collect();
+
+    sqlx::query(
+        "INSERT INTO sensor_readings (device_id, heart_rate, spo2, temperature, reading_timestamp, received_at, quality_score, metadata)
+         SELECT $1, r.heart_rate, r.spo2, r.temperature, r.at, r.at, 0.95, jsonb_build_object('steps', r.steps)
+         FROM unnest($2::timestamptz[], $3::int[], $4::int[], $5::real[], $6::int[])
+              AS r(at, heart_rate, spo2, temperature, steps)"
+    )
+    .bind(device_id)
+    .bind(&at)
+    .bind(&heart_rate)
+    .bind(&spo2)
+    .bind(&temperature)
+    .bind(&steps)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO ml_analysis (sensor_reading_id, anomaly_detected, anomaly_score, classification, alert_level, analyzed_at)
+         SELECT s.id, a.level <> 'none',
+                CASE WHEN a.level = 'none' THEN 0.1 ELSE 0.85 END,
+                CASE WHEN a.level = 'none' THEN 'normal' ELSE 'warning' END::reading_classification,
+                a.level::alert_level, s.reading_timestamp
+         FROM sensor_readings s
+         JOIN unnest($2::timestamptz[], $3::text[]) AS a(at, level) ON a.at = s.reading_timestamp
+         WHERE s.device_id = $1"
+    )
+    .bind(device_id)
+    .bind(&at)
+    .bind(&level)
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_seed_args() {
+        let parsed = SeedArgs::parse(&args(&["--profile", "demo", "--days", "14", "--seed", "42"])).unwrap();
+        assert_eq!(parsed.profile, SeedProfile::Demo);
+        assert_eq!(parsed.days, 14);
+        assert_eq!(parsed.seed, Some(42));
+        assert_eq!(parsed.password.len(), GENERATED_PASSWORD_LENGTH);
+        assert!(!parsed.force);
+        let again = SeedArgs::parse(&args(&["--profile", "demo"])).unwrap();
+        assert_ne!(again.password, parsed.password);
+
+        let given = SeedArgs::parse(&args(&["--profile", "demo", "--password", "Secret123!", "--force"])).unwrap();
+        assert_eq!(given.password, "Secret123!");
+        assert!(given.force);
+
+        assert_eq!(SeedArgs::parse(&args(&["--days", "3"])).unwrap_err(), "--profile is required");
+        assert!(SeedArgs::parse(&args(&["--profile", "huge"])).unwrap_err().contains("unknown profile"));
+        assert!(SeedArgs::parse(&args(&["--profile", "demo", "--days", "0"])).unwrap_err().contains("between"));
+        assert!(SeedArgs::parse(&args(&["--profile", "demo", "--password", "short"])).is_err());
+    }
+
+    #[test]
+    fn test_demo_readings_are_reproducible_and_hold_episodes() {
+        let end = Utc.with_ymd_and_hms(2026, 10, 1, 0, 0, 0).unwrap();
+        let (readings, episodes) = demo_readings(&mut StdRng::seed_from_u64(7), end, 30);
+        assert_eq!(readings.len(), 30 * 24 * 6);
+        assert_eq!(readings.last().unwrap().at, end - Duration::minutes(READING_INTERVAL_MINUTES));
+        assert!(readings.iter().all(|r| (0..=300).contains(&r.heart_rate) && r.spo2 <= 100));
+        assert!(readings.iter().all(|r| (25.0..=45.0).contains(&r.temperature)));
+
+        assert!(!episodes.is_empty());
+        let flagged = readings.iter().filter(|r| r.anomaly.is_some()).count();
+        assert_eq!(flagged, episodes.iter().map(|e| e.readings).sum::<usize>());
+        for reading in readings.iter().filter(|r| r.anomaly.is_some()) {
+            match reading.anomaly.unwrap() {
+                Anomaly::Tachycardia => assert!(reading.heart_rate > 120),
+                Anomaly::Desaturation => assert!(reading.spo2 < 90),
+                Anomaly::Fever => assert!(reading.temperature > 38.0),
+            }
+        }
+
+        assert_eq!(demo_readings(&mut StdRng::seed_from_u64(7), end, 30).0, readings);
+    }
+}
@@ -0,0 +1,266 @@
+use crate::models::{RestPeriod, SleepSummary};
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use sqlx::{FromRow, PgPool};
+use std::time::Duration as StdDuration;
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// Shortest still, low-HR stretch reported as a rest period
+pub const MIN_REST_MINUTES: i64 = 30;
+/// Rest periods at least this long are classified as sleep
+pub const SLEEP_MIN_MINUTES: i64 = 90;
+/// A longer silence between readings ends the current period (the walker may be off or away)
+const MAX_GAP_MINUTES: i64 = 15;
+/// Resting ceiling as a share of the night's 75th-percentile (awake) heart rate
+const REST_HR_RATIO: f32 = 0.85;
+/// Heart-rate samples needed before a night is scored at all
+const MIN_HR_SAMPLES: usize = 20;
+
+const SUMMARY_INTERVAL: StdDuration = StdDuration::from_secs(3600);
+
+/// One reading reduced to what rest detection needs
+#[derive(Debug, Clone, FromRow)]
+pub struct RestSample {
+    pub at: DateTime<Utc>,
+    pub heart_rate: Option<i32>,
+    pub steps: Option<i32>,
+}
+
+/// Bounds of the night ending on `date`: noon the day before to noon that day (UTC)
+pub fn night_window(date: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+    let end = date.and_hms_opt(12, 0, 0).unwrap_or_default().and_utc();
+    (end - Duration::days(1), end)
+}
+
+/// Heart rate at or below which a still reading counts as resting, relative to the
+/// patient's own awake rate so it adapts to beta-blockers, fitness and so on
+pub fn rest_threshold(samples: &[RestSample]) -> Option<f32> {
+    let mut rates: Vec<i32> = samples.iter().filter_map(|s| s.heart_rate).collect();
+    if rates.len() < MIN_HR_SAMPLES {
+        return None;
+    }
+    rates.sort_unstable();
+    let p75 = rates[(rates.len() * 3 / 4).min(rates.len() - 1)];
+    Some(p75 as f32 * REST_HR_RATIO)
+}
+
+/// Find sustained stretches of low heart rate with no steps in time-ordered samples.
+/// Readings without a heart rate extend a period but never start one on their own.
+pub fn detect_rest_periods(samples: &[RestSample], threshold: f32) -> Vec<RestPeriod> {
+    let mut periods = Vec::new();
+    let mut current: Vec<&RestSample> = Vec::new();
+
+    for sample in samples {
+        let still = sample.steps.unwrap_or(0) == 0;
+        let low_hr = sample.heart_rate.is_none_or(|hr| hr as f32 <= threshold);
+        let gap = current
+            .last()
+            .is_some_and(|last| sample.at - last.at > Duration::minutes(MAX_GAP_MINUTES));
+
+        if gap || !(still && low_hr) {
+            close_period(&mut current, &mut periods);
+        }
+        if still && low_hr && (!current.is_empty() || sample.heart_rate.is_some()) {
+            current.push(sample);
+        }
+    }
+    close_period(&mut current, &mut periods);
+
+    periods
+}
+
+fn close_period(current: &mut Vec<&RestSample>, periods: &mut Vec<RestPeriod>) {
+    let samples = std::mem::take(current);
+    let (Some(first), Some(last)) = (samples.first(), samples.last()) else {
+        return;
+    };
+    let minutes = (last.at - first.at).num_minutes();
+    if minutes < MIN_REST_MINUTES {
+        return;
+    }
+
+    let rates: Vec<i32> = samples.iter().filter_map(|s| s.heart_rate).collect();
+    periods.push(RestPeriod {
+        start: first.at,
+        end: last.at,
+        minutes,
+        kind: if minutes >= SLEEP_MIN_MINUTES { "sleep" } else { "rest" }.to_string(),
+        avg_heart_rate: (!rates.is_empty()).then(|| rates.iter().sum::<i32>() as f32 / rates.len() as f32),
+        min_heart_rate: rates.iter().copied().min(),
+        readings: samples.len() as i32,
+    });
+}
+
+/// Roll a night's periods up into its summary row
+pub fn summarize(
+    patient_id: Uuid,
+    sleep_date: NaiveDate,
+    threshold: Option<f32>,
+    periods: Vec<RestPeriod>,
+) -> SleepSummary {
+    let total = |kind: Option<&str>| -> i32 {
+        periods
+            .iter()
+            .filter(|p| kind.is_none_or(|k| p.kind == k))
+            .map(|p| p.minutes as i32)
+            .sum()
+    };
+
+    // Average over every heart-rate reading, not over periods
+    let (hr_sum, hr_count) = periods
+        .iter()
+        .filter_map(|p| p.avg_heart_rate.map(|avg| (avg * p.readings as f32, p.readings)))
+        .fold((0.0, 0), |(sum, count), (s, c)| (sum + s, count + c));
+
+    SleepSummary {
+        patient_id,
+        sleep_date,
+        period_count: periods.len() as i32,
+        rest_minutes: total(None),
+        sleep_minutes: total(Some("sleep")),
+        longest_minutes: periods.iter().map(|p| p.minutes as i32).max().unwrap_or(0),
+        avg_heart_rate: (hr_count > 0).then(|| hr_sum / hr_count as f32),
+        rest_hr_threshold: threshold,
+        periods: sqlx::types::Json(periods),
+        computed_at: Utc::now(),
+    }
+}
+
+/// Detect rest periods for every patient with readings during the night ending on `date`
+/// and upsert their summaries; returns the number of patients summarized
+pub async fn summarize_night(pool: &PgPool, date: NaiveDate) -> Result<usize> {
+    let (start, end) = night_window(date);
+
+    let patients: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT DISTINCT d.patient_id
+         FROM sensor_readings r
+         JOIN devices d ON d.id = r.device_id
+         WHERE d.patient_id IS NOT NULL AND r.reading_timestamp >= $1 AND r.reading_timestamp < $2"
+    )
+    .bind(start)
+    .bind(end)
+    .fetch_all(pool)
+    .await?;
+
+    let mut summarized = 0;
+    for patient_id in patients {
+        let samples: Vec<RestSample> = sqlx::query_as(
+            "SELECT r.reading_timestamp AS at, r.heart_rate, (r.metadata->>'steps')::int AS steps
+             FROM sensor_readings r
+             JOIN devices d ON d.id = r.device_id
+             WHERE d.patient_id = $1 AND r.reading_timestamp >= $2 AND r.reading_timestamp < $3
+             ORDER BY r.reading_timestamp"
+        )
+        .bind(patient_id)
+        .bind(start)
+        .bind(end)
+        .fetch_all(pool)
+        .await?;
+
+        let Some(threshold) = rest_threshold(&samples) else {
+            continue;
+        };
+        let summary = summarize(patient_id, date, Some(threshold), detect_rest_periods(&samples, threshold));
+
+        sqlx::query(
+            "INSERT INTO sleep_summaries
+                (patient_id, sleep_date, period_count, rest_minutes, sleep_minutes, longest_minutes,
+                 avg_heart_rate, rest_hr_threshold, periods)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+             ON CONFLICT (patient_id, sleep_date) DO UPDATE SET
+                period_count = EXCLUDED.period_count, rest_minutes = EXCLUDED.rest_minutes,
+                sleep_minutes = EXCLUDED.sleep_minutes, longest_minutes = EXCLUDED.longest_minutes,
+                avg_heart_rate = EXCLUDED.avg_heart_rate, rest_hr_threshold = EXCLUDED.rest_hr_threshold,
+                periods = EXCLUDED.periods, computed_at = now()"
+        )
+        .bind(summary.patient_id)
+        .bind(summary.sleep_date)
+        .bind(summary.period_count)
+        .bind(summary.rest_minutes)
+        .bind(summary.sleep_minutes)
+        .bind(summary.longest_minutes)
+        .bind(summary.avg_heart_rate)
+        .bind(summary.rest_hr_threshold)
+        .bind(&summary.periods)
+        .execute(pool)
+        .await?;
+        summarized += 1;
+    }
+
+    Ok(summarized)
+}
+
+/// Background worker: finalizes last night and keeps tonight's running summary fresh
+pub fn spawn_sleep_worker(pool: PgPool) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SUMMARY_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let today = Utc::now().date_naive();
+            for date in [today, today + Duration::days(1)] {
+                match summarize_night(&pool, date).await {
+                    Ok(count) => info!("Sleep summaries for night ending {}: {} patient(s)", date, count),
+                    Err(e) => error!("Sleep summaries for night ending {} failed: {}", date, e),
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(minute: i64, heart_rate: Option<i32>, steps: Option<i32>) -> RestSample {
+        let base = DateTime::parse_from_rfc3339("2026-03-01T22:00:00Z").unwrap().with_timezone(&Utc);
+        RestSample {
+            at: base + Duration::minutes(minute),
+            heart_rate,
+            steps,
+        }
+    }
+
+    #[test]
+    fn test_threshold_needs_enough_heart_rate_samples() {
+        let few: Vec<_> = (0..10).map(|m| sample(m, Some(80), None)).collect();
+        assert!(rest_threshold(&few).is_none());
+
+        let mut night: Vec<_> = (0..30).map(|m| sample(m, Some(80), Some(5))).collect();
+        night.extend((30..40).map(|m| sample(m, Some(55), None)));
+        assert_eq!(rest_threshold(&night), Some(80.0 * REST_HR_RATIO));
+    }
+
+    #[test]
+    fn test_detects_sleep_and_splits_on_motion_and_gaps() {
+        let mut samples: Vec<_> = (0..=120).step_by(5).map(|m| sample(m, Some(56), None)).collect();
+        // Walking to the bathroom
+        samples.push(sample(125, Some(90), Some(40)));
+        samples.extend((130..=170).step_by(5).map(|m| sample(m, Some(58), Some(0))));
+        // Walker switched off for an hour, then a short doze that is too brief to count
+        samples.extend((230..=250).step_by(5).map(|m| sample(m, Some(60), None)));
+
+        let periods = detect_rest_periods(&samples, 68.0);
+        assert_eq!(periods.len(), 2);
+        assert_eq!((periods[0].minutes, periods[0].kind.as_str()), (120, "sleep"));
+        assert_eq!((periods[1].minutes, periods[1].kind.as_str()), (40, "rest"));
+        assert_eq!(periods[1].min_heart_rate, Some(58));
+
+        let summary = summarize(Uuid::nil(), NaiveDate::from_ymd_opt(2026, 3, 2).unwrap(), Some(68.0), periods);
+        assert_eq!((summary.rest_minutes, summary.sleep_minutes, summary.longest_minutes), (160, 120, 120));
+        assert!((summary.avg_heart_rate.unwrap() - 56.5).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_missing_heart_rate_extends_but_never_starts_a_period() {
+        let mut samples: Vec<_> = (0..40).step_by(5).map(|m| sample(m, None, None)).collect();
+        assert!(detect_rest_periods(&samples, 68.0).is_empty());
+
+        samples.extend((40..=60).step_by(5).map(|m| sample(m, Some(55), None)));
+        samples.extend((65..=80).step_by(5).map(|m| sample(m, None, None)));
+        let periods = detect_rest_periods(&samples, 68.0);
+        assert_eq!(periods.len(), 1);
+        assert_eq!((periods[0].start, periods[0].minutes), (samples[8].at, 40));
+    }
+}
@@ -0,0 +1,154 @@
+use crate::config::{SloConfig, SloObjective};
+use crate::metrics::{request_totals, RequestTotals};
+use crate::models::{SloStatus, SloWindow};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::error;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+/// Rolling windows reported for every objective
+pub const WINDOWS_DAYS: &[i32] = &[7, 30];
+
+/// Requests counted against one objective since the previous sample
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SloSample {
+    pub objective: String,
+    pub total: i64,
+    pub good: i64,
+}
+
+/// Turns the process's cumulative request metrics into per-interval counts
+#[derive(Debug, Default)]
+pub struct SloSampler {
+    last: HashMap<String, RequestTotals>,
+}
+
+impl SloSampler {
+    /// Counts since the previous call (or process start), skipping objectives without traffic
+    pub fn sample(&mut self, objectives: &[SloObjective]) -> Vec<SloSample> {
+        objectives
+            .iter()
+            .filter_map(|objective| {
+                let latency = objective.latency_ms.map(Duration::from_millis);
+                let now = request_totals(objective.method.as_deref(), &objective.endpoint, latency);
+                let before = self.last.insert(objective.name.clone(), now).unwrap_or_default();
+                let sample = count(&objective.name, before, now);
+                (sample.total > 0).then_some(sample)
+            })
+            .collect()
+    }
+}
+
+/// A request is bad when it fails or is too slow. The histogram can't tell which slow
+/// requests also failed, so a slow failure counts once per reason, capped at the total.
+fn count(objective: &str, before: RequestTotals, now: RequestTotals) -> SloSample {
+    let total = now.total.saturating_sub(before.total);
+    let bad = now.slow.saturating_sub(before.slow) + now.errors.saturating_sub(before.errors);
+    SloSample {
+        objective: objective.to_string(),
+        total: total as i64,
+        good: total.saturating_sub(bad) as i64,
+    }
+}
+
+pub async fn record_samples(pool: &PgPool, samples: &[SloSample]) -> Result<(), sqlx::Error> {
+    for sample in samples {
+        sqlx::query("INSERT INTO slo_samples (objective, total, good) VALUES ($1, $2, $3)")
+            .bind(&sample.objective)
+            .bind(sample.total)
+            .bind(sample.good)
+            .execute(pool)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Compliance of every objective over each of [`WINDOWS_DAYS`]
+pub async fn report(pool: &PgPool, config: &SloConfig) -> Result<Vec<SloStatus>, sqlx::Error> {
+    let mut totals: HashMap<(String, i32), (i64, i64)> = HashMap::new();
+    for &days in WINDOWS_DAYS {
+        let rows: Vec<(String, i64, i64)> = sqlx::query_as(
+            "SELECT objective, SUM(total)::bigint, SUM(good)::bigint FROM slo_samples
+             WHERE sampled_at >= now() - make_interval(days => $1)
+             GROUP BY objective"
+        )
+        .bind(days)
+        .fetch_all(pool)
+        .await?;
+        for (objective, total, good) in rows {
+            totals.insert((objective, days), (total, good));
+        }
+    }
+
+    Ok(config
+        .objectives
+        .iter()
+        .map(|objective| SloStatus {
+            windows: WINDOWS_DAYS
+                .iter()
+                .map(|&days| {
+                    let (total, good) = totals.get(&(objective.name.clone(), days)).copied().unwrap_or_default();
+                    SloWindow::new(days, total, good, objective.target)
+                })
+                .collect(),
+            objective: objective.clone(),
+        })
+        .collect())
+}
+
+/// Background worker sampling the request metrics every minute and dropping samples
+/// older than the longest window
+pub fn spawn_sampler(pool: PgPool, config: SloConfig) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut sampler = SloSampler::default();
+        let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let samples = sampler.sample(&config.objectives);
+            if let Err(e) = record_samples(&pool, &samples).await {
+                error!("Recording SLO samples failed: {}", e);
+            }
+
+            let longest = WINDOWS_DAYS.iter().max().copied().unwrap_or(30);
+            if let Err(e) = sqlx::query("DELETE FROM slo_samples WHERE sampled_at < now() - make_interval(days => $1)")
+                .bind(longest)
+                .execute(&pool)
+                .await
+            {
+                error!("Pruning SLO samples failed: {}", e);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bad_requests_capped_at_total() {
+        let before = RequestTotals { total: 100, slow: 3, errors: 1 };
+        let sample = count("ingestion", before, RequestTotals { total: 150, slow: 5, errors: 2 });
+        assert_eq!((sample.total, sample.good), (50, 47));
+
+        let sample = count("ingestion", before, RequestTotals { total: 101, slow: 4, errors: 2 });
+        assert_eq!((sample.total, sample.good), (1, 0));
+    }
+
+    #[test]
+    fn test_window_error_budget() {
+        let window = SloWindow::new(7, 10_000, 9_995, 0.999);
+        assert!(window.met);
+        assert!((window.error_budget_remaining.unwrap() - 0.5).abs() < 1e-9);
+
+        let window = SloWindow::new(30, 10_000, 9_980, 0.999);
+        assert!(!window.met);
+        assert!(window.error_budget_remaining.unwrap() < 0.0);
+
+        let idle = SloWindow::new(7, 0, 0, 0.999);
+        assert!(idle.met);
+        assert_eq!(idle.compliance, None);
+    }
+}
@@ -1,36 +1,221 @@
-use crate::models::{LatestVitals, MlAlert, SseEvent};
-use actix_web::{web, HttpResponse, Responder};
+use crate::errors::ApiError;
+use crate::event_log::StreamSession;
+use crate::handlers::{accessible_patients, AppState};
+use crate::metrics::{StreamConnection, SSE_EVENTS_SENT};
+use crate::middleware::authenticate_stream_request;
+use crate::models::{AlertMessage, AlertStatusChange, Claims, EventEnvelope, LatestVitals, MedicationReminder, MlAlert, SseEvent};
+use crate::usage_service::SseSession;
+use actix_web::{web, HttpRequest, HttpResponse};
 use async_stream::stream;
-use std::sync::Arc;
+use bytestring::ByteString;
+use std::collections::{HashSet, VecDeque};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
 use tokio::time::interval;
 use tokio_stream::wrappers::BroadcastStream;
 use tokio_stream::StreamExt;
+use tracing::warn;
+use uuid::Uuid;
 
-/// Broadcast channel for SSE events
-pub type SseBroadcaster = Arc<broadcast::Sender<SseEvent>>;
+/// Events kept for long-polling clients, matching the broadcast channel's capacity
+const RECENT_EVENTS: usize = 100;
+
+/// Broadcast channel for SSE events, enveloped and rendered once so every subscriber sees
+/// the same id and no stream serializes an event itself
+pub type SseBroadcaster = Arc<broadcast::Sender<BroadcastEvent>>;
 
 /// Create a new SSE broadcaster
 pub fn create_broadcaster() -> SseBroadcaster {
-    let (tx, _rx) = broadcast::channel::<SseEvent>(100);
+    let (tx, _rx) = broadcast::channel::<BroadcastEvent>(100);
     Arc::new(tx)
 }
 
-/// SSE event handler - streams vitals to frontend
-pub async fn stream_vitals(
-    broadcaster: web::Data<SseBroadcaster>,
-) -> impl Responder {
-    let rx = broadcaster.subscribe();
+/// An event on the broadcast channel: the envelope, for deciding who receives it, with its
+/// JSON and SSE frame rendered up front.
+///
+/// Every subscriber receives a clone, so everything is reference counted. Formatting per
+/// subscriber dominated CPU at high event rates (see `benches/sse_fanout.rs`).
+#[derive(Debug, Clone)]
+pub struct BroadcastEvent {
+    envelope: Arc<EventEnvelope>,
+    json: ByteString,
+    frame: web::Bytes,
+}
+
+impl BroadcastEvent {
+    pub fn new(envelope: EventEnvelope) -> serde_json::Result<Self> {
+        let json = serde_json::to_string(&envelope)?;
+        let frame = web::Bytes::from(format_frame(&envelope, &json));
+        Ok(Self { envelope: Arc::new(envelope), json: json.into(), frame })
+    }
+
+    /// The envelope as JSON, as WebSocket clients receive it
+    pub fn json(&self) -> ByteString {
+        self.json.clone()
+    }
+
+    /// The SSE frame for the event (see [`sse_frame`])
+    pub fn frame(&self) -> web::Bytes {
+        self.frame.clone()
+    }
+}
+
+impl Deref for BroadcastEvent {
+    type Target = EventEnvelope;
+
+    fn deref(&self) -> &EventEnvelope {
+        &self.envelope
+    }
+}
+
+/// Render an envelope and send it to every subscriber; there may be none
+pub fn publish(broadcaster: &SseBroadcaster, envelope: EventEnvelope) {
+    match BroadcastEvent::new(envelope) {
+        Ok(event) => {
+            let _ = broadcaster.send(event);
+        }
+        Err(e) => warn!("Dropped an event that failed to serialize: {}", e),
+    }
+}
+
+/// The last events broadcast, so long-polling clients (not subscribed between polls)
+/// catch up on what they missed
+#[derive(Default)]
+pub struct RecentEvents {
+    events: Mutex<VecDeque<BroadcastEvent>>,
+}
+
+impl RecentEvents {
+    /// Keep the events broadcast from now on, for as long as the broadcaster lives
+    pub fn record(broadcaster: &SseBroadcaster) -> Arc<Self> {
+        let recent = Arc::new(Self::default());
+        let mut rx = broadcaster.subscribe();
+        let recorder = recent.clone();
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => recorder.push(event),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+        recent
+    }
+
+    fn push(&self, event: BroadcastEvent) {
+        if matches!(event.event, SseEvent::Heartbeat { .. }) {
+            return;
+        }
+        let mut events = self.events.lock().unwrap();
+        if events.len() == RECENT_EVENTS {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+
+    /// Events after `since`; every kept event when `since` has been dropped (or was never kept)
+    pub fn since(&self, since: Uuid) -> Vec<EventEnvelope> {
+        let events = self.events.lock().unwrap();
+        let start = events.iter().position(|e| e.id == since).map_or(0, |i| i + 1);
+        events.range(start..).map(|e| EventEnvelope::clone(e)).collect()
+    }
+
+    pub fn newest(&self) -> Option<Uuid> {
+        self.events.lock().unwrap().back().map(|e| e.id)
+    }
+}
+
+/// The user behind a stream or poll, and the patients whose events they may receive
+pub struct StreamViewer {
+    claims: Claims,
+    /// `None`: every patient (admins outside any organization)
+    patients: Option<HashSet<Uuid>>,
+}
+
+impl StreamViewer {
+    pub async fn load(state: &AppState, claims: Claims) -> Result<Self, ApiError> {
+        let patients = accessible_patients(state, &claims).await?;
+        Ok(Self { claims, patients })
+    }
+
+    pub fn user_id(&self) -> Uuid {
+        self.claims.user_id
+    }
+
+    /// The patients whose events the viewer receives; `None` for all of them, which only
+    /// admins outside any organization get
+    pub fn patients(&self) -> Option<&HashSet<Uuid>> {
+        self.patients.as_ref()
+    }
+
+    /// Events about walkers not assigned to a patient only reach viewers who see everyone
+    pub fn may_see(&self, envelope: &EventEnvelope) -> bool {
+        match (&self.patients, envelope.patient_id) {
+            (None, _) => true,
+            _ if matches!(envelope.event, SseEvent::Heartbeat { .. }) => true,
+            (Some(patients), Some(patient_id)) => patients.contains(&patient_id),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// For long-lived connections: re-check the token and pick up new caregiver links.
+    /// `false` once the token has expired or been revoked.
+    pub async fn refresh(&mut self, state: &AppState) -> bool {
+        if self.claims.exp <= chrono::Utc::now().timestamp()
+            || state.jwt_auth.is_token_revoked(self.claims.jti, &state.pool).await.unwrap_or(false)
+        {
+            return false;
+        }
+        if let Ok(patients) = accessible_patients(state, &self.claims).await {
+            self.patients = patients;
+        }
+        true
+    }
+}
+
+/// One SSE frame: the envelope's type as the event name and id, the whole envelope as data.
+/// For events sent to a single stream; broadcast ones come rendered ([`BroadcastEvent`]).
+pub fn sse_frame(envelope: &EventEnvelope) -> Option<web::Bytes> {
+    let json = serde_json::to_string(envelope).ok()?;
+    Some(web::Bytes::from(format_frame(envelope, &json)))
+}
+
+fn format_frame(envelope: &EventEnvelope, json: &str) -> String {
+    format!("event: {}\nid: {}\ndata: {}\n\n", envelope.event.event_type(), envelope.id, json)
+}
+
+fn heartbeat() -> EventEnvelope {
+    EventEnvelope::new(SseEvent::Heartbeat { timestamp: chrono::Utc::now().timestamp() })
+}
+
+/// SSE event handler - streams the events the caller may see to the frontend.
+///
+/// Takes the token as a bearer header or `?token=`; the stream ends at the first
+/// heartbeat after it expires or is revoked.
+pub async fn stream_vitals(req: HttpRequest, state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let claims = authenticate_stream_request(&req).await?;
+    let mut viewer = StreamViewer::load(&state, claims).await?;
+    let rx = state.sse_broadcaster.subscribe();
     let stream = BroadcastStream::new(rx);
+    let session = SseSession::open(state.pool.clone());
+    let connection = StreamConnection::open();
+    let stream_session = StreamSession::open(&state.pool, "sse", &viewer).await;
 
     let event_stream = stream! {
+        // Connected time is billed when the client disconnects and the stream is dropped,
+        // and the stream is logged as closed
+        let _session = session;
+        let _connection = connection;
+        let _stream_session = stream_session;
+
         // Send initial heartbeat
-        yield Ok::<_, actix_web::Error>(
-            web::Bytes::from(format!("event: heartbeat\ndata: {}\n\n", 
-                serde_json::to_string(&serde_json::json!({"timestamp": chrono::Utc::now().timestamp()})).unwrap()
-            ))
-        );
+        if let Some(frame) = sse_frame(&heartbeat()) {
+            yield Ok::<_, actix_web::Error>(frame);
+        }
 
         tokio::pin!(stream);
 
@@ -40,30 +225,21 @@ pub async fn stream_vitals(
         loop {
             tokio::select! {
                 _ = heartbeat_interval.tick() => {
-                    let heartbeat = SseEvent::Heartbeat { 
-                        timestamp: chrono::Utc::now().timestamp() 
-                    };
-                    if let Ok(json) = serde_json::to_string(&heartbeat) {
-                        yield Ok::<_, actix_web::Error>(
-                            web::Bytes::from(format!("event: heartbeat\ndata: {}\n\n", json))
-                        );
+                    if !viewer.refresh(&state).await {
+                        break;
+                    }
+                    if let Some(frame) = sse_frame(&heartbeat()) {
+                        SSE_EVENTS_SENT.with_label_values(&["heartbeat"]).inc();
+                        yield Ok::<_, actix_web::Error>(frame);
                     }
                 }
                 Some(msg) = stream.next() => {
                     match msg {
-                        Ok(event) => {
-                            let (event_type, data) = match &event {
-                                SseEvent::Vitals { data } => ("vitals", serde_json::to_string(data)),
-                                SseEvent::Alert { data } => ("alert", serde_json::to_string(data)),
-                                SseEvent::Heartbeat { timestamp } => ("heartbeat", serde_json::to_string(&serde_json::json!({"timestamp": timestamp}))),
-                            };
-
-                            if let Ok(json) = data {
-                                yield Ok::<_, actix_web::Error>(
-                                    web::Bytes::from(format!("event: {}\ndata: {}\n\n", event_type, json))
-                                );
-                            }
+                        Ok(event) if viewer.may_see(&event) => {
+                            SSE_EVENTS_SENT.with_label_values(&[event.event.event_type()]).inc();
+                            yield Ok::<_, actix_web::Error>(event.frame());
                         }
+                        Ok(_) => {}
                         Err(_) => {
                             // Channel closed or lagged, break the loop
                             break;
@@ -74,28 +250,45 @@ pub async fn stream_vitals(
         }
     };
 
-    HttpResponse::Ok()
+    Ok(HttpResponse::Ok()
         .content_type("text/event-stream")
         .insert_header(("Cache-Control", "no-cache"))
         .insert_header(("X-Accel-Buffering", "no"))
-        .streaming(event_stream)
+        .streaming(event_stream))
+}
+
+/// Broadcast a vitals update to the SSE clients allowed to see the patient
+pub fn broadcast_vitals(broadcaster: &SseBroadcaster, patient_id: Option<Uuid>, vitals: LatestVitals) {
+    publish(broadcaster, EventEnvelope::new(SseEvent::Vitals(vitals)).for_patient(patient_id));
+}
+
+/// Broadcast an ML alert to the SSE clients allowed to see the patient
+pub fn broadcast_alert(broadcaster: &SseBroadcaster, patient_id: Option<Uuid>, alert: MlAlert) {
+    publish(broadcaster, EventEnvelope::new(SseEvent::Alert(alert)).for_patient(patient_id));
+}
+
+/// Broadcast a medication reminder (shown on the walker display and dashboards)
+pub fn broadcast_reminder(broadcaster: &SseBroadcaster, reminder: MedicationReminder) {
+    let patient_id = reminder.patient_id;
+    publish(broadcaster, EventEnvelope::new(SseEvent::Reminder(reminder)).for_patient(Some(patient_id)));
 }
 
-/// Broadcast a vitals update to all SSE clients
-pub fn broadcast_vitals(broadcaster: &SseBroadcaster, vitals: LatestVitals) {
-    let event = SseEvent::Vitals { data: vitals };
-    let _ = broadcaster.send(event);
+/// Broadcast a new chat message on an alert so open alert views update live
+pub fn broadcast_alert_message(broadcaster: &SseBroadcaster, patient_id: Option<Uuid>, message: AlertMessage) {
+    publish(broadcaster, EventEnvelope::new(SseEvent::AlertMessage(message)).for_patient(patient_id));
 }
 
-/// Broadcast an ML alert to all SSE clients
-pub fn broadcast_alert(broadcaster: &SseBroadcaster, alert: MlAlert) {
-    let event = SseEvent::Alert { data: alert };
-    let _ = broadcaster.send(event);
+/// Broadcast an alert being acknowledged or resolved so triage views stay in step
+pub fn broadcast_alert_status(broadcaster: &SseBroadcaster, change: AlertStatusChange) {
+    let patient_id = change.patient_id;
+    publish(broadcaster, EventEnvelope::new(SseEvent::AlertStatus(change)).for_patient(patient_id));
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::AlertLevel;
+    use crate::rbac::Role;
 
     #[test]
     fn test_broadcaster_creation() {
@@ -109,7 +302,7 @@ mod tests {
         let mut rx = broadcaster.subscribe();
 
         let vitals = LatestVitals {
-            heartRate: 75,
+            heart_rate: 75,
             spo2: 98,
             temperature: 36.8,
             timestamp: 1234567890,
@@ -117,14 +310,14 @@ mod tests {
             ml_alert: None,
         };
 
-        broadcast_vitals(&broadcaster, vitals.clone());
+        broadcast_vitals(&broadcaster, None, vitals.clone());
 
         // Try to receive the event
         let result = rx.try_recv();
         assert!(result.is_ok());
 
-        if let Ok(SseEvent::Vitals { data }) = result {
-            assert_eq!(data.heartRate, 75);
+        if let Ok(SseEvent::Vitals(data)) = result.as_ref().map(|e| &e.event) {
+            assert_eq!(data.heart_rate, 75);
         }
     }
 
@@ -134,18 +327,106 @@ mod tests {
         let mut rx = broadcaster.subscribe();
 
         let alert = MlAlert {
-            level: "critical".to_string(),
+            level: AlertLevel::Critical,
             message: "Test alert".to_string(),
             details: serde_json::json!({}),
         };
 
-        broadcast_alert(&broadcaster, alert.clone());
+        broadcast_alert(&broadcaster, None, alert.clone());
 
         let result = rx.try_recv();
         assert!(result.is_ok());
 
-        if let Ok(SseEvent::Alert { data }) = result {
-            assert_eq!(data.level, "critical");
+        if let Ok(SseEvent::Alert(data)) = result.as_ref().map(|e| &e.event) {
+            assert_eq!(data.level, AlertLevel::Critical);
         }
     }
+
+    #[test]
+    fn test_recent_events_since_cursor() {
+        let recent = RecentEvents::default();
+        assert!(recent.since(Uuid::new_v4()).is_empty());
+
+        let sent: Vec<_> = (0..RECENT_EVENTS as i64 + 2)
+            .map(|timestamp| EventEnvelope::new(SseEvent::Vitals(LatestVitals {
+                heart_rate: 70,
+                spo2: 98,
+                temperature: 36.8,
+                timestamp,
+                quality_score: None,
+                ml_alert: None,
+            })))
+            .collect();
+        for envelope in &sent {
+            recent.push(BroadcastEvent::new(envelope.clone()).unwrap());
+        }
+        recent.push(BroadcastEvent::new(heartbeat()).unwrap());
+
+        let ids = |events: Vec<EventEnvelope>| events.into_iter().map(|e| e.id).collect::<Vec<_>>();
+        assert_eq!(recent.newest(), Some(sent.last().unwrap().id));
+        assert_eq!(ids(recent.since(sent[sent.len() - 3].id)), ids(sent[sent.len() - 2..].to_vec()));
+        assert!(recent.since(sent.last().unwrap().id).is_empty());
+        // The oldest two were dropped: the client gets everything still kept
+        assert_eq!(ids(recent.since(sent[0].id)), ids(sent[2..].to_vec()));
+    }
+
+    #[test]
+    fn test_viewer_sees_only_linked_patients() {
+        let linked = Uuid::new_v4();
+        let viewer = |patients: Option<HashSet<Uuid>>| StreamViewer {
+            claims: Claims {
+                sub: "caregiver@example.com".to_string(),
+                user_id: Uuid::new_v4(),
+                role: Role::Viewer,
+                org: None,
+                exp: 0,
+                iat: 0,
+                jti: Uuid::new_v4(),
+            },
+            patients,
+        };
+        let alert = |patient_id| {
+            EventEnvelope::new(SseEvent::Alert(MlAlert {
+                level: AlertLevel::High,
+                message: "Test alert".to_string(),
+                details: serde_json::json!({}),
+            }))
+            .for_patient(patient_id)
+        };
+
+        let caregiver = viewer(Some(HashSet::from([linked])));
+        assert!(caregiver.may_see(&alert(Some(linked))));
+        assert!(!caregiver.may_see(&alert(Some(Uuid::new_v4()))));
+        assert!(!caregiver.may_see(&alert(None)));
+        assert!(caregiver.may_see(&heartbeat()));
+
+        let admin = viewer(None);
+        assert!(admin.may_see(&alert(Some(Uuid::new_v4()))) && admin.may_see(&alert(None)));
+    }
+
+    #[test]
+    fn test_sse_frame_carries_the_envelope() {
+        let envelope = EventEnvelope::new(SseEvent::Heartbeat { timestamp: 1700000000 });
+        let frame = String::from_utf8(sse_frame(&envelope).unwrap().to_vec()).unwrap();
+
+        let mut lines = frame.lines();
+        assert_eq!(lines.next(), Some("event: heartbeat"));
+        assert_eq!(lines.next(), Some(format!("id: {}", envelope.id).as_str()));
+        let data: serde_json::Value = serde_json::from_str(lines.next().unwrap().strip_prefix("data: ").unwrap()).unwrap();
+        assert_eq!(data["version"], 1);
+        assert_eq!(data["type"], "heartbeat");
+        assert_eq!(data["data"], serde_json::json!({"timestamp": 1700000000}));
+        assert!(frame.ends_with("\n\n"));
+    }
+
+    #[test]
+    fn test_broadcast_event_is_rendered_once() {
+        let envelope = EventEnvelope::new(SseEvent::Heartbeat { timestamp: 1700000000 });
+        let event = BroadcastEvent::new(envelope.clone()).unwrap();
+
+        assert_eq!(event.frame(), sse_frame(&envelope).unwrap());
+        assert_eq!(&event.json()[..], serde_json::to_string(&envelope).unwrap());
+        // Subscribers share the rendered bytes rather than copying them
+        assert_eq!(event.clone().frame().as_ptr(), event.frame().as_ptr());
+    }
 }
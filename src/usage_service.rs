@@ -0,0 +1,213 @@
+use crate::config::BillingConfig;
+use crate::models::{EventEnvelope, UsageRollup, WebhookEvent};
+use anyhow::{bail, Result};
+use chrono::{Duration, NaiveDate, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::time::Instant;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+const ROLLUP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// What a usage event counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageMetric {
+    ReadingsIngested,
+    /// Connected SSE time, each started minute billed
+    SseMinutes,
+    /// FHIR bundles, legal-hold archives and PDF reports
+    ExportsGenerated,
+}
+
+impl UsageMetric {
+    pub fn name(&self) -> &'static str {
+        match self {
+            UsageMetric::ReadingsIngested => "readings_ingested",
+            UsageMetric::SseMinutes => "sse_minutes",
+            UsageMetric::ExportsGenerated => "exports_generated",
+        }
+    }
+}
+
+/// Record usage, attributed to the organization of `patient_id` when given
+pub async fn record(pool: &PgPool, patient_id: Option<Uuid>, metric: UsageMetric, quantity: i64) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO usage_events (organization_id, metric, quantity)
+         VALUES ((SELECT organization_id FROM patients WHERE id = $1), $2, $3)"
+    )
+    .bind(patient_id)
+    .bind(metric.name())
+    .bind(quantity)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Record usage without holding up the request; failures are logged, never surfaced
+pub fn record_in_background(pool: &PgPool, patient_id: Option<Uuid>, metric: UsageMetric, quantity: i64) {
+    let pool = pool.clone();
+    tokio::spawn(async move {
+        if let Err(e) = record(&pool, patient_id, metric, quantity).await {
+            warn!(metric = metric.name(), "Failed to record usage: {}", e);
+        }
+    });
+}
+
+/// Started minutes of an SSE connection, so even a brief connection counts once
+pub fn sse_minutes(connected: std::time::Duration) -> i64 {
+    connected.as_secs().div_ceil(60).max(1) as i64
+}
+
+/// Records an SSE connection's length as usage when the client goes away
+pub struct SseSession {
+    pool: PgPool,
+    opened: Instant,
+}
+
+impl SseSession {
+    pub fn open(pool: PgPool) -> Self {
+        Self { pool, opened: Instant::now() }
+    }
+}
+
+impl Drop for SseSession {
+    fn drop(&mut self) {
+        // Dropped during runtime shutdown there is nothing left to record with
+        if tokio::runtime::Handle::try_current().is_ok() {
+            record_in_background(&self.pool, None, UsageMetric::SseMinutes, sse_minutes(self.opened.elapsed()));
+        }
+    }
+}
+
+/// Recompute a day's totals from its events. Safe to re-run: totals are replaced, and a
+/// total that changes is queued to be pushed again.
+pub async fn roll_up(pool: &PgPool, day: NaiveDate) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "INSERT INTO usage_daily_rollups (organization_id, day, metric, quantity)
+         SELECT organization_id, $1, metric, SUM(quantity)::bigint
+         FROM usage_events
+         WHERE occurred_at >= $1::date::timestamp AT TIME ZONE 'UTC'
+           AND occurred_at < ($1::date + 1)::timestamp AT TIME ZONE 'UTC'
+         GROUP BY organization_id, metric
+         ON CONFLICT (organization_key, day, metric) DO UPDATE
+         SET quantity = EXCLUDED.quantity,
+             rolled_up_at = now(),
+             pushed_at = CASE WHEN usage_daily_rollups.quantity = EXCLUDED.quantity
+                              THEN usage_daily_rollups.pushed_at END"
+    )
+    .bind(day)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Daily totals between two days, inclusive
+pub async fn rollups(pool: &PgPool, from: NaiveDate, to: NaiveDate) -> Result<Vec<UsageRollup>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT organization_id, day, metric, quantity FROM usage_daily_rollups
+         WHERE day BETWEEN $1 AND $2
+         ORDER BY day, organization_id NULLS FIRST, metric"
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await
+}
+
+/// `usage-{day}-{digest}`: stable for identical totals, new whenever they change
+pub fn idempotency_key(body: &serde_json::Value) -> String {
+    let digest = format!("{:x}", Sha256::digest(body.to_string().as_bytes()));
+    format!("usage-{}-{}", body["day"].as_str().unwrap_or_default(), &digest[..16])
+}
+
+/// POST every closed day's unpushed totals to the billing endpoint, one request per day.
+///
+/// Each request carries an `Idempotency-Key` derived from the day and its totals, so a retry
+/// after a lost response doesn't bill twice while a corrected total still goes through.
+/// Returns the number of days pushed.
+pub async fn push_rollups(pool: &PgPool, http: &reqwest::Client, url: &str) -> Result<usize> {
+    let today = Utc::now().date_naive();
+    let days: Vec<NaiveDate> = sqlx::query_scalar(
+        "SELECT DISTINCT day FROM usage_daily_rollups WHERE pushed_at IS NULL AND day < $1 ORDER BY day"
+    )
+    .bind(today)
+    .fetch_all(pool)
+    .await?;
+
+    for day in &days {
+        let usage = rollups(pool, *day, *day).await?;
+        // Keyed on the day's totals, not the envelope, so a retried push reuses the key
+        let key = idempotency_key(&serde_json::json!({"day": day, "usage": usage}));
+        let body = EventEnvelope::new(WebhookEvent::UsageDaily { day: *day, usage });
+        let resp = http
+            .post(url)
+            .header("Idempotency-Key", key)
+            .json(&body)
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            bail!("Billing endpoint returned {} for {}", resp.status(), day);
+        }
+
+        sqlx::query("UPDATE usage_daily_rollups SET pushed_at = now() WHERE day = $1 AND pushed_at IS NULL")
+            .bind(day)
+            .execute(pool)
+            .await?;
+    }
+    Ok(days.len())
+}
+
+/// Background worker rolling up yesterday and today every hour, then pushing closed days
+pub fn spawn_rollup_worker(pool: PgPool, config: BillingConfig) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let http = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(config.timeout_seconds))
+            .build()
+            .unwrap_or_default();
+        let mut interval = tokio::time::interval(ROLLUP_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let today = Utc::now().date_naive();
+            for day in [today - Duration::days(1), today] {
+                if let Err(e) = roll_up(&pool, day).await {
+                    error!("Usage rollup for {} failed: {}", day, e);
+                }
+            }
+
+            if let Some(url) = &config.endpoint_url {
+                match push_rollups(&pool, &http, url).await {
+                    Ok(0) => {}
+                    Ok(n) => info!("Pushed {} day(s) of usage to the billing endpoint", n),
+                    Err(e) => error!("Usage push failed: {:#}", e),
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_started_sse_minute_is_billed() {
+        assert_eq!(sse_minutes(std::time::Duration::from_secs(0)), 1);
+        assert_eq!(sse_minutes(std::time::Duration::from_secs(59)), 1);
+        assert_eq!(sse_minutes(std::time::Duration::from_secs(60)), 1);
+        assert_eq!(sse_minutes(std::time::Duration::from_secs(61)), 2);
+        assert_eq!(sse_minutes(std::time::Duration::from_secs(30 * 60 + 5)), 31);
+    }
+
+    #[test]
+    fn test_idempotency_key_follows_totals() {
+        let body = |quantity: i64| {
+            serde_json::json!({"day": "2026-03-01", "usage": [{"organization_id": null, "metric": "sse_minutes", "quantity": quantity}]})
+        };
+
+        assert_eq!(idempotency_key(&body(5)), idempotency_key(&body(5)));
+        assert_ne!(idempotency_key(&body(5)), idempotency_key(&body(6)));
+        assert!(idempotency_key(&body(5)).starts_with("usage-2026-03-01-"));
+    }
+}
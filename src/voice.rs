@@ -0,0 +1,113 @@
+use base64::{engine::general_purpose, Engine as _};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use std::collections::BTreeMap;
+
+type HmacSha1 = Hmac<Sha1>;
+
+/// Twilio call statuses accepted from status callbacks
+pub const CALL_STATUSES: &[&str] = &[
+    "queued",
+    "initiated",
+    "ringing",
+    "in-progress",
+    "completed",
+    "busy",
+    "no-answer",
+    "canceled",
+    "failed",
+];
+
+/// Spoken text for an alert; `attempt` is the 1-based escalation call number
+pub fn alert_script(kind: &str, patient_name: &str, message: &str, attempt: i32) -> String {
+    let opening = match kind {
+        "sos" => format!(
+            "This is an emergency call from Smart Walker. {} pressed the S O S button on their walker.",
+            patient_name
+        ),
+        _ => format!("This is an urgent call from Smart Walker about {}.", patient_name),
+    };
+    let escalation = if attempt > 1 {
+        format!(" This is escalation call number {}; earlier contacts have not responded.", attempt)
+    } else {
+        " Nobody has responded to the alert yet.".to_string()
+    };
+
+    format!(
+        "{}{} {}. Please check on them now and acknowledge the alert in the Smart Walker app.",
+        opening,
+        escalation,
+        message.trim_end_matches('.')
+    )
+}
+
+/// TwiML reading the script twice with the configured text-to-speech voice
+pub fn twiml(script: &str, tts_voice: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><Response><Say voice="{}" loop="2">{}</Say></Response>"#,
+        xml_escape(tts_voice),
+        xml_escape(script)
+    )
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// `X-Twilio-Signature` for a form POST: HMAC-SHA1 over the full URL followed by
+/// every parameter name and value in name order, keyed with the account auth token
+pub fn twilio_signature(auth_token: &str, url: &str, params: &BTreeMap<String, String>) -> String {
+    let mut mac = HmacSha1::new_from_slice(auth_token.as_bytes()).unwrap();
+    mac.update(url.as_bytes());
+    for (name, value) in params {
+        mac.update(name.as_bytes());
+        mac.update(value.as_bytes());
+    }
+    general_purpose::STANDARD.encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sos_script_names_patient_and_escalation() {
+        let first = alert_script("sos", "Ada", "SOS button pressed on Walker at 10:02 UTC", 1);
+        assert!(first.contains("Ada pressed the S O S button"));
+        assert!(first.contains("Nobody has responded"));
+
+        let third = alert_script("fall", "Ada", "Fall detected.", 3);
+        assert!(third.starts_with("This is an urgent call"));
+        assert!(third.contains("escalation call number 3"));
+        assert!(!third.contains(".."));
+    }
+
+    #[test]
+    fn test_twiml_escapes_script() {
+        let xml = twiml("Tom & Jerry <3", "Polly.Joanna");
+        assert!(xml.contains("Tom &amp; Jerry &lt;3"));
+        assert!(xml.contains(r#"<Say voice="Polly.Joanna" loop="2">"#));
+    }
+
+    #[test]
+    fn test_twilio_signature_matches_reference() {
+        // Worked example from Twilio's webhook security documentation
+        let params: BTreeMap<String, String> = [
+            ("CallSid", "CA1234567890ABCDE"),
+            ("Caller", "+12349013030"),
+            ("Digits", "1234"),
+            ("From", "+12349013030"),
+            ("To", "+18005551212"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        let signature = twilio_signature("12345", "https://mycompany.com/myapp.php?foo=1&bar=2", &params);
+        assert_eq!(signature, "0/KCTR6DLpKmkAf8muzZqo1nDgQ=");
+    }
+}
@@ -0,0 +1,183 @@
+//! Signed, sequenced delivery of outgoing webhooks.
+//!
+//! Each event is recorded in `webhook_deliveries` before it is posted, numbered 1, 2, 3, ...
+//! per webhook, and sent with:
+//!
+//! - `X-Webhook-Id`: the envelope id, the same on every redelivery
+//! - `X-Webhook-Sequence`: its number, so a receiver can tell when it missed one
+//! - `X-Webhook-Timestamp`: Unix seconds of this attempt
+//! - `X-Webhook-Signature`: base64 HMAC-SHA256 of `"{timestamp}.{sequence}.{body}"` with the
+//!   webhook's secret, when it has one
+//!
+//! Receivers should reject timestamps more than [`SIGNATURE_TOLERANCE_SECONDS`] away from
+//! their clock and skip ids they already processed; `medhealth_client::webhooks` does both.
+//! A redelivery (`POST /api/webhooks/{id}/redeliver/{event_id}`) resends the stored body
+//! under its id and sequence with a fresh timestamp and signature.
+
+use crate::models::{EventEnvelope, WebhookDelivery, WebhookEvent};
+use anyhow::{bail, Result};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// The emergency contact (SMS/voice) gateway, `emergency.contact_webhook_url`
+pub const CONTACT: &str = "contact";
+
+pub const ID_HEADER: &str = "X-Webhook-Id";
+pub const SEQUENCE_HEADER: &str = "X-Webhook-Sequence";
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+/// How far a delivery's timestamp may be from the receiver's clock
+pub const SIGNATURE_TOLERANCE_SECONDS: i64 = 300;
+/// Deliveries are kept for redelivery this long
+const DELIVERY_RETENTION_DAYS: i32 = 30;
+
+const DELIVERY_COLUMNS: &str = "event_id, webhook, sequence, event_type, status, attempts, response_status, last_error,
+    created_at, last_attempt_at, delivered_at";
+
+/// An outgoing webhook: where it posts and the secret it signs with
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    pub id: &'static str,
+    pub url: String,
+    pub secret: Option<String>,
+}
+
+/// Base64 HMAC-SHA256 over `"{timestamp}.{sequence}.{body}"`, sent in `X-Webhook-Signature`
+pub fn signature(secret: &str, timestamp: i64, sequence: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("{}.{}.{}", timestamp, sequence, body).as_bytes());
+    general_purpose::STANDARD.encode(mac.finalize().into_bytes())
+}
+
+/// Record `envelope` under the webhook's next sequence number and post it
+pub async fn deliver(pool: &PgPool, http: &reqwest::Client, webhook: &Webhook, envelope: &EventEnvelope<WebhookEvent>) -> Result<()> {
+    sqlx::query("DELETE FROM webhook_deliveries WHERE webhook = $1 AND created_at < now() - make_interval(days => $2)")
+        .bind(webhook.id)
+        .bind(DELIVERY_RETENTION_DAYS)
+        .execute(pool)
+        .await?;
+
+    let body = serde_json::to_string(envelope)?;
+    let sequence: i64 = sqlx::query_scalar(
+        "WITH next AS (
+             INSERT INTO webhook_sequences (webhook, last_sequence) VALUES ($1, 1)
+             ON CONFLICT (webhook) DO UPDATE SET last_sequence = webhook_sequences.last_sequence + 1
+             RETURNING last_sequence
+         )
+         INSERT INTO webhook_deliveries (event_id, webhook, sequence, event_type, body)
+         SELECT $2, $1, last_sequence, $3, $4 FROM next
+         RETURNING sequence"
+    )
+    .bind(webhook.id)
+    .bind(envelope.id)
+    .bind(envelope.event.event_type())
+    .bind(&body)
+    .fetch_one(pool)
+    .await?;
+
+    let delivery = attempt(pool, http, webhook, envelope.id, sequence, &body).await?;
+    if delivery.status != "delivered" {
+        bail!("Webhook '{}' {}", webhook.id, delivery.last_error.unwrap_or_default());
+    }
+    Ok(())
+}
+
+/// Post a recorded delivery again; `None` when the webhook never had that event. A failed
+/// attempt is recorded in the returned delivery rather than returned as an error.
+pub async fn redeliver(pool: &PgPool, http: &reqwest::Client, webhook: &Webhook, event_id: Uuid) -> Result<Option<WebhookDelivery>, sqlx::Error> {
+    let stored: Option<(i64, String)> = sqlx::query_as(
+        "SELECT sequence, body FROM webhook_deliveries WHERE webhook = $1 AND event_id = $2"
+    )
+    .bind(webhook.id)
+    .bind(event_id)
+    .fetch_optional(pool)
+    .await?;
+    let Some((sequence, body)) = stored else {
+        return Ok(None);
+    };
+    info!(webhook = webhook.id, event_id = %event_id, sequence, "Redelivering webhook event");
+    attempt(pool, http, webhook, event_id, sequence, &body).await.map(Some)
+}
+
+/// A webhook's deliveries after `after_sequence`, oldest first
+pub async fn list_deliveries(pool: &PgPool, webhook: &str, after_sequence: i64, limit: i64) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+    sqlx::query_as(&format!(
+        "SELECT {} FROM webhook_deliveries WHERE webhook = $1 AND sequence > $2 ORDER BY sequence LIMIT $3",
+        DELIVERY_COLUMNS
+    ))
+    .bind(webhook)
+    .bind(after_sequence)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// Sign and post `body` once, recording the outcome
+async fn attempt(
+    pool: &PgPool,
+    http: &reqwest::Client,
+    webhook: &Webhook,
+    event_id: Uuid,
+    sequence: i64,
+    body: &str,
+) -> Result<WebhookDelivery, sqlx::Error> {
+    let timestamp = Utc::now().timestamp();
+    let mut request = http
+        .post(&webhook.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(ID_HEADER, event_id.to_string())
+        .header(SEQUENCE_HEADER, sequence.to_string())
+        .header(TIMESTAMP_HEADER, timestamp.to_string());
+    if let Some(secret) = &webhook.secret {
+        request = request.header(SIGNATURE_HEADER, signature(secret, timestamp, sequence, body));
+    }
+
+    let (response_status, error) = match request.body(body.to_string()).send().await {
+        Ok(resp) if resp.status().is_success() => (Some(resp.status().as_u16() as i32), None),
+        Ok(resp) => (Some(resp.status().as_u16() as i32), Some(format!("returned {}", resp.status()))),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    if let Some(error) = &error {
+        warn!(webhook = webhook.id, event_id = %event_id, sequence, "Webhook delivery failed: {}", error);
+    }
+
+    sqlx::query_as(&format!(
+        "UPDATE webhook_deliveries
+         SET attempts = attempts + 1, last_attempt_at = now(), response_status = $3, last_error = $4,
+             status = CASE WHEN $4::text IS NULL THEN 'delivered' ELSE 'failed' END,
+             delivered_at = CASE WHEN $4::text IS NULL THEN now() ELSE delivered_at END
+         WHERE webhook = $1 AND event_id = $2
+         RETURNING {}",
+        DELIVERY_COLUMNS
+    ))
+    .bind(webhook.id)
+    .bind(event_id)
+    .bind(response_status)
+    .bind(error)
+    .fetch_one(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_covers_timestamp_sequence_and_body() {
+        let body = r#"{"version":1,"type":"contact_requested"}"#;
+        let signed = signature("webhook-secret", 1700000000, 7, body);
+
+        assert_eq!(signed, signature("webhook-secret", 1700000000, 7, body));
+        assert_eq!(general_purpose::STANDARD.decode(&signed).unwrap().len(), 32);
+        assert_ne!(signed, signature("webhook-secret", 1700000001, 7, body));
+        assert_ne!(signed, signature("webhook-secret", 1700000000, 8, body));
+        assert_ne!(signed, signature("webhook-secret", 1700000000, 7, "{}"));
+        assert_ne!(signed, signature("other-secret", 1700000000, 7, body));
+    }
+}
@@ -0,0 +1,179 @@
+//! WebSocket counterpart of the SSE stream, for hospital proxies that buffer SSE but pass
+//! WebSockets through.
+//!
+//! Pushes the same envelopes as `/api/stream/vitals`, one per text message. The client may
+//! send `{"type": "subscribe", "events": ["vitals", "alert"]}` to narrow what it receives;
+//! it starts with every event type. Like SSE it only carries the events of patients the
+//! caller may see, takes the token as a bearer header or `?token=`, and is closed once the
+//! token expires or is revoked.
+
+use crate::event_log::StreamSession;
+use crate::handlers::AppState;
+use crate::metrics::{StreamConnection, SSE_EVENTS_SENT};
+use crate::middleware::authenticate_stream_request;
+use crate::models::{EventEnvelope, SseEvent, WsClientMessage};
+use crate::sse::{BroadcastEvent, StreamViewer};
+use crate::usage_service::SseSession;
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_ws::{CloseCode, CloseReason, Message, Session};
+use serde_json::json;
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::interval;
+
+/// Ping interval, also when the token is re-checked; keeps idle connections open through proxies
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Client messages are small JSON objects
+const MAX_FRAME_BYTES: usize = 4 * 1024;
+
+/// Event types a connection receives
+struct Subscription(Option<HashSet<&'static str>>);
+
+impl Subscription {
+    fn wants(&self, envelope: &EventEnvelope) -> bool {
+        self.0.as_ref().is_none_or(|types| types.contains(envelope.event.event_type()))
+    }
+
+    /// Apply a `subscribe` message; unknown event types leave the subscription unchanged
+    fn update(&mut self, events: &[String]) -> Result<(), String> {
+        let types = events
+            .iter()
+            .map(|name| {
+                SseEvent::EVENT_TYPES
+                    .iter()
+                    .copied()
+                    .find(|known| known == name)
+                    .ok_or_else(|| format!("Unknown event type: {}", name))
+            })
+            .collect::<Result<HashSet<_>, _>>()?;
+        self.0 = (!types.is_empty()).then_some(types);
+        Ok(())
+    }
+
+    fn event_types(&self) -> Vec<&'static str> {
+        SseEvent::EVENT_TYPES.iter().copied().filter(|t| self.0.as_ref().is_none_or(|types| types.contains(t))).collect()
+    }
+}
+
+/// Upgrade to a WebSocket and forward broadcast events until either side goes away
+pub async fn stream_vitals(
+    req: HttpRequest,
+    body: web::Payload,
+    state: web::Data<AppState>,
+) -> actix_web::Result<HttpResponse> {
+    let claims = authenticate_stream_request(&req).await?;
+    let viewer = StreamViewer::load(&state, claims).await?;
+    let (response, session, messages) = actix_ws::handle(&req, body)?;
+    let updates = state.sse_broadcaster.subscribe();
+    let session_usage = SseSession::open(state.pool.clone());
+    let connection = StreamConnection::open();
+    let stream_session = StreamSession::open(&state.pool, "websocket", &viewer).await;
+
+    actix_web::rt::spawn(async move {
+        // Connected time is billed, the connection counted and the stream logged, like an
+        // SSE stream's
+        let _session_usage = session_usage;
+        let _connection = connection;
+        let _stream_session = stream_session;
+        let messages = messages.max_frame_size(MAX_FRAME_BYTES);
+        let reason = forward(&state, viewer, session.clone(), messages, updates).await;
+        let _ = session.close(reason).await;
+    });
+
+    Ok(response)
+}
+
+/// Relay events and answer the client; returns why the connection is being closed
+async fn forward(
+    state: &AppState,
+    mut viewer: StreamViewer,
+    mut session: Session,
+    mut messages: actix_ws::MessageStream,
+    mut updates: tokio::sync::broadcast::Receiver<BroadcastEvent>,
+) -> Option<CloseReason> {
+    let mut subscription = Subscription(None);
+    let mut ping = interval(PING_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = ping.tick() => {
+                if !viewer.refresh(state).await {
+                    return Some(CloseReason { code: CloseCode::Policy, description: Some("Token expired or revoked".to_string()) });
+                }
+                if session.ping(b"").await.is_err() {
+                    return None;
+                }
+            }
+            update = updates.recv() => match update {
+                Ok(event) if viewer.may_see(&event) && subscription.wants(&event) => {
+                    SSE_EVENTS_SENT.with_label_values(&[event.event.event_type()]).inc();
+                    if session.text(event.json()).await.is_err() {
+                        return None;
+                    }
+                }
+                Ok(_) => {}
+                // Like the SSE stream: a client too slow to keep up reconnects
+                Err(RecvError::Lagged(_)) => {
+                    return Some(CloseReason { code: CloseCode::Again, description: Some("Fell behind; reconnect".to_string()) });
+                }
+                Err(RecvError::Closed) => return Some(CloseCode::Restart.into()),
+            },
+            message = messages.recv() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    let reply = match serde_json::from_str::<WsClientMessage>(&text) {
+                        Ok(WsClientMessage::Subscribe { events }) => match subscription.update(&events) {
+                            Ok(()) => json!({"type": "subscribed", "events": subscription.event_types()}),
+                            Err(message) => json!({"type": "error", "message": message}),
+                        },
+                        Err(e) => json!({"type": "error", "message": format!("Invalid message: {}", e)}),
+                    };
+                    if session.text(reply.to_string()).await.is_err() {
+                        return None;
+                    }
+                }
+                Some(Ok(Message::Ping(bytes))) => {
+                    if session.pong(&bytes).await.is_err() {
+                        return None;
+                    }
+                }
+                Some(Ok(Message::Pong(_))) => {}
+                Some(Ok(Message::Close(reason))) => return reason,
+                Some(Ok(_)) => return Some(CloseCode::Unsupported.into()),
+                Some(Err(_)) => return Some(CloseCode::Protocol.into()),
+                None => return None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::LatestVitals;
+
+    #[test]
+    fn test_subscription_filters_event_types() {
+        let vitals = EventEnvelope::new(SseEvent::Vitals(LatestVitals {
+            heart_rate: 72,
+            spo2: 98,
+            temperature: 36.8,
+            timestamp: 0,
+            quality_score: None,
+            ml_alert: None,
+        }));
+        let mut subscription = Subscription(None);
+        assert!(subscription.wants(&vitals));
+
+        subscription.update(&["alert".to_string()]).unwrap();
+        assert!(!subscription.wants(&vitals));
+        assert_eq!(subscription.event_types(), ["alert"]);
+
+        assert!(subscription.update(&["vitals".to_string(), "bogus".to_string()]).is_err());
+        assert_eq!(subscription.event_types(), ["alert"]);
+
+        subscription.update(&[]).unwrap();
+        assert!(subscription.wants(&vitals));
+    }
+}
@@ -0,0 +1,124 @@
+//! End-to-end SSE harness: runs the real app on a loopback port and reads
+//! `/api/stream/vitals` over HTTP exactly as a dashboard would, so tests can trigger
+//! ingestion and assert which events arrive, in what order, within a deadline.
+
+use actix_web::{web, HttpServer};
+use medhealth_backend::{app::build_app, handlers::AppState};
+use medhealth_client::{Client, DeviceClient, EventStream, RetryPolicy};
+use sqlx::PgPool;
+use std::time::Duration;
+
+/// Serve the app on an ephemeral loopback port and return its base URL
+pub fn spawn_server(state: AppState) -> String {
+    let state = web::Data::new(state);
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = HttpServer::new(move || build_app(state.clone()))
+        .listen(listener)
+        .unwrap()
+        .workers(1)
+        .run();
+    actix_web::rt::spawn(server);
+    format!("http://{}", addr)
+}
+
+/// Sign up over HTTP (if needed), set the user's role directly in the database, and log in
+pub async fn login_as(base_url: &str, pool: &PgPool, email: &str, role: &str) -> String {
+    let mut client = Client::new(base_url);
+    let _ = client.signup(email, "SecurePass123!").await;
+    sqlx::query("UPDATE users SET role = $1 WHERE email = $2")
+        .bind(role)
+        .bind(email)
+        .execute(pool)
+        .await
+        .expect("Failed to set test user role");
+    client.login(email, "SecurePass123!").await.expect("login failed").token
+}
+
+/// POST a device payload signed the way walker firmware signs it
+pub async fn post_signed(
+    base_url: &str,
+    path: &str,
+    device_id: &str,
+    secret: &str,
+    body: &serde_json::Value,
+) -> reqwest::Response {
+    DeviceClient::new(base_url, device_id, secret)
+        .with_retry(RetryPolicy::none())
+        .post_signed(path, body)
+        .await
+        .expect("ingestion request failed")
+}
+
+/// One event from the stream; `data` is the payload inside the event envelope
+#[derive(Debug, Clone)]
+pub struct SseMessage {
+    pub event: String,
+    pub data: serde_json::Value,
+}
+
+/// A live connection to the vitals stream
+pub struct SseClient {
+    stream: EventStream,
+}
+
+impl SseClient {
+    /// Open the stream as the token's user; the server has subscribed to the broadcaster
+    /// once this returns
+    pub async fn connect(base_url: &str, token: &str) -> Self {
+        let stream = Client::new(base_url).with_token(token).events().await.expect("SSE connection failed");
+        Self { stream }
+    }
+
+    /// Next event of any type, or `None` if nothing arrives in time
+    pub async fn next_message(&mut self, within: Duration) -> Option<SseMessage> {
+        let event = tokio::time::timeout(within, self.stream.next()).await.ok()?;
+        let event = event.expect("SSE stream errored").expect("SSE stream closed by the server");
+        assert_eq!(event.version, 1, "unexpected envelope {:?}", event);
+        Some(SseMessage {
+            event: event.event_type,
+            data: event.data,
+        })
+    }
+
+    /// Next frame that is not a heartbeat; panics on timeout
+    pub async fn next_event(&mut self, within: Duration) -> SseMessage {
+        let deadline = tokio::time::Instant::now() + within;
+        loop {
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            match self.next_message(remaining).await {
+                Some(message) if message.event == "heartbeat" => continue,
+                Some(message) => return message,
+                None => panic!("no SSE event within {:?}", within),
+            }
+        }
+    }
+
+    /// Assert the next non-heartbeat events carry exactly these names, in order
+    pub async fn expect_events(&mut self, names: &[&str], within: Duration) -> Vec<SseMessage> {
+        let mut received = Vec::with_capacity(names.len());
+        for name in names {
+            let message = self.next_event(within).await;
+            assert_eq!(
+                message.event, *name,
+                "expected events {:?}, got {:?} then {:?}",
+                names, received, message
+            );
+            received.push(message);
+        }
+        received
+    }
+
+    /// Assert nothing but heartbeats arrives for the given time
+    pub async fn expect_quiet(&mut self, within: Duration) {
+        let deadline = tokio::time::Instant::now() + within;
+        loop {
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            match self.next_message(remaining).await {
+                Some(message) if message.event == "heartbeat" => continue,
+                Some(message) => panic!("unexpected SSE event {:?}", message),
+                None => return,
+            }
+        }
+    }
+}
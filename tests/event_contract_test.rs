@@ -0,0 +1,205 @@
+//! Contract tests for published events.
+//!
+//! Every SSE and webhook event is serialized exactly as it goes out and validated against
+//! its JSON Schema in `schemas/events/v{EVENT_SCHEMA_VERSION}`. A payload change that breaks
+//! a schema fails here; update the schema (and bump the version if the change is not
+//! additive) rather than the sample.
+
+use chrono::{NaiveDate, Utc};
+use jsonschema::JSONSchema;
+use medhealth_backend::models::*;
+use medhealth_backend::rbac::Role;
+use medhealth_backend::sse::{
+    broadcast_alert, broadcast_alert_message, broadcast_alert_status, broadcast_reminder, broadcast_vitals, create_broadcaster, publish,
+};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+fn schema(event_type: &str) -> JSONSchema {
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "schemas",
+        "events",
+        &format!("v{}", EVENT_SCHEMA_VERSION),
+        &format!("{}.schema.json", event_type),
+    ]
+    .iter()
+    .collect();
+    let source = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("no schema at {}: {}", path.display(), e));
+    let schema: Value = serde_json::from_str(&source).unwrap();
+    JSONSchema::compile(&schema).unwrap_or_else(|e| panic!("invalid schema {}: {}", path.display(), e))
+}
+
+fn assert_valid(event: &Value) {
+    let event_type = event["type"].as_str().expect("event without a type");
+    let schema = schema(event_type);
+    if let Err(errors) = schema.validate(event) {
+        let errors: Vec<String> = errors.map(|e| format!("{} at {}", e, e.instance_path)).collect();
+        panic!("{} event violates its schema: {:?}\n{}", event_type, errors, event);
+    };
+}
+
+/// The schema file for each event type; the exhaustive match makes new variants fail to
+/// compile until they are listed (and given a schema)
+fn sse_type(event: &SseEvent) -> &'static str {
+    match event {
+        SseEvent::Vitals(_) => "vitals",
+        SseEvent::Alert(_) => "alert",
+        SseEvent::Heartbeat { .. } => "heartbeat",
+        SseEvent::Reminder(_) => "reminder",
+        SseEvent::AlertMessage(_) => "alert_message",
+        SseEvent::AlertStatus(_) => "alert_status",
+    }
+}
+
+fn webhook_type(event: &WebhookEvent) -> &'static str {
+    match event {
+        WebhookEvent::UsageDaily { .. } => "usage_daily",
+        WebhookEvent::ContactRequested { .. } => "contact_requested",
+        WebhookEvent::IntegrationTest { .. } => "integration_test",
+    }
+}
+
+fn vitals(ml_alert: Option<AlertLevel>) -> LatestVitals {
+    LatestVitals {
+        heart_rate: 72,
+        spo2: 97,
+        temperature: 36.8,
+        timestamp: Utc::now().timestamp(),
+        quality_score: ml_alert.map(|_| 0.9),
+        ml_alert,
+    }
+}
+
+fn alert_message(reply: bool) -> AlertMessage {
+    AlertMessage {
+        id: Uuid::new_v4(),
+        alert_id: Uuid::new_v4(),
+        parent_id: reply.then(Uuid::new_v4),
+        author_id: reply.then(Uuid::new_v4),
+        author_email: reply.then(|| "nurse@example.com".to_string()),
+        author_role: reply.then_some(Role::Clinician),
+        body: "On my way".to_string(),
+        created_at: Utc::now(),
+    }
+}
+
+fn reminder() -> MedicationReminder {
+    MedicationReminder {
+        dose_id: Uuid::new_v4(),
+        medication_id: Uuid::new_v4(),
+        patient_id: Uuid::new_v4(),
+        name: "Metoprolol".to_string(),
+        dosage: "25 mg".to_string(),
+        scheduled_at: Utc::now(),
+    }
+}
+
+#[test]
+fn test_broadcast_events_match_their_schemas() {
+    let broadcaster = create_broadcaster();
+    let mut rx = broadcaster.subscribe();
+
+    broadcast_vitals(&broadcaster, None, vitals(None));
+    broadcast_vitals(&broadcaster, Some(Uuid::new_v4()), vitals(Some(AlertLevel::Critical)));
+    broadcast_alert(&broadcaster, None, MlAlert {
+        level: AlertLevel::High,
+        message: "Abnormal vital signs detected. Medical review recommended.".to_string(),
+        details: json!({"anomalies": ["Fever detected"]}),
+    });
+    broadcast_reminder(&broadcaster, reminder());
+    broadcast_alert_message(&broadcaster, None, alert_message(false));
+    broadcast_alert_message(&broadcaster, None, alert_message(true));
+    for (status, patient_id) in [(AlertStatus::Acknowledged, Some(Uuid::new_v4())), (AlertStatus::Resolved, None)] {
+        broadcast_alert_status(&broadcaster, AlertStatusChange {
+            alert_id: Uuid::new_v4(),
+            patient_id,
+            kind: "vitals".to_string(),
+            level: AlertLevel::Critical,
+            status,
+            changed_by: Some(Uuid::new_v4()),
+            changed_at: Utc::now(),
+        });
+    }
+    publish(&broadcaster, EventEnvelope::new(SseEvent::Heartbeat { timestamp: Utc::now().timestamp() }));
+
+    let mut seen = Vec::new();
+    while let Ok(envelope) = rx.try_recv() {
+        // Validate what actually goes over the wire: the data line of the SSE frame
+        let frame = String::from_utf8(envelope.frame().to_vec()).unwrap();
+        let data = frame.lines().find_map(|l| l.strip_prefix("data: ")).unwrap();
+        let event: Value = serde_json::from_str(data).unwrap();
+
+        assert_eq!(event["type"], sse_type(&envelope.event));
+        assert_valid(&event);
+        seen.push(sse_type(&envelope.event));
+    }
+    seen.dedup();
+    assert_eq!(seen, vec!["vitals", "alert", "reminder", "alert_message", "alert_status", "heartbeat"]);
+}
+
+#[test]
+fn test_webhook_events_match_their_schemas() {
+    let day = NaiveDate::from_ymd_opt(2026, 3, 1).unwrap();
+    let events = [
+        WebhookEvent::UsageDaily {
+            day,
+            usage: vec![
+                UsageRollup { organization_id: Some(Uuid::new_v4()), day, metric: "readings_ingested".into(), quantity: 1440 },
+                UsageRollup { organization_id: None, day, metric: "sse_minutes".into(), quantity: 0 },
+            ],
+        },
+        WebhookEvent::UsageDaily { day, usage: vec![] },
+        WebhookEvent::ContactRequested {
+            channel: "sms".into(),
+            to: "+15555550100".into(),
+            message: "SOS from walker".into(),
+            alert_id: Uuid::new_v4(),
+        },
+        WebhookEvent::IntegrationTest { integration_id: Uuid::new_v4() },
+    ];
+
+    for event in events {
+        let event_type = webhook_type(&event);
+        let body = serde_json::to_value(EventEnvelope::new(event)).unwrap();
+        assert_eq!(body["type"], event_type);
+        assert_valid(&body);
+    }
+}
+
+#[test]
+fn test_envelopes_round_trip_through_serde() {
+    let envelope = EventEnvelope::new(SseEvent::Vitals(vitals(Some(AlertLevel::High)))).for_patient(Some(Uuid::new_v4()));
+    let json = serde_json::to_value(&envelope).unwrap();
+    assert_eq!(json["version"], EVENT_SCHEMA_VERSION);
+    assert!(json.get("patient_id").is_none());
+
+    let parsed: EventEnvelope = serde_json::from_value(json).unwrap();
+    assert_eq!(parsed.id, envelope.id);
+    assert!(matches!(parsed.event, SseEvent::Vitals(ref v) if v.ml_alert == Some(AlertLevel::High)));
+
+    // Unknown types are rejected rather than silently mis-parsed
+    let unknown = json!({"version": 1, "type": "teleport", "id": Uuid::new_v4(), "occurred_at": Utc::now(), "data": {}});
+    assert!(serde_json::from_value::<EventEnvelope>(unknown).is_err());
+}
+
+#[test]
+fn test_schemas_reject_contract_breaks() {
+    let valid = serde_json::to_value(EventEnvelope::new(SseEvent::Vitals(vitals(None)))).unwrap();
+    assert_valid(&valid);
+    let schema = schema("vitals");
+
+    let mut renamed = valid.clone();
+    let heart_rate = renamed["data"].as_object_mut().unwrap().remove("heartRate").unwrap();
+    renamed["data"]["heart_rate"] = heart_rate;
+    assert!(!schema.is_valid(&renamed));
+
+    let mut future = valid.clone();
+    future["version"] = json!(2);
+    assert!(!schema.is_valid(&future));
+
+    let mut bare = valid;
+    bare.as_object_mut().unwrap().remove("occurred_at");
+    assert!(!schema.is_valid(&bare));
+}
@@ -0,0 +1,210 @@
+//! Contract tests against a reference FHIR server (HAPI FHIR).
+//!
+//! Optional: they only run when `FHIR_CONTRACT_URL` points at a FHIR R4 base, e.g.
+//!
+//! ```bash
+//! docker compose --profile fhir-contract up -d hapi-fhir
+//! FHIR_CONTRACT_URL=http://localhost:8090/fhir cargo test --test fhir_contract_test
+//! ```
+//!
+//! Every resource the builders produce is run through the server's `$validate`, and
+//! every bundle must be accepted for storage.
+
+use chrono::{NaiveDate, Utc};
+use medhealth_backend::config::FhirConfig;
+use medhealth_backend::fhir_service::FhirService;
+use medhealth_backend::models::{CarePlan, Checkin, Device, Patient, SensorReading};
+use serde_json::Value;
+use uuid::Uuid;
+
+fn contract_url() -> Option<String> {
+    match std::env::var("FHIR_CONTRACT_URL") {
+        Ok(url) if !url.is_empty() => Some(url.trim_end_matches('/').to_string()),
+        _ => {
+            eprintln!("FHIR_CONTRACT_URL not set; skipping FHIR contract test");
+            None
+        }
+    }
+}
+
+fn fhir_service() -> FhirService {
+    FhirService::new(FhirConfig {
+        base_url: "http://localhost:8080/fhir".to_string(),
+        organization_id: "org-contract-test".to_string(),
+    })
+}
+
+fn reading(heart_rate: Option<i32>, spo2: Option<i32>, temperature: Option<f32>) -> SensorReading {
+    SensorReading {
+        id: 1,
+        device_id: Uuid::new_v4(),
+        heart_rate,
+        spo2,
+        temperature,
+        reading_timestamp: Utc::now(),
+        received_at: Utc::now(),
+        quality_score: Some(0.9),
+        metadata: serde_json::json!({}),
+    }
+}
+
+fn patient() -> Patient {
+    Patient {
+        id: Uuid::new_v4(),
+        display_name: "Contract Test Patient".to_string(),
+        date_of_birth: NaiveDate::from_ymd_opt(1944, 6, 6),
+    }
+}
+
+fn device(patient: Option<&Patient>) -> Device {
+    Device {
+        id: Uuid::new_v4(),
+        device_id: "WALKER-CONTRACT".to_string(),
+        device_name: "Contract test walker".to_string(),
+        secret_hash: String::new(),
+        secret_ciphertext: None,
+        secret_issued_at: None,
+        is_active: true,
+        created_at: Utc::now(),
+        last_seen_at: None,
+        metadata: serde_json::json!({}),
+        patient_id: patient.map(|p| p.id),
+        claimed_by: None,
+        claimed_at: None,
+        units: Default::default(),
+    }
+}
+
+fn care_plan(full: bool) -> CarePlan {
+    CarePlan {
+        id: Uuid::new_v4(),
+        patient_id: Uuid::new_v4(),
+        title: "Post-op mobility".to_string(),
+        status: "active".to_string(),
+        target_steps: full.then_some(3000),
+        target_activity_minutes: full.then_some(30),
+        prescribed_usage_minutes: full.then_some(60),
+        hr_min: full.then_some(50),
+        hr_max: full.then_some(120),
+        spo2_min: full.then_some(92),
+        start_date: NaiveDate::from_ymd_opt(2026, 1, 5).unwrap(),
+        end_date: full.then(|| NaiveDate::from_ymd_opt(2026, 3, 5).unwrap()),
+        created_by: None,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+    }
+}
+
+fn checkin(full: bool) -> Checkin {
+    Checkin {
+        id: Uuid::new_v4(),
+        patient_id: Uuid::new_v4(),
+        submitted_by: full.then(Uuid::new_v4),
+        pain_score: Some(4),
+        dizziness: full.then_some(2),
+        fatigue: full.then_some(6),
+        shortness_of_breath: full.then_some(false),
+        fell_since_last: Some(false),
+        notes: full.then(|| "Walked to the garden & back <slowly>".to_string()),
+        submitted_at: Utc::now(),
+    }
+}
+
+/// Issues of error or fatal severity in an OperationOutcome
+fn errors(outcome: &Value) -> Vec<String> {
+    outcome["issue"]
+        .as_array()
+        .map(|issues| {
+            issues
+                .iter()
+                .filter(|issue| matches!(issue["severity"].as_str(), Some("error") | Some("fatal")))
+                .map(|issue| {
+                    format!(
+                        "{} at {}",
+                        issue["diagnostics"].as_str().unwrap_or("?"),
+                        issue["expression"]
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+async fn assert_valid(client: &reqwest::Client, base: &str, resource: &Value) {
+    let resource_type = resource["resourceType"].as_str().expect("resourceType");
+    let resp = client
+        .post(format!("{}/{}/$validate", base, resource_type))
+        .header("Content-Type", "application/fhir+json")
+        .json(resource)
+        .send()
+        .await
+        .expect("FHIR server unreachable");
+    let outcome: Value = resp.json().await.expect("$validate should return an OperationOutcome");
+    let problems = errors(&outcome);
+    assert!(problems.is_empty(), "{} failed validation: {:#?}", resource_type, problems);
+}
+
+async fn assert_bundle_accepted(client: &reqwest::Client, base: &str, bundle: &Value) {
+    for entry in bundle["entry"].as_array().expect("bundle entries") {
+        assert_valid(client, base, &entry["resource"]).await;
+    }
+    assert_valid(client, base, bundle).await;
+
+    let resp = client
+        .post(format!("{}/Bundle", base))
+        .header("Content-Type", "application/fhir+json")
+        .json(bundle)
+        .send()
+        .await
+        .expect("FHIR server unreachable");
+    let status = resp.status();
+    let body: Value = resp.json().await.unwrap_or(Value::Null);
+    assert_eq!(status, 201, "bundle was not committed: {:#?}", errors(&body));
+}
+
+#[actix_web::test]
+async fn test_observation_bundles_validate_and_commit() {
+    let Some(base) = contract_url() else { return };
+    let client = reqwest::Client::new();
+    let fhir = fhir_service();
+
+    // Every combination of optional vitals, with and without a patient subject, each
+    // with the Patient and Device its references resolve to
+    for mask in 1..8u8 {
+        let reading = reading(
+            (mask & 1 != 0).then_some(74),
+            (mask & 2 != 0).then_some(96),
+            (mask & 4 != 0).then_some(36.9),
+        );
+        for patient in [None, Some(patient())] {
+            let device = device(patient.as_ref());
+            let reading = SensorReading { device_id: device.id, ..reading.clone() };
+            let bundle = fhir.create_observation_bundle(&reading, Some(&device), patient.as_ref());
+            assert_bundle_accepted(&client, &base, &bundle).await;
+        }
+    }
+}
+
+#[actix_web::test]
+async fn test_care_plan_bundles_validate_and_commit() {
+    let Some(base) = contract_url() else { return };
+    let client = reqwest::Client::new();
+    let fhir = fhir_service();
+
+    for full in [false, true] {
+        let bundle = fhir.create_care_plan_bundle(&care_plan(full));
+        assert_bundle_accepted(&client, &base, &bundle).await;
+    }
+}
+
+#[actix_web::test]
+async fn test_checkin_questionnaire_responses_validate() {
+    let Some(base) = contract_url() else { return };
+    let client = reqwest::Client::new();
+    let fhir = fhir_service();
+
+    for full in [false, true] {
+        let response = fhir.create_checkin_questionnaire_response(&checkin(full));
+        assert_valid(&client, &base, &response).await;
+    }
+}
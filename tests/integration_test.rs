@@ -1,22 +1,28 @@
 use actix_web::{test, web, App, http::header};
 use medhealth_backend::{
-    auth::JwtAuth,
-    config::{DatabaseConfig, JwtConfig, RedisConfig, MlConfig, FhirConfig},
-    database::{create_pool, run_migrations},
-    handlers::{health_check, signup, login, logout, get_latest_vitals, device_ingest, export_fhir_bundle, AppState},
-    ml_service::MlService,
-    fhir_service::FhirService,
-    redis_cache::RedisCache,
-    sse,
+    activity_service,
+    app::{build_app, init_state},
+    auth::device_signature,
+    baseline_service,
+    config::{
+        AlertRoutingConfig, BillingConfig, ComplianceConfig, CorsConfig, DatabaseConfig, DeploymentConfig,
+        DeploymentMode, DeviceConfig, EmergencyConfig, EncryptionConfig, FhirConfig, HeartbeatConfig, JwtConfig,
+        LockoutConfig, LoggingConfig, MlConfig, MqttConfig, NotificationsConfig, ObservabilityConfig, Profile, QueryDebugConfig, QuotaConfig, RateLimitConfig, RedisConfig, ReportingConfig, RetentionConfig, ServerConfig,
+        Settings, SloConfig, SloObjective, VoiceConfig,
+    },
+    database::create_pool,
+    handlers::{health_check, version},
+    ml_service::{STATISTICAL_HR, TACHYCARDIA},
+    models::{DeviceEventIngest, DeviceVitalsIngest},
+    quota_service, sleep_service, usage_service,
 };
 use serde_json::json;
-use sqlx::PgPool;
-use std::sync::Arc;
-use tokio::sync::RwLock;
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
 use base64::{engine::general_purpose, Engine as _};
 
+mod common;
+
 type HmacSha256 = Hmac<Sha256>;
 
 // Test database URL - should use test database
@@ -25,65 +31,137 @@ const TEST_REDIS_URL: &str = "redis://localhost:6379";
 const TEST_JWT_SECRET: &str = "test_secret_key_minimum_32_chars_long_for_security_testing";
 const TEST_DEVICE_SECRET: &str = "test_device_secret_for_hmac_testing_32_chars";
 
-/// Builds and returns the test App inline so the concrete type is known to init_service.
+fn test_settings() -> Settings {
+    Settings {
+        profile: Profile::Dev,
+        server: ServerConfig {
+            bind_addr: "127.0.0.1:0".to_string(),
+            workers: Some(1),
+        },
+        database: DatabaseConfig {
+            url: TEST_DATABASE_URL.to_string(),
+            max_connections: 5,
+            min_connections: 1,
+        },
+        redis: RedisConfig {
+            url: TEST_REDIS_URL.to_string(),
+            pool_size: 5,
+        },
+        jwt: JwtConfig {
+            secret: TEST_JWT_SECRET.to_string(),
+            expiration_hours: 24,
+            refresh_token_days: 7,
+        },
+        cors: CorsConfig {
+            allowed_origins: vec!["http://localhost:5173".to_string()],
+            allow_any_origin: false,
+        },
+        device: DeviceConfig {
+            secret: TEST_DEVICE_SECRET.to_string(),
+            replay_window_seconds: 60,
+            pairing_code_ttl_minutes: 30,
+            require_device_secrets: false,
+            secret_encryption_key: Some("BwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwc=".to_string()),
+        },
+        ml: MlConfig {
+            anomaly_threshold: 0.85,
+            enable_alerts: true,
+            critical_hr_low: 40,
+            critical_hr_high: 180,
+            critical_spo2_low: 88,
+            alert_cooldown_seconds: 300,
+            model: None,
+        },
+        fhir: FhirConfig {
+            base_url: "http://localhost:8080/fhir".to_string(),
+            organization_id: "org-test-001".to_string(),
+        },
+        logging: LoggingConfig {
+            level: "info".to_string(),
+            audit_log_path: "./logs/audit.log".to_string(),
+            enable_phi_encryption: false,
+        },
+        deployment: DeploymentConfig {
+            mode: DeploymentMode::Clinical,
+            max_patients: None,
+            strict_rbac: true,
+            consumer_notifications: false,
+            ehr_push: true,
+        },
+        emergency: EmergencyConfig {
+            contact_webhook_url: None,
+            contact_webhook_secret: None,
+            webhook_timeout_seconds: 5,
+        },
+        voice: VoiceConfig {
+            enabled: false,
+            account_sid: None,
+            auth_token: None,
+            from_number: None,
+            api_base_url: "https://api.twilio.com".to_string(),
+            callback_base_url: None,
+            escalate_after_minutes: 5,
+            tts_voice: "Polly.Joanna".to_string(),
+        },
+        retention: RetentionConfig {
+            sensor_readings_days: None,
+            export_signing_key: Some("test_export_signing_key_at_least_32_bytes".to_string()),
+        },
+        compliance: ComplianceConfig { non_use_alert_days: 3 },
+        // Tests that exercise on-call routing build their own notifier
+        alert_routing: AlertRoutingConfig { on_call_levels: vec![], ..Default::default() },
+        query_debug: QueryDebugConfig::default(),
+        quota: QuotaConfig::default(),
+        billing: BillingConfig::default(),
+        encryption: EncryptionConfig::default(),
+        heartbeat: HeartbeatConfig::default(),
+        slo: SloConfig::default(),
+        observability: ObservabilityConfig::default(),
+        mqtt: MqttConfig::default(),
+        notifications: NotificationsConfig::default(),
+        reporting: ReportingConfig::default(),
+        // Every test request comes from the same client; rate limits are tested on their own
+        rate_limit: RateLimitConfig { enabled: false, ..Default::default() },
+        lockout: LockoutConfig::default(),
+    }
+}
+
+/// Builds the same App as `main`, backed by the test database and Redis.
 macro_rules! build_test_app {
     () => {{
-        async {
-            let db_config = DatabaseConfig {
-                url: TEST_DATABASE_URL.to_string(),
-                max_connections: 5,
-                min_connections: 1,
-            };
-            let pool = create_pool(&db_config).await.expect("Failed to create test database pool");
-            run_migrations(&pool).await.expect("Failed to run migrations");
-            let redis = RedisCache::new(&RedisConfig {
-                url: TEST_REDIS_URL.to_string(),
-                pool_size: 5,
-            })
+        let state = init_state(&test_settings())
             .await
-            .expect("Redis required for integration tests. Run: docker-compose up -d redis");
-            let redis = Arc::new(RwLock::new(redis));
-            let jwt_config = JwtConfig {
-                secret: TEST_JWT_SECRET.to_string(),
-                expiration_hours: 24,
-                refresh_token_days: 7,
-            };
-            let jwt_auth = Arc::new(JwtAuth::new(&jwt_config));
-            let ml_service = Arc::new(MlService::new(MlConfig {
-                anomaly_threshold: 0.85,
-                enable_alerts: true,
-                critical_hr_low: 40,
-                critical_hr_high: 180,
-                critical_spo2_low: 88,
-            }));
-            let fhir_service = Arc::new(FhirService::new(FhirConfig {
-                base_url: "http://localhost:8080/fhir".to_string(),
-                organization_id: "org-test-001".to_string(),
-            }));
-            let sse_broadcaster = sse::create_broadcaster();
-            let app_state = web::Data::new(AppState {
-                pool: pool.clone(),
-                redis: redis.clone(),
-                jwt_auth: jwt_auth.clone(),
-                ml_service: ml_service.clone(),
-                fhir_service: fhir_service.clone(),
-                sse_broadcaster: sse_broadcaster.clone(),
-                device_secret: TEST_DEVICE_SECRET.to_string(),
-                replay_window_seconds: 60,
-            });
-            App::new()
-                .app_data(app_state.clone())
-                .app_data(web::Data::new(pool.clone()))
-                .app_data(web::Data::new(sse_broadcaster.clone()))
-                .route("/health", web::get().to(health_check))
-                .route("/auth/signup", web::post().to(signup))
-                .route("/auth/login", web::post().to(login))
-                .route("/auth/logout", web::post().to(logout))
-                .route("/api/vitals/latest", web::get().to(get_latest_vitals))
-                .route("/api/fhir/export", web::get().to(export_fhir_bundle))
-                .route("/api/device/vitals", web::post().to(device_ingest))
-        }
-        .await
+            .expect("PostgreSQL and Redis required for integration tests. Run: docker-compose up -d");
+        build_app(web::Data::new(state))
+    }};
+}
+
+/// Sign up (if needed), set the user's role directly in the database, and log in.
+macro_rules! login_as {
+    ($app:expr, $email:expr, $role:expr) => {{
+        let _ = test::call_service(&$app,
+            test::TestRequest::post()
+                .uri("/auth/signup")
+                .set_json(json!({"email": $email, "password": "SecurePass123!"}))
+                .to_request()
+        ).await;
+
+        let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+        sqlx::query("UPDATE users SET role = $1::user_role WHERE email = $2")
+            .bind($role)
+            .bind($email)
+            .execute(&pool)
+            .await
+            .expect("Failed to set test user role");
+
+        let resp = test::call_service(&$app,
+            test::TestRequest::post()
+                .uri("/auth/login")
+                .set_json(json!({"email": $email, "password": "SecurePass123!"}))
+                .to_request()
+        ).await;
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        body["token"].as_str().expect("login should return a token").to_string()
     }};
 }
 
@@ -114,6 +192,19 @@ async fn test_health_endpoint() {
     assert!(resp.status().is_success() || resp.status().is_server_error());
 }
 
+#[actix_web::test]
+async fn test_version_endpoint() {
+    let app = test::init_service(App::new().route("/version", web::get().to(version))).await;
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/version").to_request()).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["name"], "medhealth-backend");
+    assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+    assert!(body["features"].is_array());
+    assert!(body["built_at"].is_string());
+}
+
 #[actix_web::test]
 async fn test_signup_valid_user() {
     let app = test::init_service(build_test_app!()).await;
@@ -170,6 +261,80 @@ async fn test_login_invalid_credentials() {
     assert!(resp.status().is_client_error());
 }
 
+#[actix_web::test]
+async fn test_login_attempts_rate_limited_per_ip() {
+    let mut settings = test_settings();
+    settings.rate_limit = RateLimitConfig { auth_per_minute: 3, ..Default::default() };
+    let state = init_state(&settings).await.expect("PostgreSQL and Redis required for integration tests");
+    let app = test::init_service(build_app(web::Data::new(state))).await;
+
+    // Fresh addresses per run, so counts left in Redis by earlier runs don't apply
+    let random_ip = || -> std::net::SocketAddr {
+        format!("10.{}.{}.{}:40000", rand::random::<u8>(), rand::random::<u8>(), rand::random::<u8>()).parse().unwrap()
+    };
+    let login = |from: std::net::SocketAddr| {
+        test::TestRequest::post()
+            .uri("/auth/login")
+            .peer_addr(from)
+            .set_json(json!({"email": "nonexistent@example.com", "password": "WrongPassword123!"}))
+            .to_request()
+    };
+
+    let attacker = random_ip();
+    for _ in 0..3 {
+        let resp = test::call_service(&app, login(attacker)).await;
+        assert_eq!(resp.status(), 401);
+    }
+    let resp = test::call_service(&app, login(attacker)).await;
+    assert_eq!(resp.status(), 429);
+    let retry_after: u64 = resp.headers().get("Retry-After").unwrap().to_str().unwrap().parse().unwrap();
+    assert!((1..=120).contains(&retry_after));
+
+    // Other clients are unaffected
+    let resp = test::call_service(&app, login(random_ip())).await;
+    assert_eq!(resp.status(), 401);
+}
+
+#[actix_web::test]
+async fn test_account_locked_after_failed_logins_until_unlocked() {
+    let app = test::init_service(build_test_app!()).await;
+    let email = format!("lockout-{}@example.com", uuid::Uuid::new_v4());
+    let resp = test::call_service(&app,
+        test::TestRequest::post()
+            .uri("/auth/signup")
+            .set_json(json!({"email": email, "password": "SecurePass123!"}))
+            .to_request()
+    ).await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let user_id = body["user"]["id"].as_str().unwrap().to_string();
+    let login = |password: &str| {
+        test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(json!({"email": email, "password": password}))
+            .to_request()
+    };
+
+    for _ in 0..5 {
+        let resp = test::call_service(&app, login("WrongPassword123!")).await;
+        assert_eq!(resp.status(), 401);
+    }
+    // Locked: even the right password is refused
+    let resp = test::call_service(&app, login("SecurePass123!")).await;
+    assert_eq!(resp.status(), 403);
+
+    let admin = login_as!(app, "unlockadmin@example.com", "admin");
+    let resp = test::call_service(&app,
+        test::TestRequest::post()
+            .uri(&format!("/api/admin/users/{}/unlock", user_id))
+            .insert_header(("Authorization", format!("Bearer {}", admin)))
+            .to_request()
+    ).await;
+    assert_eq!(resp.status(), 204);
+
+    let resp = test::call_service(&app, login("SecurePass123!")).await;
+    assert_eq!(resp.status(), 200);
+}
+
 #[actix_web::test]
 async fn test_login_valid_credentials() {
     let app = test::init_service(build_test_app!()).await;
@@ -225,7 +390,7 @@ async fn test_jwt_authentication() {
     let login_resp = test::call_service(&app,
         test::TestRequest::post()
             .uri("/auth/login")
-            .set_json(&json!({
+            .set_json(json!({
                 "email": "jwttest@example.com",
                 "password": "SecurePass123!"
             }))
@@ -381,3 +546,5014 @@ async fn test_device_ingestion_timestamp_replay() {
     let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), 401); // Should reject old timestamp
 }
+
+#[actix_web::test]
+async fn test_device_ingestion_wrong_content_type() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "contenttypeadmin@example.com", "admin");
+    let serial = format!("WALKER-CT-{}", uuid::Uuid::new_v4());
+    let req = test::TestRequest::post()
+        .uri("/api/admin/devices")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .set_json(json!({"device_id": serial, "device_name": "Content-Type Walker"}))
+        .to_request();
+    let device: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let secret = device["secret"].as_str().unwrap().to_string();
+
+    let timestamp = chrono::Utc::now().timestamp();
+    let payload = r#"{"heartRate":75,"spo2":98,"temperature":36.8,"timestamp":0}"#;
+    let request = |signature: String| test::TestRequest::post()
+        .uri("/api/device/vitals")
+        .insert_header((header::CONTENT_TYPE, "text/plain"))
+        .insert_header(("X-Device-Id", serial.as_str()))
+        .insert_header(("X-Timestamp", timestamp.to_string()))
+        .insert_header(("X-Signature", signature))
+        .set_payload(payload)
+        .to_request();
+
+    // Unsigned callers learn nothing about the body
+    let resp = test::call_service(&app, request("invalid_signature".to_string())).await;
+    assert_eq!(resp.status(), 401);
+
+    let resp = test::call_service(&app, request(device_signature(&secret, timestamp, payload))).await;
+    assert_eq!(resp.status(), 415);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "Unsupported Content-Type");
+}
+
+#[actix_web::test]
+async fn test_fhir_export_not_acceptable() {
+    let app = test::init_service(build_test_app!()).await;
+
+    let token = login_as!(app, "accepttest@example.com", "viewer");
+
+    let req = test::TestRequest::get()
+        .uri("/api/fhir/export")
+        .insert_header((header::ACCEPT, "text/csv"))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 406);
+
+    let req = test::TestRequest::get()
+        .uri("/api/fhir/export?limit=5")
+        .insert_header((header::ACCEPT, "application/x-ndjson"))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), "application/x-ndjson");
+}
+
+#[actix_web::test]
+async fn test_head_and_route_listing_requires_admin() {
+    let app = test::init_service(build_test_app!()).await;
+
+    let req = test::TestRequest::default()
+        .method(actix_web::http::Method::HEAD)
+        .uri("/health")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+
+    let token = login_as!(app, "routestest@example.com", "viewer");
+
+    // Viewers may not list routes
+    let req = test::TestRequest::get()
+        .uri("/api/routes")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 403);
+}
+
+#[actix_web::test]
+async fn test_selftest_requires_admin() {
+    let app = test::init_service(build_test_app!()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/admin/selftest")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+}
+
+#[actix_web::test]
+async fn test_admin_selftest_and_route_listing() {
+    let app = test::init_service(build_test_app!()).await;
+    let token = login_as!(app, "admintest@example.com", "admin");
+
+    let req = test::TestRequest::get()
+        .uri("/api/routes")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["routes"].as_array().unwrap().iter().any(|r| r["path"] == "/api/admin/selftest"));
+    assert!(body["routes"].as_array().unwrap().iter().any(|r| r["path"] == "/api/routes" && r["roles"] == json!(["admin"])));
+
+    let req = test::TestRequest::post()
+        .uri("/api/admin/selftest")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["passed"], true);
+    assert_eq!(body["stages"].as_array().unwrap().last().unwrap()["stage"], "rollback");
+}
+
+#[actix_web::test]
+async fn test_device_claim_with_pairing_code() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin_token = login_as!(app, "pairadmin@example.com", "admin");
+    let caregiver_token = login_as!(app, "caregiver@example.com", "viewer");
+
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+    let device_id = format!("WALKER-PAIR-{}", uuid::Uuid::new_v4());
+    sqlx::query("INSERT INTO devices (device_id, device_name, secret_hash, is_active) VALUES ($1, 'Pairing Walker', '', true)")
+        .bind(&device_id)
+        .execute(&pool)
+        .await
+        .expect("Failed to insert test device");
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/admin/devices/{}/pairing-code", device_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let code = body["pairing_code"].as_str().unwrap().to_lowercase();
+
+    let claim = || test::TestRequest::post()
+        .uri("/api/devices/claim")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", caregiver_token)))
+        .set_json(json!({"pairing_code": code, "patient_name": "Grandma"}))
+        .to_request();
+
+    let resp = test::call_service(&app, claim()).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["device_id"], device_id.as_str());
+
+    // Codes are single use
+    let resp = test::call_service(&app, claim()).await;
+    assert_eq!(resp.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_claimed_patients_stay_in_claimants_organization() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin_token = login_as!(app, "pairadmin@example.com", "admin");
+    login_as!(app, "org-claimant@example.com", "viewer");
+    login_as!(app, "org-outsider@example.com", "viewer");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let mut orgs = Vec::new();
+    for name in ["Claimant Clinic", "Other Clinic"] {
+        let id: uuid::Uuid = sqlx::query_scalar("INSERT INTO organizations (name) VALUES ($1) RETURNING id")
+            .bind(format!("{} {}", name, uuid::Uuid::new_v4()))
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        orgs.push(id);
+    }
+    for (email, org) in [("org-claimant@example.com", orgs[0]), ("org-outsider@example.com", orgs[1])] {
+        sqlx::query("UPDATE users SET organization_id = $2 WHERE email = $1")
+            .bind(email)
+            .bind(org)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+    let claimant = login_as!(app, "org-claimant@example.com", "viewer");
+    let outsider = login_as!(app, "org-outsider@example.com", "viewer");
+
+    let device_id = format!("WALKER-ORG-{}", uuid::Uuid::new_v4());
+    sqlx::query("INSERT INTO devices (device_id, device_name, secret_hash, is_active) VALUES ($1, 'Org Walker', '', true)")
+        .bind(&device_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/admin/devices/{}/pairing-code", device_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin_token)))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/devices/claim")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", claimant)))
+        .set_json(json!({"pairing_code": body["pairing_code"], "patient_name": "Org Grandma"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let patient_id: uuid::Uuid = body["patient_id"].as_str().unwrap().parse().unwrap();
+
+    // Patient and walker both join the claimant's organization
+    let (patient_org, device_org): (Option<uuid::Uuid>, Option<uuid::Uuid>) = sqlx::query_as(
+        "SELECT p.organization_id, d.organization_id FROM patients p JOIN devices d ON d.patient_id = p.id WHERE d.device_id = $1"
+    )
+    .bind(&device_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(patient_org, Some(orgs[0]));
+    assert_eq!(device_org, Some(orgs[0]));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/patients/{}", patient_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", outsider)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 403);
+}
+
+#[actix_web::test]
+async fn test_organization_walkers_not_claimable_from_outside_it() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin_token = login_as!(app, "pairadmin@example.com", "admin");
+    login_as!(app, "orgless-claimant@example.com", "viewer");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    sqlx::query("UPDATE users SET organization_id = NULL WHERE email = 'orgless-claimant@example.com'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let claimant = login_as!(app, "orgless-claimant@example.com", "viewer");
+    let org: uuid::Uuid = sqlx::query_scalar("INSERT INTO organizations (name) VALUES ($1) RETURNING id")
+        .bind(format!("Walker Owner Clinic {}", uuid::Uuid::new_v4()))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+    let device_id = format!("WALKER-OWNED-{}", uuid::Uuid::new_v4());
+    sqlx::query(
+        "INSERT INTO devices (device_id, device_name, secret_hash, is_active, organization_id) VALUES ($1, 'Owned Walker', '', true, $2)"
+    )
+    .bind(&device_id)
+    .bind(org)
+    .execute(&pool)
+    .await
+    .unwrap();
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/admin/devices/{}/pairing-code", device_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+
+    // A caregiver outside any organization holding the code still cannot take the walker
+    let req = test::TestRequest::post()
+        .uri("/api/devices/claim")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", claimant)))
+        .set_json(json!({"pairing_code": body["pairing_code"], "patient_name": "Outside Grandma"}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+
+    let (device_org, patient_id): (Option<uuid::Uuid>, Option<uuid::Uuid>) =
+        sqlx::query_as("SELECT organization_id, patient_id FROM devices WHERE device_id = $1")
+            .bind(&device_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(device_org, Some(org));
+    assert_eq!(patient_id, None);
+}
+
+#[actix_web::test]
+async fn test_care_plan_lifecycle_and_evaluation() {
+    let app = test::init_service(build_test_app!()).await;
+    let token = login_as!(app, "clinician@example.com", "clinician");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    // Patient with a walker that reported steps today
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Care Plan Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO patient_caregivers (patient_id, user_id) SELECT $1, id FROM users WHERE email = 'clinician@example.com'")
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let device: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'Plan Walker', '', $2) RETURNING id"
+    )
+    .bind(format!("WALKER-PLAN-{}", uuid::Uuid::new_v4()))
+    .bind(patient_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO sensor_readings (device_id, heart_rate, spo2, temperature, reading_timestamp, metadata)
+         VALUES ($1, 80, 97, 36.7, now(), '{\"steps\": 1500}'), ($1, 85, 96, 36.8, now(), '{\"steps\": 900}')"
+    )
+    .bind(device)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let auth = (header::AUTHORIZATION, format!("Bearer {}", token));
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/patients/{}/care-plans", patient_id))
+        .insert_header(auth.clone())
+        .set_json(json!({"title": "Walk more", "target_steps": 2000, "hr_min": 50, "hr_max": 110, "spo2_min": 92}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let plan: serde_json::Value = test::read_body_json(resp).await;
+    let plan_id = plan["id"].as_str().unwrap().to_string();
+
+    medhealth_backend::care_plan_service::evaluate_day(&pool, chrono::Utc::now().date_naive())
+        .await
+        .expect("evaluation should succeed");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/care-plans/{}/progress", plan_id))
+        .insert_header(auth.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let progress: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(progress["evaluations"][0]["steps"], 2400);
+    assert_eq!(progress["steps_met_days"], 1);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/fhir/CarePlan/{}", plan_id))
+        .insert_header(auth.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let bundle: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(bundle["entry"][0]["resource"]["resourceType"], "CarePlan");
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/care-plans/{}", plan_id))
+        .insert_header(auth)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 204);
+}
+
+#[actix_web::test]
+async fn test_medication_reminder_and_adherence() {
+    let app = test::init_service(build_test_app!()).await;
+    let token = login_as!(app, "medclinician@example.com", "clinician");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Medication Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO patient_caregivers (patient_id, user_id) SELECT $1, id FROM users WHERE email = 'medclinician@example.com'")
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let auth = (header::AUTHORIZATION, format!("Bearer {}", token));
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/patients/{}/medications", patient_id))
+        .insert_header(auth.clone())
+        .set_json(json!({"name": "Metoprolol", "dosage": "25 mg", "times": ["08:00:00", "20:00:00"]}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let medication: serde_json::Value = test::read_body_json(resp).await;
+
+    // A dose that is already due
+    let dose_id: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO medication_doses (medication_id, scheduled_at) VALUES ($1, now() - interval '5 minutes') RETURNING id"
+    )
+    .bind(uuid::Uuid::parse_str(medication["id"].as_str().unwrap()).unwrap())
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    let notifier = medhealth_backend::notifier::Notifier::new(pool.clone());
+    let broadcaster = medhealth_backend::sse::create_broadcaster();
+    let mut rx = broadcaster.subscribe();
+    let sent = medhealth_backend::medication_service::run_reminder_cycle(&pool, &notifier, &broadcaster)
+        .await
+        .expect("reminder cycle should succeed");
+    assert!(sent >= 1);
+    assert!(rx.try_recv().is_ok());
+
+    let req = test::TestRequest::get()
+        .uri("/api/notifications?unread=true")
+        .insert_header(auth.clone())
+        .to_request();
+    let inbox: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert!(inbox.as_array().unwrap().iter().any(|n| n["kind"] == "medication_reminder"));
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/medication-doses/{}/confirm", dose_id))
+        .insert_header(auth.clone())
+        .set_json(json!({"status": "taken"}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/patients/{}/adherence", patient_id))
+        .insert_header(auth)
+        .to_request();
+    let adherence: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(adherence["taken"], 1);
+    assert_eq!(adherence["ratio"], 1.0);
+}
+
+#[actix_web::test]
+async fn test_checkin_timeline_and_risk() {
+    let app = test::init_service(build_test_app!()).await;
+    let token = login_as!(app, "checkin@example.com", "viewer");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Check-in Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO patient_caregivers (patient_id, user_id) SELECT $1, id FROM users WHERE email = 'checkin@example.com'")
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let auth = (header::AUTHORIZATION, format!("Bearer {}", token));
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/patients/{}/checkins", patient_id))
+        .insert_header(auth.clone())
+        .set_json(json!({}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/patients/{}/checkins", patient_id))
+        .insert_header(auth.clone())
+        .set_json(json!({"pain_score": 8, "dizziness": 6, "fell_since_last": true}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let created: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(created["risk"]["level"], "high");
+    let checkin_id = created["checkin"]["id"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/patients/{}/timeline", patient_id))
+        .insert_header(auth.clone())
+        .to_request();
+    let timeline: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert!(timeline.as_array().unwrap().iter().any(|e| e["kind"] == "checkin"));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/patients/{}/risk", patient_id))
+        .insert_header(auth.clone())
+        .to_request();
+    let risk: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert!(risk["factors"].as_array().unwrap().iter().any(|f| f.as_str().unwrap().contains("Reported a fall")));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/fhir/QuestionnaireResponse/{}", checkin_id))
+        .insert_header(auth)
+        .insert_header((header::ACCEPT, "application/fhir+json"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let fhir: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(fhir["resourceType"], "QuestionnaireResponse");
+}
+
+#[actix_web::test]
+async fn test_sos_notifies_contacts_and_records_response_chain() {
+    // Stand-in SMS/voice gateway capturing every webhook call
+    let received = std::sync::Arc::new(std::sync::Mutex::new(Vec::<serde_json::Value>::new()));
+    let sink = received.clone();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let gateway_addr = listener.local_addr().unwrap();
+    let gateway = actix_web::HttpServer::new(move || {
+        let sink = sink.clone();
+        App::new().route("/send", web::post().to(move |body: web::Json<serde_json::Value>| {
+            let sink = sink.clone();
+            async move {
+                sink.lock().unwrap().push(body.into_inner());
+                actix_web::HttpResponse::Ok().finish()
+            }
+        }))
+    })
+    .listen(listener)
+    .unwrap()
+    .workers(1)
+    .run();
+    actix_web::rt::spawn(gateway);
+
+    let mut settings = test_settings();
+    settings.emergency.contact_webhook_url = Some(format!("http://{}/send", gateway_addr));
+    let state = init_state(&settings).await.expect("PostgreSQL and Redis required for integration tests");
+    let app = test::init_service(build_app(web::Data::new(state))).await;
+    let token = login_as!(app, "sos@example.com", "viewer");
+    let pool = create_pool(&settings.database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('SOS Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO patient_caregivers (patient_id, user_id) SELECT $1, id FROM users WHERE email = 'sos@example.com'")
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let device_id = format!("SOS-{}", &uuid::Uuid::new_v4().to_string()[..8]);
+    sqlx::query("INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'SOS Walker', '', $2)")
+        .bind(&device_id)
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let auth = (header::AUTHORIZATION, format!("Bearer {}", token));
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/patients/{}/emergency-contacts", patient_id))
+        .insert_header(auth.clone())
+        .set_json(json!({"name": "Dana", "relationship": "daughter", "phone": "+15551234567", "notify_call": true}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 201);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/patients/{}/emergency-contacts", patient_id))
+        .insert_header(auth.clone())
+        .set_json(json!({"name": "Bad", "phone": "555-1234"}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+    // The walker's SOS button, signed over the bytes as sent (field order and spacing included)
+    let timestamp = chrono::Utc::now().timestamp();
+    let body = format!("{{ \"timestamp\": {},  \"event_type\": \"sos\" }}", timestamp);
+    let msg = format!("{}.{}", timestamp, body);
+    let mut mac = HmacSha256::new_from_slice(TEST_DEVICE_SECRET.as_bytes()).unwrap();
+    mac.update(msg.as_bytes());
+    let signature = general_purpose::STANDARD.encode(mac.finalize().into_bytes());
+
+    let req = test::TestRequest::post()
+        .uri("/api/device/events")
+        .insert_header(("X-Device-Id", device_id.as_str()))
+        .insert_header(("X-Timestamp", timestamp.to_string()))
+        .insert_header(("X-Signature", signature))
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_payload(body)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let accepted: serde_json::Value = test::read_body_json(resp).await;
+    let alert_id = accepted["alert_id"].as_str().unwrap().to_string();
+
+    let calls = received.lock().unwrap().clone();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0]["channel"], "sms");
+    assert_eq!(calls[1]["channel"], "call");
+    assert_eq!(calls[0]["to"], "+15551234567");
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/alerts/{}/acknowledge", alert_id))
+        .insert_header(auth.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let alert: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(alert["level"], "critical");
+
+    let chain: Vec<_> = alert["responses"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| (r["channel"].as_str().unwrap().to_string(), r["status"].as_str().unwrap().to_string()))
+        .collect();
+    let expected = [("inbox", "sent"), ("sms", "sent"), ("call", "sent"), ("acknowledgement", "acknowledged")];
+    assert_eq!(chain, expected.map(|(c, s)| (c.to_string(), s.to_string())));
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/alerts/{}/acknowledge", alert_id))
+        .insert_header(auth)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 409);
+}
+
+#[actix_web::test]
+async fn test_voice_escalation_and_call_status_callback() {
+    // Stand-in for the Twilio REST API
+    let calls = std::sync::Arc::new(std::sync::Mutex::new(Vec::<std::collections::HashMap<String, String>>::new()));
+    let sink = calls.clone();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let twilio_addr = listener.local_addr().unwrap();
+    let twilio = actix_web::HttpServer::new(move || {
+        let sink = sink.clone();
+        App::new().route(
+            "/2010-04-01/Accounts/{sid}/Calls.json",
+            web::post().to(move |form: web::Form<std::collections::HashMap<String, String>>| {
+                let sink = sink.clone();
+                async move {
+                    sink.lock().unwrap().push(form.into_inner());
+                    actix_web::HttpResponse::Created().json(json!({"sid": "CA0001", "status": "queued"}))
+                }
+            }),
+        )
+    })
+    .listen(listener)
+    .unwrap()
+    .workers(1)
+    .run();
+    actix_web::rt::spawn(twilio);
+
+    let mut settings = test_settings();
+    settings.voice = VoiceConfig {
+        enabled: true,
+        account_sid: Some("AC123".to_string()),
+        auth_token: Some("twilio-test-token".to_string()),
+        from_number: Some("+15550100000".to_string()),
+        api_base_url: format!("http://{}", twilio_addr),
+        callback_base_url: Some("https://walker.example.com".to_string()),
+        escalate_after_minutes: 5,
+        tts_voice: "Polly.Joanna".to_string(),
+    };
+    let state = init_state(&settings).await.expect("PostgreSQL and Redis required for integration tests");
+    let notifier = state.notifier.clone();
+    let app = test::init_service(build_app(web::Data::new(state))).await;
+    let token = login_as!(app, "voice@example.com", "viewer");
+    let pool = create_pool(&settings.database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Voice Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO patient_caregivers (patient_id, user_id) SELECT $1, id FROM users WHERE email = 'voice@example.com'")
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO emergency_contacts (patient_id, name, phone, priority) VALUES ($1, 'Second', '+15550000002', 2), ($1, 'First', '+15550000001', 1)")
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let alert_id: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO alerts (patient_id, kind, level, message, raised_at)
+         VALUES ($1, 'sos', 'critical', 'SOS button pressed on Walker', now() - interval '10 minutes') RETURNING id"
+    )
+    .bind(patient_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    let callback = format!("https://walker.example.com/api/voice/status?alert_id={}", alert_id);
+    let placed = medhealth_backend::emergency_service::run_escalation_cycle(&pool, &notifier, 5).await.unwrap();
+    assert!(placed >= 1);
+    let call = calls.lock().unwrap().iter().find(|c| c["StatusCallback"] == callback).cloned().expect("alert escalated");
+    assert_eq!(call["To"], "+15550000001");
+    assert!(call["Twiml"].contains("Voice Patient pressed the S O S button"));
+
+    // Not due again until the escalation interval has passed
+    medhealth_backend::emergency_service::run_escalation_cycle(&pool, &notifier, 5).await.unwrap();
+    assert_eq!(calls.lock().unwrap().iter().filter(|c| c["StatusCallback"] == callback).count(), 1);
+
+    let params: std::collections::BTreeMap<String, String> = [
+        ("CallSid", "CA0001"),
+        ("CallStatus", "completed"),
+        ("CallDuration", "42"),
+        ("To", "+15550000001"),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v.to_string()))
+    .collect();
+    let path = format!("/api/voice/status?alert_id={}", alert_id);
+    let signature = medhealth_backend::voice::twilio_signature(
+        "twilio-test-token",
+        &format!("https://walker.example.com{}", path),
+        &params,
+    );
+
+    let req = test::TestRequest::post()
+        .uri(&path)
+        .insert_header(("X-Twilio-Signature", "forged"))
+        .set_form(&params)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 401);
+
+    let req = test::TestRequest::post()
+        .uri(&path)
+        .insert_header(("X-Twilio-Signature", signature))
+        .set_form(&params)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 204);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/alerts/{}", alert_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let alert: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(alert["escalation_level"], 1);
+    let voice: Vec<_> = alert["responses"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|r| r["channel"] == "voice")
+        .map(|r| r["status"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(voice, ["queued", "completed"]);
+}
+
+#[actix_web::test]
+async fn test_alert_chat_threads_and_broadcasts() {
+    let state = init_state(&test_settings()).await.expect("PostgreSQL and Redis required for integration tests");
+    let mut rx = state.sse_broadcaster.subscribe();
+    let app = test::init_service(build_app(web::Data::new(state))).await;
+    let caregiver = login_as!(app, "chatcaregiver@example.com", "viewer");
+    let clinician = login_as!(app, "chatclinician@example.com", "clinician");
+    let outsider = login_as!(app, "chatoutsider@example.com", "viewer");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Chat Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO patient_caregivers (patient_id, user_id)
+         SELECT $1, id FROM users WHERE email IN ('chatcaregiver@example.com', 'chatclinician@example.com')"
+    )
+    .bind(patient_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    let alert_id: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO alerts (patient_id, kind, level, message) VALUES ($1, 'sos', 'high', 'SOS button pressed') RETURNING id"
+    )
+    .bind(patient_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    let uri = format!("/api/alerts/{}/messages", alert_id);
+
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", caregiver)))
+        .set_json(json!({"body": "She says she felt faint getting up."}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let first: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(first["author_email"], "chatcaregiver@example.com");
+
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", clinician)))
+        .set_json(json!({"body": "Check her BP lying and standing.", "parent_id": first["id"]}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 201);
+
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", clinician)))
+        .set_json(json!({"body": "Wrong thread", "parent_id": uuid::Uuid::new_v4()}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", outsider)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 403);
+
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", caregiver)))
+        .to_request();
+    let thread: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let thread = thread.as_array().unwrap();
+    assert_eq!(thread.len(), 2);
+    assert_eq!(thread[1]["parent_id"], first["id"]);
+    assert_eq!(thread[1]["author_role"], "clinician");
+
+    let mut pushed = 0;
+    while let Ok(event) = rx.try_recv() {
+        if let medhealth_backend::models::SseEvent::AlertMessage(data) = &event.event {
+            assert_eq!(data.alert_id, alert_id);
+            pushed += 1;
+        }
+    }
+    assert_eq!(pushed, 2);
+}
+
+#[actix_web::test]
+async fn test_ward_handoff_summary_json_and_pdf() {
+    let state = init_state(&test_settings()).await.expect("PostgreSQL and Redis required for integration tests");
+    let app = test::init_service(build_app(web::Data::new(state))).await;
+    let admin = login_as!(app, "wardadmin@example.com", "admin");
+    let clinician = login_as!(app, "wardnurse@example.com", "clinician");
+    let viewer = login_as!(app, "wardviewer@example.com", "viewer");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let req = test::TestRequest::post()
+        .uri("/api/wards")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .set_json(json!({"name": format!("Ward {}", uuid::Uuid::new_v4())}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let ward: serde_json::Value = test::read_body_json(resp).await;
+    let ward_id = ward["id"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::post()
+        .uri("/api/wards")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .set_json(json!({"name": ward["name"]}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 409);
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Handoff Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/wards/{}/patients/{}", ward_id, patient_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", clinician)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 204);
+
+    sqlx::query(
+        "INSERT INTO alerts (patient_id, kind, level, message, raised_at)
+         VALUES ($1, 'sos', 'critical', 'SOS button pressed', now() - interval '1 hour'),
+                ($1, 'fall', 'high', 'Possible fall', now() - interval '30 hours')"
+    )
+    .bind(patient_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let uri = format!("/api/wards/{}/handoff", ward_id);
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", viewer)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 403);
+
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", clinician)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let summary: serde_json::Value = test::read_body_json(resp).await;
+    let patients = summary["patients"].as_array().unwrap();
+    assert_eq!(patients.len(), 1);
+    // The day-old fall is outside the default window but still unresolved
+    assert_eq!(patients[0]["alerts"].as_array().unwrap().len(), 1);
+    assert_eq!(patients[0]["alerts"][0]["kind"], "sos");
+    let unresolved = patients[0]["unresolved"].as_array().unwrap();
+    assert_eq!(unresolved.len(), 2);
+    assert!(unresolved.iter().all(|item| item["kind"] == "unacknowledged_alert"));
+
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", clinician)))
+        .insert_header((header::ACCEPT, "application/pdf"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), "application/pdf");
+    let body = test::read_body(resp).await;
+    assert!(body.starts_with(b"%PDF-1.4"));
+    assert!(String::from_utf8_lossy(&body).contains("(Handoff Patient) Tj"));
+}
+
+#[actix_web::test]
+async fn test_legal_hold_freezes_purges_and_exports_with_custody_chain() {
+    let state = init_state(&test_settings()).await.expect("PostgreSQL and Redis required for integration tests");
+    let app = test::init_service(build_app(web::Data::new(state))).await;
+    let admin = login_as!(app, "holdadmin@example.com", "admin");
+    let clinician = login_as!(app, "holdclinician@example.com", "clinician");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    // Two patients with decade-old readings; only the first goes under hold
+    let mut readings = Vec::new();
+    let mut patients = Vec::new();
+    for name in ["Held Patient", "Unheld Patient"] {
+        let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ($1) RETURNING id")
+            .bind(name)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let device: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'Hold Walker', '', $2) RETURNING id"
+        )
+        .bind(format!("WALKER-HOLD-{}", uuid::Uuid::new_v4()))
+        .bind(patient_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let reading: i64 = sqlx::query_scalar(
+            "INSERT INTO sensor_readings (device_id, heart_rate, spo2, reading_timestamp)
+             VALUES ($1, 72, 97, now() - interval '11 years') RETURNING id"
+        )
+        .bind(device)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        patients.push(patient_id);
+        readings.push(reading);
+    }
+    let hold_uri = format!("/api/patients/{}/legal-hold", patients[0]);
+
+    let req = test::TestRequest::post()
+        .uri(&hold_uri)
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", clinician)))
+        .set_json(json!({"reason": "Litigation"}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 403);
+
+    let req = test::TestRequest::post()
+        .uri(&hold_uri)
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .set_json(json!({"reason": "Fall injury claim", "matter_reference": "CASE-2026-0142"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let hold: serde_json::Value = test::read_body_json(resp).await;
+
+    let req = test::TestRequest::post()
+        .uri(&hold_uri)
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .set_json(json!({"reason": "Duplicate"}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 409);
+
+    let purged = medhealth_backend::retention_service::run_purge_cycle(&pool, 3650).await.unwrap();
+    assert!(purged >= 1);
+    let remaining: Vec<i64> = sqlx::query_scalar("SELECT id FROM sensor_readings WHERE id = ANY($1)")
+        .bind(&readings)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining, vec![readings[0]]);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/legal-holds/{}/exports", hold["id"].as_str().unwrap()))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let export: serde_json::Value = test::read_body_json(resp).await;
+    let export_uri = format!("/api/legal-hold-exports/{}", export["id"].as_str().unwrap());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/archive?note=Produced%20to%20counsel", export_uri))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("X-Archive-SHA256").unwrap(), export["sha256"].as_str().unwrap());
+    let archive: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(archive["patient"]["display_name"], "Held Patient");
+    assert_eq!(archive["legal_hold"]["matter_reference"], "CASE-2026-0142");
+    assert_eq!(archive["manifest"]["sensor_readings"]["count"], 1);
+    assert_eq!(archive["records"]["sensor_readings"][0]["id"], readings[0]);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/verify", export_uri))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .to_request();
+    let verification: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(verification["archive_intact"], true);
+    assert_eq!(verification["signature_valid"], true);
+    assert_eq!(verification["custody_chain_intact"], true);
+
+    let req = test::TestRequest::get()
+        .uri(&export_uri)
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .to_request();
+    let details: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let custody = details["custody"].as_array().unwrap();
+    let actions: Vec<&str> = custody.iter().map(|e| e["action"].as_str().unwrap()).collect();
+    assert_eq!(actions, vec!["created", "downloaded", "verified"]);
+    assert_eq!(custody[1]["note"], "Produced to counsel");
+    assert_eq!(custody[1]["prev_hash"], custody[0]["hash"]);
+
+    // Stored exports cannot be altered
+    let tampered = sqlx::query("UPDATE legal_hold_exports SET sha256 = 'x' WHERE id = $1")
+        .bind(uuid::Uuid::parse_str(export["id"].as_str().unwrap()).unwrap())
+        .execute(&pool)
+        .await;
+    assert!(tampered.is_err());
+
+    let req = test::TestRequest::delete()
+        .uri(&hold_uri)
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+}
+
+#[actix_web::test]
+async fn test_sse_stream_delivers_vitals_and_alerts_in_order() {
+    use common::sse::{login_as, post_signed, spawn_server, SseClient};
+    use std::time::Duration;
+
+    let state = init_state(&test_settings()).await.expect("PostgreSQL and Redis required for integration tests");
+    let pool = state.pool.clone();
+    let base_url = spawn_server(state);
+
+    let device_id = format!("WALKER-SSE-{}", uuid::Uuid::new_v4());
+    sqlx::query("INSERT INTO devices (device_id, device_name, secret_hash) VALUES ($1, 'SSE Walker', '')")
+        .bind(&device_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let admin = login_as(&base_url, &pool, "sse-order-admin@example.com", "admin").await;
+    let mut stream = SseClient::connect(&base_url, &admin).await;
+    let first = stream.next_message(Duration::from_secs(5)).await.expect("initial heartbeat");
+    assert_eq!(first.event, "heartbeat");
+
+    // A normal reading produces vitals only
+    let resp = post_signed(&base_url, "/api/device/vitals", &device_id, TEST_DEVICE_SECRET, &json!({
+        "heartRate": 72, "spo2": 97, "temperature": 36.7, "timestamp": chrono::Utc::now().timestamp()
+    }))
+    .await;
+    assert_eq!(resp.status(), 200);
+    let events = stream.expect_events(&["vitals"], Duration::from_secs(5)).await;
+    assert_eq!(events[0].data["heartRate"], 72);
+    assert!(events[0].data["ml_alert"].is_null());
+
+    // A critical reading produces its vitals first, then the alert
+    let resp = post_signed(&base_url, "/api/device/vitals", &device_id, TEST_DEVICE_SECRET, &json!({
+        "heartRate": 190, "spo2": 82, "temperature": 36.9, "timestamp": chrono::Utc::now().timestamp()
+    }))
+    .await;
+    assert_eq!(resp.status(), 200);
+    let events = stream.expect_events(&["vitals", "alert"], Duration::from_secs(5)).await;
+    assert_eq!(events[0].data["heartRate"], 190);
+    assert_eq!(events[0].data["ml_alert"], "critical");
+    assert_eq!(events[1].data["level"], "critical");
+
+    // Rejected ingestion must not reach the stream
+    let resp = post_signed(&base_url, "/api/device/vitals", &device_id, "wrong-secret", &json!({
+        "heartRate": 75, "spo2": 98, "temperature": 36.8, "timestamp": chrono::Utc::now().timestamp()
+    }))
+    .await;
+    assert_eq!(resp.status(), 401);
+    stream.expect_quiet(Duration::from_millis(500)).await;
+}
+
+#[actix_web::test]
+async fn test_sse_stream_pushes_sos_alert() {
+    use common::sse::{login_as, post_signed, spawn_server, SseClient};
+    use std::time::Duration;
+
+    let state = init_state(&test_settings()).await.expect("PostgreSQL and Redis required for integration tests");
+    let pool = state.pool.clone();
+    let base_url = spawn_server(state);
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('SSE Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let device_id = format!("WALKER-SSE-{}", uuid::Uuid::new_v4());
+    sqlx::query("INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'SSE Walker', '', $2)")
+        .bind(&device_id)
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    // Only the patient's caregiver hears about it
+    let caregiver = login_as(&base_url, &pool, "sse-sos-caregiver@example.com", "viewer").await;
+    let outsider = login_as(&base_url, &pool, "sse-sos-outsider@example.com", "viewer").await;
+    sqlx::query("INSERT INTO patient_caregivers (patient_id, user_id) SELECT $1, id FROM users WHERE email = 'sse-sos-caregiver@example.com'")
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let mut stream = SseClient::connect(&base_url, &caregiver).await;
+    let mut outsider_stream = SseClient::connect(&base_url, &outsider).await;
+    let resp = post_signed(&base_url, "/api/device/events", &device_id, TEST_DEVICE_SECRET, &json!({
+        "event_type": "sos", "timestamp": chrono::Utc::now().timestamp()
+    }))
+    .await;
+    assert_eq!(resp.status(), 201);
+    let body: serde_json::Value = resp.json().await.unwrap();
+
+    let alert = stream.next_event(Duration::from_secs(5)).await;
+    assert_eq!(alert.event, "alert");
+    assert_eq!(alert.data["details"]["alert_id"], body["alert_id"]);
+    assert_eq!(alert.data["details"]["patient_id"], patient_id.to_string());
+    outsider_stream.expect_quiet(std::time::Duration::from_millis(500)).await;
+}
+
+#[actix_web::test]
+async fn test_sse_stream_requires_a_live_token() {
+    use common::sse::{login_as, spawn_server};
+    use medhealth_client::Client;
+
+    let state = init_state(&test_settings()).await.expect("PostgreSQL and Redis required for integration tests");
+    let pool = state.pool.clone();
+    let base_url = spawn_server(state);
+
+    let err = Client::new(&base_url).events().await.err().expect("stream without a token");
+    assert_eq!(err.status().map(|s| s.as_u16()), Some(401));
+
+    let token = login_as(&base_url, &pool, "sse-revoked@example.com", "viewer").await;
+    let client = Client::new(&base_url).with_token(token);
+    let _: serde_json::Value = client.post("/auth/logout", &json!({})).await.unwrap();
+    let err = client.events().await.err().expect("stream with a revoked token");
+    assert_eq!(err.status().map(|s| s.as_u16()), Some(401));
+}
+
+#[actix_web::test]
+async fn test_websocket_streams_subscribed_events() {
+    use common::sse::{login_as, post_signed, spawn_server};
+    use futures::{SinkExt, StreamExt};
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let state = init_state(&test_settings()).await.expect("PostgreSQL and Redis required for integration tests");
+    let pool = state.pool.clone();
+    let base_url = spawn_server(state);
+
+    let device_id = format!("WALKER-WS-{}", uuid::Uuid::new_v4());
+    sqlx::query("INSERT INTO devices (device_id, device_name, secret_hash) VALUES ($1, 'WS Walker', '')")
+        .bind(&device_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let admin = login_as(&base_url, &pool, "ws-admin@example.com", "admin").await;
+    let ws_url = format!("{}/api/ws/vitals?token={}", base_url.replace("http://", "ws://"), admin);
+    let (mut socket, _) = tokio_tungstenite::connect_async(ws_url)
+        .await
+        .expect("WebSocket connection failed");
+    async fn next_json<S>(socket: &mut S) -> serde_json::Value
+    where
+        S: futures::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+    {
+        loop {
+            match tokio::time::timeout(Duration::from_secs(5), socket.next()).await.expect("message in time") {
+                Some(Ok(Message::Text(text))) => return serde_json::from_str(&text).unwrap(),
+                Some(Ok(_)) => continue,
+                other => panic!("socket ended: {:?}", other),
+            }
+        }
+    }
+
+    // Unknown event types are refused without dropping the connection
+    socket.send(Message::Text(json!({"type": "subscribe", "events": ["bogus"]}).to_string())).await.unwrap();
+    assert_eq!(next_json(&mut socket).await["type"], "error");
+
+    socket.send(Message::Text(json!({"type": "subscribe", "events": ["alert"]}).to_string())).await.unwrap();
+    let reply = next_json(&mut socket).await;
+    assert_eq!(reply, json!({"type": "subscribed", "events": ["alert"]}));
+
+    // A critical reading broadcasts vitals then an alert; only the alert is subscribed
+    let resp = post_signed(&base_url, "/api/device/vitals", &device_id, TEST_DEVICE_SECRET, &json!({
+        "heartRate": 190, "spo2": 82, "temperature": 36.9, "timestamp": chrono::Utc::now().timestamp()
+    }))
+    .await;
+    assert_eq!(resp.status(), 200);
+    let event = next_json(&mut socket).await;
+    assert_eq!(event["type"], "alert");
+    assert_eq!(event["data"]["level"], "critical");
+}
+
+#[actix_web::test]
+async fn test_replay_resends_stored_readings() {
+    use medhealth_backend::device_secrets::DeviceSecrets;
+    use medhealth_backend::replay::{replay, ReplayArgs};
+
+    let state = init_state(&test_settings()).await.expect("PostgreSQL and Redis required for integration tests");
+    let pool = state.pool.clone();
+    let base_url = common::sse::spawn_server(state);
+
+    let device_id = format!("WALKER-REPLAY-{}", uuid::Uuid::new_v4());
+    let device: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO devices (device_id, device_name, secret_hash) VALUES ($1, 'Replay Walker', '') RETURNING id"
+    )
+    .bind(&device_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO sensor_readings (device_id, heart_rate, spo2, temperature, reading_timestamp, metadata)
+         VALUES ($1, 188, 86, 36.9, '2026-03-01T02:15:00Z', '{\"steps\": 12}'),
+                ($1, 70, 97, NULL, '2026-03-01T02:16:00Z', '{}')"
+    )
+    .bind(device)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let args = ReplayArgs::parse(&[
+        "--from", "2026-03-01T00:00:00Z",
+        "--to", "2026-03-02T00:00:00Z",
+        "--target", &base_url,
+        "--device", &device_id,
+    ].map(String::from))
+    .unwrap();
+    let summary = replay(&pool, &DeviceSecrets::new(&test_settings().device).unwrap(), &args).await.unwrap();
+    assert_eq!((summary.sent, summary.rejected, summary.skipped), (1, 0, 1));
+
+    // The replayed copy went through the full pipeline, keeping its original timestamp
+    let (copies, critical): (i64, i64) = sqlx::query_as(
+        "SELECT COUNT(*), COUNT(*) FILTER (WHERE a.alert_level = 'critical')
+         FROM sensor_readings r LEFT JOIN ml_analysis a ON a.sensor_reading_id = r.id
+         WHERE r.device_id = $1 AND r.reading_timestamp = '2026-03-01T02:15:00Z' AND (r.metadata->>'steps')::int = 12"
+    )
+    .bind(device)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!((copies, critical), (2, 1));
+
+    let other_secret = DeviceConfig { secret: "not-the-device-secret".to_string(), ..test_settings().device };
+    let rejected = replay(&pool, &DeviceSecrets::new(&other_secret).unwrap(), &args).await;
+    assert!(rejected.is_err());
+}
+
+#[actix_web::test]
+async fn test_fahrenheit_walker_normalized_with_raw_values_kept() {
+    use common::sse::{post_signed, spawn_server};
+    use medhealth_backend::device_secrets::DeviceSecrets;
+    use medhealth_backend::replay::{replay, ReplayArgs};
+
+    let state = init_state(&test_settings()).await.expect("PostgreSQL and Redis required for integration tests");
+    let pool = state.pool.clone();
+    let base_url = spawn_server(state);
+
+    let device_id = format!("WALKER-UNITS-{}", uuid::Uuid::new_v4());
+    let device: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO devices (device_id, device_name, secret_hash, units)
+         VALUES ($1, 'Fahrenheit Walker', '', '{\"temperature\": \"fahrenheit\"}') RETURNING id"
+    )
+    .bind(&device_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    let resp = post_signed(&base_url, "/api/device/vitals", &device_id, TEST_DEVICE_SECRET, &json!({
+        "heartRate": 72.4, "spo2": 97, "temperature": 98.6, "timestamp": 1772330400
+    }))
+    .await;
+    assert_eq!(resp.status(), 200);
+
+    // A Celsius reading from a Fahrenheit walker is out of range once converted
+    let resp = post_signed(&base_url, "/api/device/vitals", &device_id, TEST_DEVICE_SECRET, &json!({
+        "heartRate": 72, "spo2": 97, "temperature": 36.8, "timestamp": chrono::Utc::now().timestamp()
+    }))
+    .await;
+    assert_eq!(resp.status(), 400);
+
+    // Replaying sends the values as the walker sent them, and they are converted again
+    let args = ReplayArgs::parse(&[
+        "--from", "2026-03-01T00:00:00Z",
+        "--to", "2026-03-02T00:00:00Z",
+        "--target", &base_url,
+        "--device", &device_id,
+    ].map(String::from))
+    .unwrap();
+    let summary = replay(&pool, &DeviceSecrets::new(&test_settings().device).unwrap(), &args).await.unwrap();
+    assert_eq!((summary.sent, summary.rejected), (1, 0));
+
+    let stored: Vec<(i32, f32, serde_json::Value)> = sqlx::query_as(
+        "SELECT heart_rate, temperature, metadata->'raw' FROM sensor_readings WHERE device_id = $1"
+    )
+    .bind(device)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(stored.len(), 2);
+    for (heart_rate, temperature, raw) in stored {
+        assert_eq!((heart_rate, temperature), (72, 37.0));
+        assert_eq!(raw, json!({"heartRate": 72.4, "temperature": 98.6, "units": {"temperature": "fahrenheit"}}));
+    }
+}
+
+#[actix_web::test]
+async fn test_refused_reading_dead_lettered_and_reprocessed_after_fix() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "deadletteradmin@example.com", "admin");
+
+    // A walker reporting °F, registered as reporting °C
+    let serial = format!("WALKER-DLQ-{}", uuid::Uuid::new_v4());
+    let req = test::TestRequest::post()
+        .uri("/api/admin/devices")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .set_json(json!({"device_id": serial, "device_name": "Dead-letter Walker"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let device: serde_json::Value = test::read_body_json(resp).await;
+    let secret = device["secret"].as_str().unwrap().to_string();
+
+    let timestamp = chrono::Utc::now().timestamp();
+    let payload = json!({"heartRate": 72, "spo2": 97, "temperature": 98.6, "timestamp": timestamp}).to_string();
+    let req = test::TestRequest::post()
+        .uri("/api/device/vitals")
+        .insert_header(("X-Device-Id", serial.as_str()))
+        .insert_header(("X-Timestamp", timestamp.to_string()))
+        .insert_header(("X-Signature", device_signature(&secret, timestamp, &payload)))
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_payload(payload)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+    let list = |query: &str| {
+        test::TestRequest::get()
+            .uri(&format!("/api/admin/failed-ingestions?device_id={}{}", serial, query))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .to_request()
+    };
+    let pending: serde_json::Value = test::read_body_json(test::call_service(&app, list("")).await).await;
+    let entries = pending.as_array().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["channel"], "http");
+    assert_eq!(entries[0]["category"], "invalid_reading");
+    assert_eq!(entries[0]["payload"]["temperature"], 98.6);
+    let id = entries[0]["id"].as_i64().unwrap();
+
+    let reprocess = || {
+        test::TestRequest::post()
+            .uri(&format!("/api/admin/failed-ingestions/{}/reprocess", id))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .to_request()
+    };
+    // Still refused until the units are fixed, and counted as another attempt
+    assert_eq!(test::call_service(&app, reprocess()).await.status(), 400);
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/admin/devices/{}", serial))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .set_json(json!({"units": {"temperature": "fahrenheit"}}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+    let resp = test::call_service(&app, reprocess()).await;
+    assert_eq!(resp.status(), 200);
+    let accepted: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(test::call_service(&app, reprocess()).await.status(), 409);
+
+    let pending: serde_json::Value = test::read_body_json(test::call_service(&app, list("")).await).await;
+    assert!(pending.as_array().unwrap().is_empty());
+    let done: serde_json::Value = test::read_body_json(test::call_service(&app, list("&reprocessed=true")).await).await;
+    assert_eq!(done[0]["sensor_reading_id"], accepted["reading_id"]);
+    assert_eq!(done[0]["attempts"], 3);
+}
+
+#[actix_web::test]
+async fn test_anomaly_heatmap_grid() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "heatmapadmin@example.com", "admin");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Heatmap Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let device: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'Heatmap Walker', '', $2) RETURNING id"
+    )
+    .bind(format!("WALKER-HEAT-{}", uuid::Uuid::new_v4()))
+    .bind(patient_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        "WITH readings AS (
+             INSERT INTO sensor_readings (device_id, heart_rate, spo2, reading_timestamp)
+             VALUES ($1, 70, 84, date_trunc('day', now() AT TIME ZONE 'UTC') AT TIME ZONE 'UTC' + interval '3 hours 10 minutes'),
+                    ($1, 70, 85, date_trunc('day', now() AT TIME ZONE 'UTC') AT TIME ZONE 'UTC' + interval '3 hours 40 minutes'),
+                    ($1, 190, 97, date_trunc('day', now() AT TIME ZONE 'UTC') AT TIME ZONE 'UTC' + interval '3 hours 50 minutes'),
+                    ($1, 70, 86, date_trunc('day', now() AT TIME ZONE 'UTC') AT TIME ZONE 'UTC' - interval '10 hours')
+             RETURNING id, spo2
+         )
+         INSERT INTO ml_analysis (sensor_reading_id, anomaly_detected, anomaly_score, alert_level, analysis_details)
+         SELECT id, true, 0.9, 'critical',
+                CASE WHEN spo2 < 90 THEN '{\"anomalies\": [\"Hypoxemia detected (low SpO2)\"]}'::jsonb
+                     ELSE '{\"anomalies\": [\"Tachycardia detected (high heart rate)\"]}'::jsonb END
+         FROM readings"
+    )
+    .bind(device)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/ml/heatmap?patient_id={}&metric=spo2&bucket=1h&days=2", patient_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let heatmap: serde_json::Value = test::read_body_json(resp).await;
+    let rows = heatmap["rows"].as_array().unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(heatmap["buckets"].as_array().unwrap().len(), 24);
+    assert_eq!(rows[0]["counts"][14], 1);
+    assert_eq!(rows[1]["counts"][3], 2);
+    assert_eq!(heatmap["max_count"], 2);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/ml/heatmap?patient_id={}&bucket=6h&days=2", patient_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .to_request();
+    let heatmap: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(heatmap["rows"][1]["counts"], json!([3, 0, 0, 0]));
+
+    for bad in ["metric=steps", "bucket=5h", "days=0"] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/ml/heatmap?patient_id={}&{}", patient_id, bad))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400, "{}", bad);
+    }
+}
+
+#[actix_web::test]
+async fn test_sleep_summaries_from_overnight_readings() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "sleepadmin@example.com", "admin");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Sleep Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let device: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'Sleep Walker', '', $2) RETURNING id"
+    )
+    .bind(format!("WALKER-SLEEP-{}", uuid::Uuid::new_v4()))
+    .bind(patient_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    // Walking in the afternoon, then four still hours with a low heart rate from 22:00
+    let today = chrono::Utc::now().date_naive();
+    let midnight = today.and_hms_opt(0, 0, 0).unwrap().and_utc();
+    sqlx::query(
+        "INSERT INTO sensor_readings (device_id, heart_rate, spo2, reading_timestamp, metadata)
+         SELECT $1, 80, 97, t, '{\"steps\": 12}'::jsonb
+         FROM generate_series($2 - interval '10 hours', $2 - interval '7 hours 30 minutes', interval '5 minutes') t
+         UNION ALL
+         SELECT $1, 55, 95, t, '{}'::jsonb
+         FROM generate_series($2 - interval '2 hours', $2 + interval '2 hours', interval '5 minutes') t"
+    )
+    .bind(device)
+    .bind(midnight)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    assert!(sleep_service::summarize_night(&pool, today).await.unwrap() >= 1);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/patients/{}/sleep", patient_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let summaries: serde_json::Value = test::read_body_json(resp).await;
+    let summary = &summaries[0];
+    assert_eq!(summary["sleep_date"], today.to_string());
+    assert_eq!(summary["period_count"], 1);
+    assert_eq!(summary["sleep_minutes"], 240);
+    assert_eq!(summary["periods"][0]["kind"], "sleep");
+    assert_eq!(summary["periods"][0]["min_heart_rate"], 55);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/patients/{}/timeline", patient_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .to_request();
+    let timeline: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let sleep: Vec<_> = timeline.as_array().unwrap().iter().filter(|e| e["kind"] == "sleep").collect();
+    assert_eq!(sleep.len(), 1);
+    assert_eq!(sleep[0]["data"]["minutes"], 240);
+}
+
+#[actix_web::test]
+async fn test_activity_classification_and_daily_report() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "activityadmin@example.com", "admin");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Activity Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let serial = format!("WALKER-ACT-{}", uuid::Uuid::new_v4());
+    let device: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'Activity Walker', '', $2) RETURNING id"
+    )
+    .bind(&serial)
+    .bind(patient_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    // Motion channels sent by the walker are kept with the reading
+    let timestamp = chrono::Utc::now().timestamp();
+    let body = DeviceVitalsIngest {
+        heart_rate: 88,
+        spo2: 97,
+        temperature: 36.7,
+        timestamp,
+        steps: Some(12),
+        motion: Some(0.25),
+        elevation_change: Some(-0.5),
+        ambient_temperature: None,
+        humidity: None,
+        metadata: None,
+    };
+    let payload = serde_json::to_string(&body).unwrap();
+    let req = test::TestRequest::post()
+        .uri("/api/device/vitals")
+        .insert_header(("X-Device-Id", serial.as_str()))
+        .insert_header(("X-Timestamp", timestamp.to_string()))
+        .insert_header(("X-Signature", device_signature(TEST_DEVICE_SECRET, timestamp, &payload)))
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_payload(payload)
+        .to_request();
+    let resp: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let metadata: serde_json::Value = sqlx::query_scalar("SELECT metadata FROM sensor_readings WHERE id = $1")
+        .bind(resp["reading_id"].as_i64().unwrap())
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(metadata, json!({"steps": 12, "motion": 0.25, "elevation_change": -0.5}));
+
+    // Yesterday 08:00: 15 minutes walking, 5 minutes on the stairs, then 40 minutes sitting
+    let date = chrono::Utc::now().date_naive() - chrono::Duration::days(1);
+    let eight = date.and_hms_opt(8, 0, 0).unwrap().and_utc();
+    sqlx::query(
+        "INSERT INTO sensor_readings (device_id, heart_rate, spo2, reading_timestamp, metadata)
+         SELECT $1, 90, 97, $2 + m * interval '1 minute',
+                CASE WHEN m < 15 THEN '{\"steps\": 5, \"motion\": 0.3}'::jsonb
+                     WHEN m < 20 THEN '{\"steps\": 4, \"motion\": 0.4, \"elevation_change\": 0.6}'::jsonb
+                     ELSE '{\"steps\": 0, \"motion\": 0.01}'::jsonb END
+         FROM generate_series(0, 59) m"
+    )
+    .bind(device)
+    .bind(eight)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    activity_service::classify_day(&pool, date, &activity_service::RuleBasedClassifier).await.unwrap();
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/patients/{}/activity?date={}", patient_id, date))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let report: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(report["minutes"], json!({"walking": 15, "stairs": 5, "sitting": 40}));
+    assert_eq!(report["steps"], 95);
+    let activities: Vec<_> = report["segments"].as_array().unwrap().iter().map(|s| s["activity"].clone()).collect();
+    assert_eq!(activities, vec![json!("walking"), json!("stairs"), json!("sitting")]);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/patients/{}/activity?date={}", patient_id, date))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .insert_header((header::ACCEPT, "application/pdf"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), "application/pdf");
+    assert!(test::read_body(resp).await.starts_with(b"%PDF-1.4"));
+}
+
+#[actix_web::test]
+async fn test_walker_compliance_and_non_use_alert() {
+    let app = test::init_service(build_test_app!()).await;
+    let token = login_as!(app, "compliance.clinician@example.com", "clinician");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Compliance Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO patient_caregivers (patient_id, user_id) SELECT $1, id FROM users WHERE email = 'compliance.clinician@example.com'"
+    )
+    .bind(patient_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let auth = (header::AUTHORIZATION, format!("Bearer {}", token));
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/patients/{}/compliance", patient_id))
+        .insert_header(auth.clone())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+
+    let today = chrono::Utc::now().date_naive();
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/patients/{}/care-plans", patient_id))
+        .insert_header(auth.clone())
+        .set_json(json!({"title": "Daily walker use", "prescribed_usage_minutes": 30, "start_date": today - chrono::Duration::days(10)}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let plan: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(plan["prescribed_usage_minutes"], 30);
+    let plan_id: uuid::Uuid = plan["id"].as_str().unwrap().parse().unwrap();
+
+    // Used on day -4, then three days without use (one with no readings at all); today is still running
+    sqlx::query(
+        "INSERT INTO care_plan_evaluations (care_plan_id, eval_date, readings, usage_minutes, usage_met)
+         VALUES ($1, $2 - 4, 300, 45, true), ($1, $2 - 3, 250, 0, false), ($1, $2 - 2, 0, NULL, NULL),
+                ($1, $2 - 1, 120, 0, false), ($1, $2, 10, 0, false)"
+    )
+    .bind(plan_id)
+    .bind(today)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/patients/{}/compliance", patient_id))
+        .insert_header(auth.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let compliance: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(compliance["prescribed_minutes"], 30);
+    assert_eq!(compliance["days_evaluated"], 5);
+    assert_eq!(compliance["days_met"], 1);
+    assert_eq!(compliance["non_use_streak"], 3);
+
+    let notifier = medhealth_backend::notifier::Notifier::new(pool.clone());
+    for _ in 0..2 {
+        medhealth_backend::care_plan_service::check_non_use(&pool, &notifier, 3, today).await.unwrap();
+    }
+    let alerts: Vec<(String, serde_json::Value)> =
+        sqlx::query_as("SELECT message, details FROM alerts WHERE patient_id = $1 AND kind = 'walker_non_use'")
+            .bind(patient_id)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(alerts.len(), 1, "a streak alerts once");
+    assert_eq!(alerts[0].1["days"], 3);
+    assert_eq!(alerts[0].1["streak_start"], (today - chrono::Duration::days(3)).to_string());
+
+    let notified: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM notifications WHERE patient_id = $1 AND kind = 'walker_non_use'"
+    )
+    .bind(patient_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(notified, 1);
+}
+
+#[actix_web::test]
+async fn test_near_fall_events_raise_fall_risk_on_weekly_rise() {
+    let app = test::init_service(build_test_app!()).await;
+    let token = login_as!(app, "nearfall@example.com", "viewer");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Near-Fall Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO patient_caregivers (patient_id, user_id) SELECT $1, id FROM users WHERE email = 'nearfall@example.com'")
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let serial = format!("WALKER-NF-{}", uuid::Uuid::new_v4());
+    let device: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'Near-Fall Walker', '', $2) RETURNING id"
+    )
+    .bind(&serial)
+    .bind(patient_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    // One stumble the week before
+    sqlx::query(
+        "INSERT INTO near_fall_events (device_id, patient_id, event_type, occurred_at) VALUES ($1, $2, 'stumble', now() - interval '10 days')"
+    )
+    .bind(device)
+    .bind(patient_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let mut alert_ids = Vec::new();
+    for _ in 0..4 {
+        let timestamp = chrono::Utc::now().timestamp();
+        let event = DeviceEventIngest {
+            event_type: "near_fall".to_string(),
+            timestamp,
+            details: Some(json!({"speed": 0.6, "surface": "rug"})),
+        };
+        let payload = serde_json::to_string(&event).unwrap();
+        let req = test::TestRequest::post()
+            .uri("/api/device/events")
+            .insert_header(("X-Device-Id", serial.as_str()))
+            .insert_header(("X-Timestamp", timestamp.to_string()))
+            .insert_header(("X-Signature", device_signature(TEST_DEVICE_SECRET, timestamp, &payload)))
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_payload(payload)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 201);
+        let accepted: serde_json::Value = test::read_body_json(resp).await;
+        assert!(accepted["event_id"].is_i64());
+        alert_ids.push(accepted["alert_id"].clone());
+    }
+    // The third near-fall this week triples last week's count; later ones don't alert again
+    assert!(alert_ids[0].is_null() && alert_ids[1].is_null());
+    assert!(alert_ids[2].is_string());
+    assert!(alert_ids[3].is_null());
+
+    let notified: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM notifications WHERE patient_id = $1 AND kind = 'fall_risk'")
+        .bind(patient_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(notified, 1);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/patients/{}/near-falls?weeks=4", patient_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let trend: serde_json::Value = test::read_body_json(resp).await;
+    let counts: Vec<i64> = trend["weeks"].as_array().unwrap().iter().map(|w| w["events"].as_i64().unwrap()).collect();
+    assert_eq!(counts, vec![0, 0, 1, 4]);
+    assert_eq!(trend["increasing"], true);
+    assert_eq!(trend["recent"].as_array().unwrap().len(), 5);
+    assert_eq!(trend["recent"][0]["surface"], "rug");
+    assert!((trend["recent"][0]["speed"].as_f64().unwrap() - 0.6).abs() < 1e-6);
+}
+
+#[actix_web::test]
+async fn test_ambient_readings_stored_apart_and_correlated() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "ambientadmin@example.com", "admin");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Ambient Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let serial = format!("WALKER-AMB-{}", uuid::Uuid::new_v4());
+    sqlx::query("INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'Ambient Walker', '', $2)")
+        .bind(&serial)
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let mut reading_ids = Vec::new();
+    for (heart_rate, ambient, humidity) in [(115, 33.0, 40.0), (72, 22.0, 45.0), (90, 27.5, 50.0)] {
+        let timestamp = chrono::Utc::now().timestamp();
+        let body = DeviceVitalsIngest {
+            heart_rate,
+            spo2: 97,
+            temperature: 36.9,
+            timestamp,
+            steps: None,
+            motion: None,
+            elevation_change: None,
+            ambient_temperature: Some(ambient),
+            humidity: Some(humidity),
+            metadata: None,
+        };
+        let payload = serde_json::to_string(&body).unwrap();
+        assert!(payload.contains("\"ambientTemperature\""));
+        let req = test::TestRequest::post()
+            .uri("/api/device/vitals")
+            .insert_header(("X-Device-Id", serial.as_str()))
+            .insert_header(("X-Timestamp", timestamp.to_string()))
+            .insert_header(("X-Signature", device_signature(TEST_DEVICE_SECRET, timestamp, &payload)))
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_payload(payload)
+            .to_request();
+        let resp: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+        reading_ids.push(resp["reading_id"].as_i64().unwrap());
+    }
+
+    // Clinical readings stay untouched
+    let metadata: serde_json::Value = sqlx::query_scalar("SELECT metadata FROM sensor_readings WHERE id = $1")
+        .bind(reading_ids[0])
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(metadata, json!({}));
+    let flagged: bool = sqlx::query_scalar("SELECT heat_stress FROM ambient_readings WHERE sensor_reading_id = $1")
+        .bind(reading_ids[0])
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(flagged);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/patients/{}/ambient", patient_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let summary: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(summary["readings"], 3);
+    assert_eq!(summary["heat_stress_readings"], 1);
+    assert_eq!(summary["max_ambient_temperature"], 33.0);
+    assert!(summary["hr_correlation"].as_f64().unwrap() > 0.9);
+    assert_eq!(summary["heat_stress"][0]["heart_rate"], 115);
+}
+
+#[actix_web::test]
+async fn test_threshold_profiles_are_versioned_and_traceable() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "thresholdadmin@example.com", "admin");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+    let auth = || (header::AUTHORIZATION, format!("Bearer {}", admin));
+
+    let req = test::TestRequest::get().uri("/api/threshold-profiles").insert_header(auth()).to_request();
+    let profiles: Vec<serde_json::Value> = test::read_body_json(test::call_service(&app, req).await).await;
+    for seeded in ["geriatric-default", "copd", "post-op-cardiac"] {
+        assert!(profiles.iter().any(|p| p["name"] == seeded), "missing seeded profile {}", seeded);
+    }
+
+    let name = format!("cardiac-{}", uuid::Uuid::new_v4());
+    let rules = json!({"hr_low": 50, "hr_high": 110, "spo2_low": 92, "fever_temperature": 38.0, "hypothermia_temperature": 35.5});
+    let req = test::TestRequest::post()
+        .uri("/api/threshold-profiles")
+        .insert_header(auth())
+        .set_json(json!({"name": name, "rules": rules}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let profile: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(profile["version"], 1);
+    let profile_id = profile["id"].as_str().unwrap().to_string();
+    let first_version = profile["version_id"].as_i64().unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/api/threshold-profiles")
+        .insert_header(auth())
+        .set_json(json!({"name": name, "rules": rules}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 409);
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/threshold-profiles/{}/rules", profile_id))
+        .insert_header(auth())
+        .set_json(json!({"hr_low": 120, "hr_high": 110, "spo2_low": 92, "fever_temperature": 38.0, "hypothermia_temperature": 35.5}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Threshold Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let serial = format!("WALKER-THR-{}", uuid::Uuid::new_v4());
+    sqlx::query("INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'Threshold Walker', '', $2)")
+        .bind(&serial)
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/patients/{}/threshold-profile", patient_id))
+        .insert_header(auth())
+        .set_json(json!({"profile_id": profile_id}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let effective: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(effective["profile"]["name"], name.as_str());
+    assert_eq!(effective["rules"]["hr_high"], 110);
+
+    let ingest = |heart_rate: i32| {
+        let timestamp = chrono::Utc::now().timestamp();
+        let body = DeviceVitalsIngest {
+            heart_rate,
+            spo2: 97,
+            temperature: 36.9,
+            timestamp,
+            steps: None,
+            motion: None,
+            elevation_change: None,
+            ambient_temperature: None,
+            humidity: None,
+            metadata: None,
+        };
+        let payload = serde_json::to_string(&body).unwrap();
+        test::TestRequest::post()
+            .uri("/api/device/vitals")
+            .insert_header(("X-Device-Id", serial.as_str()))
+            .insert_header(("X-Timestamp", timestamp.to_string()))
+            .insert_header(("X-Signature", device_signature(TEST_DEVICE_SECRET, timestamp, &payload)))
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_payload(payload)
+            .to_request()
+    };
+    let analysis = |reading_id: i64| {
+        sqlx::query_as::<_, (String, serde_json::Value, Option<i64>)>(
+            "SELECT alert_level::text, analysis_details, threshold_profile_version_id FROM ml_analysis WHERE sensor_reading_id = $1"
+        )
+        .bind(reading_id)
+        .fetch_one(&pool)
+    };
+
+    // 125 bpm is within the configured defaults but above this profile's limit
+    let resp: serde_json::Value = test::read_body_json(test::call_service(&app, ingest(125)).await).await;
+    let first_reading = resp["reading_id"].as_i64().unwrap();
+    let (level, details, version_id) = analysis(first_reading).await.unwrap();
+    assert_eq!(level, "critical");
+    assert!(details["anomalies"].as_array().unwrap().iter().any(|a| a == TACHYCARDIA));
+    assert_eq!(details["threshold_profile"], json!({"name": name, "version": 1}));
+    assert_eq!(version_id, Some(first_version));
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/threshold-profiles/{}/rules", profile_id))
+        .insert_header(auth())
+        .set_json(json!({"hr_low": 50, "hr_high": 130, "spo2_low": 92, "fever_temperature": 38.0, "hypothermia_temperature": 35.5}))
+        .to_request();
+    let updated: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(updated["version"], 2);
+
+    let resp: serde_json::Value = test::read_body_json(test::call_service(&app, ingest(125)).await).await;
+    let (_, details, version_id) = analysis(resp["reading_id"].as_i64().unwrap()).await.unwrap();
+    assert!(!details["anomalies"].as_array().unwrap().iter().any(|a| a == TACHYCARDIA));
+    assert_eq!(version_id, updated["version_id"].as_i64());
+
+    // The earlier analysis still points at the rules that produced it
+    let (_, _, version_id) = analysis(first_reading).await.unwrap();
+    assert_eq!(version_id, Some(first_version));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/threshold-profiles/{}", profile_id))
+        .insert_header(auth())
+        .to_request();
+    let history: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let versions: Vec<_> = history["versions"].as_array().unwrap().iter().map(|v| v["version"].clone()).collect();
+    assert_eq!(versions, vec![json!(2), json!(1)]);
+    assert_eq!(history["versions"][1]["rules"]["hr_high"], 110);
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/patients/{}/threshold-profile", patient_id))
+        .insert_header(auth())
+        .set_json(json!({"profile_id": null}))
+        .to_request();
+    let effective: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert!(effective["profile"].is_null());
+    assert_eq!(effective["rules"]["hr_high"], 180);
+}
+
+#[actix_web::test]
+async fn test_night_critical_alerts_route_to_on_call_clinician() {
+    use chrono::Timelike;
+
+    let app = test::init_service(build_test_app!()).await;
+    let clinician = login_as!(app, "oncall.clinician@example.com", "clinician");
+    let viewer = login_as!(app, "oncall.caregiver@example.com", "viewer");
+    let admin = login_as!(app, "oncall.admin@example.com", "admin");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+    let user_id = |email: &'static str| {
+        sqlx::query_scalar::<_, uuid::Uuid>("SELECT id FROM users WHERE email = $1").bind(email).fetch_one(&pool)
+    };
+    let clinician_id = user_id("oncall.clinician@example.com").await.unwrap();
+    let caregiver_id = user_id("oncall.caregiver@example.com").await.unwrap();
+
+    // Scoped to a ward of its own so concurrent tests never see this cover
+    let ward_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO wards (name) VALUES ($1) RETURNING id")
+        .bind(format!("On-call Ward {}", uuid::Uuid::new_v4()))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let ends_at = chrono::Utc::now() + chrono::Duration::hours(1);
+    let req = test::TestRequest::post()
+        .uri("/api/on-call/overrides")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", viewer)))
+        .set_json(json!({"ends_at": ends_at}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 403);
+
+    let req = test::TestRequest::post()
+        .uri("/api/on-call/overrides")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .set_json(json!({"user_id": caregiver_id, "ends_at": ends_at}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+    let req = test::TestRequest::post()
+        .uri("/api/on-call/overrides")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", clinician)))
+        .set_json(json!({"ward_id": ward_id, "ends_at": ends_at, "reason": "Covering the night shift"}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 201);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/on-call?ward_id={}", ward_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", viewer)))
+        .to_request();
+    let status: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(status["on_call"]["user_id"], json!(clinician_id));
+    assert_eq!(status["on_call"]["source"], "override");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name, ward_id) VALUES ('On-call Patient', $1) RETURNING id")
+        .bind(ward_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO patient_caregivers (patient_id, user_id) VALUES ($1, $2)")
+        .bind(patient_id)
+        .bind(caregiver_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    // A night window around the current hour
+    let hour = chrono::Utc::now().hour();
+    let routing = AlertRoutingConfig {
+        night_start_hour: (hour + 23) % 24,
+        night_end_hour: (hour + 2) % 24,
+        ..Default::default()
+    };
+    let notifier = medhealth_backend::notifier::Notifier::new(pool.clone()).with_routing(&routing);
+    let notified = |user_id: uuid::Uuid, kind: &'static str| {
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM notifications WHERE user_id = $1 AND patient_id = $2 AND kind = $3")
+            .bind(user_id)
+            .bind(patient_id)
+            .bind(kind)
+            .fetch_one(&pool)
+    };
+
+    for (level, kind) in [("critical", "routing_critical"), ("high", "routing_high")] {
+        let alert: medhealth_backend::models::Alert = sqlx::query_as(
+            "INSERT INTO alerts (patient_id, kind, level, message) VALUES ($1, $2, $3::alert_level, 'Routing test') RETURNING *"
+        )
+        .bind(patient_id)
+        .bind(kind)
+        .bind(level)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        medhealth_backend::emergency_service::notify_care_team(&pool, &notifier, &alert, patient_id, "Routing test")
+            .await
+            .unwrap();
+
+        let step: (Option<uuid::Uuid>, Option<String>) =
+            sqlx::query_as("SELECT user_id, target FROM alert_responses WHERE alert_id = $1")
+                .bind(alert.id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        if level == "critical" {
+            assert_eq!(step, (Some(clinician_id), Some("on-call clinician".to_string())));
+        } else {
+            assert_eq!(step, (None, Some("1 caregiver(s)".to_string())));
+        }
+    }
+
+    assert_eq!(notified(clinician_id, "routing_critical").await.unwrap(), 1);
+    assert_eq!(notified(caregiver_id, "routing_critical").await.unwrap(), 0);
+    assert_eq!(notified(clinician_id, "routing_high").await.unwrap(), 0);
+    assert_eq!(notified(caregiver_id, "routing_high").await.unwrap(), 1);
+}
+
+#[actix_web::test]
+async fn test_on_call_rotations_resolve_per_ward() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "rota.admin@example.com", "admin");
+    let _ = login_as!(app, "rota.first@example.com", "clinician");
+    let _ = login_as!(app, "rota.second@example.com", "clinician");
+    let _ = login_as!(app, "rota.viewer@example.com", "viewer");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+    let auth = || (header::AUTHORIZATION, format!("Bearer {}", admin));
+    let user_id = |email: &'static str| {
+        sqlx::query_scalar::<_, uuid::Uuid>("SELECT id FROM users WHERE email = $1").bind(email).fetch_one(&pool)
+    };
+    let first = user_id("rota.first@example.com").await.unwrap();
+    let second = user_id("rota.second@example.com").await.unwrap();
+    let viewer = user_id("rota.viewer@example.com").await.unwrap();
+
+    let ward_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO wards (name) VALUES ($1) RETURNING id")
+        .bind(format!("Rota Ward {}", uuid::Uuid::new_v4()))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let now_on_call = || {
+        test::TestRequest::get()
+            .uri(&format!("/api/admin/oncall/now?ward_id={}", ward_id))
+            .insert_header(auth())
+            .to_request()
+    };
+
+    let req = now_on_call();
+    let status: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_ne!(status["on_call"]["ward_id"], json!(ward_id));
+
+    // Viewers cannot hold the on-call shift
+    let starts_at = chrono::Utc::now() - chrono::Duration::hours(13);
+    let rotation = |members: Vec<uuid::Uuid>| {
+        json!({"name": "Ward nights", "ward_id": ward_id, "starts_at": starts_at, "shift_hours": 12, "members": members})
+    };
+    let req = test::TestRequest::post()
+        .uri("/api/admin/oncall/rotations")
+        .insert_header(auth())
+        .set_json(rotation(vec![first, viewer]))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+    // Thirteen hours in, the second member holds the shift
+    let req = test::TestRequest::post()
+        .uri("/api/admin/oncall/rotations")
+        .insert_header(auth())
+        .set_json(rotation(vec![first, second]))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let created: serde_json::Value = test::read_body_json(resp).await;
+    let rotation_id = created["id"].as_str().unwrap().to_string();
+
+    let status: serde_json::Value = test::read_body_json(test::call_service(&app, now_on_call()).await).await;
+    assert_eq!(status["on_call"]["user_id"], json!(second));
+    assert_eq!(status["on_call"]["source"], "rotation");
+    assert_eq!(status["on_call"]["rotation_id"], rotation_id.as_str());
+
+    // An override for the ward takes precedence until it is cancelled
+    let shift_id: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO on_call_shifts (user_id, ward_id, starts_at, ends_at, is_override)
+         VALUES ($1, $2, now() - interval '1 minute', now() + interval '1 hour', true) RETURNING id"
+    )
+    .bind(first)
+    .bind(ward_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    let status: serde_json::Value = test::read_body_json(test::call_service(&app, now_on_call()).await).await;
+    assert_eq!((status["on_call"]["user_id"].clone(), status["on_call"]["source"].clone()), (json!(first), json!("override")));
+
+    let req = test::TestRequest::get().uri("/api/admin/oncall").insert_header(auth()).to_request();
+    let rota: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert!(rota["rotations"].as_array().unwrap().iter().any(|r| r["id"] == rotation_id.as_str()));
+    assert!(rota["shifts"].as_array().unwrap().iter().any(|s| s["id"] == json!(shift_id)));
+
+    // An organization's admin reaches neither this deployment-wide ward's rota nor outside staff
+    let org_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO organizations (name) VALUES ($1) RETURNING id")
+        .bind(format!("Rota Org {}", uuid::Uuid::new_v4()))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    login_as!(app, "rota.orgadmin@example.com", "admin");
+    sqlx::query("UPDATE users SET organization_id = $2 WHERE email = $1")
+        .bind("rota.orgadmin@example.com")
+        .bind(org_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let org_admin = login_as!(app, "rota.orgadmin@example.com", "admin");
+    let org_auth = || (header::AUTHORIZATION, format!("Bearer {}", org_admin));
+    let req = test::TestRequest::get().uri("/api/admin/oncall").insert_header(org_auth()).to_request();
+    let rota: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert!(rota["rotations"].as_array().unwrap().iter().all(|r| r["id"] != rotation_id.as_str()));
+    assert!(rota["shifts"].as_array().unwrap().iter().all(|s| s["id"] != json!(shift_id)));
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/admin/oncall/shifts/{}", shift_id))
+        .insert_header(org_auth())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/admin/oncall/rotations/{}", rotation_id))
+        .insert_header(org_auth())
+        .set_json(rotation(vec![first]))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+    let org_ward: uuid::Uuid = sqlx::query_scalar("INSERT INTO wards (name, organization_id) VALUES ($1, $2) RETURNING id")
+        .bind(format!("Rota Org Ward {}", uuid::Uuid::new_v4()))
+        .bind(org_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let req = test::TestRequest::post()
+        .uri("/api/admin/oncall/rotations")
+        .insert_header(org_auth())
+        .set_json(json!({"name": "Org nights", "ward_id": org_ward, "starts_at": starts_at, "shift_hours": 12, "members": [first]}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+    let req = test::TestRequest::get().uri("/api/on-call").insert_header(org_auth()).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 403);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/admin/oncall/shifts/{}", shift_id))
+        .insert_header(auth())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 204);
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/admin/oncall/rotations/{}", rotation_id))
+        .insert_header(auth())
+        .set_json(rotation(vec![second, first]))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+    let status: serde_json::Value = test::read_body_json(test::call_service(&app, now_on_call()).await).await;
+    assert_eq!(status["on_call"]["user_id"], json!(first));
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/admin/oncall/rotations/{}", rotation_id))
+        .insert_header(auth())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 204);
+    let status: serde_json::Value = test::read_body_json(test::call_service(&app, now_on_call()).await).await;
+    // Only site-wide cover (if any) is left
+    assert!(status["on_call"]["ward_id"].is_null());
+}
+
+#[actix_web::test]
+async fn test_vitals_aggregates_cached_until_a_reading_lands_in_range() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "aggregateadmin@example.com", "admin");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Aggregate Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let serial = format!("WALKER-AGG-{}", uuid::Uuid::new_v4());
+    sqlx::query("INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'Aggregate Walker', '', $2)")
+        .bind(&serial)
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let ingest = |heart_rate: i32| {
+        let timestamp = chrono::Utc::now().timestamp();
+        let body = DeviceVitalsIngest {
+            heart_rate,
+            spo2: 97,
+            temperature: 36.8,
+            timestamp,
+            steps: None,
+            motion: None,
+            elevation_change: None,
+            ambient_temperature: None,
+            humidity: None,
+            metadata: None,
+        };
+        let payload = serde_json::to_string(&body).unwrap();
+        test::TestRequest::post()
+            .uri("/api/device/vitals")
+            .insert_header(("X-Device-Id", serial.as_str()))
+            .insert_header(("X-Timestamp", timestamp.to_string()))
+            .insert_header(("X-Signature", device_signature(TEST_DEVICE_SECRET, timestamp, &payload)))
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_payload(payload)
+            .to_request()
+    };
+    let aggregate = |params: String| {
+        test::TestRequest::get()
+            .uri(&format!("/api/patients/{}/vitals/aggregate?{}", patient_id, params))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .to_request()
+    };
+    let recent = || "bucket=hour".to_string();
+    let last_week = || {
+        let to = chrono::Utc::now() - chrono::Duration::days(2);
+        let from = to - chrono::Duration::days(7);
+        format!("bucket=day&from={}&to={}", from.format("%Y-%m-%dT%H:%M:%SZ"), to.format("%Y-%m-%dT%H:%M:%SZ"))
+    };
+
+    for heart_rate in [70, 80] {
+        assert_eq!(test::call_service(&app, ingest(heart_rate)).await.status(), 200);
+    }
+
+    // Summed over buckets in case the readings straddle an hour boundary
+    let readings = |aggregate: &serde_json::Value| -> i64 {
+        aggregate["buckets"].as_array().unwrap().iter().map(|b| b["readings"].as_i64().unwrap()).sum()
+    };
+    let first: serde_json::Value = test::read_body_json(test::call_service(&app, aggregate(recent())).await).await;
+    assert_eq!(first["cached"], false);
+    assert_eq!(readings(&first), 2);
+    assert!(first["buckets"].as_array().unwrap().iter().any(|b| b["heart_rate_max"] == 80));
+    let again: serde_json::Value = test::read_body_json(test::call_service(&app, aggregate(recent())).await).await;
+    assert_eq!(again["cached"], true);
+    assert_eq!(again["buckets"], first["buckets"]);
+
+    assert_eq!(test::call_service(&app, aggregate(last_week())).await.status(), 200);
+    let older: serde_json::Value = test::read_body_json(test::call_service(&app, aggregate(last_week())).await).await;
+    assert_eq!(older["cached"], true);
+
+    // A new reading only invalidates the ranges it falls into
+    assert_eq!(test::call_service(&app, ingest(90)).await.status(), 200);
+    let fresh: serde_json::Value = test::read_body_json(test::call_service(&app, aggregate(recent())).await).await;
+    assert_eq!(fresh["cached"], false);
+    assert_eq!(readings(&fresh), 3);
+    let older: serde_json::Value = test::read_body_json(test::call_service(&app, aggregate(last_week())).await).await;
+    assert_eq!(older["cached"], true);
+
+    assert_eq!(test::call_service(&app, aggregate("bucket=week".to_string())).await.status(), 400);
+    assert_eq!(test::call_service(&app, aggregate("bucket=hour&from=2026-01-01T00:00:00Z&to=2026-03-01T00:00:00Z".to_string())).await.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_vitals_aggregate_downsamples_by_interval() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "downsampleadmin@example.com", "admin");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Downsample Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let device_id: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'Downsample Walker', '', $2) RETURNING id"
+    )
+    .bind(format!("WALKER-DOWNSAMPLE-{}", uuid::Uuid::new_v4()))
+    .bind(patient_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    // Five readings in 10:00-10:05 and one in 10:05-10:10
+    let start = chrono::DateTime::parse_from_rfc3339("2026-02-02T10:00:00Z").unwrap().with_timezone(&chrono::Utc);
+    for (minute, heart_rate) in [(0, 60), (1, 70), (2, 80), (3, 90), (4, 100), (7, 120)] {
+        sqlx::query("INSERT INTO sensor_readings (device_id, heart_rate, spo2, temperature, reading_timestamp) VALUES ($1, $2, 97, 36.8, $3)")
+            .bind(device_id)
+            .bind(heart_rate)
+            .bind(start + chrono::Duration::minutes(minute) + chrono::Duration::seconds(30))
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    let aggregate = |params: &str| {
+        test::TestRequest::get()
+            .uri(&format!("/api/vitals/aggregate?patient_id={}&{}", patient_id, params))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, aggregate("interval=5m&from=2026-02-02T10:00:00Z&to=2026-02-02T10:10:00Z")).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["bucket"], "five_minutes");
+    let buckets = body["buckets"].as_array().unwrap();
+    assert_eq!(buckets.len(), 2);
+    assert_eq!(buckets[0]["bucket_start"], "2026-02-02T10:00:00Z");
+    assert_eq!(buckets[0]["readings"], 5);
+    assert_eq!(buckets[0]["heart_rate_min"], 60);
+    assert_eq!(buckets[0]["heart_rate_p50"], 80.0);
+    assert_eq!(buckets[0]["heart_rate_p95"], 98.0);
+    assert_eq!(buckets[1]["bucket_start"], "2026-02-02T10:05:00Z");
+    assert_eq!(buckets[1]["heart_rate_avg"], 120.0);
+
+    let body: serde_json::Value = test::read_body_json(
+        test::call_service(&app, aggregate("interval=1m&from=2026-02-02T10:00:00Z&to=2026-02-02T10:10:00Z")).await,
+    )
+    .await;
+    assert_eq!(body["buckets"].as_array().unwrap().len(), 6);
+
+    // A week of minutes is too many buckets; unknown intervals are rejected
+    let resp = test::call_service(&app, aggregate("interval=1m&from=2026-02-01T00:00:00Z&to=2026-02-08T00:00:00Z")).await;
+    assert_eq!(resp.status(), 400);
+    assert_eq!(test::call_service(&app, aggregate("interval=15m")).await.status(), 400);
+
+    // Same patient access rules as the raw history
+    let viewer = login_as!(app, "downsampleviewer@example.com", "viewer");
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::get()
+            .uri(&format!("/api/vitals/aggregate?patient_id={}", patient_id))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", viewer)))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), 403);
+}
+
+#[actix_web::test]
+async fn test_reports_read_from_refreshed_views() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "reportsadmin@example.com", "admin");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let ward_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO wards (name) VALUES ($1) RETURNING id")
+        .bind(format!("Reports Ward {}", uuid::Uuid::new_v4()))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name, ward_id) VALUES ('Report Patient', $1) RETURNING id")
+        .bind(ward_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let device_id: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'Report Walker', '', $2) RETURNING id"
+    )
+    .bind(format!("WALKER-REPORT-{}", uuid::Uuid::new_v4()))
+    .bind(patient_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    let yesterday = chrono::Utc::now() - chrono::Duration::days(1);
+    for heart_rate in [60, 90] {
+        sqlx::query("INSERT INTO sensor_readings (device_id, heart_rate, spo2, temperature, reading_timestamp) VALUES ($1, $2, 96, 36.9, $3)")
+            .bind(device_id)
+            .bind(heart_rate)
+            .bind(yesterday)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+    for acknowledged_at in [Some(yesterday + chrono::Duration::minutes(10)), None] {
+        sqlx::query(
+            "INSERT INTO alerts (patient_id, kind, level, message, raised_at, acknowledged_at, status)
+             VALUES ($1, 'fall', 'critical', 'Fall', $2, $3, CASE WHEN $3 IS NULL THEN 'open' ELSE 'acknowledged' END::alert_status)"
+        )
+        .bind(patient_id)
+        .bind(yesterday)
+        .bind(acknowledged_at)
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+
+    let get = |uri: String| {
+        test::TestRequest::get()
+            .uri(&uri)
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .to_request()
+    };
+    let daily = format!("/api/reports/patients/{}/daily?days=7", patient_id);
+    let alerts = format!("/api/reports/alerts?days=7&ward_id={}", ward_id);
+
+    // Nothing shows up until the views are refreshed
+    let before: serde_json::Value = test::read_body_json(test::call_service(&app, get(daily.clone())).await).await;
+    assert_eq!(before["days"], serde_json::json!([]));
+
+    medhealth_backend::reporting_service::refresh_views(&pool).await.unwrap();
+
+    let report: serde_json::Value = test::read_body_json(test::call_service(&app, get(daily)).await).await;
+    assert!(report["refreshed_at"].is_string());
+    let days = report["days"].as_array().unwrap();
+    assert_eq!(days.len(), 1);
+    assert_eq!(days[0]["readings"], 2);
+    assert_eq!(days[0]["heart_rate_min"], 60);
+    assert_eq!(days[0]["heart_rate_max"], 90);
+    assert_eq!(days[0]["heart_rate_avg"], 75.0);
+
+    let summary: serde_json::Value = test::read_body_json(test::call_service(&app, get(alerts)).await).await;
+    let rows = summary["alerts"].as_array().unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0]["kind"], "fall");
+    assert_eq!(rows[0]["raised"], 2);
+    assert_eq!(rows[0]["acknowledged"], 1);
+    assert!((rows[0]["mean_ack_minutes"].as_f64().unwrap() - 10.0).abs() < 1e-6);
+
+    let too_long = format!("/api/reports/patients/{}/daily?days=1000", patient_id);
+    assert_eq!(test::call_service(&app, get(too_long)).await.status(), 400);
+
+    // The committee export lists each alert, read live rather than from the views
+    let range = format!("from={}&to={}&ward_id={}", yesterday.date_naive(), chrono::Utc::now().date_naive(), ward_id);
+    let resp = test::call_service(&app, get(format!("/api/reports/alerts?{}&format=csv", range))).await;
+    assert_eq!(resp.status(), 200);
+    assert!(resp.headers().get(header::CONTENT_TYPE).unwrap().to_str().unwrap().starts_with("text/csv"));
+    let csv = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("alert_id,raised_at,patient_id,patient,"));
+    assert!(lines[1].contains(",Report Patient,fall,critical,acknowledged,Fall,"));
+    assert!(lines[1].contains(",10.0,"));
+    assert!(lines[2].contains(",open,Fall,,,,"));
+
+    let resp = test::call_service(&app, get(format!("/api/reports/alerts?{}&format=pdf", range))).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), "application/pdf");
+    let pdf = String::from_utf8_lossy(&test::read_body(resp).await).to_string();
+    assert!(pdf.contains("(2 alerts raised, 1 acknowledged, 0 resolved, 1 still open.) Tj"));
+
+    assert_eq!(test::call_service(&app, get(format!("/api/reports/alerts?{}&format=xlsx", range))).await.status(), 400);
+    assert_eq!(test::call_service(&app, get("/api/reports/alerts?days=7&from=2026-01-01".to_string())).await.status(), 400);
+    assert_eq!(test::call_service(&app, get("/api/reports/alerts?from=2026-02-01&to=2026-01-01".to_string())).await.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_vitals_history_streams_ndjson() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "historyadmin@example.com", "admin");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('History Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let device_id: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'History Walker', '', $2) RETURNING id"
+    )
+    .bind(format!("WALKER-HISTORY-{}", uuid::Uuid::new_v4()))
+    .bind(patient_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    let start = chrono::Utc::now() - chrono::Duration::hours(3);
+    for (minutes, heart_rate) in [(0, 61), (10, 62), (20, 63)] {
+        sqlx::query("INSERT INTO sensor_readings (device_id, heart_rate, spo2, temperature, reading_timestamp) VALUES ($1, $2, 97, 36.7, $3)")
+            .bind(device_id)
+            .bind(heart_rate)
+            .bind(start + chrono::Duration::minutes(minutes))
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    let history = |accept: &str, params: &str| {
+        test::TestRequest::get()
+            .uri(&format!("/api/vitals/history?patient_id={}{}", patient_id, params))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .insert_header((header::ACCEPT, accept.to_string()))
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, history("application/x-ndjson", "&limit=1")).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), "application/x-ndjson");
+    let body = test::read_body(resp).await;
+    let heart_rates: Vec<i64> = std::str::from_utf8(&body)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["heart_rate"].as_i64().unwrap())
+        .collect();
+    // The row cap only applies to JSON
+    assert_eq!(heart_rates, vec![61, 62, 63]);
+
+    let capped: serde_json::Value = test::read_body_json(test::call_service(&app, history("application/json", "&limit=2")).await).await;
+    assert_eq!(capped.as_array().unwrap().len(), 2);
+    assert_eq!(capped[0]["heart_rate"], 61);
+
+    let from = (start + chrono::Duration::minutes(5)).format("%Y-%m-%dT%H:%M:%SZ");
+    let ranged: serde_json::Value = test::read_body_json(test::call_service(&app, history("application/json", &format!("&from={}", from))).await).await;
+    assert_eq!(ranged.as_array().unwrap().len(), 2);
+
+    let backwards = "&from=2026-02-01T00:00:00Z&to=2026-01-01T00:00:00Z";
+    assert_eq!(test::call_service(&app, history("application/json", backwards)).await.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_organization_quota_warns_then_rejects_ingestion() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "quotaadmin@example.com", "admin");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let req = test::TestRequest::post()
+        .uri("/api/admin/organizations")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .set_json(json!({"name": format!("Quota Org {}", uuid::Uuid::new_v4()), "max_readings": 2}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let org: serde_json::Value = test::read_body_json(resp).await;
+    let org_id = org["id"].as_str().unwrap().to_string();
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Quota Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/admin/organizations/{}/patients/{}", org_id, patient_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 204);
+
+    let serial = format!("WALKER-QUOTA-{}", uuid::Uuid::new_v4());
+    sqlx::query("INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'Quota Walker', '', $2)")
+        .bind(&serial)
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let ingest = || {
+        let timestamp = chrono::Utc::now().timestamp();
+        let payload = serde_json::to_string(&DeviceVitalsIngest {
+            heart_rate: 72,
+            spo2: 97,
+            temperature: 36.8,
+            timestamp,
+            steps: None,
+            motion: None,
+            elevation_change: None,
+            ambient_temperature: None,
+            humidity: None,
+            metadata: None,
+        })
+        .unwrap();
+        test::TestRequest::post()
+            .uri("/api/device/vitals")
+            .insert_header(("X-Device-Id", serial.as_str()))
+            .insert_header(("X-Timestamp", timestamp.to_string()))
+            .insert_header(("X-Signature", device_signature(TEST_DEVICE_SECRET, timestamp, &payload)))
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_payload(payload)
+            .to_request()
+    };
+    let notifier = medhealth_backend::notifier::Notifier::new(pool.clone());
+
+    // Unmeasured organizations are never limited
+    for _ in 0..2 {
+        let resp = test::call_service(&app, ingest()).await;
+        assert_eq!(resp.status(), 200);
+        assert!(resp.headers().get("X-Quota-Warning").is_none());
+    }
+
+    // At quota: accepted with a warning, admins told once
+    quota_service::check_quotas(&pool, &notifier).await.unwrap();
+    let resp = test::call_service(&app, ingest()).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("X-Quota-Warning").unwrap(), "readings=100%");
+    let notified: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM notifications n JOIN users u ON u.id = n.user_id
+         WHERE u.email = 'quotaadmin@example.com' AND n.kind = 'quota_exceeded' AND n.title LIKE $1"
+    )
+    .bind(format!("{}%", org["name"].as_str().unwrap()))
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(notified, 1);
+
+    // Past the grace margin: refused
+    quota_service::check_quotas(&pool, &notifier).await.unwrap();
+    let resp = test::call_service(&app, ingest()).await;
+    assert_eq!(resp.status(), 507);
+    assert_eq!(resp.headers().get("X-Quota-Warning").unwrap(), "readings=150%");
+
+    // Raising the quota lifts the block immediately
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/admin/organizations/{}/quota", org_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .set_json(json!({"max_readings": 100}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let org: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(org["readings"], 3);
+    assert_eq!(test::call_service(&app, ingest()).await.status(), 200);
+}
+
+#[actix_web::test]
+async fn test_usage_events_roll_up_per_organization() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "usageadmin@example.com", "admin");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let org_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO organizations (name) VALUES ($1) RETURNING id")
+        .bind(format!("Usage Org {}", uuid::Uuid::new_v4()))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let patient_id: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO patients (display_name, organization_id) VALUES ('Usage Patient', $1) RETURNING id"
+    )
+    .bind(org_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    let serial = format!("WALKER-USAGE-{}", uuid::Uuid::new_v4());
+    sqlx::query("INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'Usage Walker', '', $2)")
+        .bind(&serial)
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    for _ in 0..3 {
+        let timestamp = chrono::Utc::now().timestamp();
+        let payload = serde_json::to_string(&DeviceVitalsIngest {
+            heart_rate: 70,
+            spo2: 98,
+            temperature: 36.6,
+            timestamp,
+            steps: None,
+            motion: None,
+            elevation_change: None,
+            ambient_temperature: None,
+            humidity: None,
+            metadata: None,
+        })
+        .unwrap();
+        let req = test::TestRequest::post()
+            .uri("/api/device/vitals")
+            .insert_header(("X-Device-Id", serial.as_str()))
+            .insert_header(("X-Timestamp", timestamp.to_string()))
+            .insert_header(("X-Signature", device_signature(TEST_DEVICE_SECRET, timestamp, &payload)))
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_payload(payload)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+    }
+
+    // Usage is recorded off the request path
+    let mut recorded = 0i64;
+    for _ in 0..50 {
+        recorded = sqlx::query_scalar("SELECT COUNT(*) FROM usage_events WHERE organization_id = $1")
+            .bind(org_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        if recorded == 3 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    assert_eq!(recorded, 3);
+
+    let usage_for_org = |body: &serde_json::Value| {
+        body["usage"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|row| row["organization_id"] == json!(org_id.to_string()))
+            .map(|row| (row["metric"].as_str().unwrap().to_string(), row["quantity"].as_i64().unwrap()))
+            .collect::<Vec<_>>()
+    };
+    let get_usage = || {
+        test::TestRequest::get()
+            .uri("/api/admin/usage")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, get_usage()).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(usage_for_org(&body), vec![("readings_ingested".to_string(), 3)]);
+
+    // Rolling up again never double counts
+    usage_service::roll_up(&pool, chrono::Utc::now().date_naive()).await.unwrap();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, get_usage()).await).await;
+    assert_eq!(usage_for_org(&body), vec![("readings_ingested".to_string(), 3)]);
+}
+
+#[actix_rt::test]
+async fn test_gateway_relay_requires_approval_and_records_provenance() {
+    let app = test::init_service(build_test_app!()).await;
+    let caregiver = login_as!(app, "gatewaycaregiver@example.com", "viewer");
+    let phone = login_as!(app, "gatewayphone@example.com", "viewer");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Gateway Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO patient_caregivers (patient_id, user_id) SELECT $1, id FROM users WHERE email = 'gatewaycaregiver@example.com'"
+    )
+    .bind(patient_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    let serial = format!("WALKER-GATEWAY-{}", uuid::Uuid::new_v4());
+    sqlx::query("INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'Gateway Walker', '', $2)")
+        .bind(&serial)
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let relay = || {
+        test::TestRequest::post()
+            .uri("/api/gateway/vitals")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", phone)))
+            .set_json(json!({
+                "device_id": serial,
+                "heartRate": 74,
+                "spo2": 96,
+                "temperature": 36.7,
+                "timestamp": chrono::Utc::now().timestamp() - 600,
+            }))
+            .to_request()
+    };
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/devices/{}/gateways", serial))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", phone)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let gateway: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(gateway["status"], "pending");
+    let gateway_id = gateway["id"].as_str().unwrap().to_string();
+
+    // Pending phones cannot relay, and cannot approve themselves without caring for the patient
+    assert_eq!(test::call_service(&app, relay()).await.status(), 403);
+    let decide = |token: &str, status: &str| {
+        test::TestRequest::put()
+            .uri(&format!("/api/devices/{}/gateways/{}", serial, gateway_id))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+            .set_json(json!({"status": status}))
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, decide(&phone, "approved")).await.status(), 403);
+    assert_eq!(test::call_service(&app, decide(&caregiver, "approved")).await.status(), 200);
+
+    let resp = test::call_service(&app, relay()).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let (source, relayed_by): (String, String) = sqlx::query_as(
+        "SELECT r.ingest_source, u.email FROM sensor_readings r JOIN users u ON u.id = r.relayed_by WHERE r.id = $1"
+    )
+    .bind(body["reading_id"].as_i64().unwrap())
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!((source.as_str(), relayed_by.as_str()), ("relayed", "gatewayphone@example.com"));
+
+    // Revoked phones are refused again
+    assert_eq!(test::call_service(&app, decide(&caregiver, "revoked")).await.status(), 200);
+    assert_eq!(test::call_service(&app, relay()).await.status(), 403);
+}
+
+#[actix_web::test]
+async fn test_severity_rules_use_patient_age_and_diagnoses() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "severityadmin@example.com", "admin");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+    let auth = || (header::AUTHORIZATION, format!("Bearer {}", admin));
+
+    let rules = json!({
+        "hr_low": 50, "hr_high": 180, "spo2_low": 88, "fever_temperature": 38.0, "hypothermia_temperature": 35.5,
+        "severity_rules": [{"min_age": 80, "metric": "temperature", "shift": 1}],
+    });
+    let req = test::TestRequest::post()
+        .uri("/api/threshold-profiles")
+        .insert_header(auth())
+        .set_json(json!({"name": format!("elderly-{}", uuid::Uuid::new_v4()), "rules": rules}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let profile: serde_json::Value = test::read_body_json(resp).await;
+
+    let mut serials = Vec::new();
+    for born in ["1996-03-01", "1938-03-01"] {
+        let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Severity Patient') RETURNING id")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let req = test::TestRequest::put()
+            .uri(&format!("/api/patients/{}/attributes", patient_id))
+            .insert_header(auth())
+            .set_json(json!({"date_of_birth": born, "diagnoses": [" Hypertension ", "hypertension", ""]}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        let attributes: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(attributes["diagnoses"], json!(["Hypertension"]));
+
+        let req = test::TestRequest::put()
+            .uri(&format!("/api/patients/{}/threshold-profile", patient_id))
+            .insert_header(auth())
+            .set_json(json!({"profile_id": profile["id"]}))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+        let serial = format!("WALKER-SEV-{}", uuid::Uuid::new_v4());
+        sqlx::query("INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'Severity Walker', '', $2)")
+            .bind(&serial)
+            .bind(patient_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        serials.push(serial);
+    }
+
+    // The same fever reading is "high" at 30 and "critical" at 88
+    let mut levels = Vec::new();
+    for serial in &serials {
+        let timestamp = chrono::Utc::now().timestamp();
+        let payload = serde_json::to_string(&DeviceVitalsIngest {
+            heart_rate: 80,
+            spo2: 97,
+            temperature: 38.6,
+            timestamp,
+            steps: None,
+            motion: None,
+            elevation_change: None,
+            ambient_temperature: None,
+            humidity: None,
+            metadata: None,
+        })
+        .unwrap();
+        let req = test::TestRequest::post()
+            .uri("/api/device/vitals")
+            .insert_header(("X-Device-Id", serial.as_str()))
+            .insert_header(("X-Timestamp", timestamp.to_string()))
+            .insert_header(("X-Signature", device_signature(TEST_DEVICE_SECRET, timestamp, &payload)))
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_payload(payload)
+            .to_request();
+        let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+        let level: String = sqlx::query_scalar("SELECT alert_level::text FROM ml_analysis WHERE sensor_reading_id = $1")
+            .bind(body["reading_id"].as_i64().unwrap())
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        levels.push(level);
+    }
+    assert_eq!(levels, vec!["high", "critical"]);
+}
+
+#[actix_web::test]
+async fn test_payload_schemas_are_published() {
+    let app = test::init_service(build_test_app!()).await;
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/api/schemas").to_request()).await;
+    assert_eq!(resp.status(), 200);
+    let listed: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert!(listed.iter().any(|s| s["href"] == "/api/schemas/device_vitals_ingest"));
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/api/schemas/device_vitals_ingest").to_request()).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("content-type").unwrap(), "application/schema+json");
+    let schema: serde_json::Value = test::read_body_json(resp).await;
+    let schema = jsonschema::JSONSchema::compile(&schema).expect("published schema should compile");
+
+    // What firmware sends passes; an out-of-range reading is caught before sending
+    assert!(schema.is_valid(&json!({"heartRate": 72, "spo2": 97, "temperature": 36.8, "timestamp": 1700000000})));
+    assert!(!schema.is_valid(&json!({"heartRate": 400, "spo2": 97, "temperature": 36.8, "timestamp": 1700000000})));
+    assert!(!schema.is_valid(&json!({"heartRate": 72, "spo2": 97, "temperature": 36.8})));
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/api/schemas/nope").to_request()).await;
+    assert_eq!(resp.status(), 404);
+}
+
+#[actix_web::test]
+async fn test_admin_test_alert_runs_the_alerting_chain() {
+    let state = init_state(&test_settings()).await.expect("PostgreSQL and Redis required for integration tests");
+    let mut events = state.sse_broadcaster.subscribe();
+    let app = test::init_service(build_app(web::Data::new(state))).await;
+    let admin = login_as!(app, "testalert-admin@example.com", "admin");
+    let caregiver = login_as!(app, "testalert-carer@example.com", "viewer");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Commissioning Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO patient_caregivers (patient_id, user_id) SELECT $1, id FROM users WHERE email = 'testalert-carer@example.com'")
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let post = |token: &str, body: serde_json::Value| {
+        test::TestRequest::post()
+            .uri("/api/admin/test-alert")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+            .set_json(body)
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, post(&caregiver, json!({}))).await.status(), 403);
+    assert_eq!(test::call_service(&app, post(&admin, json!({"level": "apocalyptic"}))).await.status(), 400);
+    assert_eq!(test::call_service(&app, post(&admin, json!({"patient_id": uuid::Uuid::new_v4()}))).await.status(), 404);
+
+    let resp = test::call_service(&app, post(&admin, json!({"patient_id": patient_id, "level": "high"}))).await;
+    assert_eq!(resp.status(), 201);
+    let alert: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(alert["kind"], "test");
+    assert_eq!(alert["level"], "high");
+    assert!(alert["message"].as_str().unwrap().starts_with("[TEST]"));
+    assert_eq!(alert["responses"][0]["channel"], "inbox");
+    assert_eq!(alert["responses"][0]["status"], "sent");
+
+    // Dashboards see it flagged as a test
+    let pushed = loop {
+        let envelope = events.try_recv().expect("test alert should be broadcast");
+        if let medhealth_backend::models::SseEvent::Alert(pushed) = &envelope.event {
+            break pushed.clone();
+        }
+    };
+    assert_eq!(pushed.details["test"], true);
+    assert_eq!(pushed.details["alert_id"], alert["id"]);
+
+    let delivered: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM notifications n JOIN users u ON u.id = n.user_id
+         WHERE u.email = 'testalert-carer@example.com' AND n.kind = 'test' AND n.patient_id = $1"
+    )
+    .bind(patient_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(delivered, 1);
+}
+
+#[actix_web::test]
+async fn test_admin_slo_report_rolls_up_samples() {
+    let mut settings = test_settings();
+    settings.slo.objectives = vec![SloObjective {
+        name: "it_ingestion".to_string(),
+        endpoint: "/api/device/vitals".to_string(),
+        method: Some("POST".to_string()),
+        latency_ms: Some(200),
+        target: 0.999,
+    }];
+    let state = init_state(&settings).await.expect("PostgreSQL and Redis required for integration tests");
+    let app = test::init_service(build_app(web::Data::new(state))).await;
+    let admin = login_as!(app, "slo-admin@example.com", "admin");
+    let viewer = login_as!(app, "slo-viewer@example.com", "viewer");
+    let pool = create_pool(&settings.database).await.expect("Failed to create test database pool");
+
+    sqlx::query("DELETE FROM slo_samples WHERE objective = 'it_ingestion'").execute(&pool).await.unwrap();
+    sqlx::query(
+        "INSERT INTO slo_samples (objective, total, good, sampled_at) VALUES
+         ('it_ingestion', 1000, 999, now() - interval '1 day'),
+         ('it_ingestion', 1000, 990, now() - interval '20 days'),
+         ('it_ingestion', 1000, 0, now() - interval '40 days')"
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let get = |token: &str| {
+        test::TestRequest::get()
+            .uri("/api/admin/slo")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, get(&viewer)).await.status(), 403);
+
+    let resp = test::call_service(&app, get(&admin)).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let objective = &body["objectives"][0];
+    assert_eq!(objective["name"], "it_ingestion");
+    assert_eq!(objective["latency_ms"], 200);
+
+    let week = &objective["windows"][0];
+    assert_eq!((week["days"].as_i64(), week["total"].as_i64(), week["good"].as_i64()), (Some(7), Some(1000), Some(999)));
+    assert_eq!(week["met"], true);
+
+    // Samples older than 30 days are out of every window
+    let month = &objective["windows"][1];
+    assert_eq!((month["days"].as_i64(), month["total"].as_i64(), month["good"].as_i64()), (Some(30), Some(2000), Some(1989)));
+    assert_eq!(month["met"], false);
+    assert!(month["error_budget_remaining"].as_f64().unwrap() < 0.0);
+}
+
+#[actix_web::test]
+async fn test_authenticated_routes_reject_missing_invalid_and_revoked_tokens() {
+    let app = test::init_service(build_test_app!()).await;
+    let token = login_as!(app, "extractor-viewer@example.com", "viewer");
+
+    let get = |auth: Option<String>| {
+        let mut req = test::TestRequest::get().uri("/api/notifications");
+        if let Some(auth) = auth {
+            req = req.insert_header((header::AUTHORIZATION, auth));
+        }
+        req.to_request()
+    };
+    let error = |resp| async { test::read_body_json::<serde_json::Value, _>(resp).await["error"].clone() };
+
+    let resp = test::call_service(&app, get(None)).await;
+    assert_eq!(resp.status(), 401);
+    assert_eq!(error(resp).await, "Missing token");
+
+    let resp = test::call_service(&app, get(Some("Bearer not-a-jwt".to_string()))).await;
+    assert_eq!(resp.status(), 401);
+    assert_eq!(error(resp).await, "Invalid token");
+
+    let resp = test::call_service(&app, get(Some(format!("Bearer {}", token)))).await;
+    assert_eq!(resp.status(), 200);
+
+    let logout = test::TestRequest::post()
+        .uri("/auth/logout")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    assert_eq!(test::call_service(&app, logout).await.status(), 200);
+
+    let resp = test::call_service(&app, get(Some(format!("Bearer {}", token)))).await;
+    assert_eq!(resp.status(), 401);
+    assert_eq!(error(resp).await, "Token revoked");
+}
+
+#[actix_web::test]
+async fn test_registry_roles_enforced_per_route() {
+    let app = test::init_service(build_test_app!()).await;
+    let viewer = login_as!(app, "rbac-viewer@example.com", "viewer");
+    let device_manager = login_as!(app, "rbac-devices@example.com", "device_manager");
+    let clinician = login_as!(app, "rbac-clinician@example.com", "clinician");
+
+    let call = |req: test::TestRequest, token: &str| {
+        req.insert_header((header::AUTHORIZATION, format!("Bearer {}", token))).to_request()
+    };
+    let error = |resp| async { test::read_body_json::<serde_json::Value, _>(resp).await["error"].clone() };
+
+    // Clinician+ routes reject viewers before the handler runs
+    let resp = test::call_service(&app, call(test::TestRequest::get().uri("/api/fhir/export"), &viewer)).await;
+    assert_eq!(resp.status(), 403);
+    assert_eq!(error(resp).await, "Clinician role required");
+    let resp = test::call_service(&app, call(test::TestRequest::get().uri("/api/fhir/export"), &clinician)).await;
+    assert_ne!(resp.status(), 403);
+
+    // Device managers may pair walkers but nothing else under /api/admin
+    let resp = test::call_service(
+        &app,
+        call(test::TestRequest::post().uri("/api/admin/devices/RBAC-UNKNOWN/pairing-code"), &device_manager),
+    )
+    .await;
+    assert_eq!(resp.status(), 404);
+    let resp = test::call_service(&app, call(test::TestRequest::get().uri("/api/admin/slo"), &device_manager)).await;
+    assert_eq!(resp.status(), 403);
+    assert_eq!(error(resp).await, "Admin role required");
+
+    // Missing tokens are still a 401, not a 403
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/api/admin/slo").to_request()).await;
+    assert_eq!(resp.status(), 401);
+}
+
+#[actix_web::test]
+async fn test_admin_log_level_reloads_filter() {
+    let log_dir = tempfile::tempdir().unwrap();
+    let _ = medhealth_backend::logging::init_logging(log_dir.path(), "info");
+
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "loglevel-admin@example.com", "admin");
+    let put = |filter: &str| {
+        test::TestRequest::put()
+            .uri("/api/admin/log-level")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .set_json(json!({"filter": filter}))
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, put("info,medhealth_backend::sse=debug")).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["filter"].as_str().unwrap().contains("medhealth_backend::sse=debug"));
+    assert!(body["previous"].is_string());
+
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::get()
+            .uri("/api/admin/log-level")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .to_request(),
+    )
+    .await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["filter"].as_str().unwrap().contains("medhealth_backend::sse=debug"));
+
+    assert_eq!(test::call_service(&app, put("medhealth_backend=shouty")).await.status(), 400);
+    assert_eq!(test::call_service(&app, put("")).await.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_device_signs_with_its_own_secret() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "devicesecret-admin@example.com", "admin");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let serial = format!("WALKER-SECRET-{}", uuid::Uuid::new_v4());
+    sqlx::query("INSERT INTO devices (device_id, device_name, secret_hash) VALUES ($1, 'Secret Walker', '')")
+        .bind(&serial)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let ingest = |secret: &str| {
+        let timestamp = chrono::Utc::now().timestamp();
+        let payload = json!({"heartRate": 72, "spo2": 98, "temperature": 36.7, "timestamp": timestamp}).to_string();
+        test::TestRequest::post()
+            .uri("/api/device/vitals")
+            .insert_header(("X-Device-Id", serial.as_str()))
+            .insert_header(("X-Timestamp", timestamp.to_string()))
+            .insert_header(("X-Signature", device_signature(secret, timestamp, &payload)))
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_payload(payload)
+            .to_request()
+    };
+
+    // Until issued its own secret the walker signs with the shared one
+    assert_eq!(test::call_service(&app, ingest(TEST_DEVICE_SECRET)).await.status(), 200);
+
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::post()
+            .uri(&format!("/api/admin/devices/{}/secret", serial))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), 201);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let secret = body["secret"].as_str().unwrap().to_string();
+
+    // Stored hashed and encrypted, never as issued
+    let (hash, sealed): (String, String) =
+        sqlx::query_as("SELECT secret_hash, secret_ciphertext FROM devices WHERE device_id = $1")
+            .bind(&serial)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert!(!hash.contains(&secret) && !sealed.contains(&secret));
+
+    assert_eq!(test::call_service(&app, ingest(&secret)).await.status(), 200);
+    assert_eq!(test::call_service(&app, ingest(TEST_DEVICE_SECRET)).await.status(), 401);
+}
+
+#[actix_web::test]
+async fn test_admin_device_registry_lifecycle() {
+    let app = test::init_service(build_test_app!()).await;
+    let manager = login_as!(app, "fleet-manager@example.com", "device_manager");
+    let auth = (header::AUTHORIZATION, format!("Bearer {}", manager));
+    let serial = format!("WALKER-FLEET-{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+
+    let register = || {
+        test::TestRequest::post()
+            .uri("/api/admin/devices")
+            .insert_header(auth.clone())
+            .set_json(json!({"device_id": serial, "device_name": "Fleet Walker"}))
+            .to_request()
+    };
+    let resp = test::call_service(&app, register()).await;
+    assert_eq!(resp.status(), 201);
+    let created: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(created["device_id"], serial.as_str());
+    assert_eq!(created["is_active"], true);
+    let secret = created["secret"].as_str().unwrap().to_string();
+    assert_eq!(test::call_service(&app, register()).await.status(), 409);
+
+    // The new walker can sign in with its secret straight away
+    let ingest = || {
+        let timestamp = chrono::Utc::now().timestamp();
+        let payload = json!({"heartRate": 70, "spo2": 97, "temperature": 36.6, "timestamp": timestamp}).to_string();
+        test::TestRequest::post()
+            .uri("/api/device/vitals")
+            .insert_header(("X-Device-Id", serial.as_str()))
+            .insert_header(("X-Timestamp", timestamp.to_string()))
+            .insert_header(("X-Signature", device_signature(&secret, timestamp, &payload)))
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_payload(payload)
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, ingest()).await.status(), 200);
+
+    // Listed without its secret
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::get().uri("/api/admin/devices?active=true").insert_header(auth.clone()).to_request(),
+    )
+    .await;
+    let listed: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    let entry = listed.iter().find(|d| d["device_id"] == serial.as_str()).unwrap();
+    assert!(entry.get("secret").is_none() && entry.get("secret_hash").is_none());
+
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::patch()
+            .uri(&format!("/api/admin/devices/{}", serial))
+            .insert_header(auth.clone())
+            .set_json(json!({"device_name": "Ward 3 Walker"}))
+            .to_request(),
+    )
+    .await;
+    let updated: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(updated["device_name"], "Ward 3 Walker");
+
+    // Deactivated walkers are turned away
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::delete().uri(&format!("/api/admin/devices/{}", serial)).insert_header(auth.clone()).to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), 204);
+    assert_eq!(test::call_service(&app, ingest()).await.status(), 401);
+
+    let caregiver = login_as!(app, "fleet-caregiver@example.com", "caregiver");
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::get()
+            .uri("/api/admin/devices")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", caregiver)))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), 403);
+}
+
+#[actix_web::test]
+async fn test_device_reads_its_own_write() {
+    let app = test::init_service(build_test_app!()).await;
+    let token = login_as!(app, "read-your-writes@example.com", "viewer");
+    let stranger = login_as!(app, "read-your-writes-stranger@example.com", "viewer");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('RYW Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO patient_caregivers (patient_id, user_id) SELECT $1, id FROM users WHERE email = 'read-your-writes@example.com'")
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let serial = format!("WALKER-RYW-{}", uuid::Uuid::new_v4());
+    sqlx::query("INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'RYW Walker', '', $2)")
+        .bind(&serial)
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let timestamp = chrono::Utc::now().timestamp();
+    let payload = json!({"heartRate": 71, "spo2": 97, "temperature": 36.6, "timestamp": timestamp}).to_string();
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::post()
+            .uri("/api/device/vitals")
+            .insert_header(("X-Device-Id", serial.as_str()))
+            .insert_header(("X-Timestamp", timestamp.to_string()))
+            .insert_header(("X-Signature", device_signature(TEST_DEVICE_SECRET, timestamp, &payload)))
+            .insert_header(("Prefer", "return=representation"))
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_payload(payload)
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("preference-applied").unwrap(), "return=representation");
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["vitals"]["timestamp"], timestamp);
+
+    let latest = |query: String| {
+        test::TestRequest::get()
+            .uri(&format!("/api/vitals/latest?{}", query))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+            .to_request()
+    };
+
+    // Already fresh enough: answered without waiting
+    let resp = test::call_service(&app, latest(format!("min_timestamp={}", timestamp))).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["timestamp"].as_i64().unwrap() >= timestamp);
+
+    // Nothing that fresh arrives: the newest vitals once the wait is up
+    let started = std::time::Instant::now();
+    let resp = test::call_service(&app, latest(format!("min_timestamp={}&wait_seconds=1", timestamp + 3600))).await;
+    assert_eq!(resp.status(), 200);
+    assert!(started.elapsed() >= std::time::Duration::from_secs(1));
+
+    let resp = test::call_service(&app, latest("min_timestamp=0&wait_seconds=31".to_string())).await;
+    assert_eq!(resp.status(), 400);
+
+    // Users linked to no patient see none of their vitals
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::get()
+            .uri("/api/vitals/latest")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", stranger)))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["timestamp"], 0);
+}
+
+#[actix_web::test]
+async fn test_vitals_poll_catches_up_from_cursor() {
+    use medhealth_backend::models::{AlertLevel, LatestVitals, MlAlert};
+    use medhealth_backend::sse::{broadcast_alert, broadcast_vitals};
+
+    let state = web::Data::new(init_state(&test_settings()).await.expect("PostgreSQL and Redis required for integration tests"));
+    let app = test::init_service(build_app(state.clone())).await;
+    let token = login_as!(app, "vitals-poll@example.com", "admin");
+    let poll = |query: String| {
+        test::TestRequest::get()
+            .uri(&format!("/api/vitals/poll?{}", query))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+            .to_request()
+    };
+    let vitals = |timestamp: i64| LatestVitals {
+        heart_rate: 74,
+        spo2: 97,
+        temperature: 36.7,
+        timestamp,
+        quality_score: None,
+        ml_alert: None,
+    };
+
+    // Nothing new: an empty answer once the wait is up
+    let body: serde_json::Value = test::call_and_read_body_json(&app, poll("wait_seconds=0".to_string())).await;
+    assert_eq!(body["events"], json!([]));
+
+    broadcast_vitals(&state.sse_broadcaster, None, vitals(1));
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let body: serde_json::Value = test::call_and_read_body_json(&app, poll("wait_seconds=0".to_string())).await;
+    let cursor = body["cursor"].as_str().unwrap().to_string();
+
+    // Broadcast between polls: returned straight away, in order
+    broadcast_vitals(&state.sse_broadcaster, None, vitals(2));
+    broadcast_alert(&state.sse_broadcaster, None, MlAlert {
+        level: AlertLevel::Medium,
+        message: "Heart rate rising".to_string(),
+        details: json!({}),
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let body: serde_json::Value = test::call_and_read_body_json(&app, poll(format!("since={}&wait_seconds=5", cursor))).await;
+    let types: Vec<_> = body["events"].as_array().unwrap().iter().map(|e| e["type"].as_str().unwrap()).collect();
+    assert_eq!(types, ["vitals", "alert"]);
+    assert_eq!(body["events"][0]["data"]["timestamp"], 2);
+    assert_eq!(body["cursor"], body["events"][1]["id"]);
+
+    let resp = test::call_service(&app, poll("wait_seconds=61".to_string())).await;
+    assert_eq!(resp.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_fhir_export_pages_with_links() {
+    let app = test::init_service(build_test_app!()).await;
+    let token = login_as!(app, "fhir-pages@example.com", "clinician");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let device_id: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO devices (device_id, device_name, secret_hash) VALUES ($1, 'FHIR Paging Walker', '') RETURNING id"
+    )
+    .bind(format!("WALKER-FHIR-PAGES-{}", uuid::Uuid::new_v4()))
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    // Received after everything else, so `_since` picks out just these three
+    let since = chrono::DateTime::parse_from_rfc3339("2999-01-01T00:00:00Z").unwrap().with_timezone(&chrono::Utc);
+    for minute in 0..3 {
+        sqlx::query(
+            "INSERT INTO sensor_readings (device_id, heart_rate, spo2, reading_timestamp, received_at)
+             VALUES ($1, 70, 97, now(), $2)"
+        )
+        .bind(device_id)
+        .bind(since + chrono::Duration::minutes(minute))
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+
+    let export = |uri: String| {
+        test::TestRequest::get()
+            .uri(&uri)
+            .insert_header((header::ACCEPT, "application/fhir+json"))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+            .to_request()
+    };
+    let link = |bundle: &serde_json::Value, relation: &str| {
+        bundle["link"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|l| l["relation"] == relation)
+            .map(|l| l["url"].as_str().unwrap().to_string())
+    };
+    let path = |url: String| url[url.find("/api/").unwrap()..].to_string();
+
+    let resp = test::call_service(&app, export("/api/fhir/export?_since=2999-01-01T00:00:00Z&_count=2".into())).await;
+    assert_eq!(resp.status(), 200);
+    let first: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(first["type"], "searchset");
+    assert_eq!(first["total"], 6); // two Observations per reading
+    // Four Observations and, once, the walker they reference
+    let entries = first["entry"].as_array().unwrap();
+    assert_eq!(entries.len(), 5);
+    let included: Vec<_> = entries.iter().filter(|e| e["search"]["mode"] == "include").collect();
+    assert_eq!(included.len(), 1);
+    assert_eq!(included[0]["resource"]["resourceType"], "Device");
+    assert_eq!(included[0]["resource"]["id"], device_id.to_string());
+    assert!(link(&first, "previous").is_none());
+
+    // A reading ingested mid-sync lands after the cursor instead of shifting the pages
+    sqlx::query(
+        "INSERT INTO sensor_readings (device_id, heart_rate, spo2, reading_timestamp, received_at)
+         VALUES ($1, 71, 96, now(), $2)"
+    )
+    .bind(device_id)
+    .bind(since + chrono::Duration::minutes(10))
+    .execute(&pool)
+    .await
+    .unwrap();
+    let resp = test::call_service(&app, export(path(link(&first, "next").unwrap()))).await;
+    let second: serde_json::Value = test::read_body_json(resp).await;
+    let observations: Vec<_> = second["entry"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|e| e["search"]["mode"] == "match")
+        .collect();
+    assert_eq!(observations.len(), 4);
+    assert_eq!(observations[2]["resource"]["valueQuantity"]["value"].as_f64(), Some(71.0));
+    assert!(link(&second, "next").is_none());
+    assert!(link(&second, "previous").is_none());
+
+    let resp = test::call_service(&app, export("/api/fhir/export?_since=yesterday".into())).await;
+    assert_eq!(resp.status(), 400);
+
+    sqlx::query("DELETE FROM sensor_readings WHERE device_id = $1").bind(device_id).execute(&pool).await.unwrap();
+}
+
+#[actix_web::test]
+async fn test_reading_metadata_passthrough() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "reading-metadata@example.com", "admin");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Metadata Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let serial = format!("WALKER-META-{}", uuid::Uuid::new_v4());
+    sqlx::query("INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'Metadata Walker', '', $2)")
+        .bind(&serial)
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let ingest = |metadata: serde_json::Value| {
+        let timestamp = chrono::Utc::now().timestamp();
+        let payload = json!({
+            "heartRate": 74, "spo2": 97, "temperature": 36.6, "timestamp": timestamp, "steps": 3, "metadata": metadata
+        })
+        .to_string();
+        test::TestRequest::post()
+            .uri("/api/device/vitals")
+            .insert_header(("X-Device-Id", serial.as_str()))
+            .insert_header(("X-Timestamp", timestamp.to_string()))
+            .insert_header(("X-Signature", device_signature(TEST_DEVICE_SECRET, timestamp, &payload)))
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_payload(payload)
+            .to_request()
+    };
+
+    let resp = test::call_service(
+        &app,
+        ingest(json!({"firmware_version": "2.1.0", "sensor_mode": "low_power", "signal_strength": -61, "boot_count": 9})),
+    )
+    .await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let metadata: serde_json::Value = sqlx::query_scalar("SELECT metadata FROM sensor_readings WHERE id = $1")
+        .bind(body["reading_id"].as_i64().unwrap())
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(metadata["steps"], 3);
+    assert_eq!(metadata["device"]["boot_count"], 9);
+
+    // History passes on the surfaced keys only
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/vitals/history?patient_id={}", patient_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .to_request();
+    let history: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(
+        history[0]["metadata"]["device"],
+        json!({"firmware_version": "2.1.0", "sensor_mode": "low_power", "signal_strength": -61})
+    );
+
+    for rejected in [json!({"Firmware": "2.1.0"}), json!({"sensor_mode": {"nested": true}}), json!({"note": "x".repeat(65)})] {
+        let resp = test::call_service(&app, ingest(rejected)).await;
+        assert_eq!(resp.status(), 400);
+    }
+}
+
+#[actix_web::test]
+async fn test_fhir_bulk_export() {
+    let app = test::init_service(build_test_app!()).await;
+    let clinician = login_as!(app, "bulk-export@example.com", "clinician");
+    let other = login_as!(app, "bulk-export-other@example.com", "clinician");
+    let path = |url: &str| url[url.find("/api/").unwrap()..].to_string();
+    let get = |uri: String, token: &str| {
+        test::TestRequest::get()
+            .uri(&uri)
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+            .insert_header(("Prefer", "respond-async"))
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, get("/api/fhir/$export?_type=Encounter".into(), &clinician)).await;
+    assert_eq!(resp.status(), 400);
+
+    let resp = test::call_service(&app, get("/api/fhir/$export?_type=Device&_outputFormat=application/fhir%2Bndjson".into(), &clinician)).await;
+    assert_eq!(resp.status(), 202);
+    let status_url = path(resp.headers().get(header::CONTENT_LOCATION).unwrap().to_str().unwrap());
+    assert!(status_url.contains("/api/fhir/export-status/"));
+
+    // Only the clinician who asked can follow it
+    let resp = test::call_service(&app, get(status_url.clone(), &other)).await;
+    assert_eq!(resp.status(), 404);
+
+    let mut manifest = serde_json::Value::Null;
+    for _ in 0..50 {
+        let resp = test::call_service(&app, get(status_url.clone(), &clinician)).await;
+        if resp.status() == 200 {
+            manifest = test::read_body_json(resp).await;
+            break;
+        }
+        assert_eq!(resp.status(), 202);
+        assert!(resp.headers().contains_key("x-progress"));
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    }
+    assert_eq!(manifest["requiresAccessToken"], true);
+    let output = manifest["output"].as_array().expect("export did not complete");
+    assert!(!output.is_empty());
+    assert!(output.iter().all(|file| file["type"] == "Device"));
+
+    let resp = test::call_service(&app, get(path(output[0]["url"].as_str().unwrap()), &clinician)).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), "application/fhir+ndjson");
+    let body = test::read_body(resp).await;
+    let lines: Vec<serde_json::Value> =
+        std::str::from_utf8(&body).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(lines.len() as i64, output[0]["count"].as_i64().unwrap());
+    assert!(lines.iter().all(|resource| resource["resourceType"] == "Device"));
+}
+
+#[actix_web::test]
+async fn test_request_context_preferences() {
+    let app = test::init_service(build_test_app!()).await;
+    let token = login_as!(app, "preferences@example.com", "clinician");
+    let request = |method: test::TestRequest| method.uri("/auth/preferences").insert_header((header::AUTHORIZATION, format!("Bearer {}", token)));
+
+    let resp = test::call_service(&app, request(test::TestRequest::get()).to_request()).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["timezone"], "UTC");
+
+    let resp = test::call_service(&app,
+        request(test::TestRequest::put())
+            .set_json(json!({"locale": "en_gb", "timezone": "Europe/London", "units": "imperial"}))
+            .to_request()
+    ).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["locale"], "en-GB");
+
+    let resp = test::call_service(&app,
+        request(test::TestRequest::put()).set_json(json!({"timezone": "Europe/Atlantis"})).to_request()
+    ).await;
+    assert_eq!(resp.status(), 400);
+
+    // Headers override the stored preferences for one request
+    let resp = test::call_service(&app,
+        request(test::TestRequest::get())
+            .insert_header(("Time-Zone", "America/Chicago"))
+            .insert_header(("Accept-Language", "es;q=0.5, fr"))
+            .to_request()
+    ).await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["timezone"], "America/Chicago");
+    assert_eq!(body["locale"], "fr");
+    assert_eq!(body["units"], "imperial");
+}
+
+#[actix_web::test]
+async fn test_ml_rules_crud() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "mlrules-admin@example.com", "admin");
+    let clinician = login_as!(app, "mlrules-clinician@example.com", "clinician");
+    let name = format!("Sustained tachycardia {}", uuid::Uuid::new_v4());
+    let rule = json!({"name": name, "metric": "heart_rate", "comparator": ">", "value": 110, "duration_seconds": 300, "alert_level": "high"});
+    let send = |req: test::TestRequest, token: &str| req.insert_header((header::AUTHORIZATION, format!("Bearer {}", token))).to_request();
+
+    let resp = test::call_service(&app, send(test::TestRequest::post().uri("/api/ml/rules").set_json(&rule), &clinician)).await;
+    assert_eq!(resp.status(), 403);
+
+    let resp = test::call_service(&app, send(test::TestRequest::post().uri("/api/ml/rules").set_json(&rule), &admin)).await;
+    assert_eq!(resp.status(), 201);
+    let created: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(created["metric"], "heart_rate");
+    assert_eq!(created["enabled"], true);
+    let uri = format!("/api/ml/rules/{}", created["id"].as_str().unwrap());
+
+    let resp = test::call_service(&app, send(test::TestRequest::post().uri("/api/ml/rules").set_json(&rule), &admin)).await;
+    assert_eq!(resp.status(), 409);
+
+    for invalid in [json!({"comparator": "=>"}), json!({"alert_level": "urgent"}), json!({"alert_level": "none"}), json!({"duration_seconds": 7200})] {
+        let mut body = rule.clone();
+        body.as_object_mut().unwrap().extend(invalid.as_object().unwrap().clone());
+        let resp = test::call_service(&app, send(test::TestRequest::put().uri(&uri).set_json(&body), &admin)).await;
+        assert_eq!(resp.status(), 400);
+    }
+
+    let mut disabled = rule.clone();
+    disabled["enabled"] = json!(false);
+    let resp = test::call_service(&app, send(test::TestRequest::put().uri(&uri).set_json(&disabled), &admin)).await;
+    assert_eq!(resp.status(), 200);
+
+    let resp = test::call_service(&app, send(test::TestRequest::get().uri("/api/ml/rules"), &clinician)).await;
+    let rules: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert!(rules.iter().any(|r| r["id"] == created["id"] && r["enabled"] == false));
+
+    let resp = test::call_service(&app, send(test::TestRequest::delete().uri(&uri), &admin)).await;
+    assert_eq!(resp.status(), 204);
+    let resp = test::call_service(&app, send(test::TestRequest::delete().uri(&uri), &admin)).await;
+    assert_eq!(resp.status(), 404);
+}
+
+#[actix_web::test]
+async fn test_contact_webhook_deliveries_are_signed_sequenced_and_redeliverable() {
+    const SECRET: &str = "integration_test_webhook_secret_32b";
+
+    // Stand-in gateway keeping each delivery's webhook headers and body
+    let received = std::sync::Arc::new(std::sync::Mutex::new(Vec::<(Vec<(String, String)>, String)>::new()));
+    let sink = received.clone();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let gateway_addr = listener.local_addr().unwrap();
+    let gateway = actix_web::HttpServer::new(move || {
+        let sink = sink.clone();
+        App::new().route("/send", web::post().to(move |req: actix_web::HttpRequest, body: String| {
+            let sink = sink.clone();
+            async move {
+                let headers = req
+                    .headers()
+                    .iter()
+                    .filter(|(name, _)| name.as_str().starts_with("x-webhook-"))
+                    .map(|(name, value)| (name.to_string(), value.to_str().unwrap().to_string()))
+                    .collect();
+                sink.lock().unwrap().push((headers, body));
+                actix_web::HttpResponse::Ok().finish()
+            }
+        }))
+    })
+    .listen(listener)
+    .unwrap()
+    .workers(1)
+    .run();
+    actix_web::rt::spawn(gateway);
+
+    let mut settings = test_settings();
+    settings.emergency.contact_webhook_url = Some(format!("http://{}/send", gateway_addr));
+    settings.emergency.contact_webhook_secret = Some(SECRET.to_string());
+    let state = web::Data::new(init_state(&settings).await.expect("PostgreSQL and Redis required for integration tests"));
+    let app = test::init_service(build_app(state.clone())).await;
+    let admin = login_as!(app, "webhook-admin@example.com", "admin");
+    let viewer = login_as!(app, "webhook-viewer@example.com", "viewer");
+
+    let verify = |index: usize| {
+        let (headers, body) = received.lock().unwrap()[index].clone();
+        medhealth_client::webhooks::verify(
+            SECRET,
+            |name| headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str()),
+            &body,
+            chrono::Utc::now().timestamp(),
+            medhealth_client::webhooks::DEFAULT_TOLERANCE_SECONDS,
+        )
+        .expect("delivery verifies")
+    };
+
+    let alert_id = uuid::Uuid::new_v4();
+    state.notifier.notify_contact("sms", "+15551234567", "First", alert_id).await.unwrap();
+    state.notifier.notify_contact("call", "+15551234567", "Second", alert_id).await.unwrap();
+    let (first, second) = (verify(0), verify(1));
+    assert_eq!(second.sequence, first.sequence + 1);
+    assert_eq!(first.event.event_type, "contact_requested");
+    assert_eq!(first.event.data["message"], "First");
+
+    let uri = format!("/api/webhooks/contact/deliveries?after_sequence={}", first.sequence - 1);
+    let resp = test::call_service(&app, test::TestRequest::get().uri(&uri).insert_header((header::AUTHORIZATION, format!("Bearer {}", admin))).to_request()).await;
+    assert_eq!(resp.status(), 200);
+    let deliveries: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert_eq!(deliveries.len(), 2);
+    assert_eq!(deliveries[0]["event_id"], first.event.id.to_string());
+    assert_eq!(deliveries[0]["status"], "delivered");
+    assert_eq!(deliveries[0]["attempts"], 1);
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri(&uri).insert_header((header::AUTHORIZATION, format!("Bearer {}", viewer))).to_request()).await;
+    assert_eq!(resp.status(), 403);
+
+    let redeliver = |webhook: &str, event_id: uuid::Uuid| {
+        test::TestRequest::post()
+            .uri(&format!("/api/webhooks/{}/redeliver/{}", webhook, event_id))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .to_request()
+    };
+    let resp = test::call_service(&app, redeliver("contact", first.event.id)).await;
+    assert_eq!(resp.status(), 200);
+    let delivery: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(delivery["attempts"], 2);
+    assert_eq!(delivery["sequence"], first.sequence);
+
+    // Same event under the same number, which a receiver's replay guard recognises
+    let again = verify(2);
+    assert_eq!((again.event.id, again.sequence), (first.event.id, first.sequence));
+    assert_eq!(received.lock().unwrap()[2].1, received.lock().unwrap()[0].1);
+    let mut guard = medhealth_client::webhooks::ReplayGuard::new(16);
+    assert_eq!(guard.check(&first), medhealth_client::webhooks::Delivery::New);
+    assert_eq!(guard.check(&second), medhealth_client::webhooks::Delivery::New);
+    assert_eq!(guard.check(&again), medhealth_client::webhooks::Delivery::Duplicate);
+
+    assert_eq!(test::call_service(&app, redeliver("contact", uuid::Uuid::new_v4())).await.status(), 404);
+    assert_eq!(test::call_service(&app, redeliver("billing", first.event.id)).await.status(), 404);
+}
+
+#[actix_web::test]
+async fn test_organization_profile_brands_reports_and_fhir() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "branding-admin@example.com", "admin");
+    let viewer = login_as!(app, "branding-viewer@example.com", "viewer");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+    let put = |token: &str, body: serde_json::Value| {
+        test::TestRequest::put()
+            .uri("/api/admin/organization")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+            .set_json(body)
+            .to_request()
+    };
+
+    let profile = json!({
+        "name": "Springfield Rehab",
+        "logo_url": "https://springfield.example/logo.png",
+        "phone": "+15551234567",
+        "email": "care@springfield.example",
+        "address": "1 Main St\nSpringfield"
+    });
+    assert_eq!(test::call_service(&app, put(&viewer, profile.clone())).await.status(), 403);
+    for invalid in [json!({"name": ""}), json!({"name": "X", "logo_url": "not a url"}), json!({"name": "X", "phone": "555-1234"})] {
+        assert_eq!(test::call_service(&app, put(&admin, invalid)).await.status(), 400);
+    }
+    let resp = test::call_service(&app, put(&admin, profile)).await;
+    assert_eq!(resp.status(), 200);
+    let saved: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(saved["name"], "Springfield Rehab");
+    assert_eq!(saved["website"], serde_json::Value::Null);
+
+    let req = test::TestRequest::get()
+        .uri("/api/fhir/Organization/org-test-001")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", viewer)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let organization: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(organization["resourceType"], "Organization");
+    assert_eq!(organization["name"], "Springfield Rehab");
+    assert_eq!(organization["telecom"][1], json!({"system": "email", "value": "care@springfield.example"}));
+    let req = test::TestRequest::get()
+        .uri("/api/fhir/Organization/org-other")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", viewer)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Branded Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/patients/{}/activity?date=2024-01-01", patient_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .insert_header((header::ACCEPT, "application/pdf"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let pdf = String::from_utf8_lossy(&test::read_body(resp).await).into_owned();
+    assert!(pdf.contains("(Springfield Rehab) Tj"));
+    assert!(pdf.contains("(1 Main St, Springfield | +15551234567 | care@springfield.example) Tj"));
+}
+
+#[actix_web::test]
+async fn test_baselines_follow_the_patients_own_vitals() {
+    let app = test::init_service(build_test_app!()).await;
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Baseline Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let serial = format!("WALKER-BASE-{}", uuid::Uuid::new_v4());
+    let device: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'Baseline Walker', '', $2) RETURNING id"
+    )
+    .bind(&serial)
+    .bind(patient_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    // A habitually fast resting rate (96-104 bpm) over the last two days, plus readings the
+    // baseline ignores: one from before the window and one with a poor signal
+    sqlx::query(
+        "INSERT INTO sensor_readings (device_id, heart_rate, spo2, reading_timestamp, quality_score)
+         SELECT $1, 96 + m % 9, 97, now() - interval '2 days' + m * interval '10 minutes', 1.0
+         FROM generate_series(0, 199) m
+         UNION ALL SELECT $1, 160, 97, now() - interval '8 days', 1.0
+         UNION ALL SELECT $1, 40, 97, now() - interval '1 hour', 0.2"
+    )
+    .bind(device)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    baseline_service::refresh_baselines(&pool).await.unwrap();
+    let baseline = baseline_service::load_baseline(&pool, device, Some(patient_id)).await.unwrap().unwrap();
+    assert_eq!(baseline.hr_samples, 200);
+    assert!((baseline.hr_mean.unwrap() - 100.0).abs() < 0.5);
+    assert!(baseline.heart_rate().is_some());
+    assert!(baseline_service::load_baseline(&pool, device, None).await.unwrap().is_none());
+
+    // 112 bpm is a statistical outlier for the population, not for this patient
+    let timestamp = chrono::Utc::now().timestamp();
+    let body = DeviceVitalsIngest {
+        heart_rate: 112,
+        spo2: 97,
+        temperature: 36.9,
+        timestamp,
+        steps: None,
+        motion: None,
+        elevation_change: None,
+        ambient_temperature: None,
+        humidity: None,
+        metadata: None,
+    };
+    let payload = serde_json::to_string(&body).unwrap();
+    let req = test::TestRequest::post()
+        .uri("/api/device/vitals")
+        .insert_header(("X-Device-Id", serial.as_str()))
+        .insert_header(("X-Timestamp", timestamp.to_string()))
+        .insert_header(("X-Signature", device_signature(TEST_DEVICE_SECRET, timestamp, &payload)))
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_payload(payload)
+        .to_request();
+    let resp: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let details: serde_json::Value = sqlx::query_scalar("SELECT analysis_details FROM ml_analysis WHERE sensor_reading_id = $1")
+        .bind(resp["reading_id"].as_i64().unwrap())
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(details["baseline"]["patient_hr"], true);
+    assert!(!details["anomalies"].as_array().unwrap().iter().any(|a| a == STATISTICAL_HR));
+
+    // Walkers that stop reporting lose their baseline
+    sqlx::query("DELETE FROM sensor_readings WHERE device_id = $1").bind(device).execute(&pool).await.unwrap();
+    baseline_service::refresh_baselines(&pool).await.unwrap();
+    assert!(baseline_service::load_baseline(&pool, device, Some(patient_id)).await.unwrap().is_none());
+}
+
+#[actix_web::test]
+async fn test_dry_run_ingestion_analyses_without_storing() {
+    let app = test::init_service(build_test_app!()).await;
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Dry Run Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let serial = format!("WALKER-DRY-{}", uuid::Uuid::new_v4());
+    let device: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'Dry Run Walker', '', $2) RETURNING id"
+    )
+    .bind(&serial)
+    .bind(patient_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    let ingest = |heart_rate: i32, signed: bool| {
+        let timestamp = chrono::Utc::now().timestamp();
+        let body = DeviceVitalsIngest {
+            heart_rate,
+            spo2: 97,
+            temperature: 36.8,
+            timestamp,
+            steps: Some(12),
+            motion: None,
+            elevation_change: None,
+            ambient_temperature: None,
+            humidity: None,
+            metadata: None,
+        };
+        let payload = serde_json::to_string(&body).unwrap();
+        let signature = if signed { device_signature(TEST_DEVICE_SECRET, timestamp, &payload) } else { "invalid_signature".to_string() };
+        test::TestRequest::post()
+            .uri("/api/device/vitals?dry_run=true")
+            .insert_header(("X-Device-Id", serial.as_str()))
+            .insert_header(("X-Timestamp", timestamp.to_string()))
+            .insert_header(("X-Signature", signature))
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_payload(payload)
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, ingest(195, true)).await;
+    assert_eq!(resp.status(), 200);
+    let result: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(result["status"], "dry_run");
+    assert_eq!(result["would_accept"], true);
+    assert_eq!(result["patient_id"], patient_id.to_string());
+    assert_eq!(result["metadata"]["steps"], 12);
+    assert_eq!(result["analysis"]["anomaly_detected"], true);
+    assert_eq!(result["alert"]["level"], "critical");
+    assert_eq!(result["vitals"]["heartRate"], 195);
+
+    // Signatures are still checked
+    assert_eq!(test::call_service(&app, ingest(195, false)).await.status(), 401);
+
+    let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sensor_readings WHERE device_id = $1")
+        .bind(device)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(stored, 0);
+    let alerts: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM alerts WHERE device_id = $1")
+        .bind(device)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(alerts, 0);
+}
+
+#[actix_web::test]
+async fn test_vitals_alerts_are_stored_and_triaged() {
+    let state = init_state(&test_settings()).await.expect("PostgreSQL and Redis required for integration tests");
+    let mut rx = state.sse_broadcaster.subscribe();
+    let app = test::init_service(build_app(web::Data::new(state))).await;
+    let admin = login_as!(app, "triageadmin@example.com", "admin");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Triage Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let serial = format!("WALKER-TRIAGE-{}", uuid::Uuid::new_v4());
+    sqlx::query("INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'Triage Walker', '', $2)")
+        .bind(&serial)
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let ingest = |heart_rate: i32| {
+        let timestamp = chrono::Utc::now().timestamp();
+        let body = DeviceVitalsIngest {
+            heart_rate,
+            spo2: 97,
+            temperature: 36.8,
+            timestamp,
+            steps: None,
+            motion: None,
+            elevation_change: None,
+            ambient_temperature: None,
+            humidity: None,
+            metadata: None,
+        };
+        let payload = serde_json::to_string(&body).unwrap();
+        test::TestRequest::post()
+            .uri("/api/device/vitals")
+            .insert_header(("X-Device-Id", serial.as_str()))
+            .insert_header(("X-Timestamp", timestamp.to_string()))
+            .insert_header(("X-Signature", device_signature(TEST_DEVICE_SECRET, timestamp, &payload)))
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_payload(payload)
+            .to_request()
+    };
+    let post = |uri: String, body: serde_json::Value| {
+        test::TestRequest::post()
+            .uri(&uri)
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .set_json(body)
+            .to_request()
+    };
+    let list = |params: &str| {
+        test::TestRequest::get()
+            .uri(&format!("/api/alerts?patient_id={}&{}", patient_id, params))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .to_request()
+    };
+
+    // A sustained excursion raises one alert, pushed once within the cooldown
+    for _ in 0..3 {
+        assert_eq!(test::call_service(&app, ingest(195)).await.status(), 200);
+    }
+    let open: serde_json::Value = test::read_body_json(test::call_service(&app, list("status=open")).await).await;
+    let open = open.as_array().unwrap();
+    assert_eq!(open.len(), 1);
+    assert_eq!(open[0]["kind"], "vitals");
+    assert_eq!(open[0]["level"], "critical");
+    assert!(open[0]["details"]["reading_id"].is_i64());
+    let alert_id = open[0]["id"].as_str().unwrap().to_string();
+    let raised = std::iter::from_fn(|| rx.try_recv().ok())
+        .filter_map(|event| match &event.event {
+            medhealth_backend::models::SseEvent::Alert(alert) => Some(alert.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(raised.len(), 1);
+    assert!(raised[0].details.get("alert_id").is_some());
+
+    let resp = test::call_service(&app, post(format!("/api/alerts/{}/ack", alert_id), json!({}))).await;
+    assert_eq!(resp.status(), 200);
+    let acknowledged: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(acknowledged["status"], "acknowledged");
+    assert_eq!(test::call_service(&app, post(format!("/api/alerts/{}/ack", alert_id), json!({}))).await.status(), 409);
+
+    let resp = test::call_service(&app, post(format!("/api/alerts/{}/resolve", alert_id), json!({"resolution": "Sensor reseated"}))).await;
+    assert_eq!(resp.status(), 200);
+    let resolved: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(resolved["status"], "resolved");
+    assert_eq!(resolved["resolution"], "Sensor reseated");
+    assert!(resolved["responses"].as_array().unwrap().iter().any(|r| r["channel"] == "resolution"));
+    assert_eq!(test::call_service(&app, post(format!("/api/alerts/{}/resolve", alert_id), json!({}))).await.status(), 409);
+
+    let changes: Vec<String> = std::iter::from_fn(|| rx.try_recv().ok())
+        .filter_map(|event| match &event.event {
+            medhealth_backend::models::SseEvent::AlertStatus(change) => Some(serde_json::to_value(change.status).unwrap().as_str().unwrap().to_string()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(changes, ["acknowledged", "resolved"]);
+
+    // Once resolved, a new excursion is a new alert
+    assert_eq!(test::call_service(&app, ingest(195)).await.status(), 200);
+    let open: serde_json::Value = test::read_body_json(test::call_service(&app, list("status=open&min_level=high")).await).await;
+    assert_eq!(open.as_array().unwrap().len(), 1);
+    assert_ne!(open[0]["id"], alert_id.as_str());
+    let resolved: serde_json::Value = test::read_body_json(test::call_service(&app, list("status=resolved")).await).await;
+    assert_eq!(resolved.as_array().unwrap().len(), 1);
+
+    assert_eq!(test::call_service(&app, list("limit=0")).await.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_threshold_simulation_compares_current_and_proposed_rules() {
+    let state = init_state(&test_settings()).await.expect("PostgreSQL and Redis required for integration tests");
+    let app = test::init_service(build_app(web::Data::new(state))).await;
+    let admin = login_as!(app, "simadmin@example.com", "admin");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Simulated Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let device: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'Sim Walker', '', $2) RETURNING id"
+    )
+    .bind(format!("WALKER-SIM-{}", uuid::Uuid::new_v4()))
+    .bind(patient_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    // Ten minutes of tachycardia with low SpO2, a reading a minute
+    sqlx::query(
+        "INSERT INTO sensor_readings (device_id, heart_rate, spo2, temperature, reading_timestamp)
+         SELECT $1, 195, 85, 36.8, now() - interval '1 hour' + m * interval '1 minute' FROM generate_series(0, 9) m"
+    )
+    .bind(device)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let from = chrono::Utc::now() - chrono::Duration::hours(2);
+    let to = chrono::Utc::now();
+    let simulate = |body: serde_json::Value| {
+        test::TestRequest::post()
+            .uri("/api/admin/ml/simulate")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .set_json(body)
+            .to_request()
+    };
+    let rules = |hr_high: i32, spo2_low: i32| {
+        json!({"hr_low": 40, "hr_high": hr_high, "spo2_low": spo2_low, "fever_temperature": 38.0, "hypothermia_temperature": 35.0})
+    };
+
+    let resp = test::call_service(&app, simulate(json!({"from": from, "to": to, "patient_id": patient_id, "rules": rules(200, 80)}))).await;
+    assert_eq!(resp.status(), 200);
+    let simulation: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(simulation["devices"], 1);
+    assert_eq!(simulation["readings"], 10);
+    assert_eq!(simulation["current"]["alerting_readings"], 10);
+    assert_eq!(simulation["current"]["alerts"]["critical"], 2);
+    assert_eq!(simulation["proposed"]["total_alerts"], 0);
+
+    let alerts: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM alerts WHERE device_id = $1")
+        .bind(device)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(alerts, 0);
+
+    assert_eq!(test::call_service(&app, simulate(json!({"from": to, "to": from, "rules": rules(200, 80)}))).await.status(), 400);
+    let long = json!({"from": to - chrono::Duration::days(40), "to": to, "rules": rules(200, 80)});
+    assert_eq!(test::call_service(&app, simulate(long)).await.status(), 400);
+    assert_eq!(test::call_service(&app, simulate(json!({"from": from, "to": to, "rules": rules(300, 80)}))).await.status(), 400);
+    let unknown = json!({"from": from, "to": to, "patient_id": uuid::Uuid::new_v4(), "rules": rules(200, 80)});
+    assert_eq!(test::call_service(&app, simulate(unknown)).await.status(), 404);
+}
+
+#[actix_web::test]
+async fn test_vitals_alerts_reach_notification_channels_with_retries() {
+    const SECRET: &str = "caregiver-webhook-secret";
+
+    // Stand-in receiver keeping each post's headers and body
+    let received = std::sync::Arc::new(std::sync::Mutex::new(Vec::<(Vec<(String, String)>, String)>::new()));
+    let sink = received.clone();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let receiver_addr = listener.local_addr().unwrap();
+    let receiver = actix_web::HttpServer::new(move || {
+        let sink = sink.clone();
+        App::new().route("/alerts", web::post().to(move |req: actix_web::HttpRequest, body: String| {
+            let sink = sink.clone();
+            async move {
+                let headers = req
+                    .headers()
+                    .iter()
+                    .filter(|(name, _)| name.as_str().starts_with("x-webhook-"))
+                    .map(|(name, value)| (name.to_string(), value.to_str().unwrap().to_string()))
+                    .collect();
+                sink.lock().unwrap().push((headers, body));
+                actix_web::HttpResponse::Ok().finish()
+            }
+        }))
+    })
+    .listen(listener)
+    .unwrap()
+    .workers(1)
+    .run();
+    actix_web::rt::spawn(receiver);
+
+    let settings = test_settings();
+    let state = web::Data::new(init_state(&settings).await.expect("PostgreSQL and Redis required for integration tests"));
+    let app = test::init_service(build_app(state.clone())).await;
+    let caregiver = login_as!(app, "channels@example.com", "clinician");
+    let pool = create_pool(&settings.database).await.expect("Failed to create test database pool");
+
+    let preferences = |body: serde_json::Value| {
+        test::TestRequest::put()
+            .uri("/api/notifications/preferences")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", caregiver)))
+            .set_json(body)
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, preferences(json!({"sms_enabled": true}))).await.status(), 400);
+    assert_eq!(test::call_service(&app, preferences(json!({"webhook_url": "ftp://example.com"}))).await.status(), 400);
+    let webhook_url = format!("http://{}/alerts", receiver_addr);
+    let resp = test::call_service(&app, preferences(json!({
+        "email_enabled": true,
+        "webhook_enabled": true,
+        "webhook_url": webhook_url,
+        "webhook_secret": SECRET,
+        "min_level": "high",
+    })))
+    .await;
+    assert_eq!(resp.status(), 200);
+    let saved: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(saved["webhook_signed"], true);
+    assert!(saved.get("webhook_secret").is_none());
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Channel Patient') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO patient_caregivers (patient_id, user_id) SELECT $1, id FROM users WHERE email = 'channels@example.com'")
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let serial = format!("WALKER-CHANNELS-{}", uuid::Uuid::new_v4());
+    sqlx::query("INSERT INTO devices (device_id, device_name, secret_hash, patient_id) VALUES ($1, 'Channel Walker', '', $2)")
+        .bind(&serial)
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let timestamp = chrono::Utc::now().timestamp();
+    let body = DeviceVitalsIngest {
+        heart_rate: 195,
+        spo2: 97,
+        temperature: 36.8,
+        timestamp,
+        steps: None,
+        motion: None,
+        elevation_change: None,
+        ambient_temperature: None,
+        humidity: None,
+        metadata: None,
+    };
+    let payload = serde_json::to_string(&body).unwrap();
+    let req = test::TestRequest::post()
+        .uri("/api/device/vitals")
+        .insert_header(("X-Device-Id", serial.as_str()))
+        .insert_header(("X-Timestamp", timestamp.to_string()))
+        .insert_header(("X-Signature", device_signature(TEST_DEVICE_SECRET, timestamp, &payload)))
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_payload(payload)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+    // Queued in the background; email is skipped because SMTP is not configured
+    let mut queued = Vec::new();
+    for _ in 0..50 {
+        queued = sqlx::query_as::<_, (uuid::Uuid, String)>(
+            "SELECT d.id, d.channel FROM notification_deliveries d JOIN users u ON u.id = d.user_id
+             WHERE u.email = 'channels@example.com' AND d.patient_id = $1"
+        )
+        .bind(patient_id)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        if !queued.is_empty() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].1, "webhook");
+    let delivery_id = queued[0].0;
+
+    let dispatcher = medhealth_backend::notifications::Dispatcher::new(pool.clone(), settings.notifications.clone()).unwrap();
+    assert!(dispatcher.run_cycle().await.unwrap() >= 1);
+    let (headers, body) = received.lock().unwrap()[0].clone();
+    let header = |name: &str| headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.clone()).unwrap();
+    assert_eq!(header("X-Webhook-Id"), delivery_id.to_string());
+    let signed_at: i64 = header("X-Webhook-Timestamp").parse().unwrap();
+    assert_eq!(header("X-Webhook-Signature"), medhealth_backend::webhooks::signature(SECRET, signed_at, 0, &body));
+    let posted: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(posted["level"], "critical");
+    assert_eq!(posted["patient_id"], patient_id.to_string());
+
+    // A receiver that is down leaves the delivery pending with a later retry
+    let resp = test::call_service(&app, preferences(json!({"webhook_url": "http://127.0.0.1:9/alerts"}))).await;
+    assert_eq!(resp.status(), 200);
+    let failing: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO notification_deliveries (user_id, patient_id, channel, target, level, subject, body)
+         SELECT id, $1, 'webhook', 'http://127.0.0.1:9/alerts', 'critical', 'Walker alert', 'Test' FROM users
+         WHERE email = 'channels@example.com'
+         RETURNING id"
+    )
+    .bind(patient_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    dispatcher.run_cycle().await.unwrap();
+    let (status, attempts, retry_later): (String, i32, bool) = sqlx::query_as(
+        "SELECT status, attempts, next_attempt_at > now() FROM notification_deliveries WHERE id = $1"
+    )
+    .bind(failing)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!((status.as_str(), attempts, retry_later), ("pending", 1, true));
+
+    let resp = test::call_service(&app, test::TestRequest::get()
+        .uri("/api/notifications/deliveries")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", caregiver)))
+        .to_request()).await;
+    let deliveries: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert!(deliveries.iter().any(|d| d["id"] == delivery_id.to_string() && d["status"] == "delivered"));
+    assert!(deliveries.iter().any(|d| d["id"] == failing.to_string() && d["last_error"].is_string()));
+}
+
+#[actix_web::test]
+async fn test_archived_patients_are_read_only_and_transfers_need_both_sides() {
+    let state = init_state(&test_settings()).await.expect("PostgreSQL and Redis required for integration tests");
+    let app = test::init_service(build_app(web::Data::new(state))).await;
+    let stale = login_as!(app, "transfer-source@example.com", "clinician");
+    login_as!(app, "transfer-target@example.com", "clinician");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let mut orgs = Vec::new();
+    for name in ["Source Clinic", "Target Clinic"] {
+        let id: uuid::Uuid = sqlx::query_scalar("INSERT INTO organizations (name) VALUES ($1) RETURNING id")
+            .bind(format!("{} {}", name, uuid::Uuid::new_v4()))
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        orgs.push(id);
+    }
+    for (email, org) in [("transfer-source@example.com", orgs[0]), ("transfer-target@example.com", orgs[1])] {
+        sqlx::query("UPDATE users SET organization_id = $2 WHERE email = $1")
+            .bind(email)
+            .bind(org)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+    // Tokens carry the organization, so moving a user signs them out
+    let req = test::TestRequest::get()
+        .uri("/auth/preferences")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", stale)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 401);
+    let source = login_as!(app, "transfer-source@example.com", "clinician");
+    let target = login_as!(app, "transfer-target@example.com", "clinician");
+    let patient_id: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO patients (display_name, organization_id) VALUES ('Moving Patient', $1) RETURNING id"
+    )
+    .bind(orgs[0])
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO patient_caregivers (patient_id, user_id) SELECT $1, id FROM users WHERE email = 'transfer-source@example.com'"
+    )
+    .bind(patient_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query("INSERT INTO alerts (patient_id, kind, level, message) VALUES ($1, 'sos', 'critical', 'SOS button pressed')")
+        .bind(patient_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    // Asked for by the source, confirmed by the target
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/patients/{}/transfers", patient_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", source)))
+        .set_json(json!({"to_organization_id": orgs[1], "reason": "Moving closer to family"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let transfer: serde_json::Value = test::read_body_json(resp).await;
+    let transfer_id = transfer["id"].as_str().unwrap().to_string();
+    assert_eq!(transfer["status"], "pending");
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/transfers/{}/confirm", transfer_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", source)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 409, "one side cannot confirm twice");
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/transfers/{}/confirm", transfer_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", target)))
+        .set_json(json!({"note": "Bed ready"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let transfer: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(transfer["status"], "completed");
+    assert_eq!(transfer["moved"]["alerts"], 1);
+    let actions: Vec<&str> = transfer["events"].as_array().unwrap().iter().map(|e| e["action"].as_str().unwrap()).collect();
+    assert_eq!(actions, ["requested", "confirmed", "completed"]);
+    let moved_to: Option<uuid::Uuid> = sqlx::query_scalar("SELECT organization_id FROM patients WHERE id = $1")
+        .bind(patient_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(moved_to, Some(orgs[1]));
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/patients/{}/attributes", patient_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", source)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 403, "the old organization's link is dropped");
+    assert!(sqlx::query("DELETE FROM patient_transfer_events").execute(&pool).await.is_err());
+
+    // Archived: readable but read-only, and off the alert list
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/patients/{}/archive", patient_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", target)))
+        .set_json(json!({"reason": "Discharged home"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let status: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(status["archive_reason"], "Discharged home");
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/patients/{}/checkins", patient_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", target)))
+        .set_json(json!({"pain_score": 2}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 409);
+
+    let req = test::TestRequest::get()
+        .uri("/api/alerts?limit=500")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", target)))
+        .to_request();
+    let alerts: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert!(alerts.as_array().unwrap().iter().all(|a| a["patient_id"] != json!(patient_id.to_string())));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/patients/{}/transfers", patient_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", target)))
+        .to_request();
+    let transfers: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(transfers.as_array().unwrap().len(), 1);
+}
+
+#[actix_web::test]
+async fn test_patient_crud_and_device_assignment_set_fhir_subject() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "patientadmin@example.com", "admin");
+    let clinician = login_as!(app, "patientnurse@example.com", "clinician");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let req = test::TestRequest::post()
+        .uri("/api/patients")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", clinician)))
+        .set_json(json!({"display_name": "  Ward Patient ", "date_of_birth": "1941-05-02"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let patient: serde_json::Value = test::read_body_json(resp).await;
+    let patient_id = patient["id"].as_str().unwrap().to_string();
+    assert_eq!(patient["display_name"], "Ward Patient");
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/patients/{}", patient_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", clinician)))
+        .set_json(json!({"display_name": "Ward Patient B"}))
+        .to_request();
+    let patient: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(patient["display_name"], "Ward Patient B");
+    assert_eq!(patient["date_of_birth"], "1941-05-02");
+
+    let req = test::TestRequest::get()
+        .uri("/api/patients")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", clinician)))
+        .to_request();
+    let patients: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert!(patients.as_array().unwrap().iter().any(|p| p["id"] == patient_id.as_str()));
+
+    let serial = format!("WALKER-ASSIGN-{}", uuid::Uuid::new_v4());
+    sqlx::query("INSERT INTO devices (device_id, device_name, secret_hash) VALUES ($1, 'Assigned Walker', '')")
+        .bind(&serial)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/admin/devices/{}/patient", serial))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", clinician)))
+        .set_json(json!({"patient_id": patient_id}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 403);
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/admin/devices/{}/patient", serial))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .set_json(json!({"patient_id": patient_id}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let device: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(device["patient_id"], patient_id.as_str());
+
+    let timestamp = chrono::Utc::now().timestamp();
+    let body = DeviceVitalsIngest {
+        heart_rate: 72,
+        spo2: 98,
+        temperature: 36.8,
+        timestamp,
+        steps: None,
+        motion: None,
+        elevation_change: None,
+        ambient_temperature: None,
+        humidity: None,
+        metadata: None,
+    };
+    let payload = serde_json::to_string(&body).unwrap();
+    let req = test::TestRequest::post()
+        .uri("/api/device/vitals")
+        .insert_header(("X-Device-Id", serial.as_str()))
+        .insert_header(("X-Timestamp", timestamp.to_string()))
+        .insert_header(("X-Signature", device_signature(TEST_DEVICE_SECRET, timestamp, &payload)))
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_payload(payload)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+    let mut subject: Option<String> = None;
+    for _ in 0..50 {
+        subject = sqlx::query_scalar(
+            "SELECT o.subject_reference FROM fhir_observations o
+             JOIN sensor_readings r ON r.id = o.sensor_reading_id
+             JOIN devices d ON d.id = r.device_id
+             WHERE d.device_id = $1"
+        )
+        .bind(&serial)
+        .fetch_optional(&pool)
+        .await
+        .unwrap()
+        .flatten();
+        if subject.is_some() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(subject, Some(format!("Patient/{}", patient_id)));
+
+    // A patient with a walker is not deleted outright
+    let delete = || test::TestRequest::delete()
+        .uri(&format!("/api/patients/{}", patient_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .to_request();
+    assert_eq!(test::call_service(&app, delete()).await.status(), 409);
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/admin/devices/{}/patient", serial))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+    assert_eq!(test::call_service(&app, delete()).await.status(), 204);
+}
+
+#[actix_web::test]
+async fn test_reporting_credentials_see_only_their_organization() {
+    use sqlx::postgres::PgConnectOptions;
+    use sqlx::{ConnectOptions, Connection};
+    use std::str::FromStr;
+
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "reportingadmin@example.com", "admin");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let mut patients = Vec::new();
+    for name in ["BI Clinic", "Other Clinic"] {
+        let org: uuid::Uuid = sqlx::query_scalar("INSERT INTO organizations (name) VALUES ($1) RETURNING id")
+            .bind(format!("{} {}", name, uuid::Uuid::new_v4()))
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let patient: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO patients (display_name, organization_id) VALUES ('BI Patient', $1) RETURNING id"
+        )
+        .bind(org)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let ward: uuid::Uuid = sqlx::query_scalar("INSERT INTO wards (name, organization_id) VALUES ('BI Ward', $1) RETURNING id")
+            .bind(org)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        patients.push((org, patient, ward));
+    }
+
+    let req = test::TestRequest::post()
+        .uri("/api/admin/reporting-credentials")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .set_json(json!({"label": "Quality dashboard", "organization_id": patients[0].0, "days": 7}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let issued: serde_json::Value = test::read_body_json(resp).await;
+    let login = PgConnectOptions::from_str(&test_settings().database.url)
+        .unwrap()
+        .username(issued["role_name"].as_str().unwrap())
+        .password(issued["password"].as_str().unwrap());
+
+    let mut conn = login.connect().await.expect("BI login should connect");
+    let visible: Vec<uuid::Uuid> = sqlx::query_scalar("SELECT id FROM patients").fetch_all(&mut conn).await.unwrap();
+    assert!(visible.contains(&patients[0].1));
+    assert!(!visible.contains(&patients[1].1));
+    let wards: Vec<uuid::Uuid> = sqlx::query_scalar("SELECT id FROM wards").fetch_all(&mut conn).await.unwrap();
+    assert!(wards.contains(&patients[0].2));
+    assert!(!wards.contains(&patients[1].2));
+    assert!(sqlx::query("SELECT 1 FROM public.patients").execute(&mut conn).await.is_err());
+    assert!(sqlx::query("SELECT password_hash FROM public.users").execute(&mut conn).await.is_err());
+
+    // Revoking empties open sessions at once and drops the login
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/admin/reporting-credentials/{}", issued["id"].as_str().unwrap()))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 204);
+    let visible: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM patients").fetch_one(&mut conn).await.unwrap();
+    assert_eq!(visible, 0);
+    conn.close().await.unwrap();
+    assert!(login.connect().await.is_err());
+
+    // The constrained pool reads the reporting views but cannot write
+    let mut settings = test_settings();
+    settings.reporting.mode = medhealth_backend::config::ReportingMode::Constrained;
+    let reporting = medhealth_backend::database::create_reporting_pool(&pool, &settings.database, &settings.reporting)
+        .await
+        .unwrap();
+    sqlx::query("SELECT COUNT(*) FROM alert_daily_summary").execute(&reporting).await.unwrap();
+    assert!(sqlx::query("DELETE FROM reporting_view_refreshes").execute(&reporting).await.is_err());
+    assert!(sqlx::query("SELECT password_hash FROM users").execute(&reporting).await.is_err());
+}
+
+#[actix_web::test]
+async fn test_analytics_query_aggregates_whitelisted_fields_only() {
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "analyticsadmin@example.com", "admin");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+
+    let patient: uuid::Uuid =
+        sqlx::query_scalar("INSERT INTO patients (display_name) VALUES ('Analytics Patient') RETURNING id")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    for (level, ack_minutes) in [("high", 4.0), ("high", 8.0), ("low", 30.0)] {
+        sqlx::query(
+            "INSERT INTO alerts (patient_id, kind, level, message, raised_at, acknowledged_at)
+             VALUES ($1, 'fall', $2::alert_level, 'Fall detected', now() - interval '1 hour',
+                     now() - interval '1 hour' + $3 * interval '1 minute')"
+        )
+        .bind(patient)
+        .bind(level)
+        .bind(ack_minutes)
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+
+    let query = |body: serde_json::Value| {
+        test::TestRequest::post()
+            .uri("/api/analytics/query")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .set_json(body)
+            .to_request()
+    };
+    let resp = test::call_service(&app, query(json!({
+        "source": "alerts",
+        "metrics": [{"fn": "count"}, {"fn": "avg", "field": "ack_minutes"}],
+        "group_by": ["level"],
+        "filters": [{"field": "patient_id", "op": "eq", "value": patient}],
+    })))
+    .await;
+    assert_eq!(resp.status(), 200);
+    let result: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(result["columns"], json!(["level", "count", "avg_ack_minutes"]));
+    assert_eq!(result["truncated"], false);
+    let high = result["rows"].as_array().unwrap().iter().find(|r| r["level"] == "high").unwrap();
+    assert_eq!(high["count"], 2);
+    assert!((high["avg_ack_minutes"].as_f64().unwrap() - 6.0).abs() < 0.01);
+
+    // Columns outside the catalog and grouping by a numeric field are refused
+    let resp = test::call_service(&app, query(json!({
+        "source": "alerts", "metrics": [{"fn": "count"}], "group_by": ["message"],
+    })))
+    .await;
+    assert_eq!(resp.status(), 400);
+    let resp = test::call_service(&app, query(json!({
+        "source": "readings", "metrics": [{"fn": "max", "field": "metadata"}],
+    })))
+    .await;
+    assert_eq!(resp.status(), 400);
+    let resp = test::call_service(&app, query(json!({
+        "source": "alerts", "metrics": [{"fn": "count"}], "group_by": ["ack_minutes"],
+    })))
+    .await;
+    assert_eq!(resp.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_metrics_endpoint_counts_requests_behind_basic_auth() {
+    let mut settings = test_settings();
+    settings.observability.metrics_username = Some("prometheus".into());
+    settings.observability.metrics_password = Some("scrape-secret".into());
+    let state = init_state(&settings).await.expect("PostgreSQL and Redis required for integration tests");
+    let app = test::init_service(build_app(web::Data::new(state))).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/login")
+        .set_json(json!({"email": "nobody@example.com", "password": "WrongPass123!"}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 401);
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/metrics").to_request()).await;
+    assert_eq!(resp.status(), 401);
+    assert!(resp.headers().contains_key(header::WWW_AUTHENTICATE));
+
+    let credentials = general_purpose::STANDARD.encode("prometheus:scrape-secret");
+    let req = test::TestRequest::get()
+        .uri("/metrics")
+        .insert_header((header::AUTHORIZATION, format!("Basic {}", credentials)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(body.contains(r#"http_requests_total{endpoint="/auth/login",method="POST",status="401"}"#));
+    assert!(body.contains(r#"auth_attempts_total{result="failure"}"#));
+    assert!(body.contains("db_connections_active"));
+}
+
+#[actix_web::test]
+async fn test_integration_configured_and_tested() {
+    // Stand-in webhook receiver keeping the headers of each call
+    let received = std::sync::Arc::new(std::sync::Mutex::new(Vec::<(Option<String>, Option<String>)>::new()));
+    let sink = received.clone();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let receiver_addr = listener.local_addr().unwrap();
+    let receiver = actix_web::HttpServer::new(move || {
+        let sink = sink.clone();
+        App::new().route("/hook", web::post().to(move |req: actix_web::HttpRequest| {
+            let sink = sink.clone();
+            async move {
+                let header = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+                sink.lock().unwrap().push((header("X-Webhook-Sequence"), header("X-Webhook-Signature")));
+                actix_web::HttpResponse::Ok().finish()
+            }
+        }))
+    })
+    .listen(listener)
+    .unwrap()
+    .workers(1)
+    .run();
+    actix_web::rt::spawn(receiver);
+
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "integrationadmin@example.com", "admin");
+    let resp = test::call_service(&app,
+        test::TestRequest::post()
+            .uri("/api/admin/organizations")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .set_json(json!({"name": format!("Integration Org {}", uuid::Uuid::new_v4())}))
+            .to_request()
+    ).await;
+    let org: serde_json::Value = test::read_body_json(resp).await;
+
+    // Organization-wide admins must say whose integration it is
+    let webhook = json!({
+        "kind": "webhook", "name": "Alerts", "url": format!("http://{}/hook", receiver_addr),
+        "secret": "integration-signing-secret-of-32-bytes", "settings": {"events": ["usage_daily"]}
+    });
+    let resp = test::call_service(&app,
+        test::TestRequest::post()
+            .uri("/api/admin/integrations")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .set_json(&webhook)
+            .to_request()
+    ).await;
+    assert_eq!(resp.status(), 400);
+
+    let mut request = webhook.clone();
+    request["organization_id"] = org["id"].clone();
+    let resp = test::call_service(&app,
+        test::TestRequest::post()
+            .uri("/api/admin/integrations")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .set_json(&request)
+            .to_request()
+    ).await;
+    assert_eq!(resp.status(), 201);
+    let integration: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(integration["has_secret"], true);
+    assert!(integration.get("secret").is_none());
+
+    let resp = test::call_service(&app,
+        test::TestRequest::post()
+            .uri(&format!("/api/admin/integrations/{}/test", integration["id"].as_str().unwrap()))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .to_request()
+    ).await;
+    assert_eq!(resp.status(), 200);
+    let result: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(result["ok"], true, "{}", result);
+    assert_eq!(result["status"], 200);
+    {
+        let calls = received.lock().unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].0.as_deref(), Some("0"));
+        assert!(calls[0].1.is_some());
+    }
+
+    // The organization's own admins cannot point tests at this host or a private network
+    login_as!(app, "integration-orgadmin@example.com", "admin");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+    sqlx::query("UPDATE users SET organization_id = $2 WHERE email = $1")
+        .bind("integration-orgadmin@example.com")
+        .bind(uuid::Uuid::parse_str(org["id"].as_str().unwrap()).unwrap())
+        .execute(&pool)
+        .await
+        .unwrap();
+    let org_admin = login_as!(app, "integration-orgadmin@example.com", "admin");
+    let resp = test::call_service(&app,
+        test::TestRequest::post()
+            .uri(&format!("/api/admin/integrations/{}/test", integration["id"].as_str().unwrap()))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", org_admin)))
+            .to_request()
+    ).await;
+    assert_eq!(resp.status(), 200);
+    let result: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(result["ok"], false);
+    assert!(result["error"].as_str().unwrap().contains("private network"), "{}", result);
+    assert_eq!(received.lock().unwrap().len(), 1);
+
+    // A FHIR server nobody listens on fails its test, and the failure is kept
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let resp = test::call_service(&app,
+        test::TestRequest::post()
+            .uri("/api/admin/integrations")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .set_json(json!({
+                "kind": "fhir", "name": "HIE", "url": format!("http://{}/fhir", closed),
+                "settings": {"timeout_seconds": 2}, "organization_id": org["id"]
+            }))
+            .to_request()
+    ).await;
+    assert_eq!(resp.status(), 201);
+    let fhir: serde_json::Value = test::read_body_json(resp).await;
+    let fhir_id = fhir["id"].as_str().unwrap();
+    let resp = test::call_service(&app,
+        test::TestRequest::post()
+            .uri(&format!("/api/admin/integrations/{}/test", fhir_id))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .to_request()
+    ).await;
+    let result: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(result["ok"], false);
+    assert!(result["error"].is_string());
+
+    let resp = test::call_service(&app,
+        test::TestRequest::get()
+            .uri(&format!("/api/admin/integrations/{}", fhir_id))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .to_request()
+    ).await;
+    let fhir: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(fhir["last_test_ok"], false);
+}
+
+#[actix_web::test]
+async fn test_clinical_readings_pushed_to_hl7_integration() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Stand-in HL7 receiver acknowledging one message
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let receiver_addr = listener.local_addr().unwrap();
+    let receiver = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut received = Vec::new();
+        let mut buffer = [0u8; 4096];
+        while !received.ends_with(b"\x1c\r") {
+            let read = stream.read(&mut buffer).await.unwrap();
+            assert!(read > 0, "sender closed before the end of the message");
+            received.extend_from_slice(&buffer[..read]);
+        }
+        stream.write_all(b"\x0bMSH|^~\\&|EHR|WARD\rMSA|AA|MH1\x1c\r").await.unwrap();
+        String::from_utf8(received).unwrap()
+    });
+
+    let app = test::init_service(build_test_app!()).await;
+    let admin = login_as!(app, "ehrpushadmin@example.com", "admin");
+    let pool = create_pool(&test_settings().database).await.expect("Failed to create test database pool");
+    let org: uuid::Uuid = sqlx::query_scalar("INSERT INTO organizations (name) VALUES ($1) RETURNING id")
+        .bind(format!("EHR Push Clinic {}", uuid::Uuid::new_v4()))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+    // Set up by an administrator outside any organization, so it may be on this host
+    let resp = test::call_service(&app,
+        test::TestRequest::post()
+            .uri("/api/admin/integrations")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .set_json(json!({
+                "kind": "hl7", "name": "Ward EHR", "url": format!("mllp://{}", receiver_addr),
+                "settings": {"timeout_seconds": 5}, "organization_id": org
+            }))
+            .to_request()
+    ).await;
+    assert_eq!(resp.status(), 201);
+
+    let patient_id: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO patients (display_name, organization_id) VALUES ('Pushed Patient', $1) RETURNING id"
+    )
+    .bind(org)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    let serial = format!("WALKER-EHR-{}", uuid::Uuid::new_v4());
+    let resp = test::call_service(&app,
+        test::TestRequest::post()
+            .uri("/api/admin/devices")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", admin)))
+            .set_json(json!({"device_id": serial, "device_name": "EHR Walker"}))
+            .to_request()
+    ).await;
+    assert_eq!(resp.status(), 201);
+    let device: serde_json::Value = test::read_body_json(resp).await;
+    let secret = device["secret"].as_str().unwrap().to_string();
+    sqlx::query("UPDATE devices SET patient_id = $2, organization_id = $3 WHERE device_id = $1")
+        .bind(&serial)
+        .bind(patient_id)
+        .bind(org)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let timestamp = chrono::Utc::now().timestamp();
+    let payload = json!({"heartRate": 72, "spo2": 97, "temperature": 36.8, "timestamp": timestamp}).to_string();
+    let req = test::TestRequest::post()
+        .uri("/api/device/vitals")
+        .insert_header(("X-Device-Id", serial.as_str()))
+        .insert_header(("X-Timestamp", timestamp.to_string()))
+        .insert_header(("X-Signature", device_signature(&secret, timestamp, &payload)))
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_payload(payload)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+    let message = tokio::time::timeout(std::time::Duration::from_secs(10), receiver)
+        .await
+        .expect("reading pushed in time")
+        .unwrap();
+    let segments: Vec<&str> = message.trim_start_matches('\u{b}').trim_end_matches("\u{1c}\r").split('\r').collect();
+    assert!(segments[0].contains("|ORU^R01^ORU_R01|"), "{}", segments[0]);
+    assert!(segments[1].starts_with(&format!("PID|1||{}^^^MEDHEALTH||Pushed Patient", patient_id)));
+    assert!(segments.iter().any(|s| s.starts_with("OBX|1|NM|8867-4^Heart rate^LN||72|")));
+    assert!(segments.iter().any(|s| s.starts_with("OBX|3|NM|8310-5^Body temperature^LN||36.80|")));
+}
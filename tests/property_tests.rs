@@ -1,35 +1,128 @@
-// Property-based testing examples using proptest
+// Property-based tests run against the real auth, validation, ML and FHIR code
 
 #[cfg(test)]
 mod tests {
+    use chrono::Utc;
+    use medhealth_backend::auth::{device_signature, verify_device_signature, JwtAuth};
+    use medhealth_backend::config::{FhirConfig, JwtConfig, MlConfig};
+    use medhealth_backend::fhir_service::FhirService;
+    use medhealth_backend::ml_service::MlService;
+    use medhealth_backend::rbac::Role;
+    use medhealth_backend::models::{DeviceVitalsIngest, EventEnvelope, Patient, SensorReading, WebhookEvent, MAX_READING_METADATA_KEYS};
+    use medhealth_backend::webhooks;
     use proptest::prelude::*;
+    use uuid::Uuid;
+    use validator::Validate;
 
-    // Example property tests for ML service
-    proptest! {
-        #[test]
-        fn test_heart_rate_validation(hr in 0i32..300) {
-            // Property: Heart rate should always be validated correctly
-            assert!(hr >= 0 && hr <= 300);
+    const DEVICE_SECRET: &str = "property_test_device_secret";
+    const WEBHOOK_SECRET: &str = "property_test_webhook_secret_of_32_bytes";
+
+    fn jwt_auth(secret: &str) -> JwtAuth {
+        JwtAuth::new(&JwtConfig {
+            secret: secret.to_string(),
+            expiration_hours: 24,
+            refresh_token_days: 7,
+        })
+    }
+
+    fn ml_service() -> MlService {
+        MlService::new(MlConfig {
+            anomaly_threshold: 0.85,
+            enable_alerts: true,
+            critical_hr_low: 40,
+            critical_hr_high: 180,
+            critical_spo2_low: 88,
+            alert_cooldown_seconds: 300,
+            model: None,
+        })
+    }
+
+    fn reading(heart_rate: Option<i32>, spo2: Option<i32>, temperature: Option<f32>) -> SensorReading {
+        SensorReading {
+            id: 1,
+            device_id: Uuid::nil(),
+            heart_rate,
+            spo2,
+            temperature,
+            reading_timestamp: Utc::now(),
+            received_at: Utc::now(),
+            quality_score: None,
+            metadata: serde_json::json!({}),
         }
+    }
+
+    fn vitals_body(hr: i32, spo2: i32, timestamp: i64) -> String {
+        serde_json::to_string(&DeviceVitalsIngest {
+            heart_rate: hr,
+            spo2,
+            temperature: 36.8,
+            timestamp,
+            steps: None,
+            motion: None,
+            elevation_change: None,
+            ambient_temperature: None,
+            humidity: None,
+            metadata: None,
+        })
+        .unwrap()
+    }
 
+    // Ingestion validation accepts exactly the documented ranges
+    proptest! {
         #[test]
-        fn test_spo2_validation(spo2 in 0i32..=100) {
-            // Property: SpO2 should always be between 0-100
-            assert!(spo2 >= 0 && spo2 <= 100);
+        fn test_vitals_validation_matches_ranges(hr in -50i32..400, spo2 in -20i32..150, temp in 15.0f32..55.0) {
+            let body = DeviceVitalsIngest {
+                heart_rate: hr, spo2, temperature: temp, timestamp: 0, steps: None, motion: None, elevation_change: None,
+                ambient_temperature: None, humidity: None, metadata: None,
+            };
+            let in_range = (0..=300).contains(&hr) && (0..=100).contains(&spo2) && (25.0..=45.0).contains(&temp);
+            prop_assert_eq!(body.validate().is_ok(), in_range);
         }
 
         #[test]
-        fn test_temperature_validation(temp in 25.0f32..45.0) {
-            // Property: Temperature should be in valid range
-            assert!(temp >= 25.0 && temp <= 45.0);
+        fn test_reading_metadata_limits(keys in 0usize..24, value_len in 0usize..80, key in "[a-zA-Z0-9_-]{1,40}") {
+            let metadata = |entries: Vec<(String, serde_json::Value)>| {
+                let mut body: DeviceVitalsIngest = serde_json::from_str(&vitals_body(70, 97, 0)).unwrap();
+                body.metadata = Some(entries.into_iter().collect());
+                body.validate().is_ok()
+            };
+
+            let many = (0..keys).map(|i| (format!("key_{}", i), serde_json::json!(i))).collect();
+            prop_assert_eq!(metadata(many), keys <= MAX_READING_METADATA_KEYS);
+
+            let long = vec![("firmware_version".to_string(), serde_json::json!("x".repeat(value_len)))];
+            prop_assert_eq!(metadata(long), value_len <= 64);
+
+            let key_ok = key.len() <= 32
+                && key.starts_with(|c: char| c.is_ascii_lowercase())
+                && key.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+            prop_assert_eq!(metadata(vec![(key, serde_json::json!(1))]), key_ok);
+
+            let nested = vec![("nested".to_string(), serde_json::json!({"a": 1}))];
+            prop_assert!(!metadata(nested));
         }
     }
 
     proptest! {
         #[test]
-        fn test_jwt_token_roundtrip(email in "[a-z]{5,10}@[a-z]{3,7}\\.com") {
-            // Property: Any valid email should produce a valid JWT that can be decoded
-            // TODO: Implement with actual JWT generation
+        fn test_jwt_token_roundtrip(
+            email in "[a-z]{5,10}@[a-z]{3,7}\\.com",
+            role in prop_oneof![Just(Role::Admin), Just(Role::Clinician), Just(Role::Viewer)],
+            id in any::<u128>(),
+        ) {
+            let auth = jwt_auth("property_test_jwt_secret_at_least_32_bytes");
+            let user_id = Uuid::from_u128(id);
+
+            let token = auth.generate_token(user_id, &email, role, None).unwrap();
+            let claims = auth.validate_token(&token).unwrap();
+            prop_assert_eq!(claims.sub, email.clone());
+            prop_assert_eq!(claims.role, role);
+            prop_assert_eq!(claims.user_id, user_id);
+            prop_assert!(claims.exp > claims.iat);
+
+            // A token from another key is never accepted
+            let foreign = jwt_auth("another_property_test_secret_of_32_bytes").generate_token(user_id, &email, role, None).unwrap();
+            prop_assert!(auth.validate_token(&foreign).is_err());
         }
     }
 
@@ -40,8 +133,112 @@ mod tests {
             hr in 50i32..150,
             spo2 in 90i32..100
         ) {
-            // Property: Same input should always produce same HMAC signature
-            // TODO: Implement
+            let body = vitals_body(hr, spo2, timestamp);
+            let signature = device_signature(DEVICE_SECRET, timestamp, &body);
+            prop_assert_eq!(&signature, &device_signature(DEVICE_SECRET, timestamp, &body));
+            prop_assert!(verify_device_signature(DEVICE_SECRET, timestamp, &body, &signature));
+        }
+
+        #[test]
+        fn test_client_signatures_verify(
+            timestamp in 1000000000i64..2000000000i64,
+            hr in 50i32..150,
+            spo2 in 90i32..100
+        ) {
+            let body = vitals_body(hr, spo2, timestamp);
+            let signature = medhealth_client::signing::device_signature(DEVICE_SECRET, timestamp, &body);
+            prop_assert!(verify_device_signature(DEVICE_SECRET, timestamp, &body, &signature));
+        }
+
+        #[test]
+        fn test_hmac_detects_tampering(
+            timestamp in 1000000000i64..2000000000i64,
+            hr in 50i32..150,
+            spo2 in 90i32..100,
+            delta in 1i32..50,
+        ) {
+            let body = vitals_body(hr, spo2, timestamp);
+            let signature = device_signature(DEVICE_SECRET, timestamp, &body);
+
+            prop_assert!(!verify_device_signature(DEVICE_SECRET, timestamp, &vitals_body(hr + delta, spo2, timestamp), &signature));
+            prop_assert!(!verify_device_signature(DEVICE_SECRET, timestamp + 1, &body, &signature));
+            prop_assert!(!verify_device_signature("wrong_secret", timestamp, &body, &signature));
+        }
+    }
+
+    // Webhook deliveries signed by the server verify with the client's receiver helper
+    proptest! {
+        #[test]
+        fn test_client_verifies_webhook_signatures(
+            timestamp in 1000000000i64..2000000000i64,
+            sequence in 1i64..i64::MAX,
+            message in "[ -~]{0,160}",
+        ) {
+            let envelope = EventEnvelope::new(WebhookEvent::ContactRequested {
+                channel: "sms".into(),
+                to: "+15550100".into(),
+                message,
+                alert_id: Uuid::new_v4(),
+            });
+            let body = serde_json::to_string(&envelope).unwrap();
+            let headers = [
+                (webhooks::ID_HEADER, envelope.id.to_string()),
+                (webhooks::SEQUENCE_HEADER, sequence.to_string()),
+                (webhooks::TIMESTAMP_HEADER, timestamp.to_string()),
+                (webhooks::SIGNATURE_HEADER, webhooks::signature(WEBHOOK_SECRET, timestamp, sequence, &body)),
+            ];
+            let header = |name: &str| headers.iter().find(|(n, _)| *n == name).map(|(_, v)| v.as_str());
+
+            let delivery = medhealth_client::webhooks::verify(WEBHOOK_SECRET, header, &body, timestamp, webhooks::SIGNATURE_TOLERANCE_SECONDS).unwrap();
+            prop_assert_eq!(delivery.event.id, envelope.id);
+            prop_assert_eq!(delivery.sequence, sequence as u64);
+            prop_assert_eq!(delivery.event.event_type, "contact_requested");
+            prop_assert!(medhealth_client::webhooks::verify("wrong_secret", header, &body, timestamp, 300).is_err());
+        }
+    }
+
+    // The anomaly score never falls as heart rate moves further from normal
+    proptest! {
+        #[test]
+        fn test_ml_score_monotonic_above_normal(a in 70i32..=300, b in 70i32..=300) {
+            let (low, high) = (a.min(b), a.max(b));
+            let ml = ml_service();
+            let score = |hr| ml.analyze_reading(&reading(Some(hr), Some(97), Some(36.8))).anomaly_score;
+            prop_assert!(score(low) <= score(high));
+        }
+
+        #[test]
+        fn test_ml_score_monotonic_below_normal(a in 1i32..=70, b in 1i32..=70) {
+            let (low, high) = (a.min(b), a.max(b));
+            let ml = ml_service();
+            let score = |hr| ml.analyze_reading(&reading(Some(hr), Some(97), Some(36.8))).anomaly_score;
+            prop_assert!(score(high) <= score(low));
+        }
+    }
+
+    // One valid Observation per vital present on the reading
+    proptest! {
+        #[test]
+        fn test_fhir_bundle_entry_counts(
+            hr in proptest::option::of(30i32..220),
+            spo2 in proptest::option::of(70i32..=100),
+            temp in proptest::option::of(34.0f32..41.0),
+            patient in proptest::option::of("[A-Za-z ]{1,20}"),
+        ) {
+            let fhir = FhirService::new(FhirConfig {
+                base_url: "http://localhost:8080/fhir".to_string(),
+                organization_id: "org-prop".to_string(),
+            });
+            let expected = [hr.is_some(), spo2.is_some(), temp.is_some()].iter().filter(|p| **p).count();
+
+            let patient = patient.map(|display_name| Patient { id: Uuid::new_v4(), display_name, date_of_birth: None });
+            let bundle = fhir.create_observation_bundle(&reading(hr, spo2, temp), None, patient.as_ref());
+            let entries = bundle["entry"].as_array().unwrap();
+            prop_assert_eq!(entries.len(), expected + usize::from(patient.is_some()));
+            for entry in entries.iter().filter(|e| e["resource"]["resourceType"] != "Patient") {
+                prop_assert_eq!(&entry["resource"]["resourceType"], "Observation");
+                prop_assert!(fhir.validate_observation(&entry["resource"]));
+            }
         }
     }
 }